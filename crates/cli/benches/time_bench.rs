@@ -1,7 +1,7 @@
 use std::time::Duration;
 
 use cli_utils::bench_utils::{
-    bench_cfold, bench_deriv, bench_nqueens, bench_quicksort, bench_rbtree_ck,
+    bench_cfold, bench_deriv, bench_nqueens, bench_quicksort, bench_rbtree_ck, bench_str_from_int,
 };
 use criterion::{measurement::WallTime, BenchmarkGroup, Criterion, SamplingMode};
 
@@ -30,7 +30,8 @@ fn bench_group_wall_time(c: &mut Criterion) {
         bench_deriv,     // nest deriv 8 f
         bench_rbtree_ck, // ms = makeMap 5 80000
         // bench_rbtree_delete, // m = makeMap 100000
-        bench_quicksort, // list size 10000
+        bench_quicksort,    // list size 10000
+        bench_str_from_int, // 0..100 to Str, 100000 times
     ];
 
     for bench_func in bench_funcs.iter() {
@@ -328,6 +328,15 @@ fn build_loaded_file<'a>(
         );
     }
 
+    if let Some(stats) = &code_gen_timing.stats {
+        println!("{}", stats.to_json());
+    } else if code_gen_options.emit_stats {
+        // the dev backend
+        if let Some(size_report) = &code_gen_timing.size_report {
+            println!("{}", size_report.table());
+        }
+    }
+
     if let Some(HostRebuildTiming::ConcurrentWithApp(thread)) = opt_rebuild_timing {
         let rebuild_duration = thread.join().expect("Failed to (re)build platform.");
 
@@ -586,6 +595,10 @@ pub fn build_str_test<'a>(
         backend: CodeGenBackend::Llvm,
         opt_level: OptLevel::Normal,
         emit_debug_info: false,
+        emit_stats: false,
+        precise_float_reduction: false,
+        strict_fp_exceptions: false,
+        llvm_fallback: false,
     };
 
     let emit_timings = false;
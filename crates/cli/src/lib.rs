@@ -58,8 +58,13 @@ pub const FLAG_MAX_THREADS: &str = "max-threads";
 pub const FLAG_OPT_SIZE: &str = "opt-size";
 pub const FLAG_LIB: &str = "lib";
 pub const FLAG_NO_LINK: &str = "no-link";
+pub const FLAG_EMIT_OBJECT: &str = "emit-object";
 pub const FLAG_TARGET: &str = "target";
 pub const FLAG_TIME: &str = "time";
+pub const FLAG_STATS: &str = "stats";
+pub const FLAG_PRECISE_FLOAT_REDUCTION: &str = "precise-float-reduction";
+pub const FLAG_STRICT_FP_EXCEPTIONS: &str = "strict-fp-exceptions";
+pub const FLAG_LLVM_FALLBACK: &str = "llvm-fallback";
 pub const FLAG_LINKER: &str = "linker";
 pub const FLAG_PREBUILT: &str = "prebuilt-platform";
 pub const FLAG_CHECK: &str = "check";
@@ -95,6 +100,11 @@ pub fn build_app<'a>() -> Command<'a> {
         .help("Make compilation finish as soon as possible, at the expense of runtime performance")
         .required(false);
 
+    let flag_llvm_fallback = Arg::new(FLAG_LLVM_FALLBACK)
+        .long(FLAG_LLVM_FALLBACK)
+        .help("With --dev, build the functions the dev backend does not support yet with LLVM instead")
+        .required(false);
+
     let flag_debug = Arg::new(FLAG_DEBUG)
         .long(FLAG_DEBUG)
         .help("Store LLVM debug information in the generated program")
@@ -105,6 +115,21 @@ pub fn build_app<'a>() -> Command<'a> {
         .help("Print detailed compilation time information")
         .required(false);
 
+    let flag_stats = Arg::new(FLAG_STATS)
+        .long(FLAG_STATS)
+        .help("Print code generation statistics: as JSON for LLVM, and as a table of function sizes for the dev backend")
+        .required(false);
+
+    let flag_precise_float_reduction = Arg::new(FLAG_PRECISE_FLOAT_REDUCTION)
+        .long(FLAG_PRECISE_FLOAT_REDUCTION)
+        .help("Sum lists of F64 with compensated summation, which is more precise but slower (LLVM backend only)")
+        .required(false);
+
+    let flag_strict_fp_exceptions = Arg::new(FLAG_STRICT_FP_EXCEPTIONS)
+        .long(FLAG_STRICT_FP_EXCEPTIONS)
+        .help("Crash when a float operation is invalid, divides by zero, or overflows, instead of giving NaN or an infinity (LLVM backend only)")
+        .required(false);
+
     let flag_linker = Arg::new(FLAG_LINKER)
         .long(FLAG_LINKER)
         .help("Set which linker to use\n(The surgical linker is enabled by default only when building for wasm32 or x86_64 Linux, because those are the only targets it currently supports. Otherwise the legacy linker is used by default.)")
@@ -147,8 +172,12 @@ pub fn build_app<'a>() -> Command<'a> {
             .arg(flag_max_threads.clone())
             .arg(flag_opt_size.clone())
             .arg(flag_dev.clone())
+            .arg(flag_llvm_fallback.clone())
             .arg(flag_debug.clone())
             .arg(flag_time.clone())
+            .arg(flag_stats.clone())
+            .arg(flag_precise_float_reduction.clone())
+            .arg(flag_strict_fp_exceptions.clone())
             .arg(flag_linker.clone())
             .arg(flag_prebuilt.clone())
             .arg(flag_wasm_stack_size_kb.clone())
@@ -182,6 +211,21 @@ pub fn build_app<'a>() -> Command<'a> {
                     .help("Do not link\n(Instead, just output the `.o` file.)")
                     .required(false),
             )
+            .arg(
+                Arg::new(FLAG_EMIT_OBJECT)
+                    .long(FLAG_EMIT_OBJECT)
+                    .help("Compile the app with the dev backend, and write its object file to the given path\n(The platform's host is not linked in; that is up to you.)")
+                    .takes_value(true)
+                    .allow_invalid_utf8(true)
+                    .conflicts_with_all(&[
+                        FLAG_LIB,
+                        FLAG_NO_LINK,
+                        FLAG_BUNDLE,
+                        FLAG_OPTIMIZE,
+                        FLAG_OPT_SIZE,
+                    ])
+                    .required(false),
+            )
             .arg(
                 Arg::new(ROC_FILE)
                     .help("The .roc file to build")
@@ -218,8 +262,12 @@ pub fn build_app<'a>() -> Command<'a> {
             .arg(flag_max_threads.clone())
             .arg(flag_opt_size.clone())
             .arg(flag_dev.clone())
+            .arg(flag_llvm_fallback.clone())
             .arg(flag_debug.clone())
             .arg(flag_time.clone())
+            .arg(flag_stats.clone())
+            .arg(flag_precise_float_reduction.clone())
+            .arg(flag_strict_fp_exceptions.clone())
             .arg(flag_linker.clone())
             .arg(flag_prebuilt.clone())
             .arg(roc_file_to_run.clone())
@@ -231,8 +279,12 @@ pub fn build_app<'a>() -> Command<'a> {
             .arg(flag_max_threads.clone())
             .arg(flag_opt_size.clone())
             .arg(flag_dev.clone())
+            .arg(flag_llvm_fallback.clone())
             .arg(flag_debug.clone())
             .arg(flag_time.clone())
+            .arg(flag_stats.clone())
+            .arg(flag_precise_float_reduction.clone())
+            .arg(flag_strict_fp_exceptions.clone())
             .arg(flag_linker.clone())
             .arg(flag_prebuilt.clone())
             .arg(roc_file_to_run.clone())
@@ -317,8 +369,12 @@ pub fn build_app<'a>() -> Command<'a> {
         .arg(flag_max_threads.clone())
         .arg(flag_opt_size)
         .arg(flag_dev)
+        .arg(flag_llvm_fallback)
         .arg(flag_debug)
         .arg(flag_time)
+        .arg(flag_stats)
+        .arg(flag_precise_float_reduction)
+        .arg(flag_strict_fp_exceptions)
         .arg(flag_linker)
         .arg(flag_prebuilt)
         .arg(roc_file_to_run.required(false))
@@ -594,7 +650,26 @@ pub fn build(
     // so we don't want to spend time freeing these values
     let arena = ManuallyDrop::new(Bump::new());
 
-    let code_gen_backend = if matches!(triple.architecture, Architecture::Wasm32) {
+    let emit_object_path = match config {
+        BuildOnly => matches.value_of_os(FLAG_EMIT_OBJECT).map(PathBuf::from),
+        _ => None,
+    };
+
+    if emit_object_path.is_some()
+        && !matches!(
+            triple.binary_format,
+            BinaryFormat::Elf | BinaryFormat::Macho
+        )
+    {
+        user_error!(
+            "--{} only supports targets with ELF or Mach-O object files for now",
+            FLAG_EMIT_OBJECT
+        );
+    }
+
+    let code_gen_backend = if emit_object_path.is_some() {
+        CodeGenBackend::Assembly
+    } else if matches!(triple.architecture, Architecture::Wasm32) {
         CodeGenBackend::Wasm
     } else {
         match matches.is_present(FLAG_DEV) {
@@ -620,6 +695,10 @@ pub fn build(
     };
     let emit_debug_info = matches.is_present(FLAG_DEBUG);
     let emit_timings = matches.is_present(FLAG_TIME);
+    let emit_stats = matches.is_present(FLAG_STATS);
+    let precise_float_reduction = matches.is_present(FLAG_PRECISE_FLOAT_REDUCTION);
+    let strict_fp_exceptions = matches.is_present(FLAG_STRICT_FP_EXCEPTIONS);
+    let llvm_fallback = matches.is_present(FLAG_LLVM_FALLBACK);
 
     let threading = match matches
         .value_of(FLAG_MAX_THREADS)
@@ -636,6 +715,9 @@ pub fn build(
 
     let linking_strategy = if wasm_dev_backend {
         LinkingStrategy::Additive
+    } else if emit_object_path.is_some() {
+        // with `LinkType::None`, this writes the object file without linking it
+        LinkingStrategy::Legacy
     } else if !roc_linker::supported(link_type, &triple)
         || matches.value_of(FLAG_LINKER) == Some("legacy")
     {
@@ -669,6 +751,10 @@ pub fn build(
         backend: code_gen_backend,
         opt_level,
         emit_debug_info,
+        emit_stats,
+        precise_float_reduction,
+        strict_fp_exceptions,
+        llvm_fallback,
     };
 
     let load_config = standard_load_config(&triple, build_ordering, threading);
@@ -696,6 +782,16 @@ pub fn build(
         }) => {
             match config {
                 BuildOnly => {
+                    let binary_path = match emit_object_path {
+                        Some(emit_object_path) => {
+                            // copy instead of renaming, because the path may be on another mount
+                            std::fs::copy(&binary_path, &emit_object_path)?;
+                            std::fs::remove_file(&binary_path)?;
+                            emit_object_path
+                        }
+                        None => binary_path,
+                    };
+
                     // If possible, report the generated executable name relative to the current dir.
                     let generated_filename = binary_path
                         .strip_prefix(env::current_dir().unwrap())
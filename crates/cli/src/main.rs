@@ -4,8 +4,8 @@ use roc_cli::build::check_file;
 use roc_cli::{
     build_app, format, test, BuildConfig, FormatMode, Target, CMD_BUILD, CMD_CHECK, CMD_DEV,
     CMD_DOCS, CMD_EDIT, CMD_FORMAT, CMD_GEN_STUB_LIB, CMD_GLUE, CMD_REPL, CMD_RUN, CMD_TEST,
-    CMD_VERSION, DIRECTORY_OR_FILES, FLAG_CHECK, FLAG_LIB, FLAG_NO_LINK, FLAG_TARGET, FLAG_TIME,
-    GLUE_FILE, ROC_FILE,
+    CMD_VERSION, DIRECTORY_OR_FILES, FLAG_CHECK, FLAG_EMIT_OBJECT, FLAG_LIB, FLAG_NO_LINK,
+    FLAG_TARGET, FLAG_TIME, GLUE_FILE, ROC_FILE,
 };
 use roc_docs::generate_docs_html;
 use roc_error_macros::user_error;
@@ -111,7 +111,7 @@ fn main() -> io::Result<()> {
             let target: Target = matches.value_of_t(FLAG_TARGET).unwrap_or_default();
             let link_type = match (
                 matches.is_present(FLAG_LIB),
-                matches.is_present(FLAG_NO_LINK),
+                matches.is_present(FLAG_NO_LINK) || matches.is_present(FLAG_EMIT_OBJECT),
             ) {
                 (true, false) => LinkType::Dylib,
                 (true, true) => user_error!("build can only be one of `--lib` or `--no-link`"),
@@ -18,6 +18,7 @@ mod cli_run {
     use roc_cli::{CMD_BUILD, CMD_CHECK, CMD_DEV, CMD_FORMAT, CMD_RUN, CMD_TEST};
     use roc_test_utils::assert_multiline_str_eq;
     use serial_test::serial;
+    use std::ffi::OsStr;
     use std::iter;
     use std::path::Path;
 
@@ -53,6 +54,12 @@ mod cli_run {
     const CHECK_FLAG: &str = concatcp!("--", roc_cli::FLAG_CHECK);
     const PREBUILT_PLATFORM: &str = concatcp!("--", roc_cli::FLAG_PREBUILT, "=true");
     #[allow(dead_code)]
+    const DEV_FLAG: &str = concatcp!("--", roc_cli::FLAG_DEV);
+    #[allow(dead_code)]
+    const LLVM_FALLBACK_FLAG: &str = concatcp!("--", roc_cli::FLAG_LLVM_FALLBACK);
+    #[allow(dead_code)]
+    const STATS_FLAG: &str = concatcp!("--", roc_cli::FLAG_STATS);
+    #[allow(dead_code)]
     const TARGET_FLAG: &str = concatcp!("--", roc_cli::FLAG_TARGET);
 
     #[derive(Debug)]
@@ -664,6 +671,38 @@ mod cli_run {
         )
     }
 
+    #[test]
+    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+    fn fibonacci_with_llvm_fallback() {
+        let path = file_path_from_root(
+            "crates/cli_testing_examples/algorithms",
+            "fibonacci-fallback.roc",
+        );
+        let out = run_roc(
+            [
+                CMD_RUN,
+                DEV_FLAG,
+                LLVM_FALLBACK_FLAG,
+                STATS_FLAG,
+                path.to_str().unwrap(),
+            ],
+            &[],
+            &[],
+        );
+
+        assert!(out.status.success(), "{}", out.stderr);
+        assert!(out.stdout.ends_with("55\n"), "{}", out.stdout);
+
+        // `checked` crashes, so LLVM builds it, and the dev backend builds the rest
+        assert!(
+            out.stdout
+                .lines()
+                .any(|line| line.contains("checked") && line.contains("llvm")),
+            "{}",
+            out.stdout
+        );
+    }
+
     #[test]
     fn hello_gui() {
         test_roc_app_slim(
@@ -1149,6 +1188,18 @@ mod cli_run {
             )
         }
 
+        #[test]
+        #[cfg_attr(windows, ignore)]
+        fn str_from_int() {
+            test_benchmark(
+                "StrFromInt.roc",
+                "str-from-int",
+                &["2"],
+                "380\n",
+                UseValgrind::Yes,
+            )
+        }
+
         #[test]
         #[cfg_attr(windows, ignore)]
         fn rbtree_insert() {
@@ -1297,6 +1348,46 @@ mod cli_run {
         );
     }
 
+    #[test]
+    #[cfg_attr(windows, ignore)]
+    fn emit_object_links_with_c_driver() {
+        let out_dir = tempfile::tempdir().unwrap();
+        let object = out_dir.path().join("app.o");
+        let executable = out_dir.path().join("app");
+
+        let build_out = run_roc(
+            [
+                OsStr::new(CMD_BUILD),
+                OsStr::new(concatcp!("--", roc_cli::FLAG_EMIT_OBJECT)),
+                object.as_os_str(),
+                fixture_file("emit-object", "app.roc").as_os_str(),
+            ],
+            &[],
+            &[],
+        );
+        assert!(build_out.status.success(), "{}", build_out.stderr);
+        assert!(object.exists());
+
+        let link_out = run_cmd(
+            "cc",
+            [],
+            &[
+                fixture_file("emit-object", "driver.c")
+                    .display()
+                    .to_string(),
+                object.display().to_string(),
+                "-o".to_string(),
+                executable.display().to_string(),
+            ],
+            [],
+        );
+        assert!(link_out.status.success(), "{}", link_out.stderr);
+
+        let run_out = run_cmd(executable.to_str().unwrap(), [], &[], []);
+        assert!(run_out.status.success(), "{}", run_out.stderr);
+        assert_eq!(run_out.stdout, "42\n");
+    }
+
     #[test]
     #[serial(multi_dep_thunk)]
     #[cfg_attr(windows, ignore)]
@@ -0,0 +1,7 @@
+app "emit-object"
+    packages { pf: "platform/main.roc" }
+    imports []
+    provides [main] to pf
+
+main : I64
+main = 6 * 7
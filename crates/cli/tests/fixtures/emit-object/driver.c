@@ -0,0 +1,46 @@
+#include <inttypes.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+// A host written by hand, to link against the object file from `roc build --emit-object`
+
+void *roc_alloc(size_t size, unsigned int alignment) { return malloc(size); }
+
+void *roc_realloc(void *ptr, size_t new_size, size_t old_size,
+                  unsigned int alignment) {
+  return realloc(ptr, new_size);
+}
+
+void roc_dealloc(void *ptr, unsigned int alignment) { free(ptr); }
+
+// Where a crash is in the source, matching `SourceInfo` in the builtins' panic.zig
+struct SourceInfo {
+  const char *file;
+  uint32_t line;
+  uint32_t column;
+};
+
+void roc_panic(void *msg, unsigned int tag_id, const struct SourceInfo *source) {
+  if (source != NULL) {
+    fprintf(stderr, "Roc crashed at %s:%" PRIu32 ":%" PRIu32 "\n", source->file,
+            source->line, source->column);
+  } else {
+    fprintf(stderr, "Roc crashed\n");
+  }
+  exit(1);
+}
+
+void *roc_memcpy(void *dest, const void *src, size_t n) {
+  return memcpy(dest, src, n);
+}
+
+void *roc_memset(void *str, int c, size_t n) { return memset(str, c, n); }
+
+extern int64_t roc__mainForHost_1_exposed(void);
+
+int main(void) {
+  printf("%" PRId64 "\n", roc__mainForHost_1_exposed());
+
+  return 0;
+}
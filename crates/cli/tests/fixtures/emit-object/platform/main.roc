@@ -0,0 +1,9 @@
+platform "emit-object"
+    requires {} { main : I64 }
+    exposes []
+    packages {}
+    imports []
+    provides [mainForHost]
+
+mainForHost : I64
+mainForHost = main
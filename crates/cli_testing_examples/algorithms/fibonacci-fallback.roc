@@ -0,0 +1,19 @@
+app "fibonacci-fallback"
+    packages { pf: "fibonacci-platform/main.roc" }
+    imports []
+    provides [main] to pf
+
+main = \n -> fib (checked n) 0 1
+
+# the dev backend does not implement `crash` yet, so `--llvm-fallback` builds this with LLVM
+checked = \n ->
+    if n < 0 then
+        crash "fib only works on non-negative numbers"
+    else
+        n
+
+fib = \n, a, b ->
+    if n == 0 then
+        a
+    else
+        fib (n - 1) b (a + b)
@@ -0,0 +1,30 @@
+app "str-from-int"
+    packages { pf: "platform/main.roc" }
+    imports [pf.Task]
+    provides [main] to pf
+
+main : Task.Task {} []
+main =
+    Task.after
+        Task.getInt
+        \n ->
+            totalLength n 0
+            |> Num.toStr
+            |> Task.putLine
+
+# every round converts the numbers from 0 to 99, which add up to 190 bytes
+totalLength : I64, I64 -> I64
+totalLength = \rounds, acc ->
+    if rounds <= 0 then
+        acc
+    else
+        totalLength (rounds - 1) (roundLength 0 acc)
+
+roundLength : I64, I64 -> I64
+roundLength = \i, acc ->
+    if i >= 100 then
+        acc
+    else
+        length = Num.toStr i |> Str.countUtf8Bytes |> Num.toI64
+
+        roundLength (i + 1) (acc + length)
@@ -160,6 +160,16 @@ pub fn bench_rbtree_delete<T: Measurement>(bench_group_opt: Option<&mut Benchmar
     );
 }
 
+pub fn bench_str_from_int<T: Measurement>(bench_group_opt: Option<&mut BenchmarkGroup<T>>) {
+    exec_bench_w_input(
+        &file_path_from_root("crates/cli_testing_examples/benchmarks", "StrFromInt.roc"),
+        "100000",
+        "str-from-int",
+        "19000000\n",
+        bench_group_opt,
+    );
+}
+
 pub fn bench_quicksort<T: Measurement>(bench_group_opt: Option<&mut BenchmarkGroup<T>>) {
     exec_bench_w_input(
         &file_path_from_root("crates/cli_testing_examples/benchmarks", "QuicksortApp.roc"),
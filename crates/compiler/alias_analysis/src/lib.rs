@@ -644,7 +644,7 @@ fn stmt_spec<'a>(
             let jpid = env.join_points[id];
             builder.add_jump(block, jpid, argument, ret_type_id)
         }
-        Crash(msg, _) => {
+        Crash(msg, _, _) => {
             // Model this as a foreign call rather than TERMINATE because
             // we want ownership of the message.
             let result_type = layout_spec(env, builder, interner, layout)?;
@@ -981,6 +981,19 @@ fn call_spec<'a>(
 
                     add_loop(builder, block, state_type, init_state, loop_body)
                 }
+
+                BoxMap { boxed } => {
+                    let boxed = env.symbols[boxed];
+
+                    let value = builder.add_get_tuple_field(block, boxed, BOX_VALUE_INDEX)?;
+                    let new_value = call_function!(builder, block, [value]);
+
+                    // a unique box is updated in place
+                    let cell = builder.add_get_tuple_field(block, boxed, BOX_CELL_INDEX)?;
+                    builder.add_update(block, update_mode_var, cell)?;
+
+                    with_new_heap_cell(builder, block, new_value)
+                }
             }
         }
     }
@@ -1076,6 +1089,10 @@ fn lowlevel_spec<'a>(
             // just dream up a unit value
             builder.add_make_tuple(block, &[])
         }
+        ListSum | ListProduct | ListMinUnsafe | ListMaxUnsafe => {
+            // the elements are numbers, so just dream up a unit value
+            builder.add_make_tuple(block, &[])
+        }
         ListGetUnsafe => {
             // NOTE the ListGet lowlevel op is only evaluated if the index is in-bounds
             let list = env.symbols[&arguments[0]];
@@ -1144,7 +1161,7 @@ fn lowlevel_spec<'a>(
                 _ => unreachable!("empty array does not have a list layout"),
             }
         }
-        ListReserve => {
+        ListReserve | EncodeAppendNumLe => {
             let list = env.symbols[&arguments[0]];
 
             list_clone(builder, block, update_mode_var, list)
@@ -1155,6 +1172,16 @@ fn lowlevel_spec<'a>(
 
             list_append(builder, block, update_mode_var, list, to_insert)
         }
+        StrConcat => {
+            let string = env.symbols[&arguments[0]];
+
+            // the first string is reallocated, in place if it is unique
+            let cell = builder.add_get_tuple_field(block, string, LIST_CELL_INDEX)?;
+            let _unit = builder.add_update(block, update_mode_var, cell)?;
+
+            let new_cell = builder.add_new_heap_cell(block)?;
+            builder.add_make_tuple(block, &[new_cell])
+        }
         StrToUtf8 => {
             let string = env.symbols[&arguments[0]];
 
@@ -1609,7 +1636,6 @@ fn static_list_type<TC: TypeContext>(builder: &mut TC) -> Result<TypeId> {
 const LIST_CELL_INDEX: u32 = 0;
 const LIST_BAG_INDEX: u32 = 1;
 
-#[allow(dead_code)]
 const BOX_CELL_INDEX: u32 = LIST_CELL_INDEX;
 const BOX_VALUE_INDEX: u32 = LIST_BAG_INDEX;
 
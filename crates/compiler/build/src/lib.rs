@@ -4,4 +4,5 @@
 #![allow(clippy::large_enum_variant)]
 pub mod link;
 pub mod program;
+pub mod size_report;
 pub mod target;
@@ -1591,6 +1591,25 @@ pub fn preprocess_host_wasm32(host_input_path: &Path, preprocessed_host_path: &P
     let _ = builtins_host_tempfile;
 }
 
+/// Combines relocatable objects into one, like the ones the dev backend and LLVM built for an app
+pub fn merge_objects(objects: &[&[u8]]) -> Vec<u8> {
+    let dir = tempfile::tempdir().unwrap();
+    let merged_path = dir.path().join("app.o");
+
+    let mut ld_cmd = Command::new("ld");
+    ld_cmd.arg("-r");
+    for (index, bytes) in objects.iter().enumerate() {
+        let path = dir.path().join(format!("part{}.o", index));
+        fs::write(&path, bytes).unwrap();
+        ld_cmd.arg(path);
+    }
+    ld_cmd.arg("-o").arg(&merged_path);
+
+    run_build_command(ld_cmd, "app.o", 0);
+
+    fs::read(merged_path).unwrap()
+}
+
 fn run_build_command(mut command: Command, file_to_build: &str, flaky_fail_counter: usize) {
     let mut command_string = std::ffi::OsString::new();
     command_string.push(command.get_program());
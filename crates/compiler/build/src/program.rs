@@ -1,20 +1,29 @@
+use crate::size_report::SizeReport;
 use inkwell::memory_buffer::MemoryBuffer;
+use roc_collections::all::{MutMap, MutSet};
 use roc_error_macros::internal_error;
 use roc_gen_llvm::llvm::build::{module_from_builtins, LlvmBackendMode};
 use roc_gen_llvm::llvm::externs::add_default_roc_externs;
+use roc_gen_llvm::llvm::memory_model::RocRuntimeMemoryModel;
+use roc_gen_llvm::llvm::verify::{isolate_broken_functions, stub_broken_functions};
+use roc_gen_llvm::CompilationStats;
 use roc_load::{EntryPoint, ExpectMetadata, LoadedModule, MonomorphizedModule};
-use roc_mono::ir::{OptLevel, SingleEntryPoint};
+use roc_module::symbol::{Interns, ModuleId, Symbol};
+use roc_mono::ir::{OptLevel, Proc, ProcLayout, SingleEntryPoint};
+use roc_mono::layout::STLayoutInterner;
 use roc_reporting::cli::{report_problems, Problems};
 use std::ops::Deref;
 use std::path::{Path, PathBuf};
 use std::time::{Duration, Instant};
 
-#[cfg(feature = "target-wasm32")]
-use roc_collections::all::MutSet;
-
-#[derive(Debug, Clone, Copy, Default)]
+#[derive(Debug, Clone, Default)]
 pub struct CodeGenTiming {
     pub code_gen: Duration,
+    /// only collected by the LLVM backend, and only when `emit_stats` is set
+    pub stats: Option<CompilationStats>,
+    /// The size of every procedure in the object, biggest first. Only for objects with
+    /// machine code, so neither for Wasm nor when LLVM emits bitcode.
+    pub size_report: Option<SizeReport>,
 }
 
 pub fn report_problems_monomorphized(loaded: &mut MonomorphizedModule) -> Problems {
@@ -53,18 +62,33 @@ impl Deref for CodeObject {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum CodeGenBackend {
     Assembly,
     Llvm,
     Wasm,
 }
 
+impl CodeGenBackend {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CodeGenBackend::Assembly => "dev",
+            CodeGenBackend::Llvm => "llvm",
+            CodeGenBackend::Wasm => "wasm",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct CodeGenOptions {
     pub backend: CodeGenBackend,
     pub opt_level: OptLevel,
     pub emit_debug_info: bool,
+    pub emit_stats: bool,
+    pub precise_float_reduction: bool,
+    pub strict_fp_exceptions: bool,
+    /// With the dev backend, build the procedures it does not support yet with LLVM
+    pub llvm_fallback: bool,
 }
 
 type GenFromMono<'a> = (CodeObject, CodeGenTiming, ExpectMetadata<'a>);
@@ -86,6 +110,7 @@ pub fn gen_from_mono_module<'a>(
             target,
             preprocessed_host_path,
             wasm_dev_stack_bytes,
+            code_gen_options.llvm_fallback,
         ),
         CodeGenBackend::Llvm => {
             gen_from_mono_module_llvm(arena, loaded, roc_file_path, target, code_gen_options)
@@ -97,6 +122,35 @@ pub fn gen_from_mono_module<'a>(
     }
 }
 
+/// The sanitizers in `ROC_SANITIZERS`, a list like `address,thread`.
+/// They are only applied with the `sanitizers` feature.
+fn sanitizers_from_env() -> Vec<roc_gen_llvm::Sanitizer> {
+    let names = match std::env::var("ROC_SANITIZERS") {
+        Ok(names) if cfg!(feature = "sanitizers") => names,
+        _ => return Vec::new(),
+    };
+
+    let mut sanitizers = vec![];
+    let mut unrecognized = vec![];
+    for name in names.split(',').map(|x| x.trim()) {
+        match roc_gen_llvm::Sanitizer::from_name(name) {
+            Some(sanitizer) => sanitizers.push(sanitizer),
+            None => unrecognized.push(name.to_owned()),
+        }
+    }
+    if !unrecognized.is_empty() {
+        let out = unrecognized
+            .iter()
+            .map(|x| format!("{:?}", x))
+            .collect::<Vec<String>>()
+            .join(", ");
+        eprintln!("Unrecognized sanitizer: {}\nSupported options are \"address\", \"memory\", \"thread\", \"cargo-fuzz\", and \"afl.rs\".", out);
+        eprintln!("Note: \"cargo-fuzz\" and \"afl.rs\" both enable sanitizer coverage for fuzzing. They just use different parameters to match the respective libraries.")
+    }
+
+    sanitizers
+}
+
 // TODO how should imported modules factor into this? What if those use builtins too?
 // TODO this should probably use more helper functions
 // TODO make this polymorphic in the llvm functions so it can be reused for another backend.
@@ -114,6 +168,7 @@ fn gen_from_mono_module_llvm<'a>(
     use inkwell::targets::{FileType, RelocMode};
 
     let code_gen_start = Instant::now();
+    let mut stats = CompilationStats::new();
 
     // Generate the binary
     let target_info = roc_target::TargetInfo::from(target);
@@ -159,10 +214,21 @@ fn gen_from_mono_module_llvm<'a>(
         backend: _,
         opt_level,
         emit_debug_info,
+        emit_stats,
+        precise_float_reduction,
+        strict_fp_exceptions,
+        llvm_fallback: _,
     } = code_gen_options;
+    let options = roc_gen_llvm::CodegenOptions {
+        emit_debug_info,
+        sanitizers: sanitizers_from_env(),
+        precise_float_reduction,
+        strict_fp_exceptions,
+        ..roc_gen_llvm::CodegenOptions::new(opt_level, target.clone())
+    };
 
     let builder = context.create_builder();
-    let (dibuilder, compile_unit) = roc_gen_llvm::llvm::build::Env::new_debug_info(module);
+    let (dibuilder, compile_unit) = roc_gen_llvm::llvm::build::Env::new_debug_info(module, target);
     let (mpm, _fpm) = roc_gen_llvm::llvm::build::construct_optimization_passes(module, opt_level);
 
     // Compile and add all the Procs before adding main
@@ -179,8 +245,14 @@ fn gen_from_mono_module_llvm<'a>(
             OptLevel::Development => LlvmBackendMode::BinaryDev,
             OptLevel::Normal | OptLevel::Size | OptLevel::Optimize => LlvmBackendMode::Binary,
         },
+        options,
+        memory_model: &RocRuntimeMemoryModel,
 
         exposed_to_host: loaded.exposed_to_host.values.keys().copied().collect(),
+        host_exposed: Default::default(),
+        wrappers_in_progress: Default::default(),
+        fn_origins: Default::default(),
+        sources: loaded.sources,
     };
 
     // does not add any externs for this mode (we have a host) but cleans up some functions around
@@ -201,10 +273,13 @@ fn gen_from_mono_module_llvm<'a>(
         EntryPoint::Test => roc_mono::ir::EntryPoint::Expects { symbols: &[] },
     };
 
+    stats.record_phase_start("generate");
+
+    stats.record_procs_compiled(loaded.procedures.len());
+
     roc_gen_llvm::llvm::build::build_procedures(
         &env,
         &mut loaded.layout_interner,
-        opt_level,
         loaded.procedures,
         entry_point,
         Some(&app_ll_file),
@@ -215,29 +290,52 @@ fn gen_from_mono_module_llvm<'a>(
     // we don't use the debug info, and it causes weird errors.
     module.strip_debug_info();
 
+    stats.record_memory_usage(arena.allocated_bytes());
+    stats.record_phase_end("generate");
+
     // Uncomment this to see the module's optimized LLVM instruction output:
     // env.module.print_to_stderr();
 
+    stats.record_phase_start("optimize");
+
     mpm.run_on(module);
 
+    stats.record_phase_end("optimize");
+
     // Verify the module
     if let Err(errors) = env.module.verify() {
+        let report = isolate_broken_functions(&env, errors.to_string(), stub_broken_functions());
+
         // write the ll code to a file, so we can modify it
         env.module.print_to_file(&app_ll_file).unwrap();
 
-        internal_error!(
-            "😱 LLVM errors when defining module; I wrote the full LLVM IR to {:?}\n\n {}",
+        if !report.stubbed {
+            internal_error!(
+                "😱 LLVM errors when defining module; I wrote the full LLVM IR to {:?}\n\n {}",
+                app_ll_file,
+                report,
+            );
+        }
+
+        eprintln!(
+            "😱 LLVM errors when defining module; I wrote the LLVM IR with the broken functions stubbed out to {:?}\n\n {}",
             app_ll_file,
-            errors.to_string(),
+            report,
         );
     }
 
     // Uncomment this to see the module's optimized LLVM instruction output:
     // env.module.print_to_stderr();
 
+    if emit_stats {
+        stats.record_ir_instructions(roc_gen_llvm::count_ir_instructions(module));
+    }
+
+    stats.record_phase_start("emit");
+
     // annotate the LLVM IR output with debug info
     // so errors are reported with the line number of the LLVM source
-    let memory_buffer = if cfg!(feature = "sanitizers") && std::env::var("ROC_SANITIZERS").is_ok() {
+    let memory_buffer = if !env.options.sanitizers.is_empty() {
         let dir = tempfile::tempdir().unwrap();
         let dir = dir.into_path();
 
@@ -253,44 +351,9 @@ fn gen_from_mono_module_llvm<'a>(
         // It most likely will not work with other fuzzer setups without modification.
         let mut passes = vec![];
         let mut extra_args = vec![];
-        let mut unrecognized = vec![];
-        for sanitizer in std::env::var("ROC_SANITIZERS")
-            .unwrap()
-            .split(',')
-            .map(|x| x.trim())
-        {
-            match sanitizer {
-                "address" => passes.push("asan-module"),
-                "memory" => passes.push("msan-module"),
-                "thread" => passes.push("tsan-module"),
-                "cargo-fuzz" => {
-                    passes.push("sancov-module");
-                    extra_args.extend_from_slice(&[
-                        "-sanitizer-coverage-level=3",
-                        "-sanitizer-coverage-prune-blocks=0",
-                        "-sanitizer-coverage-inline-8bit-counters",
-                        "-sanitizer-coverage-pc-table",
-                    ]);
-                }
-                "afl.rs" => {
-                    passes.push("sancov-module");
-                    extra_args.extend_from_slice(&[
-                        "-sanitizer-coverage-level=3",
-                        "-sanitizer-coverage-prune-blocks=0",
-                        "-sanitizer-coverage-trace-pc-guard",
-                    ]);
-                }
-                x => unrecognized.push(x.to_owned()),
-            }
-        }
-        if !unrecognized.is_empty() {
-            let out = unrecognized
-                .iter()
-                .map(|x| format!("{:?}", x))
-                .collect::<Vec<String>>()
-                .join(", ");
-            eprintln!("Unrecognized sanitizer: {}\nSupported options are \"address\", \"memory\", \"thread\", \"cargo-fuzz\", and \"afl.rs\".", out);
-            eprintln!("Note: \"cargo-fuzz\" and \"afl.rs\" both enable sanitizer coverage for fuzzing. They just use different parameters to match the respective libraries.")
+        for sanitizer in env.options.sanitizers.iter() {
+            passes.push(sanitizer.opt_pass());
+            extra_args.extend_from_slice(sanitizer.opt_args());
         }
 
         use std::process::Command;
@@ -327,7 +390,7 @@ fn gen_from_mono_module_llvm<'a>(
         assert!(bc_to_object.status.success(), "{:#?}", bc_to_object);
 
         MemoryBuffer::create_from_file(&app_o_file).expect("memory buffer creation works")
-    } else if emit_debug_info {
+    } else if env.options.emit_debug_info {
         module.strip_debug_info();
 
         let mut app_ll_dbg_file = PathBuf::from(roc_file_path);
@@ -411,11 +474,20 @@ fn gen_from_mono_module_llvm<'a>(
         }
     };
 
+    stats.record_phase_end("emit");
+    stats.record_memory_usage(arena.allocated_bytes());
+
+    let size_report = SizeReport::from_object(memory_buffer.as_slice(), CodeGenBackend::Llvm).ok();
+
     let code_gen = code_gen_start.elapsed();
 
     (
         CodeObject::MemoryBuffer(memory_buffer),
-        CodeGenTiming { code_gen },
+        CodeGenTiming {
+            code_gen,
+            stats: emit_stats.then_some(stats),
+            size_report,
+        },
         ExpectMetadata {
             interns: env.interns,
             layout_interner: loaded.layout_interner,
@@ -431,6 +503,7 @@ fn gen_from_mono_module_dev<'a>(
     target: &target_lexicon::Triple,
     preprocessed_host_path: &Path,
     wasm_dev_stack_bytes: Option<u32>,
+    llvm_fallback: bool,
 ) -> GenFromMono<'a> {
     use target_lexicon::Architecture;
 
@@ -442,7 +515,7 @@ fn gen_from_mono_module_dev<'a>(
             wasm_dev_stack_bytes,
         ),
         Architecture::X86_64 | Architecture::Aarch64(_) => {
-            gen_from_mono_module_dev_assembly(arena, loaded, target)
+            gen_from_mono_module_dev_assembly(arena, loaded, target, llvm_fallback)
         }
         _ => todo!(),
     }
@@ -455,12 +528,13 @@ pub fn gen_from_mono_module_dev<'a>(
     target: &target_lexicon::Triple,
     _host_input_path: &Path,
     _wasm_dev_stack_bytes: Option<u32>,
+    llvm_fallback: bool,
 ) -> GenFromMono<'a> {
     use target_lexicon::Architecture;
 
     match target.architecture {
         Architecture::X86_64 | Architecture::Aarch64(_) => {
-            gen_from_mono_module_dev_assembly(arena, loaded, target)
+            gen_from_mono_module_dev_assembly(arena, loaded, target, llvm_fallback)
         }
         _ => todo!(),
     }
@@ -524,7 +598,11 @@ fn gen_from_mono_module_dev_wasm32<'a>(
 
     (
         CodeObject::Vector(final_binary_bytes),
-        CodeGenTiming { code_gen },
+        CodeGenTiming {
+            code_gen,
+            stats: None,
+            size_report: None,
+        },
         ExpectMetadata {
             interns,
             layout_interner,
@@ -537,41 +615,59 @@ fn gen_from_mono_module_dev_assembly<'a>(
     arena: &'a bumpalo::Bump,
     loaded: MonomorphizedModule<'a>,
     target: &target_lexicon::Triple,
+    llvm_fallback: bool,
 ) -> GenFromMono<'a> {
     let code_gen_start = Instant::now();
 
-    let lazy_literals = true;
-    let generate_allocators = false; // provided by the platform
-
     let MonomorphizedModule {
         module_id,
         procedures,
         mut interns,
         exposed_to_host,
         mut layout_interner,
+        sources,
         ..
     } = loaded;
+    let exposed_to_host: MutSet<Symbol> = exposed_to_host.values.keys().copied().collect();
 
-    let env = roc_gen_dev::Env {
-        arena,
-        module_id,
-        exposed_to_host: exposed_to_host.values.keys().copied().collect(),
-        lazy_literals,
-        generate_allocators,
-    };
+    // kept for a second attempt, which leaves out what the dev backend does not support
+    let fallback_procedures = llvm_fallback.then(|| procedures.clone());
 
-    let module_object =
-        roc_gen_dev::build_module(&env, &mut interns, &mut layout_interner, target, procedures);
+    let env = dev_env(arena, module_id, &exposed_to_host);
+    let result =
+        roc_gen_dev::build_procedures(&env, &mut interns, &mut layout_interner, target, procedures);
 
-    let code_gen = code_gen_start.elapsed();
+    let (module_out, size_report) = match (result, fallback_procedures) {
+        (Ok(output), _) => (
+            output.bytes,
+            SizeReport::from_dev_backend(&env.proc_sizes.borrow()),
+        ),
+        (Err(unsupported), Some(procedures)) => gen_with_llvm_fallback(
+            arena,
+            target,
+            module_id,
+            &exposed_to_host,
+            &mut interns,
+            &mut layout_interner,
+            procedures,
+            &unsupported,
+            sources,
+        ),
+        (Err(unsupported), None) => exit_unsupported(
+            &unsupported,
+            "Try building with LLVM instead, by leaving out --dev, or only these functions, with --llvm-fallback.",
+        ),
+    };
 
-    let module_out = module_object
-        .write()
-        .expect("failed to build output object");
+    let code_gen = code_gen_start.elapsed();
 
     (
         CodeObject::Vector(module_out),
-        CodeGenTiming { code_gen },
+        CodeGenTiming {
+            code_gen,
+            stats: None,
+            size_report: Some(size_report),
+        },
         ExpectMetadata {
             interns,
             layout_interner,
@@ -579,3 +675,187 @@ fn gen_from_mono_module_dev_assembly<'a>(
         },
     )
 }
+
+fn dev_env<'a>(
+    arena: &'a bumpalo::Bump,
+    module_id: ModuleId,
+    exposed_to_host: &MutSet<Symbol>,
+) -> roc_gen_dev::Env<'a> {
+    roc_gen_dev::Env {
+        arena,
+        module_id,
+        exposed_to_host: exposed_to_host.clone(),
+        export_names: Default::default(),
+        lazy_literals: true,
+        generate_allocators: false, // provided by the platform
+        host_symbols: Default::default(),
+        alias_host_symbols: false,
+        peak_arena_bytes: Default::default(),
+        stats: Default::default(),
+        proc_sizes: Default::default(),
+        reg_alloc_order: None,
+        inline_small_procs: false,
+        codegen_threads: std::thread::available_parallelism().map_or(1, |threads| threads.get()),
+        stack_canary: None,
+        patchpoints: false,
+        panic_cleanup: false,
+        runtime_context: false,
+        serialize_ticks: false,
+        // the dev backend is for development builds
+        zero_padding: true,
+        proc_alignment: 16,
+        statement_markers: false,
+        listings: Default::default(),
+    }
+}
+
+fn exit_unsupported(unsupported: &[roc_gen_dev::UnsupportedError<'_>], hint: &str) -> ! {
+    eprintln!(
+        "\n{}\n\n{}",
+        roc_gen_dev::describe_unsupported(unsupported),
+        hint
+    );
+
+    std::process::exit(1);
+}
+
+/// Builds the procedures the dev backend does not support with LLVM, and everything else with
+/// the dev backend, into one object. The dev backend calls the procedures LLVM built through
+/// wrappers that have the names it gives its own procedures. LLVM builds its own copy of
+/// whatever those procedures call, so no call goes from LLVM's code back to the dev backend's.
+#[allow(clippy::too_many_arguments)]
+fn gen_with_llvm_fallback<'a>(
+    arena: &'a bumpalo::Bump,
+    target: &target_lexicon::Triple,
+    module_id: ModuleId,
+    exposed_to_host: &MutSet<Symbol>,
+    interns: &mut Interns,
+    layout_interner: &mut STLayoutInterner<'a>,
+    procedures: MutMap<(Symbol, ProcLayout<'a>), Proc<'a>>,
+    unsupported: &[roc_gen_dev::UnsupportedError<'a>],
+    sources: MutMap<ModuleId, (PathBuf, Box<str>)>,
+) -> (Vec<u8>, SizeReport) {
+    use crate::target::{self, convert_opt_level};
+    use inkwell::context::Context;
+    use inkwell::module::Linkage;
+    use inkwell::targets::{FileType, RelocMode};
+
+    if unsupported
+        .iter()
+        .any(|proc| !procedures.contains_key(&(proc.symbol, proc.layout)))
+    {
+        exit_unsupported(
+            unsupported,
+            "LLVM can only stand in for functions from the program, not for the refcounting helpers the dev backend generates.",
+        );
+    }
+
+    let mut dev_procedures = procedures.clone();
+    for proc in unsupported {
+        dev_procedures.remove(&(proc.symbol, proc.layout));
+    }
+
+    let env = dev_env(arena, module_id, exposed_to_host);
+    let dev_output =
+        match roc_gen_dev::build_procedures(&env, interns, layout_interner, target, dev_procedures)
+        {
+            Ok(output) => output,
+            Err(unsupported) => internal_error!(
+                "the dev backend failed on procedures it built before:\n{}",
+                roc_gen_dev::describe_unsupported(&unsupported)
+            ),
+        };
+
+    // no optimizations, like the rest of a --dev build
+    let opt_level = OptLevel::Normal;
+    let context = Context::create();
+    let module = arena.alloc(module_from_builtins(target, &context, "app_fallback"));
+
+    for function in module.get_functions() {
+        // mark our zig-defined builtins as internal
+        if function
+            .get_name()
+            .to_str()
+            .unwrap()
+            .starts_with("roc_builtins")
+        {
+            function.set_linkage(Linkage::Internal);
+        }
+    }
+
+    let builder = context.create_builder();
+    let (dibuilder, compile_unit) = roc_gen_llvm::llvm::build::Env::new_debug_info(module, target);
+
+    let llvm_env = roc_gen_llvm::llvm::build::Env {
+        arena,
+        builder: &builder,
+        dibuilder: &dibuilder,
+        compile_unit: &compile_unit,
+        context: &context,
+        interns: interns.clone(),
+        module,
+        target_info: roc_target::TargetInfo::from(target),
+        mode: LlvmBackendMode::Binary,
+        options: roc_gen_llvm::CodegenOptions::new(opt_level, target.clone()),
+        memory_model: &RocRuntimeMemoryModel,
+
+        // the host calls these through the wrappers LLVM gives them
+        exposed_to_host: unsupported
+            .iter()
+            .map(|proc| proc.symbol)
+            .filter(|symbol| exposed_to_host.contains(symbol))
+            .collect(),
+        host_exposed: Default::default(),
+        wrappers_in_progress: Default::default(),
+        fn_origins: Default::default(),
+        sources,
+    };
+
+    add_default_roc_externs(&llvm_env);
+
+    let foreign: Vec<_> = unsupported
+        .iter()
+        .filter(|proc| !exposed_to_host.contains(&proc.symbol))
+        .map(|proc| (proc.symbol, proc.layout, proc.name.clone()))
+        .collect();
+
+    roc_gen_llvm::llvm::build::build_procedures_for_dev_backend(
+        &llvm_env,
+        layout_interner,
+        procedures,
+        &foreign,
+    );
+
+    llvm_env.dibuilder.finalize();
+    module.strip_debug_info();
+
+    if let Err(errors) = llvm_env.module.verify() {
+        let report =
+            isolate_broken_functions(&llvm_env, errors.to_string(), stub_broken_functions());
+
+        if !report.stubbed {
+            internal_error!(
+                "😱 LLVM errors in the functions it built for the dev backend:\n\n {}",
+                report,
+            );
+        }
+
+        eprintln!(
+            "😱 LLVM errors in the functions it built for the dev backend, which now crash instead:\n\n {}",
+            report,
+        );
+    }
+
+    let target_machine =
+        target::target_machine(target, convert_opt_level(opt_level), RelocMode::PIC).unwrap();
+    let llvm_object = target_machine
+        .write_to_memory_buffer(llvm_env.module, FileType::Object)
+        .expect("Writing .o file failed");
+
+    let size_report = SizeReport::from_dev_backend(&env.proc_sizes.borrow()).merge(
+        SizeReport::from_object(llvm_object.as_slice(), CodeGenBackend::Llvm).unwrap_or_default(),
+    );
+    let bytes = crate::link::merge_objects(&[&dev_output.bytes, llvm_object.as_slice()]);
+
+    (bytes, size_report)
+}
@@ -0,0 +1,340 @@
+//! How many bytes every procedure takes up in the object a backend emitted,
+//! to find out what makes a binary big.
+use crate::program::CodeGenBackend;
+use object::{Object, ObjectSection, ObjectSymbol, SectionKind, SymbolKind};
+use std::fmt::Write;
+
+/// What a procedure in a [`SizeReport`] is for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum SizeCategory {
+    /// A specialization of a procedure, from the program or a module written in Roc
+    Proc,
+    /// A generated helper that increments refcounts
+    Inc,
+    /// A generated helper that decrements refcounts, or resets a value so it can be reused
+    Dec,
+    /// A generated helper that compares two values
+    Eq,
+    /// A generated caller that lets a builtin call the function passed to a higher-order function
+    Transform,
+    /// An entry point for the host, or a wrapper around the allocator and panic functions
+    Wrapper,
+    /// Code from the builtins written in Zig
+    Builtin,
+}
+
+impl SizeCategory {
+    /// The category of a procedure, going by the name of its symbol in the object.
+    pub fn of_symbol(name: &str) -> Self {
+        // Mach-O puts an underscore in front of every name
+        let unprefixed = name.strip_prefix('_').unwrap_or(name);
+
+        // The dev backend names its helpers after their `HelperOp`, e.g. `Test_#help3_Inc_...`
+        if let Some((_, help)) = name.split_once("#help") {
+            let op = help
+                .trim_start_matches(|c: char| c.is_ascii_digit())
+                .trim_start_matches('_');
+            if op.starts_with("Inc") {
+                return SizeCategory::Inc;
+            } else if op.starts_with("Dec") || op.starts_with("Reset") {
+                return SizeCategory::Dec;
+            } else if op.starts_with("Eq") {
+                return SizeCategory::Eq;
+            }
+        }
+
+        // The LLVM backend names them after the symbols they implement, e.g. `#Attr_#inc_3`
+        if name.contains("#generic_eq") {
+            SizeCategory::Eq
+        } else if name.contains("#generic_rc_by_ref") {
+            if name.ends_with("_dec") {
+                SizeCategory::Dec
+            } else {
+                SizeCategory::Inc
+            }
+        } else if name.contains("#inc_") {
+            SizeCategory::Inc
+        } else if name.contains("#dec_") || unprefixed.starts_with("decrement_refcounted_ptr_") {
+            SizeCategory::Dec
+        } else if name.ends_with("_zig_function_caller") {
+            SizeCategory::Transform
+        } else if unprefixed.starts_with("roc__")
+            || ["roc_alloc", "roc_realloc", "roc_dealloc", "roc_panic"].contains(&unprefixed)
+        {
+            SizeCategory::Wrapper
+        } else if unprefixed.starts_with(|c: char| c.is_ascii_uppercase() || c == '#') {
+            // Roc procedures start with the name of their module
+            SizeCategory::Proc
+        } else {
+            SizeCategory::Builtin
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SizeCategory::Proc => "proc",
+            SizeCategory::Inc => "inc",
+            SizeCategory::Dec => "dec",
+            SizeCategory::Eq => "eq",
+            SizeCategory::Transform => "transform",
+            SizeCategory::Wrapper => "wrapper",
+            SizeCategory::Builtin => "builtin",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SizeEntry {
+    pub name: String,
+    pub category: SizeCategory,
+    /// With `--llvm-fallback`, the dev backend leaves some procedures to LLVM
+    pub backend: CodeGenBackend,
+    pub code_bytes: u64,
+    /// The literals the procedure loads from the data section.
+    /// Only the dev backend knows which procedure a literal belongs to.
+    pub data_bytes: u64,
+}
+
+impl SizeEntry {
+    pub fn total_bytes(&self) -> u64 {
+        self.code_bytes + self.data_bytes
+    }
+}
+
+/// The size of every procedure in an object, biggest first
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SizeReport {
+    pub entries: Vec<SizeEntry>,
+}
+
+impl SizeReport {
+    pub fn new(mut entries: Vec<SizeEntry>) -> Self {
+        entries.sort_by(|a, b| {
+            b.total_bytes()
+                .cmp(&a.total_bytes())
+                .then_with(|| a.name.cmp(&b.name))
+        });
+
+        SizeReport { entries }
+    }
+
+    /// The sizes the dev backend recorded while it added the procedures to the object
+    pub fn from_dev_backend(proc_sizes: &[roc_gen_dev::ProcSize]) -> Self {
+        Self::new(
+            proc_sizes
+                .iter()
+                .map(|size| SizeEntry {
+                    name: size.name.clone(),
+                    category: SizeCategory::of_symbol(&size.name),
+                    backend: CodeGenBackend::Assembly,
+                    code_bytes: size.code_bytes as u64,
+                    data_bytes: size.data_bytes as u64,
+                })
+                .collect(),
+        )
+    }
+
+    /// The sizes of the functions in an object file, e.g. the one LLVM emitted.
+    /// A function without a size in the symbol table, like on Mach-O,
+    /// extends to the next symbol in its section.
+    pub fn from_object(bytes: &[u8], backend: CodeGenBackend) -> Result<Self, object::Error> {
+        let file = object::File::parse(bytes)?;
+
+        let mut symbols: Vec<_> = file
+            .symbols()
+            .filter(|symbol| symbol.kind() == SymbolKind::Text && symbol.is_definition())
+            .filter_map(|symbol| {
+                let section = file.section_by_index(symbol.section_index()?).ok()?;
+                (section.kind() == SectionKind::Text).then_some((section, symbol))
+            })
+            .collect();
+        symbols.sort_by_key(|(section, symbol)| (section.index().0, symbol.address()));
+
+        let mut entries = Vec::with_capacity(symbols.len());
+        for (i, (section, symbol)) in symbols.iter().enumerate() {
+            let code_bytes = if symbol.size() > 0 {
+                symbol.size()
+            } else {
+                let next = match symbols.get(i + 1) {
+                    Some((next_section, next)) if next_section.index() == section.index() => {
+                        next.address()
+                    }
+                    _ => section.address() + section.size(),
+                };
+                next - symbol.address()
+            };
+            let name = symbol.name()?.to_string();
+
+            entries.push(SizeEntry {
+                category: SizeCategory::of_symbol(&name),
+                name,
+                backend,
+                code_bytes,
+                data_bytes: 0,
+            });
+        }
+
+        Ok(Self::new(entries))
+    }
+
+    /// The procedures of both reports, e.g. of two objects that are linked together
+    pub fn merge(self, other: SizeReport) -> Self {
+        Self::new(self.entries.into_iter().chain(other.entries).collect())
+    }
+
+    pub fn total_bytes(&self) -> u64 {
+        self.entries.iter().map(SizeEntry::total_bytes).sum()
+    }
+
+    /// The total size of every category that occurs, biggest first
+    pub fn category_totals(&self) -> Vec<(SizeCategory, u64)> {
+        let mut totals: Vec<(SizeCategory, u64)> = Vec::new();
+        for entry in self.entries.iter() {
+            match totals
+                .iter_mut()
+                .find(|(category, _)| *category == entry.category)
+            {
+                Some((_, total)) => *total += entry.total_bytes(),
+                None => totals.push((entry.category, entry.total_bytes())),
+            }
+        }
+        totals.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+        totals
+    }
+
+    /// A table with one row per procedure, followed by the totals of every category
+    pub fn table(&self) -> String {
+        let name_width = self
+            .entries
+            .iter()
+            .map(|entry| entry.name.len())
+            .chain(["proc".len()])
+            .max()
+            .unwrap_or_default();
+
+        let mut out = String::new();
+        writeln!(
+            out,
+            "{:<width$} {:>7} {:>10} {:>10} {:>10}",
+            "proc",
+            "backend",
+            "category",
+            "code",
+            "data",
+            width = name_width
+        )
+        .unwrap();
+        for entry in self.entries.iter() {
+            writeln!(
+                out,
+                "{:<width$} {:>7} {:>10} {:>10} {:>10}",
+                entry.name,
+                entry.backend.as_str(),
+                entry.category.as_str(),
+                entry.code_bytes,
+                entry.data_bytes,
+                width = name_width
+            )
+            .unwrap();
+        }
+
+        out.push('\n');
+        for (category, total) in self.category_totals() {
+            writeln!(out, "{:>10} {:>10}", category.as_str(), total).unwrap();
+        }
+        writeln!(out, "{:>10} {:>10}", "total", self.total_bytes()).unwrap();
+
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use object::write::{self, StandardSegment, SymbolSection};
+    use object::{Architecture, BinaryFormat, Endianness, SymbolFlags, SymbolScope};
+
+    #[test]
+    fn categories_of_symbols() {
+        use SizeCategory::*;
+
+        for (name, category) in [
+            ("#UserApp_main_1f", Proc),
+            ("Test_#help0_Inc_InLayout(U64)_0123456789abcdef", Inc),
+            ("Test_#help1_Reset_InLayout(U64)_0123456789abcdef", Dec),
+            ("Test_#help2_Eq_InLayout(STR)_0123456789abcdef", Eq),
+            ("#Attr_#inc_3", Inc),
+            ("#Attr_#dec_3_reset", Dec),
+            ("#Attr_#generic_rc_by_ref_2_dec", Dec),
+            ("#Attr_#generic_eq_by_ref_2", Eq),
+            ("_#UserApp_#lambda_0_zig_function_caller", Transform),
+            ("roc__mainForHost_1_exposed_generic", Wrapper),
+            ("_roc_alloc", Wrapper),
+            ("roc_builtins.list.map", Builtin),
+            ("__muloti4", Builtin),
+        ] {
+            assert_eq!(SizeCategory::of_symbol(name), category, "{}", name);
+        }
+    }
+
+    #[test]
+    fn function_sizes_add_up_to_the_text_section() {
+        let mut output =
+            write::Object::new(BinaryFormat::Elf, Architecture::X86_64, Endianness::Little);
+        let text = output.add_section(
+            output.segment_name(StandardSegment::Text).to_vec(),
+            b".text".to_vec(),
+            SectionKind::Text,
+        );
+
+        for (name, size) in [
+            ("roc__main_1_exposed", 12),
+            ("#UserApp_main_1", 40),
+            ("#Attr_#inc_1", 4),
+        ] {
+            let symbol = output.add_symbol(write::Symbol {
+                name: name.as_bytes().to_vec(),
+                value: 0,
+                size: 0,
+                kind: SymbolKind::Text,
+                scope: SymbolScope::Linkage,
+                weak: false,
+                section: SymbolSection::Section(text),
+                flags: SymbolFlags::None,
+            });
+            output.add_symbol_data(symbol, text, &vec![0x90; size], 1);
+        }
+        let bytes = output.write().unwrap();
+
+        let report = SizeReport::from_object(&bytes, CodeGenBackend::Llvm).unwrap();
+        let file = object::File::parse(&bytes[..]).unwrap();
+        let text_bytes: u64 = file
+            .sections()
+            .filter(|section| section.kind() == SectionKind::Text)
+            .map(|section| section.size())
+            .sum();
+
+        assert_eq!(report.total_bytes(), text_bytes);
+        assert_eq!(
+            report
+                .entries
+                .iter()
+                .map(|entry| (entry.name.as_str(), entry.code_bytes))
+                .collect::<Vec<_>>(),
+            [
+                ("#UserApp_main_1", 40),
+                ("roc__main_1_exposed", 12),
+                ("#Attr_#inc_1", 4)
+            ]
+        );
+        assert_eq!(
+            report.category_totals(),
+            [
+                (SizeCategory::Proc, 40),
+                (SizeCategory::Wrapper, 12),
+                (SizeCategory::Inc, 4)
+            ]
+        );
+    }
+}
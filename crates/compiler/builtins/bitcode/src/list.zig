@@ -6,7 +6,10 @@ const math = std.math;
 
 const expect = std.testing.expect;
 
+// A bool is a byte that is exactly 0 or 1, see `roc_builtins::repr` in the compiler.
 const EqFn = fn (?[*]u8, ?[*]u8) callconv(.C) bool;
+// Not a bool either: the byte is the tag id of a `utils.Ordering`.
+// Sorting passes pointers into the middle of the list's buffer, see `WrapperPointers::Interior`.
 const CompareFn = fn (?[*]u8, ?[*]u8, ?[*]u8) callconv(.C) u8;
 const Opaque = ?[*]u8;
 
@@ -236,6 +236,54 @@ fn bytesToU32(arg: RocList, position: usize) u32 {
     return @bitCast(u32, [_]u8{ bytes[position], bytes[position + 1], bytes[position + 2], bytes[position + 3] });
 }
 
+const HEX_DIGITS = "0123456789abcdef";
+
+// Num.toHexStr : U64 -> Str
+pub fn toHexStrC(value: u64) callconv(.C) RocStr {
+    return @call(.{ .modifier = always_inline }, toHexStr, .{value});
+}
+
+fn toHexStr(value: u64) RocStr {
+    var buf: [16]u8 = undefined;
+
+    var i: usize = 0;
+    while (i < buf.len) : (i += 1) {
+        const shift = @intCast(u6, 60 - 4 * i);
+        buf[i] = HEX_DIGITS[@intCast(usize, (value >> shift) & 0xf)];
+    }
+
+    return RocStr.init(&buf, buf.len);
+}
+
+// Str.toU64Hex : Str -> Result U64 [InvalidHexStr]
+pub fn parseHexU64C(string: RocStr) callconv(.C) NumParseResult(u64) {
+    return @call(.{ .modifier = always_inline }, parseHexU64, .{string});
+}
+
+fn parseHexU64(string: RocStr) NumParseResult(u64) {
+    const invalid = NumParseResult(u64){ .errorcode = 1, .value = 0 };
+    const bytes = string.asSlice();
+
+    // 16 hex digits fill a u64; anything longer would overflow
+    if (bytes.len == 0 or bytes.len > 16) {
+        return invalid;
+    }
+
+    var value: u64 = 0;
+    for (bytes) |byte| {
+        const digit: u64 = switch (byte) {
+            '0'...'9' => byte - '0',
+            'a'...'f' => byte - 'a' + 10,
+            'A'...'F' => byte - 'A' + 10,
+            else => return invalid,
+        };
+
+        value = (value << 4) | digit;
+    }
+
+    return .{ .errorcode = 0, .value = value };
+}
+
 fn addWithOverflow(comptime T: type, self: T, other: T) WithOverflow(T) {
     switch (@typeInfo(T)) {
         .Int => {
@@ -460,3 +508,32 @@ pub fn exportMulOrPanic(comptime T: type, comptime W: type, comptime name: []con
     }.func;
     @export(f, .{ .name = name ++ @typeName(T), .linkage = .Strong });
 }
+
+test "toHexStr: zero and max" {
+    const zero = toHexStr(0);
+    defer RocStr.deinit(zero);
+    try std.testing.expectEqualStrings("0000000000000000", zero.asSlice());
+
+    const max = toHexStr(std.math.maxInt(u64));
+    defer RocStr.deinit(max);
+    try std.testing.expectEqualStrings("ffffffffffffffff", max.asSlice());
+}
+
+test "parseHexU64: odd and even lengths" {
+    const odd = RocStr.fromSlice("aBc");
+    defer RocStr.deinit(odd);
+    try std.testing.expectEqual(NumParseResult(u64){ .errorcode = 0, .value = 0xabc }, parseHexU64(odd));
+
+    const even = RocStr.fromSlice("00ff");
+    defer RocStr.deinit(even);
+    try std.testing.expectEqual(NumParseResult(u64){ .errorcode = 0, .value = 0xff }, parseHexU64(even));
+}
+
+test "parseHexU64: invalid input" {
+    const inputs = [_][]const u8{ "", "12g4", "0x12", "10000000000000000" };
+    for (inputs) |input| {
+        const str = RocStr.fromSlice(input);
+        defer RocStr.deinit(str);
+        try std.testing.expectEqual(@as(u8, 1), parseHexU64(str).errorcode);
+    }
+}
@@ -158,11 +158,20 @@ pub const RocStr = extern struct {
     pub fn reallocate(
         self: RocStr,
         new_length: usize,
+    ) RocStr {
+        return self.reallocateWithMode(new_length, .Immutable);
+    }
+
+    /// With `.InPlace`, the caller knows that `self` is unique, so the refcount is not checked
+    pub fn reallocateWithMode(
+        self: RocStr,
+        new_length: usize,
+        update_mode: UpdateMode,
     ) RocStr {
         const element_width = 1;
         const old_capacity = self.getCapacity();
 
-        if (self.isSmallStr() or !self.isUnique()) {
+        if (self.isSmallStr() or (update_mode == .Immutable and !self.isUnique())) {
             return self.reallocateFresh(new_length);
         }
 
@@ -1618,10 +1627,15 @@ test "endsWith: hello world ends with world" {
 
 // Str.concat
 pub fn strConcatC(arg1: RocStr, arg2: RocStr) callconv(.C) RocStr {
-    return @call(.{ .modifier = always_inline }, strConcat, .{ arg1, arg2 });
+    return @call(.{ .modifier = always_inline }, strConcat, .{ arg1, arg2, .Immutable });
 }
 
-fn strConcat(arg1: RocStr, arg2: RocStr) RocStr {
+// Str.concat, when the compiler knows that the first argument is unique
+pub fn strConcatInPlaceC(arg1: RocStr, arg2: RocStr) callconv(.C) RocStr {
+    return @call(.{ .modifier = always_inline }, strConcat, .{ arg1, arg2, .InPlace });
+}
+
+fn strConcat(arg1: RocStr, arg2: RocStr, update_mode: UpdateMode) RocStr {
     // NOTE: we don't special-case the first argument being empty. That is because it is owned and
     // may have sufficient capacity to store the rest of the list.
     if (arg2.isEmpty()) {
@@ -1630,7 +1644,7 @@ fn strConcat(arg1: RocStr, arg2: RocStr) RocStr {
     } else {
         const combined_length = arg1.len() + arg2.len();
 
-        var result = arg1.reallocate(combined_length);
+        var result = arg1.reallocateWithMode(combined_length, update_mode);
         @memcpy(result.asU8ptrMut() + arg1.len(), arg2.asU8ptr(), arg2.len());
 
         return result;
@@ -1659,13 +1673,25 @@ test "RocStr.concat: small concat small" {
         roc_str3.deinit();
     }
 
-    const result = strConcat(roc_str1, roc_str2);
+    const result = strConcat(roc_str1, roc_str2, .Immutable);
 
     defer result.deinit();
 
     try expect(roc_str3.eq(result));
 }
 
+test "RocStr.concat: in place and immutable agree" {
+    const big = "a string that is too long to be small";
+
+    var immutable = strConcat(RocStr.init(big, big.len), RocStr.init("!", 1), .Immutable);
+    defer immutable.deinit();
+    var in_place = strConcat(RocStr.init(big, big.len), RocStr.init("!", 1), .InPlace);
+    defer in_place.deinit();
+
+    try expect(immutable.eq(in_place));
+    try expectEqual(big.len + 1, in_place.len());
+}
+
 pub const RocListStr = extern struct {
     list_elements: ?[*]RocStr,
     list_length: usize,
@@ -2657,6 +2683,127 @@ test "capacity: big string" {
     try expect(data.getCapacity() >= data_bytes.len);
 }
 
+// Str.nfcNormalize
+// Composes a letter and the combining mark after it into a single scalar, like NFC does.
+// So far that covers the letters of Latin-1 with a grave, acute, circumflex, tilde, or
+// diaeresis; everything else is returned as it is.
+pub fn strNfcNormalize(string: RocStr) callconv(.C) RocStr {
+    const bytes = string.asSlice();
+
+    var composed_len: usize = 0;
+    var i: usize = 0;
+    while (i < bytes.len) {
+        if (composeLatin1(bytes, i) != null) {
+            i += 3;
+            composed_len += 2;
+        } else {
+            i += 1;
+            composed_len += 1;
+        }
+    }
+
+    if (composed_len == bytes.len) {
+        return string;
+    }
+
+    var result = RocStr.allocate(composed_len);
+    const result_ptr = result.asU8ptrMut();
+
+    var j: usize = 0;
+    i = 0;
+    while (i < bytes.len) {
+        if (composeLatin1(bytes, i)) |scalar| {
+            // every scalar from U+00C0 to U+00FF takes two bytes
+            result_ptr[j] = 0xC3;
+            result_ptr[j + 1] = 0x80 | (scalar - 0xC0);
+            i += 3;
+            j += 2;
+        } else {
+            result_ptr[j] = bytes[i];
+            i += 1;
+            j += 1;
+        }
+    }
+
+    string.decref();
+
+    return result;
+}
+
+const Latin1Composition = struct {
+    // the last byte of the mark; U+0300 to U+0308 are all 0xCC followed by 0x80 to 0x88
+    mark: u8,
+    letters: []const u8,
+    composed: []const u8,
+};
+
+const latin1_compositions = [_]Latin1Composition{
+    .{ .mark = 0x80, .letters = "AEIOUaeiou", .composed = "\xC0\xC8\xCC\xD2\xD9\xE0\xE8\xEC\xF2\xF9" },
+    .{ .mark = 0x81, .letters = "AEIOUYaeiouy", .composed = "\xC1\xC9\xCD\xD3\xDA\xDD\xE1\xE9\xED\xF3\xFA\xFD" },
+    .{ .mark = 0x82, .letters = "AEIOUaeiou", .composed = "\xC2\xCA\xCE\xD4\xDB\xE2\xEA\xEE\xF4\xFB" },
+    .{ .mark = 0x83, .letters = "ANOano", .composed = "\xC3\xD1\xD5\xE3\xF1\xF5" },
+    .{ .mark = 0x88, .letters = "AEIOUaeiouy", .composed = "\xC4\xCB\xCF\xD6\xDC\xE4\xEB\xEF\xF6\xFC\xFF" },
+};
+
+// The Latin-1 scalar that the letter at `index` and the mark after it compose into, if any
+fn composeLatin1(bytes: []const u8, index: usize) ?u8 {
+    if (index + 2 >= bytes.len or bytes[index + 1] != 0xCC) {
+        return null;
+    }
+
+    for (latin1_compositions) |composition| {
+        if (composition.mark == bytes[index + 2]) {
+            const letter = std.mem.indexOfScalar(u8, composition.letters, bytes[index]) orelse return null;
+            return composition.composed[letter];
+        }
+    }
+
+    return null;
+}
+
+test "strNfcNormalize: decomposed e acute" {
+    const original_bytes = "caf\u{0065}\u{0301}";
+    const original = RocStr.init(original_bytes, original_bytes.len);
+
+    const expected_bytes = "caf\u{00E9}";
+    const expected = RocStr.init(expected_bytes, expected_bytes.len);
+    defer expected.deinit();
+
+    const normalized = strNfcNormalize(original);
+    defer normalized.deinit();
+
+    try expect(normalized.eq(expected));
+}
+
+test "strNfcNormalize: precomposed e acute" {
+    const original_bytes = "caf\u{00E9}";
+    const original = RocStr.init(original_bytes, original_bytes.len);
+
+    const expected = RocStr.init(original_bytes, original_bytes.len);
+    defer expected.deinit();
+
+    const normalized = strNfcNormalize(original);
+    defer normalized.deinit();
+
+    try expect(normalized.eq(expected));
+}
+
+test "strNfcNormalize: large string" {
+    const original_bytes = "e\u{0301}le\u{0300}ve, n\u{0303}o, u\u{0308}ber, and a mark on its own: \u{0301}";
+    const original = RocStr.init(original_bytes, original_bytes.len);
+
+    try expect(!original.isSmallStr());
+
+    const expected_bytes = "\u{00E9}l\u{00E8}ve, \u{00F1}o, \u{00FC}ber, and a mark on its own: \u{0301}";
+    const expected = RocStr.init(expected_bytes, expected_bytes.len);
+    defer expected.deinit();
+
+    const normalized = strNfcNormalize(original);
+    defer normalized.deinit();
+
+    try expect(normalized.eq(expected));
+}
+
 pub fn appendScalar(string: RocStr, scalar_u32: u32) callconv(.C) RocStr {
     const scalar = @intCast(u21, scalar_u32);
     const width = std.unicode.utf8CodepointSequenceLength(scalar) catch unreachable;
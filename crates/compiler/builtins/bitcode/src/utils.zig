@@ -1,6 +1,7 @@
 const std = @import("std");
 const always_inline = std.builtin.CallOptions.Modifier.always_inline;
 const Monotonic = std.builtin.AtomicOrder.Monotonic;
+const SourceInfo = @import("panic.zig").SourceInfo;
 
 pub fn WithOverflow(comptime T: type) type {
     return extern struct { value: T, has_overflowed: bool };
@@ -74,10 +75,14 @@ fn testing_roc_dealloc(c_ptr: *anyopaque, _: u32) callconv(.C) void {
     std.testing.allocator.destroy(ptr);
 }
 
-fn testing_roc_panic(c_ptr: *anyopaque, tag_id: u32) callconv(.C) void {
+fn testing_roc_panic(c_ptr: *anyopaque, tag_id: u32, source: ?*const SourceInfo) callconv(.C) void {
     _ = c_ptr;
     _ = tag_id;
 
+    if (source) |info| {
+        std.debug.panic("Roc panicked at {s}:{d}:{d}", .{ info.file, info.line, info.column });
+    }
+
     @panic("Roc panicked");
 }
 
@@ -107,9 +112,10 @@ pub fn memcpy(dst: [*]u8, src: [*]u8, size: usize) void {
 
 // indirection because otherwise zig creates an alias to the panic function which our LLVM code
 // does not know how to deal with
-pub fn test_panic(c_ptr: *anyopaque, alignment: u32) callconv(.C) void {
+pub fn test_panic(c_ptr: *anyopaque, alignment: u32, source: ?*const SourceInfo) callconv(.C) void {
     _ = c_ptr;
     _ = alignment;
+    _ = source;
     // const cstr = @ptrCast([*:0]u8, c_ptr);
 
     // const stderr = std.io.getStdErr().writer();
@@ -122,6 +128,7 @@ pub const Inc = fn (?[*]u8) callconv(.C) void;
 pub const IncN = fn (?[*]u8, u64) callconv(.C) void;
 pub const Dec = fn (?[*]u8) callconv(.C) void;
 
+// Keep these and `allocateWithRefcount` in sync with `roc_builtins::rc_layout`
 const REFCOUNT_MAX_ISIZE: isize = 0;
 pub const REFCOUNT_ONE_ISIZE: isize = std.math.minInt(isize);
 pub const REFCOUNT_ONE: usize = @bitCast(usize, REFCOUNT_ONE_ISIZE);
@@ -333,6 +340,8 @@ pub fn unsafeReallocate(
     return new_source;
 }
 
+// What a compare wrapper returns. The wrapper translates the tag id of the Roc `Ordering` to it.
+// Keep in sync with `roc_builtins::repr::CompareResult` in the compiler.
 pub const Ordering = enum(u8) {
     EQ = 0,
     GT = 1,
@@ -1,5 +1,5 @@
 interface Box
-    exposes [box, unbox]
+    exposes [box, unbox, map]
     imports []
 
 ## Allocate a value on the heap. Boxing is an expensive process as it copies
@@ -15,11 +15,8 @@ box : a -> Box a
 ##     expect Box.unbox (Box.box "Stack Faster") == "Stack Faster"
 unbox : Box a -> a
 
-# # we'd need reset/reuse for box for this to be efficient
-# # that is currently not implemented
-# map : Box a, (a -> b) -> Box b
-# map = \boxed, transform =
-#     boxed
-#         |> Box.unbox
-#         |> transform
-#         |> Box.box
+## Transforms the boxed value. When nothing else refers to the box and the new
+## value has the same size, the box is reused instead of allocating a new one.
+##
+##     expect Box.unbox (Box.map (Box.box 41) \n -> n + 1) == 42
+map : Box a, (a -> b) -> Box b
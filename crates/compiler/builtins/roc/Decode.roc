@@ -28,11 +28,13 @@ interface Decode
         fromBytesPartial,
         fromBytes,
         mapResult,
+        numFromBytesLe,
     ]
     imports [
         List,
         Result.{ Result },
         Num.{
+            Num,
             U8,
             U16,
             U32,
@@ -46,6 +48,7 @@ interface Decode
             F32,
             F64,
             Dec,
+            Nat,
         },
         Bool.{ Bool },
     ]
@@ -100,3 +103,21 @@ fromBytes = \bytes, fmt ->
 
 mapResult : DecodeResult a, (a -> b) -> DecodeResult b
 mapResult = \{ result, rest }, mapper -> { result: Result.map result mapper, rest }
+
+numFromBytesLeLowlevel : List U8, Nat -> { aresult : Num *, binbounds : Bool }
+
+## Reads a number from the bytes of a list, least significant byte first, starting at the
+## given index. This is the inverse of [Encode.appendNumLe].
+##
+## >>> Decode.numFromBytesLe [0x02, 0x01] 0 # Ok 0x0102u16
+##
+## It is an `Err TooShort` when the list runs out of bytes before the number does.
+numFromBytesLe : List U8, Nat -> Result (Num a) DecodeError
+numFromBytesLe = \bytes, index ->
+    result : { aresult : Num a, binbounds : Bool }
+    result = numFromBytesLeLowlevel bytes index
+
+    if result.binbounds then
+        Ok result.aresult
+    else
+        Err TooShort
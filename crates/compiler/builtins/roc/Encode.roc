@@ -26,9 +26,11 @@ interface Encode
         appendWith,
         append,
         toBytes,
+        appendNumLe,
     ]
     imports [
         Num.{
+            Num,
             U8,
             U16,
             U32,
@@ -82,3 +84,9 @@ append = \lst, val, fmt -> appendWith lst (toEncoder val) fmt
 
 toBytes : val, fmt -> List U8 | val has Encoding, fmt has EncoderFormatting
 toBytes = \val, fmt -> appendWith [] (toEncoder val) fmt
+
+## Appends the bytes of a number to a list, least significant byte first, e.g. for
+## formats that encode numbers in binary.
+##
+## >>> Encode.appendNumLe [] 0x0102u16 # [0x02, 0x01]
+appendNumLe : List U8, Num * -> List U8
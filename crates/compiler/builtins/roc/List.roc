@@ -349,11 +349,6 @@ reverseHelp = \list, left, right ->
 ##
 ## >>> List.join []
 join : List (List a) -> List a
-join = \lists ->
-    totalLength =
-        List.walk lists 0 (\state, list -> state + List.len list)
-
-    List.walk lists (List.withCapacity totalLength) (\state, list -> List.concat state list)
 
 contains : List a, a -> Bool | a has Eq
 contains = \list, needle ->
@@ -457,12 +452,8 @@ walkFromUntil = \list, index, state, func ->
         Break new -> new
 
 sum : List (Num a) -> Num a
-sum = \list ->
-    List.walk list 0 Num.add
 
 product : List (Num a) -> Num a
-product = \list ->
-    List.walk list 1 Num.mul
 
 ## Run the given predicate on each element of the list, returning `Bool.true` if
 ## any of the elements satisfy it.
@@ -869,37 +860,21 @@ dropAt : List elem, Nat -> List elem
 
 min : List (Num a) -> Result (Num a) [ListWasEmpty]
 min = \list ->
-    when List.first list is
-        Ok initial ->
-            Ok (minHelp list initial)
-
-        Err ListWasEmpty ->
-            Err ListWasEmpty
-
-minHelp : List (Num a), Num a -> Num a
-minHelp = \list, initial ->
-    List.walk list initial \bestSoFar, current ->
-        if current < bestSoFar then
-            current
-        else
-            bestSoFar
+    if List.isEmpty list then
+        Err ListWasEmpty
+    else
+        Ok (minUnsafe list)
+
+minUnsafe : List (Num a) -> Num a
 
 max : List (Num a) -> Result (Num a) [ListWasEmpty]
 max = \list ->
-    when List.first list is
-        Ok initial ->
-            Ok (maxHelp list initial)
-
-        Err ListWasEmpty ->
-            Err ListWasEmpty
-
-maxHelp : List (Num a), Num a -> Num a
-maxHelp = \list, initial ->
-    List.walk list initial \bestSoFar, current ->
-        if current > bestSoFar then
-            current
-        else
-            bestSoFar
+    if List.isEmpty list then
+        Err ListWasEmpty
+    else
+        Ok (maxUnsafe list)
+
+maxUnsafe : List (Num a) -> Num a
 
 ## Like [List.map], except the transformation function wraps the return value
 ## in a list. At the end, all the lists get joined together into one list.
@@ -70,6 +70,7 @@ interface Num
         powInt,
         addWrap,
         addChecked,
+        addWithOverflow,
         addSaturated,
         bitwiseAnd,
         bitwiseXor,
@@ -91,6 +92,13 @@ interface Num
         divTrunc,
         divTruncChecked,
         toStr,
+        toHexStr,
+        gcd,
+        lcm,
+        toF32Bits,
+        toF64Bits,
+        fromF32Bits,
+        fromF64Bits,
         isMultipleOf,
         minI8,
         maxI8,
@@ -506,6 +514,48 @@ Dec : Num (FloatingPoint Decimal)
 ##
 ## To get strings in hexadecimal, octal, or binary format, use `Num.format`.
 toStr : Num * -> Str
+
+## Convert a [U64] to a [Str] of 16 lowercase hexadecimal digits, including leading zeros.
+##
+## >>> Num.toHexStr 255
+##
+## >>> Num.toHexStr 0
+toHexStr : U64 -> Str
+
+## The greatest common divisor of two numbers: the largest number that divides both of them.
+##
+## >>> Num.gcd 12 8
+##
+## If one of them is zero, the result is the other one.
+gcd : U64, U64 -> U64
+
+## The least common multiple of two numbers: the smallest number that both of them divide.
+##
+## >>> Num.lcm 12 8
+##
+## If one of them is zero, the result is zero.
+## Crashes if the result does not fit in a [U64].
+lcm : U64, U64 -> U64
+
+## The bits of a [F32], unchanged, as a [U32]. This keeps the sign and payload of a *NaN*.
+##
+## >>> Num.toF32Bits 1.5
+toF32Bits : F32 -> U32
+
+## The bits of a [F64], unchanged, as a [U64]. This keeps the sign and payload of a *NaN*.
+##
+## >>> Num.toF64Bits 1.5
+toF64Bits : F64 -> U64
+
+## The [F32] with the given bits; the inverse of [Num.toF32Bits].
+##
+## >>> Num.fromF32Bits 0x3FC00000
+fromF32Bits : U32 -> F32
+
+## The [F64] with the given bits; the inverse of [Num.toF64Bits].
+##
+## >>> Num.fromF64Bits 0x3FF8000000000000
+fromF64Bits : U64 -> F64
 intCast : Int a -> Int b
 
 bytesToU16Lowlevel : List U8, Nat -> U16
@@ -953,6 +1003,14 @@ addChecked = \a, b ->
 
 addCheckedLowlevel : Num a, Num a -> { b : Bool, a : Num a }
 
+## Add two [I64]s, wrapping around like [Num.addWrap], and also say whether the
+## addition overflowed.
+##
+## >>> Num.addWithOverflow Num.maxI64 1
+##
+## gives `{ value: Num.minI64, didOverflow: Bool.true }`.
+addWithOverflow : I64, I64 -> { value : I64, didOverflow : Bool }
+
 subWrap : Int range, Int range -> Int range
 
 ## Subtract two numbers, clamping on the minimum representable number rather
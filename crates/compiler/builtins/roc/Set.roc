@@ -113,11 +113,26 @@ union = \@Set dict1, @Set dict2 ->
 
 intersection : Set k, Set k -> Set k | k has Hash & Eq
 intersection = \@Set dict1, @Set dict2 ->
-    Dict.keepShared dict1 dict2 |> @Set
+    # look up the keys of the smaller set in the bigger one
+    if Dict.len dict1 <= Dict.len dict2 then
+        Dict.keepShared dict1 dict2 |> @Set
+    else
+        Dict.keepShared dict2 dict1 |> @Set
 
 difference : Set k, Set k -> Set k | k has Hash & Eq
 difference = \@Set dict1, @Set dict2 ->
-    Dict.removeAll dict1 dict2 |> @Set
+    if Dict.len dict1 < Dict.len dict2 then
+        # keep the keys of the smaller set that the bigger one does not have
+        kept =
+            Dict.walk dict1 (Dict.withCapacity (Dict.len dict1)) \state, k, v ->
+                if Dict.contains dict2 k then
+                    state
+                else
+                    Dict.insert state k v
+
+        @Set kept
+    else
+        Dict.removeAll dict1 dict2 |> @Set
 
 walk : Set k, state, (state, k -> state) -> state | k has Hash & Eq
 walk = \@Set dict, state, step ->
@@ -103,6 +103,7 @@ interface Str
         toI16,
         toU8,
         toI8,
+        toU64Hex,
         toScalars,
         replaceEach,
         replaceFirst,
@@ -470,6 +471,22 @@ toU8 = \string -> strToNumHelp string
 toI8 : Str -> Result I8 [InvalidNumStr]
 toI8 = \string -> strToNumHelp string
 
+## Encode a [Str] of hexadecimal digits to an unsigned [U64] integer. Upper and
+## lowercase digits are both accepted, but a `0x` prefix is not.
+##
+##     expect Str.toU64Hex "ff" == Ok 255u64
+##     expect Str.toU64Hex "00FF" == Ok 255u64
+##     expect Str.toU64Hex "0xff" == Err InvalidHexStr
+##     expect Str.toU64Hex "" == Err InvalidHexStr
+toU64Hex : Str -> Result U64 [InvalidHexStr]
+toU64Hex = \string ->
+    result = toU64HexLowlevel string
+
+    if result.berrorcode == 0 then
+        Ok result.aresult
+    else
+        Err InvalidHexStr
+
 ## Get the byte at the given index, without performing a bounds check.
 getUnsafe : Str, Nat -> U8
 
@@ -784,6 +801,8 @@ walkScalarsUntilHelp = \string, state, step, index, length ->
 
 strToNum : Str -> { berrorcode : U8, aresult : Num * }
 
+toU64HexLowlevel : Str -> { berrorcode : U8, aresult : U64 }
+
 strToNumHelp : Str -> Result (Num a) [InvalidNumStr]
 strToNumHelp = \string ->
     result : { berrorcode : U8, aresult : Num a }
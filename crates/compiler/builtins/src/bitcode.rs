@@ -223,6 +223,57 @@ impl IntWidth {
             Self::U128 => "u128",
         }
     }
+
+    /// The smallest value of this width
+    pub const fn min_value(&self) -> i128 {
+        use IntWidth::*;
+
+        match self {
+            U8 | U16 | U32 | U64 | U128 => 0,
+            I8 => i8::MIN as i128,
+            I16 => i16::MIN as i128,
+            I32 => i32::MIN as i128,
+            I64 => i64::MIN as i128,
+            I128 => i128::MIN,
+        }
+    }
+
+    /// The largest value of this width
+    pub const fn max_value(&self) -> u128 {
+        use IntWidth::*;
+
+        match self {
+            U8 => u8::MAX as u128,
+            U16 => u16::MAX as u128,
+            U32 => u32::MAX as u128,
+            U64 => u64::MAX as u128,
+            U128 => u128::MAX,
+            I8 => i8::MAX as u128,
+            I16 => i16::MAX as u128,
+            I32 => i32::MAX as u128,
+            I64 => i64::MAX as u128,
+            I128 => i128::MAX as u128,
+        }
+    }
+
+    /// The bounds a value of this width has to be checked against before it is converted to
+    /// `target`, as `(min, max)`. A bound is `None` if every value of this width is within it.
+    ///
+    /// Both backends go by this for `Num.toXChecked`, so they agree on what is out of bounds.
+    pub const fn conversion_bounds(&self, target: IntWidth) -> (Option<i128>, Option<u128>) {
+        let min = if self.min_value() < target.min_value() {
+            Some(target.min_value())
+        } else {
+            None
+        };
+        let max = if self.max_value() > target.max_value() {
+            Some(target.max_value())
+        } else {
+            None
+        };
+
+        (min, max)
+    }
 }
 
 impl Index<DecWidth> for IntrinsicName {
@@ -358,10 +409,12 @@ pub const NUM_MUL_CHECKED_FLOAT: IntrinsicName =
 
 pub const NUM_BYTES_TO_U16: &str = "roc_builtins.num.bytes_to_u16";
 pub const NUM_BYTES_TO_U32: &str = "roc_builtins.num.bytes_to_u32";
+pub const NUM_TO_HEX_STR: &str = "roc_builtins.num.to_hex_str";
 
 pub const STR_INIT: &str = "roc_builtins.str.init";
 pub const STR_COUNT_SEGMENTS: &str = "roc_builtins.str.count_segments";
 pub const STR_CONCAT: &str = "roc_builtins.str.concat";
+pub const STR_CONCAT_IN_PLACE: &str = "roc_builtins.str.concat_in_place";
 pub const STR_JOIN_WITH: &str = "roc_builtins.str.joinWith";
 pub const STR_SPLIT: &str = "roc_builtins.str.str_split";
 pub const STR_TO_SCALARS: &str = "roc_builtins.str.to_scalars";
@@ -392,6 +445,8 @@ pub const STR_GET_SCALAR_UNSAFE: &str = "roc_builtins.str.get_scalar_unsafe";
 pub const STR_CLONE_TO: &str = "roc_builtins.str.clone_to";
 pub const STR_WITH_CAPACITY: &str = "roc_builtins.str.with_capacity";
 pub const STR_GRAPHEMES: &str = "roc_builtins.str.graphemes";
+pub const STR_TO_U64_HEX: &str = "roc_builtins.str.to_u64_hex";
+pub const STR_NFC_NORMALIZE: &str = "roc_builtins.str.nfc_normalize";
 
 pub const LIST_MAP: &str = "roc_builtins.list.map";
 pub const LIST_MAP2: &str = "roc_builtins.list.map2";
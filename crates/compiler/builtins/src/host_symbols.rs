@@ -0,0 +1,64 @@
+//! The names of the functions that a host provides to allocate, free, and crash. The zig builtins
+//! always call the standard names, so other names only change the calls that a backend generates
+//! itself. A backend can also define the standard names as aliases of the others, so the builtins
+//! end up in the same functions.
+
+/// The names of the allocator and of the function that crashes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HostSymbols {
+    pub alloc: &'static str,
+    pub realloc: &'static str,
+    pub dealloc: &'static str,
+    pub panic: &'static str,
+}
+
+impl HostSymbols {
+    /// The names that the zig builtins call
+    pub const STANDARD: Self = HostSymbols {
+        alloc: "roc_alloc",
+        realloc: "roc_realloc",
+        dealloc: "roc_dealloc",
+        panic: "roc_panic",
+    };
+
+    /// The standard name and the name in `self` of every function that `self` renames
+    pub fn renamed(&self) -> impl Iterator<Item = (&'static str, &'static str)> {
+        let standard = Self::STANDARD;
+
+        [
+            (standard.alloc, self.alloc),
+            (standard.realloc, self.realloc),
+            (standard.dealloc, self.dealloc),
+            (standard.panic, self.panic),
+        ]
+        .into_iter()
+        .filter(|(standard, name)| standard != name)
+    }
+}
+
+impl Default for HostSymbols {
+    fn default() -> Self {
+        Self::STANDARD
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_overridden_names_are_renamed() {
+        assert_eq!(HostSymbols::default().renamed().count(), 0);
+
+        let symbols = HostSymbols {
+            alloc: "arena_alloc",
+            panic: "host_crash",
+            ..HostSymbols::STANDARD
+        };
+        let renamed: Vec<_> = symbols.renamed().collect();
+        assert_eq!(
+            renamed,
+            [("roc_alloc", "arena_alloc"), ("roc_panic", "host_crash")]
+        );
+    }
+}
@@ -3,4 +3,7 @@
 // See github.com/roc-lang/roc/issues/800 for discussion of the large_enum_variant check.
 #![allow(clippy::large_enum_variant)]
 pub mod bitcode;
+pub mod host_symbols;
+pub mod rc_layout;
+pub mod repr;
 pub mod roc;
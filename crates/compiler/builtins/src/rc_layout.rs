@@ -0,0 +1,83 @@
+//! Where a heap allocation keeps its refcount, and which values the refcount takes. The zig
+//! builtins allocate and free this way (see `allocateWithRefcount` in `utils.zig`), and every
+//! backend reads and writes refcounts and lays out static data the same way, so none of this may
+//! change on one side only.
+//!
+//! An allocation starts with [allocation_padding] bytes, followed by the data. Pointers to the
+//! allocation point at the data, and the refcount is the word right before it.
+use roc_target::PtrWidth;
+
+/// The refcount of a value in static data, like a string literal. Nothing increments or
+/// decrements it, so the value is never freed. Zig calls it `REFCOUNT_MAX_ISIZE`.
+pub const REFCOUNT_CONSTANT_SENTINEL: i64 = 0;
+
+/// The refcount of a value with exactly one reference. Refcounts count up from the smallest
+/// `isize`, so that [REFCOUNT_CONSTANT_SENTINEL] is as far away as it gets.
+pub const fn refcount_one(ptr_width: PtrWidth) -> i64 {
+    match ptr_width {
+        PtrWidth::Bytes4 => i32::MIN as i64,
+        PtrWidth::Bytes8 => i64::MIN,
+    }
+}
+
+/// Where the refcount is, in bytes from the pointer to the data
+pub const fn refcount_offset(ptr_width: PtrWidth) -> i32 {
+    -(ptr_width as i32)
+}
+
+/// The bytes before the data of an allocation, for data with `alignment`. There is room for the
+/// refcount, and the data is aligned as long as the allocation is.
+pub const fn allocation_padding(ptr_width: PtrWidth, alignment: u32) -> u32 {
+    let ptr_bytes = ptr_width as u32;
+
+    if alignment > ptr_bytes {
+        alignment
+    } else {
+        ptr_bytes
+    }
+}
+
+/// A [REFCOUNT_CONSTANT_SENTINEL] refcount as it is stored in static data on a little endian target
+pub const fn constant_refcount_le_bytes(ptr_width: PtrWidth) -> &'static [u8] {
+    const BYTES4: [u8; 4] = (REFCOUNT_CONSTANT_SENTINEL as i32).to_le_bytes();
+    const BYTES8: [u8; 8] = REFCOUNT_CONSTANT_SENTINEL.to_le_bytes();
+
+    match ptr_width {
+        PtrWidth::Bytes4 => &BYTES4,
+        PtrWidth::Bytes8 => &BYTES8,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // These are what `utils.zig` has. They are spelled out, so that changing either side fails.
+
+    #[test]
+    fn refcount_values_match_zig() {
+        // `REFCOUNT_MAX_ISIZE`
+        assert_eq!(REFCOUNT_CONSTANT_SENTINEL, 0);
+
+        // `REFCOUNT_ONE_ISIZE`, which is `std.math.minInt(isize)`
+        assert_eq!(refcount_one(PtrWidth::Bytes4), -0x8000_0000);
+        assert_eq!(refcount_one(PtrWidth::Bytes8), -0x8000_0000_0000_0000);
+
+        assert_eq!(constant_refcount_le_bytes(PtrWidth::Bytes4), [0; 4]);
+        assert_eq!(constant_refcount_le_bytes(PtrWidth::Bytes8), [0; 8]);
+    }
+
+    #[test]
+    fn refcount_position_matches_zig() {
+        // `data_ptr - ptr_width` in `allocateWithRefcount`
+        assert_eq!(refcount_offset(PtrWidth::Bytes4), -4);
+        assert_eq!(refcount_offset(PtrWidth::Bytes8), -8);
+
+        // `std.math.max(ptr_width, element_alignment)` in `allocateWithRefcount`
+        assert_eq!(allocation_padding(PtrWidth::Bytes8, 1), 8);
+        assert_eq!(allocation_padding(PtrWidth::Bytes8, 8), 8);
+        assert_eq!(allocation_padding(PtrWidth::Bytes8, 16), 16);
+        assert_eq!(allocation_padding(PtrWidth::Bytes4, 1), 4);
+        assert_eq!(allocation_padding(PtrWidth::Bytes4, 8), 8);
+    }
+}
@@ -0,0 +1,52 @@
+//! How values are represented where they cross between the code the backends generate and the
+//! zig builtins. Both backends follow these, so that zig, LLVM, and the dev backend all read
+//! a value the same way, whichever of them made it.
+use crate::bitcode::IntWidth;
+
+/// A `Bool` is handed over as a byte that is exactly [BOOL_FALSE] or [BOOL_TRUE].
+/// LLVM works with an `i1`, which is zero extended to this width where zig gets to see it.
+/// The dev backend only ever puts 0 or 1 in a register, so it zero extends the byte a zig
+/// builtin returns, above which the register holds whatever the builtin left there.
+pub const BOOL_WIDTH: IntWidth = IntWidth::U8;
+pub const BOOL_FALSE: u8 = 0;
+pub const BOOL_TRUE: u8 = 1;
+
+/// What a compare wrapper returns to `listSortWith`, which is a byte too, but not a `Bool`.
+/// See `utils.Ordering` in zig. The comparator returns the tag id of a `[LT, EQ, GT]`, which the
+/// wrapper translates to these values, so zig does not depend on how tag ids are numbered.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareResult {
+    Eq = 0,
+    Gt = 1,
+    Lt = 2,
+}
+
+pub const COMPARE_RESULT_WIDTH: IntWidth = IntWidth::U8;
+
+/// The tags of a `[LT, EQ, GT]`, in the order of their [CompareResult]s
+pub const ORDERING_TAGS: [&str; 3] = ["EQ", "GT", "LT"];
+
+// Tag ids follow the sorted tag names, so the values zig expects are the tag ids that an
+// `Ordering` has today, and the translation in the wrapper is free. If either side changes, the
+// wrapper keeps sorts working, but it costs a couple of instructions per comparison.
+const _: () = {
+    assert!(CompareResult::Eq as usize == 0 && CompareResult::Gt as usize == 1);
+    assert!(CompareResult::Lt as usize == 2);
+    assert!(str_lt(ORDERING_TAGS[0], ORDERING_TAGS[1]));
+    assert!(str_lt(ORDERING_TAGS[1], ORDERING_TAGS[2]));
+};
+
+const fn str_lt(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+
+    let mut i = 0;
+    while i < a.len() && i < b.len() {
+        if a[i] != b[i] {
+            return a[i] < b[i];
+        }
+        i += 1;
+    }
+
+    a.len() < b.len()
+}
@@ -87,6 +87,9 @@ macro_rules! map_symbol_to_lowlevel_and_arity {
                 LowLevel::PtrCast => unimplemented!(),
                 LowLevel::RefCountInc => unimplemented!(),
                 LowLevel::RefCountDec => unimplemented!(),
+                LowLevel::RuntimeContextLoad => unimplemented!(),
+                LowLevel::RuntimeContextStore => unimplemented!(),
+                LowLevel::MonotonicTicks => unimplemented!(),
 
                 // these are not implemented, not sure why
                 LowLevel::StrFromInt => unimplemented!(),
@@ -126,6 +129,7 @@ map_symbol_to_lowlevel_and_arity! {
     StrGetCapacity; STR_CAPACITY; 1,
     StrWithCapacity; STR_WITH_CAPACITY; 1,
     StrGraphemes; STR_GRAPHEMES; 1,
+    StrToU64Hex; STR_TO_U64_HEX_LOWLEVEL; 1,
 
     ListLen; LIST_LEN; 1,
     ListWithCapacity; LIST_WITH_CAPACITY; 1,
@@ -145,6 +149,11 @@ map_symbol_to_lowlevel_and_arity! {
     ListDropAt; LIST_DROP_AT; 2,
     ListSwap; LIST_SWAP; 3,
     ListGetCapacity; LIST_CAPACITY; 1,
+    ListSum; LIST_SUM; 1,
+    ListProduct; LIST_PRODUCT; 1,
+    ListMinUnsafe; LIST_MIN_UNSAFE; 1,
+    ListMaxUnsafe; LIST_MAX_UNSAFE; 1,
+    ListJoin; LIST_JOIN; 1,
 
     ListGetUnsafe; DICT_LIST_GET_UNSAFE; 2,
 
@@ -194,6 +203,16 @@ map_symbol_to_lowlevel_and_arity! {
     NumShiftRightBy; NUM_SHIFT_RIGHT; 2,
     NumShiftRightZfBy; NUM_SHIFT_RIGHT_ZERO_FILL; 2,
     NumToStr; NUM_TO_STR; 1,
+    NumToHexStr; NUM_TO_HEX_STR; 1,
+    NumGcd; NUM_GCD; 2,
+    NumLcm; NUM_LCM; 2,
+    NumAddWithOverflow; NUM_ADD_WITH_OVERFLOW; 2,
+    NumToF32Bits; NUM_TO_F32_BITS; 1,
+    NumToF64Bits; NUM_TO_F64_BITS; 1,
+    NumFromF32Bits; NUM_FROM_F32_BITS; 1,
+    NumFromF64Bits; NUM_FROM_F64_BITS; 1,
+    EncodeAppendNumLe; ENCODE_APPEND_NUM_LE; 2,
+    DecodeNumFromBytesLe; DECODE_NUM_FROM_BYTES_LE_LOWLEVEL; 2,
 
     Eq; BOOL_STRUCTURAL_EQ; 2,
     NotEq; BOOL_STRUCTURAL_NOT_EQ; 2,
@@ -202,6 +221,7 @@ map_symbol_to_lowlevel_and_arity! {
     Not; BOOL_NOT; 1,
     BoxExpr; BOX_BOX_FUNCTION; 1,
     UnboxExpr; BOX_UNBOX; 1,
+    BoxMap; BOX_MAP; 2,
     Unreachable; LIST_UNREACHABLE; 1,
 }
 
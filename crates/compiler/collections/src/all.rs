@@ -38,6 +38,11 @@ pub type BumpSet<K> = hashbrown::HashSet<K, BuildHasher>;
 
 pub type FnvMap<K, V> = fnv::FnvHashMap<K, V>;
 
+/// Unlike `std`'s `DefaultHasher`, whose algorithm may change in any Rust release, FNV hashes
+/// the same bytes the same way everywhere. Use it for hashes that end up in the generated code,
+/// like symbol names.
+pub use fnv::FnvHasher;
+
 pub trait BumpMapDefault<'a> {
     fn new_in(arena: &'a bumpalo::Bump) -> Self;
 
@@ -148,6 +148,10 @@ flags! {
     /// Prints LLVM function verification output.
     ROC_PRINT_LLVM_FN_VERIFICATION
 
+    /// When a module fails LLVM verification, replaces the body of every function that fails on
+    /// its own with a crash, so the rest of the module can still be emitted.
+    ROC_STUB_BROKEN_LLVM_FNS
+
     // ===WASM Gen===
 
     /// Writes a `final.wasm` file to /tmp
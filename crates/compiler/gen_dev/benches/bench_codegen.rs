@@ -0,0 +1,335 @@
+use bumpalo::Bump;
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use roc_collections::all::MutMap;
+use roc_gen_dev::{build_module, Env};
+use roc_module::low_level::LowLevel;
+use roc_module::symbol::{Interns, Symbol};
+use roc_mono::ir::{
+    Call, CallSpecId, CallType, Expr, HostExposedLayouts, Literal, Proc, ProcLayout, SelfRecursive,
+    Stmt, UpdateModeId,
+};
+use roc_mono::layout::{LambdaName, Layout, Niche, STLayoutInterner};
+use roc_target::TargetInfo;
+use target_lexicon::Triple;
+
+const PROCS: usize = 500;
+
+/// How many calls the procedure of the call dense benchmark makes
+const CALLS: usize = 500;
+
+/// How many values stay live across every one of those calls
+const LIVE_ACROSS_CALLS: usize = 4;
+
+/// How many arithmetic operations every procedure does before calling the next one
+const OPS_PER_PROC: usize = 24;
+
+/// The name of every procedure, followed by the symbols its body defines
+fn module_symbols(interns: &mut Interns) -> Vec<Vec<Symbol>> {
+    let module_id = interns.module_id(&"Bench".into());
+    let ident_ids = interns.all_ident_ids.get_or_insert(module_id);
+
+    (0..PROCS)
+        .map(|i| {
+            let name = if i == 0 {
+                "main".to_string()
+            } else {
+                format!("proc_{}", i)
+            };
+            let mut symbols = vec![Symbol::new(module_id, ident_ids.add_str(&name))];
+            for j in 0..2 * OPS_PER_PROC + 2 {
+                let ident = format!("v{}", j);
+                symbols.push(Symbol::new(module_id, ident_ids.add_str(&ident)));
+            }
+            symbols
+        })
+        .collect()
+}
+
+/// Every procedure takes an `I64`, mixes in some literals, and passes the result on to the
+/// next procedure. The last one returns it, and `main` is the first one.
+fn synthetic_module<'a>(
+    arena: &'a Bump,
+    symbols: &[Vec<Symbol>],
+) -> MutMap<(Symbol, ProcLayout<'a>), Proc<'a>> {
+    let one_i64 = &*arena.alloc([Layout::I64]);
+    let layout = ProcLayout {
+        arguments: one_i64,
+        result: Layout::I64,
+        niche: Niche::NONE,
+    };
+
+    symbols
+        .iter()
+        .enumerate()
+        .map(|(i, proc_symbols)| {
+            let name = proc_symbols[0];
+            let arg = proc_symbols[1];
+            let mut lets = Vec::new();
+            let mut value = arg;
+            for (j, pair) in proc_symbols[2..].chunks(2).take(OPS_PER_PROC).enumerate() {
+                let [literal, result] = [pair[0], pair[1]];
+                let op = [LowLevel::NumAdd, LowLevel::NumMul, LowLevel::NumSubWrap][j % 3];
+                let literal_value = (i * OPS_PER_PROC + j) as i128 % 7 + 3;
+
+                lets.push((
+                    literal,
+                    Expr::Literal(Literal::Int(literal_value.to_ne_bytes())),
+                ));
+                lets.push((
+                    result,
+                    Expr::Call(Call {
+                        call_type: CallType::LowLevel {
+                            op,
+                            update_mode: UpdateModeId::BACKEND_DUMMY,
+                        },
+                        arguments: arena.alloc([value, literal]),
+                    }),
+                ));
+                value = result;
+            }
+
+            let ret = match symbols.get(i + 1) {
+                Some(next) => {
+                    let called = *proc_symbols.last().unwrap();
+                    lets.push((
+                        called,
+                        Expr::Call(Call {
+                            call_type: CallType::ByName {
+                                name: LambdaName::no_niche(next[0]),
+                                ret_layout: Layout::I64,
+                                arg_layouts: one_i64,
+                                specialization_id: CallSpecId::BACKEND_DUMMY,
+                            },
+                            arguments: arena.alloc([value]),
+                        }),
+                    ));
+                    called
+                }
+                None => value,
+            };
+
+            let body = lets
+                .into_iter()
+                .rev()
+                .fold(Stmt::Ret(ret), |stmt, (symbol, expr)| {
+                    Stmt::Let(symbol, expr, Layout::I64, arena.alloc(stmt))
+                });
+            let proc = Proc {
+                name: LambdaName::no_niche(name),
+                args: arena.alloc([(Layout::I64, arg)]),
+                body,
+                closure_data_layout: None,
+                ret_layout: Layout::I64,
+                is_self_recursive: SelfRecursive::NotSelfRecursive,
+                must_own_arguments: false,
+                host_exposed_layouts: HostExposedLayouts::NotHostExposed,
+            };
+
+            ((name, layout), proc)
+        })
+        .collect()
+}
+
+/// `main` makes `CALLS` calls to `callee`, and keeps `LIVE_ACROSS_CALLS` values live across every
+/// one of them, so every call has to move values out of the caller saved registers first.
+fn call_dense_module<'a>(
+    arena: &'a Bump,
+    interns: &mut Interns,
+) -> (Symbol, MutMap<(Symbol, ProcLayout<'a>), Proc<'a>>) {
+    let module_id = interns.module_id(&"CallDense".into());
+    let ident_ids = interns.all_ident_ids.get_or_insert(module_id);
+    let mut symbol = |name: String| Symbol::new(module_id, ident_ids.add_str(&name));
+
+    let one_i64 = &*arena.alloc([Layout::I64]);
+    let layout = ProcLayout {
+        arguments: one_i64,
+        result: Layout::I64,
+        niche: Niche::NONE,
+    };
+    let proc = |name, arg, body| Proc {
+        name: LambdaName::no_niche(name),
+        args: arena.alloc([(Layout::I64, arg)]),
+        body,
+        closure_data_layout: None,
+        ret_layout: Layout::I64,
+        is_self_recursive: SelfRecursive::NotSelfRecursive,
+        must_own_arguments: false,
+        host_exposed_layouts: HostExposedLayouts::NotHostExposed,
+    };
+    let num_add = |a, b| {
+        Expr::Call(Call {
+            call_type: CallType::LowLevel {
+                op: LowLevel::NumAdd,
+                update_mode: UpdateModeId::BACKEND_DUMMY,
+            },
+            arguments: arena.alloc([a, b]),
+        })
+    };
+
+    // callee(x) = x + x
+    let callee = symbol("callee".to_string());
+    let callee_arg = symbol("x".to_string());
+    let callee_result = symbol("doubled".to_string());
+    let callee_body = Stmt::Let(
+        callee_result,
+        num_add(callee_arg, callee_arg),
+        Layout::I64,
+        arena.alloc(Stmt::Ret(callee_result)),
+    );
+
+    // main adds up what the calls return in `LIVE_ACROSS_CALLS` running sums
+    let main = symbol("main".to_string());
+    let main_arg = symbol("arg".to_string());
+    let mut lets = Vec::new();
+    let mut sums: Vec<Symbol> = (0..LIVE_ACROSS_CALLS)
+        .map(|i| {
+            let sum = symbol(format!("sum_{}", i));
+            lets.push((sum, num_add(main_arg, main_arg)));
+            sum
+        })
+        .collect();
+    for i in 0..CALLS {
+        let called = symbol(format!("called_{}", i));
+        let sum = symbol(format!("sum_{}_{}", i % LIVE_ACROSS_CALLS, i));
+        lets.push((
+            called,
+            Expr::Call(Call {
+                call_type: CallType::ByName {
+                    name: LambdaName::no_niche(callee),
+                    ret_layout: Layout::I64,
+                    arg_layouts: one_i64,
+                    specialization_id: CallSpecId::BACKEND_DUMMY,
+                },
+                arguments: arena.alloc([sums[(i + 1) % LIVE_ACROSS_CALLS]]),
+            }),
+        ));
+        lets.push((sum, num_add(sums[i % LIVE_ACROSS_CALLS], called)));
+        sums[i % LIVE_ACROSS_CALLS] = sum;
+    }
+    let main_body = lets
+        .into_iter()
+        .rev()
+        .fold(Stmt::Ret(sums[0]), |stmt, (symbol, expr)| {
+            Stmt::Let(symbol, expr, Layout::I64, arena.alloc(stmt))
+        });
+
+    let procedures = [
+        ((main, layout), proc(main, main_arg, main_body)),
+        ((callee, layout), proc(callee, callee_arg, callee_body)),
+    ]
+    .into_iter()
+    .collect();
+
+    (main, procedures)
+}
+
+pub fn call_dense_benchmark(c: &mut Criterion) {
+    let target: Triple = "x86_64-unknown-linux-gnu".parse().unwrap();
+
+    c.bench_function("gen_dev 500 calls", |b| {
+        b.iter(|| {
+            let arena = Bump::new();
+            let mut interns = Interns::default();
+            let (main, procedures) = call_dense_module(&arena, &mut interns);
+            let env = Env {
+                arena: &arena,
+                module_id: main.module_id(),
+                exposed_to_host: [main].into_iter().collect(),
+                export_names: Default::default(),
+                lazy_literals: true,
+                generate_allocators: false,
+                host_symbols: Default::default(),
+                alias_host_symbols: false,
+                peak_arena_bytes: Default::default(),
+                stats: Default::default(),
+                proc_sizes: Default::default(),
+                reg_alloc_order: None,
+                inline_small_procs: false,
+                codegen_threads: 1,
+                stack_canary: None,
+                patchpoints: false,
+                panic_cleanup: false,
+                runtime_context: false,
+                serialize_ticks: false,
+                zero_padding: false,
+                proc_alignment: 16,
+                statement_markers: false,
+                listings: Default::default(),
+            };
+            let mut layout_interner =
+                STLayoutInterner::with_capacity(4, TargetInfo::default_x86_64());
+
+            let object = build_module(
+                &env,
+                &mut interns,
+                &mut layout_interner,
+                &target,
+                procedures,
+            )
+            .unwrap();
+            black_box(object.write().unwrap().len());
+        })
+    });
+}
+
+pub fn codegen_benchmark(c: &mut Criterion) {
+    let target: Triple = "x86_64-unknown-linux-gnu".parse().unwrap();
+    let mut interns = Interns::default();
+    let symbols = module_symbols(&mut interns);
+    let main = symbols[0][0];
+
+    let mut group = c.benchmark_group("gen_dev 500 procs");
+    for threads in [1, 2, 4, 8] {
+        group.bench_with_input(
+            BenchmarkId::from_parameter(threads),
+            &threads,
+            |b, &threads| {
+                b.iter(|| {
+                    let arena = Bump::new();
+                    let env = Env {
+                        arena: &arena,
+                        module_id: main.module_id(),
+                        exposed_to_host: [main].into_iter().collect(),
+                        export_names: Default::default(),
+                        lazy_literals: true,
+                        generate_allocators: false,
+                        host_symbols: Default::default(),
+                        alias_host_symbols: false,
+                        peak_arena_bytes: Default::default(),
+                        stats: Default::default(),
+                        proc_sizes: Default::default(),
+                        reg_alloc_order: None,
+                        inline_small_procs: false,
+                        codegen_threads: threads,
+                        stack_canary: None,
+                        patchpoints: false,
+                        panic_cleanup: false,
+                        runtime_context: false,
+                        serialize_ticks: false,
+                        zero_padding: false,
+                        proc_alignment: 16,
+                        statement_markers: false,
+                        listings: Default::default(),
+                    };
+                    let mut layout_interner =
+                        STLayoutInterner::with_capacity(4, TargetInfo::default_x86_64());
+                    let procedures = synthetic_module(&arena, &symbols);
+
+                    let object = build_module(
+                        &env,
+                        &mut interns,
+                        &mut layout_interner,
+                        &target,
+                        procedures,
+                    )
+                    .unwrap();
+                    black_box(object.write().unwrap().len());
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, codegen_benchmark, call_dense_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,110 @@
+//! What instructions cost on a target, so that the choices between sequences that do the same,
+//! like a multiplication or a shift, or a conditional move or a branch, are made in one place.
+//! The sizes come from the encoders of the target. The latencies are rough numbers for its usual
+//! cores, and are meant to be tuned per target.
+
+/// The kinds of instructions that have a latency in a [LatencyTable]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum InstClass {
+    Alu,
+    Shift,
+    /// An add of a shifted register, like `lea` on x86-64
+    Lea,
+    Mul,
+    Cmov,
+    /// A conditional branch that is predicted right
+    Branch,
+}
+
+/// An instruction a decision point considers, with what the size of its encoding depends on
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PlannedInst {
+    /// `dst = src`
+    MovRegReg,
+    /// `dst = imm`
+    MovImm(i64),
+    /// `dst = -dst`
+    Neg,
+    /// `dst = dst << imm`
+    ShlImm,
+    /// `dst = src1 + (src2 << shift)`
+    AddShifted,
+    /// `dst = dst * src`
+    MulRegReg,
+    /// `dst = src` if a condition holds
+    Cmov,
+    /// A conditional jump over a few instructions
+    ShortJcc,
+}
+
+impl PlannedInst {
+    pub fn class(self) -> InstClass {
+        match self {
+            PlannedInst::MovRegReg | PlannedInst::MovImm(_) | PlannedInst::Neg => InstClass::Alu,
+            PlannedInst::ShlImm => InstClass::Shift,
+            PlannedInst::AddShifted => InstClass::Lea,
+            PlannedInst::MulRegReg => InstClass::Mul,
+            PlannedInst::Cmov => InstClass::Cmov,
+            PlannedInst::ShortJcc => InstClass::Branch,
+        }
+    }
+}
+
+/// The latency of every [InstClass], in cycles
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct LatencyTable {
+    pub alu: u8,
+    pub shift: u8,
+    pub lea: u8,
+    pub mul: u8,
+    pub cmov: u8,
+    pub branch: u8,
+}
+
+impl LatencyTable {
+    pub fn latency(&self, class: InstClass) -> u8 {
+        match class {
+            InstClass::Alu => self.alu,
+            InstClass::Shift => self.shift,
+            InstClass::Lea => self.lea,
+            InstClass::Mul => self.mul,
+            InstClass::Cmov => self.cmov,
+            InstClass::Branch => self.branch,
+        }
+    }
+}
+
+/// What a sequence of instructions costs. The cycles count first, and the bytes break ties.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Cost {
+    pub cycles: u32,
+    pub bytes: u32,
+}
+
+pub trait CostModel {
+    /// The size of the encoding of `inst`
+    fn size_bytes(&self, inst: PlannedInst) -> u8;
+
+    fn latencies(&self) -> &LatencyTable;
+
+    /// The cost of `insts`, as if each one depends on the one before it
+    fn cost(&self, insts: &[PlannedInst]) -> Cost {
+        let latencies = self.latencies();
+        insts.iter().fold(
+            Cost {
+                cycles: 0,
+                bytes: 0,
+            },
+            |cost, inst| Cost {
+                cycles: cost.cycles + latencies.latency(inst.class()) as u32,
+                bytes: cost.bytes + self.size_bytes(*inst) as u32,
+            },
+        )
+    }
+
+    /// Whether a conditional move is cheaper than a branch over a move
+    fn prefers_cmov(&self) -> bool {
+        self.cost(&[PlannedInst::Cmov])
+            <= self.cost(&[PlannedInst::ShortJcc, PlannedInst::MovRegReg])
+    }
+}
@@ -1,10 +1,17 @@
-use crate::generic64::{storage::StorageManager, Assembler, CallConv, RegTrait};
+use crate::cost_model::{CostModel, LatencyTable, PlannedInst};
+use crate::generic64::{
+    branches::BranchKind, operand::RegisterWidth, storage::StorageManager, Assembler, CallConv,
+    RegTrait, SignCondition,
+};
+use crate::unsupported::{BuildResult, UnsupportedConstruct};
 use crate::Relocation;
 use bumpalo::collections::Vec;
 use packed_struct::prelude::*;
+use roc_builtins::bitcode::FloatWidth;
 use roc_error_macros::internal_error;
 use roc_module::symbol::Symbol;
 use roc_mono::layout::{InLayout, STLayoutInterner};
+use roc_target::Endianness;
 
 #[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
 #[allow(dead_code)]
@@ -92,6 +99,49 @@ impl std::fmt::Display for AArch64GeneralReg {
     }
 }
 
+impl std::str::FromStr for AArch64GeneralReg {
+    type Err = String;
+
+    /// Parses register names in either case, e.g. "x19" or "X19".
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "x0" => Ok(AArch64GeneralReg::X0),
+            "x1" => Ok(AArch64GeneralReg::X1),
+            "x2" => Ok(AArch64GeneralReg::X2),
+            "x3" => Ok(AArch64GeneralReg::X3),
+            "x4" => Ok(AArch64GeneralReg::X4),
+            "x5" => Ok(AArch64GeneralReg::X5),
+            "x6" => Ok(AArch64GeneralReg::X6),
+            "x7" => Ok(AArch64GeneralReg::X7),
+            "xr" => Ok(AArch64GeneralReg::XR),
+            "x9" => Ok(AArch64GeneralReg::X9),
+            "x10" => Ok(AArch64GeneralReg::X10),
+            "x11" => Ok(AArch64GeneralReg::X11),
+            "x12" => Ok(AArch64GeneralReg::X12),
+            "x13" => Ok(AArch64GeneralReg::X13),
+            "x14" => Ok(AArch64GeneralReg::X14),
+            "x15" => Ok(AArch64GeneralReg::X15),
+            "ip0" => Ok(AArch64GeneralReg::IP0),
+            "ip1" => Ok(AArch64GeneralReg::IP1),
+            "pr" => Ok(AArch64GeneralReg::PR),
+            "x19" => Ok(AArch64GeneralReg::X19),
+            "x20" => Ok(AArch64GeneralReg::X20),
+            "x21" => Ok(AArch64GeneralReg::X21),
+            "x22" => Ok(AArch64GeneralReg::X22),
+            "x23" => Ok(AArch64GeneralReg::X23),
+            "x24" => Ok(AArch64GeneralReg::X24),
+            "x25" => Ok(AArch64GeneralReg::X25),
+            "x26" => Ok(AArch64GeneralReg::X26),
+            "x27" => Ok(AArch64GeneralReg::X27),
+            "x28" => Ok(AArch64GeneralReg::X28),
+            "fp" => Ok(AArch64GeneralReg::FP),
+            "lr" => Ok(AArch64GeneralReg::LR),
+            "zrsp" => Ok(AArch64GeneralReg::ZRSP),
+            _ => Err(format!("unknown aarch64 general register: {:?}", s)),
+        }
+    }
+}
+
 impl AArch64GeneralReg {
     #[inline(always)]
     fn id(&self) -> u8 {
@@ -112,6 +162,14 @@ impl std::fmt::Display for AArch64FloatReg {
         write!(f, "TODO",)
     }
 }
+impl std::str::FromStr for AArch64FloatReg {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // TODO: no float registers are implemented yet
+        Err(format!("unknown aarch64 float register: {:?}", s))
+    }
+}
 
 #[derive(Copy, Clone)]
 pub struct AArch64Assembler {}
@@ -178,6 +236,8 @@ impl CallConv<AArch64GeneralReg, AArch64FloatReg, AArch64Assembler> for AArch64C
         AArch64GeneralReg::IP0,
         AArch64GeneralReg::IP1,
     ];
+    const SCRATCH_REGS: [AArch64GeneralReg; 2] = [AArch64GeneralReg::IP0, AArch64GeneralReg::IP1];
+    const RUNTIME_CONTEXT_REG: AArch64GeneralReg = AArch64GeneralReg::X28;
     const FLOAT_PARAM_REGS: &'static [AArch64FloatReg] = &[];
     const FLOAT_RETURN_REGS: &'static [AArch64FloatReg] = Self::FLOAT_PARAM_REGS;
     const FLOAT_DEFAULT_FREE_REGS: &'static [AArch64FloatReg] = &[];
@@ -212,6 +272,7 @@ impl CallConv<AArch64GeneralReg, AArch64FloatReg, AArch64Assembler> for AArch64C
         saved_float_regs: &[AArch64FloatReg],
         requested_stack_size: i32,
         fn_call_stack_size: i32,
+        _red_zone_size: i32,
     ) -> i32 {
         // Full size is upcast to i64 to make sure we don't overflow here.
         let full_stack_size = match requested_stack_size
@@ -277,6 +338,7 @@ impl CallConv<AArch64GeneralReg, AArch64FloatReg, AArch64Assembler> for AArch64C
         saved_float_regs: &[AArch64FloatReg],
         aligned_stack_size: i32,
         fn_call_stack_size: i32,
+        _red_zone_size: i32,
     ) {
         if aligned_stack_size > 0 {
             // All the following stores could be optimized by using `STP` to store pairs.
@@ -318,8 +380,10 @@ impl CallConv<AArch64GeneralReg, AArch64FloatReg, AArch64Assembler> for AArch64C
         _layout_interner: &mut STLayoutInterner<'a>,
         _args: &'a [(InLayout<'a>, Symbol)],
         _ret_layout: &InLayout<'a>,
-    ) {
-        todo!("Loading args for AArch64");
+    ) -> BuildResult {
+        Err(UnsupportedConstruct::CallConvDetail(
+            "loading arguments on AArch64",
+        ))
     }
 
     #[inline(always)]
@@ -338,8 +402,10 @@ impl CallConv<AArch64GeneralReg, AArch64FloatReg, AArch64Assembler> for AArch64C
         _args: &[Symbol],
         _arg_layouts: &[InLayout<'a>],
         _ret_layout: &InLayout<'a>,
-    ) {
-        todo!("Storing args for AArch64");
+    ) -> BuildResult {
+        Err(UnsupportedConstruct::CallConvDetail(
+            "passing arguments on AArch64",
+        ))
     }
 
     fn return_complex_symbol<'a, 'r>(
@@ -355,8 +421,10 @@ impl CallConv<AArch64GeneralReg, AArch64FloatReg, AArch64Assembler> for AArch64C
         _layout_interner: &mut STLayoutInterner<'a>,
         _sym: &Symbol,
         _layout: &InLayout<'a>,
-    ) {
-        todo!("Returning complex symbols for AArch64");
+    ) -> BuildResult {
+        Err(UnsupportedConstruct::CallConvDetail(
+            "returning a complex value on AArch64",
+        ))
     }
 
     fn load_returned_complex_symbol<'a, 'r>(
@@ -372,12 +440,54 @@ impl CallConv<AArch64GeneralReg, AArch64FloatReg, AArch64Assembler> for AArch64C
         _layout_interner: &mut STLayoutInterner<'a>,
         _sym: &Symbol,
         _layout: &InLayout<'a>,
-    ) {
-        todo!("Loading returned complex symbols for AArch64");
+    ) -> BuildResult {
+        Err(UnsupportedConstruct::CallConvDetail(
+            "loading a complex value that was returned on AArch64",
+        ))
+    }
+}
+
+/// Every instruction is 4 bytes, and the latencies fit most Cortex-A cores
+pub struct AArch64CostModel {
+    pub latencies: LatencyTable,
+}
+
+impl AArch64CostModel {
+    pub const DEFAULT: AArch64CostModel = AArch64CostModel {
+        latencies: LatencyTable {
+            alu: 1,
+            shift: 1,
+            lea: 2,
+            mul: 3,
+            cmov: 1,
+            branch: 1,
+        },
+    };
+}
+
+impl CostModel for AArch64CostModel {
+    fn size_bytes(&self, inst: PlannedInst) -> u8 {
+        match inst {
+            // a `movz` for the lowest 16 bits, and a `movk` for every higher 16 bits up to the
+            // last ones that are not zero
+            PlannedInst::MovImm(imm) => {
+                let bits = 64 - (imm as u64).leading_zeros();
+                4 * ((bits + 15) / 16).max(1) as u8
+            }
+            _ => 4,
+        }
+    }
+
+    fn latencies(&self) -> &LatencyTable {
+        &self.latencies
     }
 }
 
 impl Assembler<AArch64GeneralReg, AArch64FloatReg> for AArch64Assembler {
+    const MUL_DIV_FIXED_REGS: &'static [AArch64GeneralReg] = &[];
+    const ZERO_FIXED_REGS: &'static [AArch64GeneralReg] = &[];
+    const TIMESTAMP_FIXED_REGS: &'static [AArch64GeneralReg] = &[];
+
     #[inline(always)]
     fn abs_reg64_reg64(_buf: &mut Vec<'_, u8>, _dst: AArch64GeneralReg, _src: AArch64GeneralReg) {
         todo!("abs_reg64_reg64 for AArch64");
@@ -389,6 +499,7 @@ impl Assembler<AArch64GeneralReg, AArch64FloatReg> for AArch64Assembler {
         _relocs: &mut Vec<'_, Relocation>,
         _dst: AArch64FloatReg,
         _src: AArch64FloatReg,
+        _endianness: Endianness,
     ) {
         todo!("abs_reg64_reg64 for AArch64");
     }
@@ -418,6 +529,28 @@ impl Assembler<AArch64GeneralReg, AArch64FloatReg> for AArch64Assembler {
         add_reg64_reg64_reg64(buf, dst, src1, src2);
     }
     #[inline(always)]
+    fn lea_reg64_base64_index64(
+        buf: &mut Vec<'_, u8>,
+        dst: AArch64GeneralReg,
+        base: AArch64GeneralReg,
+        index: AArch64GeneralReg,
+    ) {
+        // Unlike `adds`, `add` leaves the flags alone.
+        add_reg64_reg64_reg64(buf, dst, base, index);
+    }
+    #[inline(always)]
+    fn add_shifted_reg64_reg64_reg64(
+        buf: &mut Vec<'_, u8>,
+        dst: AArch64GeneralReg,
+        src1: AArch64GeneralReg,
+        src2: AArch64GeneralReg,
+        shift: u8,
+    ) {
+        let inst = ArithmeticShifted::new(false, false, ShiftType::LSL, shift, src2, src1, dst);
+
+        buf.extend(inst.bytes());
+    }
+    #[inline(always)]
     fn add_freg32_freg32_freg32(
         _buf: &mut Vec<'_, u8>,
         _dst: AArch64FloatReg,
@@ -451,6 +584,16 @@ impl Assembler<AArch64GeneralReg, AArch64FloatReg> for AArch64Assembler {
         todo!("register signed multiplication for AArch64");
     }
 
+    #[inline(always)]
+    fn imul_reg64_reg64_imm32(
+        _buf: &mut Vec<'_, u8>,
+        _dst: AArch64GeneralReg,
+        _src: AArch64GeneralReg,
+        _imm32: i32,
+    ) {
+        todo!("immediate signed multiplication for AArch64");
+    }
+
     fn umul_reg64_reg64_reg64<'a, 'r, ASM, CC>(
         _buf: &mut Vec<'a, u8>,
         _storage_manager: &mut StorageManager<'a, 'r, AArch64GeneralReg, AArch64FloatReg, ASM, CC>,
@@ -464,9 +607,10 @@ impl Assembler<AArch64GeneralReg, AArch64FloatReg> for AArch64Assembler {
         todo!("register unsigned multiplication for AArch64");
     }
 
-    fn idiv_reg64_reg64_reg64<'a, 'r, ASM, CC>(
+    fn idiv_reg_reg_reg<'a, 'r, ASM, CC>(
         _buf: &mut Vec<'a, u8>,
         _storage_manager: &mut StorageManager<'a, 'r, AArch64GeneralReg, AArch64FloatReg, ASM, CC>,
+        _width: RegisterWidth,
         _dst: AArch64GeneralReg,
         _src1: AArch64GeneralReg,
         _src2: AArch64GeneralReg,
@@ -477,9 +621,10 @@ impl Assembler<AArch64GeneralReg, AArch64FloatReg> for AArch64Assembler {
         todo!("register signed division for AArch64");
     }
 
-    fn udiv_reg64_reg64_reg64<'a, 'r, ASM, CC>(
+    fn udiv_reg_reg_reg<'a, 'r, ASM, CC>(
         _buf: &mut Vec<'a, u8>,
         _storage_manager: &mut StorageManager<'a, 'r, AArch64GeneralReg, AArch64FloatReg, ASM, CC>,
+        _width: RegisterWidth,
         _dst: AArch64GeneralReg,
         _src1: AArch64GeneralReg,
         _src2: AArch64GeneralReg,
@@ -490,6 +635,34 @@ impl Assembler<AArch64GeneralReg, AArch64FloatReg> for AArch64Assembler {
         todo!("register unsigned division for AArch64");
     }
 
+    fn irem_reg_reg_reg<'a, 'r, ASM, CC>(
+        _buf: &mut Vec<'a, u8>,
+        _storage_manager: &mut StorageManager<'a, 'r, AArch64GeneralReg, AArch64FloatReg, ASM, CC>,
+        _width: RegisterWidth,
+        _dst: AArch64GeneralReg,
+        _src1: AArch64GeneralReg,
+        _src2: AArch64GeneralReg,
+    ) where
+        ASM: Assembler<AArch64GeneralReg, AArch64FloatReg>,
+        CC: CallConv<AArch64GeneralReg, AArch64FloatReg, ASM>,
+    {
+        todo!("register signed remainder for AArch64");
+    }
+
+    fn urem_reg_reg_reg<'a, 'r, ASM, CC>(
+        _buf: &mut Vec<'a, u8>,
+        _storage_manager: &mut StorageManager<'a, 'r, AArch64GeneralReg, AArch64FloatReg, ASM, CC>,
+        _width: RegisterWidth,
+        _dst: AArch64GeneralReg,
+        _src1: AArch64GeneralReg,
+        _src2: AArch64GeneralReg,
+    ) where
+        ASM: Assembler<AArch64GeneralReg, AArch64FloatReg>,
+        CC: CallConv<AArch64GeneralReg, AArch64FloatReg, ASM>,
+    {
+        todo!("register unsigned remainder for AArch64");
+    }
+
     #[inline(always)]
     fn mul_freg32_freg32_freg32(
         _buf: &mut Vec<'_, u8>,
@@ -539,6 +712,21 @@ impl Assembler<AArch64GeneralReg, AArch64FloatReg> for AArch64Assembler {
         buf.len() as u64 - 4 // TODO is 4 the correct offset in ARM?
     }
 
+    #[inline(always)]
+    fn jump_size(_kind: BranchKind, _short: bool) -> u64 {
+        todo!("jump instructions for AArch64");
+    }
+
+    #[inline(always)]
+    fn fits_short_jump(_displacement: i64) -> bool {
+        todo!("jump instructions for AArch64");
+    }
+
+    #[inline(always)]
+    fn jump(_buf: &mut Vec<'_, u8>, _kind: BranchKind, _offset: i32, _short: bool) {
+        todo!("jump instructions for AArch64");
+    }
+
     #[inline(always)]
     fn jne_reg64_imm64_imm32(
         _buf: &mut Vec<'_, u8>,
@@ -555,6 +743,7 @@ impl Assembler<AArch64GeneralReg, AArch64FloatReg> for AArch64Assembler {
         _relocs: &mut Vec<'_, Relocation>,
         _dst: AArch64FloatReg,
         _imm: f32,
+        _endianness: Endianness,
     ) {
         todo!("loading f32 literal for AArch64");
     }
@@ -564,10 +753,29 @@ impl Assembler<AArch64GeneralReg, AArch64FloatReg> for AArch64Assembler {
         _relocs: &mut Vec<'_, Relocation>,
         _dst: AArch64FloatReg,
         _imm: f64,
+        _endianness: Endianness,
     ) {
         todo!("loading f64 literal for AArch64");
     }
     #[inline(always)]
+    fn load_local_data_address(
+        _buf: &mut Vec<'_, u8>,
+        _relocs: &mut Vec<'_, Relocation>,
+        _dst: AArch64GeneralReg,
+        _data: std::vec::Vec<u8>,
+    ) {
+        todo!("loading the address of local data for AArch64");
+    }
+    #[inline(always)]
+    fn load_linked_data_address(
+        _buf: &mut Vec<'_, u8>,
+        _relocs: &mut Vec<'_, Relocation>,
+        _dst: AArch64GeneralReg,
+        _name: String,
+    ) {
+        todo!("loading the address of linked data for AArch64");
+    }
+    #[inline(always)]
     fn mov_reg64_imm64(buf: &mut Vec<'_, u8>, dst: AArch64GeneralReg, imm: i64) {
         let mut remaining = imm as u64;
         movz_reg64_imm16(buf, dst, remaining as u16, 0);
@@ -585,6 +793,11 @@ impl Assembler<AArch64GeneralReg, AArch64FloatReg> for AArch64Assembler {
         }
     }
     #[inline(always)]
+    fn load_constant(buf: &mut Vec<'_, u8>, dst: AArch64GeneralReg, value: i64, _flags_live: bool) {
+        // movz/movk never touch the flags
+        Self::mov_reg64_imm64(buf, dst, value);
+    }
+    #[inline(always)]
     fn mov_freg64_freg64(_buf: &mut Vec<'_, u8>, _dst: AArch64FloatReg, _src: AArch64FloatReg) {
         todo!("moving data between float registers for AArch64");
     }
@@ -613,6 +826,47 @@ impl Assembler<AArch64GeneralReg, AArch64FloatReg> for AArch64Assembler {
         todo!("saving floating point reg to base offset for AArch64");
     }
     #[inline(always)]
+    fn mov_base32_imm32(buf: &mut Vec<'_, u8>, offset: i32, imm: i32) {
+        if imm == 0 {
+            // the store of register 31 stores the zero register
+            Self::mov_base32_reg64(buf, offset, AArch64GeneralReg::ZRSP);
+        } else {
+            todo!("storing non-zero immediates for AArch64");
+        }
+    }
+    #[inline(always)]
+    fn mov_base32_imm(buf: &mut Vec<'_, u8>, offset: i32, imm: i32, width: RegisterWidth) {
+        match width {
+            RegisterWidth::W64 => Self::mov_base32_imm32(buf, offset, imm),
+            _ => todo!("storing immediates of less than 8 bytes for AArch64"),
+        }
+    }
+
+    #[inline(always)]
+    fn zero_base32(buf: &mut Vec<'_, u8>, offset: i32, size: u32) {
+        debug_assert!(size % 8 == 0);
+        for i in (0..size as i32).step_by(8) {
+            Self::mov_base32_imm32(buf, offset + i, 0);
+        }
+    }
+
+    fn read_timestamp_counter(_buf: &mut Vec<'_, u8>, _dst: AArch64GeneralReg, _serialize: bool) {
+        todo!("reading the timestamp counter for AArch64");
+    }
+
+    #[inline(always)]
+    fn mov_base32_reg32(_buf: &mut Vec<'_, u8>, _offset: i32, _src: AArch64GeneralReg) {
+        todo!("saving 4 byte values to base offset for AArch64");
+    }
+    #[inline(always)]
+    fn mov_base32_reg16(_buf: &mut Vec<'_, u8>, _offset: i32, _src: AArch64GeneralReg) {
+        todo!("saving 2 byte values to base offset for AArch64");
+    }
+    #[inline(always)]
+    fn mov_base32_reg8(_buf: &mut Vec<'_, u8>, _offset: i32, _src: AArch64GeneralReg) {
+        todo!("saving 1 byte values to base offset for AArch64");
+    }
+    #[inline(always)]
     fn mov_base32_reg64(buf: &mut Vec<'_, u8>, offset: i32, src: AArch64GeneralReg) {
         if offset < 0 {
             todo!("negative base offsets for AArch64");
@@ -656,6 +910,15 @@ impl Assembler<AArch64GeneralReg, AArch64FloatReg> for AArch64Assembler {
             todo!("mem offsets over 32k for AArch64");
         }
     }
+    #[inline(always)]
+    fn mov_mem8_offset32_reg8(
+        _buf: &mut Vec<'_, u8>,
+        _dst: AArch64GeneralReg,
+        _offset: i32,
+        _src: AArch64GeneralReg,
+    ) {
+        todo!("saving 1 byte values to mem offsets for AArch64");
+    }
 
     #[inline(always)]
     fn movsx_reg64_base32(buf: &mut Vec<'_, u8>, dst: AArch64GeneralReg, offset: i32, size: u8) {
@@ -673,6 +936,34 @@ impl Assembler<AArch64GeneralReg, AArch64FloatReg> for AArch64Assembler {
         }
     }
     #[inline(always)]
+    fn movsx_reg64_reg64(
+        buf: &mut Vec<'_, u8>,
+        dst: AArch64GeneralReg,
+        src: AArch64GeneralReg,
+        size: u8,
+    ) {
+        debug_assert!(size <= 8);
+        if size == 8 {
+            Self::mov_reg64_reg64(buf, dst, src);
+        } else {
+            todo!("sign extending {} byte registers", size);
+        }
+    }
+    #[inline(always)]
+    fn movzx_reg64_reg64(
+        buf: &mut Vec<'_, u8>,
+        dst: AArch64GeneralReg,
+        src: AArch64GeneralReg,
+        size: u8,
+    ) {
+        debug_assert!(size <= 8);
+        if size == 8 {
+            Self::mov_reg64_reg64(buf, dst, src);
+        } else {
+            todo!("zero extending {} byte registers", size);
+        }
+    }
+    #[inline(always)]
     fn movzx_reg64_base32(buf: &mut Vec<'_, u8>, dst: AArch64GeneralReg, offset: i32, size: u8) {
         debug_assert!(size <= 8);
         if size == 8 {
@@ -868,6 +1159,58 @@ impl Assembler<AArch64GeneralReg, AArch64FloatReg> for AArch64Assembler {
         todo!("set if overflow for AArch64");
     }
 
+    fn int_sign_test_reg_reg(
+        _buf: &mut Vec<'_, u8>,
+        _dst: AArch64GeneralReg,
+        _src: AArch64GeneralReg,
+        _width: RegisterWidth,
+        _signed: bool,
+        _condition: SignCondition,
+    ) {
+        todo!("integer sign tests for AArch64");
+    }
+
+    fn float_sign_test_reg_freg(
+        _buf: &mut Vec<'_, u8>,
+        _dst: AArch64GeneralReg,
+        _src: AArch64FloatReg,
+        _tmp: AArch64FloatReg,
+        _width: FloatWidth,
+        _condition: SignCondition,
+    ) {
+        todo!("float sign tests for AArch64");
+    }
+
+    const PATCHPOINT_SIZE: usize = 4;
+
+    const COST_MODEL: &'static dyn CostModel = &AArch64CostModel::DEFAULT;
+
+    /// A `nop`, which a `b` replaces. That reaches 128MiB either way.
+    #[inline(always)]
+    fn patchpoint(buf: &mut Vec<'_, u8>) {
+        buf.extend(0xD503201Fu32.to_le_bytes());
+    }
+
+    /// `brk #0`s in debug builds, and `nop`s otherwise. Instructions are 4 bytes, and so is
+    /// every gap between procedures.
+    #[inline(always)]
+    fn padding(buf: &mut Vec<'_, u8>, size: usize) {
+        debug_assert_eq!(
+            size % 4,
+            0,
+            "can not pad with {} bytes of instructions",
+            size
+        );
+        let instruction: u32 = if cfg!(debug_assertions) {
+            0xD4200000
+        } else {
+            0xD503201F
+        };
+        for _ in 0..size / 4 {
+            buf.extend(instruction.to_le_bytes());
+        }
+    }
+
     #[inline(always)]
     fn ret(buf: &mut Vec<'_, u8>) {
         ret_reg64(buf, AArch64GeneralReg::LR)
@@ -938,6 +1281,33 @@ impl Assembler<AArch64GeneralReg, AArch64FloatReg> for AArch64Assembler {
     {
         todo!("sar for AArch64")
     }
+
+    fn shl_reg64_reg64_imm8(
+        _buf: &mut Vec<'_, u8>,
+        _dst: AArch64GeneralReg,
+        _src: AArch64GeneralReg,
+        _imm: u8,
+    ) {
+        todo!("immediate shl for AArch64")
+    }
+
+    fn shr_reg64_reg64_imm8(
+        _buf: &mut Vec<'_, u8>,
+        _dst: AArch64GeneralReg,
+        _src: AArch64GeneralReg,
+        _imm: u8,
+    ) {
+        todo!("immediate shr for AArch64")
+    }
+
+    fn sar_reg64_reg64_imm8(
+        _buf: &mut Vec<'_, u8>,
+        _dst: AArch64GeneralReg,
+        _src: AArch64GeneralReg,
+        _imm: u8,
+    ) {
+        todo!("immediate sar for AArch64")
+    }
 }
 
 impl AArch64Assembler {}
@@ -1444,6 +1814,17 @@ mod tests {
         (buf, cs)
     }
 
+    #[test]
+    fn test_parse_registers() {
+        for reg in ALL_GENERAL_REGS {
+            let name = reg.to_string();
+            assert_eq!(name.parse::<AArch64GeneralReg>(), Ok(*reg));
+            assert_eq!(name.to_uppercase().parse::<AArch64GeneralReg>(), Ok(*reg));
+        }
+
+        assert!("x32".parse::<AArch64GeneralReg>().is_err());
+    }
+
     #[test]
     fn test_add_reg64_reg64_reg64() {
         disassembler_test!(
@@ -1586,4 +1967,36 @@ mod tests {
             ALL_GENERAL_REGS
         );
     }
+
+    #[test]
+    fn test_cost_model_sizes_match_the_encoders() {
+        let arena = bumpalo::Bump::new();
+        let costs = &AArch64CostModel::DEFAULT;
+        let size_of = |emit: &dyn Fn(&mut Vec<'_, u8>)| {
+            let mut buf = bumpalo::vec![in &arena];
+            emit(&mut buf);
+            buf.len()
+        };
+        let size = |inst| costs.size_bytes(inst) as usize;
+
+        for (dst, src) in ALL_GENERAL_REGS.iter().zip(ALL_GENERAL_REGS.iter().rev()) {
+            let (dst, src) = (*dst, *src);
+            assert_eq!(
+                size_of(&|buf| AArch64Assembler::mov_reg64_reg64(buf, dst, src)),
+                size(PlannedInst::MovRegReg)
+            );
+            for imm in [0, 0xFFFF, 0x1_0000, 0x1234_0000_0000, -1, i64::MIN] {
+                assert_eq!(
+                    size_of(&|buf| AArch64Assembler::mov_reg64_imm64(buf, dst, imm)),
+                    size(PlannedInst::MovImm(imm))
+                );
+            }
+            assert_eq!(
+                size_of(&|buf| AArch64Assembler::add_shifted_reg64_reg64_reg64(
+                    buf, dst, dst, src, 3
+                )),
+                size(PlannedInst::AddShifted)
+            );
+        }
+    }
 }
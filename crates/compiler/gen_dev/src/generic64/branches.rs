@@ -0,0 +1,409 @@
+//! Branch relaxation: jumps are emitted in their long form while a procedure is built,
+//! because their targets are not known yet. Once the body is done, jumps to the instruction
+//! right after them are dropped, and the others get the short form whenever it reaches.
+//! Shrinking a jump can bring others into range, so this repeats until nothing changes.
+use crate::generic64::{Assembler, OffsetTable, RegTrait};
+use bumpalo::collections::Vec;
+use bumpalo::Bump;
+use roc_error_macros::internal_error;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum BranchKind {
+    /// An unconditional jump
+    Jmp,
+    /// A jump if not equal, after the compare that sets the flags for it
+    Jne,
+}
+
+/// A jump in the body of a procedure, with its target resolved.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Branch {
+    pub kind: BranchKind,
+    /// Where the jump instruction starts, in its long form.
+    /// For a `Jne` this is after the compare.
+    pub location: u64,
+    /// Where the jump goes.
+    pub target: u64,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum Form {
+    Long,
+    Short,
+    Removed,
+}
+
+/// Maps offsets in a body with long jumps to offsets in the relaxed body.
+pub struct RelaxedOffsets<'a> {
+    /// The locations of the branches, in order
+    locations: Vec<'a, u64>,
+    /// How many bytes the first `i` branches got smaller by, for every `i`
+    saved_before: Vec<'a, u64>,
+    /// The bytes that were cut from the end of every jump that got smaller, as where they start
+    /// in the body with long jumps and how many there are
+    removed: Vec<'a, (u64, u64)>,
+}
+
+impl<'a> RelaxedOffsets<'a> {
+    fn new<GeneralReg: RegTrait, FloatReg: RegTrait, ASM: Assembler<GeneralReg, FloatReg>>(
+        arena: &'a Bump,
+        branches: &[Branch],
+        forms: &[Form],
+    ) -> Self {
+        let mut saved_before = Vec::with_capacity_in(branches.len() + 1, arena);
+        saved_before.push(0);
+        let mut removed = Vec::new_in(arena);
+        for (branch, form) in branches.iter().zip(forms) {
+            let new_size = size::<_, _, ASM>(branch.kind, *form);
+            let saved = ASM::jump_size(branch.kind, false) - new_size;
+            saved_before.push(saved_before.last().unwrap() + saved);
+            if saved > 0 {
+                removed.push((branch.location + new_size, saved));
+            }
+        }
+
+        RelaxedOffsets {
+            locations: Vec::from_iter_in(branches.iter().map(|branch| branch.location), arena),
+            saved_before,
+            removed,
+        }
+    }
+
+    /// Moves the offsets in `table`, which are in the body with long jumps, to the relaxed body.
+    pub fn shift(&self, table: &mut OffsetTable<'_>) {
+        // From the back, so the bytes that are cut next are still where they were
+        for (at, len) in self.removed.iter().rev() {
+            table.apply_shift(*at, -(*len as i64));
+        }
+    }
+
+    /// The new offset of `offset`, which must not be inside one of the jumps.
+    pub fn new_offset(&self, offset: u64) -> u64 {
+        let branches_before = self
+            .locations
+            .partition_point(|location| *location < offset);
+
+        offset - self.saved_before[branches_before]
+    }
+
+    /// How many bytes smaller the body got.
+    pub fn saved_bytes(&self) -> u64 {
+        *self.saved_before.last().unwrap()
+    }
+}
+
+fn size<GeneralReg: RegTrait, FloatReg: RegTrait, ASM: Assembler<GeneralReg, FloatReg>>(
+    kind: BranchKind,
+    form: Form,
+) -> u64 {
+    match form {
+        Form::Long => ASM::jump_size(kind, false),
+        Form::Short => ASM::jump_size(kind, true),
+        Form::Removed => 0,
+    }
+}
+
+/// Appends `body` to `out`, with all of its `branches` as small as they can be,
+/// and returns how to find the new offsets of the rest of its instructions.
+pub fn relax_branches<
+    'a,
+    GeneralReg: RegTrait,
+    FloatReg: RegTrait,
+    ASM: Assembler<GeneralReg, FloatReg>,
+>(
+    arena: &'a Bump,
+    body: &[u8],
+    branches: &mut [Branch],
+    out: &mut Vec<'_, u8>,
+) -> RelaxedOffsets<'a> {
+    branches.sort_unstable_by_key(|branch| branch.location);
+
+    let mut forms = Vec::from_iter_in(branches.iter().map(|_| Form::Long), arena);
+    loop {
+        let offsets = RelaxedOffsets::new::<_, _, ASM>(arena, branches, &forms);
+        let mut changed = false;
+
+        for (branch, form) in branches.iter().zip(forms.iter_mut()) {
+            if *form == Form::Removed {
+                continue;
+            }
+
+            // The displacement if this jump had the given size. Any other jump only gets smaller,
+            // so a displacement that fits now keeps fitting.
+            let current_size = size::<_, _, ASM>(branch.kind, *form) as i64;
+            let start = offsets.new_offset(branch.location) as i64;
+            let displacement = |size: i64| {
+                let mut target = offsets.new_offset(branch.target) as i64;
+                if branch.target > branch.location {
+                    target += size - current_size;
+                }
+                target - (start + size)
+            };
+
+            if branch.target > branch.location && displacement(0) == 0 {
+                *form = Form::Removed;
+                changed = true;
+            } else if *form == Form::Long {
+                let short_size = ASM::jump_size(branch.kind, true) as i64;
+                if ASM::fits_short_jump(displacement(short_size)) {
+                    *form = Form::Short;
+                    changed = true;
+                }
+            }
+        }
+
+        if !changed {
+            break;
+        }
+    }
+
+    let offsets = RelaxedOffsets::new::<_, _, ASM>(arena, branches, &forms);
+    let out_start = out.len() as u64;
+    let mut copied = 0;
+    for (branch, form) in branches.iter().zip(forms.iter()) {
+        out.extend_from_slice(&body[copied..branch.location as usize]);
+        copied = (branch.location + ASM::jump_size(branch.kind, false)) as usize;
+
+        if *form == Form::Removed {
+            continue;
+        }
+
+        let end = out.len() as u64 - out_start + size::<_, _, ASM>(branch.kind, *form);
+        let displacement = offsets.new_offset(branch.target) as i64 - end as i64;
+        let displacement = match i32::try_from(displacement) {
+            Ok(displacement) => displacement,
+            Err(_) => internal_error!(
+                "the jump at {} is {} bytes away from its target",
+                branch.location,
+                displacement
+            ),
+        };
+        ASM::jump(out, branch.kind, displacement, *form == Form::Short);
+    }
+    out.extend_from_slice(&body[copied..]);
+
+    debug_assert_eq!(
+        out.len() as u64 - out_start,
+        body.len() as u64 - offsets.saved_bytes()
+    );
+
+    offsets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generic64::x86_64::{X86_64Assembler, X86_64FloatReg, X86_64GeneralReg};
+
+    type ASM = X86_64Assembler;
+
+    const NOP: u8 = 0x90;
+
+    fn relax<'a>(
+        arena: &'a Bump,
+        body: &[u8],
+        branches: &mut [Branch],
+    ) -> (Vec<'a, u8>, RelaxedOffsets<'a>) {
+        let mut out = Vec::new_in(arena);
+        let offsets = relax_branches::<X86_64GeneralReg, X86_64FloatReg, ASM>(
+            arena, body, branches, &mut out,
+        );
+
+        (out, offsets)
+    }
+
+    /// Appends `nops` nops, followed by a long jump to nowhere, and returns where the jump is.
+    fn nops_then_jump(body: &mut Vec<'_, u8>, nops: usize, kind: BranchKind) -> u64 {
+        body.extend(std::iter::repeat(NOP).take(nops));
+        let location = body.len() as u64;
+        <ASM as Assembler<X86_64GeneralReg, X86_64FloatReg>>::jump(body, kind, 0, false);
+
+        location
+    }
+
+    /// The target of the jump at `location` in `out`, read back from its encoding.
+    fn target_of(out: &[u8], location: u64) -> u64 {
+        let location = location as usize;
+        let (size, displacement) = match out[location] {
+            0xEB | 0x75 => (2, out[location + 1] as i8 as i64),
+            0xE9 => (
+                5,
+                i32::from_le_bytes(out[location + 1..location + 5].try_into().unwrap()) as i64,
+            ),
+            0x0F => (
+                6,
+                i32::from_le_bytes(out[location + 2..location + 6].try_into().unwrap()) as i64,
+            ),
+            other => panic!("no jump at {}: {:x}", location, other),
+        };
+
+        (location as i64 + size + displacement) as u64
+    }
+
+    #[test]
+    fn jump_to_next_instruction_is_removed() {
+        let arena = Bump::new();
+        let mut body = Vec::new_in(&arena);
+        let location = nops_then_jump(&mut body, 3, BranchKind::Jmp);
+        body.push(NOP);
+
+        let mut branches = [Branch {
+            kind: BranchKind::Jmp,
+            location,
+            target: location + 5,
+        }];
+        let (out, offsets) = relax(&arena, &body, &mut branches);
+
+        assert_eq!(&out[..], &[NOP; 4]);
+        assert_eq!(offsets.new_offset(location + 5), 3);
+    }
+
+    #[test]
+    fn nested_ifs_get_short_jumps() {
+        // like the code for nested ifs: every branch skips over the ones inside of it
+        let arena = Bump::new();
+        let mut body = Vec::new_in(&arena);
+        let mut locations = std::vec::Vec::new();
+        for _ in 0..8 {
+            locations.push(nops_then_jump(&mut body, 4, BranchKind::Jne));
+        }
+        body.extend([NOP; 4]);
+        let end = body.len() as u64;
+        let mut branches: std::vec::Vec<_> = locations
+            .iter()
+            .map(|location| Branch {
+                kind: BranchKind::Jne,
+                location: *location,
+                target: end,
+            })
+            .collect();
+        let long_size = body.len();
+
+        let (out, offsets) = relax(&arena, &body, &mut branches);
+
+        // every jne went from 6 to 2 bytes
+        assert_eq!(out.len(), long_size - 8 * 4);
+        for location in locations {
+            assert_eq!(
+                target_of(&out, offsets.new_offset(location)),
+                offsets.new_offset(end)
+            );
+        }
+    }
+
+    #[test]
+    fn shrinking_brings_jumps_into_range() {
+        // the first jump only fits in a short form once the second one is short
+        let arena = Bump::new();
+        let mut body = Vec::new_in(&arena);
+        let first = nops_then_jump(&mut body, 0, BranchKind::Jmp);
+        let second = nops_then_jump(&mut body, 120, BranchKind::Jmp);
+        body.extend([NOP; 4]);
+        let target = body.len() as u64;
+        body.push(NOP);
+
+        let mut branches = [
+            Branch {
+                kind: BranchKind::Jmp,
+                location: first,
+                target,
+            },
+            Branch {
+                kind: BranchKind::Jmp,
+                location: second,
+                target,
+            },
+        ];
+        let (out, offsets) = relax(&arena, &body, &mut branches);
+
+        assert_eq!(out.len(), body.len() - 2 * 3);
+        assert_eq!(out[0], 0xEB);
+        assert_eq!(target_of(&out, 0), offsets.new_offset(target));
+        assert_eq!(
+            target_of(&out, offsets.new_offset(second)),
+            offsets.new_offset(target)
+        );
+    }
+
+    #[test]
+    fn backward_jumps_are_relaxed() {
+        let arena = Bump::new();
+        let mut body = Vec::new_in(&arena);
+        let far = nops_then_jump(&mut body, 200, BranchKind::Jmp);
+        let near = nops_then_jump(&mut body, 10, BranchKind::Jmp);
+
+        let mut branches = [
+            Branch {
+                kind: BranchKind::Jmp,
+                location: far,
+                target: 0,
+            },
+            Branch {
+                kind: BranchKind::Jmp,
+                location: near,
+                target: far + 5,
+            },
+        ];
+        let (out, offsets) = relax(&arena, &body, &mut branches);
+
+        // the far one stays long, the near one gets short
+        assert_eq!(out.len(), body.len() - 3);
+        assert_eq!(target_of(&out, offsets.new_offset(far)), 0);
+        assert_eq!(
+            target_of(&out, offsets.new_offset(near)),
+            offsets.new_offset(far + 5)
+        );
+    }
+
+    #[test]
+    fn shifted_tables_match_the_new_offsets() {
+        // a removed jump, a short one, and a long one, with every instruction in the table
+        let arena = Bump::new();
+        let mut body = Vec::new_in(&arena);
+        let removed = nops_then_jump(&mut body, 2, BranchKind::Jmp);
+        let short = nops_then_jump(&mut body, 3, BranchKind::Jne);
+        let long = nops_then_jump(&mut body, 1, BranchKind::Jmp);
+        body.extend([NOP; 200]);
+        let end = body.len() as u64;
+
+        let mut branches = [
+            Branch {
+                kind: BranchKind::Jmp,
+                location: removed,
+                target: removed + 5,
+            },
+            Branch {
+                kind: BranchKind::Jne,
+                location: short,
+                target: long,
+            },
+            Branch {
+                kind: BranchKind::Jmp,
+                location: long,
+                target: end,
+            },
+        ];
+        let (out, offsets) = relax(&arena, &body, &mut branches);
+        assert_eq!(out.len(), body.len() - 5 - 4);
+
+        let instructions: std::vec::Vec<u64> = (0..=end)
+            .filter(|offset| {
+                let in_jump = |location: u64, size: u64| (location + 1..location + size);
+                !in_jump(removed, 5).contains(offset)
+                    && !in_jump(short, 6).contains(offset)
+                    && !in_jump(long, 5).contains(offset)
+            })
+            .collect();
+        let mut table = OffsetTable::new_in(&arena);
+        for offset in instructions.iter() {
+            table.track(*offset);
+        }
+        offsets.shift(&mut table);
+
+        let expected: std::vec::Vec<u64> = instructions
+            .iter()
+            .map(|offset| offsets.new_offset(*offset))
+            .collect();
+        assert_eq!(table.iter().collect::<std::vec::Vec<_>>(), expected);
+    }
+}
@@ -1,32 +1,69 @@
+use crate::cost_model::CostModel;
+use crate::generic64::operand::RegisterWidth;
+use crate::listing::{describe_statement, StatementMarker};
+use crate::panic_cleanup::{
+    call_can_panic, expr_makes_owned_value, symbols_handed_on, CallCleanup, CleanupKind,
+    CleanupValue,
+};
+use crate::unsupported::{BuildResult, UnsupportedConstruct};
 use crate::{
-    single_register_floats, single_register_int_builtins, single_register_integers, Backend, Env,
-    Relocation,
+    single_register_floats, single_register_int_builtins, single_register_integers,
+    single_register_layouts, Backend, Env, Relocation, UnwindInfo,
 };
 use bumpalo::collections::Vec;
 use roc_builtins::bitcode::{self, FloatWidth, IntWidth};
+use roc_builtins::repr::{BOOL_FALSE, BOOL_TRUE, BOOL_WIDTH};
 use roc_collections::all::MutMap;
 use roc_error_macros::internal_error;
-use roc_module::symbol::{Interns, ModuleId, Symbol};
-use roc_mono::code_gen_help::CodeGenHelp;
+use roc_module::low_level::LowLevel;
+use roc_module::symbol::{Interns, Symbol};
 use roc_mono::ir::{
-    BranchInfo, JoinPointId, ListLiteralElement, Literal, Param, ProcLayout, SelfRecursive, Stmt,
+    BranchInfo, Call, CallType, Expr, JoinPointId, ListLiteralElement, Literal, Param,
+    SelfRecursive, Stmt,
 };
 use roc_mono::layout::{
     Builtin, InLayout, Layout, LayoutInterner, STLayoutInterner, TagIdIntType, UnionLayout,
 };
-use roc_target::TargetInfo;
+use roc_target::{Endianness, TargetInfo};
 use std::marker::PhantomData;
 
 pub(crate) mod aarch64;
+pub(crate) mod branches;
 #[cfg(test)]
 mod disassembler_test_macro;
+pub(crate) mod operand;
+pub(crate) mod stack_canary;
 pub(crate) mod storage;
 pub(crate) mod x86_64;
 
+use branches::{relax_branches, Branch, BranchKind};
+use stack_canary::{
+    check_stack_canary, store_stack_canary, STACK_CANARY_OFFSET, STACK_CANARY_SIZE,
+};
 use storage::{RegStorage, StorageManager};
 
 // TODO: on all number functions double check and deal with over/underflow.
 
+/// What `Num.isZero`, `Num.isPositive` and `Num.isNegative` compare a number with zero for
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum SignCondition {
+    Zero,
+    Positive,
+    Negative,
+}
+
+impl SignCondition {
+    /// The condition the builtin `predicate` tests for, if it is one of the sign predicates
+    pub fn of_predicate(predicate: Symbol) -> Option<Self> {
+        match predicate {
+            Symbol::NUM_IS_ZERO => Some(SignCondition::Zero),
+            Symbol::NUM_IS_POSITIVE => Some(SignCondition::Positive),
+            Symbol::NUM_IS_NEGATIVE => Some(SignCondition::Negative),
+            _ => None,
+        }
+    }
+}
+
 pub trait CallConv<GeneralReg: RegTrait, FloatReg: RegTrait, ASM: Assembler<GeneralReg, FloatReg>>:
     Sized + Copy
 {
@@ -36,6 +73,11 @@ pub trait CallConv<GeneralReg: RegTrait, FloatReg: RegTrait, ASM: Assembler<Gene
     const GENERAL_PARAM_REGS: &'static [GeneralReg];
     const GENERAL_RETURN_REGS: &'static [GeneralReg];
     const GENERAL_DEFAULT_FREE_REGS: &'static [GeneralReg];
+    /// Registers that hold neither arguments nor return values, and that callers do not expect
+    /// to be preserved, so the prologue and the epilogue can use them.
+    const SCRATCH_REGS: [GeneralReg; 2];
+    /// The callee saved register that holds the host's context with `Env::runtime_context`
+    const RUNTIME_CONTEXT_REG: GeneralReg;
 
     const FLOAT_PARAM_REGS: &'static [FloatReg];
     const FLOAT_RETURN_REGS: &'static [FloatReg];
@@ -43,6 +85,12 @@ pub trait CallConv<GeneralReg: RegTrait, FloatReg: RegTrait, ASM: Assembler<Gene
 
     const SHADOW_SPACE_SIZE: u8;
 
+    /// How many bytes below the stack pointer a procedure that calls nothing may use, without
+    /// moving the stack pointer over them.
+    fn red_zone_size(_target_info: TargetInfo) -> i32 {
+        0
+    }
+
     fn general_callee_saved(reg: &GeneralReg) -> bool;
     #[inline(always)]
     fn general_caller_saved(reg: &GeneralReg) -> bool {
@@ -60,13 +108,38 @@ pub trait CallConv<GeneralReg: RegTrait, FloatReg: RegTrait, ASM: Assembler<Gene
         float_saved_regs: &[FloatReg],
         requested_stack_size: i32,
         fn_call_stack_size: i32,
+        red_zone_size: i32,
     ) -> i32;
+
+    /// `setup_stack`, along with the unwind info that describes the prologue it emitted, for the
+    /// calling conventions whose unwinder needs it in a table next to the code.
+    fn setup_stack_with_unwind_info<'a>(
+        buf: &mut Vec<'a, u8>,
+        general_saved_regs: &[GeneralReg],
+        float_saved_regs: &[FloatReg],
+        requested_stack_size: i32,
+        fn_call_stack_size: i32,
+        red_zone_size: i32,
+    ) -> (i32, Option<std::vec::Vec<u8>>) {
+        let aligned_stack_size = Self::setup_stack(
+            buf,
+            general_saved_regs,
+            float_saved_regs,
+            requested_stack_size,
+            fn_call_stack_size,
+            red_zone_size,
+        );
+
+        (aligned_stack_size, None)
+    }
+
     fn cleanup_stack<'a>(
         buf: &mut Vec<'a, u8>,
         general_saved_regs: &[GeneralReg],
         float_saved_regs: &[FloatReg],
         aligned_stack_size: i32,
         fn_call_stack_size: i32,
+        red_zone_size: i32,
     );
 
     /// load_args updates the storage manager to know where every arg is stored.
@@ -77,7 +150,7 @@ pub trait CallConv<GeneralReg: RegTrait, FloatReg: RegTrait, ASM: Assembler<Gene
         args: &'a [(InLayout<'a>, Symbol)],
         // ret_layout is needed because if it is a complex type, we pass a pointer as the first arg.
         ret_layout: &InLayout<'a>,
-    );
+    ) -> BuildResult;
 
     /// store_args stores the args in registers and on the stack for function calling.
     /// It also updates the amount of temporary stack space needed in the storage manager.
@@ -90,7 +163,7 @@ pub trait CallConv<GeneralReg: RegTrait, FloatReg: RegTrait, ASM: Assembler<Gene
         arg_layouts: &[InLayout<'a>],
         // ret_layout is needed because if it is a complex type, we pass a pointer as the first arg.
         ret_layout: &InLayout<'a>,
-    );
+    ) -> BuildResult;
 
     /// return_complex_symbol returns the specified complex/non-primative symbol.
     /// It uses the layout to determine how the data should be returned.
@@ -100,7 +173,7 @@ pub trait CallConv<GeneralReg: RegTrait, FloatReg: RegTrait, ASM: Assembler<Gene
         layout_interner: &mut STLayoutInterner<'a>,
         sym: &Symbol,
         layout: &InLayout<'a>,
-    );
+    ) -> BuildResult;
 
     /// load_returned_complex_symbol loads a complex symbol that was returned from a function call.
     /// It uses the layout to determine how the data should be loaded into the symbol.
@@ -110,7 +183,7 @@ pub trait CallConv<GeneralReg: RegTrait, FloatReg: RegTrait, ASM: Assembler<Gene
         layout_interner: &mut STLayoutInterner<'a>,
         sym: &Symbol,
         layout: &InLayout<'a>,
-    );
+    ) -> BuildResult;
 }
 
 /// Assembler contains calls to the backend assembly generator.
@@ -121,11 +194,13 @@ pub trait CallConv<GeneralReg: RegTrait, FloatReg: RegTrait, ASM: Assembler<Gene
 /// dst should always come before sources.
 pub trait Assembler<GeneralReg: RegTrait, FloatReg: RegTrait>: Sized + Copy {
     fn abs_reg64_reg64(buf: &mut Vec<'_, u8>, dst: GeneralReg, src: GeneralReg);
+    /// The mask this needs goes in the data section, in the byte order `endianness`.
     fn abs_freg64_freg64(
         buf: &mut Vec<'_, u8>,
         relocs: &mut Vec<'_, Relocation>,
         dst: FloatReg,
         src: FloatReg,
+        endianness: Endianness,
     );
 
     fn add_reg64_reg64_imm32(buf: &mut Vec<'_, u8>, dst: GeneralReg, src1: GeneralReg, imm32: i32);
@@ -147,6 +222,21 @@ pub trait Assembler<GeneralReg: RegTrait, FloatReg: RegTrait>: Sized + Copy {
         src1: GeneralReg,
         src2: GeneralReg,
     );
+    /// `dst = base + index`, leaving `base`, `index`, and the flags as they are.
+    fn lea_reg64_base64_index64(
+        buf: &mut Vec<'_, u8>,
+        dst: GeneralReg,
+        base: GeneralReg,
+        index: GeneralReg,
+    );
+    /// `dst = src1 + (src2 << shift)`, for a shift of at most 3.
+    fn add_shifted_reg64_reg64_reg64(
+        buf: &mut Vec<'_, u8>,
+        dst: GeneralReg,
+        src1: GeneralReg,
+        src2: GeneralReg,
+        shift: u8,
+    );
 
     fn and_reg64_reg64_reg64(
         buf: &mut Vec<'_, u8>,
@@ -199,6 +289,10 @@ pub trait Assembler<GeneralReg: RegTrait, FloatReg: RegTrait>: Sized + Copy {
         ASM: Assembler<GeneralReg, FloatReg>,
         CC: CallConv<GeneralReg, FloatReg, ASM>;
 
+    fn shl_reg64_reg64_imm8(buf: &mut Vec<'_, u8>, dst: GeneralReg, src: GeneralReg, imm: u8);
+    fn shr_reg64_reg64_imm8(buf: &mut Vec<'_, u8>, dst: GeneralReg, src: GeneralReg, imm: u8);
+    fn sar_reg64_reg64_imm8(buf: &mut Vec<'_, u8>, dst: GeneralReg, src: GeneralReg, imm: u8);
+
     fn call(buf: &mut Vec<'_, u8>, relocs: &mut Vec<'_, Relocation>, fn_name: String);
 
     /// Jumps by an offset of offset bytes unconditionally.
@@ -208,6 +302,16 @@ pub trait Assembler<GeneralReg: RegTrait, FloatReg: RegTrait>: Sized + Copy {
 
     fn tail_call(buf: &mut Vec<'_, u8>) -> u64;
 
+    /// The size of a jump of this kind, in its short form if `short` is set.
+    fn jump_size(kind: BranchKind, short: bool) -> u64;
+
+    /// Whether a short jump can go `displacement` bytes.
+    fn fits_short_jump(displacement: i64) -> bool;
+
+    /// Jumps by `offset` bytes from the end of the jump, just like `jmp_imm32` and the jump in
+    /// `jne_reg64_imm64_imm32` do, but only the jump, and in its short form if `short` is set.
+    fn jump(buf: &mut Vec<'_, u8>, kind: BranchKind, offset: i32, short: bool);
+
     /// Jumps by an offset of offset bytes if reg is not equal to imm.
     /// It should always generate the same number of bytes to enable replacement if offset changes.
     /// It returns the base offset to calculate the jump from (generally the instruction after the jump).
@@ -218,19 +322,41 @@ pub trait Assembler<GeneralReg: RegTrait, FloatReg: RegTrait>: Sized + Copy {
         offset: i32,
     ) -> usize;
 
+    /// Loads `imm` from the data section, where it is put in the byte order `endianness`.
     fn mov_freg32_imm32(
         buf: &mut Vec<'_, u8>,
         relocs: &mut Vec<'_, Relocation>,
         dst: FloatReg,
         imm: f32,
+        endianness: Endianness,
     );
+    /// Loads `imm` from the data section, where it is put in the byte order `endianness`.
     fn mov_freg64_imm64(
         buf: &mut Vec<'_, u8>,
         relocs: &mut Vec<'_, Relocation>,
         dst: FloatReg,
         imm: f64,
+        endianness: Endianness,
     );
     fn mov_reg64_imm64(buf: &mut Vec<'_, u8>, dst: GeneralReg, imm: i64);
+    /// Load the address of `data`, which is put in the data section of the object.
+    fn load_local_data_address(
+        buf: &mut Vec<'_, u8>,
+        relocs: &mut Vec<'_, Relocation>,
+        dst: GeneralReg,
+        data: std::vec::Vec<u8>,
+    );
+    /// Load the address of the data symbol `name`, which another object defines.
+    fn load_linked_data_address(
+        buf: &mut Vec<'_, u8>,
+        relocs: &mut Vec<'_, Relocation>,
+        dst: GeneralReg,
+        name: String,
+    );
+    /// Load `value` into `dst` using the shortest encoding available.
+    /// The shortest encodings may clobber the flags, so `flags_live` must be set
+    /// while a comparison result is still waiting to be consumed.
+    fn load_constant(buf: &mut Vec<'_, u8>, dst: GeneralReg, value: i64, flags_live: bool);
     fn mov_freg64_freg64(buf: &mut Vec<'_, u8>, dst: FloatReg, src: FloatReg);
     fn mov_reg64_reg64(buf: &mut Vec<'_, u8>, dst: GeneralReg, src: GeneralReg);
 
@@ -239,6 +365,33 @@ pub trait Assembler<GeneralReg: RegTrait, FloatReg: RegTrait>: Sized + Copy {
     fn mov_reg64_base32(buf: &mut Vec<'_, u8>, dst: GeneralReg, offset: i32);
     fn mov_base32_freg64(buf: &mut Vec<'_, u8>, offset: i32, src: FloatReg);
     fn mov_base32_reg64(buf: &mut Vec<'_, u8>, offset: i32, src: GeneralReg);
+    /// Stores the low 4 bytes of `src` at `offset` from the base pointer.
+    fn mov_base32_reg32(buf: &mut Vec<'_, u8>, offset: i32, src: GeneralReg);
+    /// Stores the low 2 bytes of `src` at `offset` from the base pointer.
+    fn mov_base32_reg16(buf: &mut Vec<'_, u8>, offset: i32, src: GeneralReg);
+    /// Stores the low byte of `src` at `offset` from the base pointer.
+    fn mov_base32_reg8(buf: &mut Vec<'_, u8>, offset: i32, src: GeneralReg);
+    /// Stores `imm` sign extended to 64 bits at `offset` from the base pointer.
+    fn mov_base32_imm32(buf: &mut Vec<'_, u8>, offset: i32, imm: i32);
+    /// Stores the low `width` bits of `imm` at `offset` from the base pointer. A store of 64 bits
+    /// sign extends `imm`, like `mov_base32_imm32`.
+    fn mov_base32_imm(buf: &mut Vec<'_, u8>, offset: i32, imm: i32, width: RegisterWidth);
+
+    /// The general registers that `zero_base32` uses implicitly.
+    /// They must be reserved first, so no live value is in them.
+    const ZERO_FIXED_REGS: &'static [GeneralReg];
+
+    /// Zeroes the `size` bytes at `offset` from the base pointer, `size` being a multiple of 8.
+    /// This is for large areas, small ones are zeroed faster with `mov_base32_imm32`.
+    fn zero_base32(buf: &mut Vec<'_, u8>, offset: i32, size: u32);
+
+    /// The general registers that `read_timestamp_counter` uses implicitly.
+    /// They must be reserved before claiming `dst`, so no live value is in them.
+    const TIMESTAMP_FIXED_REGS: &'static [GeneralReg];
+
+    /// Reads the timestamp counter of the CPU into `dst`. With `serialize`, the read waits for
+    /// the instructions before it, so they are part of the time measured.
+    fn read_timestamp_counter(buf: &mut Vec<'_, u8>, dst: GeneralReg, serialize: bool);
 
     fn mov_reg64_mem64_offset32(
         buf: &mut Vec<'_, u8>,
@@ -252,6 +405,8 @@ pub trait Assembler<GeneralReg: RegTrait, FloatReg: RegTrait>: Sized + Copy {
         offset: i32,
         src: GeneralReg,
     );
+    /// Stores the low byte of `src` at `offset` from the address in `dst`.
+    fn mov_mem8_offset32_reg8(buf: &mut Vec<'_, u8>, dst: GeneralReg, offset: i32, src: GeneralReg);
 
     /// Sign extends the data at `offset` with `size` as it copies it to `dst`
     /// size must be less than or equal to 8.
@@ -259,6 +414,12 @@ pub trait Assembler<GeneralReg: RegTrait, FloatReg: RegTrait>: Sized + Copy {
     /// Zero extends the data at `offset` with `size` as it copies it to `dst`
     /// size must be less than or equal to 8.
     fn movzx_reg64_base32(buf: &mut Vec<'_, u8>, dst: GeneralReg, offset: i32, size: u8);
+    /// Sign extends the low `size` bytes of `src` as it copies them to `dst`
+    /// size must be less than or equal to 8.
+    fn movsx_reg64_reg64(buf: &mut Vec<'_, u8>, dst: GeneralReg, src: GeneralReg, size: u8);
+    /// Zero extends the low `size` bytes of `src` as it copies them to `dst`
+    /// size must be less than or equal to 8.
+    fn movzx_reg64_reg64(buf: &mut Vec<'_, u8>, dst: GeneralReg, src: GeneralReg, size: u8);
 
     fn mov_freg64_stack32(buf: &mut Vec<'_, u8>, dst: FloatReg, offset: i32);
     fn mov_reg64_stack32(buf: &mut Vec<'_, u8>, dst: GeneralReg, offset: i32);
@@ -296,6 +457,8 @@ pub trait Assembler<GeneralReg: RegTrait, FloatReg: RegTrait>: Sized + Copy {
         src1: GeneralReg,
         src2: GeneralReg,
     );
+    /// Multiplies `src` by `imm32`, which is sign-extended to 64 bits
+    fn imul_reg64_reg64_imm32(buf: &mut Vec<'_, u8>, dst: GeneralReg, src: GeneralReg, imm32: i32);
     fn umul_reg64_reg64_reg64<'a, 'r, ASM, CC>(
         buf: &mut Vec<'a, u8>,
         storage_manager: &mut StorageManager<'a, 'r, GeneralReg, FloatReg, ASM, CC>,
@@ -306,18 +469,55 @@ pub trait Assembler<GeneralReg: RegTrait, FloatReg: RegTrait>: Sized + Copy {
         ASM: Assembler<GeneralReg, FloatReg>,
         CC: CallConv<GeneralReg, FloatReg, ASM>;
 
-    fn idiv_reg64_reg64_reg64<'a, 'r, ASM, CC>(
+    /// The general registers that `umul_reg64_reg64_reg64`, and the divisions and remainders
+    /// like `idiv_reg_reg_reg`, use implicitly.
+    /// They must be reserved before loading the operands, so no operand ends up in them.
+    const MUL_DIV_FIXED_REGS: &'static [GeneralReg];
+
+    /// Divides the integers of `width` bits in `src1` and `src2`, rounding toward zero. The
+    /// quotient in `dst` is sign extended to 64 bits. Like the `sdiv` LLVM builds, dividing the
+    /// smallest integer of `width` by -1 traps.
+    fn idiv_reg_reg_reg<'a, 'r, ASM, CC>(
+        buf: &mut Vec<'a, u8>,
+        storage_manager: &mut StorageManager<'a, 'r, GeneralReg, FloatReg, ASM, CC>,
+        width: RegisterWidth,
+        dst: GeneralReg,
+        src1: GeneralReg,
+        src2: GeneralReg,
+    ) where
+        ASM: Assembler<GeneralReg, FloatReg>,
+        CC: CallConv<GeneralReg, FloatReg, ASM>;
+    /// Divides the unsigned integers of `width` bits in `src1` and `src2`. The quotient in `dst`
+    /// is zero extended to 64 bits.
+    fn udiv_reg_reg_reg<'a, 'r, ASM, CC>(
+        buf: &mut Vec<'a, u8>,
+        storage_manager: &mut StorageManager<'a, 'r, GeneralReg, FloatReg, ASM, CC>,
+        width: RegisterWidth,
+        dst: GeneralReg,
+        src1: GeneralReg,
+        src2: GeneralReg,
+    ) where
+        ASM: Assembler<GeneralReg, FloatReg>,
+        CC: CallConv<GeneralReg, FloatReg, ASM>;
+    /// The remainder of dividing the integers of `width` bits in `src1` and `src2`, rounding
+    /// toward zero, so it has the sign of `src1`. It is sign extended to 64 bits in `dst`. Like the
+    /// `srem` LLVM builds, the smallest integer of `width` by -1 traps.
+    fn irem_reg_reg_reg<'a, 'r, ASM, CC>(
         buf: &mut Vec<'a, u8>,
         storage_manager: &mut StorageManager<'a, 'r, GeneralReg, FloatReg, ASM, CC>,
+        width: RegisterWidth,
         dst: GeneralReg,
         src1: GeneralReg,
         src2: GeneralReg,
     ) where
         ASM: Assembler<GeneralReg, FloatReg>,
         CC: CallConv<GeneralReg, FloatReg, ASM>;
-    fn udiv_reg64_reg64_reg64<'a, 'r, ASM, CC>(
+    /// The remainder of dividing the unsigned integers of `width` bits in `src1` and `src2`,
+    /// zero extended to 64 bits in `dst`.
+    fn urem_reg_reg_reg<'a, 'r, ASM, CC>(
         buf: &mut Vec<'a, u8>,
         storage_manager: &mut StorageManager<'a, 'r, GeneralReg, FloatReg, ASM, CC>,
+        width: RegisterWidth,
         dst: GeneralReg,
         src1: GeneralReg,
         src2: GeneralReg,
@@ -399,6 +599,45 @@ pub trait Assembler<GeneralReg: RegTrait, FloatReg: RegTrait>: Sized + Copy {
 
     fn set_if_overflow(buf: &mut Vec<'_, u8>, dst: GeneralReg);
 
+    /// Sets `dst` to whether the integer of `width` bits in `src` meets `condition`, as a `Bool`.
+    /// Only the low `width` bits of `src` are looked at.
+    fn int_sign_test_reg_reg(
+        buf: &mut Vec<'_, u8>,
+        dst: GeneralReg,
+        src: GeneralReg,
+        width: RegisterWidth,
+        signed: bool,
+        condition: SignCondition,
+    );
+
+    /// Sets `dst` to whether the float in `src` meets `condition`, as a `Bool`, with `tmp` to
+    /// compare against. Like in the LLVM backend, NaN meets none of the conditions, and -0.0 is
+    /// zero but neither positive nor negative.
+    fn float_sign_test_reg_freg(
+        buf: &mut Vec<'_, u8>,
+        dst: GeneralReg,
+        src: FloatReg,
+        tmp: FloatReg,
+        width: FloatWidth,
+        condition: SignCondition,
+    );
+
+    /// The number of bytes `patchpoint` emits.
+    const PATCHPOINT_SIZE: usize;
+
+    /// What the instructions this assembler emits cost.
+    const COST_MODEL: &'static dyn CostModel;
+
+    /// Nops at the very start of a procedure, which a runtime can overwrite with a jump to a newer
+    /// version of the procedure. Procedures start at aligned addresses, so one aligned store
+    /// replaces the first instruction, even while another thread is running the procedure.
+    fn patchpoint(buf: &mut Vec<'_, u8>);
+
+    /// Fills the `size` bytes after a procedure, up to where the next one starts. Nothing jumps
+    /// there, so debug builds of the compiler fill them with traps, which stop any code that
+    /// runs into the padding. Otherwise they are as few nops as fit.
+    fn padding(buf: &mut Vec<'_, u8>, size: usize);
+
     fn ret(buf: &mut Vec<'_, u8>);
 }
 
@@ -406,6 +645,62 @@ pub trait RegTrait:
     Copy + PartialEq + Eq + std::hash::Hash + std::fmt::Debug + std::fmt::Display + 'static
 {
     fn value(&self) -> u8;
+
+    /// A dense index for this register, used to key register sets and tables.
+    /// Must be less than 64.
+    fn to_index(&self) -> usize {
+        self.value() as usize
+    }
+}
+
+/// Offsets into the code of a procedure that have to keep pointing at the same byte while bytes
+/// are put in or cut out before them, e.g. when the prologue goes in front of the body, or when
+/// jumps shrink. Whatever records where it is in the code, like a relocation or the return address
+/// of a call, registers that offset here for the patch pass, and reads it back from here after.
+pub(crate) struct OffsetTable<'a> {
+    offsets: Vec<'a, u64>,
+}
+
+impl<'a> OffsetTable<'a> {
+    pub fn new_in(arena: &'a bumpalo::Bump) -> Self {
+        OffsetTable {
+            offsets: Vec::new_in(arena),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.offsets.clear();
+    }
+
+    pub fn track(&mut self, offset: u64) {
+        self.offsets.push(offset);
+    }
+
+    /// With a positive `delta`, that many bytes were put in at `at`, in front of the byte that was
+    /// there. With a negative one, that many bytes were cut out from `at` on. Nothing may point
+    /// into bytes that are cut out, except for the first, which then points at what follows them.
+    pub fn apply_shift(&mut self, at: u64, delta: i64) {
+        let len = delta.unsigned_abs();
+        for offset in self.offsets.iter_mut() {
+            if delta > 0 && *offset >= at {
+                *offset += len;
+            } else if delta < 0 && *offset >= at + len {
+                *offset -= len;
+            } else if delta < 0 && *offset > at {
+                internal_error!(
+                    "the offset {} points into the {} bytes cut out at {}",
+                    offset,
+                    len,
+                    at
+                );
+            }
+        }
+    }
+
+    /// Every offset, in the order they were tracked
+    pub fn iter(&self) -> impl Iterator<Item = u64> + '_ {
+        self.offsets.iter().copied()
+    }
 }
 
 pub struct Backend64Bit<
@@ -422,20 +717,42 @@ pub struct Backend64Bit<
     phantom_cc: PhantomData<CC>,
     env: &'r Env<'a>,
     layout_interner: &'r mut STLayoutInterner<'a>,
-    interns: &'r mut Interns,
-    helper_proc_gen: CodeGenHelp<'a>,
-    helper_proc_symbols: Vec<'a, (Symbol, ProcLayout<'a>)>,
+    interns: &'r Interns,
     buf: Vec<'a, u8>,
     relocs: Vec<'a, Relocation>,
+    // The finalized bytes and relocations of the current procedure.
+    // These are kept around so their capacity is reused by the next procedure.
+    out_buf: Vec<'a, u8>,
+    out_relocs: Vec<'a, Relocation>,
     proc_name: Option<String>,
     is_self_recursive: Option<SelfRecursive>,
 
     last_seen_map: MutMap<Symbol, *const Stmt<'a>>,
     layout_map: MutMap<Symbol, InLayout<'a>>,
-    free_map: MutMap<*const Stmt<'a>, Vec<'a, Symbol>>,
+    free_map: MutMap<*const Stmt<'a>, std::vec::Vec<Symbol>>,
 
     literal_map: MutMap<Symbol, (*const Literal<'a>, *const InLayout<'a>)>,
     join_map: MutMap<JoinPointId, Vec<'a, (u64, u64)>>,
+    /// Every jump in `buf` whose target is known, so they can be shrunk in `finalize`.
+    branches: Vec<'a, Branch>,
+    /// The offsets of the relocations, the cleanup sites and the statement markers, while
+    /// `finalize` moves them from the start of `buf` to the start of `out_buf`.
+    offset_table: OffsetTable<'a>,
+
+    /// With `Env::panic_cleanup`, the refcounted values the procedure owns right now,
+    /// and the calls that can panic so far, relative to the start of `buf`.
+    owned_values: MutMap<Symbol, CleanupKind>,
+    cleanup_sites: std::vec::Vec<CallCleanup>,
+    out_cleanup_sites: std::vec::Vec<CallCleanup>,
+
+    /// With `Env::statement_markers`, where the code of every statement so far starts, relative
+    /// to the start of `buf`.
+    statement_markers: std::vec::Vec<StatementMarker>,
+    out_statement_markers: std::vec::Vec<StatementMarker>,
+
+    /// The unwind info of the prologue `finalize` emitted,
+    /// see `CallConv::setup_stack_with_unwind_info`.
+    out_unwind_info: Option<UnwindInfo>,
 
     storage_manager: StorageManager<'a, 'r, GeneralReg, FloatReg, ASM, CC>,
 }
@@ -451,7 +768,7 @@ pub fn new_backend_64bit<
 >(
     env: &'r Env<'a>,
     target_info: TargetInfo,
-    interns: &'r mut Interns,
+    interns: &'r Interns,
     layout_interner: &'r mut STLayoutInterner<'a>,
 ) -> Backend64Bit<'a, 'r, GeneralReg, FloatReg, ASM, CC> {
     Backend64Bit {
@@ -460,17 +777,25 @@ pub fn new_backend_64bit<
         env,
         interns,
         layout_interner,
-        helper_proc_gen: CodeGenHelp::new(env.arena, target_info, env.module_id),
-        helper_proc_symbols: bumpalo::vec![in env.arena],
         proc_name: None,
         is_self_recursive: None,
         buf: bumpalo::vec![in env.arena],
         relocs: bumpalo::vec![in env.arena],
+        out_buf: bumpalo::vec![in env.arena],
+        out_relocs: bumpalo::vec![in env.arena],
         last_seen_map: MutMap::default(),
         layout_map: MutMap::default(),
         free_map: MutMap::default(),
         literal_map: MutMap::default(),
         join_map: MutMap::default(),
+        branches: bumpalo::vec![in env.arena],
+        offset_table: OffsetTable::new_in(env.arena),
+        owned_values: MutMap::default(),
+        cleanup_sites: std::vec::Vec::new(),
+        out_cleanup_sites: std::vec::Vec::new(),
+        statement_markers: std::vec::Vec::new(),
+        out_statement_markers: std::vec::Vec::new(),
+        out_unwind_info: None,
         storage_manager: storage::new_storage_manager(env, target_info),
     }
 }
@@ -506,40 +831,30 @@ impl<
     fn interner(&self) -> &STLayoutInterner<'a> {
         self.layout_interner
     }
-    fn module_interns_helpers_mut(
-        &mut self,
-    ) -> (
-        ModuleId,
-        &mut STLayoutInterner<'a>,
-        &mut Interns,
-        &mut CodeGenHelp<'a>,
-    ) {
-        (
-            self.env.module_id,
-            self.layout_interner,
-            self.interns,
-            &mut self.helper_proc_gen,
-        )
-    }
-    fn helper_proc_gen_mut(&mut self) -> &mut CodeGenHelp<'a> {
-        &mut self.helper_proc_gen
-    }
-    fn helper_proc_symbols_mut(&mut self) -> &mut Vec<'a, (Symbol, ProcLayout<'a>)> {
-        &mut self.helper_proc_symbols
-    }
-    fn helper_proc_symbols(&self) -> &Vec<'a, (Symbol, ProcLayout<'a>)> {
-        &self.helper_proc_symbols
+    fn cost_model(&self) -> &'static dyn CostModel {
+        ASM::COST_MODEL
     }
-
     fn reset(&mut self, name: String, is_self_recursive: SelfRecursive) {
+        self.storage_manager.stats.reset(&name);
+        if self.env.stats.borrow().is_timed() {
+            self.storage_manager.stats.start_timer();
+        }
         self.proc_name = Some(name);
         self.is_self_recursive = Some(is_self_recursive);
         self.last_seen_map.clear();
         self.layout_map.clear();
         self.join_map.clear();
         self.free_map.clear();
+        self.branches.clear();
+        self.owned_values.clear();
+        self.cleanup_sites.clear();
+        self.statement_markers.clear();
         self.buf.clear();
         self.storage_manager.reset();
+        if self.env.stack_canary.is_some() {
+            let offset = self.storage_manager.claim_stack_canary();
+            debug_assert_eq!(offset, STACK_CANARY_OFFSET);
+        }
     }
 
     fn literal_map(&mut self) -> &mut MutMap<Symbol, (*const Literal<'a>, *const InLayout<'a>)> {
@@ -554,116 +869,211 @@ impl<
         &mut self.layout_map
     }
 
-    fn set_free_map(&mut self, map: MutMap<*const Stmt<'a>, Vec<'a, Symbol>>) {
+    fn set_free_map(&mut self, map: MutMap<*const Stmt<'a>, std::vec::Vec<Symbol>>) {
         self.free_map = map;
     }
 
-    fn free_map(&mut self) -> &mut MutMap<*const Stmt<'a>, Vec<'a, Symbol>> {
+    fn free_map(&mut self) -> &mut MutMap<*const Stmt<'a>, std::vec::Vec<Symbol>> {
         &mut self.free_map
     }
 
-    fn finalize(&mut self) -> (Vec<u8>, Vec<Relocation>) {
-        let mut out = bumpalo::vec![in self.env.arena];
+    fn finalize(&mut self) -> (&[u8], &[Relocation]) {
+        self.out_buf.clear();
+        self.out_relocs.clear();
+
+        // A procedure that calls nothing, and has nothing but the canary on the stack, can not
+        // write past its frame. It goes without a canary, and without the slot for it.
+        let mut stack_size = self.storage_manager.stack_size();
+        let stack_canary = self.env.stack_canary.filter(|_| {
+            stack_size > STACK_CANARY_SIZE || self.storage_manager.fn_call_stack_size() > 0
+        });
+        if self.env.stack_canary.is_some() && stack_canary.is_none() {
+            stack_size -= STACK_CANARY_SIZE;
+        }
+
+        if self.env.patchpoints {
+            ASM::patchpoint(&mut self.out_buf);
+        }
+
+        // A procedure that calls nothing can keep its frame below the stack pointer.
+        let is_leaf = stack_canary.is_none()
+            && self.storage_manager.fn_call_stack_size() == 0
+            && !self
+                .relocs
+                .iter()
+                .any(|reloc| matches!(reloc, Relocation::LinkedFunction { .. }));
+        let red_zone_size = if is_leaf {
+            CC::red_zone_size(self.target_info)
+        } else {
+            0
+        };
 
         // Setup stack.
         let used_general_regs = self.storage_manager.general_used_callee_saved_regs();
         let used_float_regs = self.storage_manager.float_used_callee_saved_regs();
-        let aligned_stack_size = CC::setup_stack(
-            &mut out,
+        let prologue_offset = self.out_buf.len() as u64;
+        let (aligned_stack_size, unwind_info) = CC::setup_stack_with_unwind_info(
+            &mut self.out_buf,
             &used_general_regs,
             &used_float_regs,
-            self.storage_manager.stack_size() as i32,
+            stack_size as i32,
             self.storage_manager.fn_call_stack_size() as i32,
+            red_zone_size,
         );
-        let setup_offset = out.len();
+        self.out_unwind_info = unwind_info.map(|info| UnwindInfo {
+            prologue_offset,
+            info,
+        });
+        if let Some(canary) = stack_canary {
+            store_stack_canary::<_, _, ASM, CC>(&mut self.out_buf, &mut self.out_relocs, canary);
+        }
+        let setup_offset = self.out_buf.len();
 
         // Deal with jumps to the return address.
-        let old_relocs = std::mem::replace(&mut self.relocs, bumpalo::vec![in self.env.arena]);
-
-        // Check if their is an unnessary jump to return right at the end of the function.
-        let mut end_jmp_size = 0;
-        for reloc in old_relocs
-            .iter()
-            .filter(|reloc| matches!(reloc, Relocation::JmpToReturn { .. }))
-        {
-            if let Relocation::JmpToReturn {
-                inst_loc,
-                inst_size,
-                ..
-            } = reloc
-            {
-                if *inst_loc as usize + *inst_size as usize == self.buf.len() {
-                    end_jmp_size = *inst_size as usize;
-                    break;
-                }
-            }
-        }
+        // This does not allocate, the old buffer is put back once it has been drained.
+        let mut old_relocs = std::mem::replace(&mut self.relocs, bumpalo::vec![in self.env.arena]);
 
-        // Update jumps to returns.
-        let ret_offset = self.buf.len() - end_jmp_size;
+        // Update jumps to returns. They go to the stack cleanup right after the body.
+        let ret_offset = self.buf.len() as u64;
         let mut tmp = bumpalo::vec![in self.env.arena];
-        for reloc in old_relocs
-            .iter()
-            .filter(|reloc| matches!(reloc, Relocation::JmpToReturn { .. }))
-        {
+        for reloc in old_relocs.iter() {
             if let Relocation::JmpToReturn {
-                inst_loc,
-                inst_size,
-                offset,
+                inst_loc, offset, ..
             } = reloc
             {
-                if *inst_loc as usize + *inst_size as usize != self.buf.len() {
-                    self.update_jmp_imm32_offset(&mut tmp, *inst_loc, *offset, ret_offset as u64);
-                }
+                self.update_jmp_imm32_offset(&mut tmp, *inst_loc, *offset, ret_offset);
             }
         }
 
-        // Add function body.
-        out.extend(&self.buf[..self.buf.len() - end_jmp_size]);
+        // Add function body, with its jumps made as small as they can be.
+        // Among others, this drops the jump to return at the end of the body.
+        let offsets = relax_branches::<_, _, ASM>(
+            self.env.arena,
+            &self.buf,
+            &mut self.branches,
+            &mut self.out_buf,
+        );
+
+        if let Some(canary) = stack_canary {
+            check_stack_canary::<_, _, ASM, CC>(
+                self.env.arena,
+                &mut self.out_buf,
+                &mut self.out_relocs,
+                canary,
+                self.env.host_symbols.panic,
+            );
+        }
 
         // Cleanup stack.
         CC::cleanup_stack(
-            &mut out,
+            &mut self.out_buf,
             &used_general_regs,
             &used_float_regs,
             aligned_stack_size,
             self.storage_manager.fn_call_stack_size() as i32,
+            red_zone_size,
         );
-        ASM::ret(&mut out);
+        ASM::ret(&mut self.out_buf);
+
+        // Move the other relocs and the cleanup sites past the shrunk jumps and the stack setup.
+        old_relocs.retain(|reloc| !matches!(reloc, Relocation::JmpToReturn { .. }));
+        self.offset_table.clear();
+        for reloc in old_relocs.iter() {
+            match reloc {
+                Relocation::LocalData { offset, .. }
+                | Relocation::LinkedData { offset, .. }
+                | Relocation::LinkedFunction { offset, .. } => self.offset_table.track(*offset),
+                Relocation::JmpToReturn { .. } => unreachable!(),
+            }
+        }
+        for site in self.cleanup_sites.iter() {
+            self.offset_table.track(site.return_offset);
+        }
+        for marker in self.statement_markers.iter() {
+            self.offset_table.track(marker.offset);
+        }
+        offsets.shift(&mut self.offset_table);
+        self.offset_table.apply_shift(0, setup_offset as i64);
 
-        // Update other relocs to include stack setup offset.
-        let mut out_relocs = bumpalo::vec![in self.env.arena];
-        out_relocs.extend(
+        let mut moved = self.offset_table.iter();
+        self.out_relocs.extend(
             old_relocs
-                .into_iter()
-                .filter(|reloc| !matches!(reloc, Relocation::JmpToReturn { .. }))
-                .map(|reloc| match reloc {
-                    Relocation::LocalData { offset, data } => Relocation::LocalData {
-                        offset: offset + setup_offset as u64,
-                        data,
-                    },
-                    Relocation::LinkedData { offset, name } => Relocation::LinkedData {
-                        offset: offset + setup_offset as u64,
-                        name,
-                    },
-                    Relocation::LinkedFunction { offset, name } => Relocation::LinkedFunction {
-                        offset: offset + setup_offset as u64,
-                        name,
-                    },
+                .drain(..)
+                .zip(moved.by_ref())
+                .map(|(reloc, offset)| match reloc {
+                    Relocation::LocalData { data, .. } => Relocation::LocalData { offset, data },
+                    Relocation::LinkedData { name, .. } => Relocation::LinkedData { offset, name },
+                    Relocation::LinkedFunction { name, .. } => {
+                        Relocation::LinkedFunction { offset, name }
+                    }
                     Relocation::JmpToReturn { .. } => unreachable!(),
                 }),
         );
-        (out, out_relocs)
+        self.relocs = old_relocs;
+
+        self.out_cleanup_sites.clear();
+        self.out_cleanup_sites
+            .extend(self.cleanup_sites.drain(..).zip(moved.by_ref()).map(
+                |(site, return_offset)| CallCleanup {
+                    return_offset,
+                    values: site.values,
+                },
+            ));
+
+        self.out_statement_markers.clear();
+        self.out_statement_markers
+            .extend(
+                self.statement_markers
+                    .drain(..)
+                    .zip(moved)
+                    .map(|(marker, offset)| StatementMarker {
+                        offset,
+                        statement: marker.statement,
+                    }),
+            );
+
+        self.storage_manager
+            .stats
+            .record_finished(self.out_buf.len(), aligned_stack_size as u32);
+        self.env
+            .stats
+            .borrow_mut()
+            .record_proc(&self.storage_manager.stats);
+
+        (&self.out_buf[..], &self.out_relocs[..])
+    }
+
+    fn cleanup_sites(&self) -> &[CallCleanup] {
+        &self.out_cleanup_sites
+    }
+
+    fn mark_statement(&mut self, stmt: &Stmt<'a>) {
+        self.statement_markers.push(StatementMarker {
+            offset: self.buf.len() as u64,
+            statement: describe_statement(stmt, self.interns),
+        });
+    }
+
+    fn statement_markers(&self) -> &[StatementMarker] {
+        &self.out_statement_markers
+    }
+
+    fn unwind_info(&self) -> Option<&UnwindInfo> {
+        self.out_unwind_info.as_ref()
     }
 
-    fn load_args(&mut self, args: &'a [(InLayout<'a>, Symbol)], ret_layout: &InLayout<'a>) {
+    fn load_args(
+        &mut self,
+        args: &'a [(InLayout<'a>, Symbol)],
+        ret_layout: &InLayout<'a>,
+    ) -> BuildResult {
         CC::load_args(
             &mut self.buf,
             &mut self.storage_manager,
             self.layout_interner,
             args,
             ret_layout,
-        );
+        )
     }
 
     /// Used for generating wrappers for malloc/realloc/free
@@ -681,7 +1091,7 @@ impl<
         args: &[Symbol],
         arg_layouts: &[InLayout<'a>],
         ret_layout: &InLayout<'a>,
-    ) {
+    ) -> BuildResult {
         if let Some(SelfRecursive::SelfRecursive(id)) = self.is_self_recursive {
             if &fn_name == self.proc_name.as_ref().unwrap() && self.join_map.contains_key(&id) {
                 return self.build_jump(&id, args, arg_layouts, ret_layout);
@@ -689,7 +1099,30 @@ impl<
         }
         // Save used caller saved regs.
         self.storage_manager
-            .push_used_caller_saved_regs_to_stack(&mut self.buf);
+            .push_used_caller_saved_regs_to_stack(&mut self.buf, args);
+
+        // The values a panic in the callee would have to release have to be in the frame.
+        // Its arguments are left out, the callee may consume them.
+        let cleanup_values = if self.env.panic_cleanup && call_can_panic(&fn_name) {
+            let mut owned: std::vec::Vec<_> = self
+                .owned_values
+                .iter()
+                .filter(|(sym, _)| !args.contains(sym) && self.storage_manager.has_storage(sym))
+                .map(|(sym, kind)| (*sym, *kind))
+                .collect();
+            owned.sort();
+
+            let mut values = std::vec::Vec::with_capacity(owned.len());
+            for (sym, kind) in owned {
+                self.storage_manager
+                    .ensure_symbol_on_stack(&mut self.buf, &sym);
+                let (offset, _) = self.storage_manager.stack_offset_and_size(&sym);
+                values.push(CleanupValue { offset, kind });
+            }
+            Some(values)
+        } else {
+            None
+        };
 
         // Put values in param regs or on top of the stack.
         CC::store_args(
@@ -700,16 +1133,32 @@ impl<
             args,
             arg_layouts,
             ret_layout,
-        );
+        )?;
 
         // Call function and generate reloc.
         ASM::call(&mut self.buf, &mut self.relocs, fn_name);
+        if let Some(values) = cleanup_values {
+            self.cleanup_sites.push(CallCleanup {
+                return_offset: self.buf.len() as u64,
+                values,
+            });
+        }
 
         // move return value to dst.
         match *ret_layout {
             single_register_integers!() => {
                 let dst_reg = self.storage_manager.claim_general_reg(&mut self.buf, dst);
-                ASM::mov_reg64_reg64(&mut self.buf, dst_reg, CC::GENERAL_RETURN_REGS[0]);
+                if *ret_layout == Layout::BOOL {
+                    // Only the low byte is the `Bool`, a builtin may leave anything above it.
+                    ASM::movzx_reg64_reg64(
+                        &mut self.buf,
+                        dst_reg,
+                        CC::GENERAL_RETURN_REGS[0],
+                        BOOL_WIDTH.stack_size() as u8,
+                    );
+                } else {
+                    ASM::mov_reg64_reg64(&mut self.buf, dst_reg, CC::GENERAL_RETURN_REGS[0]);
+                }
             }
             single_register_floats!() => {
                 let dst_reg = self.storage_manager.claim_float_reg(&mut self.buf, dst);
@@ -729,11 +1178,12 @@ impl<
                             self.layout_interner,
                             dst,
                             ret_layout,
-                        );
+                        )?;
                     }
                 }
             }
         }
+        Ok(())
     }
 
     fn build_switch(
@@ -743,7 +1193,14 @@ impl<
         branches: &'a [(u64, BranchInfo<'a>, Stmt<'a>)],
         default_branch: &(BranchInfo<'a>, &'a Stmt<'a>),
         ret_layout: &InLayout<'a>,
-    ) {
+    ) -> BuildResult {
+        // A value is compared with as the sign extended 32-bit immediate of an instruction.
+        if branches.iter().any(|(val, _, _)| *val > i32::MAX as u64) {
+            return Err(UnsupportedConstruct::Expr(
+                "switches on values greater than i32::MAX",
+            ));
+        }
+
         // Switches are a little complex due to keeping track of jumps.
         // In general I am trying to not have to loop over things multiple times or waste memory.
         // The basic plan is to make jumps to nowhere and then correct them once we know the correct address.
@@ -765,7 +1222,7 @@ impl<
 
             // Build all statements in this branch. Using storage as from before any branch.
             self.storage_manager = base_storage.clone();
-            self.build_stmt(stmt, ret_layout);
+            self.build_stmt(stmt, ret_layout)?;
 
             // Build unconditional jump to the end of this switch.
             // Since we don't know the offset yet, set it to 0 and overwrite later.
@@ -780,6 +1237,11 @@ impl<
             for (i, byte) in tmp.iter().enumerate() {
                 self.buf[jne_location + i] = *byte;
             }
+            self.branches.push(Branch {
+                kind: BranchKind::Jne,
+                location: (start_offset as u64) - ASM::jump_size(BranchKind::Jne, false),
+                target: end_offset as u64,
+            });
 
             // Update important storage information to avoid overwrites.
             max_branch_stack_size =
@@ -790,7 +1252,7 @@ impl<
         self.storage_manager
             .update_stack_size(max_branch_stack_size);
         let (_branch_info, stmt) = default_branch;
-        self.build_stmt(stmt, ret_layout);
+        self.build_stmt(stmt, ret_layout)?;
 
         // Update all return jumps to jump past the default case.
         let ret_offset = self.buf.len();
@@ -802,6 +1264,7 @@ impl<
                 ret_offset as u64,
             );
         }
+        Ok(())
     }
 
     fn build_join(
@@ -811,25 +1274,32 @@ impl<
         body: &'a Stmt<'a>,
         remainder: &'a Stmt<'a>,
         ret_layout: &InLayout<'a>,
-    ) {
+    ) -> BuildResult {
         // Free everything to the stack to make sure they don't get messed up when looping back to this point.
         // TODO: look into a nicer solution.
         self.storage_manager.free_all_to_stack(&mut self.buf);
 
         // Ensure all the joinpoint parameters have storage locations.
         // On jumps to the joinpoint, we will overwrite those locations as a way to "pass parameters" to the joinpoint.
-        self.storage_manager
-            .setup_joinpoint(self.layout_interner, &mut self.buf, id, parameters);
+        self.storage_manager.setup_joinpoint(
+            self.layout_interner,
+            &mut self.buf,
+            id,
+            parameters,
+        )?;
 
         self.join_map.insert(*id, bumpalo::vec![in self.env.arena]);
 
         // Build remainder of function first. It is what gets run and jumps to join.
-        self.build_stmt(remainder, ret_layout);
+        self.build_stmt(remainder, ret_layout)?;
 
         let join_location = self.buf.len() as u64;
 
+        // The jumps may have handed on any value the body could see.
+        self.owned_values.clear();
+
         // Build all statements in body.
-        self.build_stmt(body, ret_layout);
+        self.build_stmt(body, ret_layout)?;
 
         // Overwrite the all jumps to the joinpoint with the correct offset.
         let mut tmp = bumpalo::vec![in self.env.arena];
@@ -841,6 +1311,7 @@ impl<
             tmp.clear();
             self.update_jmp_imm32_offset(&mut tmp, jmp_location, start_offset, join_location);
         }
+        Ok(())
     }
 
     fn build_jump(
@@ -849,9 +1320,17 @@ impl<
         args: &[Symbol],
         arg_layouts: &[InLayout<'a>],
         _ret_layout: &InLayout<'a>,
-    ) {
-        self.storage_manager
-            .setup_jump(self.layout_interner, &mut self.buf, id, args, arg_layouts);
+    ) -> BuildResult {
+        self.storage_manager.setup_jump(
+            self.layout_interner,
+            &mut self.buf,
+            id,
+            args,
+            arg_layouts,
+        )?;
+        for arg in args {
+            self.owned_values.remove(arg);
+        }
 
         let jmp_location = self.buf.len();
         let start_offset = ASM::jmp_imm32(&mut self.buf, 0x1234_5678);
@@ -861,9 +1340,10 @@ impl<
         } else {
             internal_error!("Jump: unknown point specified to jump to: {:?}", id);
         }
+        Ok(())
     }
 
-    fn build_num_abs(&mut self, dst: &Symbol, src: &Symbol, layout: &InLayout<'a>) {
+    fn build_num_abs(&mut self, dst: &Symbol, src: &Symbol, layout: &InLayout<'a>) -> BuildResult {
         match self.interner().get(*layout) {
             Layout::Builtin(Builtin::Int(IntWidth::I64 | IntWidth::U64)) => {
                 let dst_reg = self.storage_manager.claim_general_reg(&mut self.buf, dst);
@@ -873,13 +1353,26 @@ impl<
             Layout::Builtin(Builtin::Float(FloatWidth::F64)) => {
                 let dst_reg = self.storage_manager.claim_float_reg(&mut self.buf, dst);
                 let src_reg = self.storage_manager.load_to_float_reg(&mut self.buf, src);
-                ASM::abs_freg64_freg64(&mut self.buf, &mut self.relocs, dst_reg, src_reg);
+                ASM::abs_freg64_freg64(
+                    &mut self.buf,
+                    &mut self.relocs,
+                    dst_reg,
+                    src_reg,
+                    self.target_info.endianness(),
+                );
             }
-            x => todo!("NumAbs: layout, {:?}", x),
+            x => return Err(UnsupportedConstruct::low_level_with(LowLevel::NumAbs, &x)),
         }
+        Ok(())
     }
 
-    fn build_num_add(&mut self, dst: &Symbol, src1: &Symbol, src2: &Symbol, layout: &InLayout<'a>) {
+    fn build_num_add(
+        &mut self,
+        dst: &Symbol,
+        src1: &Symbol,
+        src2: &Symbol,
+        layout: &InLayout<'a>,
+    ) -> BuildResult {
         match self.layout_interner.get(*layout) {
             Layout::Builtin(Builtin::Int(quadword_and_smaller!())) => {
                 let dst_reg = self.storage_manager.claim_general_reg(&mut self.buf, dst);
@@ -889,7 +1382,11 @@ impl<
                 let src2_reg = self
                     .storage_manager
                     .load_to_general_reg(&mut self.buf, src2);
-                ASM::add_reg64_reg64_reg64(&mut self.buf, dst_reg, src1_reg, src2_reg);
+                if adds_with_lea(dst_reg, src1_reg, src2_reg) {
+                    ASM::lea_reg64_base64_index64(&mut self.buf, dst_reg, src1_reg, src2_reg);
+                } else {
+                    ASM::add_reg64_reg64_reg64(&mut self.buf, dst_reg, src1_reg, src2_reg);
+                }
             }
             Layout::Builtin(Builtin::Float(FloatWidth::F64)) => {
                 let dst_reg = self.storage_manager.claim_float_reg(&mut self.buf, dst);
@@ -903,8 +1400,9 @@ impl<
                 let src2_reg = self.storage_manager.load_to_float_reg(&mut self.buf, src2);
                 ASM::add_freg32_freg32_freg32(&mut self.buf, dst_reg, src1_reg, src2_reg);
             }
-            x => todo!("NumAdd: layout, {:?}", x),
+            x => return Err(UnsupportedConstruct::low_level_with(LowLevel::NumAdd, &x)),
         }
+        Ok(())
     }
 
     fn build_num_add_checked(
@@ -914,7 +1412,7 @@ impl<
         src2: &Symbol,
         num_layout: &InLayout<'a>,
         return_layout: &InLayout<'a>,
-    ) {
+    ) -> BuildResult {
         use Builtin::Int;
 
         let buf = &mut self.buf;
@@ -945,20 +1443,23 @@ impl<
                 self.free_symbol(&Symbol::DEV_TMP);
                 self.free_symbol(&Symbol::DEV_TMP2);
             }
-            Layout::Builtin(Int(IntWidth::U64 | IntWidth::U32 | IntWidth::U16 | IntWidth::U8)) => {
-                todo!("addChecked for unsigned integers")
-            }
-            Layout::Builtin(Builtin::Float(FloatWidth::F64)) => {
-                todo!("addChecked for f64")
-            }
-            Layout::Builtin(Builtin::Float(FloatWidth::F32)) => {
-                todo!("addChecked for f32")
+            x => {
+                return Err(UnsupportedConstruct::low_level_with(
+                    LowLevel::NumAddChecked,
+                    &x,
+                ))
             }
-            x => todo!("NumAdd: layout, {:?}", x),
         }
+        Ok(())
     }
 
-    fn build_num_mul(&mut self, dst: &Symbol, src1: &Symbol, src2: &Symbol, layout: &InLayout<'a>) {
+    fn build_num_mul(
+        &mut self,
+        dst: &Symbol,
+        src1: &Symbol,
+        src2: &Symbol,
+        layout: &InLayout<'a>,
+    ) -> BuildResult {
         use Builtin::Int;
 
         match self.layout_interner.get(*layout) {
@@ -973,20 +1474,22 @@ impl<
                 ASM::imul_reg64_reg64_reg64(&mut self.buf, dst_reg, src1_reg, src2_reg);
             }
             Layout::Builtin(Int(IntWidth::U64 | IntWidth::U32 | IntWidth::U16 | IntWidth::U8)) => {
-                let dst_reg = self.storage_manager.claim_general_reg(&mut self.buf, dst);
-                let src1_reg = self
-                    .storage_manager
-                    .load_to_general_reg(&mut self.buf, src1);
-                let src2_reg = self
-                    .storage_manager
-                    .load_to_general_reg(&mut self.buf, src2);
-
-                ASM::umul_reg64_reg64_reg64(
+                self.storage_manager.with_reserved_general_regs(
                     &mut self.buf,
-                    &mut self.storage_manager,
-                    dst_reg,
-                    src1_reg,
-                    src2_reg,
+                    ASM::MUL_DIV_FIXED_REGS,
+                    |storage_manager, buf| {
+                        let dst_reg = storage_manager.claim_general_reg(buf, dst);
+                        let src1_reg = storage_manager.load_to_general_reg(buf, src1);
+                        let src2_reg = storage_manager.load_to_general_reg(buf, src2);
+
+                        ASM::umul_reg64_reg64_reg64(
+                            buf,
+                            storage_manager,
+                            dst_reg,
+                            src1_reg,
+                            src2_reg,
+                        );
+                    },
                 );
             }
             Layout::Builtin(Builtin::Float(FloatWidth::F64)) => {
@@ -1001,48 +1504,53 @@ impl<
                 let src2_reg = self.storage_manager.load_to_float_reg(&mut self.buf, src2);
                 ASM::mul_freg32_freg32_freg32(&mut self.buf, dst_reg, src1_reg, src2_reg);
             }
-            x => todo!("NumMul: layout, {:?}", x),
+            x => return Err(UnsupportedConstruct::low_level_with(LowLevel::NumMul, &x)),
         }
+        Ok(())
     }
 
-    fn build_num_div(&mut self, dst: &Symbol, src1: &Symbol, src2: &Symbol, layout: &InLayout<'a>) {
+    fn build_num_div(
+        &mut self,
+        dst: &Symbol,
+        src1: &Symbol,
+        src2: &Symbol,
+        layout: &InLayout<'a>,
+    ) -> BuildResult {
         match self.layout_interner.get(*layout) {
-            Layout::Builtin(Builtin::Int(
-                IntWidth::I64 | IntWidth::I32 | IntWidth::I16 | IntWidth::I8,
-            )) => {
-                let dst_reg = self.storage_manager.claim_general_reg(&mut self.buf, dst);
-                let src1_reg = self
-                    .storage_manager
-                    .load_to_general_reg(&mut self.buf, src1);
-                let src2_reg = self
-                    .storage_manager
-                    .load_to_general_reg(&mut self.buf, src2);
-
-                ASM::idiv_reg64_reg64_reg64(
-                    &mut self.buf,
-                    &mut self.storage_manager,
-                    dst_reg,
-                    src1_reg,
-                    src2_reg,
-                );
-            }
-            Layout::Builtin(Builtin::Int(
-                IntWidth::U64 | IntWidth::U32 | IntWidth::U16 | IntWidth::U8,
-            )) => {
-                let dst_reg = self.storage_manager.claim_general_reg(&mut self.buf, dst);
-                let src1_reg = self
-                    .storage_manager
-                    .load_to_general_reg(&mut self.buf, src1);
-                let src2_reg = self
-                    .storage_manager
-                    .load_to_general_reg(&mut self.buf, src2);
-
-                ASM::udiv_reg64_reg64_reg64(
+            Layout::Builtin(Builtin::Int(int_width))
+                if !matches!(int_width, IntWidth::I128 | IntWidth::U128) =>
+            {
+                // divide at the width of the layout, so only its bits count, and the smallest
+                // signed integer divided by -1 traps like it does with LLVM
+                let width = RegisterWidth::of_int(int_width);
+                self.storage_manager.with_reserved_general_regs(
                     &mut self.buf,
-                    &mut self.storage_manager,
-                    dst_reg,
-                    src1_reg,
-                    src2_reg,
+                    ASM::MUL_DIV_FIXED_REGS,
+                    |storage_manager, buf| {
+                        let dst_reg = storage_manager.claim_general_reg(buf, dst);
+                        let src1_reg = storage_manager.load_to_general_reg(buf, src1);
+                        let src2_reg = storage_manager.load_to_general_reg(buf, src2);
+
+                        if int_width.is_signed() {
+                            ASM::idiv_reg_reg_reg(
+                                buf,
+                                storage_manager,
+                                width,
+                                dst_reg,
+                                src1_reg,
+                                src2_reg,
+                            );
+                        } else {
+                            ASM::udiv_reg_reg_reg(
+                                buf,
+                                storage_manager,
+                                width,
+                                dst_reg,
+                                src1_reg,
+                                src2_reg,
+                            );
+                        }
+                    },
                 );
             }
             Layout::Builtin(Builtin::Float(FloatWidth::F64)) => {
@@ -1057,22 +1565,88 @@ impl<
                 let src2_reg = self.storage_manager.load_to_float_reg(&mut self.buf, src2);
                 ASM::div_freg32_freg32_freg32(&mut self.buf, dst_reg, src1_reg, src2_reg);
             }
-            x => todo!("NumDiv: layout, {:?}", x),
+            x => {
+                let op = match x {
+                    Layout::Builtin(Builtin::Int(_)) => LowLevel::NumDivTruncUnchecked,
+                    _ => LowLevel::NumDivFrac,
+                };
+                return Err(UnsupportedConstruct::low_level_with(op, &x));
+            }
+        }
+        Ok(())
+    }
+
+    fn build_num_rem(
+        &mut self,
+        dst: &Symbol,
+        src1: &Symbol,
+        src2: &Symbol,
+        layout: &InLayout<'a>,
+    ) -> BuildResult {
+        match self.layout_interner.get(*layout) {
+            Layout::Builtin(Builtin::Int(int_width))
+                if !matches!(int_width, IntWidth::I128 | IntWidth::U128) =>
+            {
+                let width = RegisterWidth::of_int(int_width);
+                self.storage_manager.with_reserved_general_regs(
+                    &mut self.buf,
+                    ASM::MUL_DIV_FIXED_REGS,
+                    |storage_manager, buf| {
+                        let dst_reg = storage_manager.claim_general_reg(buf, dst);
+                        let src1_reg = storage_manager.load_to_general_reg(buf, src1);
+                        let src2_reg = storage_manager.load_to_general_reg(buf, src2);
+
+                        if int_width.is_signed() {
+                            ASM::irem_reg_reg_reg(
+                                buf,
+                                storage_manager,
+                                width,
+                                dst_reg,
+                                src1_reg,
+                                src2_reg,
+                            );
+                        } else {
+                            ASM::urem_reg_reg_reg(
+                                buf,
+                                storage_manager,
+                                width,
+                                dst_reg,
+                                src1_reg,
+                                src2_reg,
+                            );
+                        }
+                    },
+                );
+            }
+            x => {
+                return Err(UnsupportedConstruct::low_level_with(
+                    LowLevel::NumRemUnchecked,
+                    &x,
+                ))
+            }
         }
+        Ok(())
     }
 
-    fn build_num_neg(&mut self, dst: &Symbol, src: &Symbol, layout: &InLayout<'a>) {
+    fn build_num_neg(&mut self, dst: &Symbol, src: &Symbol, layout: &InLayout<'a>) -> BuildResult {
         match self.layout_interner.get(*layout) {
             Layout::Builtin(Builtin::Int(IntWidth::I64 | IntWidth::U64)) => {
                 let dst_reg = self.storage_manager.claim_general_reg(&mut self.buf, dst);
                 let src_reg = self.storage_manager.load_to_general_reg(&mut self.buf, src);
                 ASM::neg_reg64_reg64(&mut self.buf, dst_reg, src_reg);
             }
-            x => todo!("NumNeg: layout, {:?}", x),
+            x => return Err(UnsupportedConstruct::low_level_with(LowLevel::NumNeg, &x)),
         }
+        Ok(())
     }
 
-    fn build_num_sub(&mut self, dst: &Symbol, src1: &Symbol, src2: &Symbol, layout: &InLayout<'a>) {
+    fn build_num_sub(
+        &mut self,
+        dst: &Symbol,
+        src1: &Symbol,
+        src2: &Symbol,
+        layout: &InLayout<'a>,
+    ) -> BuildResult {
         // for the time being, `num_sub` is implemented as wrapping subtraction. In roc, the normal
         // `sub` should panic on overflow, but we just don't do that yet
         self.build_num_sub_wrap(dst, src1, src2, layout)
@@ -1084,7 +1658,7 @@ impl<
         src1: &Symbol,
         src2: &Symbol,
         layout: &InLayout<'a>,
-    ) {
+    ) -> BuildResult {
         match self.layout_interner.get(*layout) {
             Layout::Builtin(Builtin::Int(quadword_and_smaller!())) => {
                 let dst_reg = self.storage_manager.claim_general_reg(&mut self.buf, dst);
@@ -1096,13 +1670,26 @@ impl<
                     .load_to_general_reg(&mut self.buf, src2);
                 ASM::sub_reg64_reg64_reg64(&mut self.buf, dst_reg, src1_reg, src2_reg);
             }
-            x => todo!("NumSubWrap: layout, {:?}", x),
+            x => {
+                return Err(UnsupportedConstruct::low_level_with(
+                    LowLevel::NumSubWrap,
+                    &x,
+                ))
+            }
         }
+        Ok(())
     }
 
-    fn build_eq(&mut self, dst: &Symbol, src1: &Symbol, src2: &Symbol, arg_layout: &InLayout<'a>) {
+    fn build_eq(
+        &mut self,
+        dst: &Symbol,
+        src1: &Symbol,
+        src2: &Symbol,
+        arg_layout: &InLayout<'a>,
+    ) -> BuildResult {
         match *arg_layout {
-            single_register_int_builtins!() => {
+            // A `Bool` is always 0 or 1 in a register, so it compares like an integer.
+            Layout::BOOL | single_register_int_builtins!() => {
                 let dst_reg = self.storage_manager.claim_general_reg(&mut self.buf, dst);
                 let src1_reg = self
                     .storage_manager
@@ -1112,11 +1699,21 @@ impl<
                     .load_to_general_reg(&mut self.buf, src2);
                 ASM::eq_reg64_reg64_reg64(&mut self.buf, dst_reg, src1_reg, src2_reg);
             }
-            x => todo!("NumEq: layout, {:?}", x),
+            x => {
+                let layout = self.layout_interner.get(x);
+                return Err(UnsupportedConstruct::low_level_with(LowLevel::Eq, &layout));
+            }
         }
+        Ok(())
     }
 
-    fn build_neq(&mut self, dst: &Symbol, src1: &Symbol, src2: &Symbol, arg_layout: &InLayout<'a>) {
+    fn build_neq(
+        &mut self,
+        dst: &Symbol,
+        src1: &Symbol,
+        src2: &Symbol,
+        arg_layout: &InLayout<'a>,
+    ) -> BuildResult {
         match self.layout_interner.get(*arg_layout) {
             Layout::Builtin(Builtin::Int(IntWidth::I64 | IntWidth::U64)) => {
                 let dst_reg = self.storage_manager.claim_general_reg(&mut self.buf, dst);
@@ -1128,8 +1725,9 @@ impl<
                     .load_to_general_reg(&mut self.buf, src2);
                 ASM::neq_reg64_reg64_reg64(&mut self.buf, dst_reg, src1_reg, src2_reg);
             }
-            x => todo!("NumNeq: layout, {:?}", x),
+            x => return Err(UnsupportedConstruct::low_level_with(LowLevel::NotEq, &x)),
         }
+        Ok(())
     }
 
     fn build_num_lt(
@@ -1138,7 +1736,7 @@ impl<
         src1: &Symbol,
         src2: &Symbol,
         arg_layout: &InLayout<'a>,
-    ) {
+    ) -> BuildResult {
         match self.layout_interner.get(*arg_layout) {
             Layout::Builtin(Builtin::Int(IntWidth::I64)) => {
                 let dst_reg = self.storage_manager.claim_general_reg(&mut self.buf, dst);
@@ -1160,8 +1758,9 @@ impl<
                     .load_to_general_reg(&mut self.buf, src2);
                 ASM::ult_reg64_reg64_reg64(&mut self.buf, dst_reg, src1_reg, src2_reg);
             }
-            x => todo!("NumLt: layout, {:?}", x),
+            x => return Err(UnsupportedConstruct::low_level_with(LowLevel::NumLt, &x)),
         }
+        Ok(())
     }
 
     fn build_num_gt(
@@ -1170,7 +1769,7 @@ impl<
         src1: &Symbol,
         src2: &Symbol,
         arg_layout: &InLayout<'a>,
-    ) {
+    ) -> BuildResult {
         match self.layout_interner.get(*arg_layout) {
             Layout::Builtin(Builtin::Int(IntWidth::I64)) => {
                 let dst_reg = self.storage_manager.claim_general_reg(&mut self.buf, dst);
@@ -1192,8 +1791,9 @@ impl<
                     .load_to_general_reg(&mut self.buf, src2);
                 ASM::ugt_reg64_reg64_reg64(&mut self.buf, dst_reg, src1_reg, src2_reg);
             }
-            x => todo!("NumGt: layout, {:?}", x),
+            x => return Err(UnsupportedConstruct::low_level_with(LowLevel::NumGt, &x)),
         }
+        Ok(())
     }
 
     fn build_num_to_frac(
@@ -1202,7 +1802,7 @@ impl<
         src: &Symbol,
         arg_layout: &InLayout<'a>,
         ret_layout: &InLayout<'a>,
-    ) {
+    ) -> BuildResult {
         let dst_reg = self.storage_manager.claim_float_reg(&mut self.buf, dst);
         match (
             self.layout_interner.get(*arg_layout),
@@ -1243,15 +1843,110 @@ impl<
                 let src_reg = self.storage_manager.load_to_float_reg(&mut self.buf, src);
                 ASM::mov_freg64_freg64(&mut self.buf, dst_reg, src_reg);
             }
-            (
-                Layout::Builtin(Builtin::Float(FloatWidth::F32)),
-                Layout::Builtin(Builtin::Float(FloatWidth::F32)),
-            ) => {
-                let src_reg = self.storage_manager.load_to_float_reg(&mut self.buf, src);
-                ASM::mov_freg64_freg64(&mut self.buf, dst_reg, src_reg);
+            (
+                Layout::Builtin(Builtin::Float(FloatWidth::F32)),
+                Layout::Builtin(Builtin::Float(FloatWidth::F32)),
+            ) => {
+                let src_reg = self.storage_manager.load_to_float_reg(&mut self.buf, src);
+                ASM::mov_freg64_freg64(&mut self.buf, dst_reg, src_reg);
+            }
+            (arg, _) => {
+                return Err(UnsupportedConstruct::low_level_with(
+                    LowLevel::NumToFrac,
+                    &arg,
+                ))
+            }
+        }
+        Ok(())
+    }
+
+    fn build_num_to_int_checked(
+        &mut self,
+        dst: &Symbol,
+        src: &Symbol,
+        arg_layout: &InLayout<'a>,
+        ret_layout: &InLayout<'a>,
+    ) -> BuildResult {
+        let arg_width = match self.layout_interner.get(*arg_layout) {
+            Layout::Builtin(Builtin::Int(int_width)) => int_width,
+            x => internal_error!("NumToIntChecked: expected an integer, found {:?}", x),
+        };
+        let target_width = match self.layout_interner.get(*ret_layout) {
+            Layout::Struct { field_layouts, .. } if field_layouts.len() == 2 => {
+                field_layouts[0].to_int_width()
+            }
+            x => internal_error!("NumToIntChecked: expected a result struct, found {:?}", x),
+        };
+        if arg_width.stack_size() > 8 || target_width.stack_size() > 8 {
+            let wide = if arg_width.stack_size() > 8 {
+                arg_width
+            } else {
+                target_width
+            };
+            return Err(UnsupportedConstruct::low_level_with(
+                LowLevel::NumToIntChecked,
+                &Layout::Builtin(Builtin::Int(wide)),
+            ));
+        }
+        let arg_size = arg_width.stack_size() as u8;
+        let target_size = target_width.stack_size() as u8;
+        let (min, max) = arg_width.conversion_bounds(target_width);
+
+        let buf = &mut self.buf;
+
+        let struct_size = self.layout_interner.stack_size(*ret_layout);
+        let base_offset = self.storage_manager.claim_stack_area(dst, struct_size);
+
+        let value_reg = self
+            .storage_manager
+            .claim_general_reg(buf, &Symbol::DEV_TMP);
+        let out_of_bounds_reg = self
+            .storage_manager
+            .claim_general_reg(buf, &Symbol::DEV_TMP2);
+        let bound_reg = self
+            .storage_manager
+            .claim_general_reg(buf, &Symbol::DEV_TMP3);
+        let src_reg = self.storage_manager.load_to_general_reg(buf, src);
+
+        // The bits above the width of the argument can be left over from e.g. a wrapping add.
+        if arg_width.is_signed() {
+            ASM::movsx_reg64_reg64(buf, value_reg, src_reg, arg_size);
+        } else {
+            ASM::movzx_reg64_reg64(buf, value_reg, src_reg, arg_size);
+        }
+
+        // The bounds are the same ones the LLVM backend checks against.
+        ASM::mov_reg64_imm64(buf, out_of_bounds_reg, 0);
+        if let Some(min) = min {
+            // only a signed argument can be below the minimum of the target
+            ASM::mov_reg64_imm64(buf, bound_reg, min as i64);
+            ASM::ilt_reg64_reg64_reg64(buf, out_of_bounds_reg, value_reg, bound_reg);
+        }
+        if let Some(max) = max {
+            ASM::mov_reg64_imm64(buf, bound_reg, max as u64 as i64);
+            if arg_width.is_signed() {
+                ASM::igt_reg64_reg64_reg64(buf, bound_reg, value_reg, bound_reg);
+            } else {
+                ASM::ugt_reg64_reg64_reg64(buf, bound_reg, value_reg, bound_reg);
             }
-            (a, r) => todo!("NumToFrac: layout, arg {:?}, ret {:?}", a, r),
+            ASM::or_reg64_reg64_reg64(buf, out_of_bounds_reg, out_of_bounds_reg, bound_reg);
+        }
+
+        if target_size == 8 {
+            ASM::mov_base32_reg64(buf, base_offset, value_reg);
+            ASM::mov_base32_reg64(buf, base_offset + 8, out_of_bounds_reg);
+        } else {
+            // The area is padded to 8 bytes, so the value is stored as a whole word,
+            // whose bytes above the target width are zeroed, and overwritten by the flag.
+            ASM::movzx_reg64_reg64(buf, value_reg, value_reg, target_size);
+            ASM::mov_base32_reg64(buf, base_offset, value_reg);
+            ASM::mov_base32_reg8(buf, base_offset + target_size as i32, out_of_bounds_reg);
         }
+
+        self.free_symbol(&Symbol::DEV_TMP);
+        self.free_symbol(&Symbol::DEV_TMP2);
+        self.free_symbol(&Symbol::DEV_TMP3);
+        Ok(())
     }
 
     fn build_num_lte(
@@ -1260,7 +1955,7 @@ impl<
         src1: &Symbol,
         src2: &Symbol,
         arg_layout: &InLayout<'a>,
-    ) {
+    ) -> BuildResult {
         match *arg_layout {
             single_register_int_builtins!() => {
                 let dst_reg = self.storage_manager.claim_general_reg(&mut self.buf, dst);
@@ -1272,8 +1967,15 @@ impl<
                     .load_to_general_reg(&mut self.buf, src2);
                 ASM::lte_reg64_reg64_reg64(&mut self.buf, dst_reg, src1_reg, src2_reg);
             }
-            x => todo!("NumLte: layout, {:?}", x),
+            x => {
+                let layout = self.layout_interner.get(x);
+                return Err(UnsupportedConstruct::low_level_with(
+                    LowLevel::NumLte,
+                    &layout,
+                ));
+            }
         }
+        Ok(())
     }
 
     fn build_num_gte(
@@ -1282,7 +1984,7 @@ impl<
         src1: &Symbol,
         src2: &Symbol,
         arg_layout: &InLayout<'a>,
-    ) {
+    ) -> BuildResult {
         match *arg_layout {
             single_register_int_builtins!() => {
                 let dst_reg = self.storage_manager.claim_general_reg(&mut self.buf, dst);
@@ -1294,7 +1996,54 @@ impl<
                     .load_to_general_reg(&mut self.buf, src2);
                 ASM::gte_reg64_reg64_reg64(&mut self.buf, dst_reg, src1_reg, src2_reg);
             }
-            x => todo!("NumGte: layout, {:?}", x),
+            x => {
+                let layout = self.layout_interner.get(x);
+                return Err(UnsupportedConstruct::low_level_with(
+                    LowLevel::NumGte,
+                    &layout,
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn build_num_sign_test(
+        &mut self,
+        dst: &Symbol,
+        src: &Symbol,
+        arg_layout: &InLayout<'a>,
+        condition: SignCondition,
+    ) {
+        match self.layout_interner.get(*arg_layout) {
+            Layout::Builtin(Builtin::Int(int_width)) => {
+                let dst_reg = self.storage_manager.claim_general_reg(&mut self.buf, dst);
+                let src_reg = self.storage_manager.load_to_general_reg(&mut self.buf, src);
+                ASM::int_sign_test_reg_reg(
+                    &mut self.buf,
+                    dst_reg,
+                    src_reg,
+                    RegisterWidth::of_int(int_width),
+                    int_width.is_signed(),
+                    condition,
+                );
+            }
+            Layout::Builtin(Builtin::Float(float_width)) => {
+                let dst_reg = self.storage_manager.claim_general_reg(&mut self.buf, dst);
+                let src_reg = self.storage_manager.load_to_float_reg(&mut self.buf, src);
+                let tmp_reg = self
+                    .storage_manager
+                    .claim_float_reg(&mut self.buf, &Symbol::DEV_TMP);
+                ASM::float_sign_test_reg_freg(
+                    &mut self.buf,
+                    dst_reg,
+                    src_reg,
+                    tmp_reg,
+                    float_width,
+                    condition,
+                );
+                self.free_symbol(&Symbol::DEV_TMP);
+            }
+            x => internal_error!("{:?}: expected a number, found {:?}", condition, x),
         }
     }
 
@@ -1309,7 +2058,7 @@ impl<
         capacity_layout: InLayout<'a>,
         elem_layout: InLayout<'a>,
         ret_layout: &InLayout<'a>,
-    ) {
+    ) -> BuildResult {
         // List alignment argument (u32).
         self.load_layout_alignment(*ret_layout, Symbol::DEV_TMP);
 
@@ -1337,7 +2086,7 @@ impl<
             &lowlevel_args,
             &lowlevel_arg_layouts,
             ret_layout,
-        );
+        )?;
         self.free_symbol(&Symbol::DEV_TMP);
         self.free_symbol(&Symbol::DEV_TMP2);
 
@@ -1348,9 +2097,10 @@ impl<
             base_offset,
             &Symbol::DEV_TMP3,
             ret_layout,
-        );
+        )?;
 
         self.free_symbol(&Symbol::DEV_TMP3);
+        Ok(())
     }
 
     fn build_list_reserve(
@@ -1359,7 +2109,7 @@ impl<
         args: &'a [Symbol],
         arg_layouts: &[InLayout<'a>],
         ret_layout: &InLayout<'a>,
-    ) {
+    ) -> BuildResult {
         let list = args[0];
         let list_layout = arg_layouts[0];
         let spare = args[1];
@@ -1378,7 +2128,7 @@ impl<
             &Symbol::DEV_TMP3,
             &u8_layout,
             &Literal::Int((update_mode as i128).to_ne_bytes()),
-        );
+        )?;
 
         // Setup the return location.
         let base_offset = self
@@ -1411,7 +2161,7 @@ impl<
             &lowlevel_args,
             &lowlevel_arg_layouts,
             ret_layout,
-        );
+        )?;
         self.free_symbol(&Symbol::DEV_TMP);
         self.free_symbol(&Symbol::DEV_TMP2);
         self.free_symbol(&Symbol::DEV_TMP3);
@@ -1423,9 +2173,10 @@ impl<
             base_offset,
             &Symbol::DEV_TMP4,
             ret_layout,
-        );
+        )?;
 
         self.free_symbol(&Symbol::DEV_TMP4);
+        Ok(())
     }
 
     fn build_list_append_unsafe(
@@ -1434,7 +2185,7 @@ impl<
         args: &'a [Symbol],
         arg_layouts: &[InLayout<'a>],
         ret_layout: &InLayout<'a>,
-    ) {
+    ) -> BuildResult {
         let list = args[0];
         let list_layout = arg_layouts[0];
         let elem = args[1];
@@ -1475,7 +2226,7 @@ impl<
             &lowlevel_args,
             &lowlevel_arg_layouts,
             ret_layout,
-        );
+        )?;
         self.free_symbol(&Symbol::DEV_TMP);
         self.free_symbol(&Symbol::DEV_TMP2);
 
@@ -1486,9 +2237,10 @@ impl<
             base_offset,
             &Symbol::DEV_TMP3,
             ret_layout,
-        );
+        )?;
 
         self.free_symbol(&Symbol::DEV_TMP3);
+        Ok(())
     }
 
     fn build_list_get_unsafe(
@@ -1497,7 +2249,7 @@ impl<
         list: &Symbol,
         index: &Symbol,
         ret_layout: &InLayout<'a>,
-    ) {
+    ) -> BuildResult {
         let (base_offset, _) = self.storage_manager.stack_offset_and_size(list);
         let index_reg = self
             .storage_manager
@@ -1511,8 +2263,7 @@ impl<
                 ASM::mov_reg64_base32(buf, list_ptr, base_offset as i32);
                 storage_manager.with_tmp_general_reg(buf, |storage_manager, buf, tmp| {
                     // calculate `element_width * index`
-                    ASM::mov_reg64_imm64(buf, tmp, ret_stack_size as i64);
-                    ASM::imul_reg64_reg64_reg64(buf, tmp, tmp, index_reg);
+                    ASM::imul_reg64_reg64_imm32(buf, tmp, index_reg, ret_stack_size as i32);
 
                     // add the offset to the list pointer, store in `tmp`
                     ASM::add_reg64_reg64_reg64(buf, tmp, tmp, list_ptr);
@@ -1546,18 +2297,19 @@ impl<
                                     let dst_reg = storage_manager.claim_general_reg(buf, dst);
                                     ASM::mov_reg64_reg64(buf, dst_reg, CC::GENERAL_RETURN_REGS[0]);
                                 }
-                                _ => {
-                                    todo!(
-                                        "cannot load {} from the heap yet",
-                                        self.layout_interner.dbg(other)
-                                    );
+                                layout => {
+                                    return Err(UnsupportedConstruct::low_level_with(
+                                        LowLevel::ListGetUnsafe,
+                                        &layout,
+                                    ));
                                 }
                             }
                         }
                     }
-                });
+                    Ok(())
+                })
             },
-        );
+        )
     }
 
     fn build_list_replace_unsafe(
@@ -1566,7 +2318,7 @@ impl<
         args: &'a [Symbol],
         arg_layouts: &[InLayout<'a>],
         ret_layout: &InLayout<'a>,
-    ) {
+    ) -> BuildResult {
         // We want to delegate to the zig builtin, but it takes some extra parameters.
         // Firstly, it takes the alignment of the list.
         // Secondly, it takes the stack size of an element.
@@ -1655,7 +2407,7 @@ impl<
             &lowlevel_args,
             &lowlevel_arg_layouts,
             &list_layout,
-        );
+        )?;
         self.free_symbol(&Symbol::DEV_TMP);
         self.free_symbol(&Symbol::DEV_TMP2);
         self.free_symbol(&Symbol::DEV_TMP3);
@@ -1668,9 +2420,10 @@ impl<
             out_list_offset,
             &Symbol::DEV_TMP5,
             &list_layout,
-        );
+        )?;
 
         self.free_symbol(&Symbol::DEV_TMP5);
+        Ok(())
     }
 
     fn build_list_concat(
@@ -1680,7 +2433,7 @@ impl<
         arg_layouts: &[InLayout<'a>],
         elem_layout: InLayout<'a>,
         ret_layout: &InLayout<'a>,
-    ) {
+    ) -> BuildResult {
         let list_a = args[0];
         let list_a_layout = arg_layouts[0];
         let list_b = args[1];
@@ -1714,7 +2467,7 @@ impl<
             &lowlevel_args,
             &lowlevel_arg_layouts,
             ret_layout,
-        );
+        )?;
 
         self.free_symbol(&Symbol::DEV_TMP);
         self.free_symbol(&Symbol::DEV_TMP2);
@@ -1726,9 +2479,10 @@ impl<
             base_offset,
             &Symbol::DEV_TMP3,
             ret_layout,
-        );
+        )?;
 
         self.free_symbol(&Symbol::DEV_TMP3);
+        Ok(())
     }
 
     fn build_list_prepend(
@@ -1737,7 +2491,7 @@ impl<
         args: &'a [Symbol],
         arg_layouts: &[InLayout<'a>],
         ret_layout: &InLayout<'a>,
-    ) {
+    ) -> BuildResult {
         let list = args[0];
         let list_layout = arg_layouts[0];
         let elem = args[1];
@@ -1783,7 +2537,7 @@ impl<
             &lowlevel_args,
             &lowlevel_arg_layouts,
             ret_layout,
-        );
+        )?;
         self.free_symbol(&Symbol::DEV_TMP);
         self.free_symbol(&Symbol::DEV_TMP2);
         self.free_symbol(&Symbol::DEV_TMP3);
@@ -1795,9 +2549,10 @@ impl<
             base_offset,
             &Symbol::DEV_TMP4,
             ret_layout,
-        );
+        )?;
 
         self.free_symbol(&Symbol::DEV_TMP4);
+        Ok(())
     }
 
     fn build_ptr_cast(&mut self, dst: &Symbol, src: &Symbol) {
@@ -1808,11 +2563,116 @@ impl<
         ASM::add_reg64_reg64_imm32(&mut self.buf, dst_reg, CC::BASE_PTR_REG, offset);
     }
 
+    fn load_runtime_context(&mut self, sym: &Symbol) {
+        debug_assert!(self.env.runtime_context);
+
+        // the register belongs to the host, so the procedure has to restore it
+        self.storage_manager.claim_runtime_context_reg();
+        let reg = self.storage_manager.load_to_general_reg(&mut self.buf, sym);
+        ASM::mov_reg64_reg64(&mut self.buf, CC::RUNTIME_CONTEXT_REG, reg);
+        self.free_symbol(sym);
+    }
+
+    fn build_runtime_context_arg(&mut self, sym: &Symbol) {
+        debug_assert!(self.env.runtime_context);
+
+        let reg = self.storage_manager.claim_general_reg(&mut self.buf, sym);
+        ASM::mov_reg64_reg64(&mut self.buf, reg, CC::RUNTIME_CONTEXT_REG);
+    }
+
+    fn build_runtime_context_load(
+        &mut self,
+        dst: &Symbol,
+        offset: &Symbol,
+        layout: &InLayout<'a>,
+    ) -> BuildResult {
+        debug_assert!(self.env.runtime_context);
+
+        match self.layout_interner.get(*layout) {
+            Layout::Builtin(Builtin::Int(IntWidth::I64 | IntWidth::U64)) => {
+                let offset_reg = self
+                    .storage_manager
+                    .load_to_general_reg(&mut self.buf, offset);
+                let dst_reg = self.storage_manager.claim_general_reg(&mut self.buf, dst);
+                ASM::add_reg64_reg64_reg64(
+                    &mut self.buf,
+                    dst_reg,
+                    CC::RUNTIME_CONTEXT_REG,
+                    offset_reg,
+                );
+                ASM::mov_reg64_mem64_offset32(&mut self.buf, dst_reg, dst_reg, 0);
+            }
+            x => {
+                return Err(UnsupportedConstruct::low_level_with(
+                    LowLevel::RuntimeContextLoad,
+                    &x,
+                ))
+            }
+        }
+        Ok(())
+    }
+
+    fn build_runtime_context_store(
+        &mut self,
+        dst: &Symbol,
+        offset: &Symbol,
+        value: &Symbol,
+        layout: &InLayout<'a>,
+    ) -> BuildResult {
+        debug_assert!(self.env.runtime_context);
+
+        match self.layout_interner.get(*layout) {
+            Layout::Builtin(Builtin::Int(IntWidth::I64 | IntWidth::U64)) => {
+                let offset_reg = self
+                    .storage_manager
+                    .load_to_general_reg(&mut self.buf, offset);
+                let value_reg = self
+                    .storage_manager
+                    .load_to_general_reg(&mut self.buf, value);
+                self.storage_manager.with_tmp_general_reg(
+                    &mut self.buf,
+                    |_storage_manager, buf, address_reg| {
+                        ASM::add_reg64_reg64_reg64(
+                            buf,
+                            address_reg,
+                            CC::RUNTIME_CONTEXT_REG,
+                            offset_reg,
+                        );
+                        ASM::mov_mem64_offset32_reg64(buf, address_reg, 0, value_reg);
+                    },
+                );
+            }
+            x => {
+                return Err(UnsupportedConstruct::low_level_with(
+                    LowLevel::RuntimeContextStore,
+                    &x,
+                ))
+            }
+        }
+
+        // the store itself returns `{}`
+        self.storage_manager.no_data_arg(dst);
+        Ok(())
+    }
+
+    fn build_monotonic_ticks(&mut self, dst: &Symbol) {
+        let serialize = self.env.serialize_ticks;
+
+        self.storage_manager.with_reserved_general_regs(
+            &mut self.buf,
+            ASM::TIMESTAMP_FIXED_REGS,
+            |storage_manager, buf| {
+                let dst_reg = storage_manager.claim_general_reg(buf, dst);
+                ASM::read_timestamp_counter(buf, dst_reg, serialize);
+            },
+        );
+    }
+
     fn create_empty_array(&mut self, sym: &Symbol) {
         let base_offset = self.storage_manager.claim_stack_area(sym, 24);
         self.storage_manager
             .with_tmp_general_reg(&mut self.buf, |_storage_manager, buf, reg| {
-                ASM::mov_reg64_imm64(buf, reg, 0);
+                ASM::load_constant(buf, reg, 0, false);
                 ASM::mov_base32_reg64(buf, base_offset, reg);
                 ASM::mov_base32_reg64(buf, base_offset + 8, reg);
                 ASM::mov_base32_reg64(buf, base_offset + 16, reg);
@@ -1824,7 +2684,7 @@ impl<
         sym: &Symbol,
         element_layout: &InLayout<'a>,
         elements: &'a [ListLiteralElement<'a>],
-    ) {
+    ) -> BuildResult {
         let element_width = self.layout_interner.stack_size(*element_layout) as u64;
 
         // load the total size of the data we want to store (excludes refcount)
@@ -1834,7 +2694,7 @@ impl<
             &data_bytes_symbol,
             &Layout::U64,
             &Literal::Int((data_bytes as i128).to_ne_bytes()),
-        );
+        )?;
 
         // Load allocation alignment (u32)
         let element_alignment_symbol = Symbol::DEV_TMP2;
@@ -1844,7 +2704,7 @@ impl<
             Symbol::DEV_TMP3,
             data_bytes_symbol,
             element_alignment_symbol,
-        );
+        )?;
 
         self.free_symbol(&data_bytes_symbol);
         self.free_symbol(&element_alignment_symbol);
@@ -1865,18 +2725,24 @@ impl<
             let elem_sym = match elem {
                 ListLiteralElement::Symbol(sym) => sym,
                 ListLiteralElement::Literal(lit) => {
-                    self.load_literal(&Symbol::DEV_TMP, element_layout, lit);
+                    self.load_literal(&Symbol::DEV_TMP, element_layout, lit)?;
                     &Symbol::DEV_TMP
                 }
             };
             // TODO: Expand to all types.
             match self.layout_interner.get(*element_layout) {
-                Layout::Builtin(Builtin::Int(IntWidth::I64 | IntWidth::U64) | Builtin::Bool) => {
+                Layout::Builtin(Builtin::Int(IntWidth::I64 | IntWidth::U64)) => {
                     let sym_reg = self
                         .storage_manager
                         .load_to_general_reg(&mut self.buf, elem_sym);
                     ASM::mov_mem64_offset32_reg64(&mut self.buf, ptr_reg, elem_offset, sym_reg);
                 }
+                Layout::Builtin(Builtin::Bool) => {
+                    let sym_reg = self
+                        .storage_manager
+                        .load_to_general_reg(&mut self.buf, elem_sym);
+                    ASM::mov_mem8_offset32_reg8(&mut self.buf, ptr_reg, elem_offset, sym_reg);
+                }
                 _ if element_width == 0 => {}
                 _ if element_width > 8 => {
                     let (from_offset, size) = self.storage_manager.stack_offset_and_size(elem_sym);
@@ -1898,7 +2764,7 @@ impl<
                         },
                     );
                 }
-                x => todo!("copying data to list with layout, {:?}", x),
+                x => return Err(UnsupportedConstruct::expr_with("list literals", &x)),
             }
             elem_offset += element_width as i32;
             if elem_sym == &Symbol::DEV_TMP {
@@ -1913,22 +2779,23 @@ impl<
                 let base_offset = storage_manager.claim_stack_area(sym, 24);
                 ASM::mov_base32_reg64(buf, base_offset, ptr_reg);
 
-                ASM::mov_reg64_imm64(buf, tmp_reg, elements.len() as i64);
+                ASM::load_constant(buf, tmp_reg, elements.len() as i64, false);
                 ASM::mov_base32_reg64(buf, base_offset + 8, tmp_reg);
                 ASM::mov_base32_reg64(buf, base_offset + 16, tmp_reg);
             },
         );
         self.free_symbol(&Symbol::DEV_TMP3);
+        Ok(())
     }
 
-    fn create_struct(&mut self, sym: &Symbol, layout: &InLayout<'a>, fields: &'a [Symbol]) {
-        self.storage_manager.create_struct(
-            self.layout_interner,
-            &mut self.buf,
-            sym,
-            layout,
-            fields,
-        );
+    fn create_struct(
+        &mut self,
+        sym: &Symbol,
+        layout: &InLayout<'a>,
+        fields: &'a [Symbol],
+    ) -> BuildResult {
+        self.storage_manager
+            .create_struct(self.layout_interner, &mut self.buf, sym, layout, fields)
     }
 
     fn load_struct_at_index(
@@ -1954,7 +2821,7 @@ impl<
         tag_id: TagIdIntType,
         index: u64,
         union_layout: &UnionLayout<'a>,
-    ) {
+    ) -> BuildResult {
         match union_layout {
             UnionLayout::NonRecursive(tag_layouts) | UnionLayout::Recursive(tag_layouts) => {
                 self.storage_manager.load_field_at_index(
@@ -1964,18 +2831,20 @@ impl<
                     index,
                     tag_layouts[tag_id as usize],
                 );
+                Ok(())
             }
-            _ => {
-                let union_in_layout = self.layout_interner.insert(Layout::Union(*union_layout));
-                todo!(
-                    "loading from union type: {:?}",
-                    self.layout_interner.dbg(union_in_layout)
-                )
-            }
+            _ => Err(UnsupportedConstruct::of_layout(&Layout::Union(
+                *union_layout,
+            ))),
         }
     }
 
-    fn expr_box(&mut self, sym: Symbol, value: Symbol, element_layout: InLayout<'a>) {
+    fn expr_box(
+        &mut self,
+        sym: Symbol,
+        value: Symbol,
+        element_layout: InLayout<'a>,
+    ) -> BuildResult {
         let element_width_symbol = Symbol::DEV_TMP;
         self.load_layout_stack_size(element_layout, element_width_symbol);
 
@@ -1987,7 +2856,7 @@ impl<
             Symbol::DEV_TMP3,
             element_width_symbol,
             element_alignment_symbol,
-        );
+        )?;
 
         self.free_symbol(&element_width_symbol);
         self.free_symbol(&element_alignment_symbol);
@@ -2030,7 +2899,7 @@ impl<
                     },
                 );
             }
-            x => todo!("copying data to list with layout, {:?}", x),
+            x => return Err(UnsupportedConstruct::expr_with("boxes", &x)),
         }
 
         if value == Symbol::DEV_TMP {
@@ -2042,9 +2911,15 @@ impl<
         ASM::mov_base32_reg64(&mut self.buf, base_offset, ptr_reg);
 
         self.free_symbol(&Symbol::DEV_TMP3);
+        Ok(())
     }
 
-    fn expr_unbox(&mut self, dst: Symbol, ptr: Symbol, element_layout: InLayout<'a>) {
+    fn expr_unbox(
+        &mut self,
+        dst: Symbol,
+        ptr: Symbol,
+        element_layout: InLayout<'a>,
+    ) -> BuildResult {
         let ptr_reg = self
             .storage_manager
             .load_to_general_reg(&mut self.buf, &ptr);
@@ -2065,19 +2940,26 @@ impl<
                 );
             }
             _ => {
-                todo!("unboxing of {:?}", self.layout_interner.dbg(element_layout))
+                let layout = self.layout_interner.get(element_layout);
+                return Err(UnsupportedConstruct::expr_with("unboxing", &layout));
             }
         }
+        Ok(())
     }
 
-    fn get_tag_id(&mut self, sym: &Symbol, structure: &Symbol, union_layout: &UnionLayout<'a>) {
+    fn get_tag_id(
+        &mut self,
+        sym: &Symbol,
+        structure: &Symbol,
+        union_layout: &UnionLayout<'a>,
+    ) -> BuildResult {
         self.storage_manager.load_union_tag_id(
             self.layout_interner,
             &mut self.buf,
             sym,
             structure,
             union_layout,
-        );
+        )
     }
 
     fn tag(
@@ -2086,7 +2968,7 @@ impl<
         fields: &'a [Symbol],
         union_layout: &UnionLayout<'a>,
         tag_id: TagIdIntType,
-    ) {
+    ) -> BuildResult {
         self.storage_manager.create_union(
             self.layout_interner,
             &mut self.buf,
@@ -2097,7 +2979,12 @@ impl<
         )
     }
 
-    fn load_literal(&mut self, sym: &Symbol, layout: &InLayout<'a>, lit: &Literal<'a>) {
+    fn load_literal(
+        &mut self,
+        sym: &Symbol,
+        layout: &InLayout<'a>,
+        lit: &Literal<'a>,
+    ) -> BuildResult {
         match (lit, self.layout_interner.get(*layout)) {
             (
                 Literal::Int(x),
@@ -2114,25 +3001,38 @@ impl<
             ) => {
                 let reg = self.storage_manager.claim_general_reg(&mut self.buf, sym);
                 let val = *x;
-                ASM::mov_reg64_imm64(&mut self.buf, reg, i128::from_ne_bytes(val) as i64);
+                ASM::load_constant(&mut self.buf, reg, i128::from_ne_bytes(val) as i64, false);
             }
             (Literal::Bool(x), Layout::Builtin(Builtin::Bool)) => {
                 let reg = self.storage_manager.claim_general_reg(&mut self.buf, sym);
-                let val = [*x as u8; 16];
-                ASM::mov_reg64_imm64(&mut self.buf, reg, i128::from_ne_bytes(val) as i64);
+                let val = if *x { BOOL_TRUE } else { BOOL_FALSE };
+                ASM::load_constant(&mut self.buf, reg, val as i64, false);
             }
             (Literal::Float(x), Layout::Builtin(Builtin::Float(FloatWidth::F64))) => {
                 let reg = self.storage_manager.claim_float_reg(&mut self.buf, sym);
                 let val = *x;
-                ASM::mov_freg64_imm64(&mut self.buf, &mut self.relocs, reg, val);
+                ASM::mov_freg64_imm64(
+                    &mut self.buf,
+                    &mut self.relocs,
+                    reg,
+                    val,
+                    self.target_info.endianness(),
+                );
             }
             (Literal::Float(x), Layout::Builtin(Builtin::Float(FloatWidth::F32))) => {
                 let reg = self.storage_manager.claim_float_reg(&mut self.buf, sym);
                 let val = *x as f32;
-                ASM::mov_freg32_imm32(&mut self.buf, &mut self.relocs, reg, val);
+                ASM::mov_freg32_imm32(
+                    &mut self.buf,
+                    &mut self.relocs,
+                    reg,
+                    val,
+                    self.target_info.endianness(),
+                );
             }
             (Literal::Str(x), Layout::Builtin(Builtin::Str)) if x.len() < 24 => {
                 // Load small string.
+                let endianness = self.target_info.endianness();
                 self.storage_manager.with_tmp_general_reg(
                     &mut self.buf,
                     |storage_manager, buf, reg| {
@@ -2141,34 +3041,98 @@ impl<
                         bytes[..x.len()].copy_from_slice(x.as_bytes());
                         bytes[23] = (x.len() as u8) | 0b1000_0000;
 
+                        // The register is stored in the byte order of the target, so that is
+                        // the order the bytes are read in, whatever the compiler runs on.
                         let mut num_bytes = [0; 8];
                         num_bytes.copy_from_slice(&bytes[..8]);
-                        let num = i64::from_ne_bytes(num_bytes);
-                        ASM::mov_reg64_imm64(buf, reg, num);
+                        let num = endianness.i64_from_bytes(num_bytes);
+                        ASM::load_constant(buf, reg, num, false);
                         ASM::mov_base32_reg64(buf, base_offset, reg);
 
                         num_bytes.copy_from_slice(&bytes[8..16]);
-                        let num = i64::from_ne_bytes(num_bytes);
-                        ASM::mov_reg64_imm64(buf, reg, num);
+                        let num = endianness.i64_from_bytes(num_bytes);
+                        ASM::load_constant(buf, reg, num, false);
                         ASM::mov_base32_reg64(buf, base_offset + 8, reg);
 
                         num_bytes.copy_from_slice(&bytes[16..]);
-                        let num = i64::from_ne_bytes(num_bytes);
-                        ASM::mov_reg64_imm64(buf, reg, num);
+                        let num = endianness.i64_from_bytes(num_bytes);
+                        ASM::load_constant(buf, reg, num, false);
                         ASM::mov_base32_reg64(buf, base_offset + 16, reg);
                     },
                 );
             }
-            x => todo!("loading literal, {:?}", x),
+            (_, layout) => return Err(UnsupportedConstruct::expr_with("literals", &layout)),
         }
+        Ok(())
     }
 
     fn free_symbol(&mut self, sym: &Symbol) {
         self.join_map.remove(&JoinPointId(*sym));
+        self.owned_values.remove(sym);
         self.storage_manager.free_symbol(sym);
     }
 
-    fn return_symbol(&mut self, sym: &Symbol, layout: &InLayout<'a>) {
+    fn track_owned_values(&mut self, sym: &Symbol, expr: &Expr<'a>, layout: &InLayout<'a>) {
+        for handed_on in symbols_handed_on(expr) {
+            self.owned_values.remove(&handed_on);
+        }
+
+        if expr_makes_owned_value(expr) {
+            if let Some(kind) = CleanupKind::of_layout(self.layout_interner, *layout) {
+                self.owned_values.insert(*sym, kind);
+            }
+        }
+    }
+
+    fn hint_result_reg(&mut self, sym: &Symbol, layout: &InLayout<'a>, following: &Stmt<'a>) {
+        if !matches!(*layout, single_register_integers!()) {
+            return;
+        }
+
+        match following {
+            Stmt::Ret(ret) if ret == sym => {
+                self.storage_manager
+                    .hint_general_reg(sym, CC::GENERAL_RETURN_REGS[0]);
+            }
+            Stmt::Let(
+                _,
+                Expr::Call(Call {
+                    call_type:
+                        CallType::ByName {
+                            arg_layouts,
+                            ret_layout,
+                            ..
+                        },
+                    arguments,
+                }),
+                _,
+                _,
+            ) => {
+                // Only if the call is the last use, so the argument can stay in its register
+                // instead of being saved before the call. With nothing but integers, the first
+                // arguments are in the param regs in order for every calling convention.
+                let last_use = self.last_seen_map.get(sym) == Some(&(following as *const _));
+                let only_integers = arg_layouts
+                    .iter()
+                    .all(|layout| matches!(*layout, single_register_integers!()));
+                let returned_in_reg = matches!(*ret_layout, single_register_layouts!());
+                let passed_once = arguments.iter().filter(|argument| *argument == sym).count() == 1;
+
+                if last_use && only_integers && returned_in_reg && passed_once {
+                    let i = arguments
+                        .iter()
+                        .position(|argument| argument == sym)
+                        .unwrap();
+                    if let Some(reg) = CC::GENERAL_PARAM_REGS.get(i) {
+                        self.storage_manager.hint_general_reg(sym, *reg);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn return_symbol(&mut self, sym: &Symbol, layout: &InLayout<'a>) -> BuildResult {
         if self.storage_manager.is_stored_primitive(sym) {
             // Just load it to the correct type of reg as a stand alone value.
             match *layout {
@@ -2207,7 +3171,7 @@ impl<
                 self.layout_interner,
                 sym,
                 layout,
-            )
+            )?;
         }
         let inst_loc = self.buf.len() as u64;
         let offset = ASM::jmp_imm32(&mut self.buf, 0x1234_5678) as u64;
@@ -2216,6 +3180,7 @@ impl<
             inst_size: self.buf.len() as u64 - inst_loc,
             offset,
         });
+        Ok(())
     }
 
     fn build_int_bitwise_and(
@@ -2224,11 +3189,16 @@ impl<
         src1: &Symbol,
         src2: &Symbol,
         int_width: IntWidth,
-    ) {
+    ) -> BuildResult {
         let buf = &mut self.buf;
 
         match int_width {
-            IntWidth::U128 | IntWidth::I128 => todo!(),
+            IntWidth::U128 | IntWidth::I128 => {
+                return Err(UnsupportedConstruct::low_level_with(
+                    LowLevel::NumBitwiseAnd,
+                    &Layout::Builtin(Builtin::Int(int_width)),
+                ))
+            }
             _ => {
                 let dst_reg = self.storage_manager.claim_general_reg(buf, dst);
                 let src1_reg = self.storage_manager.load_to_general_reg(buf, src1);
@@ -2236,6 +3206,7 @@ impl<
                 ASM::and_reg64_reg64_reg64(buf, dst_reg, src1_reg, src2_reg);
             }
         }
+        Ok(())
     }
 
     fn build_int_bitwise_or(
@@ -2244,11 +3215,16 @@ impl<
         src1: &Symbol,
         src2: &Symbol,
         int_width: IntWidth,
-    ) {
+    ) -> BuildResult {
         let buf = &mut self.buf;
 
         match int_width {
-            IntWidth::U128 | IntWidth::I128 => todo!(),
+            IntWidth::U128 | IntWidth::I128 => {
+                return Err(UnsupportedConstruct::low_level_with(
+                    LowLevel::NumBitwiseOr,
+                    &Layout::Builtin(Builtin::Int(int_width)),
+                ))
+            }
             _ => {
                 let dst_reg = self.storage_manager.claim_general_reg(buf, dst);
                 let src1_reg = self.storage_manager.load_to_general_reg(buf, src1);
@@ -2256,6 +3232,7 @@ impl<
                 ASM::or_reg64_reg64_reg64(buf, dst_reg, src1_reg, src2_reg);
             }
         }
+        Ok(())
     }
 
     fn build_int_bitwise_xor(
@@ -2264,11 +3241,16 @@ impl<
         src1: &Symbol,
         src2: &Symbol,
         int_width: IntWidth,
-    ) {
+    ) -> BuildResult {
         let buf = &mut self.buf;
 
         match int_width {
-            IntWidth::U128 | IntWidth::I128 => todo!(),
+            IntWidth::U128 | IntWidth::I128 => {
+                return Err(UnsupportedConstruct::low_level_with(
+                    LowLevel::NumBitwiseXor,
+                    &Layout::Builtin(Builtin::Int(int_width)),
+                ))
+            }
             _ => {
                 let dst_reg = self.storage_manager.claim_general_reg(buf, dst);
                 let src1_reg = self.storage_manager.load_to_general_reg(buf, src1);
@@ -2276,6 +3258,7 @@ impl<
                 ASM::xor_reg64_reg64_reg64(buf, dst_reg, src1_reg, src2_reg);
             }
         }
+        Ok(())
     }
 
     fn build_int_shift_left(
@@ -2284,11 +3267,16 @@ impl<
         src1: &Symbol,
         src2: &Symbol,
         int_width: IntWidth,
-    ) {
+    ) -> BuildResult {
         let buf = &mut self.buf;
 
         match int_width {
-            IntWidth::U128 | IntWidth::I128 => todo!(),
+            IntWidth::U128 | IntWidth::I128 => {
+                return Err(UnsupportedConstruct::low_level_with(
+                    LowLevel::NumShiftLeftBy,
+                    &Layout::Builtin(Builtin::Int(int_width)),
+                ))
+            }
             _ => {
                 let dst_reg = self.storage_manager.claim_general_reg(buf, dst);
                 let src1_reg = self.storage_manager.load_to_general_reg(buf, src1);
@@ -2303,6 +3291,7 @@ impl<
                 );
             }
         }
+        Ok(())
     }
 
     fn build_int_shift_right(
@@ -2311,11 +3300,16 @@ impl<
         src1: &Symbol,
         src2: &Symbol,
         int_width: IntWidth,
-    ) {
+    ) -> BuildResult {
         let buf = &mut self.buf;
 
         match int_width {
-            IntWidth::U128 | IntWidth::I128 => todo!(),
+            IntWidth::U128 | IntWidth::I128 => {
+                return Err(UnsupportedConstruct::low_level_with(
+                    LowLevel::NumShiftRightBy,
+                    &Layout::Builtin(Builtin::Int(int_width)),
+                ))
+            }
             _ => {
                 let dst_reg = self.storage_manager.claim_general_reg(buf, dst);
                 let src1_reg = self.storage_manager.load_to_general_reg(buf, src1);
@@ -2332,7 +3326,7 @@ impl<
                     self.storage_manager.with_tmp_general_reg(
                         buf,
                         |storage_manager, buf, tmp_reg| {
-                            ASM::mov_reg64_imm64(buf, tmp_reg, sign_extend_shift_amount);
+                            ASM::load_constant(buf, tmp_reg, sign_extend_shift_amount, false);
                             ASM::shl_reg64_reg64_reg64(
                                 buf,
                                 storage_manager,
@@ -2357,7 +3351,7 @@ impl<
                     self.storage_manager.with_tmp_general_reg(
                         &mut self.buf,
                         |storage_manager, buf, tmp_reg| {
-                            ASM::mov_reg64_imm64(buf, tmp_reg, sign_extend_shift_amount);
+                            ASM::load_constant(buf, tmp_reg, sign_extend_shift_amount, false);
                             ASM::shr_reg64_reg64_reg64(
                                 buf,
                                 storage_manager,
@@ -2370,6 +3364,7 @@ impl<
                 }
             }
         }
+        Ok(())
     }
 
     fn build_int_shift_right_zero_fill(
@@ -2378,11 +3373,16 @@ impl<
         src1: &Symbol,
         src2: &Symbol,
         int_width: IntWidth,
-    ) {
+    ) -> BuildResult {
         let buf = &mut self.buf;
 
         match int_width {
-            IntWidth::U128 | IntWidth::I128 => todo!(),
+            IntWidth::U128 | IntWidth::I128 => {
+                return Err(UnsupportedConstruct::low_level_with(
+                    LowLevel::NumShiftRightZfBy,
+                    &Layout::Builtin(Builtin::Int(int_width)),
+                ))
+            }
             _ => {
                 let dst_reg = self.storage_manager.claim_general_reg(buf, dst);
                 let src1_reg = self.storage_manager.load_to_general_reg(buf, src1);
@@ -2397,6 +3397,43 @@ impl<
                 );
             }
         }
+        Ok(())
+    }
+
+    fn build_int_move(&mut self, dst: &Symbol, src: &Symbol) {
+        let dst_reg = self.storage_manager.claim_general_reg(&mut self.buf, dst);
+        let src_reg = self.storage_manager.load_to_general_reg(&mut self.buf, src);
+        ASM::mov_reg64_reg64(&mut self.buf, dst_reg, src_reg);
+    }
+
+    fn build_int_shift_left_imm(&mut self, dst: &Symbol, src: &Symbol, shift: u8) {
+        let dst_reg = self.storage_manager.claim_general_reg(&mut self.buf, dst);
+        let src_reg = self.storage_manager.load_to_general_reg(&mut self.buf, src);
+        ASM::shl_reg64_reg64_imm8(&mut self.buf, dst_reg, src_reg, shift);
+    }
+
+    fn build_int_add_shifted(&mut self, dst: &Symbol, src: &Symbol, shift: u8) {
+        let dst_reg = self.storage_manager.claim_general_reg(&mut self.buf, dst);
+        let src_reg = self.storage_manager.load_to_general_reg(&mut self.buf, src);
+        ASM::add_shifted_reg64_reg64_reg64(&mut self.buf, dst_reg, src_reg, src_reg, shift);
+    }
+
+    fn build_int_div_pow2(&mut self, dst: &Symbol, src: &Symbol, shift: u8, signed: bool) {
+        let buf = &mut self.buf;
+        let dst_reg = self.storage_manager.claim_general_reg(buf, dst);
+        let src_reg = self.storage_manager.load_to_general_reg(buf, src);
+
+        if signed {
+            // A shift rounds toward negative infinity, so negative numbers first get
+            // `2^shift - 1` added to them. That is their sign bit, repeated `shift` times.
+            debug_assert_ne!(dst_reg, src_reg);
+            ASM::sar_reg64_reg64_imm8(buf, dst_reg, src_reg, 63);
+            ASM::shr_reg64_reg64_imm8(buf, dst_reg, dst_reg, 64 - shift);
+            ASM::add_reg64_reg64_reg64(buf, dst_reg, dst_reg, src_reg);
+            ASM::sar_reg64_reg64_imm8(buf, dst_reg, dst_reg, shift);
+        } else {
+            ASM::shr_reg64_reg64_imm8(buf, dst_reg, src_reg, shift);
+        }
     }
 }
 
@@ -2416,14 +3453,14 @@ impl<
         dst: Symbol,
         data_bytes: Symbol,
         element_alignment: Symbol,
-    ) {
+    ) -> BuildResult {
         self.build_fn_call(
             &dst,
             bitcode::UTILS_ALLOCATE_WITH_REFCOUNT.to_string(),
             &[data_bytes, element_alignment],
             &[Layout::U64, Layout::U32],
             &Layout::U64,
-        );
+        )
     }
 
     fn unbox_str_or_list(
@@ -2454,32 +3491,57 @@ impl<
         target_offset: u64,
     ) {
         tmp.clear();
-        let jmp_offset = target_offset as i32 - base_offset as i32;
+        let jmp_offset = match i32::try_from(target_offset as i64 - base_offset as i64) {
+            Ok(jmp_offset) => jmp_offset,
+            Err(_) => internal_error!(
+                "the jump to the return at {} is out of range of a rel32",
+                jmp_location
+            ),
+        };
         ASM::jmp_imm32(tmp, jmp_offset);
         for (i, byte) in tmp.iter().enumerate() {
             self.buf[jmp_location as usize + i] = *byte;
         }
+
+        self.branches.push(Branch {
+            kind: BranchKind::Jmp,
+            location: jmp_location,
+            target: target_offset,
+        });
     }
 
     /// Loads the alignment bytes of `layout` into the given `symbol`
     fn load_layout_alignment(&mut self, layout: InLayout<'a>, symbol: Symbol) {
-        let u32_layout = Layout::U32;
         let alignment = self.layout_interner.alignment_bytes(layout);
-        let alignment_literal = Literal::Int((alignment as i128).to_ne_bytes());
 
-        self.load_literal(&symbol, &u32_layout, &alignment_literal);
+        let reg = self
+            .storage_manager
+            .claim_general_reg(&mut self.buf, &symbol);
+        ASM::load_constant(&mut self.buf, reg, alignment as i64, false);
     }
 
     /// Loads the stack size of `layout` into the given `symbol`
     fn load_layout_stack_size(&mut self, layout: InLayout<'a>, symbol: Symbol) {
-        let u64_layout = Layout::U64;
         let width = self.layout_interner.stack_size(layout);
-        let width_literal = Literal::Int((width as i128).to_ne_bytes());
 
-        self.load_literal(&symbol, &u64_layout, &width_literal);
+        let reg = self
+            .storage_manager
+            .claim_general_reg(&mut self.buf, &symbol);
+        ASM::load_constant(&mut self.buf, reg, width as i64, false);
     }
 }
 
+/// Whether `dst = src1 + src2` is built with `lea`, which keeps both sources, rather than with
+/// an `add` into the source that `dst` already is. An addition with a third register would
+/// otherwise take a `mov` and an `add`. The checked additions need the flags of an `add`.
+fn adds_with_lea<GeneralReg: RegTrait>(
+    dst: GeneralReg,
+    src1: GeneralReg,
+    src2: GeneralReg,
+) -> bool {
+    dst != src1 && dst != src2
+}
+
 #[macro_export]
 macro_rules! sign_extended_int_builtins {
     () => {
@@ -2528,3 +3590,99 @@ macro_rules! single_register_layouts {
         single_register_integers!() | single_register_floats!()
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::Rng;
+    use bumpalo::Bump;
+
+    const FILLER: u8 = 0;
+    const SENTINELS: u8 = 16;
+
+    /// Checks that the `i`th offset in `table` still points at the sentinel `i + 1`
+    fn assert_on_sentinels(buf: &[u8], table: &OffsetTable<'_>) {
+        for (sentinel, offset) in (1..=SENTINELS).zip(table.iter()) {
+            assert_eq!(buf[offset as usize], sentinel, "at offset {}", offset);
+        }
+    }
+
+    #[test]
+    fn shifts_keep_offsets_on_their_bytes() {
+        for seed in 1..=64u64 {
+            let arena = Bump::new();
+            let mut rng = Rng(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15));
+
+            // starts out small, so inserting grows it past its capacity again and again
+            let mut buf = Vec::with_capacity_in(8, &arena);
+            buf.extend(std::iter::repeat(FILLER).take(64));
+            let mut table = OffsetTable::new_in(&arena);
+            for sentinel in 1..=SENTINELS {
+                let mut at = rng.below(buf.len());
+                while buf[at] != FILLER {
+                    at = (at + 1) % buf.len();
+                }
+                buf[at] = sentinel;
+                table.track(at as u64);
+            }
+
+            for _ in 0..200 {
+                if buf.len() < 32 || rng.below(2) == 0 {
+                    let at = rng.below(buf.len() + 1);
+                    let len = 1 + rng.below(16);
+                    for _ in 0..len {
+                        buf.insert(at, FILLER);
+                    }
+                    table.apply_shift(at as u64, len as i64);
+                } else {
+                    // a cut only takes filler, like shrinking a jump never takes what follows it
+                    let at = rng.below(buf.len());
+                    let run = buf[at..].iter().take_while(|byte| **byte == FILLER).count();
+                    if run == 0 {
+                        continue;
+                    }
+                    let len = 1 + rng.below(run);
+                    buf.drain(at..at + len);
+                    table.apply_shift(at as u64, -(len as i64));
+                }
+
+                assert_on_sentinels(&buf, &table);
+            }
+        }
+    }
+
+    #[test]
+    fn additions_into_a_third_reg_use_lea() {
+        use x86_64::X86_64GeneralReg::*;
+
+        assert!(adds_with_lea(RAX, RCX, RDX));
+        assert!(adds_with_lea(R13, R12, R12));
+        assert!(!adds_with_lea(RAX, RAX, RDX));
+        assert!(!adds_with_lea(RDX, RAX, RDX));
+        assert!(!adds_with_lea(R12, R12, R12));
+    }
+
+    #[test]
+    fn offsets_at_the_end_stay_at_the_end() {
+        let arena = Bump::new();
+        let mut table = OffsetTable::new_in(&arena);
+        table.track(0);
+        table.track(10);
+
+        // what follows is cut, and then more is put in at the end
+        table.apply_shift(4, -6);
+        table.apply_shift(4, 3);
+
+        assert_eq!(table.iter().collect::<std::vec::Vec<_>>(), [0, 7]);
+    }
+
+    #[test]
+    #[should_panic(expected = "points into the 4 bytes cut out at 2")]
+    fn cutting_tracked_bytes_is_an_error() {
+        let arena = Bump::new();
+        let mut table = OffsetTable::new_in(&arena);
+        table.track(3);
+
+        table.apply_shift(2, -4);
+    }
+}
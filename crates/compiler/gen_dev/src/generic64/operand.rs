@@ -0,0 +1,111 @@
+//! A thin layer over the Assembler's encoders, so lowering code can say what it moves where,
+//! instead of picking one of the `mov_<dst>_<src>` methods by hand.
+//! Every supported combination maps onto exactly one low-level encoder.
+use crate::generic64::{storage::RegStorage, Assembler, RegTrait};
+use bumpalo::collections::Vec;
+use roc_builtins::bitcode::IntWidth;
+use roc_error_macros::internal_error;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum RegisterWidth {
+    W8,
+    W16,
+    W32,
+    W64,
+}
+
+impl RegisterWidth {
+    /// The width of the part of a register that holds an integer of `int_width`
+    pub fn of_int(int_width: IntWidth) -> Self {
+        match int_width {
+            IntWidth::I8 | IntWidth::U8 => RegisterWidth::W8,
+            IntWidth::I16 | IntWidth::U16 => RegisterWidth::W16,
+            IntWidth::I32 | IntWidth::U32 => RegisterWidth::W32,
+            IntWidth::I64 | IntWidth::U64 => RegisterWidth::W64,
+            IntWidth::I128 | IntWidth::U128 => {
+                internal_error!("{:?} does not fit in a general register", int_width)
+            }
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Operand<GeneralReg: RegTrait, FloatReg: RegTrait> {
+    Reg(GeneralReg),
+    FReg(FloatReg),
+    /// Stores of immediates to memory take at most 32 bits, sign-extended to 64 bits for `W64`
+    Imm(i64),
+    /// An offset from the stack pointer
+    Stack(i32),
+    /// An offset from the base/frame pointer
+    Base(i32),
+    /// The memory at `base + offset`
+    Mem {
+        base: GeneralReg,
+        offset: i32,
+    },
+}
+
+impl<GeneralReg: RegTrait, FloatReg: RegTrait> From<RegStorage<GeneralReg, FloatReg>>
+    for Operand<GeneralReg, FloatReg>
+{
+    fn from(storage: RegStorage<GeneralReg, FloatReg>) -> Self {
+        match storage {
+            RegStorage::General(reg) => Operand::Reg(reg),
+            RegStorage::Float(reg) => Operand::FReg(reg),
+        }
+    }
+}
+
+/// Move the low `width` bits of `src` into `dst`. Registers are only ever read or written whole,
+/// so narrower widths only apply to stores to memory.
+/// Panics for combinations that no encoder exists for, e.g. memory to memory moves.
+pub fn mov<GeneralReg: RegTrait, FloatReg: RegTrait, ASM: Assembler<GeneralReg, FloatReg>>(
+    buf: &mut Vec<'_, u8>,
+    width: RegisterWidth,
+    dst: Operand<GeneralReg, FloatReg>,
+    src: Operand<GeneralReg, FloatReg>,
+) {
+    use Operand::*;
+    use RegisterWidth::*;
+
+    match (width, dst, src) {
+        (W64, Reg(dst), Reg(src)) => ASM::mov_reg64_reg64(buf, dst, src),
+        (W64, Reg(dst), Imm(imm)) => ASM::mov_reg64_imm64(buf, dst, imm),
+        (W64, Reg(dst), Base(offset)) => ASM::mov_reg64_base32(buf, dst, offset),
+        (W64, Reg(dst), Stack(offset)) => ASM::mov_reg64_stack32(buf, dst, offset),
+        (W64, Reg(dst), Mem { base, offset }) => {
+            ASM::mov_reg64_mem64_offset32(buf, dst, base, offset)
+        }
+
+        (W64, FReg(dst), FReg(src)) => ASM::mov_freg64_freg64(buf, dst, src),
+        (W64, FReg(dst), Base(offset)) => ASM::mov_freg64_base32(buf, dst, offset),
+        (W64, FReg(dst), Stack(offset)) => ASM::mov_freg64_stack32(buf, dst, offset),
+
+        (W64, Base(offset), Reg(src)) => ASM::mov_base32_reg64(buf, offset, src),
+        (W64, Base(offset), FReg(src)) => ASM::mov_base32_freg64(buf, offset, src),
+        (W64, Stack(offset), Reg(src)) => ASM::mov_stack32_reg64(buf, offset, src),
+        (W64, Stack(offset), FReg(src)) => ASM::mov_stack32_freg64(buf, offset, src),
+        (W64, Mem { base, offset }, Reg(src)) => {
+            ASM::mov_mem64_offset32_reg64(buf, base, offset, src)
+        }
+
+        (W32, Base(offset), Reg(src)) => ASM::mov_base32_reg32(buf, offset, src),
+        (W16, Base(offset), Reg(src)) => ASM::mov_base32_reg16(buf, offset, src),
+        (W8, Base(offset), Reg(src)) => ASM::mov_base32_reg8(buf, offset, src),
+        (W8, Mem { base, offset }, Reg(src)) => ASM::mov_mem8_offset32_reg8(buf, base, offset, src),
+
+        (width, Base(offset), Imm(imm)) if i32::try_from(imm).is_ok() => {
+            ASM::mov_base32_imm(buf, offset, imm as i32, width)
+        }
+
+        (width, dst, src) => {
+            internal_error!(
+                "mov is not supported for {:?} from {:?} to {:?}",
+                width,
+                src,
+                dst
+            )
+        }
+    }
+}
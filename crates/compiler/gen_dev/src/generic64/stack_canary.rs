@@ -0,0 +1,225 @@
+//! A stack canary is a value the prologue puts right below the saved frame pointer and the return
+//! address, where code that writes past the end of a value on the stack overwrites it first.
+//! The epilogue checks that it is unchanged before returning.
+use crate::generic64::{Assembler, CallConv, RegTrait};
+use crate::{Relocation, StackCanary};
+use bumpalo::collections::Vec;
+use bumpalo::Bump;
+
+pub(crate) const STACK_CANARY_SIZE: u32 = 8;
+
+/// Where the canary goes relative to the base pointer: it is the first stack slot a procedure
+/// claims, so all the others are below it.
+pub(crate) const STACK_CANARY_OFFSET: i32 = -(STACK_CANARY_SIZE as i32);
+
+/// The message `roc_panic` gets when a canary from `StackCanary::Constant` was overwritten.
+pub(crate) const STACK_SMASHED_MESSAGE: &str = "stack smashing detected";
+
+const STACK_CHK_GUARD: &str = "__stack_chk_guard";
+const STACK_CHK_FAIL: &str = "__stack_chk_fail";
+
+/// A small `RocStr`: the bytes, followed by zeroes, and the length with the small string flag set
+fn small_roc_str(s: &str) -> std::vec::Vec<u8> {
+    debug_assert!(s.len() < 24);
+    let mut bytes = vec![0; 24];
+    bytes[..s.len()].copy_from_slice(s.as_bytes());
+    bytes[23] = s.len() as u8 | 0b1000_0000;
+
+    bytes
+}
+
+fn load_stack_canary<GeneralReg, FloatReg, ASM>(
+    buf: &mut Vec<'_, u8>,
+    relocs: &mut Vec<'_, Relocation>,
+    canary: StackCanary,
+    dst: GeneralReg,
+) where
+    GeneralReg: RegTrait,
+    FloatReg: RegTrait,
+    ASM: Assembler<GeneralReg, FloatReg>,
+{
+    match canary {
+        StackCanary::Guard => {
+            ASM::load_linked_data_address(buf, relocs, dst, STACK_CHK_GUARD.to_string());
+            ASM::mov_reg64_mem64_offset32(buf, dst, dst, 0);
+        }
+        StackCanary::Constant(value) => ASM::mov_reg64_imm64(buf, dst, value as i64),
+    }
+}
+
+/// Puts the canary on the stack. This goes right after the prologue, where nothing but the
+/// argument registers is live.
+pub(crate) fn store_stack_canary<GeneralReg, FloatReg, ASM, CC>(
+    buf: &mut Vec<'_, u8>,
+    relocs: &mut Vec<'_, Relocation>,
+    canary: StackCanary,
+) where
+    GeneralReg: RegTrait,
+    FloatReg: RegTrait,
+    ASM: Assembler<GeneralReg, FloatReg>,
+    CC: CallConv<GeneralReg, FloatReg, ASM>,
+{
+    let reg = CC::SCRATCH_REGS[0];
+    load_stack_canary::<_, _, ASM>(buf, relocs, canary, reg);
+    ASM::mov_base32_reg64(buf, STACK_CANARY_OFFSET, reg);
+}
+
+/// Fails when the canary on the stack changed. This goes right before the epilogue, so it leaves
+/// the return registers alone. A `StackCanary::Constant` fails by calling `panic_fn_name`, which
+/// takes the arguments of `roc_panic`.
+pub(crate) fn check_stack_canary<GeneralReg, FloatReg, ASM, CC>(
+    arena: &Bump,
+    buf: &mut Vec<'_, u8>,
+    relocs: &mut Vec<'_, Relocation>,
+    canary: StackCanary,
+    panic_fn_name: &str,
+) where
+    GeneralReg: RegTrait,
+    FloatReg: RegTrait,
+    ASM: Assembler<GeneralReg, FloatReg>,
+    CC: CallConv<GeneralReg, FloatReg, ASM>,
+{
+    let [expected, actual] = CC::SCRATCH_REGS;
+    load_stack_canary::<_, _, ASM>(buf, relocs, canary, expected);
+    ASM::mov_reg64_base32(buf, actual, STACK_CANARY_OFFSET);
+    ASM::eq_reg64_reg64_reg64(buf, expected, expected, actual);
+
+    // Jump over the failure when the canary is unchanged. The jump is patched once the size of
+    // the failure is known.
+    let jump_start = buf.len();
+    let jump_end = ASM::jne_reg64_imm64_imm32(buf, expected, 0, 0);
+
+    match canary {
+        StackCanary::Guard => ASM::call(buf, relocs, STACK_CHK_FAIL.to_string()),
+        StackCanary::Constant(_) => {
+            ASM::load_local_data_address(
+                buf,
+                relocs,
+                CC::GENERAL_PARAM_REGS[0],
+                small_roc_str(STACK_SMASHED_MESSAGE),
+            );
+            ASM::mov_reg64_imm64(buf, CC::GENERAL_PARAM_REGS[1], 0);
+            // no source info
+            ASM::mov_reg64_imm64(buf, CC::GENERAL_PARAM_REGS[2], 0);
+            ASM::call(buf, relocs, panic_fn_name.to_string());
+        }
+    }
+
+    let mut jump = bumpalo::vec![in arena];
+    ASM::jne_reg64_imm64_imm32(&mut jump, expected, 0, (buf.len() - jump_end) as i32);
+    buf[jump_start..jump_end].copy_from_slice(&jump);
+}
+
+#[cfg(all(test, target_arch = "x86_64", target_os = "linux"))]
+mod tests {
+    use super::*;
+    use crate::generic64::x86_64::{X86_64Assembler, X86_64GeneralReg, X86_64SystemV};
+    use crate::link_relocation;
+    use crate::test_utils::JitPage;
+    use roc_std::RocStr;
+    use std::cell::RefCell;
+
+    type ASM = X86_64Assembler;
+    type CC = X86_64SystemV;
+
+    const CANARY: u64 = 0x5ca1_ab1e_0ff1_ce00;
+
+    thread_local! {
+        static PANIC_MESSAGE: RefCell<Option<String>> = RefCell::new(None);
+    }
+
+    extern "C" fn roc_panic(msg: &RocStr, _tag_id: u32, _source: *const u8) {
+        PANIC_MESSAGE.with(|message| *message.borrow_mut() = Some(msg.as_str().to_string()));
+    }
+
+    /// Builds a procedure that returns 42 and has one 8 byte value on the stack. With `smash_stack`
+    /// it writes 16 bytes to that value, like a backend bug that gets the size of a value wrong.
+    /// The procedure is run in memory, with `roc_panic` above standing in for the real one.
+    fn run(stack_canary: bool, smash_stack: bool) -> (i64, Option<String>) {
+        use X86_64GeneralReg::*;
+
+        let arena = Bump::new();
+        let mut buf = bumpalo::vec![in &arena];
+        let mut relocs = bumpalo::vec![in &arena];
+        let canary = StackCanary::Constant(CANARY);
+
+        let aligned_stack_size = CC::setup_stack(&mut buf, &[], &[], 16, 0, 0);
+        if stack_canary {
+            store_stack_canary::<_, _, ASM, CC>(&mut buf, &mut relocs, canary);
+        }
+
+        let value_offset = -16;
+        ASM::mov_reg64_imm64(&mut buf, RAX, 7);
+        ASM::mov_base32_reg64(&mut buf, value_offset, RAX);
+        if smash_stack {
+            ASM::mov_base32_reg64(&mut buf, value_offset + 8, RAX);
+        }
+        ASM::mov_reg64_imm64(&mut buf, RAX, 42);
+
+        if stack_canary {
+            check_stack_canary::<_, _, ASM, CC>(&arena, &mut buf, &mut relocs, canary, "roc_panic");
+        }
+        CC::cleanup_stack(&mut buf, &[], &[], aligned_stack_size, 0, 0);
+        ASM::ret(&mut buf);
+
+        // Link the relocations by hand: local data goes after the code, and calls go through a
+        // stub that jumps to the absolute address of the function.
+        let mut patches = std::vec::Vec::new();
+        for reloc in relocs.iter() {
+            match reloc {
+                Relocation::LocalData { data, .. } => {
+                    patches.push((reloc, buf.len()));
+                    buf.extend(data.iter().copied());
+                }
+                Relocation::LinkedFunction { name, .. } => {
+                    assert_eq!(name, "roc_panic");
+                    patches.push((reloc, buf.len()));
+                    ASM::mov_reg64_imm64(&mut buf, RAX, roc_panic as usize as i64);
+                    // jmp rax
+                    buf.extend([0xFF, 0xE0]);
+                }
+                other => panic!("unexpected relocation {:?}", other),
+            }
+        }
+        // everything is in one mapping, so only the offsets in it matter
+        for (reloc, target) in patches {
+            link_relocation(&mut buf, 0, "run", reloc, target as u64).unwrap();
+        }
+
+        PANIC_MESSAGE.with(|message| message.borrow_mut().take());
+        let mut page = JitPage::new();
+        page.write(0, &buf);
+        page.make_executable();
+        let result = unsafe { page.function::<extern "C" fn() -> i64>(0)() };
+
+        (
+            result,
+            PANIC_MESSAGE.with(|message| message.borrow_mut().take()),
+        )
+    }
+
+    #[test]
+    fn smashed_canary_panics() {
+        let (_, message) = run(true, true);
+
+        assert_eq!(message.as_deref(), Some(STACK_SMASHED_MESSAGE));
+    }
+
+    #[test]
+    fn smashed_stack_goes_unnoticed_without_canary() {
+        assert_eq!(run(false, true), (42, None));
+    }
+
+    #[test]
+    fn intact_canary_keeps_the_return_value() {
+        assert_eq!(run(true, false), (42, None));
+    }
+
+    #[test]
+    fn small_roc_str_layout() {
+        let bytes = small_roc_str(STACK_SMASHED_MESSAGE);
+        let roc_str: RocStr = unsafe { std::ptr::read(bytes.as_ptr() as *const RocStr) };
+
+        assert_eq!(roc_str.as_str(), STACK_SMASHED_MESSAGE);
+    }
+}
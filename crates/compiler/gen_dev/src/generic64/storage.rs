@@ -1,18 +1,26 @@
 use crate::{
-    generic64::{Assembler, CallConv, RegTrait},
+    generic64::{
+        operand::{self, Operand, RegisterWidth},
+        stack_canary::STACK_CANARY_SIZE,
+        Assembler, CallConv, RegTrait,
+    },
     sign_extended_int_builtins, single_register_floats, single_register_int_builtins,
-    single_register_integers, single_register_layouts, Env,
+    single_register_integers, single_register_layouts,
+    stats::ProcStats,
+    unsupported::{BuildResult, UnsupportedConstruct},
+    Env, RegAllocOrder,
 };
 use bumpalo::collections::Vec;
 use roc_builtins::bitcode::{FloatWidth, IntWidth};
-use roc_collections::all::{MutMap, MutSet};
+use roc_collections::all::MutMap;
 use roc_error_macros::internal_error;
 use roc_module::symbol::Symbol;
 use roc_mono::{
     borrow::Ownership,
     ir::{JoinPointId, Param},
     layout::{
-        Builtin, InLayout, Layout, LayoutInterner, STLayoutInterner, TagIdIntType, UnionLayout,
+        field_offsets, Builtin, InLayout, Layout, LayoutInterner, STLayoutInterner, TagIdIntType,
+        UnionLayout,
     },
 };
 use roc_target::TargetInfo;
@@ -24,6 +32,31 @@ use RegStorage::*;
 use StackStorage::*;
 use Storage::*;
 
+/// Stack slots up to this size are zeroed with a store per 8 bytes, bigger ones with `zero_base32`.
+const ZERO_BY_STORES_MAX_SIZE: u32 = 64;
+
+/// The immediate with which `mov_base32_imm` stores `value` in `width` bytes, if there is one.
+/// `value` is all of what the bytes should hold, so it is zero extended for unsigned integers.
+/// The 8 byte store sign extends its immediate: that stores `-1` as an `I64`, but not
+/// `0xFFFF_FFFF` as a `U32`, which has to go through a register.
+pub(crate) fn constant_store_imm(value: i64, width: RegisterWidth) -> Option<i32> {
+    let bits = match width {
+        RegisterWidth::W8 => 8,
+        RegisterWidth::W16 => 16,
+        RegisterWidth::W32 => 32,
+        RegisterWidth::W64 => return i32::try_from(value).ok(),
+    };
+    debug_assert!(
+        (-(1 << (bits - 1))..(1 << bits)).contains(&value),
+        "{} does not fit in {} bits",
+        value,
+        bits
+    );
+
+    // the narrower stores only keep the low bits of the immediate
+    Some(value as i32)
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum RegStorage<GeneralReg: RegTrait, FloatReg: RegTrait> {
     General(GeneralReg),
@@ -72,6 +105,24 @@ enum StackStorage<GeneralReg: RegTrait, FloatReg: RegTrait> {
     },
 }
 
+/// A set of registers, stored as a bitmask indexed by `RegTrait::to_index`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+struct RegSet(u64);
+
+impl RegSet {
+    fn insert<Reg: RegTrait>(&mut self, reg: Reg) {
+        self.0 |= 1 << reg.to_index();
+    }
+
+    fn contains<Reg: RegTrait>(&self, reg: Reg) -> bool {
+        self.0 & (1 << reg.to_index()) != 0
+    }
+
+    fn clear(&mut self) {
+        self.0 = 0;
+    }
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 enum Storage<GeneralReg: RegTrait, FloatReg: RegTrait> {
     Reg(RegStorage<GeneralReg, FloatReg>),
@@ -106,27 +157,40 @@ pub struct StorageManager<
     // When jumping to the join point, the parameters should be setup to match this.
     join_param_map: MutMap<JoinPointId, Vec<'a, Storage<GeneralReg, FloatReg>>>,
 
+    // The general regs that are free at the start of every procedure, last one used first.
+    // This is `CC::GENERAL_DEFAULT_FREE_REGS` unless the env picks another order.
+    general_default_free_regs: Vec<'a, GeneralReg>,
+
     // This should probably be smarter than a vec.
     // There are certain registers we should always use first. With pushing and popping, this could get mixed.
     general_free_regs: Vec<'a, GeneralReg>,
     float_free_regs: Vec<'a, FloatReg>,
 
+    // General regs that are neither free nor used, because a lowering needs them for itself.
+    // See `with_reserved_general_regs`.
+    general_reserved_regs: Vec<'a, GeneralReg>,
+
     // The last major thing we need is a way to decide what reg to free when all of them are full.
     // Theoretically we want a basic lru cache for the currently loaded symbols.
     // For now just a vec of used registers and the symbols they contain.
     general_used_regs: Vec<'a, (GeneralReg, Symbol)>,
     float_used_regs: Vec<'a, (FloatReg, Symbol)>,
 
-    // TODO: it probably would be faster to make these a list that linearly scans rather than hashing.
+    // The general regs that symbols should be claimed in, if they are free by then.
+    // See `hint_general_reg`.
+    general_reg_hints: MutMap<Symbol, GeneralReg>,
+
     // used callee saved regs must be tracked for pushing and popping at the beginning/end of the function.
-    general_used_callee_saved_regs: MutSet<GeneralReg>,
-    float_used_callee_saved_regs: MutSet<FloatReg>,
+    general_used_callee_saved_regs: RegSet,
+    float_used_callee_saved_regs: RegSet,
 
     free_stack_chunks: Vec<'a, (i32, u32)>,
     stack_size: u32,
 
     // The amount of extra stack space needed to pass args for function calling.
     fn_call_stack_size: u32,
+
+    pub(crate) stats: ProcStats,
 }
 
 pub fn new_storage_manager<
@@ -140,26 +204,103 @@ pub fn new_storage_manager<
     env: &'r Env<'a>,
     target_info: TargetInfo,
 ) -> StorageManager<'a, 'r, GeneralReg, FloatReg, ASM, CC> {
+    let order = env.reg_alloc_order;
+    let regs =
+        general_free_regs_in_order::<GeneralReg, FloatReg, ASM, CC>(order).unwrap_or_else(|e| {
+            internal_error!("invalid register allocation order {:?}: {}", order, e)
+        });
+    let mut general_default_free_regs = bumpalo::vec![in env.arena];
+    general_default_free_regs.extend(regs);
+    if env.runtime_context {
+        general_default_free_regs.retain(|reg| *reg != CC::RUNTIME_CONTEXT_REG);
+    }
+
     StorageManager {
         phantom_asm: PhantomData,
         phantom_cc: PhantomData,
         env,
         target_info,
+        general_default_free_regs,
         symbol_storage_map: MutMap::default(),
         allocation_map: MutMap::default(),
         join_param_map: MutMap::default(),
         general_free_regs: bumpalo::vec![in env.arena],
+        general_reserved_regs: bumpalo::vec![in env.arena],
         general_used_regs: bumpalo::vec![in env.arena],
-        general_used_callee_saved_regs: MutSet::default(),
+        general_reg_hints: MutMap::default(),
+        general_used_callee_saved_regs: RegSet::default(),
         float_free_regs: bumpalo::vec![in env.arena],
         float_used_regs: bumpalo::vec![in env.arena],
-        float_used_callee_saved_regs: MutSet::default(),
+        float_used_callee_saved_regs: RegSet::default(),
         free_stack_chunks: bumpalo::vec![in env.arena],
         stack_size: 0,
         fn_call_stack_size: 0,
+        stats: ProcStats::default(),
     }
 }
 
+/// The general regs to start every procedure with, in free list order (the last one is used first).
+pub(crate) fn general_free_regs_in_order<
+    GeneralReg: RegTrait,
+    FloatReg: RegTrait,
+    ASM: Assembler<GeneralReg, FloatReg>,
+    CC: CallConv<GeneralReg, FloatReg, ASM>,
+>(
+    order: Option<RegAllocOrder>,
+) -> Result<std::vec::Vec<GeneralReg>, String> {
+    let defaults = CC::GENERAL_DEFAULT_FREE_REGS;
+
+    let regs = match order {
+        None => defaults.to_vec(),
+        // The free list is popped from the back, so the preferred registers go last.
+        Some(RegAllocOrder::CallerSavedFirst) => {
+            let (callee_saved, caller_saved): (std::vec::Vec<_>, _) = defaults
+                .iter()
+                .copied()
+                .partition(|reg| CC::general_callee_saved(reg));
+            [callee_saved, caller_saved].concat()
+        }
+        Some(RegAllocOrder::CalleeSavedFirst) => {
+            let (callee_saved, caller_saved): (std::vec::Vec<_>, _) = defaults
+                .iter()
+                .copied()
+                .partition(|reg| CC::general_callee_saved(reg));
+            [caller_saved, callee_saved].concat()
+        }
+        Some(RegAllocOrder::Custom(names)) => {
+            if names.is_empty() {
+                return Err("no registers given".to_string());
+            }
+
+            let mut regs = std::vec::Vec::with_capacity(names.len());
+            for name in names.iter().rev() {
+                let matches = |reg: &GeneralReg| reg.to_string().eq_ignore_ascii_case(name);
+
+                if matches(&CC::STACK_PTR_REG) || matches(&CC::BASE_PTR_REG) {
+                    return Err(format!("{} is reserved for the stack frame", name));
+                }
+
+                match defaults.iter().find(|reg| matches(reg)) {
+                    Some(reg) if regs.contains(reg) => {
+                        return Err(format!("{} is listed more than once", name));
+                    }
+                    Some(reg) => regs.push(*reg),
+                    None => {
+                        return Err(format!(
+                            "{} is not an allocatable register in this calling convention",
+                            name
+                        ));
+                    }
+                }
+            }
+
+            regs
+        }
+    };
+
+    Ok(regs)
+}
+
 impl<
         'a,
         'r,
@@ -175,9 +316,11 @@ impl<
         self.join_param_map.clear();
         self.general_used_callee_saved_regs.clear();
         self.general_free_regs.clear();
+        self.general_reserved_regs.clear();
         self.general_used_regs.clear();
+        self.general_reg_hints.clear();
         self.general_free_regs
-            .extend_from_slice(CC::GENERAL_DEFAULT_FREE_REGS);
+            .extend_from_slice(&self.general_default_free_regs);
         self.float_used_callee_saved_regs.clear();
         self.float_free_regs.clear();
         self.float_used_regs.clear();
@@ -197,14 +340,25 @@ impl<
     }
 
     pub fn general_used_callee_saved_regs(&self) -> Vec<'a, GeneralReg> {
+        // Callee saved regs are only ever marked as used when they are taken from the free list,
+        // or when they hold the host's context.
         let mut used_regs = bumpalo::vec![in self.env.arena];
-        used_regs.extend(&self.general_used_callee_saved_regs);
+        used_regs.extend(
+            CC::GENERAL_DEFAULT_FREE_REGS
+                .iter()
+                .filter(|reg| self.general_used_callee_saved_regs.contains(**reg)),
+        );
         used_regs
     }
 
     pub fn float_used_callee_saved_regs(&self) -> Vec<'a, FloatReg> {
+        // Callee saved regs are only ever marked as used when they are taken from the free list.
         let mut used_regs = bumpalo::vec![in self.env.arena];
-        used_regs.extend(&self.float_used_callee_saved_regs);
+        used_regs.extend(
+            CC::FLOAT_DEFAULT_FREE_REGS
+                .iter()
+                .filter(|reg| self.float_used_callee_saved_regs.contains(**reg)),
+        );
         used_regs
     }
 
@@ -223,6 +377,7 @@ impl<
             if CC::general_callee_saved(&reg) {
                 self.general_used_callee_saved_regs.insert(reg);
             }
+            self.stats.record_general_reg(reg.to_index());
             reg
         } else if !self.general_used_regs.is_empty() {
             let (reg, sym) = self.general_used_regs.remove(0);
@@ -240,6 +395,7 @@ impl<
             if CC::float_callee_saved(&reg) {
                 self.float_used_callee_saved_regs.insert(reg);
             }
+            self.stats.record_float_reg(reg.to_index());
             reg
         } else if !self.float_used_regs.is_empty() {
             let (reg, sym) = self.float_used_regs.remove(0);
@@ -254,7 +410,20 @@ impl<
     /// They symbol should not already have storage.
     pub fn claim_general_reg(&mut self, buf: &mut Vec<'a, u8>, sym: &Symbol) -> GeneralReg {
         debug_assert_eq!(self.symbol_storage_map.get(sym), None);
-        let reg = self.get_general_reg(buf);
+        let hinted = self.general_reg_hints.get(sym).and_then(|hint| {
+            let index = self.general_free_regs.iter().position(|reg| reg == hint)?;
+            Some(self.general_free_regs.remove(index))
+        });
+        let reg = match hinted {
+            Some(reg) => {
+                if CC::general_callee_saved(&reg) {
+                    self.general_used_callee_saved_regs.insert(reg);
+                }
+                self.stats.record_general_reg(reg.to_index());
+                reg
+            }
+            None => self.get_general_reg(buf),
+        };
         self.general_used_regs.push((reg, *sym));
         self.symbol_storage_map.insert(*sym, Reg(General(reg)));
         reg
@@ -270,16 +439,24 @@ impl<
         reg
     }
 
-    /// This claims a temporary general register and enables is used in the passed in function.
+    /// Makes `claim_general_reg` put `sym` in `reg`, if that is free when `sym` is claimed.
+    /// That saves a move when `sym` is returned or passed in `reg` right after.
+    pub fn hint_general_reg(&mut self, sym: &Symbol, reg: GeneralReg) {
+        self.general_reg_hints.insert(*sym, reg);
+    }
+
+    /// This claims a temporary general register and enables is used in the passed in function,
+    /// and returns what that returns.
     /// Temporary registers are not safe across call instructions.
-    pub fn with_tmp_general_reg<F: FnOnce(&mut Self, &mut Vec<'a, u8>, GeneralReg)>(
+    pub fn with_tmp_general_reg<R, F: FnOnce(&mut Self, &mut Vec<'a, u8>, GeneralReg) -> R>(
         &mut self,
         buf: &mut Vec<'a, u8>,
         callback: F,
-    ) {
+    ) -> R {
         let reg = self.get_general_reg(buf);
-        callback(self, buf, reg);
+        let result = callback(self, buf, reg);
         self.general_free_regs.push(reg);
+        result
     }
 
     #[allow(dead_code)]
@@ -295,6 +472,40 @@ impl<
         self.float_free_regs.push(reg);
     }
 
+    /// Gives the passed in function sole use of `regs`, e.g. for instructions with fixed operand registers.
+    /// Symbols stored in those registers are moved to the stack first,
+    /// and the allocator will not hand the registers out until the function returns.
+    /// So symbols must be loaded inside the function, loading them before would return a reserved register.
+    pub fn with_reserved_general_regs<F: FnOnce(&mut Self, &mut Vec<'a, u8>)>(
+        &mut self,
+        buf: &mut Vec<'a, u8>,
+        regs: &[GeneralReg],
+        callback: F,
+    ) {
+        let mut reserved = bumpalo::vec![in self.env.arena];
+        for reg in regs {
+            if self.general_reserved_regs.contains(reg) {
+                debug_assert!(false, "register {} is already reserved", reg);
+                continue;
+            }
+
+            self.ensure_reg_free(buf, General(*reg));
+            self.general_free_regs.retain(|r| r != reg);
+            if CC::general_callee_saved(reg) {
+                self.general_used_callee_saved_regs.insert(*reg);
+            }
+            self.general_reserved_regs.push(*reg);
+            reserved.push(*reg);
+        }
+
+        callback(self, buf);
+
+        for reg in reserved {
+            self.general_reserved_regs.retain(|r| *r != reg);
+            self.general_free_regs.push(reg);
+        }
+    }
+
     /// Loads a symbol into a general reg and returns that register.
     /// The symbol must already be stored somewhere.
     /// Will fail on values stored in float regs.
@@ -324,6 +535,7 @@ impl<
                 debug_assert_eq!(base_offset % 8, 0);
                 let reg = self.get_general_reg(buf);
                 ASM::mov_reg64_base32(buf, reg, base_offset);
+                self.stats.record_reload();
                 self.general_used_regs.push((reg, *sym));
                 self.symbol_storage_map.insert(
                     *sym,
@@ -388,6 +600,7 @@ impl<
                 debug_assert_eq!(base_offset % 8, 0);
                 let reg = self.get_float_reg(buf);
                 ASM::mov_freg64_base32(buf, reg, base_offset);
+                self.stats.record_reload();
                 self.float_used_regs.push((reg, *sym));
                 self.symbol_storage_map.insert(
                     *sym,
@@ -398,10 +611,9 @@ impl<
                 );
                 reg
             }
-            Stack(ReferencedPrimitive {
-                base_offset, size, ..
-            }) if base_offset % 8 == 0 && size == 8 => {
-                // The primitive is aligned and the data is exactly 8 bytes, treat it like regular stack.
+            Stack(ReferencedPrimitive { base_offset, .. }) => {
+                // An `F32` is in the low bytes that this loads, which is all that `F32`
+                // instructions read. The load has no alignment requirement.
                 let reg = self.get_float_reg(buf);
                 ASM::mov_freg64_base32(buf, reg, base_offset);
                 self.float_used_regs.push((reg, *sym));
@@ -409,9 +621,6 @@ impl<
                 self.free_reference(sym);
                 reg
             }
-            Stack(ReferencedPrimitive { .. }) => {
-                todo!("loading referenced primitives")
-            }
             Stack(Complex { .. }) => {
                 internal_error!("Cannot load large values into float registers: {}", sym)
             }
@@ -427,12 +636,13 @@ impl<
     /// It will not try to free the register first.
     /// This will not track the symbol change (it makes no assumptions about the new reg).
     pub fn load_to_specified_general_reg(
-        &self,
+        &mut self,
         buf: &mut Vec<'a, u8>,
         sym: &Symbol,
         reg: GeneralReg,
     ) {
-        match self.get_storage_for_sym(sym) {
+        let storage = *self.get_storage_for_sym(sym);
+        match &storage {
             Reg(General(old_reg))
             | Stack(Primitive {
                 reg: Some(General(old_reg)),
@@ -441,6 +651,7 @@ impl<
                 if *old_reg == reg {
                     return;
                 }
+                self.stats.record_move();
                 ASM::mov_reg64_reg64(buf, reg, *old_reg);
             }
             Reg(Float(_))
@@ -463,8 +674,17 @@ impl<
                 // The primitive is aligned and the data is exactly 8 bytes, treat it like regular stack.
                 ASM::mov_reg64_base32(buf, reg, *base_offset);
             }
-            Stack(ReferencedPrimitive { .. }) => {
-                todo!("loading referenced primitives")
+            Stack(ReferencedPrimitive {
+                base_offset,
+                size,
+                sign_extend,
+            }) => {
+                // like `load_to_general_reg` does it
+                if *sign_extend {
+                    ASM::movsx_reg64_base32(buf, reg, *base_offset, *size as u8);
+                } else {
+                    ASM::movzx_reg64_base32(buf, reg, *base_offset, *size as u8);
+                }
             }
             Stack(Complex { .. }) => {
                 internal_error!("Cannot load large values into general registers: {}", sym)
@@ -480,8 +700,14 @@ impl<
     /// This is only made to be used in special cases where exact regs are needed (function args and returns).
     /// It will not try to free the register first.
     /// This will not track the symbol change (it makes no assumptions about the new reg).
-    pub fn load_to_specified_float_reg(&self, buf: &mut Vec<'a, u8>, sym: &Symbol, reg: FloatReg) {
-        match self.get_storage_for_sym(sym) {
+    pub fn load_to_specified_float_reg(
+        &mut self,
+        buf: &mut Vec<'a, u8>,
+        sym: &Symbol,
+        reg: FloatReg,
+    ) {
+        let storage = *self.get_storage_for_sym(sym);
+        match &storage {
             Reg(Float(old_reg))
             | Stack(Primitive {
                 reg: Some(Float(old_reg)),
@@ -490,6 +716,7 @@ impl<
                 if *old_reg == reg {
                     return;
                 }
+                self.stats.record_move();
                 ASM::mov_freg64_freg64(buf, reg, *old_reg);
             }
             Reg(General(_))
@@ -506,15 +733,10 @@ impl<
                 debug_assert_eq!(base_offset % 8, 0);
                 ASM::mov_freg64_base32(buf, reg, *base_offset);
             }
-            Stack(ReferencedPrimitive {
-                base_offset, size, ..
-            }) if base_offset % 8 == 0 && *size == 8 => {
-                // The primitive is aligned and the data is exactly 8 bytes, treat it like regular stack.
+            Stack(ReferencedPrimitive { base_offset, .. }) => {
+                // like `load_to_float_reg` does it
                 ASM::mov_freg64_base32(buf, reg, *base_offset);
             }
-            Stack(ReferencedPrimitive { .. }) => {
-                todo!("loading referenced primitives")
-            }
             Stack(Complex { .. }) => {
                 internal_error!("Cannot load large values into float registers: {}", sym)
             }
@@ -542,13 +764,10 @@ impl<
         match self.get_storage_for_sym(structure) {
             Stack(Complex { base_offset, size }) => {
                 let (base_offset, size) = (*base_offset, *size);
-                let mut data_offset = base_offset;
-                for layout in field_layouts.iter().take(index as usize) {
-                    let field_size = layout_interner.stack_size(*layout);
-                    data_offset += field_size as i32;
-                }
+                let (field_offset, layout) =
+                    field_offsets(layout_interner, field_layouts)[index as usize];
+                let data_offset = base_offset + field_offset as i32;
                 debug_assert!(data_offset < base_offset + size as i32);
-                let layout = field_layouts[index as usize];
                 let size = layout_interner.stack_size(layout);
                 self.allocation_map.insert(*sym, owned_data);
                 self.symbol_storage_map.insert(
@@ -583,7 +802,7 @@ impl<
         sym: &Symbol,
         structure: &Symbol,
         union_layout: &UnionLayout<'a>,
-    ) {
+    ) -> BuildResult {
         // This must be removed and reinserted for ownership and mutability reasons.
         let owned_data = self.remove_allocation_for_sym(structure);
         self.allocation_map
@@ -608,8 +827,9 @@ impl<
                     }),
                 );
             }
-            x => todo!("getting tag id of union with layout ({:?})", x),
+            x => return Err(UnsupportedConstruct::of_layout(&Layout::Union(*x))),
         }
+        Ok(())
     }
 
     // Loads the dst to be the later 64 bits of a list (its length).
@@ -636,31 +856,33 @@ impl<
         sym: &Symbol,
         layout: &InLayout<'a>,
         fields: &'a [Symbol],
-    ) {
+    ) -> BuildResult {
         let struct_size = layout_interner.stack_size(*layout);
         if struct_size == 0 {
             self.symbol_storage_map.insert(*sym, NoData);
-            return;
+            return Ok(());
         }
         let base_offset = self.claim_stack_area(sym, struct_size);
+        if self.env.zero_padding || layout_interner.is_byte_comparable(*layout) {
+            self.zero_stack_area(buf, base_offset, struct_size);
+        }
 
         if let Layout::Struct { field_layouts, .. } = layout_interner.get(*layout) {
-            let mut current_offset = base_offset;
-            for (field, field_layout) in fields.iter().zip(field_layouts.iter()) {
+            let offsets = field_offsets(layout_interner, field_layouts);
+            for (field, (field_offset, field_layout)) in fields.iter().zip(offsets.iter()) {
                 self.copy_symbol_to_stack_offset(
                     layout_interner,
                     buf,
-                    current_offset,
+                    base_offset + *field_offset as i32,
                     field,
                     field_layout,
-                );
-                let field_size = layout_interner.stack_size(*field_layout);
-                current_offset += field_size as i32;
+                )?;
             }
+            Ok(())
         } else {
             // This is a single element struct. Just copy the single field to the stack.
             debug_assert_eq!(fields.len(), 1);
-            self.copy_symbol_to_stack_offset(layout_interner, buf, base_offset, &fields[0], layout);
+            self.copy_symbol_to_stack_offset(layout_interner, buf, base_offset, &fields[0], layout)
         }
     }
 
@@ -673,37 +895,84 @@ impl<
         union_layout: &UnionLayout<'a>,
         fields: &'a [Symbol],
         tag_id: TagIdIntType,
-    ) {
+    ) -> BuildResult {
         match union_layout {
             UnionLayout::NonRecursive(field_layouts) => {
                 let (data_size, data_alignment) =
                     union_layout.data_size_and_alignment(layout_interner, self.target_info);
                 let id_offset = data_size - data_alignment;
                 if data_alignment < 8 || data_alignment % 8 != 0 {
-                    todo!("small/unaligned tagging");
+                    return Err(UnsupportedConstruct::Expr(
+                        "tag unions with an alignment below 8 bytes",
+                    ));
                 }
                 let base_offset = self.claim_stack_area(sym, data_size);
-                let mut current_offset = base_offset;
-                for (field, field_layout) in
-                    fields.iter().zip(field_layouts[tag_id as usize].iter())
+                if self.env.zero_padding
+                    || Layout::Union(*union_layout).is_byte_comparable(layout_interner)
                 {
+                    self.zero_stack_area(buf, base_offset, data_size);
+                }
+                let offsets = field_offsets(layout_interner, field_layouts[tag_id as usize]);
+                for (field, (field_offset, field_layout)) in fields.iter().zip(offsets.iter()) {
                     self.copy_symbol_to_stack_offset(
                         layout_interner,
                         buf,
-                        current_offset,
+                        base_offset + *field_offset as i32,
                         field,
                         field_layout,
-                    );
-                    let field_size = layout_interner.stack_size(*field_layout);
-                    current_offset += field_size as i32;
+                    )?;
                 }
-                self.with_tmp_general_reg(buf, |_symbol_storage, buf, reg| {
-                    ASM::mov_reg64_imm64(buf, reg, tag_id as i64);
-                    debug_assert!((base_offset + id_offset as i32) % 8 == 0);
-                    ASM::mov_base32_reg64(buf, base_offset + id_offset as i32, reg);
-                });
+                debug_assert!((base_offset + id_offset as i32) % 8 == 0);
+                self.store_constant_to_stack(
+                    buf,
+                    base_offset + id_offset as i32,
+                    tag_id as i64,
+                    RegisterWidth::W64,
+                );
+                Ok(())
+            }
+            x => Err(UnsupportedConstruct::of_layout(&Layout::Union(*x))),
+        }
+    }
+
+    /// Stores the constant `value` in the `width` bytes at `offset` from the base pointer. That is
+    /// a single store, unless the constant does not fit the immediate of one, see
+    /// [constant_store_imm].
+    pub fn store_constant_to_stack(
+        &mut self,
+        buf: &mut Vec<'a, u8>,
+        offset: i32,
+        value: i64,
+        width: RegisterWidth,
+    ) {
+        match constant_store_imm(value, width) {
+            Some(imm) => operand::mov::<_, _, ASM>(
+                buf,
+                width,
+                Operand::Base(offset),
+                Operand::Imm(i64::from(imm)),
+            ),
+            None => self.with_tmp_general_reg(buf, |_storage_manager, buf, reg| {
+                ASM::load_constant(buf, reg, value, false);
+                ASM::mov_base32_reg64(buf, offset, reg);
+            }),
+        }
+    }
+
+    /// Zeroes the stack area at `base_offset`, so the bytes that no field is stored to,
+    /// i.e. the padding, are deterministic.
+    fn zero_stack_area(&mut self, buf: &mut Vec<'a, u8>, base_offset: i32, size: u32) {
+        debug_assert!(base_offset % 8 == 0);
+        // the area takes up whole words, see `claim_stack_size`
+        let size = (size + 7) / 8 * 8;
+        if size <= ZERO_BY_STORES_MAX_SIZE {
+            for i in (0..size as i32).step_by(8) {
+                ASM::mov_base32_imm32(buf, base_offset + i, 0);
             }
-            x => todo!("creating unions with layout: {:?}", x),
+        } else {
+            self.with_reserved_general_regs(buf, ASM::ZERO_FIXED_REGS, |_storage_manager, buf| {
+                ASM::zero_base32(buf, base_offset, size);
+            });
         }
     }
 
@@ -721,7 +990,15 @@ impl<
         self.with_tmp_general_reg(buf, |_storage_manager, buf, tmp_reg| {
             for i in (0..size as i32).step_by(8) {
                 ASM::mov_reg64_base32(buf, tmp_reg, base_offset + i);
-                ASM::mov_mem64_offset32_reg64(buf, ret_reg, i, tmp_reg);
+                operand::mov::<_, _, ASM>(
+                    buf,
+                    RegisterWidth::W64,
+                    Operand::Mem {
+                        base: ret_reg,
+                        offset: i,
+                    },
+                    Operand::Reg(tmp_reg),
+                );
             }
         });
     }
@@ -737,13 +1014,38 @@ impl<
         to_offset: i32,
         sym: &Symbol,
         layout: &InLayout<'a>,
-    ) {
+    ) -> BuildResult {
         match layout_interner.get(*layout) {
             Layout::Builtin(Builtin::Int(IntWidth::I64 | IntWidth::U64)) => {
                 debug_assert_eq!(to_offset % 8, 0);
                 let reg = self.load_to_general_reg(buf, sym);
                 ASM::mov_base32_reg64(buf, to_offset, reg);
             }
+            Layout::Builtin(Builtin::Int(
+                int_width @ (IntWidth::I32
+                | IntWidth::U32
+                | IntWidth::I16
+                | IntWidth::U16
+                | IntWidth::I8
+                | IntWidth::U8),
+            )) => {
+                let reg = self.load_to_general_reg(buf, sym);
+                operand::mov::<_, _, ASM>(
+                    buf,
+                    RegisterWidth::of_int(int_width),
+                    Operand::Base(to_offset),
+                    Operand::Reg(reg),
+                );
+            }
+            Layout::Builtin(Builtin::Bool) => {
+                let reg = self.load_to_general_reg(buf, sym);
+                operand::mov::<_, _, ASM>(
+                    buf,
+                    RegisterWidth::W8,
+                    Operand::Base(to_offset),
+                    Operand::Reg(reg),
+                );
+            }
             Layout::Builtin(Builtin::Float(FloatWidth::F64)) => {
                 debug_assert_eq!(to_offset % 8, 0);
                 let reg = self.load_to_float_reg(buf, sym);
@@ -766,11 +1068,11 @@ impl<
                     }
                 });
             }
-            x => todo!("copying data to the stack with layout, {:?}", x),
+            x => return Err(UnsupportedConstruct::of_layout(&x)),
         }
+        Ok(())
     }
 
-    #[allow(dead_code)]
     /// Ensures that a register is free. If it is not free, data will be moved to make it free.
     pub fn ensure_reg_free(
         &mut self,
@@ -823,10 +1125,12 @@ impl<
         match self.remove_storage_for_sym(sym) {
             Reg(reg_storage) => {
                 let base_offset = self.claim_stack_size(8);
-                match reg_storage {
-                    General(reg) => ASM::mov_base32_reg64(buf, base_offset, reg),
-                    Float(reg) => ASM::mov_base32_freg64(buf, base_offset, reg),
-                }
+                operand::mov::<_, _, ASM>(
+                    buf,
+                    RegisterWidth::W64,
+                    Operand::Base(base_offset),
+                    reg_storage.into(),
+                );
                 self.symbol_storage_map.insert(
                     *sym,
                     Stack(Primitive {
@@ -883,10 +1187,13 @@ impl<
             Reg(reg_storage) => {
                 debug_assert_eq!(reg_storage, wanted_reg);
                 let base_offset = self.claim_stack_size(8);
-                match reg_storage {
-                    General(reg) => ASM::mov_base32_reg64(buf, base_offset, reg),
-                    Float(reg) => ASM::mov_base32_freg64(buf, base_offset, reg),
-                }
+                self.stats.record_spill();
+                operand::mov::<_, _, ASM>(
+                    buf,
+                    RegisterWidth::W64,
+                    Operand::Base(base_offset),
+                    reg_storage.into(),
+                );
                 self.symbol_storage_map.insert(
                     *sym,
                     Stack(Primitive {
@@ -915,6 +1222,11 @@ impl<
         }
     }
 
+    /// Whether the symbol has storage, i.e. it is defined and not freed yet.
+    pub fn has_storage(&self, sym: &Symbol) -> bool {
+        self.symbol_storage_map.contains_key(sym)
+    }
+
     /// gets the stack offset and size of the specified symbol.
     /// the symbol must already be stored on the stack.
     pub fn stack_offset_and_size(&self, sym: &Symbol) -> (i32, u32) {
@@ -1003,7 +1315,7 @@ impl<
         _buf: &mut Vec<'a, u8>,
         id: &JoinPointId,
         params: &'a [Param<'a>],
-    ) {
+    ) -> BuildResult {
         let mut param_storage = bumpalo::vec![in self.env.arena];
         param_storage.reserve(params.len());
         for Param {
@@ -1015,7 +1327,9 @@ impl<
             if *ownership == Ownership::Borrowed {
                 // These probably need to be passed by pointer/reference?
                 // Otherwise, we probably need to copy back to the param at the end of the joinpoint.
-                todo!("joinpoints with borrowed parameters");
+                return Err(UnsupportedConstruct::Expr(
+                    "join points with borrowed parameters",
+                ));
             }
             // Claim a location for every join point parameter to be loaded at.
             // Put everything on the stack for simplicity.
@@ -1044,6 +1358,7 @@ impl<
             param_storage.push(*self.get_storage_for_sym(symbol));
         }
         self.join_param_map.insert(*id, param_storage);
+        Ok(())
     }
 
     /// Setup jump loads the parameters for the joinpoint.
@@ -1055,7 +1370,7 @@ impl<
         id: &JoinPointId,
         args: &[Symbol],
         arg_layouts: &[InLayout<'a>],
-    ) {
+    ) -> BuildResult {
         // TODO: remove was use here and for current_storage to deal with borrow checker.
         // See if we can do this better.
         let param_storage = match self.join_param_map.remove(id) {
@@ -1084,7 +1399,7 @@ impl<
                         *base_offset,
                         sym,
                         layout,
-                    );
+                    )?;
                 }
                 Stack(Primitive {
                     base_offset,
@@ -1119,6 +1434,7 @@ impl<
             }
         }
         self.join_param_map.insert(*id, param_storage);
+        Ok(())
     }
 
     /// claim_stack_area is the public wrapper around claim_stack_size.
@@ -1134,6 +1450,20 @@ impl<
         base_offset
     }
 
+    /// Marks the register of the host's context as used, so the procedure saves and restores it.
+    /// It never is in the free list, see `Env::runtime_context`.
+    pub fn claim_runtime_context_reg(&mut self) {
+        self.general_used_callee_saved_regs
+            .insert(CC::RUNTIME_CONTEXT_REG);
+    }
+
+    /// Claims the slot for the stack canary, which has to be the first one, right below the base
+    /// pointer. It is never freed.
+    pub fn claim_stack_canary(&mut self) -> i32 {
+        debug_assert_eq!(self.stack_size, 0);
+        self.claim_stack_size(STACK_CANARY_SIZE)
+    }
+
     /// claim_stack_size claims `amount` bytes from the stack alignind to 8.
     /// This may be free space in the stack or result in increasing the stack size.
     /// It returns base pointer relative offset of the new data.
@@ -1268,27 +1598,33 @@ impl<
         }
     }
 
-    pub fn push_used_caller_saved_regs_to_stack(&mut self, buf: &mut Vec<'a, u8>) {
-        let old_general_used_regs = std::mem::replace(
-            &mut self.general_used_regs,
-            bumpalo::vec![in self.env.arena],
-        );
-        for (reg, saved_sym) in old_general_used_regs.into_iter() {
-            if CC::general_caller_saved(&reg) {
+    pub fn push_used_caller_saved_regs_to_stack(&mut self, buf: &mut Vec<'a, u8>, args: &[Symbol]) {
+        // This runs for every call, so sweep the used regs in place.
+        // Replacing them with a fresh vec would leak arena memory on every call.
+        let mut i = 0;
+        while i < self.general_used_regs.len() {
+            let (reg, saved_sym) = self.general_used_regs[i];
+            // An argument that got the param reg it was hinted for is already where the call
+            // needs it, and it is only hinted if it is not used after the call.
+            let in_param_reg =
+                args.contains(&saved_sym) && self.general_reg_hints.get(&saved_sym) == Some(&reg);
+            if CC::general_caller_saved(&reg) && !in_param_reg {
+                self.general_used_regs.remove(i);
                 self.general_free_regs.push(reg);
                 self.free_to_stack(buf, &saved_sym, General(reg));
             } else {
-                self.general_used_regs.push((reg, saved_sym));
+                i += 1;
             }
         }
-        let old_float_used_regs =
-            std::mem::replace(&mut self.float_used_regs, bumpalo::vec![in self.env.arena]);
-        for (reg, saved_sym) in old_float_used_regs.into_iter() {
+        let mut i = 0;
+        while i < self.float_used_regs.len() {
+            let (reg, saved_sym) = self.float_used_regs[i];
             if CC::float_caller_saved(&reg) {
+                self.float_used_regs.remove(i);
                 self.float_free_regs.push(reg);
                 self.free_to_stack(buf, &saved_sym, Float(reg));
             } else {
-                self.float_used_regs.push((reg, saved_sym));
+                i += 1;
             }
         }
     }
@@ -1334,3 +1670,198 @@ impl<
 fn is_primitive(layout: InLayout<'_>) -> bool {
     matches!(layout, single_register_layouts!())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generic64::aarch64::AArch64GeneralReg;
+    use crate::generic64::x86_64::{
+        X86_64Assembler, X86_64FloatReg, X86_64GeneralReg, X86_64SystemV,
+    };
+    use crate::test_utils::Rng;
+    use bumpalo::Bump;
+    use roc_collections::all::MutSet;
+    use roc_module::symbol::{Interns, ModuleId};
+
+    type X86_64StorageManager<'a, 'r> =
+        StorageManager<'a, 'r, X86_64GeneralReg, X86_64FloatReg, X86_64Assembler, X86_64SystemV>;
+
+    /// `push_used_caller_saved_regs_to_stack` as it was before it swept the used regs in place:
+    /// it moved all of them to a new vec, and put back the ones that stay in their register.
+    fn push_used_caller_saved_regs_by_copying<'a>(
+        storage_manager: &mut X86_64StorageManager<'a, '_>,
+        buf: &mut Vec<'a, u8>,
+        args: &[Symbol],
+    ) {
+        let arena = storage_manager.env.arena;
+
+        let old_general_used_regs = std::mem::replace(
+            &mut storage_manager.general_used_regs,
+            bumpalo::vec![in arena],
+        );
+        for (reg, saved_sym) in old_general_used_regs.into_iter() {
+            let in_param_reg = args.contains(&saved_sym)
+                && storage_manager.general_reg_hints.get(&saved_sym) == Some(&reg);
+            if X86_64SystemV::general_caller_saved(&reg) && !in_param_reg {
+                storage_manager.general_free_regs.push(reg);
+                storage_manager.free_to_stack(buf, &saved_sym, General(reg));
+            } else {
+                storage_manager.general_used_regs.push((reg, saved_sym));
+            }
+        }
+        let old_float_used_regs = std::mem::replace(
+            &mut storage_manager.float_used_regs,
+            bumpalo::vec![in arena],
+        );
+        for (reg, saved_sym) in old_float_used_regs.into_iter() {
+            if X86_64SystemV::float_caller_saved(&reg) {
+                storage_manager.float_free_regs.push(reg);
+                storage_manager.free_to_stack(buf, &saved_sym, Float(reg));
+            } else {
+                storage_manager.float_used_regs.push((reg, saved_sym));
+            }
+        }
+    }
+
+    fn assert_same_regs(
+        in_place: &X86_64StorageManager<'_, '_>,
+        by_copying: &X86_64StorageManager<'_, '_>,
+    ) {
+        assert_eq!(in_place.general_used_regs, by_copying.general_used_regs);
+        assert_eq!(in_place.general_free_regs, by_copying.general_free_regs);
+        assert_eq!(in_place.float_used_regs, by_copying.float_used_regs);
+        assert_eq!(in_place.float_free_regs, by_copying.float_free_regs);
+        assert_eq!(in_place.symbol_storage_map, by_copying.symbol_storage_map);
+    }
+
+    #[test]
+    fn register_indices_fit_in_a_reg_set() {
+        fn assert_distinct_and_small<Reg: RegTrait>(regs: &[Reg]) {
+            let indices: MutSet<usize> = regs.iter().map(|reg| reg.to_index()).collect();
+
+            assert_eq!(indices.len(), regs.len(), "{:?}", regs);
+            assert!(indices.iter().all(|index| *index < 64), "{:?}", regs);
+        }
+
+        assert_distinct_and_small::<X86_64GeneralReg>(
+            <X86_64SystemV as CallConv<_, _, X86_64Assembler>>::GENERAL_DEFAULT_FREE_REGS,
+        );
+        assert_distinct_and_small::<X86_64FloatReg>(
+            <X86_64SystemV as CallConv<_, _, X86_64Assembler>>::FLOAT_DEFAULT_FREE_REGS,
+        );
+        assert_distinct_and_small::<AArch64GeneralReg>(
+            <crate::generic64::aarch64::AArch64Call as CallConv<
+                _,
+                _,
+                crate::generic64::aarch64::AArch64Assembler,
+            >>::GENERAL_DEFAULT_FREE_REGS,
+        );
+    }
+
+    #[test]
+    fn reg_sets_agree_with_hash_sets() {
+        let regs = <X86_64SystemV as CallConv<_, _, X86_64Assembler>>::GENERAL_DEFAULT_FREE_REGS;
+        let mut rng = Rng(0x9E37_79B9_7F4A_7C15);
+
+        for _ in 0..100 {
+            let mut reg_set = RegSet::default();
+            let mut hash_set = MutSet::default();
+            for _ in 0..rng.below(2 * regs.len()) {
+                let reg = regs[rng.below(regs.len())];
+                reg_set.insert(reg);
+                hash_set.insert(reg);
+            }
+
+            for reg in regs {
+                assert_eq!(reg_set.contains(*reg), hash_set.contains(reg), "{:?}", reg);
+            }
+
+            reg_set.clear();
+            assert!(regs.iter().all(|reg| !reg_set.contains(*reg)));
+        }
+    }
+
+    /// Claims, frees and calls at random, for 500 calls, and checks after every step that
+    /// sweeping the used regs in place leaves the same registers and stack as copying them did.
+    #[test]
+    fn sweeping_caller_saved_regs_in_place_matches_copying_them() {
+        let arena = Bump::new();
+        let env = crate::Env::for_tests(&arena);
+        let target_info = TargetInfo::default_x86_64();
+        let mut in_place: X86_64StorageManager = new_storage_manager(&env, target_info);
+        let mut by_copying: X86_64StorageManager = new_storage_manager(&env, target_info);
+        in_place.reset();
+        by_copying.reset();
+        let mut in_place_buf = bumpalo::vec![in &arena];
+        let mut by_copying_buf = bumpalo::vec![in &arena];
+
+        let param_regs = <X86_64SystemV as CallConv<_, _, X86_64Assembler>>::GENERAL_PARAM_REGS;
+        let mut rng = Rng(0x2545_F491_4F6C_DD1D);
+        let mut live = std::vec::Vec::new();
+        let mut callee_saved_taken = MutSet::default();
+        let mut next_symbol = 1000;
+        let mut calls = 0;
+
+        while calls < 500 {
+            match rng.below(8) {
+                0..=2 => {
+                    let sym = Interns::from_index(ModuleId::NUM, next_symbol);
+                    next_symbol += 1;
+                    if rng.below(4) == 0 {
+                        let hint = param_regs[rng.below(param_regs.len())];
+                        in_place.hint_general_reg(&sym, hint);
+                        by_copying.hint_general_reg(&sym, hint);
+                    }
+
+                    let reg = in_place.claim_general_reg(&mut in_place_buf, &sym);
+                    assert_eq!(by_copying.claim_general_reg(&mut by_copying_buf, &sym), reg);
+                    if X86_64SystemV::general_callee_saved(&reg) {
+                        callee_saved_taken.insert(reg);
+                    }
+                    live.push(sym);
+                }
+                3 => {
+                    let sym = Interns::from_index(ModuleId::NUM, next_symbol);
+                    next_symbol += 1;
+
+                    let reg = in_place.claim_float_reg(&mut in_place_buf, &sym);
+                    assert_eq!(by_copying.claim_float_reg(&mut by_copying_buf, &sym), reg);
+                    live.push(sym);
+                }
+                4 if !live.is_empty() => {
+                    let sym = live.swap_remove(rng.below(live.len()));
+                    in_place.free_symbol(&sym);
+                    by_copying.free_symbol(&sym);
+                }
+                _ => {
+                    let args: std::vec::Vec<_> =
+                        live.iter().copied().filter(|_| rng.below(3) == 0).collect();
+                    in_place.push_used_caller_saved_regs_to_stack(&mut in_place_buf, &args);
+                    push_used_caller_saved_regs_by_copying(
+                        &mut by_copying,
+                        &mut by_copying_buf,
+                        &args,
+                    );
+                    calls += 1;
+                }
+            }
+
+            assert_same_regs(&in_place, &by_copying);
+            assert_eq!(in_place_buf, by_copying_buf);
+        }
+
+        // the hashed set these replaced had the same regs, just not in a predictable order
+        let used_callee_saved = in_place.general_used_callee_saved_regs();
+        assert_eq!(
+            used_callee_saved.iter().copied().collect::<MutSet<_>>(),
+            callee_saved_taken
+        );
+        let default_order: std::vec::Vec<_> =
+            <X86_64SystemV as CallConv<_, _, X86_64Assembler>>::GENERAL_DEFAULT_FREE_REGS
+                .iter()
+                .filter(|reg| callee_saved_taken.contains(*reg))
+                .copied()
+                .collect();
+        assert_eq!(&used_callee_saved[..], &default_order[..]);
+    }
+}
@@ -1,12 +1,25 @@
-use crate::generic64::{storage::StorageManager, Assembler, CallConv, RegTrait};
+use crate::cost_model::{CostModel, LatencyTable, PlannedInst};
+use crate::generic64::{
+    branches::BranchKind,
+    operand::{self, Operand, RegisterWidth},
+    storage::StorageManager,
+    Assembler, CallConv, RegTrait, SignCondition,
+};
+use crate::unsupported::{BuildResult, UnsupportedConstruct};
 use crate::{
     single_register_floats, single_register_int_builtins, single_register_integers,
     single_register_layouts, Relocation,
 };
 use bumpalo::collections::Vec;
+use roc_builtins::bitcode::FloatWidth;
 use roc_error_macros::internal_error;
 use roc_module::symbol::Symbol;
 use roc_mono::layout::{InLayout, Layout, LayoutInterner, STLayoutInterner};
+use roc_target::{Endianness, TargetInfo};
+
+pub(crate) mod windows_unwind;
+
+use windows_unwind::{unwind_info, PrologueBuilder, UnwindCode, MAX_FRAME_POINTER_OFFSET};
 
 // Not sure exactly how I want to represent registers.
 // If we want max speed, we would likely make them structs that impl the same trait to avoid ifs.
@@ -36,28 +49,108 @@ impl RegTrait for X86_64GeneralReg {
 }
 impl std::fmt::Display for X86_64GeneralReg {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(
-            f,
-            "{}",
-            match self {
-                X86_64GeneralReg::RAX => "rax",
-                X86_64GeneralReg::RBX => "rbx",
-                X86_64GeneralReg::RCX => "rcx",
-                X86_64GeneralReg::RDX => "rdx",
-                X86_64GeneralReg::RBP => "rbp",
-                X86_64GeneralReg::RSP => "rsp",
-                X86_64GeneralReg::RDI => "rdi",
-                X86_64GeneralReg::RSI => "rsi",
-                X86_64GeneralReg::R8 => "r8",
-                X86_64GeneralReg::R9 => "r9",
-                X86_64GeneralReg::R10 => "r10",
-                X86_64GeneralReg::R11 => "r11",
-                X86_64GeneralReg::R12 => "r12",
-                X86_64GeneralReg::R13 => "r13",
-                X86_64GeneralReg::R14 => "r14",
-                X86_64GeneralReg::R15 => "r15",
-            }
-        )
+        write!(f, "{}", self.name_for_width(RegisterWidth::W64))
+    }
+}
+
+impl std::str::FromStr for X86_64GeneralReg {
+    type Err = String;
+
+    /// Parses the 64-bit register names, in either case, e.g. "rax" or "RAX".
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "rax" => Ok(X86_64GeneralReg::RAX),
+            "rcx" => Ok(X86_64GeneralReg::RCX),
+            "rdx" => Ok(X86_64GeneralReg::RDX),
+            "rbx" => Ok(X86_64GeneralReg::RBX),
+            "rsp" => Ok(X86_64GeneralReg::RSP),
+            "rbp" => Ok(X86_64GeneralReg::RBP),
+            "rsi" => Ok(X86_64GeneralReg::RSI),
+            "rdi" => Ok(X86_64GeneralReg::RDI),
+            "r8" => Ok(X86_64GeneralReg::R8),
+            "r9" => Ok(X86_64GeneralReg::R9),
+            "r10" => Ok(X86_64GeneralReg::R10),
+            "r11" => Ok(X86_64GeneralReg::R11),
+            "r12" => Ok(X86_64GeneralReg::R12),
+            "r13" => Ok(X86_64GeneralReg::R13),
+            "r14" => Ok(X86_64GeneralReg::R14),
+            "r15" => Ok(X86_64GeneralReg::R15),
+            _ => Err(format!("unknown x86_64 general register: {:?}", s)),
+        }
+    }
+}
+
+impl X86_64GeneralReg {
+    /// The name of this register when used with the given operand width, e.g. "al" for the low 8 bits of rax.
+    pub fn name_for_width(&self, width: RegisterWidth) -> &'static str {
+        use RegisterWidth::*;
+
+        match (self, width) {
+            (X86_64GeneralReg::RAX, W8) => "al",
+            (X86_64GeneralReg::RAX, W16) => "ax",
+            (X86_64GeneralReg::RAX, W32) => "eax",
+            (X86_64GeneralReg::RAX, W64) => "rax",
+            (X86_64GeneralReg::RCX, W8) => "cl",
+            (X86_64GeneralReg::RCX, W16) => "cx",
+            (X86_64GeneralReg::RCX, W32) => "ecx",
+            (X86_64GeneralReg::RCX, W64) => "rcx",
+            (X86_64GeneralReg::RDX, W8) => "dl",
+            (X86_64GeneralReg::RDX, W16) => "dx",
+            (X86_64GeneralReg::RDX, W32) => "edx",
+            (X86_64GeneralReg::RDX, W64) => "rdx",
+            (X86_64GeneralReg::RBX, W8) => "bl",
+            (X86_64GeneralReg::RBX, W16) => "bx",
+            (X86_64GeneralReg::RBX, W32) => "ebx",
+            (X86_64GeneralReg::RBX, W64) => "rbx",
+            (X86_64GeneralReg::RSP, W8) => "spl",
+            (X86_64GeneralReg::RSP, W16) => "sp",
+            (X86_64GeneralReg::RSP, W32) => "esp",
+            (X86_64GeneralReg::RSP, W64) => "rsp",
+            (X86_64GeneralReg::RBP, W8) => "bpl",
+            (X86_64GeneralReg::RBP, W16) => "bp",
+            (X86_64GeneralReg::RBP, W32) => "ebp",
+            (X86_64GeneralReg::RBP, W64) => "rbp",
+            (X86_64GeneralReg::RSI, W8) => "sil",
+            (X86_64GeneralReg::RSI, W16) => "si",
+            (X86_64GeneralReg::RSI, W32) => "esi",
+            (X86_64GeneralReg::RSI, W64) => "rsi",
+            (X86_64GeneralReg::RDI, W8) => "dil",
+            (X86_64GeneralReg::RDI, W16) => "di",
+            (X86_64GeneralReg::RDI, W32) => "edi",
+            (X86_64GeneralReg::RDI, W64) => "rdi",
+            (X86_64GeneralReg::R8, W8) => "r8b",
+            (X86_64GeneralReg::R8, W16) => "r8w",
+            (X86_64GeneralReg::R8, W32) => "r8d",
+            (X86_64GeneralReg::R8, W64) => "r8",
+            (X86_64GeneralReg::R9, W8) => "r9b",
+            (X86_64GeneralReg::R9, W16) => "r9w",
+            (X86_64GeneralReg::R9, W32) => "r9d",
+            (X86_64GeneralReg::R9, W64) => "r9",
+            (X86_64GeneralReg::R10, W8) => "r10b",
+            (X86_64GeneralReg::R10, W16) => "r10w",
+            (X86_64GeneralReg::R10, W32) => "r10d",
+            (X86_64GeneralReg::R10, W64) => "r10",
+            (X86_64GeneralReg::R11, W8) => "r11b",
+            (X86_64GeneralReg::R11, W16) => "r11w",
+            (X86_64GeneralReg::R11, W32) => "r11d",
+            (X86_64GeneralReg::R11, W64) => "r11",
+            (X86_64GeneralReg::R12, W8) => "r12b",
+            (X86_64GeneralReg::R12, W16) => "r12w",
+            (X86_64GeneralReg::R12, W32) => "r12d",
+            (X86_64GeneralReg::R12, W64) => "r12",
+            (X86_64GeneralReg::R13, W8) => "r13b",
+            (X86_64GeneralReg::R13, W16) => "r13w",
+            (X86_64GeneralReg::R13, W32) => "r13d",
+            (X86_64GeneralReg::R13, W64) => "r13",
+            (X86_64GeneralReg::R14, W8) => "r14b",
+            (X86_64GeneralReg::R14, W16) => "r14w",
+            (X86_64GeneralReg::R14, W32) => "r14d",
+            (X86_64GeneralReg::R14, W64) => "r14",
+            (X86_64GeneralReg::R15, W8) => "r15b",
+            (X86_64GeneralReg::R15, W16) => "r15w",
+            (X86_64GeneralReg::R15, W32) => "r15d",
+            (X86_64GeneralReg::R15, W64) => "r15",
+        }
     }
 }
 
@@ -112,6 +205,33 @@ impl std::fmt::Display for X86_64FloatReg {
     }
 }
 
+impl std::str::FromStr for X86_64FloatReg {
+    type Err = String;
+
+    /// Parses register names in either case, e.g. "xmm3" or "XMM3".
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "xmm0" => Ok(X86_64FloatReg::XMM0),
+            "xmm1" => Ok(X86_64FloatReg::XMM1),
+            "xmm2" => Ok(X86_64FloatReg::XMM2),
+            "xmm3" => Ok(X86_64FloatReg::XMM3),
+            "xmm4" => Ok(X86_64FloatReg::XMM4),
+            "xmm5" => Ok(X86_64FloatReg::XMM5),
+            "xmm6" => Ok(X86_64FloatReg::XMM6),
+            "xmm7" => Ok(X86_64FloatReg::XMM7),
+            "xmm8" => Ok(X86_64FloatReg::XMM8),
+            "xmm9" => Ok(X86_64FloatReg::XMM9),
+            "xmm10" => Ok(X86_64FloatReg::XMM10),
+            "xmm11" => Ok(X86_64FloatReg::XMM11),
+            "xmm12" => Ok(X86_64FloatReg::XMM12),
+            "xmm13" => Ok(X86_64FloatReg::XMM13),
+            "xmm14" => Ok(X86_64FloatReg::XMM14),
+            "xmm15" => Ok(X86_64FloatReg::XMM15),
+            _ => Err(format!("unknown x86_64 float register: {:?}", s)),
+        }
+    }
+}
+
 #[derive(Copy, Clone)]
 pub struct X86_64Assembler {}
 #[derive(Copy, Clone)]
@@ -157,6 +277,8 @@ impl CallConv<X86_64GeneralReg, X86_64FloatReg, X86_64Assembler> for X86_64Syste
         X86_64GeneralReg::R10,
         X86_64GeneralReg::R11,
     ];
+    const SCRATCH_REGS: [X86_64GeneralReg; 2] = [X86_64GeneralReg::R10, X86_64GeneralReg::R11];
+    const RUNTIME_CONTEXT_REG: X86_64GeneralReg = X86_64GeneralReg::R15;
 
     const FLOAT_PARAM_REGS: &'static [X86_64FloatReg] = &[
         X86_64FloatReg::XMM0,
@@ -194,6 +316,16 @@ impl CallConv<X86_64GeneralReg, X86_64FloatReg, X86_64Assembler> for X86_64Syste
     ];
     const SHADOW_SPACE_SIZE: u8 = 0;
 
+    /// The 128 bytes below `rsp` that signal handlers and interrupts leave alone.
+    #[inline(always)]
+    fn red_zone_size(target_info: TargetInfo) -> i32 {
+        if target_info.disable_red_zone {
+            0
+        } else {
+            128
+        }
+    }
+
     #[inline(always)]
     fn general_callee_saved(reg: &X86_64GeneralReg) -> bool {
         matches!(
@@ -219,6 +351,7 @@ impl CallConv<X86_64GeneralReg, X86_64FloatReg, X86_64Assembler> for X86_64Syste
         saved_float_regs: &[X86_64FloatReg],
         requested_stack_size: i32,
         fn_call_stack_size: i32,
+        red_zone_size: i32,
     ) -> i32 {
         x86_64_generic_setup_stack(
             buf,
@@ -226,6 +359,7 @@ impl CallConv<X86_64GeneralReg, X86_64FloatReg, X86_64Assembler> for X86_64Syste
             saved_float_regs,
             requested_stack_size,
             fn_call_stack_size,
+            red_zone_size,
         )
     }
 
@@ -236,6 +370,7 @@ impl CallConv<X86_64GeneralReg, X86_64FloatReg, X86_64Assembler> for X86_64Syste
         saved_float_regs: &[X86_64FloatReg],
         aligned_stack_size: i32,
         fn_call_stack_size: i32,
+        red_zone_size: i32,
     ) {
         x86_64_generic_cleanup_stack(
             buf,
@@ -243,6 +378,7 @@ impl CallConv<X86_64GeneralReg, X86_64FloatReg, X86_64Assembler> for X86_64Syste
             saved_float_regs,
             aligned_stack_size,
             fn_call_stack_size,
+            red_zone_size,
         )
     }
 
@@ -260,7 +396,7 @@ impl CallConv<X86_64GeneralReg, X86_64FloatReg, X86_64Assembler> for X86_64Syste
         layout_interner: &mut STLayoutInterner<'a>,
         args: &'a [(InLayout<'a>, Symbol)],
         ret_layout: &InLayout<'a>,
-    ) {
+    ) -> BuildResult {
         let mut arg_offset = Self::SHADOW_SPACE_SIZE as i32 + 16; // 16 is the size of the pushed return address and base pointer.
         let mut general_i = 0;
         let mut float_i = 0;
@@ -309,12 +445,11 @@ impl CallConv<X86_64GeneralReg, X86_64FloatReg, X86_64Assembler> for X86_64Syste
                             arg_offset += 8;
                         }
                     }
-                    _ => {
-                        todo!("Loading args with layout {:?}", layout_interner.dbg(other));
-                    }
+                    _ => return Err(UnsupportedConstruct::layout(layout_interner, other)),
                 },
             }
         }
+        Ok(())
     }
 
     #[inline(always)]
@@ -333,7 +468,7 @@ impl CallConv<X86_64GeneralReg, X86_64FloatReg, X86_64Assembler> for X86_64Syste
         args: &[Symbol],
         arg_layouts: &[InLayout<'a>],
         ret_layout: &InLayout<'a>,
-    ) {
+    ) -> BuildResult {
         let mut tmp_stack_offset = Self::SHADOW_SPACE_SIZE as i32;
         let mut general_i = 0;
         let mut float_i = 0;
@@ -407,16 +542,19 @@ impl CallConv<X86_64GeneralReg, X86_64FloatReg, X86_64Assembler> for X86_64Syste
                     // Use return reg as buffer because it will be empty right now.
                     let (base_offset, size) = storage_manager.stack_offset_and_size(sym);
                     debug_assert_eq!(base_offset % 8, 0);
+                    let tmp = Operand::Reg(Self::GENERAL_RETURN_REGS[0]);
                     for i in (0..size as i32).step_by(8) {
-                        X86_64Assembler::mov_reg64_base32(
+                        operand::mov::<_, _, X86_64Assembler>(
                             buf,
-                            Self::GENERAL_RETURN_REGS[0],
-                            base_offset + i,
+                            RegisterWidth::W64,
+                            tmp,
+                            Operand::Base(base_offset + i),
                         );
-                        X86_64Assembler::mov_stack32_reg64(
+                        operand::mov::<_, _, X86_64Assembler>(
                             buf,
-                            tmp_stack_offset + i,
-                            Self::GENERAL_RETURN_REGS[0],
+                            RegisterWidth::W64,
+                            Operand::Stack(tmp_stack_offset + i),
+                            tmp,
                         );
                     }
                     tmp_stack_offset += size as i32;
@@ -448,14 +586,13 @@ impl CallConv<X86_64GeneralReg, X86_64FloatReg, X86_64Assembler> for X86_64Syste
                                 tmp_stack_offset += 8;
                             }
                         }
-                        _ => {
-                            todo!("calling with arg type, {:?}", layout_interner.dbg(other));
-                        }
+                        _ => return Err(UnsupportedConstruct::layout(layout_interner, other)),
                     }
                 }
             }
         }
         storage_manager.update_fn_call_stack_size(tmp_stack_offset as u32);
+        Ok(())
     }
 
     fn return_complex_symbol<'a, 'r>(
@@ -471,7 +608,7 @@ impl CallConv<X86_64GeneralReg, X86_64FloatReg, X86_64Assembler> for X86_64Syste
         layout_interner: &mut STLayoutInterner<'a>,
         sym: &Symbol,
         layout: &InLayout<'a>,
-    ) {
+    ) -> BuildResult {
         match *layout {
             single_register_layouts!() => {
                 internal_error!("single register layouts are not complex symbols");
@@ -514,6 +651,7 @@ impl CallConv<X86_64GeneralReg, X86_64FloatReg, X86_64Assembler> for X86_64Syste
                 );
             }
         }
+        Ok(())
     }
 
     fn load_returned_complex_symbol<'a, 'r>(
@@ -529,12 +667,15 @@ impl CallConv<X86_64GeneralReg, X86_64FloatReg, X86_64Assembler> for X86_64Syste
         layout_interner: &mut STLayoutInterner<'a>,
         sym: &Symbol,
         layout: &InLayout<'a>,
-    ) {
+    ) -> BuildResult {
         match *layout {
             single_register_layouts!() => {
                 internal_error!("single register layouts are not complex symbols");
             }
-            x if layout_interner.stack_size(x) == 0 => {}
+            x if layout_interner.stack_size(x) == 0 => {
+                // nothing was returned, but the result can still be used, e.g. returned in turn
+                storage_manager.no_data_arg(sym);
+            }
             x if !Self::returns_via_arg_pointer(layout_interner, &x) => {
                 let size = layout_interner.stack_size(*layout);
                 let offset = storage_manager.claim_stack_area(sym, size);
@@ -559,6 +700,7 @@ impl CallConv<X86_64GeneralReg, X86_64FloatReg, X86_64Assembler> for X86_64Syste
                 // Nothing to do.
             }
         }
+        Ok(())
     }
 }
 
@@ -608,6 +750,8 @@ impl CallConv<X86_64GeneralReg, X86_64FloatReg, X86_64Assembler> for X86_64Windo
         X86_64GeneralReg::R10,
         X86_64GeneralReg::R11,
     ];
+    const SCRATCH_REGS: [X86_64GeneralReg; 2] = [X86_64GeneralReg::R10, X86_64GeneralReg::R11];
+    const RUNTIME_CONTEXT_REG: X86_64GeneralReg = X86_64GeneralReg::R15;
     const FLOAT_PARAM_REGS: &'static [X86_64FloatReg] = &[
         X86_64FloatReg::XMM0,
         X86_64FloatReg::XMM1,
@@ -620,7 +764,7 @@ impl CallConv<X86_64GeneralReg, X86_64FloatReg, X86_64Assembler> for X86_64Windo
         // We will use pop to get which reg to use next
         // Use callee saved regs last.
         X86_64FloatReg::XMM15,
-        X86_64FloatReg::XMM15,
+        X86_64FloatReg::XMM14,
         X86_64FloatReg::XMM13,
         X86_64FloatReg::XMM12,
         X86_64FloatReg::XMM11,
@@ -659,12 +803,16 @@ impl CallConv<X86_64GeneralReg, X86_64FloatReg, X86_64Assembler> for X86_64Windo
     fn float_callee_saved(reg: &X86_64FloatReg) -> bool {
         matches!(
             reg,
-            X86_64FloatReg::XMM0
-                | X86_64FloatReg::XMM1
-                | X86_64FloatReg::XMM2
-                | X86_64FloatReg::XMM3
-                | X86_64FloatReg::XMM4
-                | X86_64FloatReg::XMM5
+            X86_64FloatReg::XMM6
+                | X86_64FloatReg::XMM7
+                | X86_64FloatReg::XMM8
+                | X86_64FloatReg::XMM9
+                | X86_64FloatReg::XMM10
+                | X86_64FloatReg::XMM11
+                | X86_64FloatReg::XMM12
+                | X86_64FloatReg::XMM13
+                | X86_64FloatReg::XMM14
+                | X86_64FloatReg::XMM15
         )
     }
 
@@ -675,31 +823,74 @@ impl CallConv<X86_64GeneralReg, X86_64FloatReg, X86_64Assembler> for X86_64Windo
         saved_float_regs: &[X86_64FloatReg],
         requested_stack_size: i32,
         fn_call_stack_size: i32,
+        _red_zone_size: i32,
     ) -> i32 {
-        x86_64_generic_setup_stack(
+        // `setup_stack_with_unwind_info` is the one that keeps the unwind codes.
+        let (aligned_stack_size, _) = Self::emit_prologue(
             buf,
             saved_general_regs,
             saved_float_regs,
             requested_stack_size,
             fn_call_stack_size,
-        )
+        );
+
+        aligned_stack_size
     }
 
     #[inline(always)]
-    fn cleanup_stack<'a>(
+    fn setup_stack_with_unwind_info<'a>(
         buf: &mut Vec<'a, u8>,
         saved_general_regs: &[X86_64GeneralReg],
         saved_float_regs: &[X86_64FloatReg],
-        aligned_stack_size: i32,
+        requested_stack_size: i32,
         fn_call_stack_size: i32,
-    ) {
-        x86_64_generic_cleanup_stack(
+        _red_zone_size: i32,
+    ) -> (i32, Option<std::vec::Vec<u8>>) {
+        let (aligned_stack_size, unwind_codes) = Self::emit_prologue(
             buf,
             saved_general_regs,
             saved_float_regs,
-            aligned_stack_size,
+            requested_stack_size,
             fn_call_stack_size,
-        )
+        );
+
+        (aligned_stack_size, Some(unwind_info(&unwind_codes)))
+    }
+
+    #[inline(always)]
+    fn cleanup_stack<'a>(
+        buf: &mut Vec<'a, u8>,
+        saved_general_regs: &[X86_64GeneralReg],
+        saved_float_regs: &[X86_64FloatReg],
+        aligned_stack_size: i32,
+        fn_call_stack_size: i32,
+        _red_zone_size: i32,
+    ) {
+        if aligned_stack_size > 0 {
+            // The slots of `emit_prologue`, relative to `rbp` instead of `rsp`.
+            let (float_offset, _) = Self::save_area(
+                saved_general_regs.len(),
+                saved_float_regs.len(),
+                fn_call_stack_size,
+            );
+            let mut offset = fn_call_stack_size - aligned_stack_size;
+            for reg in saved_general_regs {
+                X86_64Assembler::mov_reg64_base32(buf, *reg, offset);
+                offset += 8;
+            }
+            let mut offset = float_offset - aligned_stack_size;
+            for reg in saved_float_regs {
+                movaps_freg_base64_offset32(buf, *reg, X86_64GeneralReg::RBP, offset);
+                offset += 16;
+            }
+            X86_64Assembler::add_reg64_reg64_imm32(
+                buf,
+                X86_64GeneralReg::RSP,
+                X86_64GeneralReg::RSP,
+                aligned_stack_size,
+            );
+        }
+        X86_64Assembler::pop_reg64(buf, X86_64GeneralReg::RBP);
     }
 
     #[inline(always)]
@@ -716,7 +907,7 @@ impl CallConv<X86_64GeneralReg, X86_64FloatReg, X86_64Assembler> for X86_64Windo
         layout_interner: &mut STLayoutInterner<'a>,
         args: &'a [(InLayout<'a>, Symbol)],
         ret_layout: &InLayout<'a>,
-    ) {
+    ) -> BuildResult {
         let mut arg_offset = Self::SHADOW_SPACE_SIZE as i32 + 16; // 16 is the size of the pushed return address and base pointer.
         let mut i = 0;
         if X86_64WindowsFastcall::returns_via_arg_pointer(layout_interner, ret_layout) {
@@ -724,7 +915,10 @@ impl CallConv<X86_64GeneralReg, X86_64FloatReg, X86_64Assembler> for X86_64Windo
             i += 1;
         }
         for (layout, sym) in args.iter() {
-            if i < Self::GENERAL_PARAM_REGS.len() {
+            if layout_interner.stack_size(*layout) == 0 {
+                // zero-sized arguments take up no position, like in `store_args`
+                storage_manager.no_data_arg(sym);
+            } else if i < Self::GENERAL_PARAM_REGS.len() {
                 match *layout {
                     single_register_integers!() => {
                         storage_manager.general_reg_arg(sym, Self::GENERAL_PARAM_REGS[i]);
@@ -734,10 +928,7 @@ impl CallConv<X86_64GeneralReg, X86_64FloatReg, X86_64Assembler> for X86_64Windo
                         storage_manager.float_reg_arg(sym, Self::FLOAT_PARAM_REGS[i]);
                         i += 1;
                     }
-                    x if layout_interner.stack_size(x) == 0 => {}
-                    x => {
-                        todo!("Loading args with layout {:?}", x);
-                    }
+                    x => return Err(UnsupportedConstruct::layout(layout_interner, x)),
                 }
             } else {
                 match *layout {
@@ -745,12 +936,11 @@ impl CallConv<X86_64GeneralReg, X86_64FloatReg, X86_64Assembler> for X86_64Windo
                         storage_manager.primitive_stack_arg(sym, arg_offset);
                         arg_offset += 8;
                     }
-                    x => {
-                        todo!("Loading args with layout {:?}", x);
-                    }
+                    x => return Err(UnsupportedConstruct::layout(layout_interner, x)),
                 };
             }
         }
+        Ok(())
     }
 
     #[inline(always)]
@@ -769,14 +959,21 @@ impl CallConv<X86_64GeneralReg, X86_64FloatReg, X86_64Assembler> for X86_64Windo
         args: &[Symbol],
         arg_layouts: &[InLayout<'a>],
         ret_layout: &InLayout<'a>,
-    ) {
+    ) -> BuildResult {
         let mut tmp_stack_offset = Self::SHADOW_SPACE_SIZE as i32;
         if Self::returns_via_arg_pointer(layout_interner, ret_layout) {
             // Save space on the stack for the arg we will return.
             storage_manager.claim_stack_area(dst, layout_interner.stack_size(*ret_layout));
-            todo!("claim first parama reg for the address");
+            return Err(UnsupportedConstruct::CallConvDetail(
+                "returning a value through a pointer",
+            ));
         }
-        for (i, (sym, layout)) in args.iter().zip(arg_layouts.iter()).enumerate() {
+        let mut i = 0;
+        for (sym, layout) in args.iter().zip(arg_layouts.iter()) {
+            if layout_interner.stack_size(*layout) == 0 {
+                // zero-sized arguments take up no position, like in `load_args`
+                continue;
+            }
             match *layout {
                 single_register_integers!() => {
                     if i < Self::GENERAL_PARAM_REGS.len() {
@@ -822,13 +1019,12 @@ impl CallConv<X86_64GeneralReg, X86_64FloatReg, X86_64Assembler> for X86_64Windo
                         tmp_stack_offset += 8;
                     }
                 }
-                x if layout_interner.stack_size(x) == 0 => {}
-                x => {
-                    todo!("calling with arg type, {:?}", x);
-                }
+                x => return Err(UnsupportedConstruct::layout(layout_interner, x)),
             }
+            i += 1;
         }
         storage_manager.update_fn_call_stack_size(tmp_stack_offset as u32);
+        Ok(())
     }
 
     fn return_complex_symbol<'a, 'r>(
@@ -841,16 +1037,21 @@ impl CallConv<X86_64GeneralReg, X86_64FloatReg, X86_64Assembler> for X86_64Windo
             X86_64Assembler,
             X86_64WindowsFastcall,
         >,
-        _layout_interner: &mut STLayoutInterner<'a>,
+        layout_interner: &mut STLayoutInterner<'a>,
         _sym: &Symbol,
-        _layout: &InLayout<'a>,
-    ) {
-        todo!("Returning complex symbols for X86_64");
+        layout: &InLayout<'a>,
+    ) -> BuildResult {
+        if layout_interner.stack_size(*layout) != 0 {
+            return Err(UnsupportedConstruct::CallConvDetail(
+                "returning a complex value",
+            ));
+        }
+        Ok(())
     }
 
     fn load_returned_complex_symbol<'a, 'r>(
         _buf: &mut Vec<'a, u8>,
-        _storage_manager: &mut StorageManager<
+        storage_manager: &mut StorageManager<
             'a,
             'r,
             X86_64GeneralReg,
@@ -858,11 +1059,18 @@ impl CallConv<X86_64GeneralReg, X86_64FloatReg, X86_64Assembler> for X86_64Windo
             X86_64Assembler,
             X86_64WindowsFastcall,
         >,
-        _layout_interner: &mut STLayoutInterner<'a>,
-        _sym: &Symbol,
-        _layout: &InLayout<'a>,
-    ) {
-        todo!("Loading returned complex symbols for X86_64");
+        layout_interner: &mut STLayoutInterner<'a>,
+        sym: &Symbol,
+        layout: &InLayout<'a>,
+    ) -> BuildResult {
+        if layout_interner.stack_size(*layout) == 0 {
+            storage_manager.no_data_arg(sym);
+        } else {
+            return Err(UnsupportedConstruct::CallConvDetail(
+                "loading a complex value that was returned",
+            ));
+        }
+        Ok(())
     }
 }
 
@@ -875,21 +1083,92 @@ impl X86_64WindowsFastcall {
         // details here: https://docs.microsoft.com/en-us/cpp/build/x64-calling-convention?view=msvc-160#return-values
         interner.stack_size(*ret_layout) > 8
     }
+
+    /// Where in the frame the prologue saves registers, as offsets from `rsp`: the general
+    /// registers go in 8-byte slots right above the stack the calls of the body use, and the
+    /// float registers in the 16-byte slots after them, which `movaps` needs to be aligned.
+    /// Returns the offset of the first float slot and the end of the slots.
+    fn save_area(general_regs: usize, float_regs: usize, fn_call_stack_size: i32) -> (i32, i32) {
+        let general_end = fn_call_stack_size + 8 * general_regs as i32;
+        if float_regs == 0 {
+            return (general_end, general_end);
+        }
+
+        let float_offset = (general_end + 15) & !15;
+        (float_offset, float_offset + 16 * float_regs as i32)
+    }
+
+    /// The prologue `setup_stack` emits, along with the unwind codes that describe it.
+    /// It lays out the frame like `x86_64_generic_setup_stack` does, but in the order
+    /// Windows can unwind: push `rbp`, allocate the frame, point `rbp` at the pushed `rbp`,
+    /// and then save the registers relative to `rsp`. The float registers are XMM6 to XMM15,
+    /// which are nonvolatile like the general ones, so they get unwind codes too.
+    pub(crate) fn emit_prologue<'a>(
+        buf: &mut Vec<'a, u8>,
+        saved_general_regs: &[X86_64GeneralReg],
+        saved_float_regs: &[X86_64FloatReg],
+        requested_stack_size: i32,
+        fn_call_stack_size: i32,
+    ) -> (i32, std::vec::Vec<UnwindCode>) {
+        let (float_offset, save_area_end) = Self::save_area(
+            saved_general_regs.len(),
+            saved_float_regs.len(),
+            fn_call_stack_size,
+        );
+        // A size that is a multiple of 16 keeps `rsp` aligned, which the float slots rely on.
+        let aligned_stack_size = x86_64_aligned_stack_size(
+            save_area_end - fn_call_stack_size,
+            requested_stack_size,
+            fn_call_stack_size,
+        );
+
+        let mut prologue = PrologueBuilder::new(buf);
+        prologue.push(buf, X86_64GeneralReg::RBP);
+        if aligned_stack_size > 0 {
+            prologue.alloc(buf, aligned_stack_size as u32);
+        }
+
+        // The frame pointer can only be a few bytes above `rsp`. In a bigger frame, `rbp` is
+        // set up after the prologue, and the unwinder uses `rsp`, which the body never moves.
+        let has_frame_pointer = aligned_stack_size as u32 <= MAX_FRAME_POINTER_OFFSET;
+        if has_frame_pointer {
+            prologue.set_frame_pointer(buf, X86_64GeneralReg::RBP, aligned_stack_size as u32);
+        }
+
+        // The same slots at the top of the stack as `x86_64_generic_setup_stack` uses.
+        let mut offset = fn_call_stack_size;
+        for reg in saved_general_regs {
+            prologue.save(buf, *reg, offset as u32);
+            offset += 8;
+        }
+        let mut offset = float_offset;
+        for reg in saved_float_regs {
+            prologue.save_xmm128(buf, *reg, offset as u32);
+            offset += 16;
+        }
+        let unwind_codes = prologue.finish(buf);
+
+        if !has_frame_pointer {
+            lea_reg64_base64_offset32(
+                buf,
+                X86_64GeneralReg::RBP,
+                X86_64GeneralReg::RSP,
+                aligned_stack_size,
+            );
+        }
+
+        (aligned_stack_size, unwind_codes)
+    }
 }
 
-#[inline(always)]
-fn x86_64_generic_setup_stack<'a>(
-    buf: &mut Vec<'a, u8>,
-    saved_general_regs: &[X86_64GeneralReg],
-    saved_float_regs: &[X86_64FloatReg],
+/// The size of a frame with `saved_regs_size` bytes for the saved registers, aligned for calls.
+fn x86_64_aligned_stack_size(
+    saved_regs_size: i32,
     requested_stack_size: i32,
     fn_call_stack_size: i32,
 ) -> i32 {
-    X86_64Assembler::push_reg64(buf, X86_64GeneralReg::RBP);
-    X86_64Assembler::mov_reg64_reg64(buf, X86_64GeneralReg::RBP, X86_64GeneralReg::RSP);
-
     let full_stack_size = match requested_stack_size
-        .checked_add(8 * (saved_general_regs.len() + saved_float_regs.len()) as i32)
+        .checked_add(saved_regs_size)
         .and_then(|size| size.checked_add(fn_call_stack_size))
     {
         Some(size) => size,
@@ -905,32 +1184,53 @@ fn x86_64_generic_setup_stack<'a>(
     } else {
         STACK_ALIGNMENT - alignment as u8
     };
-    if let Some(aligned_stack_size) = full_stack_size.checked_add(offset as i32) {
-        if aligned_stack_size > 0 {
+    match full_stack_size.checked_add(offset as i32) {
+        Some(aligned_stack_size) => aligned_stack_size.max(0),
+        None => internal_error!("Ran out of stack space"),
+    }
+}
+
+#[inline(always)]
+fn x86_64_generic_setup_stack<'a>(
+    buf: &mut Vec<'a, u8>,
+    saved_general_regs: &[X86_64GeneralReg],
+    saved_float_regs: &[X86_64FloatReg],
+    requested_stack_size: i32,
+    fn_call_stack_size: i32,
+    red_zone_size: i32,
+) -> i32 {
+    X86_64Assembler::push_reg64(buf, X86_64GeneralReg::RBP);
+    X86_64Assembler::mov_reg64_reg64(buf, X86_64GeneralReg::RBP, X86_64GeneralReg::RSP);
+
+    let aligned_stack_size = x86_64_aligned_stack_size(
+        8 * (saved_general_regs.len() + saved_float_regs.len()) as i32,
+        requested_stack_size,
+        fn_call_stack_size,
+    );
+    if aligned_stack_size > 0 {
+        // A frame that fits in the red zone is used without moving `rsp` over it.
+        if aligned_stack_size > red_zone_size {
             X86_64Assembler::sub_reg64_reg64_imm32(
                 buf,
                 X86_64GeneralReg::RSP,
                 X86_64GeneralReg::RSP,
                 aligned_stack_size,
             );
+        }
 
-            // Put values at the top of the stack to avoid conflicts with previously saved variables.
-            let mut offset = aligned_stack_size - fn_call_stack_size;
-            for reg in saved_general_regs {
-                X86_64Assembler::mov_base32_reg64(buf, -offset, *reg);
-                offset -= 8;
-            }
-            for reg in saved_float_regs {
-                X86_64Assembler::mov_base32_freg64(buf, -offset, *reg);
-                offset -= 8;
-            }
-            aligned_stack_size
-        } else {
-            0
+        // Put values at the top of the stack to avoid conflicts with previously saved variables.
+        let mut offset = aligned_stack_size - fn_call_stack_size;
+        for reg in saved_general_regs {
+            X86_64Assembler::mov_base32_reg64(buf, -offset, *reg);
+            offset -= 8;
+        }
+        for reg in saved_float_regs {
+            X86_64Assembler::mov_base32_freg64(buf, -offset, *reg);
+            offset -= 8;
         }
-    } else {
-        internal_error!("Ran out of stack space");
     }
+
+    aligned_stack_size
 }
 
 #[inline(always)]
@@ -941,6 +1241,7 @@ fn x86_64_generic_cleanup_stack<'a>(
     saved_float_regs: &[X86_64FloatReg],
     aligned_stack_size: i32,
     fn_call_stack_size: i32,
+    red_zone_size: i32,
 ) {
     if aligned_stack_size > 0 {
         let mut offset = aligned_stack_size - fn_call_stack_size;
@@ -952,12 +1253,14 @@ fn x86_64_generic_cleanup_stack<'a>(
             X86_64Assembler::mov_freg64_base32(buf, *reg, -offset);
             offset -= 8;
         }
-        X86_64Assembler::add_reg64_reg64_imm32(
-            buf,
-            X86_64GeneralReg::RSP,
-            X86_64GeneralReg::RSP,
-            aligned_stack_size,
-        );
+        if aligned_stack_size > red_zone_size {
+            X86_64Assembler::add_reg64_reg64_imm32(
+                buf,
+                X86_64GeneralReg::RSP,
+                X86_64GeneralReg::RSP,
+                aligned_stack_size,
+            );
+        }
     }
     //X86_64Assembler::mov_reg64_reg64(buf, X86_64GeneralReg::RSP, X86_64GeneralReg::RBP);
     X86_64Assembler::pop_reg64(buf, X86_64GeneralReg::RBP);
@@ -979,14 +1282,80 @@ where
     }
 }
 
+/// The sizes of the encoders below, and latencies that fit most x86-64 cores since Haswell
+pub struct X86_64CostModel {
+    pub latencies: LatencyTable,
+}
+
+impl X86_64CostModel {
+    pub const DEFAULT: X86_64CostModel = X86_64CostModel {
+        latencies: LatencyTable {
+            alu: 1,
+            shift: 1,
+            lea: 1,
+            mul: 3,
+            cmov: 1,
+            branch: 1,
+        },
+    };
+}
+
+impl CostModel for X86_64CostModel {
+    fn size_bytes(&self, inst: PlannedInst) -> u8 {
+        match inst {
+            PlannedInst::MovRegReg => 3,
+            PlannedInst::MovImm(imm) if i32::try_from(imm).is_ok() => 7,
+            PlannedInst::MovImm(_) => 10,
+            PlannedInst::Neg => 3,
+            PlannedInst::ShlImm => 4,
+            // a base of RBP or R13 needs a displacement byte on top
+            PlannedInst::AddShifted => 4,
+            PlannedInst::MulRegReg => 4,
+            PlannedInst::Cmov => 4,
+            PlannedInst::ShortJcc => 2,
+        }
+    }
+
+    fn latencies(&self) -> &LatencyTable {
+        &self.latencies
+    }
+}
+
+/// `abs` with a `cmov`, or with a branch over a `mov` if `costs` says that is cheaper
+fn abs_reg64_reg64_with(
+    buf: &mut Vec<'_, u8>,
+    dst: X86_64GeneralReg,
+    src: X86_64GeneralReg,
+    costs: &dyn CostModel,
+) {
+    mov_reg64_reg64(buf, dst, src);
+    neg_reg64(buf, dst);
+    if costs.prefers_cmov() {
+        cmovl_reg64_reg64(buf, dst, src);
+    } else {
+        jge_imm8(buf, 3);
+        raw_mov_reg64_reg64(buf, dst, src);
+    }
+}
+
 impl Assembler<X86_64GeneralReg, X86_64FloatReg> for X86_64Assembler {
+    // `mul` and `div` take one operand in RAX and write their results to RDX:RAX.
+    const MUL_DIV_FIXED_REGS: &'static [X86_64GeneralReg] =
+        &[X86_64GeneralReg::RAX, X86_64GeneralReg::RDX];
+    const ZERO_FIXED_REGS: &'static [X86_64GeneralReg] = &[
+        X86_64GeneralReg::RAX,
+        X86_64GeneralReg::RCX,
+        X86_64GeneralReg::RDI,
+    ];
+    // `rdtsc` writes the counter to EDX:EAX.
+    const TIMESTAMP_FIXED_REGS: &'static [X86_64GeneralReg] =
+        &[X86_64GeneralReg::RAX, X86_64GeneralReg::RDX];
+
     // These functions should map to the raw assembly functions below.
     // In some cases, that means you can just directly call one of the direct assembly functions.
     #[inline(always)]
     fn abs_reg64_reg64(buf: &mut Vec<'_, u8>, dst: X86_64GeneralReg, src: X86_64GeneralReg) {
-        mov_reg64_reg64(buf, dst, src);
-        neg_reg64(buf, dst);
-        cmovl_reg64_reg64(buf, dst, src);
+        abs_reg64_reg64_with(buf, dst, src, Self::COST_MODEL);
     }
 
     #[inline(always)]
@@ -995,13 +1364,14 @@ impl Assembler<X86_64GeneralReg, X86_64FloatReg> for X86_64Assembler {
         relocs: &mut Vec<'_, Relocation>,
         dst: X86_64FloatReg,
         src: X86_64FloatReg,
+        endianness: Endianness,
     ) {
         movsd_freg64_rip_offset32(buf, dst, 0);
 
         // TODO: make sure this constant only loads once instead of every call to abs
         relocs.push(Relocation::LocalData {
             offset: buf.len() as u64 - 4,
-            data: 0x7fffffffffffffffu64.to_le_bytes().to_vec(),
+            data: endianness.u64_bytes(0x7fffffffffffffff).to_vec(),
         });
 
         andpd_freg64_freg64(buf, dst, src);
@@ -1023,6 +1393,27 @@ impl Assembler<X86_64GeneralReg, X86_64FloatReg> for X86_64Assembler {
         binop_move_src_to_dst_reg64(buf, add_reg64_reg64, dst, src1, src2)
     }
 
+    #[inline(always)]
+    fn lea_reg64_base64_index64(buf: &mut Vec<'_, u8>, dst: Reg64, base: Reg64, index: Reg64) {
+        // RSP cannot be an index, but with a scale of 1 the two can be swapped.
+        if index == X86_64GeneralReg::RSP {
+            lea_reg64_base64_index64(buf, dst, index, base, 1);
+        } else {
+            lea_reg64_base64_index64(buf, dst, base, index, 1);
+        }
+    }
+
+    #[inline(always)]
+    fn add_shifted_reg64_reg64_reg64(
+        buf: &mut Vec<'_, u8>,
+        dst: Reg64,
+        src1: Reg64,
+        src2: Reg64,
+        shift: u8,
+    ) {
+        lea_reg64_base64_index64(buf, dst, src1, src2, 1 << shift);
+    }
+
     #[inline(always)]
     fn add_freg32_freg32_freg32(
         buf: &mut Vec<'_, u8>,
@@ -1076,9 +1467,19 @@ impl Assembler<X86_64GeneralReg, X86_64FloatReg> for X86_64Assembler {
         imul_reg64_reg64(buf, dst, src2);
     }
 
+    #[inline(always)]
+    fn imul_reg64_reg64_imm32(
+        buf: &mut Vec<'_, u8>,
+        dst: X86_64GeneralReg,
+        src: X86_64GeneralReg,
+        imm32: i32,
+    ) {
+        imul_reg64_reg64_imm32(buf, dst, src, imm32);
+    }
+
     fn umul_reg64_reg64_reg64<'a, 'r, ASM, CC>(
         buf: &mut Vec<'a, u8>,
-        storage_manager: &mut StorageManager<'a, 'r, X86_64GeneralReg, X86_64FloatReg, ASM, CC>,
+        _storage_manager: &mut StorageManager<'a, 'r, X86_64GeneralReg, X86_64FloatReg, ASM, CC>,
         dst: X86_64GeneralReg,
         src1: X86_64GeneralReg,
         src2: X86_64GeneralReg,
@@ -1086,13 +1487,15 @@ impl Assembler<X86_64GeneralReg, X86_64FloatReg> for X86_64Assembler {
         ASM: Assembler<X86_64GeneralReg, X86_64FloatReg>,
         CC: CallConv<X86_64GeneralReg, X86_64FloatReg, ASM>,
     {
-        use crate::generic64::RegStorage;
-
-        storage_manager.ensure_reg_free(buf, RegStorage::General(X86_64GeneralReg::RAX));
-        storage_manager.ensure_reg_free(buf, RegStorage::General(X86_64GeneralReg::RDX));
+        debug_assert!(
+            !Self::MUL_DIV_FIXED_REGS
+                .iter()
+                .any(|reg| [dst, src1, src2].contains(reg)),
+            "operands must not be in the reserved RAX or RDX"
+        );
 
         mov_reg64_reg64(buf, X86_64GeneralReg::RAX, src1);
-        mul_reg64_reg64(buf, src2);
+        mul_reg64(buf, src2);
         mov_reg64_reg64(buf, dst, X86_64GeneralReg::RAX);
     }
 
@@ -1160,9 +1563,10 @@ impl Assembler<X86_64GeneralReg, X86_64FloatReg> for X86_64Assembler {
         }
     }
 
-    fn idiv_reg64_reg64_reg64<'a, 'r, ASM, CC>(
+    fn idiv_reg_reg_reg<'a, 'r, ASM, CC>(
         buf: &mut Vec<'a, u8>,
-        storage_manager: &mut StorageManager<'a, 'r, X86_64GeneralReg, X86_64FloatReg, ASM, CC>,
+        _storage_manager: &mut StorageManager<'a, 'r, X86_64GeneralReg, X86_64FloatReg, ASM, CC>,
+        width: RegisterWidth,
         dst: X86_64GeneralReg,
         src1: X86_64GeneralReg,
         src2: X86_64GeneralReg,
@@ -1170,19 +1574,27 @@ impl Assembler<X86_64GeneralReg, X86_64FloatReg> for X86_64Assembler {
         ASM: Assembler<X86_64GeneralReg, X86_64FloatReg>,
         CC: CallConv<X86_64GeneralReg, X86_64FloatReg, ASM>,
     {
-        use crate::generic64::RegStorage;
-
-        storage_manager.ensure_reg_free(buf, RegStorage::General(X86_64GeneralReg::RAX));
-        storage_manager.ensure_reg_free(buf, RegStorage::General(X86_64GeneralReg::RDX));
+        debug_assert!(
+            !Self::MUL_DIV_FIXED_REGS
+                .iter()
+                .any(|reg| [dst, src1, src2].contains(reg)),
+            "operands must not be in the reserved RAX or RDX"
+        );
 
-        mov_reg64_reg64(buf, X86_64GeneralReg::RAX, src1);
-        idiv_reg64_reg64(buf, src2);
-        mov_reg64_reg64(buf, dst, X86_64GeneralReg::RAX);
+        idiv_at_width(buf, width, src1, src2);
+        match width {
+            RegisterWidth::W64 => mov_reg64_reg64(buf, dst, X86_64GeneralReg::RAX),
+            RegisterWidth::W32 => movsxd_reg64_reg32(buf, dst, X86_64GeneralReg::RAX),
+            RegisterWidth::W16 => movsx_reg64_reg16(buf, dst, X86_64GeneralReg::RAX),
+            // the remainder ends up in AH, which the extension leaves out
+            RegisterWidth::W8 => movsx_reg64_reg8(buf, dst, X86_64GeneralReg::RAX),
+        }
     }
 
-    fn udiv_reg64_reg64_reg64<'a, 'r, ASM, CC>(
+    fn irem_reg_reg_reg<'a, 'r, ASM, CC>(
         buf: &mut Vec<'a, u8>,
-        storage_manager: &mut StorageManager<'a, 'r, X86_64GeneralReg, X86_64FloatReg, ASM, CC>,
+        _storage_manager: &mut StorageManager<'a, 'r, X86_64GeneralReg, X86_64FloatReg, ASM, CC>,
+        width: RegisterWidth,
         dst: X86_64GeneralReg,
         src1: X86_64GeneralReg,
         src2: X86_64GeneralReg,
@@ -1190,28 +1602,125 @@ impl Assembler<X86_64GeneralReg, X86_64FloatReg> for X86_64Assembler {
         ASM: Assembler<X86_64GeneralReg, X86_64FloatReg>,
         CC: CallConv<X86_64GeneralReg, X86_64FloatReg, ASM>,
     {
-        use crate::generic64::RegStorage;
+        use X86_64GeneralReg::{RAX, RDX};
 
-        storage_manager.ensure_reg_free(buf, RegStorage::General(X86_64GeneralReg::RAX));
-        storage_manager.ensure_reg_free(buf, RegStorage::General(X86_64GeneralReg::RDX));
-
-        mov_reg64_reg64(buf, X86_64GeneralReg::RAX, src1);
-        udiv_reg64_reg64(buf, src2);
-        mov_reg64_reg64(buf, dst, X86_64GeneralReg::RAX);
-    }
+        debug_assert!(
+            !Self::MUL_DIV_FIXED_REGS
+                .iter()
+                .any(|reg| [dst, src1, src2].contains(reg)),
+            "operands must not be in the reserved RAX or RDX"
+        );
 
-    #[inline(always)]
-    fn jmp_imm32(buf: &mut Vec<'_, u8>, offset: i32) -> usize {
-        jmp_imm32(buf, offset);
-        buf.len()
+        idiv_at_width(buf, width, src1, src2);
+        match width {
+            RegisterWidth::W64 => mov_reg64_reg64(buf, dst, RDX),
+            RegisterWidth::W32 => movsxd_reg64_reg32(buf, dst, RDX),
+            RegisterWidth::W16 => movsx_reg64_reg16(buf, dst, RDX),
+            // AH can not be read along with a REX prefix, so it is moved to AL first
+            RegisterWidth::W8 => {
+                shr_reg64_imm8(buf, RAX, 8);
+                movsx_reg64_reg8(buf, dst, RAX);
+            }
+        }
     }
 
-    #[inline(always)]
-    fn tail_call(buf: &mut Vec<'_, u8>) -> u64 {
-        Self::jmp_imm32(buf, 0);
+    fn udiv_reg_reg_reg<'a, 'r, ASM, CC>(
+        buf: &mut Vec<'a, u8>,
+        _storage_manager: &mut StorageManager<'a, 'r, X86_64GeneralReg, X86_64FloatReg, ASM, CC>,
+        width: RegisterWidth,
+        dst: X86_64GeneralReg,
+        src1: X86_64GeneralReg,
+        src2: X86_64GeneralReg,
+    ) where
+        ASM: Assembler<X86_64GeneralReg, X86_64FloatReg>,
+        CC: CallConv<X86_64GeneralReg, X86_64FloatReg, ASM>,
+    {
+        use X86_64GeneralReg::{RAX, RDX};
+
+        debug_assert!(
+            !Self::MUL_DIV_FIXED_REGS
+                .iter()
+                .any(|reg| [dst, src1, src2].contains(reg)),
+            "operands must not be in the reserved RAX or RDX"
+        );
+
+        div_at_width(buf, width, src1, src2);
+        match width {
+            // a 32-bit division zero extends its results
+            RegisterWidth::W64 | RegisterWidth::W32 => mov_reg64_reg64(buf, dst, RAX),
+            RegisterWidth::W16 => movzx_reg64_reg16(buf, dst, RAX),
+            RegisterWidth::W8 => movzx_reg64_reg8(buf, dst, RAX),
+        }
+    }
+
+    fn urem_reg_reg_reg<'a, 'r, ASM, CC>(
+        buf: &mut Vec<'a, u8>,
+        _storage_manager: &mut StorageManager<'a, 'r, X86_64GeneralReg, X86_64FloatReg, ASM, CC>,
+        width: RegisterWidth,
+        dst: X86_64GeneralReg,
+        src1: X86_64GeneralReg,
+        src2: X86_64GeneralReg,
+    ) where
+        ASM: Assembler<X86_64GeneralReg, X86_64FloatReg>,
+        CC: CallConv<X86_64GeneralReg, X86_64FloatReg, ASM>,
+    {
+        use X86_64GeneralReg::{RAX, RDX};
+
+        debug_assert!(
+            !Self::MUL_DIV_FIXED_REGS
+                .iter()
+                .any(|reg| [dst, src1, src2].contains(reg)),
+            "operands must not be in the reserved RAX or RDX"
+        );
+
+        div_at_width(buf, width, src1, src2);
+        match width {
+            RegisterWidth::W64 | RegisterWidth::W32 => mov_reg64_reg64(buf, dst, RDX),
+            RegisterWidth::W16 => movzx_reg64_reg16(buf, dst, RDX),
+            // AH can not be read along with a REX prefix, so it is moved to AL first
+            RegisterWidth::W8 => {
+                shr_reg64_imm8(buf, RAX, 8);
+                movzx_reg64_reg8(buf, dst, RAX);
+            }
+        }
+    }
+
+    #[inline(always)]
+    fn jmp_imm32(buf: &mut Vec<'_, u8>, offset: i32) -> usize {
+        jmp_imm32(buf, offset);
+        buf.len()
+    }
+
+    #[inline(always)]
+    fn tail_call(buf: &mut Vec<'_, u8>) -> u64 {
+        Self::jmp_imm32(buf, 0);
         buf.len() as u64 - 4
     }
 
+    #[inline(always)]
+    fn jump_size(kind: BranchKind, short: bool) -> u64 {
+        match (kind, short) {
+            (_, true) => 2,
+            (BranchKind::Jmp, false) => 5,
+            (BranchKind::Jne, false) => 6,
+        }
+    }
+
+    #[inline(always)]
+    fn fits_short_jump(displacement: i64) -> bool {
+        i8::try_from(displacement).is_ok()
+    }
+
+    #[inline(always)]
+    fn jump(buf: &mut Vec<'_, u8>, kind: BranchKind, offset: i32, short: bool) {
+        match (kind, short) {
+            (BranchKind::Jmp, true) => jmp_imm8(buf, offset as i8),
+            (BranchKind::Jne, true) => jne_imm8(buf, offset as i8),
+            (BranchKind::Jmp, false) => jmp_imm32(buf, offset),
+            (BranchKind::Jne, false) => jne_imm32(buf, offset),
+        }
+    }
+
     #[inline(always)]
     fn jne_reg64_imm64_imm32(
         buf: &mut Vec<'_, u8>,
@@ -1221,7 +1730,7 @@ impl Assembler<X86_64GeneralReg, X86_64FloatReg> for X86_64Assembler {
     ) -> usize {
         buf.reserve(13);
         if imm > i32::MAX as u64 {
-            todo!("comparison with values greater than i32::max");
+            internal_error!("build_switch only compares with values up to i32::MAX");
         }
         cmp_reg64_imm32(buf, reg, imm as i32);
         jne_imm32(buf, offset);
@@ -1234,11 +1743,12 @@ impl Assembler<X86_64GeneralReg, X86_64FloatReg> for X86_64Assembler {
         relocs: &mut Vec<'_, Relocation>,
         dst: X86_64FloatReg,
         imm: f32,
+        endianness: Endianness,
     ) {
         movss_freg32_rip_offset32(buf, dst, 0);
         relocs.push(Relocation::LocalData {
             offset: buf.len() as u64 - 4,
-            data: imm.to_le_bytes().to_vec(),
+            data: endianness.f32_bytes(imm).to_vec(),
         });
     }
     #[inline(always)]
@@ -1247,11 +1757,12 @@ impl Assembler<X86_64GeneralReg, X86_64FloatReg> for X86_64Assembler {
         relocs: &mut Vec<'_, Relocation>,
         dst: X86_64FloatReg,
         imm: f64,
+        endianness: Endianness,
     ) {
         movsd_freg64_rip_offset32(buf, dst, 0);
         relocs.push(Relocation::LocalData {
             offset: buf.len() as u64 - 4,
-            data: imm.to_le_bytes().to_vec(),
+            data: endianness.f64_bytes(imm).to_vec(),
         });
     }
     #[inline(always)]
@@ -1259,6 +1770,44 @@ impl Assembler<X86_64GeneralReg, X86_64FloatReg> for X86_64Assembler {
         mov_reg64_imm64(buf, dst, imm);
     }
     #[inline(always)]
+    fn load_constant(buf: &mut Vec<'_, u8>, dst: X86_64GeneralReg, value: i64, flags_live: bool) {
+        // writes to a 32-bit register zero the upper half, so both of the 32-bit forms are safe
+        if value == 0 && !flags_live {
+            xor_reg32_reg32(buf, dst, dst);
+        } else if (0..=u32::MAX as i64).contains(&value) {
+            mov_reg32_imm32(buf, dst, value as u32);
+        } else {
+            mov_reg64_imm64(buf, dst, value);
+        }
+    }
+    #[inline(always)]
+    fn load_local_data_address(
+        buf: &mut Vec<'_, u8>,
+        relocs: &mut Vec<'_, Relocation>,
+        dst: X86_64GeneralReg,
+        data: std::vec::Vec<u8>,
+    ) {
+        lea_reg64_rip_offset32(buf, dst, 0);
+        relocs.push(Relocation::LocalData {
+            offset: buf.len() as u64 - 4,
+            data,
+        });
+    }
+    #[inline(always)]
+    fn load_linked_data_address(
+        buf: &mut Vec<'_, u8>,
+        relocs: &mut Vec<'_, Relocation>,
+        dst: X86_64GeneralReg,
+        name: String,
+    ) {
+        // The address is in the global offset table.
+        mov_reg64_rip_offset32(buf, dst, 0);
+        relocs.push(Relocation::LinkedData {
+            offset: buf.len() as u64 - 4,
+            name,
+        });
+    }
+    #[inline(always)]
     fn mov_freg64_freg64(buf: &mut Vec<'_, u8>, dst: X86_64FloatReg, src: X86_64FloatReg) {
         movsd_freg64_freg64(buf, dst, src);
     }
@@ -1283,6 +1832,57 @@ impl Assembler<X86_64GeneralReg, X86_64FloatReg> for X86_64Assembler {
     fn mov_base32_reg64(buf: &mut Vec<'_, u8>, offset: i32, src: X86_64GeneralReg) {
         mov_base64_offset32_reg64(buf, X86_64GeneralReg::RBP, offset, src)
     }
+    #[inline(always)]
+    fn mov_base32_reg32(buf: &mut Vec<'_, u8>, offset: i32, src: X86_64GeneralReg) {
+        mov_base32_offset32_reg32(buf, X86_64GeneralReg::RBP, offset, src)
+    }
+    #[inline(always)]
+    fn mov_base32_reg16(buf: &mut Vec<'_, u8>, offset: i32, src: X86_64GeneralReg) {
+        mov_base16_offset32_reg16(buf, X86_64GeneralReg::RBP, offset, src)
+    }
+    #[inline(always)]
+    fn mov_base32_reg8(buf: &mut Vec<'_, u8>, offset: i32, src: X86_64GeneralReg) {
+        mov_base8_offset32_reg8(buf, X86_64GeneralReg::RBP, offset, src)
+    }
+    #[inline(always)]
+    fn mov_base32_imm32(buf: &mut Vec<'_, u8>, offset: i32, imm: i32) {
+        mov_base64_offset32_imm32(buf, X86_64GeneralReg::RBP, offset, imm)
+    }
+    #[inline(always)]
+    fn mov_base32_imm(buf: &mut Vec<'_, u8>, offset: i32, imm: i32, width: RegisterWidth) {
+        use X86_64GeneralReg::RBP;
+
+        match width {
+            RegisterWidth::W64 => mov_base64_offset32_imm32(buf, RBP, offset, imm),
+            RegisterWidth::W32 => mov_base32_offset32_imm32(buf, RBP, offset, imm),
+            RegisterWidth::W16 => mov_base16_offset32_imm16(buf, RBP, offset, imm as i16),
+            RegisterWidth::W8 => mov_base8_offset32_imm8(buf, RBP, offset, imm as i8),
+        }
+    }
+    #[inline(always)]
+    fn zero_base32(buf: &mut Vec<'_, u8>, offset: i32, size: u32) {
+        debug_assert!(size % 8 == 0);
+        lea_reg64_base64_offset32(buf, X86_64GeneralReg::RDI, X86_64GeneralReg::RBP, offset);
+        mov_reg64_imm32(buf, X86_64GeneralReg::RCX, size as i32);
+        xor_reg64_reg64(buf, X86_64GeneralReg::RAX, X86_64GeneralReg::RAX);
+        rep_stosb(buf);
+    }
+
+    fn read_timestamp_counter(buf: &mut Vec<'_, u8>, dst: X86_64GeneralReg, serialize: bool) {
+        debug_assert!(
+            !Self::TIMESTAMP_FIXED_REGS.contains(&dst),
+            "dst must not be in the reserved RAX or RDX"
+        );
+
+        if serialize {
+            lfence(buf);
+        }
+        rdtsc(buf);
+        // the upper halves of RAX and RDX are zeroed
+        shl_reg64_imm8(buf, X86_64GeneralReg::RDX, 32);
+        or_reg64_reg64(buf, X86_64GeneralReg::RAX, X86_64GeneralReg::RDX);
+        mov_reg64_reg64(buf, dst, X86_64GeneralReg::RAX);
+    }
 
     #[inline(always)]
     fn mov_reg64_mem64_offset32(
@@ -1302,13 +1902,24 @@ impl Assembler<X86_64GeneralReg, X86_64FloatReg> for X86_64Assembler {
     ) {
         mov_base64_offset32_reg64(buf, dst, offset, src)
     }
+    #[inline(always)]
+    fn mov_mem8_offset32_reg8(
+        buf: &mut Vec<'_, u8>,
+        dst: X86_64GeneralReg,
+        offset: i32,
+        src: X86_64GeneralReg,
+    ) {
+        mov_base8_offset32_reg8(buf, dst, offset, src)
+    }
 
     #[inline(always)]
     fn movsx_reg64_base32(buf: &mut Vec<'_, u8>, dst: X86_64GeneralReg, offset: i32, size: u8) {
         debug_assert!(size <= 8);
         match size {
             8 => Self::mov_reg64_base32(buf, dst, offset),
-            4 | 2 | 1 => todo!("sign extending {size} byte values"),
+            4 => movsxd_reg64_base32_offset32(buf, dst, X86_64GeneralReg::RBP, offset),
+            2 => movsx_reg64_base16_offset32(buf, dst, X86_64GeneralReg::RBP, offset),
+            1 => movsx_reg64_base8_offset32(buf, dst, X86_64GeneralReg::RBP, offset),
             _ => internal_error!("Invalid size for sign extension: {size}"),
         }
     }
@@ -1317,11 +1928,42 @@ impl Assembler<X86_64GeneralReg, X86_64FloatReg> for X86_64Assembler {
         debug_assert!(size <= 8);
         match size {
             8 => Self::mov_reg64_base32(buf, dst, offset),
-            4 | 2 => todo!("zero extending {size} byte values"),
+            4 => mov_reg32_base32_offset32(buf, dst, X86_64GeneralReg::RBP, offset),
+            2 => movzx_reg64_base16_offset32(buf, dst, X86_64GeneralReg::RBP, offset),
             1 => movzx_reg64_base8_offset32(buf, dst, X86_64GeneralReg::RBP, offset),
             _ => internal_error!("Invalid size for zero extension: {size}"),
         }
     }
+    #[inline(always)]
+    fn movsx_reg64_reg64(
+        buf: &mut Vec<'_, u8>,
+        dst: X86_64GeneralReg,
+        src: X86_64GeneralReg,
+        size: u8,
+    ) {
+        match size {
+            8 => mov_reg64_reg64(buf, dst, src),
+            4 => movsxd_reg64_reg32(buf, dst, src),
+            2 => movsx_reg64_reg16(buf, dst, src),
+            1 => movsx_reg64_reg8(buf, dst, src),
+            _ => internal_error!("Invalid size for sign extension: {size}"),
+        }
+    }
+    #[inline(always)]
+    fn movzx_reg64_reg64(
+        buf: &mut Vec<'_, u8>,
+        dst: X86_64GeneralReg,
+        src: X86_64GeneralReg,
+        size: u8,
+    ) {
+        match size {
+            8 => mov_reg64_reg64(buf, dst, src),
+            4 => mov_reg32_reg32(buf, dst, src),
+            2 => movzx_reg64_reg16(buf, dst, src),
+            1 => movzx_reg64_reg8(buf, dst, src),
+            _ => internal_error!("Invalid size for zero extension: {size}"),
+        }
+    }
 
     #[inline(always)]
     fn mov_freg64_stack32(buf: &mut Vec<'_, u8>, dst: X86_64FloatReg, offset: i32) {
@@ -1484,6 +2126,87 @@ impl Assembler<X86_64GeneralReg, X86_64FloatReg> for X86_64Assembler {
         seto_reg64(buf, dst);
     }
 
+    fn int_sign_test_reg_reg(
+        buf: &mut Vec<'_, u8>,
+        dst: X86_64GeneralReg,
+        src: X86_64GeneralReg,
+        width: RegisterWidth,
+        signed: bool,
+        condition: SignCondition,
+    ) {
+        // `test` clears the overflow flag, so the signed conditions only look at the sign and the
+        // zero flags
+        let set = match (condition, signed) {
+            (SignCondition::Zero, _) => sete_reg64,
+            (SignCondition::Positive, true) => setg_reg64,
+            (SignCondition::Negative, true) => setl_reg64,
+            (SignCondition::Positive, false) => setne_reg64,
+            (SignCondition::Negative, false) => {
+                // an unsigned integer is never negative
+                Self::load_constant(buf, dst, 0, false);
+                return;
+            }
+        };
+        test_reg_reg(buf, width, src);
+        set(buf, dst);
+    }
+
+    fn float_sign_test_reg_freg(
+        buf: &mut Vec<'_, u8>,
+        dst: X86_64GeneralReg,
+        src: X86_64FloatReg,
+        tmp: X86_64FloatReg,
+        width: FloatWidth,
+        condition: SignCondition,
+    ) {
+        xorps_freg_freg(buf, tmp, tmp);
+        match condition {
+            SignCondition::Zero => {
+                // no one flag of `ucomisd` tells zero from NaN, so this compares for equality, which
+                // sets `tmp` to all ones or all zeroes
+                match width {
+                    FloatWidth::F64 => cmpeqsd_freg_freg(buf, tmp, src),
+                    FloatWidth::F32 => cmpeqss_freg_freg(buf, tmp, src),
+                }
+                movq_reg64_freg64(buf, dst, tmp);
+                and_reg64_imm8(buf, dst, 1);
+            }
+            // an unordered comparison sets the carry flag, so `seta` is false for NaN
+            SignCondition::Positive => {
+                ucomis_freg_freg(buf, width, src, tmp);
+                seta_reg64(buf, dst);
+            }
+            SignCondition::Negative => {
+                ucomis_freg_freg(buf, width, tmp, src);
+                seta_reg64(buf, dst);
+            }
+        }
+    }
+
+    const PATCHPOINT_SIZE: usize = 8;
+
+    const COST_MODEL: &'static dyn CostModel = &X86_64CostModel::DEFAULT;
+
+    /// A 5 byte nop, which a `jmp rel32` replaces, and a 3 byte nop that fills up the aligned
+    /// 8 bytes, so that the runtime can store them all at once.
+    #[inline(always)]
+    fn patchpoint(buf: &mut Vec<'_, u8>) {
+        // nop dword ptr [rax + rax*1 + 0]
+        buf.extend([0x0F, 0x1F, 0x44, 0x00, 0x00]);
+        // nop dword ptr [rax]
+        buf.extend([0x0F, 0x1F, 0x00]);
+    }
+
+    #[inline(always)]
+    fn padding(buf: &mut Vec<'_, u8>, size: usize) {
+        if cfg!(debug_assertions) {
+            // int3
+            buf.extend(std::iter::repeat(0xCC).take(size));
+        } else {
+            nops(buf, size);
+        }
+    }
+
     fn and_reg64_reg64_reg64(buf: &mut Vec<'_, u8>, dst: Reg64, src1: Reg64, src2: Reg64) {
         binop_move_src_to_dst_reg64(buf, and_reg64_reg64, dst, src1, src2)
     }
@@ -1534,6 +2257,24 @@ impl Assembler<X86_64GeneralReg, X86_64FloatReg> for X86_64Assembler {
     {
         shift_reg64_reg64_reg64(buf, storage_manager, sar_reg64_reg64, dst, src1, src2)
     }
+
+    #[inline(always)]
+    fn shl_reg64_reg64_imm8(buf: &mut Vec<'_, u8>, dst: Reg64, src: Reg64, imm: u8) {
+        mov_reg64_reg64(buf, dst, src);
+        shl_reg64_imm8(buf, dst, imm);
+    }
+
+    #[inline(always)]
+    fn shr_reg64_reg64_imm8(buf: &mut Vec<'_, u8>, dst: Reg64, src: Reg64, imm: u8) {
+        mov_reg64_reg64(buf, dst, src);
+        shr_reg64_imm8(buf, dst, imm);
+    }
+
+    #[inline(always)]
+    fn sar_reg64_reg64_imm8(buf: &mut Vec<'_, u8>, dst: Reg64, src: Reg64, imm: u8) {
+        mov_reg64_reg64(buf, dst, src);
+        sar_reg64_imm8(buf, dst, imm);
+    }
 }
 
 fn shift_reg64_reg64_reg64<'a, 'r, ASM, CC>(
@@ -1592,7 +2333,6 @@ const REX: u8 = 0x40;
 const REX_PREFIX_W: u8 = 0b1000;
 /// Extension to the MODRM.reg
 const REX_PREFIX_R: u8 = 0b0100;
-#[allow(unused)]
 /// Extension to the SIB.index field
 const REX_PREFIX_X: u8 = 0b0010;
 /// Extension to the MODRM.rm
@@ -1601,26 +2341,103 @@ const REX_PREFIX_B: u8 = 0b0001;
 /// Wide REX
 const REX_W: u8 = REX | REX_PREFIX_W;
 
-#[inline(always)]
-fn add_rm_extension<T: RegTrait>(reg: T, byte: u8) -> u8 {
-    if reg.value() > 7 {
-        byte | REX_PREFIX_B
-    } else {
-        byte
-    }
+/// The REX prefix of an instruction, as the encoder fills in its fields. With debug assertions,
+/// it checks that no field gets two registers, and that an instruction with one of AH, CH, DH,
+/// and BH gets no REX prefix, which would turn those into SPL, BPL, SIL, and DIL. What it checks
+/// is never read otherwise, so it is the same bit math as writing out the byte by hand.
+#[derive(Clone, Copy, Debug)]
+#[must_use]
+struct RexBuilder {
+    byte: u8,
+    /// The `REX_PREFIX_R`, `REX_PREFIX_X` and `REX_PREFIX_B` fields that have a register
+    fields_set: u8,
+    /// Whether the instruction uses one of AH, CH, DH, and BH
+    high_byte_reg: bool,
 }
 
-#[inline(always)]
-fn add_opcode_extension(reg: X86_64GeneralReg, byte: u8) -> u8 {
-    add_rm_extension(reg, byte)
-}
+impl RexBuilder {
+    #[inline(always)]
+    const fn new() -> Self {
+        RexBuilder {
+            byte: REX,
+            fields_set: 0,
+            high_byte_reg: false,
+        }
+    }
 
-#[inline(always)]
-fn add_reg_extension<T: RegTrait>(reg: T, byte: u8) -> u8 {
-    if reg.value() > 7 {
-        byte | REX_PREFIX_R
-    } else {
-        byte
+    /// A prefix with 64-bit operands
+    #[inline(always)]
+    const fn wide() -> Self {
+        RexBuilder {
+            byte: REX_W,
+            ..Self::new()
+        }
+    }
+
+    #[inline(always)]
+    fn set(mut self, field: u8, reg: u8) -> Self {
+        debug_assert!(
+            self.fields_set & field == 0,
+            "the REX prefix already has a register in the field {:#06b}",
+            field
+        );
+        self.fields_set |= field;
+        if reg > 7 {
+            self.byte |= field;
+        }
+        self
+    }
+
+    /// For `reg` in MODRM.rm, or in SIB.base
+    #[inline(always)]
+    fn rm<T: RegTrait>(self, reg: T) -> Self {
+        self.set(REX_PREFIX_B, reg.value())
+    }
+
+    /// For `reg` in the low 3 bits of the opcode
+    #[inline(always)]
+    fn opcode(self, reg: X86_64GeneralReg) -> Self {
+        self.set(REX_PREFIX_B, reg.value())
+    }
+
+    /// For `reg` in MODRM.reg
+    #[inline(always)]
+    fn reg<T: RegTrait>(self, reg: T) -> Self {
+        self.set(REX_PREFIX_R, reg.value())
+    }
+
+    /// For `reg` in SIB.index
+    #[inline(always)]
+    fn index(self, reg: X86_64GeneralReg) -> Self {
+        self.set(REX_PREFIX_X, reg.value())
+    }
+
+    /// For an instruction that uses one of AH, CH, DH, and BH, which can not have a REX prefix
+    #[allow(dead_code)]
+    #[inline(always)]
+    fn high_byte_reg(mut self) -> Self {
+        self.high_byte_reg = true;
+        self
+    }
+
+    /// The prefix, for an instruction that always has one
+    #[inline(always)]
+    fn byte(self) -> u8 {
+        debug_assert!(
+            !self.high_byte_reg,
+            "AH, CH, DH, and BH can not be used with a REX prefix"
+        );
+        self.byte
+    }
+
+    /// The prefix, unless the instruction does the same without one
+    #[inline(always)]
+    fn byte_if_needed(self) -> Option<u8> {
+        if self.byte == REX {
+            None
+        } else {
+            Some(self.byte())
+        }
     }
 }
 
@@ -1631,8 +2448,7 @@ fn binop_reg64_reg64(
     dst: X86_64GeneralReg,
     src: X86_64GeneralReg,
 ) {
-    let rex = add_rm_extension(dst, REX_W);
-    let rex = add_reg_extension(src, rex);
+    let rex = RexBuilder::wide().rm(dst).reg(src).byte();
     let dst_mod = dst as u8 % 8;
     let src_mod = (src as u8 % 8) << 3;
     buf.extend([rex, op_code, 0xC0 | dst_mod | src_mod]);
@@ -1646,8 +2462,7 @@ fn extended_binop_reg64_reg64(
     dst: X86_64GeneralReg,
     src: X86_64GeneralReg,
 ) {
-    let rex = add_rm_extension(dst, REX_W);
-    let rex = add_reg_extension(src, rex);
+    let rex = RexBuilder::wide().rm(dst).reg(src).byte();
     let dst_mod = dst as u8 % 8;
     let src_mod = (src as u8 % 8) << 3;
     buf.extend([rex, op_code1, op_code2, 0xC0 | dst_mod | src_mod]);
@@ -1662,7 +2477,7 @@ fn extended_binop_reg64_reg64(
 #[inline(always)]
 fn add_reg64_imm32(buf: &mut Vec<'_, u8>, dst: X86_64GeneralReg, imm: i32) {
     // This can be optimized if the immediate is 1 byte.
-    let rex = add_rm_extension(dst, REX_W);
+    let rex = RexBuilder::wide().rm(dst).byte();
     let dst_mod = dst as u8 % 8;
     buf.reserve(7);
     buf.extend([rex, 0x81, 0xC0 | dst_mod]);
@@ -1689,6 +2504,19 @@ fn or_reg64_reg64(buf: &mut Vec<'_, u8>, dst: X86_64GeneralReg, src: X86_64Gener
     binop_reg64_reg64(0x0B, buf, src, dst);
 }
 
+/// `XOR r/m32,r32` -> Bitwise logical exclusive or r32 to r/m32, zeroing the upper 32 bits.
+#[inline(always)]
+fn xor_reg32_reg32(buf: &mut Vec<'_, u8>, dst: X86_64GeneralReg, src: X86_64GeneralReg) {
+    let rex = RexBuilder::new().rm(dst).reg(src).byte_if_needed();
+    let dst_mod = dst as u8 % 8;
+    let src_mod = (src as u8 % 8) << 3;
+
+    if let Some(rex) = rex {
+        buf.push(rex);
+    }
+    buf.extend([0x31, 0xC0 | dst_mod | src_mod]);
+}
+
 /// `XOR r/m64,r64` -> Bitwise logical exclusive or r64 to r/m64.
 #[inline(always)]
 fn xor_reg64_reg64(buf: &mut Vec<'_, u8>, dst: X86_64GeneralReg, src: X86_64GeneralReg) {
@@ -1699,8 +2527,7 @@ fn xor_reg64_reg64(buf: &mut Vec<'_, u8>, dst: X86_64GeneralReg, src: X86_64Gene
 /// `SHL r/m64, CL` -> Multiply r/m64 by 2, CL times.
 #[inline(always)]
 fn shl_reg64_reg64(buf: &mut Vec<'_, u8>, dst: X86_64GeneralReg) {
-    let rex = add_rm_extension(dst, REX_W);
-    let rex = add_reg_extension(dst, rex);
+    let rex = RexBuilder::wide().rm(dst).reg(dst).byte();
 
     let dst_mod = dst as u8 % 8;
     buf.extend([rex, 0xD3, 0xC0 | (4 << 3) | dst_mod]);
@@ -1709,8 +2536,7 @@ fn shl_reg64_reg64(buf: &mut Vec<'_, u8>, dst: X86_64GeneralReg) {
 /// `SHR r/m64, CL` -> Unsigned divide r/m64 by 2, CL times.
 #[inline(always)]
 fn shr_reg64_reg64(buf: &mut Vec<'_, u8>, dst: X86_64GeneralReg) {
-    let rex = add_rm_extension(dst, REX_W);
-    let rex = add_reg_extension(dst, rex);
+    let rex = RexBuilder::wide().rm(dst).reg(dst).byte();
 
     let dst_mod = dst as u8 % 8;
     buf.extend([rex, 0xD3, 0xC0 | (5 << 3) | dst_mod]);
@@ -1719,13 +2545,36 @@ fn shr_reg64_reg64(buf: &mut Vec<'_, u8>, dst: X86_64GeneralReg) {
 /// `SAR r/m64, CL` -> Signed divide r/m64 by 2, CL times.
 #[inline(always)]
 fn sar_reg64_reg64(buf: &mut Vec<'_, u8>, dst: X86_64GeneralReg) {
-    let rex = add_rm_extension(dst, REX_W);
-    let rex = add_reg_extension(dst, rex);
+    let rex = RexBuilder::wide().rm(dst).reg(dst).byte();
 
     let dst_mod = dst as u8 % 8;
     buf.extend([rex, 0xD3, 0xC0 | (7 << 3) | dst_mod]);
 }
 
+/// `SHL r/m64, imm8` -> Multiply r/m64 by 2, imm8 times.
+#[inline(always)]
+fn shl_reg64_imm8(buf: &mut Vec<'_, u8>, dst: X86_64GeneralReg, imm: u8) {
+    let rex = RexBuilder::wide().rm(dst).byte();
+    let dst_mod = dst as u8 % 8;
+    buf.extend([rex, 0xC1, 0xC0 | (4 << 3) | dst_mod, imm]);
+}
+
+/// `SHR r/m64, imm8` -> Unsigned divide r/m64 by 2, imm8 times.
+#[inline(always)]
+fn shr_reg64_imm8(buf: &mut Vec<'_, u8>, dst: X86_64GeneralReg, imm: u8) {
+    let rex = RexBuilder::wide().rm(dst).byte();
+    let dst_mod = dst as u8 % 8;
+    buf.extend([rex, 0xC1, 0xC0 | (5 << 3) | dst_mod, imm]);
+}
+
+/// `SAR r/m64, imm8` -> Signed divide r/m64 by 2, imm8 times.
+#[inline(always)]
+fn sar_reg64_imm8(buf: &mut Vec<'_, u8>, dst: X86_64GeneralReg, imm: u8) {
+    let rex = RexBuilder::wide().rm(dst).byte();
+    let dst_mod = dst as u8 % 8;
+    buf.extend([rex, 0xC1, 0xC0 | (7 << 3) | dst_mod, imm]);
+}
+
 /// `ADDSD xmm1,xmm2/m64` -> Add the low double-precision floating-point value from xmm2/mem to xmm1 and store the result in xmm1.
 #[inline(always)]
 fn addsd_freg64_freg64(buf: &mut Vec<'_, u8>, dst: X86_64FloatReg, src: X86_64FloatReg) {
@@ -1866,10 +2715,81 @@ fn andpd_freg64_freg64(buf: &mut Vec<'_, u8>, dst: X86_64FloatReg, src: X86_64Fl
     }
 }
 
+/// An SSE instruction on two float registers: `prefix`, if the instruction has one, then
+/// `0F opcode`, with `xmm1` in MODRM.reg and `xmm2` in MODRM.rm.
+#[inline(always)]
+fn sse_freg_freg(
+    buf: &mut Vec<'_, u8>,
+    prefix: Option<u8>,
+    opcode: u8,
+    xmm1: X86_64FloatReg,
+    xmm2: X86_64FloatReg,
+) {
+    buf.reserve(5);
+    if let Some(prefix) = prefix {
+        buf.push(prefix);
+    }
+    if let Some(rex) = RexBuilder::new().reg(xmm1).rm(xmm2).byte_if_needed() {
+        buf.push(rex);
+    }
+    buf.extend([
+        0x0F,
+        opcode,
+        0xC0 | ((xmm1 as u8 % 8) << 3) | (xmm2 as u8 % 8),
+    ]);
+}
+
+/// `XORPS xmm1,xmm2/m128` -> Bitwise exclusive-OR of xmm1 and xmm2/m128.
+#[inline(always)]
+fn xorps_freg_freg(buf: &mut Vec<'_, u8>, dst: X86_64FloatReg, src: X86_64FloatReg) {
+    sse_freg_freg(buf, None, 0x57, dst, src);
+}
+
+/// `UCOMISD xmm1,xmm2/m64` or `UCOMISS xmm1,xmm2/m32`, by `width` -> Compare the low floats of
+/// xmm1 and xmm2 unordered and set ZF, PF, CF accordingly.
+#[inline(always)]
+fn ucomis_freg_freg(
+    buf: &mut Vec<'_, u8>,
+    width: FloatWidth,
+    src1: X86_64FloatReg,
+    src2: X86_64FloatReg,
+) {
+    let prefix = match width {
+        FloatWidth::F64 => Some(0x66),
+        FloatWidth::F32 => None,
+    };
+    sse_freg_freg(buf, prefix, 0x2E, src1, src2);
+}
+
+/// `CMPEQSD xmm1,xmm2/m64` -> Set the low quadword of xmm1 to all ones if it is equal to that of
+/// xmm2, and to all zeroes otherwise, which includes NaN.
+#[inline(always)]
+fn cmpeqsd_freg_freg(buf: &mut Vec<'_, u8>, dst: X86_64FloatReg, src: X86_64FloatReg) {
+    sse_freg_freg(buf, Some(0xF2), 0xC2, dst, src);
+    buf.push(0);
+}
+
+/// `CMPEQSS xmm1,xmm2/m32` -> Set the low doubleword of xmm1 to all ones if it is equal to that
+/// of xmm2, and to all zeroes otherwise, which includes NaN.
+#[inline(always)]
+fn cmpeqss_freg_freg(buf: &mut Vec<'_, u8>, dst: X86_64FloatReg, src: X86_64FloatReg) {
+    sse_freg_freg(buf, Some(0xF3), 0xC2, dst, src);
+    buf.push(0);
+}
+
+/// `MOVQ r/m64,xmm` -> Move quadword from xmm to r/m64.
+#[inline(always)]
+fn movq_reg64_freg64(buf: &mut Vec<'_, u8>, dst: X86_64GeneralReg, src: X86_64FloatReg) {
+    let rex = RexBuilder::wide().reg(src).rm(dst).byte();
+    let dst_mod = dst as u8 % 8;
+    let src_mod = (src as u8 % 8) << 3;
+    buf.extend([0x66, rex, 0x0F, 0x7E, 0xC0 | src_mod | dst_mod]);
+}
+
 /// r/m64 AND imm8 (sign-extended).
 #[inline(always)]
 fn and_reg64_imm8(buf: &mut Vec<'_, u8>, dst: X86_64GeneralReg, imm: i8) {
-    let rex = add_rm_extension(dst, REX_W);
+    let rex = RexBuilder::wide().rm(dst).byte();
     let dst_mod = dst as u8 % 8;
     buf.extend([rex, 0x83, 0xE0 | dst_mod, imm as u8]);
 }
@@ -1877,8 +2797,7 @@ fn and_reg64_imm8(buf: &mut Vec<'_, u8>, dst: X86_64GeneralReg, imm: i8) {
 /// `CMOVL r64,r/m64` -> Move if less (SF≠ OF).
 #[inline(always)]
 fn cmovl_reg64_reg64(buf: &mut Vec<'_, u8>, dst: X86_64GeneralReg, src: X86_64GeneralReg) {
-    let rex = add_reg_extension(dst, REX_W);
-    let rex = add_rm_extension(src, rex);
+    let rex = RexBuilder::wide().reg(dst).rm(src).byte();
     let dst_mod = (dst as u8 % 8) << 3;
     let src_mod = src as u8 % 8;
     buf.extend([rex, 0x0F, 0x4C, 0xC0 | dst_mod | src_mod]);
@@ -1887,7 +2806,7 @@ fn cmovl_reg64_reg64(buf: &mut Vec<'_, u8>, dst: X86_64GeneralReg, src: X86_64Ge
 /// `CMP r/m64,i32` -> Compare i32 to r/m64.
 #[inline(always)]
 fn cmp_reg64_imm32(buf: &mut Vec<'_, u8>, dst: X86_64GeneralReg, imm: i32) {
-    let rex = add_rm_extension(dst, REX_W);
+    let rex = RexBuilder::wide().rm(dst).byte();
     let dst_mod = dst as u8 % 8;
     buf.reserve(7);
     buf.extend([rex, 0x81, 0xF8 | dst_mod]);
@@ -1907,6 +2826,28 @@ fn test_reg64_reg64(buf: &mut Vec<'_, u8>, dst: X86_64GeneralReg, src: X86_64Gen
     binop_reg64_reg64(0x85, buf, dst, src);
 }
 
+/// `TEST r/m,r` with `reg` as both operands, at `width` -> set SF, ZF, PF according to `reg`.
+#[inline(always)]
+fn test_reg_reg(buf: &mut Vec<'_, u8>, width: RegisterWidth, reg: X86_64GeneralReg) {
+    let rex = RexBuilder::new().rm(reg).reg(reg);
+    let (prefix, rex, opcode) = match width {
+        RegisterWidth::W64 => (None, Some(RexBuilder::wide().rm(reg).reg(reg).byte()), 0x85),
+        RegisterWidth::W32 => (None, rex.byte_if_needed(), 0x85),
+        RegisterWidth::W16 => (Some(0x66), rex.byte_if_needed(), 0x85),
+        // without a REX prefix, the low bytes of RSP, RBP, RSI, and RDI would be AH to BH instead
+        RegisterWidth::W8 => (None, Some(rex.byte()), 0x84),
+    };
+    let reg_mod = reg as u8 % 8;
+    buf.reserve(4);
+    if let Some(prefix) = prefix {
+        buf.push(prefix);
+    }
+    if let Some(rex) = rex {
+        buf.push(rex);
+    }
+    buf.extend([opcode, 0xC0 | (reg_mod << 3) | reg_mod]);
+}
+
 /// `IMUL r64,r/m64` -> Signed Multiply r/m64 to r64.
 #[inline(always)]
 fn imul_reg64_reg64(buf: &mut Vec<'_, u8>, dst: X86_64GeneralReg, src: X86_64GeneralReg) {
@@ -1915,60 +2856,218 @@ fn imul_reg64_reg64(buf: &mut Vec<'_, u8>, dst: X86_64GeneralReg, src: X86_64Gen
     extended_binop_reg64_reg64(0x0F, 0xAF, buf, src, dst);
 }
 
-/// `MUL r/m64` -> Unsigned Multiply r/m64 to r64.
+/// `IMUL r64,r/m64,imm32` -> r64 ← r/m64 * imm32, the immediate sign-extended to 64 bits.
 #[inline(always)]
-fn mul_reg64_reg64(buf: &mut Vec<'_, u8>, src: X86_64GeneralReg) {
-    let mut rex = REX_W;
-    rex = add_reg_extension(src, rex);
-
-    if src.value() > 7 {
-        rex |= REX_PREFIX_B;
-    }
-
-    buf.extend([rex, 0xF7, 0b1110_0000 | (src as u8 % 8)]);
-}
-
+fn imul_reg64_reg64_imm32(
+    buf: &mut Vec<'_, u8>,
+    dst: X86_64GeneralReg,
+    src: X86_64GeneralReg,
+    imm: i32,
+) {
+    let rex = RexBuilder::wide().reg(dst).rm(src).byte();
+    let dst_mod = (dst as u8 % 8) << 3;
+    let src_mod = src as u8 % 8;
+    buf.reserve(7);
+    buf.extend([rex, 0x69, 0xC0 | dst_mod | src_mod]);
+    buf.extend(imm.to_le_bytes());
+}
+
+/// `IMUL r/m64` -> Signed multiply RAX by r/m64, with the 128-bit result stored in RDX:RAX.
+#[allow(dead_code)]
+#[inline(always)]
+fn imul_reg64(buf: &mut Vec<'_, u8>, src: X86_64GeneralReg) {
+    // the reg field of ModRM is the opcode extension, so only REX.B extends `src`
+    let rex = RexBuilder::wide().rm(src).byte();
+
+    buf.extend([rex, 0xF7, 0b1110_1000 | (src as u8 % 8)]);
+}
+
+/// `MUL r/m64` -> Unsigned multiply RAX by r/m64, with the 128-bit result stored in RDX:RAX.
+#[inline(always)]
+fn mul_reg64(buf: &mut Vec<'_, u8>, src: X86_64GeneralReg) {
+    let rex = RexBuilder::wide().rm(src).byte();
+
+    buf.extend([rex, 0xF7, 0b1110_0000 | (src as u8 % 8)]);
+}
+
 /// `IDIV r/m64` -> Signed divide RDX:RAX by r/m64, with result stored in RAX ← Quotient, RDX ← Remainder.
 #[inline(always)]
-fn idiv_reg64_reg64(buf: &mut Vec<'_, u8>, src: X86_64GeneralReg) {
-    let mut rex = REX_W;
-    rex = add_reg_extension(src, rex);
+fn idiv_reg64(buf: &mut Vec<'_, u8>, src: X86_64GeneralReg) {
+    let rex = RexBuilder::wide().rm(src).byte();
 
-    if src.value() > 7 {
-        rex |= REX_PREFIX_B;
-    }
+    buf.extend([rex, 0xF7, 0b1111_1000 | (src as u8 % 8)]);
+}
 
-    // The CQO instruction can be used to produce a double quadword dividend
-    // from a quadword before a quadword division.
-    //
-    // The CQO instruction (available in 64-bit mode only) copies the sign (bit 63)
-    // of the value in the RAX register into every bit position in the RDX register
+/// `CQO` -> RDX:RAX ← sign-extend of RAX.
+#[inline(always)]
+fn cqo(buf: &mut Vec<'_, u8>) {
     buf.extend([0x48, 0x99]);
+}
 
-    buf.extend([rex, 0xF7, 0b1111_1000 | (src as u8 % 8)]);
+/// `CDQ` -> EDX:EAX ← sign-extend of EAX.
+#[inline(always)]
+fn cdq(buf: &mut Vec<'_, u8>) {
+    buf.push(0x99);
 }
 
-/// `DIV r/m64` -> Unsigned divide RDX:RAX by r/m64, with result stored in RAX ← Quotient, RDX ← Remainder.
+/// `CWD` -> DX:AX ← sign-extend of AX.
+#[inline(always)]
+fn cwd(buf: &mut Vec<'_, u8>) {
+    buf.extend([0x66, 0x99]);
+}
+
+/// `CBW` -> AX ← sign-extend of AL.
+#[inline(always)]
+fn cbw(buf: &mut Vec<'_, u8>) {
+    buf.extend([0x66, 0x98]);
+}
+
+/// `RDTSC` -> EDX:EAX ← time-stamp counter.
+#[inline(always)]
+fn rdtsc(buf: &mut Vec<'_, u8>) {
+    buf.extend([0x0F, 0x31]);
+}
+
+/// `LFENCE` -> Serializes load operations; later instructions wait for the earlier ones.
 #[inline(always)]
-fn udiv_reg64_reg64(buf: &mut Vec<'_, u8>, src: X86_64GeneralReg) {
-    let mut rex = REX_W;
-    rex = add_reg_extension(src, rex);
+fn lfence(buf: &mut Vec<'_, u8>) {
+    buf.extend([0x0F, 0xAE, 0xE8]);
+}
 
-    if src.value() > 7 {
-        rex |= REX_PREFIX_B;
+/// The division of the implicit dividend by `src` at a width below 64 bits. `prefix` is the
+/// operand size prefix, if the width needs one.
+#[inline(always)]
+fn div_narrow(
+    buf: &mut Vec<'_, u8>,
+    prefix: Option<u8>,
+    opcode: u8,
+    extension: u8,
+    src: X86_64GeneralReg,
+) {
+    buf.reserve(4);
+    if let Some(prefix) = prefix {
+        buf.push(prefix);
+    }
+    // without a REX prefix, the low bytes of RSP, RBP, RSI, and RDI would be AH to BH instead
+    let rex = RexBuilder::new().rm(src);
+    let rex = if opcode == 0xF6 {
+        Some(rex.byte())
+    } else {
+        rex.byte_if_needed()
+    };
+    if let Some(rex) = rex {
+        buf.push(rex);
     }
+    buf.extend([opcode, 0xC0 | (extension << 3) | (src as u8 % 8)]);
+}
 
-    // The CQO instruction can be used to produce a double quadword dividend
-    // from a quadword before a quadword division.
-    //
-    // The CQO instruction (available in 64-bit mode only) copies the sign (bit 63)
-    // of the value in the RAX register into every bit position in the RDX register
-    buf.extend([0x48, 0x99]);
+/// `IDIV r/m32` -> Signed divide EDX:EAX by r/m32, with result stored in EAX ← Quotient, EDX ← Remainder.
+#[inline(always)]
+fn idiv_reg32(buf: &mut Vec<'_, u8>, src: X86_64GeneralReg) {
+    div_narrow(buf, None, 0xF7, 7, src);
+}
+
+/// `DIV r/m32` -> Unsigned divide EDX:EAX by r/m32, with result stored in EAX ← Quotient, EDX ← Remainder.
+#[inline(always)]
+fn div_reg32(buf: &mut Vec<'_, u8>, src: X86_64GeneralReg) {
+    div_narrow(buf, None, 0xF7, 6, src);
+}
+
+/// `IDIV r/m16` -> Signed divide DX:AX by r/m16, with result stored in AX ← Quotient, DX ← Remainder.
+#[inline(always)]
+fn idiv_reg16(buf: &mut Vec<'_, u8>, src: X86_64GeneralReg) {
+    div_narrow(buf, Some(0x66), 0xF7, 7, src);
+}
+
+/// `DIV r/m16` -> Unsigned divide DX:AX by r/m16, with result stored in AX ← Quotient, DX ← Remainder.
+#[inline(always)]
+fn div_reg16(buf: &mut Vec<'_, u8>, src: X86_64GeneralReg) {
+    div_narrow(buf, Some(0x66), 0xF7, 6, src);
+}
+
+/// `IDIV r/m8` -> Signed divide AX by r/m8, with result stored in AL ← Quotient, AH ← Remainder.
+#[inline(always)]
+fn idiv_reg8(buf: &mut Vec<'_, u8>, src: X86_64GeneralReg) {
+    div_narrow(buf, None, 0xF6, 7, src);
+}
+
+/// `DIV r/m8` -> Unsigned divide AX by r/m8, with result stored in AL ← Quotient, AH ← Remainder.
+#[inline(always)]
+fn div_reg8(buf: &mut Vec<'_, u8>, src: X86_64GeneralReg) {
+    div_narrow(buf, None, 0xF6, 6, src);
+}
+
+/// `DIV r/m64` -> Unsigned divide RDX:RAX by r/m64, with result stored in RAX ← Quotient, RDX ← Remainder.
+#[inline(always)]
+fn div_reg64(buf: &mut Vec<'_, u8>, src: X86_64GeneralReg) {
+    let rex = RexBuilder::wide().rm(src).byte();
 
-    // adds a cqo (convert doubleword to quadword)
     buf.extend([rex, 0xF7, 0b1111_0000 | (src as u8 % 8)]);
 }
 
+/// Divides `src1` by `src2` at `width`, signed. The quotient ends up in RAX and the remainder in
+/// RDX, except at 8 bits, where they are AL and AH. Only the low `width` bits of them are set.
+#[inline(always)]
+fn idiv_at_width(
+    buf: &mut Vec<'_, u8>,
+    width: RegisterWidth,
+    src1: X86_64GeneralReg,
+    src2: X86_64GeneralReg,
+) {
+    mov_reg64_reg64(buf, X86_64GeneralReg::RAX, src1);
+    match width {
+        RegisterWidth::W64 => {
+            cqo(buf);
+            idiv_reg64(buf, src2);
+        }
+        RegisterWidth::W32 => {
+            cdq(buf);
+            idiv_reg32(buf, src2);
+        }
+        RegisterWidth::W16 => {
+            cwd(buf);
+            idiv_reg16(buf, src2);
+        }
+        RegisterWidth::W8 => {
+            cbw(buf);
+            idiv_reg8(buf, src2);
+        }
+    }
+}
+
+/// Divides `src1` by `src2` at `width`, unsigned, like [idiv_at_width]. The upper half of the
+/// dividend is zeroed; sign extending it would make a large dividend negative.
+#[inline(always)]
+fn div_at_width(
+    buf: &mut Vec<'_, u8>,
+    width: RegisterWidth,
+    src1: X86_64GeneralReg,
+    src2: X86_64GeneralReg,
+) {
+    use X86_64GeneralReg::{RAX, RDX};
+
+    mov_reg64_reg64(buf, RAX, src1);
+    match width {
+        RegisterWidth::W64 => {
+            xor_reg32_reg32(buf, RDX, RDX);
+            div_reg64(buf, src2);
+        }
+        RegisterWidth::W32 => {
+            xor_reg32_reg32(buf, RDX, RDX);
+            div_reg32(buf, src2);
+        }
+        RegisterWidth::W16 => {
+            xor_reg32_reg32(buf, RDX, RDX);
+            div_reg16(buf, src2);
+        }
+        // the dividend is all of AX
+        RegisterWidth::W8 => {
+            movzx_reg64_reg8(buf, RAX, RAX);
+            div_reg8(buf, src2);
+        }
+    }
+}
+
 /// Jump near, relative, RIP = RIP + 32-bit displacement sign extended to 64-bits.
 #[inline(always)]
 fn jmp_imm32(buf: &mut Vec<'_, u8>, imm: i32) {
@@ -1977,6 +3076,24 @@ fn jmp_imm32(buf: &mut Vec<'_, u8>, imm: i32) {
     buf.extend(imm.to_le_bytes());
 }
 
+/// Jump short, relative, RIP = RIP + 8-bit displacement sign extended to 64-bits.
+#[inline(always)]
+fn jmp_imm8(buf: &mut Vec<'_, u8>, imm: i8) {
+    buf.extend([0xEB, imm as u8]);
+}
+
+/// Jump short if greater or equal (SF=OF).
+#[inline(always)]
+fn jge_imm8(buf: &mut Vec<'_, u8>, imm: i8) {
+    buf.extend([0x7D, imm as u8]);
+}
+
+/// Jump short if not equal (ZF=0).
+#[inline(always)]
+fn jne_imm8(buf: &mut Vec<'_, u8>, imm: i8) {
+    buf.extend([0x75, imm as u8]);
+}
+
 /// Jump near if not equal (ZF=0).
 #[inline(always)]
 fn jne_imm32(buf: &mut Vec<'_, u8>, imm: i32) {
@@ -1986,10 +3103,24 @@ fn jne_imm32(buf: &mut Vec<'_, u8>, imm: i32) {
     buf.extend(imm.to_le_bytes());
 }
 
+/// `MOV r32, imm32` -> Move imm32 to r32, zeroing the upper 32 bits.
+#[inline(always)]
+fn mov_reg32_imm32(buf: &mut Vec<'_, u8>, dst: X86_64GeneralReg, imm: u32) {
+    let rex = RexBuilder::new().opcode(dst).byte_if_needed();
+    let dst_mod = dst as u8 % 8;
+    buf.reserve(6);
+
+    if let Some(rex) = rex {
+        buf.push(rex);
+    }
+    buf.push(0xB8 | dst_mod);
+    buf.extend(imm.to_le_bytes());
+}
+
 /// `MOV r/m64, imm32` -> Move imm32 sign extended to 64-bits to r/m64.
 #[inline(always)]
 fn mov_reg64_imm32(buf: &mut Vec<'_, u8>, dst: X86_64GeneralReg, imm: i32) {
-    let rex = add_rm_extension(dst, REX_W);
+    let rex = RexBuilder::wide().rm(dst).byte();
     let dst_mod = dst as u8 % 8;
     buf.reserve(7);
     buf.extend([rex, 0xC7, 0xC0 | dst_mod]);
@@ -2002,7 +3133,7 @@ fn mov_reg64_imm64(buf: &mut Vec<'_, u8>, dst: X86_64GeneralReg, imm: i64) {
     if imm <= i32::MAX as i64 && imm >= i32::MIN as i64 {
         mov_reg64_imm32(buf, dst, imm as i32)
     } else {
-        let rex = add_opcode_extension(dst, REX_W);
+        let rex = RexBuilder::wide().opcode(dst).byte();
         let dst_mod = dst as u8 % 8;
         buf.reserve(10);
         buf.extend([rex, 0xB8 | dst_mod]);
@@ -2010,6 +3141,68 @@ fn mov_reg64_imm64(buf: &mut Vec<'_, u8>, dst: X86_64GeneralReg, imm: i64) {
     }
 }
 
+/// `LEA r64,m` -> Store the effective address of `base + index * scale` in r64.
+/// RSP cannot be an index, and the scale must be 1, 2, 4, or 8.
+#[inline(always)]
+fn lea_reg64_base64_index64(
+    buf: &mut Vec<'_, u8>,
+    dst: X86_64GeneralReg,
+    base: X86_64GeneralReg,
+    index: X86_64GeneralReg,
+    scale: u8,
+) {
+    debug_assert_ne!(index, X86_64GeneralReg::RSP);
+    let scale_bits = match scale {
+        1 => 0b00,
+        2 => 0b01,
+        4 => 0b10,
+        8 => 0b11,
+        _ => internal_error!("lea can only scale by 1, 2, 4, or 8, not {}", scale),
+    };
+
+    let rex = RexBuilder::wide().rm(base).reg(dst).index(index).byte();
+    let dst_mod = (dst as u8 % 8) << 3;
+    let sib = (scale_bits << 6) | ((index as u8 % 8) << 3) | (base as u8 % 8);
+
+    // The rm field of 0b100 means a SIB byte follows.
+    // A base of RBP or R13 has no encoding without a displacement, so those get a zero one.
+    if base == X86_64GeneralReg::RBP || base == X86_64GeneralReg::R13 {
+        buf.extend([rex, 0x8D, 0x44 | dst_mod, sib, 0]);
+    } else {
+        buf.extend([rex, 0x8D, 0x04 | dst_mod, sib]);
+    }
+}
+
+/// `LEA r64,m` -> Store the effective address of `rip + offset` in r64.
+#[inline(always)]
+fn lea_reg64_rip_offset32(buf: &mut Vec<'_, u8>, dst: X86_64GeneralReg, offset: u32) {
+    let rex = RexBuilder::wide().reg(dst).byte();
+    let dst_mod = (dst as u8 % 8) << 3;
+    buf.reserve(7);
+    buf.extend([rex, 0x8D, 0x05 | dst_mod]);
+    buf.extend(offset.to_le_bytes());
+}
+
+/// `LEA r64,m` -> Store the effective address of `base + offset` in r64.
+#[inline(always)]
+fn lea_reg64_base64_offset32(
+    buf: &mut Vec<'_, u8>,
+    dst: X86_64GeneralReg,
+    base: X86_64GeneralReg,
+    offset: i32,
+) {
+    let rex = RexBuilder::wide().rm(base).reg(dst).byte();
+    let dst_mod = (dst as u8 % 8) << 3;
+    let base_mod = base as u8 % 8;
+    buf.reserve(8);
+    buf.extend([rex, 0x8D, 0x80 | dst_mod | base_mod]);
+    // Using RSP or R12 requires a secondary index byte.
+    if base == X86_64GeneralReg::RSP || base == X86_64GeneralReg::R12 {
+        buf.push(0x24);
+    }
+    buf.extend(offset.to_le_bytes());
+}
+
 /// `MOV r/m64,r64` -> Move r64 to r/m64.
 /// This will not generate anything if dst and src are the same.
 #[inline(always)]
@@ -2036,8 +3229,7 @@ fn mov_base64_offset32_reg64(
     offset: i32,
     src: X86_64GeneralReg,
 ) {
-    let rex = add_rm_extension(base, REX_W);
-    let rex = add_reg_extension(src, rex);
+    let rex = RexBuilder::wide().rm(base).reg(src).byte();
     let src_mod = (src as u8 % 8) << 3;
     let base_mod = base as u8 % 8;
     buf.reserve(8);
@@ -2049,6 +3241,87 @@ fn mov_base64_offset32_reg64(
     buf.extend(offset.to_le_bytes());
 }
 
+/// `MOV r/m64, imm32` -> Move imm32 sign extended to 64-bits to r/m64, where m64 references a base + offset.
+#[inline(always)]
+fn mov_base64_offset32_imm32(buf: &mut Vec<'_, u8>, base: X86_64GeneralReg, offset: i32, imm: i32) {
+    let rex = RexBuilder::wide().rm(base).byte();
+    let base_mod = base as u8 % 8;
+    buf.reserve(12);
+    buf.extend([rex, 0xC7, 0x80 | base_mod]);
+    // Using RSP or R12 requires a secondary index byte.
+    if base == X86_64GeneralReg::RSP || base == X86_64GeneralReg::R12 {
+        buf.push(0x24);
+    }
+    buf.extend(offset.to_le_bytes());
+    buf.extend(imm.to_le_bytes());
+}
+
+/// `MOV r/m32, imm32` -> Move imm32 to r/m32, where m32 references a base + offset.
+#[inline(always)]
+fn mov_base32_offset32_imm32(buf: &mut Vec<'_, u8>, base: X86_64GeneralReg, offset: i32, imm: i32) {
+    mov_base_offset32_imm(buf, RegisterWidth::W32, base, offset, imm)
+}
+
+/// `MOV r/m16, imm16` -> Move imm16 to r/m16, where m16 references a base + offset.
+#[inline(always)]
+fn mov_base16_offset32_imm16(buf: &mut Vec<'_, u8>, base: X86_64GeneralReg, offset: i32, imm: i16) {
+    mov_base_offset32_imm(buf, RegisterWidth::W16, base, offset, imm as i32)
+}
+
+/// `MOV r/m8, imm8` -> Move imm8 to r/m8, where m8 references a base + offset.
+#[inline(always)]
+fn mov_base8_offset32_imm8(buf: &mut Vec<'_, u8>, base: X86_64GeneralReg, offset: i32, imm: i8) {
+    mov_base_offset32_imm(buf, RegisterWidth::W8, base, offset, imm as i32)
+}
+
+/// The stores of the low 1, 2, or 4 bytes of `imm` to `base + offset`. There is no 8 byte form,
+/// that is `mov_base64_offset32_imm32`, which sign extends `imm`.
+#[inline(always)]
+fn mov_base_offset32_imm(
+    buf: &mut Vec<'_, u8>,
+    width: RegisterWidth,
+    base: X86_64GeneralReg,
+    offset: i32,
+    imm: i32,
+) {
+    let rex = RexBuilder::new().rm(base).byte_if_needed();
+    let base_mod = base as u8 % 8;
+    buf.reserve(11);
+    let opcode = match width {
+        RegisterWidth::W8 => 0xC6,
+        RegisterWidth::W16 => {
+            buf.push(0x66);
+            0xC7
+        }
+        RegisterWidth::W32 => 0xC7,
+        RegisterWidth::W64 => internal_error!("8 byte immediate stores sign extend their imm32"),
+    };
+    if let Some(rex) = rex {
+        buf.push(rex);
+    }
+    buf.extend([opcode, 0x80 | base_mod]);
+    // Using RSP or R12 requires a secondary index byte.
+    if base == X86_64GeneralReg::RSP || base == X86_64GeneralReg::R12 {
+        buf.push(0x24);
+    }
+    buf.extend(offset.to_le_bytes());
+    match width {
+        RegisterWidth::W8 => buf.push(imm as u8),
+        RegisterWidth::W16 => buf.extend((imm as i16).to_le_bytes()),
+        _ => buf.extend(imm.to_le_bytes()),
+    }
+}
+
+/// `MOV r64,r/m64` -> Move r/m64 to r64, where m64 references `rip + offset`.
+#[inline(always)]
+fn mov_reg64_rip_offset32(buf: &mut Vec<'_, u8>, dst: X86_64GeneralReg, offset: u32) {
+    let rex = RexBuilder::wide().reg(dst).byte();
+    let dst_mod = (dst as u8 % 8) << 3;
+    buf.reserve(7);
+    buf.extend([rex, 0x8B, 0x05 | dst_mod]);
+    buf.extend(offset.to_le_bytes());
+}
+
 /// `MOV r64,r/m64` -> Move r/m64 to r64, where m64 references a base + offset.
 #[inline(always)]
 fn mov_reg64_base64_offset32(
@@ -2057,8 +3330,7 @@ fn mov_reg64_base64_offset32(
     base: X86_64GeneralReg,
     offset: i32,
 ) {
-    let rex = add_rm_extension(base, REX_W);
-    let rex = add_reg_extension(dst, rex);
+    let rex = RexBuilder::wide().rm(base).reg(dst).byte();
     let dst_mod = (dst as u8 % 8) << 3;
     let base_mod = base as u8 % 8;
     buf.reserve(8);
@@ -2078,8 +3350,7 @@ fn movzx_reg64_base8_offset32(
     base: X86_64GeneralReg,
     offset: i32,
 ) {
-    let rex = add_rm_extension(base, REX_W);
-    let rex = add_reg_extension(dst, rex);
+    let rex = RexBuilder::wide().rm(base).reg(dst).byte();
     let dst_mod = (dst as u8 % 8) << 3;
     let base_mod = base as u8 % 8;
     buf.reserve(9);
@@ -2091,63 +3362,284 @@ fn movzx_reg64_base8_offset32(
     buf.extend(offset.to_le_bytes());
 }
 
-/// `MOVSD xmm1,xmm2` -> Move scalar double-precision floating-point value from xmm2 to xmm1 register.
-/// This will not generate anything if dst and src are the same.
+/// An instruction that extends r/m to r64, where m references a base + offset.
 #[inline(always)]
-fn movsd_freg64_freg64(buf: &mut Vec<'_, u8>, dst: X86_64FloatReg, src: X86_64FloatReg) {
-    if dst != src {
-        raw_movsd_freg64_freg64(buf, dst, src);
+fn extend_reg64_base_offset32(
+    buf: &mut Vec<'_, u8>,
+    opcode: &[u8],
+    dst: X86_64GeneralReg,
+    base: X86_64GeneralReg,
+    offset: i32,
+) {
+    let rex = RexBuilder::wide().rm(base).reg(dst).byte();
+    let dst_mod = (dst as u8 % 8) << 3;
+    let base_mod = base as u8 % 8;
+    buf.reserve(9);
+    buf.push(rex);
+    buf.extend(opcode);
+    buf.push(0x80 | dst_mod | base_mod);
+    // Using RSP or R12 requires a secondary index byte.
+    if base == X86_64GeneralReg::RSP || base == X86_64GeneralReg::R12 {
+        buf.push(0x24);
     }
+    buf.extend(offset.to_le_bytes());
 }
 
-/// `MOVSD xmm1,xmm2` -> Move scalar double-precision floating-point value from xmm2 to xmm1 register.
-/// This will always generate the move. It is used for verification.
+/// `MOVSX r64,r/m8` -> Move r/m8 with sign extention to r64, where m8 references a base + offset.
 #[inline(always)]
-fn raw_movsd_freg64_freg64(buf: &mut Vec<'_, u8>, dst: X86_64FloatReg, src: X86_64FloatReg) {
-    let dst_high = dst as u8 > 7;
-    let dst_mod = dst as u8 % 8;
-    let src_high = src as u8 > 7;
-    let src_mod = src as u8 % 8;
-    if dst_high || src_high {
-        buf.extend([
-            0xF2,
-            0x40 | ((dst_high as u8) << 2) | (src_high as u8),
-            0x0F,
-            0x10,
-            0xC0 | (dst_mod << 3) | (src_mod),
-        ])
-    } else {
-        buf.extend([0xF2, 0x0F, 0x10, 0xC0 | (dst_mod << 3) | (src_mod)])
-    }
+fn movsx_reg64_base8_offset32(
+    buf: &mut Vec<'_, u8>,
+    dst: X86_64GeneralReg,
+    base: X86_64GeneralReg,
+    offset: i32,
+) {
+    extend_reg64_base_offset32(buf, &[0x0F, 0xBE], dst, base, offset)
 }
 
-/// `MOVSS xmm1,xmm2` -> Move scalar low single-precision floating-point value from xmm2 to xmm1 register.
-/// This will not generate anything if dst and src are the same.
+/// `MOVSX r64,r/m16` -> Move r/m16 with sign extention to r64, where m16 references a base + offset.
 #[inline(always)]
-fn movss_freg32_freg32(buf: &mut Vec<'_, u8>, dst: X86_64FloatReg, src: X86_64FloatReg) {
-    if dst != src {
-        raw_movss_freg32_freg32(buf, dst, src);
-    }
+fn movsx_reg64_base16_offset32(
+    buf: &mut Vec<'_, u8>,
+    dst: X86_64GeneralReg,
+    base: X86_64GeneralReg,
+    offset: i32,
+) {
+    extend_reg64_base_offset32(buf, &[0x0F, 0xBF], dst, base, offset)
 }
 
-/// `MOVSS xmm1,xmm2` -> Move scalar low single-precision floating-point from xmm2 to xmm1 register.
-/// This will always generate the move. It is used for verification.
+/// `MOVSXD r64,r/m32` -> Move r/m32 with sign extention to r64, where m32 references a base + offset.
 #[inline(always)]
-fn raw_movss_freg32_freg32(buf: &mut Vec<'_, u8>, dst: X86_64FloatReg, src: X86_64FloatReg) {
-    let dst_high = dst as u8 > 7;
-    let dst_mod = dst as u8 % 8;
-    let src_high = src as u8 > 7;
-    let src_mod = src as u8 % 8;
-    if dst_high || src_high {
-        buf.extend([
-            0xF3,
-            0x40 | ((dst_high as u8) << 2) | (src_high as u8),
-            0x0F,
-            0x10,
-            0xC0 | (dst_mod << 3) | (src_mod),
-        ])
-    } else {
-        buf.extend([0xF3, 0x0F, 0x10, 0xC0 | (dst_mod << 3) | (src_mod)])
+fn movsxd_reg64_base32_offset32(
+    buf: &mut Vec<'_, u8>,
+    dst: X86_64GeneralReg,
+    base: X86_64GeneralReg,
+    offset: i32,
+) {
+    extend_reg64_base_offset32(buf, &[0x63], dst, base, offset)
+}
+
+/// `MOVZX r64,r/m16` -> Move r/m16 with zero extention to r64, where m16 references a base + offset.
+#[inline(always)]
+fn movzx_reg64_base16_offset32(
+    buf: &mut Vec<'_, u8>,
+    dst: X86_64GeneralReg,
+    base: X86_64GeneralReg,
+    offset: i32,
+) {
+    extend_reg64_base_offset32(buf, &[0x0F, 0xB7], dst, base, offset)
+}
+
+/// `MOV r32,r/m32` -> Move r/m32 to r32, zeroing the upper 32 bits, where m32 references a base + offset.
+#[inline(always)]
+fn mov_reg32_base32_offset32(
+    buf: &mut Vec<'_, u8>,
+    dst: X86_64GeneralReg,
+    base: X86_64GeneralReg,
+    offset: i32,
+) {
+    let rex = RexBuilder::new().rm(base).reg(dst).byte_if_needed();
+    let dst_mod = (dst as u8 % 8) << 3;
+    let base_mod = base as u8 % 8;
+    buf.reserve(8);
+    if let Some(rex) = rex {
+        buf.push(rex);
+    }
+    buf.extend([0x8B, 0x80 | dst_mod | base_mod]);
+    // Using RSP or R12 requires a secondary index byte.
+    if base == X86_64GeneralReg::RSP || base == X86_64GeneralReg::R12 {
+        buf.push(0x24);
+    }
+    buf.extend(offset.to_le_bytes());
+}
+
+/// `MOV r/m32,r32` -> Move r32 to r/m32, where m32 references a base + offset.
+/// `MOV r/m16,r16` when `operand_size_prefix` is set.
+#[inline(always)]
+fn mov_base_offset32_reg(
+    buf: &mut Vec<'_, u8>,
+    operand_size_prefix: bool,
+    base: X86_64GeneralReg,
+    offset: i32,
+    src: X86_64GeneralReg,
+) {
+    let rex = RexBuilder::new().rm(base).reg(src).byte_if_needed();
+    let src_mod = (src as u8 % 8) << 3;
+    let base_mod = base as u8 % 8;
+    buf.reserve(9);
+    if operand_size_prefix {
+        buf.push(0x66);
+    }
+    if let Some(rex) = rex {
+        buf.push(rex);
+    }
+    buf.extend([0x89, 0x80 | src_mod | base_mod]);
+    // Using RSP or R12 requires a secondary index byte.
+    if base == X86_64GeneralReg::RSP || base == X86_64GeneralReg::R12 {
+        buf.push(0x24);
+    }
+    buf.extend(offset.to_le_bytes());
+}
+
+/// `MOV r/m32,r32` -> Move r32 to r/m32, where m32 references a base + offset.
+#[inline(always)]
+fn mov_base32_offset32_reg32(
+    buf: &mut Vec<'_, u8>,
+    base: X86_64GeneralReg,
+    offset: i32,
+    src: X86_64GeneralReg,
+) {
+    mov_base_offset32_reg(buf, false, base, offset, src)
+}
+
+/// `MOV r/m16,r16` -> Move r16 to r/m16, where m16 references a base + offset.
+#[inline(always)]
+fn mov_base16_offset32_reg16(
+    buf: &mut Vec<'_, u8>,
+    base: X86_64GeneralReg,
+    offset: i32,
+    src: X86_64GeneralReg,
+) {
+    mov_base_offset32_reg(buf, true, base, offset, src)
+}
+
+/// `MOV r/m8,r8` -> Move r8 to r/m8, where m8 references a base + offset.
+#[inline(always)]
+fn mov_base8_offset32_reg8(
+    buf: &mut Vec<'_, u8>,
+    base: X86_64GeneralReg,
+    offset: i32,
+    src: X86_64GeneralReg,
+) {
+    // Without a REX prefix, 4 to 7 would be AH, CH, DH and BH instead of SPL, BPL, SIL and DIL.
+    let rex = RexBuilder::new().rm(base).reg(src).byte();
+    let src_mod = (src as u8 % 8) << 3;
+    let base_mod = base as u8 % 8;
+    buf.reserve(8);
+    buf.extend([rex, 0x88, 0x80 | src_mod | base_mod]);
+    // Using RSP or R12 requires a secondary index byte.
+    if base == X86_64GeneralReg::RSP || base == X86_64GeneralReg::R12 {
+        buf.push(0x24);
+    }
+    buf.extend(offset.to_le_bytes());
+}
+
+/// An instruction that extends r/m to r64.
+#[inline(always)]
+fn extend_reg64_reg(
+    buf: &mut Vec<'_, u8>,
+    opcode: &[u8],
+    dst: X86_64GeneralReg,
+    src: X86_64GeneralReg,
+) {
+    let rex = RexBuilder::wide().rm(src).reg(dst).byte();
+    let dst_mod = (dst as u8 % 8) << 3;
+    let src_mod = src as u8 % 8;
+    buf.reserve(4);
+    buf.push(rex);
+    buf.extend(opcode);
+    buf.push(0xC0 | dst_mod | src_mod);
+}
+
+/// `MOVSX r64,r/m8` -> Move r/m8 with sign extention to r64.
+#[inline(always)]
+fn movsx_reg64_reg8(buf: &mut Vec<'_, u8>, dst: X86_64GeneralReg, src: X86_64GeneralReg) {
+    extend_reg64_reg(buf, &[0x0F, 0xBE], dst, src)
+}
+
+/// `MOVSX r64,r/m16` -> Move r/m16 with sign extention to r64.
+#[inline(always)]
+fn movsx_reg64_reg16(buf: &mut Vec<'_, u8>, dst: X86_64GeneralReg, src: X86_64GeneralReg) {
+    extend_reg64_reg(buf, &[0x0F, 0xBF], dst, src)
+}
+
+/// `MOVSXD r64,r/m32` -> Move r/m32 with sign extention to r64.
+#[inline(always)]
+fn movsxd_reg64_reg32(buf: &mut Vec<'_, u8>, dst: X86_64GeneralReg, src: X86_64GeneralReg) {
+    extend_reg64_reg(buf, &[0x63], dst, src)
+}
+
+/// `MOVZX r64,r/m8` -> Move r/m8 with zero extention to r64.
+#[inline(always)]
+fn movzx_reg64_reg8(buf: &mut Vec<'_, u8>, dst: X86_64GeneralReg, src: X86_64GeneralReg) {
+    extend_reg64_reg(buf, &[0x0F, 0xB6], dst, src)
+}
+
+/// `MOVZX r64,r/m16` -> Move r/m16 with zero extention to r64.
+#[inline(always)]
+fn movzx_reg64_reg16(buf: &mut Vec<'_, u8>, dst: X86_64GeneralReg, src: X86_64GeneralReg) {
+    extend_reg64_reg(buf, &[0x0F, 0xB7], dst, src)
+}
+
+/// `MOV r32,r/m32` -> Move r/m32 to r32, zeroing the upper 32 bits.
+#[inline(always)]
+fn mov_reg32_reg32(buf: &mut Vec<'_, u8>, dst: X86_64GeneralReg, src: X86_64GeneralReg) {
+    let rex = RexBuilder::new().rm(src).reg(dst).byte_if_needed();
+    let dst_mod = (dst as u8 % 8) << 3;
+    let src_mod = src as u8 % 8;
+
+    if let Some(rex) = rex {
+        buf.push(rex);
+    }
+    buf.extend([0x8B, 0xC0 | dst_mod | src_mod]);
+}
+
+/// `MOVSD xmm1,xmm2` -> Move scalar double-precision floating-point value from xmm2 to xmm1 register.
+/// This will not generate anything if dst and src are the same.
+#[inline(always)]
+fn movsd_freg64_freg64(buf: &mut Vec<'_, u8>, dst: X86_64FloatReg, src: X86_64FloatReg) {
+    if dst != src {
+        raw_movsd_freg64_freg64(buf, dst, src);
+    }
+}
+
+/// `MOVSD xmm1,xmm2` -> Move scalar double-precision floating-point value from xmm2 to xmm1 register.
+/// This will always generate the move. It is used for verification.
+#[inline(always)]
+fn raw_movsd_freg64_freg64(buf: &mut Vec<'_, u8>, dst: X86_64FloatReg, src: X86_64FloatReg) {
+    let dst_high = dst as u8 > 7;
+    let dst_mod = dst as u8 % 8;
+    let src_high = src as u8 > 7;
+    let src_mod = src as u8 % 8;
+    if dst_high || src_high {
+        buf.extend([
+            0xF2,
+            0x40 | ((dst_high as u8) << 2) | (src_high as u8),
+            0x0F,
+            0x10,
+            0xC0 | (dst_mod << 3) | (src_mod),
+        ])
+    } else {
+        buf.extend([0xF2, 0x0F, 0x10, 0xC0 | (dst_mod << 3) | (src_mod)])
+    }
+}
+
+/// `MOVSS xmm1,xmm2` -> Move scalar low single-precision floating-point value from xmm2 to xmm1 register.
+/// This will not generate anything if dst and src are the same.
+#[inline(always)]
+fn movss_freg32_freg32(buf: &mut Vec<'_, u8>, dst: X86_64FloatReg, src: X86_64FloatReg) {
+    if dst != src {
+        raw_movss_freg32_freg32(buf, dst, src);
+    }
+}
+
+/// `MOVSS xmm1,xmm2` -> Move scalar low single-precision floating-point from xmm2 to xmm1 register.
+/// This will always generate the move. It is used for verification.
+#[inline(always)]
+fn raw_movss_freg32_freg32(buf: &mut Vec<'_, u8>, dst: X86_64FloatReg, src: X86_64FloatReg) {
+    let dst_high = dst as u8 > 7;
+    let dst_mod = dst as u8 % 8;
+    let src_high = src as u8 > 7;
+    let src_mod = src as u8 % 8;
+    if dst_high || src_high {
+        buf.extend([
+            0xF3,
+            0x40 | ((dst_high as u8) << 2) | (src_high as u8),
+            0x0F,
+            0x10,
+            0xC0 | (dst_mod << 3) | (src_mod),
+        ])
+    } else {
+        buf.extend([0xF3, 0x0F, 0x10, 0xC0 | (dst_mod << 3) | (src_mod)])
     }
 }
 
@@ -2187,8 +3679,7 @@ fn movsd_base64_offset32_freg64(
     offset: i32,
     src: X86_64FloatReg,
 ) {
-    let rex = add_rm_extension(base, REX_W);
-    let rex = add_reg_extension(src, rex);
+    let rex = RexBuilder::wide().rm(base).reg(src).byte();
     let src_mod = (src as u8 % 8) << 3;
     let base_mod = base as u8 % 8;
     buf.reserve(10);
@@ -2212,8 +3703,7 @@ fn movsd_freg64_base64_offset32(
     base: X86_64GeneralReg,
     offset: i32,
 ) {
-    let rex = add_rm_extension(base, REX_W);
-    let rex = add_reg_extension(dst, rex);
+    let rex = RexBuilder::wide().rm(base).reg(dst).byte();
     let dst_mod = (dst as u8 % 8) << 3;
     let base_mod = base as u8 % 8;
     buf.reserve(10);
@@ -2229,10 +3719,79 @@ fn movsd_freg64_base64_offset32(
     buf.extend(offset.to_le_bytes());
 }
 
+/// `MOVAPS m128,xmm1` -> Move all of xmm1 to m128, which has to be 16-byte aligned.
+#[inline(always)]
+fn movaps_base64_offset32_freg(
+    buf: &mut Vec<'_, u8>,
+    base: X86_64GeneralReg,
+    offset: i32,
+    src: X86_64FloatReg,
+) {
+    let rex = RexBuilder::new().rm(base).reg(src).byte();
+    let src_mod = (src as u8 % 8) << 3;
+    let base_mod = base as u8 % 8;
+    buf.reserve(9);
+    if src as u8 > 7 || base as u8 > 7 {
+        buf.push(rex);
+    }
+    buf.extend([0x0F, 0x29, 0x80 | src_mod | base_mod]);
+    // Using RSP or R12 requires a secondary index byte.
+    if base == X86_64GeneralReg::RSP || base == X86_64GeneralReg::R12 {
+        buf.push(0x24);
+    }
+    buf.extend(offset.to_le_bytes());
+}
+
+/// `MOVAPS xmm1,m128` -> Move m128, which has to be 16-byte aligned, to all of xmm1.
+#[inline(always)]
+fn movaps_freg_base64_offset32(
+    buf: &mut Vec<'_, u8>,
+    dst: X86_64FloatReg,
+    base: X86_64GeneralReg,
+    offset: i32,
+) {
+    let rex = RexBuilder::new().rm(base).reg(dst).byte();
+    let dst_mod = (dst as u8 % 8) << 3;
+    let base_mod = base as u8 % 8;
+    buf.reserve(9);
+    if dst as u8 > 7 || base as u8 > 7 {
+        buf.push(rex);
+    }
+    buf.extend([0x0F, 0x28, 0x80 | dst_mod | base_mod]);
+    // Using RSP or R12 requires a secondary index byte.
+    if base == X86_64GeneralReg::RSP || base == X86_64GeneralReg::R12 {
+        buf.push(0x24);
+    }
+    buf.extend(offset.to_le_bytes());
+}
+
+/// `NOP` -> `size` bytes of no operation, in the longest nops that Intel recommends.
+#[inline(always)]
+fn nops(buf: &mut Vec<'_, u8>, size: usize) {
+    const NOPS: [&[u8]; 9] = [
+        &[0x90],
+        &[0x66, 0x90],
+        &[0x0F, 0x1F, 0x00],
+        &[0x0F, 0x1F, 0x40, 0x00],
+        &[0x0F, 0x1F, 0x44, 0x00, 0x00],
+        &[0x66, 0x0F, 0x1F, 0x44, 0x00, 0x00],
+        &[0x0F, 0x1F, 0x80, 0x00, 0x00, 0x00, 0x00],
+        &[0x0F, 0x1F, 0x84, 0x00, 0x00, 0x00, 0x00, 0x00],
+        &[0x66, 0x0F, 0x1F, 0x84, 0x00, 0x00, 0x00, 0x00, 0x00],
+    ];
+
+    let mut left = size;
+    while left > 0 {
+        let nop = NOPS[left.min(NOPS.len()) - 1];
+        buf.extend_from_slice(nop);
+        left -= nop.len();
+    }
+}
+
 /// `NEG r/m64` -> Two's complement negate r/m64.
 #[inline(always)]
 fn neg_reg64(buf: &mut Vec<'_, u8>, reg: X86_64GeneralReg) {
-    let rex = add_rm_extension(reg, REX_W);
+    let rex = RexBuilder::wide().rm(reg).byte();
     let reg_mod = reg as u8 % 8;
     buf.extend([rex, 0xF7, 0xD8 | reg_mod]);
 }
@@ -2267,8 +3826,7 @@ fn cvtsi2_help<T: RegTrait, U: RegTrait>(
     dst: T,
     src: U,
 ) {
-    let rex = add_rm_extension(src, REX_W);
-    let rex = add_reg_extension(dst, rex);
+    let rex = RexBuilder::wide().rm(src).reg(dst).byte();
     let mod1 = (dst.value() % 8) << 3;
     let mod2 = src.value() % 8;
 
@@ -2375,6 +3933,12 @@ fn seto_reg64(buf: &mut Vec<'_, u8>, reg: X86_64GeneralReg) {
     set_reg64_help(0x90, buf, reg);
 }
 
+/// `REP STOSB` -> Fill RCX bytes at RDI with AL.
+#[inline(always)]
+fn rep_stosb(buf: &mut Vec<'_, u8>) {
+    buf.extend([0xF3, 0xAA]);
+}
+
 /// `RET` -> Near return to calling procedure.
 #[inline(always)]
 fn ret(buf: &mut Vec<'_, u8>) {
@@ -2385,7 +3949,7 @@ fn ret(buf: &mut Vec<'_, u8>) {
 #[inline(always)]
 fn sub_reg64_imm32(buf: &mut Vec<'_, u8>, dst: X86_64GeneralReg, imm: i32) {
     // This can be optimized if the immediate is 1 byte.
-    let rex = add_rm_extension(dst, REX_W);
+    let rex = RexBuilder::wide().rm(dst).byte();
     let dst_mod = dst as u8 % 8;
     buf.reserve(7);
     buf.extend([rex, 0x81, 0xE8 | dst_mod]);
@@ -2403,7 +3967,7 @@ fn sub_reg64_reg64(buf: &mut Vec<'_, u8>, dst: X86_64GeneralReg, src: X86_64Gene
 fn pop_reg64(buf: &mut Vec<'_, u8>, reg: X86_64GeneralReg) {
     let reg_mod = reg as u8 % 8;
     if reg as u8 > 7 {
-        let rex = add_opcode_extension(reg, REX);
+        let rex = RexBuilder::new().opcode(reg).byte();
         buf.extend([rex, 0x58 | reg_mod]);
     } else {
         buf.push(0x58 | reg_mod);
@@ -2415,7 +3979,7 @@ fn pop_reg64(buf: &mut Vec<'_, u8>, reg: X86_64GeneralReg) {
 fn push_reg64(buf: &mut Vec<'_, u8>, reg: X86_64GeneralReg) {
     let reg_mod = reg as u8 % 8;
     if reg as u8 > 7 {
-        let rex = add_opcode_extension(reg, REX);
+        let rex = RexBuilder::new().opcode(reg).byte();
         buf.extend([rex, 0x50 | reg_mod]);
     } else {
         buf.push(0x50 | reg_mod);
@@ -2430,29 +3994,8 @@ mod tests {
     use crate::disassembler_test;
     use capstone::prelude::*;
 
-    impl X86_64GeneralReg {
-        #[allow(dead_code)]
-        fn low_8bits_string(&self) -> &str {
-            match self {
-                X86_64GeneralReg::RAX => "al",
-                X86_64GeneralReg::RBX => "bl",
-                X86_64GeneralReg::RCX => "cl",
-                X86_64GeneralReg::RDX => "dl",
-                X86_64GeneralReg::RBP => "bpl",
-                X86_64GeneralReg::RSP => "spl",
-                X86_64GeneralReg::RDI => "dil",
-                X86_64GeneralReg::RSI => "sil",
-                X86_64GeneralReg::R8 => "r8b",
-                X86_64GeneralReg::R9 => "r9b",
-                X86_64GeneralReg::R10 => "r10b",
-                X86_64GeneralReg::R11 => "r11b",
-                X86_64GeneralReg::R12 => "r12b",
-                X86_64GeneralReg::R13 => "r13b",
-                X86_64GeneralReg::R14 => "r14b",
-                X86_64GeneralReg::R15 => "r15b",
-            }
-        }
-    }
+    const TEST_I8: i8 = 0x12;
+    const TEST_I16: i16 = 0x1234;
     const TEST_I32: i32 = 0x12345678;
     const TEST_I64: i64 = 0x1234_5678_9ABC_DEF0;
 
@@ -2507,6 +4050,18 @@ mod tests {
         (buf, cs)
     }
 
+    fn disassemble_lines(cs: &Capstone, buf: &[u8]) -> std::vec::Vec<String> {
+        cs.disasm_all(buf, 0)
+            .expect("Failed to disassemble")
+            .iter()
+            .map(|inst| {
+                format!("{} {}", inst.mnemonic().unwrap(), inst.op_str().unwrap())
+                    .trim_end()
+                    .to_string()
+            })
+            .collect()
+    }
+
     #[test]
     fn test_add_reg64_imm32() {
         disassembler_test!(
@@ -2598,71 +4153,220 @@ mod tests {
     }
 
     #[test]
-    fn test_shl_reg64_reg64() {
+    fn test_xor_reg32_reg32() {
         disassembler_test!(
-            shl_reg64_reg64,
-            |reg| format!("shl {reg}, cl"),
+            xor_reg32_reg32,
+            |reg1: X86_64GeneralReg, reg2: X86_64GeneralReg| format!(
+                "xor {}, {}",
+                reg1.name_for_width(RegisterWidth::W32),
+                reg2.name_for_width(RegisterWidth::W32)
+            ),
+            ALL_GENERAL_REGS,
             ALL_GENERAL_REGS
         );
     }
 
     #[test]
-    fn test_shr_reg64_reg64() {
+    fn test_shl_reg64_reg64() {
         disassembler_test!(
-            shr_reg64_reg64,
-            |reg| format!("shr {reg}, cl"),
+            shl_reg64_reg64,
+            |reg| format!("shl {reg}, cl"),
             ALL_GENERAL_REGS
         );
     }
 
     #[test]
-    fn test_sar_reg64_reg64() {
+    fn test_shl_reg64_imm8() {
         disassembler_test!(
-            sar_reg64_reg64,
-            |reg| format!("sar {reg}, cl"),
-            ALL_GENERAL_REGS
+            shl_reg64_imm8,
+            |reg, imm| format!("shl {reg}, 0x{:x}", imm),
+            ALL_GENERAL_REGS,
+            [0x12]
         );
     }
 
     #[test]
-    fn test_cmovl_reg64_reg64() {
+    fn test_shr_reg64_imm8() {
         disassembler_test!(
-            cmovl_reg64_reg64,
-            |reg1, reg2| format!("cmovl {}, {}", reg1, reg2),
+            shr_reg64_imm8,
+            |reg, imm| format!("shr {reg}, 0x{:x}", imm),
             ALL_GENERAL_REGS,
-            ALL_GENERAL_REGS
+            [0x12]
         );
     }
 
     #[test]
-    fn test_cmp_reg64_imm32() {
+    fn test_sar_reg64_imm8() {
         disassembler_test!(
-            cmp_reg64_imm32,
-            |reg, imm| format!("cmp {}, 0x{:x}", reg, imm),
+            sar_reg64_imm8,
+            |reg, imm| format!("sar {reg}, 0x{:x}", imm),
             ALL_GENERAL_REGS,
-            [TEST_I32]
+            [0x12]
         );
     }
 
     #[test]
-    fn test_imul_reg64_reg64() {
+    fn test_lea_reg64_base64_index64() {
+        let index_regs: std::vec::Vec<_> = ALL_GENERAL_REGS
+            .iter()
+            .copied()
+            .filter(|reg| *reg != X86_64GeneralReg::RSP)
+            .collect();
+
         disassembler_test!(
-            imul_reg64_reg64,
-            |reg1, reg2| format!("imul {}, {}", reg1, reg2),
+            |buf: &mut Vec<'_, u8>, dst, src, scale| lea_reg64_base64_index64(
+                buf, dst, src, src, scale
+            ),
+            |dst, src, scale| format!("lea {dst}, [{src} + {src}*{scale}]"),
             ALL_GENERAL_REGS,
-            ALL_GENERAL_REGS
+            index_regs,
+            [2, 4, 8]
         );
     }
 
     #[test]
-    fn test_mul_reg64_reg64() {
+    fn test_lea_reg64_base64_index64_unscaled() {
+        use X86_64GeneralReg::*;
+
+        // R12 as a base and R13 as an index take a SIB byte, and R13 as a base a displacement,
+        // like RSP and RBP do.
+        let operands = [RAX, RSP, RBP, R8, R12, R13, R15];
         disassembler_test!(
-            mul_reg64_reg64,
-            |reg| format!("mul {}", reg),
-            ALL_GENERAL_REGS
+            |buf: &mut Vec<'_, u8>, dst, base, index| {
+                if (base, index) != (RSP, RSP) {
+                    X86_64Assembler::lea_reg64_base64_index64(buf, dst, base, index)
+                }
+            },
+            |dst, base, index| match (base, index) {
+                (RSP, RSP) => String::new(),
+                (_, RSP) => format!("lea {dst}, [{index} + {base}]"),
+                _ => format!("lea {dst}, [{base} + {index}]"),
+            },
+            ALL_GENERAL_REGS,
+            operands,
+            operands
+        );
+    }
+
+    #[test]
+    fn test_lea_reg64_rip_offset32() {
+        disassembler_test!(
+            lea_reg64_rip_offset32,
+            |reg, imm| format!("lea {}, [rip + 0x{:x}]", reg, imm),
+            ALL_GENERAL_REGS,
+            [TEST_I32 as u32]
+        );
+    }
+
+    #[test]
+    fn test_mov_reg64_rip_offset32() {
+        disassembler_test!(
+            mov_reg64_rip_offset32,
+            |reg, imm| format!("mov {}, qword ptr [rip + 0x{:x}]", reg, imm),
+            ALL_GENERAL_REGS,
+            [TEST_I32 as u32]
         );
     }
 
+    #[test]
+    fn test_lea_reg64_base64_offset32() {
+        disassembler_test!(
+            lea_reg64_base64_offset32,
+            |dst, base, imm| format!("lea {}, [{} + 0x{:x}]", dst, base, imm),
+            ALL_GENERAL_REGS,
+            ALL_GENERAL_REGS,
+            [TEST_I32]
+        );
+    }
+
+    #[test]
+    fn test_shr_reg64_reg64() {
+        disassembler_test!(
+            shr_reg64_reg64,
+            |reg| format!("shr {reg}, cl"),
+            ALL_GENERAL_REGS
+        );
+    }
+
+    #[test]
+    fn test_sar_reg64_reg64() {
+        disassembler_test!(
+            sar_reg64_reg64,
+            |reg| format!("sar {reg}, cl"),
+            ALL_GENERAL_REGS
+        );
+    }
+
+    #[test]
+    fn test_cmovl_reg64_reg64() {
+        disassembler_test!(
+            cmovl_reg64_reg64,
+            |reg1, reg2| format!("cmovl {}, {}", reg1, reg2),
+            ALL_GENERAL_REGS,
+            ALL_GENERAL_REGS
+        );
+    }
+
+    #[test]
+    fn test_cmp_reg64_imm32() {
+        disassembler_test!(
+            cmp_reg64_imm32,
+            |reg, imm| format!("cmp {}, 0x{:x}", reg, imm),
+            ALL_GENERAL_REGS,
+            [TEST_I32]
+        );
+    }
+
+    #[test]
+    fn test_imul_reg64_reg64() {
+        disassembler_test!(
+            imul_reg64_reg64,
+            |reg1, reg2| format!("imul {}, {}", reg1, reg2),
+            ALL_GENERAL_REGS,
+            ALL_GENERAL_REGS
+        );
+    }
+
+    #[test]
+    fn test_imul_reg64_reg64_imm32() {
+        disassembler_test!(
+            imul_reg64_reg64_imm32,
+            |reg1, reg2, imm| format!("imul {}, {}, 0x{:x}", reg1, reg2, imm),
+            ALL_GENERAL_REGS,
+            ALL_GENERAL_REGS,
+            [TEST_I32]
+        );
+    }
+
+    #[test]
+    fn test_mul_reg64() {
+        disassembler_test!(mul_reg64, |reg| format!("mul {}", reg), ALL_GENERAL_REGS);
+    }
+
+    #[test]
+    fn test_imul_reg64() {
+        disassembler_test!(imul_reg64, |reg| format!("imul {}", reg), ALL_GENERAL_REGS);
+
+        // the register is in ModRM.rm, and only REX.B extends it
+        let arena = bumpalo::Bump::new();
+        let mut buf = bumpalo::vec![in &arena];
+        for (assemble, rcx, r12) in [
+            (
+                imul_reg64 as fn(&mut Vec<'_, u8>, X86_64GeneralReg),
+                [0x48, 0xF7, 0xE9],
+                [0x49, 0xF7, 0xEC],
+            ),
+            (mul_reg64, [0x48, 0xF7, 0xE1], [0x49, 0xF7, 0xE4]),
+        ] {
+            buf.clear();
+            assemble(&mut buf, X86_64GeneralReg::RCX);
+            assert_eq!(buf, rcx);
+            buf.clear();
+            assemble(&mut buf, X86_64GeneralReg::R12);
+            assert_eq!(buf, r12);
+        }
+    }
+
     #[test]
     fn test_mulsd_freg64_freg64() {
         disassembler_test!(
@@ -2684,19 +4388,116 @@ mod tests {
     }
 
     #[test]
-    fn test_idiv_reg64_reg64() {
-        disassembler_test!(
-            idiv_reg64_reg64,
-            |reg| format!("cqo\nidiv {}", reg),
-            ALL_GENERAL_REGS
+    fn test_idiv_reg64() {
+        disassembler_test!(idiv_reg64, |reg| format!("idiv {}", reg), ALL_GENERAL_REGS);
+    }
+
+    #[test]
+    fn test_div_reg64() {
+        disassembler_test!(div_reg64, |reg| format!("div {}", reg), ALL_GENERAL_REGS);
+    }
+
+    #[test]
+    fn test_division_sets_up_the_upper_half() {
+        use X86_64GeneralReg::*;
+
+        let arena = bumpalo::Bump::new();
+        let (mut buf, cs) = setup_capstone_and_arena(&arena);
+
+        idiv_at_width(&mut buf, RegisterWidth::W64, RCX, R15);
+        assert_eq!(
+            disassemble_lines(&cs, &buf),
+            ["mov rax, rcx", "cqo", "idiv r15"]
+        );
+
+        // a dividend with the top bit set is not negative
+        buf.clear();
+        div_at_width(&mut buf, RegisterWidth::W64, RCX, R15);
+        assert_eq!(
+            disassemble_lines(&cs, &buf),
+            ["mov rax, rcx", "xor edx, edx", "div r15"]
+        );
+    }
+
+    #[test]
+    fn test_cqo_cdq_cwd_cbw() {
+        disassembler_test!(cqo, || "cqo");
+        disassembler_test!(cdq, || "cdq");
+        disassembler_test!(cwd, || "cwd");
+        disassembler_test!(cbw, || "cbw");
+    }
+
+    #[test]
+    fn test_rdtsc_lfence() {
+        disassembler_test!(rdtsc, || "rdtsc");
+        disassembler_test!(lfence, || "lfence");
+
+        let arena = bumpalo::Bump::new();
+        let mut buf = bumpalo::vec![in &arena];
+        rdtsc(&mut buf);
+        assert_eq!(buf, [0x0F, 0x31]);
+        buf.clear();
+        lfence(&mut buf);
+        assert_eq!(buf, [0x0F, 0xAE, 0xE8]);
+    }
+
+    #[test]
+    fn test_read_timestamp_counter() {
+        use X86_64GeneralReg::*;
+
+        let arena = bumpalo::Bump::new();
+        let (mut buf, cs) = setup_capstone_and_arena(&arena);
+
+        X86_64Assembler::read_timestamp_counter(&mut buf, RCX, false);
+        assert_eq!(
+            disassemble_lines(&cs, &buf),
+            ["rdtsc", "shl rdx, 0x20", "or rax, rdx", "mov rcx, rax"]
+        );
+
+        buf.clear();
+        X86_64Assembler::read_timestamp_counter(&mut buf, R12, true);
+        assert_eq!(
+            disassemble_lines(&cs, &buf),
+            [
+                "lfence",
+                "rdtsc",
+                "shl rdx, 0x20",
+                "or rax, rdx",
+                "mov r12, rax"
+            ]
         );
     }
 
     #[test]
-    fn test_div_reg64_reg64() {
+    fn test_narrow_divisions() {
+        disassembler_test!(
+            idiv_reg32,
+            |reg: X86_64GeneralReg| format!("idiv {}", reg.name_for_width(RegisterWidth::W32)),
+            ALL_GENERAL_REGS
+        );
+        disassembler_test!(
+            div_reg32,
+            |reg: X86_64GeneralReg| format!("div {}", reg.name_for_width(RegisterWidth::W32)),
+            ALL_GENERAL_REGS
+        );
+        disassembler_test!(
+            idiv_reg16,
+            |reg: X86_64GeneralReg| format!("idiv {}", reg.name_for_width(RegisterWidth::W16)),
+            ALL_GENERAL_REGS
+        );
+        disassembler_test!(
+            div_reg16,
+            |reg: X86_64GeneralReg| format!("div {}", reg.name_for_width(RegisterWidth::W16)),
+            ALL_GENERAL_REGS
+        );
+        disassembler_test!(
+            idiv_reg8,
+            |reg: X86_64GeneralReg| format!("idiv {}", reg.name_for_width(RegisterWidth::W8)),
+            ALL_GENERAL_REGS
+        );
         disassembler_test!(
-            udiv_reg64_reg64,
-            |reg| format!("cqo\ndiv {}", reg),
+            div_reg8,
+            |reg: X86_64GeneralReg| format!("div {}", reg.name_for_width(RegisterWidth::W8)),
             ALL_GENERAL_REGS
         );
     }
@@ -2730,105 +4531,476 @@ mod tests {
             [TEST_I32]
         );
     }
-
+
+    #[test]
+    fn test_jmp_imm8() {
+        const INST_SIZE: i32 = 2;
+        disassembler_test!(
+            jmp_imm8,
+            |imm: i8| format!("jmp 0x{:x}", imm as i32 + INST_SIZE),
+            [0x12]
+        );
+    }
+
+    #[test]
+    fn test_jge_imm8() {
+        const INST_SIZE: i32 = 2;
+        disassembler_test!(
+            jge_imm8,
+            |imm: i8| format!("jge 0x{:x}", imm as i32 + INST_SIZE),
+            [0x12]
+        );
+    }
+
+    #[test]
+    fn test_jne_imm8() {
+        const INST_SIZE: i32 = 2;
+        disassembler_test!(
+            jne_imm8,
+            |imm: i8| format!("jne 0x{:x}", imm as i32 + INST_SIZE),
+            [0x12]
+        );
+    }
+
+    #[test]
+    fn test_jne_imm32() {
+        const INST_SIZE: i32 = 6;
+        disassembler_test!(
+            jne_imm32,
+            |imm| format!("jne 0x{:x}", imm + INST_SIZE),
+            [TEST_I32]
+        );
+    }
+
+    #[test]
+    fn test_mov_reg64_imm32() {
+        disassembler_test!(
+            mov_reg64_imm32,
+            |reg, imm| format!("mov {}, 0x{:x}", reg, imm),
+            ALL_GENERAL_REGS,
+            [TEST_I32]
+        );
+    }
+
+    #[test]
+    fn test_mov_reg32_imm32() {
+        disassembler_test!(
+            mov_reg32_imm32,
+            |reg: X86_64GeneralReg, imm| format!(
+                "mov {}, 0x{:x}",
+                reg.name_for_width(RegisterWidth::W32),
+                imm
+            ),
+            ALL_GENERAL_REGS,
+            [TEST_I32 as u32, u32::MAX]
+        );
+    }
+
+    #[test]
+    fn test_load_constant() {
+        let arena = bumpalo::Bump::new();
+        let mut buf = bumpalo::vec![in &arena];
+
+        // (value, flags_live, encoded length for a low register, encoded length for r8-r15)
+        let cases = [
+            // xor r32, r32
+            (0, false, 2, 3),
+            // mov r32, imm32
+            (0, true, 5, 6),
+            (1, false, 5, 6),
+            (u32::MAX as i64, false, 5, 6),
+            // mov r64, imm32 (sign extended)
+            (-1, false, 7, 7),
+            (i32::MIN as i64, false, 7, 7),
+            // movabs r64, imm64
+            (i32::MIN as i64 - 1, false, 10, 10),
+            (u32::MAX as i64 + 1, false, 10, 10),
+            (i64::MAX, false, 10, 10),
+        ];
+
+        for (value, flags_live, low_len, high_len) in cases {
+            for (reg, expected_len) in [
+                (X86_64GeneralReg::RAX, low_len),
+                (X86_64GeneralReg::RDI, low_len),
+                (X86_64GeneralReg::R8, high_len),
+                (X86_64GeneralReg::R15, high_len),
+            ] {
+                buf.clear();
+                X86_64Assembler::load_constant(&mut buf, reg, value, flags_live);
+                assert_eq!(
+                    buf.len(),
+                    expected_len,
+                    "load_constant({:?}, {}, flags_live: {})",
+                    reg,
+                    value,
+                    flags_live
+                );
+            }
+        }
+
+        // with live flags, zero must not be loaded with xor
+        buf.clear();
+        X86_64Assembler::load_constant(&mut buf, X86_64GeneralReg::RAX, 0, true);
+        assert_eq!(&buf[..], &[0xB8, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_mov_reg64_imm64() {
+        disassembler_test!(
+            mov_reg64_imm64,
+            |reg, imm| format!("movabs {}, 0x{:x}", reg, imm),
+            ALL_GENERAL_REGS,
+            [TEST_I64]
+        );
+        disassembler_test!(
+            mov_reg64_imm64,
+            |reg, imm| format!("mov {}, 0x{:x}", reg, imm),
+            ALL_GENERAL_REGS,
+            [TEST_I32 as i64]
+        );
+    }
+
+    #[test]
+    fn test_mov_reg64_reg64() {
+        disassembler_test!(
+            raw_mov_reg64_reg64,
+            |reg1, reg2| format!("mov {}, {}", reg1, reg2),
+            ALL_GENERAL_REGS,
+            ALL_GENERAL_REGS
+        );
+    }
+
+    #[test]
+    fn test_movsd_freg64_base64_offset32() {
+        disassembler_test!(
+            movsd_freg64_base64_offset32,
+            |reg1, reg2, imm| format!("movsd {}, qword ptr [{} + 0x{:x}]", reg1, reg2, imm),
+            ALL_FLOAT_REGS,
+            ALL_GENERAL_REGS,
+            [TEST_I32]
+        );
+    }
+
+    #[test]
+    fn test_movsd_base64_offset32_freg64() {
+        disassembler_test!(
+            movsd_base64_offset32_freg64,
+            |reg1, imm, reg2| format!("movsd qword ptr [{} + 0x{:x}], {}", reg1, imm, reg2),
+            ALL_GENERAL_REGS,
+            [TEST_I32],
+            ALL_FLOAT_REGS
+        );
+    }
+
+    #[test]
+    fn test_nops() {
+        let arena = bumpalo::Bump::new();
+        let (mut buf, cs) = setup_capstone_and_arena(&arena);
+        for size in 0..=20 {
+            buf.clear();
+            nops(&mut buf, size);
+            assert_eq!(buf.len(), size);
+
+            let instructions = cs.disasm_all(&buf, 0).unwrap();
+            assert!(instructions
+                .iter()
+                .all(|instruction| instruction.mnemonic() == Some("nop")));
+            assert_eq!(
+                instructions
+                    .iter()
+                    .map(|instruction| instruction.bytes().len())
+                    .sum::<usize>(),
+                size
+            );
+            // the longest nops come first
+            assert_eq!(instructions.len(), (size + 8) / 9);
+        }
+    }
+
+    #[test]
+    fn test_movaps_freg_base64_offset32() {
+        disassembler_test!(
+            movaps_freg_base64_offset32,
+            |reg1, reg2, imm| format!("movaps {}, xmmword ptr [{} + 0x{:x}]", reg1, reg2, imm),
+            ALL_FLOAT_REGS,
+            ALL_GENERAL_REGS,
+            [TEST_I32]
+        );
+    }
+
+    #[test]
+    fn test_movaps_base64_offset32_freg() {
+        disassembler_test!(
+            movaps_base64_offset32_freg,
+            |reg1, imm, reg2| format!("movaps xmmword ptr [{} + 0x{:x}], {}", reg1, imm, reg2),
+            ALL_GENERAL_REGS,
+            [TEST_I32],
+            ALL_FLOAT_REGS
+        );
+    }
+
+    #[test]
+    fn test_mov_reg64_base64_offset32() {
+        disassembler_test!(
+            mov_reg64_base64_offset32,
+            |reg1, reg2, imm| format!("mov {}, qword ptr [{} + 0x{:x}]", reg1, reg2, imm),
+            ALL_GENERAL_REGS,
+            ALL_GENERAL_REGS,
+            [TEST_I32]
+        );
+    }
+
+    #[test]
+    fn test_mov_base64_offset32_reg64() {
+        disassembler_test!(
+            mov_base64_offset32_reg64,
+            |reg1, imm, reg2| format!("mov qword ptr [{} + 0x{:x}], {}", reg1, imm, reg2),
+            ALL_GENERAL_REGS,
+            [TEST_I32],
+            ALL_GENERAL_REGS
+        );
+    }
+
+    #[test]
+    fn test_mov_base64_offset32_imm32() {
+        disassembler_test!(
+            mov_base64_offset32_imm32,
+            |reg, offset, imm| format!("mov qword ptr [{} + 0x{:x}], 0x{:x}", reg, offset, imm),
+            ALL_GENERAL_REGS,
+            [TEST_I32],
+            [TEST_I32]
+        );
+    }
+
+    #[test]
+    fn test_mov_base32_offset32_imm32() {
+        disassembler_test!(
+            mov_base32_offset32_imm32,
+            |reg, offset, imm| format!("mov dword ptr [{} + 0x{:x}], 0x{:x}", reg, offset, imm),
+            ALL_GENERAL_REGS,
+            [TEST_I32],
+            [TEST_I32]
+        );
+    }
+
+    #[test]
+    fn test_mov_base16_offset32_imm16() {
+        disassembler_test!(
+            mov_base16_offset32_imm16,
+            |reg, offset, imm| format!("mov word ptr [{} + 0x{:x}], 0x{:x}", reg, offset, imm),
+            ALL_GENERAL_REGS,
+            [TEST_I32],
+            [TEST_I16]
+        );
+    }
+
+    #[test]
+    fn test_mov_base8_offset32_imm8() {
+        disassembler_test!(
+            mov_base8_offset32_imm8,
+            |reg, offset, imm| format!("mov byte ptr [{} + 0x{:x}], 0x{:x}", reg, offset, imm),
+            ALL_GENERAL_REGS,
+            [TEST_I32],
+            [TEST_I8]
+        );
+    }
+
+    #[test]
+    fn test_movzx_reg64_base8_offset32() {
+        disassembler_test!(
+            movzx_reg64_base8_offset32,
+            |reg1, reg2, imm| format!("movzx {}, byte ptr [{} + 0x{:x}]", reg1, reg2, imm),
+            ALL_GENERAL_REGS,
+            ALL_GENERAL_REGS,
+            [TEST_I32]
+        );
+    }
+
+    #[test]
+    fn test_movsx_reg64_base8_offset32() {
+        disassembler_test!(
+            movsx_reg64_base8_offset32,
+            |reg1, reg2, imm| format!("movsx {}, byte ptr [{} + 0x{:x}]", reg1, reg2, imm),
+            ALL_GENERAL_REGS,
+            ALL_GENERAL_REGS,
+            [TEST_I32]
+        );
+    }
+    #[test]
+    fn test_movsx_reg64_base16_offset32() {
+        disassembler_test!(
+            movsx_reg64_base16_offset32,
+            |reg1, reg2, imm| format!("movsx {}, word ptr [{} + 0x{:x}]", reg1, reg2, imm),
+            ALL_GENERAL_REGS,
+            ALL_GENERAL_REGS,
+            [TEST_I32]
+        );
+    }
+    #[test]
+    fn test_movsxd_reg64_base32_offset32() {
+        disassembler_test!(
+            movsxd_reg64_base32_offset32,
+            |reg1, reg2, imm| format!("movsxd {}, dword ptr [{} + 0x{:x}]", reg1, reg2, imm),
+            ALL_GENERAL_REGS,
+            ALL_GENERAL_REGS,
+            [TEST_I32]
+        );
+    }
+    #[test]
+    fn test_movzx_reg64_base16_offset32() {
+        disassembler_test!(
+            movzx_reg64_base16_offset32,
+            |reg1, reg2, imm| format!("movzx {}, word ptr [{} + 0x{:x}]", reg1, reg2, imm),
+            ALL_GENERAL_REGS,
+            ALL_GENERAL_REGS,
+            [TEST_I32]
+        );
+    }
     #[test]
-    fn test_jne_imm32() {
-        const INST_SIZE: i32 = 6;
+    fn test_mov_reg32_base32_offset32() {
         disassembler_test!(
-            jne_imm32,
-            |imm| format!("jne 0x{:x}", imm + INST_SIZE),
+            mov_reg32_base32_offset32,
+            |reg1: X86_64GeneralReg, reg2, imm| format!(
+                "mov {}, dword ptr [{} + 0x{:x}]",
+                reg1.name_for_width(RegisterWidth::W32),
+                reg2,
+                imm
+            ),
+            ALL_GENERAL_REGS,
+            ALL_GENERAL_REGS,
             [TEST_I32]
         );
     }
 
     #[test]
-    fn test_mov_reg64_imm32() {
+    fn test_mov_base32_offset32_reg32() {
         disassembler_test!(
-            mov_reg64_imm32,
-            |reg, imm| format!("mov {}, 0x{:x}", reg, imm),
+            mov_base32_offset32_reg32,
+            |reg1, imm, reg2: X86_64GeneralReg| format!(
+                "mov dword ptr [{} + 0x{:x}], {}",
+                reg1,
+                imm,
+                reg2.name_for_width(RegisterWidth::W32)
+            ),
             ALL_GENERAL_REGS,
-            [TEST_I32]
+            [TEST_I32],
+            ALL_GENERAL_REGS
         );
     }
 
     #[test]
-    fn test_mov_reg64_imm64() {
+    fn test_mov_base16_offset32_reg16() {
         disassembler_test!(
-            mov_reg64_imm64,
-            |reg, imm| format!("movabs {}, 0x{:x}", reg, imm),
+            mov_base16_offset32_reg16,
+            |reg1, imm, reg2: X86_64GeneralReg| format!(
+                "mov word ptr [{} + 0x{:x}], {}",
+                reg1,
+                imm,
+                reg2.name_for_width(RegisterWidth::W16)
+            ),
             ALL_GENERAL_REGS,
-            [TEST_I64]
+            [TEST_I32],
+            ALL_GENERAL_REGS
         );
+    }
+
+    #[test]
+    fn test_mov_base8_offset32_reg8() {
         disassembler_test!(
-            mov_reg64_imm64,
-            |reg, imm| format!("mov {}, 0x{:x}", reg, imm),
+            mov_base8_offset32_reg8,
+            |reg1, imm, reg2: X86_64GeneralReg| format!(
+                "mov byte ptr [{} + 0x{:x}], {}",
+                reg1,
+                imm,
+                reg2.name_for_width(RegisterWidth::W8)
+            ),
             ALL_GENERAL_REGS,
-            [TEST_I32 as i64]
+            [TEST_I32],
+            ALL_GENERAL_REGS
         );
     }
 
     #[test]
-    fn test_mov_reg64_reg64() {
+    fn test_movsx_reg64_reg8() {
         disassembler_test!(
-            raw_mov_reg64_reg64,
-            |reg1, reg2| format!("mov {}, {}", reg1, reg2),
+            movsx_reg64_reg8,
+            |reg1, reg2: X86_64GeneralReg| format!(
+                "movsx {}, {}",
+                reg1,
+                reg2.name_for_width(RegisterWidth::W8)
+            ),
             ALL_GENERAL_REGS,
             ALL_GENERAL_REGS
         );
     }
 
     #[test]
-    fn test_movsd_freg64_base64_offset32() {
+    fn test_movsx_reg64_reg16() {
         disassembler_test!(
-            movsd_freg64_base64_offset32,
-            |reg1, reg2, imm| format!("movsd {}, qword ptr [{} + 0x{:x}]", reg1, reg2, imm),
-            ALL_FLOAT_REGS,
+            movsx_reg64_reg16,
+            |reg1, reg2: X86_64GeneralReg| format!(
+                "movsx {}, {}",
+                reg1,
+                reg2.name_for_width(RegisterWidth::W16)
+            ),
             ALL_GENERAL_REGS,
-            [TEST_I32]
+            ALL_GENERAL_REGS
         );
     }
 
     #[test]
-    fn test_movsd_base64_offset32_freg64() {
+    fn test_movsxd_reg64_reg32() {
         disassembler_test!(
-            movsd_base64_offset32_freg64,
-            |reg1, imm, reg2| format!("movsd qword ptr [{} + 0x{:x}], {}", reg1, imm, reg2),
+            movsxd_reg64_reg32,
+            |reg1, reg2: X86_64GeneralReg| format!(
+                "movsxd {}, {}",
+                reg1,
+                reg2.name_for_width(RegisterWidth::W32)
+            ),
             ALL_GENERAL_REGS,
-            [TEST_I32],
-            ALL_FLOAT_REGS
+            ALL_GENERAL_REGS
         );
     }
 
     #[test]
-    fn test_mov_reg64_base64_offset32() {
+    fn test_movzx_reg64_reg8() {
         disassembler_test!(
-            mov_reg64_base64_offset32,
-            |reg1, reg2, imm| format!("mov {}, qword ptr [{} + 0x{:x}]", reg1, reg2, imm),
+            movzx_reg64_reg8,
+            |reg1, reg2: X86_64GeneralReg| format!(
+                "movzx {}, {}",
+                reg1,
+                reg2.name_for_width(RegisterWidth::W8)
+            ),
             ALL_GENERAL_REGS,
-            ALL_GENERAL_REGS,
-            [TEST_I32]
+            ALL_GENERAL_REGS
         );
     }
 
     #[test]
-    fn test_mov_base64_offset32_reg64() {
+    fn test_movzx_reg64_reg16() {
         disassembler_test!(
-            mov_base64_offset32_reg64,
-            |reg1, imm, reg2| format!("mov qword ptr [{} + 0x{:x}], {}", reg1, imm, reg2),
+            movzx_reg64_reg16,
+            |reg1, reg2: X86_64GeneralReg| format!(
+                "movzx {}, {}",
+                reg1,
+                reg2.name_for_width(RegisterWidth::W16)
+            ),
             ALL_GENERAL_REGS,
-            [TEST_I32],
             ALL_GENERAL_REGS
         );
     }
 
     #[test]
-    fn test_movzx_reg64_base8_offset32() {
+    fn test_mov_reg32_reg32() {
         disassembler_test!(
-            movzx_reg64_base8_offset32,
-            |reg1, reg2, imm| format!("movzx {}, byte ptr [{} + 0x{:x}]", reg1, reg2, imm),
+            mov_reg32_reg32,
+            |reg1: X86_64GeneralReg, reg2: X86_64GeneralReg| format!(
+                "mov {}, {}",
+                reg1.name_for_width(RegisterWidth::W32),
+                reg2.name_for_width(RegisterWidth::W32)
+            ),
             ALL_GENERAL_REGS,
-            ALL_GENERAL_REGS,
-            [TEST_I32]
+            ALL_GENERAL_REGS
         );
     }
 
@@ -2910,11 +5082,382 @@ mod tests {
     fn test_set_reg64_help() {
         disassembler_test!(
             |buf, reg| set_reg64_help(0x94, buf, reg),
-            |reg: X86_64GeneralReg| format!("sete {}\nand {}, 1", reg.low_8bits_string(), reg),
+            |reg: X86_64GeneralReg| format!(
+                "sete {}\nand {}, 1",
+                reg.name_for_width(RegisterWidth::W8),
+                reg
+            ),
             ALL_GENERAL_REGS
         );
     }
 
+    #[test]
+    fn test_test_reg_reg() {
+        for width in [
+            RegisterWidth::W8,
+            RegisterWidth::W16,
+            RegisterWidth::W32,
+            RegisterWidth::W64,
+        ] {
+            disassembler_test!(
+                |buf, reg| test_reg_reg(buf, width, reg),
+                |reg: X86_64GeneralReg| format!(
+                    "test {}, {}",
+                    reg.name_for_width(width),
+                    reg.name_for_width(width)
+                ),
+                ALL_GENERAL_REGS
+            );
+        }
+    }
+
+    #[test]
+    fn test_float_compares() {
+        disassembler_test!(
+            xorps_freg_freg,
+            |reg1, reg2| format!("xorps {}, {}", reg1, reg2),
+            ALL_FLOAT_REGS,
+            ALL_FLOAT_REGS
+        );
+        disassembler_test!(
+            |buf, reg1, reg2| ucomis_freg_freg(buf, FloatWidth::F64, reg1, reg2),
+            |reg1, reg2| format!("ucomisd {}, {}", reg1, reg2),
+            ALL_FLOAT_REGS,
+            ALL_FLOAT_REGS
+        );
+        disassembler_test!(
+            |buf, reg1, reg2| ucomis_freg_freg(buf, FloatWidth::F32, reg1, reg2),
+            |reg1, reg2| format!("ucomiss {}, {}", reg1, reg2),
+            ALL_FLOAT_REGS,
+            ALL_FLOAT_REGS
+        );
+        disassembler_test!(
+            cmpeqsd_freg_freg,
+            |reg1, reg2| format!("cmpeqsd {}, {}", reg1, reg2),
+            ALL_FLOAT_REGS,
+            ALL_FLOAT_REGS
+        );
+        disassembler_test!(
+            cmpeqss_freg_freg,
+            |reg1, reg2| format!("cmpeqss {}, {}", reg1, reg2),
+            ALL_FLOAT_REGS,
+            ALL_FLOAT_REGS
+        );
+        disassembler_test!(
+            movq_reg64_freg64,
+            |reg1, reg2| format!("movq {}, {}", reg1, reg2),
+            ALL_GENERAL_REGS,
+            ALL_FLOAT_REGS
+        );
+    }
+
+    #[test]
+    fn test_int_sign_tests_are_short() {
+        use X86_64GeneralReg::*;
+
+        let arena = bumpalo::Bump::new();
+        let mut buf = bumpalo::vec![in &arena];
+        // (condition, signed, the second opcode byte of the `setcc` that reads the flags)
+        let cases = [
+            (SignCondition::Zero, true, 0x94),
+            (SignCondition::Zero, false, 0x94),
+            (SignCondition::Positive, true, 0x9f),
+            (SignCondition::Positive, false, 0x95),
+            (SignCondition::Negative, true, 0x9c),
+        ];
+        for (condition, signed, set) in cases {
+            buf.clear();
+            X86_64Assembler::int_sign_test_reg_reg(
+                &mut buf,
+                RAX,
+                RCX,
+                RegisterWidth::W32,
+                signed,
+                condition,
+            );
+            // test ecx, ecx; setcc al; and rax, 1
+            assert_eq!(buf.len(), 2 + 3 + 4, "{:?}", condition);
+            assert_eq!(buf[3], set, "{:?}", condition);
+        }
+
+        // an unsigned integer is never negative, so there is nothing to test
+        buf.clear();
+        X86_64Assembler::int_sign_test_reg_reg(
+            &mut buf,
+            RAX,
+            RCX,
+            RegisterWidth::W32,
+            false,
+            SignCondition::Negative,
+        );
+        let mut zero = bumpalo::vec![in &arena];
+        X86_64Assembler::load_constant(&mut zero, RAX, 0, false);
+        assert_eq!(buf, zero);
+    }
+
+    #[test]
+    fn test_literals_follow_the_target_byte_order() {
+        use X86_64FloatReg::*;
+
+        let arena = bumpalo::Bump::new();
+        let emit = |endianness| {
+            let mut buf = bumpalo::vec![in &arena];
+            let mut relocs = bumpalo::vec![in &arena];
+            X86_64Assembler::mov_freg64_imm64(&mut buf, &mut relocs, XMM3, 1.5, endianness);
+            X86_64Assembler::mov_freg32_imm32(&mut buf, &mut relocs, XMM3, 1.5, endianness);
+            X86_64Assembler::abs_freg64_freg64(&mut buf, &mut relocs, XMM3, XMM4, endianness);
+            X86_64Assembler::mov_reg64_imm64(&mut buf, X86_64GeneralReg::RAX, 0x0102030405060708);
+
+            let data: std::vec::Vec<_> = relocs
+                .iter()
+                .map(|reloc| match reloc {
+                    Relocation::LocalData { data, .. } => data.clone(),
+                    x => panic!("expected a literal, found {:?}", x),
+                })
+                .collect();
+            (buf.to_vec(), data)
+        };
+
+        let (little_code, little_data) = emit(Endianness::Little);
+        let (big_code, big_data) = emit(Endianness::Big);
+
+        assert_eq!(little_data[0], 1.5f64.to_le_bytes());
+        assert_eq!(little_data[1], 1.5f32.to_le_bytes());
+        for (little, big) in little_data.iter().zip(big_data.iter()) {
+            let mut reversed = little.clone();
+            reversed.reverse();
+            assert_eq!(big, &reversed);
+        }
+
+        // x86 encodes the immediates in its instructions in little-endian either way
+        assert_eq!(little_code, big_code);
+    }
+
+    #[test]
+    fn test_operand_mov_matches_encoders() {
+        use crate::generic64::operand::{mov, Operand::*};
+        use RegisterWidth::*;
+
+        type Operand = crate::generic64::operand::Operand<X86_64GeneralReg, X86_64FloatReg>;
+        type Asm = X86_64Assembler;
+
+        let arena = bumpalo::Bump::new();
+        let mut expected = bumpalo::vec![in &arena];
+        let mut actual = bumpalo::vec![in &arena];
+
+        let mut check = |width: RegisterWidth,
+                         encode: &dyn Fn(&mut Vec<'_, u8>),
+                         dst: Operand,
+                         src: Operand| {
+            expected.clear();
+            actual.clear();
+            encode(&mut expected);
+            mov::<_, _, Asm>(&mut actual, width, dst, src);
+            assert_eq!(expected, actual, "mov {:?} {:?} <- {:?}", width, dst, src);
+        };
+
+        for &r1 in ALL_GENERAL_REGS {
+            for &r2 in ALL_GENERAL_REGS {
+                check(
+                    W64,
+                    &|buf| Asm::mov_reg64_reg64(buf, r1, r2),
+                    Reg(r1),
+                    Reg(r2),
+                );
+                check(
+                    W64,
+                    &|buf| Asm::mov_reg64_mem64_offset32(buf, r1, r2, TEST_I32),
+                    Reg(r1),
+                    Mem {
+                        base: r2,
+                        offset: TEST_I32,
+                    },
+                );
+                check(
+                    W64,
+                    &|buf| Asm::mov_mem64_offset32_reg64(buf, r1, TEST_I32, r2),
+                    Mem {
+                        base: r1,
+                        offset: TEST_I32,
+                    },
+                    Reg(r2),
+                );
+                check(
+                    W8,
+                    &|buf| Asm::mov_mem8_offset32_reg8(buf, r1, TEST_I32, r2),
+                    Mem {
+                        base: r1,
+                        offset: TEST_I32,
+                    },
+                    Reg(r2),
+                );
+            }
+
+            check(
+                W64,
+                &|buf| Asm::mov_reg64_imm64(buf, r1, TEST_I64),
+                Reg(r1),
+                Imm(TEST_I64),
+            );
+            check(
+                W64,
+                &|buf| Asm::mov_reg64_base32(buf, r1, TEST_I32),
+                Reg(r1),
+                Base(TEST_I32),
+            );
+            check(
+                W64,
+                &|buf| Asm::mov_reg64_stack32(buf, r1, TEST_I32),
+                Reg(r1),
+                Stack(TEST_I32),
+            );
+            check(
+                W64,
+                &|buf| Asm::mov_base32_reg64(buf, TEST_I32, r1),
+                Base(TEST_I32),
+                Reg(r1),
+            );
+            check(
+                W32,
+                &|buf| Asm::mov_base32_reg32(buf, TEST_I32, r1),
+                Base(TEST_I32),
+                Reg(r1),
+            );
+            check(
+                W16,
+                &|buf| Asm::mov_base32_reg16(buf, TEST_I32, r1),
+                Base(TEST_I32),
+                Reg(r1),
+            );
+            check(
+                W8,
+                &|buf| Asm::mov_base32_reg8(buf, TEST_I32, r1),
+                Base(TEST_I32),
+                Reg(r1),
+            );
+            check(
+                W64,
+                &|buf| Asm::mov_stack32_reg64(buf, TEST_I32, r1),
+                Stack(TEST_I32),
+                Reg(r1),
+            );
+        }
+
+        for width in [W8, W16, W32, W64] {
+            check(
+                width,
+                &|buf| Asm::mov_base32_imm(buf, TEST_I32, -1, width),
+                Base(TEST_I32),
+                Imm(-1),
+            );
+        }
+
+        for &f1 in ALL_FLOAT_REGS {
+            for &f2 in ALL_FLOAT_REGS {
+                check(
+                    W64,
+                    &|buf| Asm::mov_freg64_freg64(buf, f1, f2),
+                    FReg(f1),
+                    FReg(f2),
+                );
+            }
+
+            check(
+                W64,
+                &|buf| Asm::mov_freg64_base32(buf, f1, TEST_I32),
+                FReg(f1),
+                Base(TEST_I32),
+            );
+            check(
+                W64,
+                &|buf| Asm::mov_freg64_stack32(buf, f1, TEST_I32),
+                FReg(f1),
+                Stack(TEST_I32),
+            );
+            check(
+                W64,
+                &|buf| Asm::mov_base32_freg64(buf, TEST_I32, f1),
+                Base(TEST_I32),
+                FReg(f1),
+            );
+            check(
+                W64,
+                &|buf| Asm::mov_stack32_freg64(buf, TEST_I32, f1),
+                Stack(TEST_I32),
+                FReg(f1),
+            );
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "mov is not supported for W64 from Stack(8) to Base(16)")]
+    fn test_operand_mov_unsupported() {
+        use crate::generic64::operand::{mov, Operand};
+
+        let arena = bumpalo::Bump::new();
+        let mut buf = bumpalo::vec![in &arena];
+
+        mov::<X86_64GeneralReg, X86_64FloatReg, X86_64Assembler>(
+            &mut buf,
+            RegisterWidth::W64,
+            Operand::Base(16),
+            Operand::Stack(8),
+        );
+    }
+
+    #[test]
+    fn test_name_for_width() {
+        use RegisterWidth::*;
+
+        let cases = [
+            (X86_64GeneralReg::RAX, ["al", "ax", "eax", "rax"]),
+            (X86_64GeneralReg::RSP, ["spl", "sp", "esp", "rsp"]),
+            (X86_64GeneralReg::RBP, ["bpl", "bp", "ebp", "rbp"]),
+            (X86_64GeneralReg::RSI, ["sil", "si", "esi", "rsi"]),
+            (X86_64GeneralReg::RDI, ["dil", "di", "edi", "rdi"]),
+            (X86_64GeneralReg::R8, ["r8b", "r8w", "r8d", "r8"]),
+            (X86_64GeneralReg::R15, ["r15b", "r15w", "r15d", "r15"]),
+        ];
+
+        for (reg, names) in cases {
+            for (width, name) in [W8, W16, W32, W64].into_iter().zip(names) {
+                assert_eq!(reg.name_for_width(width), name);
+            }
+        }
+
+        // no two registers share a name at any width
+        for width in [W8, W16, W32, W64] {
+            let names: std::collections::HashSet<_> = ALL_GENERAL_REGS
+                .iter()
+                .map(|reg| reg.name_for_width(width))
+                .collect();
+            assert_eq!(names.len(), ALL_GENERAL_REGS.len(), "{:?}", width);
+        }
+    }
+
+    #[test]
+    fn test_parse_registers() {
+        for reg in ALL_GENERAL_REGS {
+            let name = reg.to_string();
+            assert_eq!(name.parse::<X86_64GeneralReg>(), Ok(*reg));
+            assert_eq!(name.to_uppercase().parse::<X86_64GeneralReg>(), Ok(*reg));
+        }
+
+        for reg in ALL_FLOAT_REGS {
+            let name = reg.to_string();
+            assert_eq!(name.parse::<X86_64FloatReg>(), Ok(*reg));
+            assert_eq!(name.to_uppercase().parse::<X86_64FloatReg>(), Ok(*reg));
+        }
+
+        assert!("eax".parse::<X86_64GeneralReg>().is_err());
+        assert!("xmm16".parse::<X86_64FloatReg>().is_err());
+    }
+
+    #[test]
+    fn test_rep_stosb() {
+        disassembler_test!(rep_stosb, || "rep stosb byte ptr [rdi], al");
+    }
+
     #[test]
     fn test_ret() {
         disassembler_test!(ret, || "ret");
@@ -2939,4 +5482,456 @@ mod tests {
     fn test_push_reg64() {
         disassembler_test!(push_reg64, |reg| format!("push {}", reg), ALL_GENERAL_REGS);
     }
+
+    #[test]
+    fn test_stats_spills_and_reloads() {
+        use crate::generic64::storage::new_storage_manager;
+        use roc_module::symbol::{Interns, ModuleId};
+
+        let arena = bumpalo::Bump::new();
+        let env = crate::Env::for_tests(&arena);
+        let mut storage_manager = new_storage_manager::<
+            X86_64GeneralReg,
+            X86_64FloatReg,
+            X86_64Assembler,
+            X86_64SystemV,
+        >(&env, TargetInfo::default_x86_64());
+        storage_manager.reset();
+        storage_manager.stats.reset("test");
+        let mut buf = bumpalo::vec![in &arena];
+
+        // one more simultaneously live value than there are registers to hold them
+        let reg_count = X86_64SystemV::GENERAL_DEFAULT_FREE_REGS.len() as u32;
+        let symbols: std::vec::Vec<_> = (0..=reg_count)
+            .map(|i| Interns::from_index(ModuleId::NUM, 1000 + i))
+            .collect();
+        for sym in symbols.iter() {
+            storage_manager.claim_general_reg(&mut buf, sym);
+        }
+
+        assert_eq!(storage_manager.stats.spills, 1);
+        assert_eq!(storage_manager.stats.reloads, 0);
+        assert_eq!(storage_manager.stats.general_regs_used, reg_count);
+        assert_eq!(storage_manager.stats.float_regs_used, 0);
+
+        // the oldest value was spilled; using it again reloads it and spills the next oldest
+        storage_manager.load_to_general_reg(&mut buf, &symbols[0]);
+
+        assert_eq!(storage_manager.stats.spills, 2);
+        assert_eq!(storage_manager.stats.reloads, 1);
+        assert_eq!(storage_manager.stats.general_regs_used, reg_count);
+    }
+
+    #[test]
+    fn test_with_reserved_general_regs() {
+        use crate::generic64::storage::new_storage_manager;
+        use roc_module::symbol::{Interns, ModuleId};
+
+        let arena = bumpalo::Bump::new();
+        let env = crate::Env::for_tests(&arena);
+        let mut storage_manager = new_storage_manager::<
+            X86_64GeneralReg,
+            X86_64FloatReg,
+            X86_64Assembler,
+            X86_64SystemV,
+        >(&env, TargetInfo::default_x86_64());
+        storage_manager.reset();
+        let mut buf = bumpalo::vec![in &arena];
+
+        let live = Interns::from_index(ModuleId::NUM, 1000);
+        let other = Interns::from_index(ModuleId::NUM, 1001);
+        storage_manager.general_reg_arg(&live, X86_64GeneralReg::RDX);
+
+        let reserved = [X86_64GeneralReg::RAX, X86_64GeneralReg::RDX];
+        storage_manager.with_reserved_general_regs(&mut buf, &reserved, |storage_manager, buf| {
+            // the live value was moved out of the way, so it is reloaded elsewhere
+            let reg = storage_manager.load_to_general_reg(buf, &live);
+            assert!(!reserved.contains(&reg));
+            let reg = storage_manager.claim_general_reg(buf, &other);
+            assert!(!reserved.contains(&reg));
+        });
+
+        // afterwards both registers can be handed out again
+        let free_count = X86_64SystemV::GENERAL_DEFAULT_FREE_REGS.len() as u32 - 2;
+        let mut claimed = std::vec::Vec::new();
+        for i in 0..free_count {
+            let sym = Interns::from_index(ModuleId::NUM, 2000 + i);
+            claimed.push(storage_manager.claim_general_reg(&mut buf, &sym));
+        }
+        assert!(claimed.contains(&X86_64GeneralReg::RAX));
+        assert!(claimed.contains(&X86_64GeneralReg::RDX));
+    }
+
+    #[test]
+    fn test_read_timestamp_counter_keeps_a_live_rdx() {
+        use crate::generic64::storage::new_storage_manager;
+        use roc_module::symbol::{Interns, ModuleId};
+
+        let arena = bumpalo::Bump::new();
+        let env = crate::Env::for_tests(&arena);
+        let mut storage_manager = new_storage_manager::<
+            X86_64GeneralReg,
+            X86_64FloatReg,
+            X86_64Assembler,
+            X86_64SystemV,
+        >(&env, TargetInfo::default_x86_64());
+        storage_manager.reset();
+        let (mut buf, cs) = setup_capstone_and_arena(&arena);
+
+        let live = Interns::from_index(ModuleId::NUM, 1000);
+        let ticks = Interns::from_index(ModuleId::NUM, 1001);
+        storage_manager.general_reg_arg(&live, X86_64GeneralReg::RDX);
+
+        storage_manager.with_reserved_general_regs(
+            &mut buf,
+            X86_64Assembler::TIMESTAMP_FIXED_REGS,
+            |storage_manager, buf| {
+                let dst_reg = storage_manager.claim_general_reg(buf, &ticks);
+                assert!(!X86_64Assembler::TIMESTAMP_FIXED_REGS.contains(&dst_reg));
+                X86_64Assembler::read_timestamp_counter(buf, dst_reg, false);
+            },
+        );
+        let reloaded = storage_manager.load_to_general_reg(&mut buf, &live);
+
+        let instructions = cs.disasm_all(&buf, 0).expect("Failed to disassemble");
+        let instructions: std::vec::Vec<_> = instructions
+            .iter()
+            .map(|inst| (inst.mnemonic().unwrap(), inst.op_str().unwrap().to_string()))
+            .collect();
+
+        // the live value goes to the stack before `rdtsc` overwrites RDX, and comes back from there
+        let (spill, rdtsc) = (&instructions[0], &instructions[1]);
+        assert_eq!(spill.0, "mov");
+        let (slot, spilled) = spill.1.split_once(", ").unwrap();
+        assert_eq!(spilled, "rdx");
+        assert_eq!(rdtsc.0, "rdtsc");
+
+        let reload = instructions.last().unwrap();
+        assert_eq!(reload.1, format!("{}, {}", reloaded, slot));
+    }
+
+    #[test]
+    fn test_runtime_context_reg_is_never_handed_out() {
+        use crate::generic64::storage::new_storage_manager;
+        use roc_module::symbol::{Interns, ModuleId};
+
+        let arena = bumpalo::Bump::new();
+        let env = crate::Env {
+            runtime_context: true,
+            ..crate::Env::for_tests(&arena)
+        };
+        let mut storage_manager = new_storage_manager::<
+            X86_64GeneralReg,
+            X86_64FloatReg,
+            X86_64Assembler,
+            X86_64SystemV,
+        >(&env, TargetInfo::default_x86_64());
+        storage_manager.reset();
+        let mut buf = bumpalo::vec![in &arena];
+
+        // one more value than there are registers, so the last one has to spill another
+        let free_count = X86_64SystemV::GENERAL_DEFAULT_FREE_REGS.len() as u32 - 1;
+        let mut claimed = std::vec::Vec::new();
+        for i in 0..=free_count {
+            let sym = Interns::from_index(ModuleId::NUM, 1000 + i);
+            claimed.push(storage_manager.claim_general_reg(&mut buf, &sym));
+        }
+        assert!(!claimed.contains(&X86_64SystemV::RUNTIME_CONTEXT_REG));
+        claimed.sort();
+        claimed.dedup();
+        assert_eq!(claimed.len() as u32, free_count);
+
+        // the procedure still saves the register for the host
+        assert!(!storage_manager
+            .general_used_callee_saved_regs()
+            .contains(&X86_64SystemV::RUNTIME_CONTEXT_REG));
+        storage_manager.claim_runtime_context_reg();
+        assert!(storage_manager
+            .general_used_callee_saved_regs()
+            .contains(&X86_64SystemV::RUNTIME_CONTEXT_REG));
+    }
+
+    #[test]
+    fn test_reg_alloc_orders() {
+        use crate::generic64::storage::general_free_regs_in_order;
+        use crate::RegAllocOrder;
+
+        let in_order = |order| {
+            general_free_regs_in_order::<
+                X86_64GeneralReg,
+                X86_64FloatReg,
+                X86_64Assembler,
+                X86_64SystemV,
+            >(order)
+        };
+        let default = X86_64SystemV::GENERAL_DEFAULT_FREE_REGS;
+
+        assert_eq!(in_order(None).unwrap(), default);
+
+        // the free list is popped from the back, so the last register is used first
+        let caller_saved_first = in_order(Some(RegAllocOrder::CallerSavedFirst)).unwrap();
+        assert_eq!(caller_saved_first.len(), default.len());
+        assert!(!X86_64SystemV::general_callee_saved(
+            caller_saved_first.last().unwrap()
+        ));
+        assert!(X86_64SystemV::general_callee_saved(&caller_saved_first[0]));
+
+        let callee_saved_first = in_order(Some(RegAllocOrder::CalleeSavedFirst)).unwrap();
+        assert_eq!(callee_saved_first.len(), default.len());
+        assert!(X86_64SystemV::general_callee_saved(
+            callee_saved_first.last().unwrap()
+        ));
+        assert!(!X86_64SystemV::general_callee_saved(&callee_saved_first[0]));
+
+        assert_eq!(
+            in_order(Some(RegAllocOrder::Custom(&["r12", "RAX", "rdi"]))).unwrap(),
+            [
+                X86_64GeneralReg::RDI,
+                X86_64GeneralReg::RAX,
+                X86_64GeneralReg::R12
+            ]
+        );
+
+        assert!(in_order(Some(RegAllocOrder::Custom(&[]))).is_err());
+        assert!(in_order(Some(RegAllocOrder::Custom(&["rax", "rsp"]))).is_err());
+        assert!(in_order(Some(RegAllocOrder::Custom(&["rbp"]))).is_err());
+        assert!(in_order(Some(RegAllocOrder::Custom(&["rax", "rax"]))).is_err());
+        assert!(in_order(Some(RegAllocOrder::Custom(&["xmm0"]))).is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "rsp is reserved for the stack frame")]
+    fn test_reg_alloc_order_rejects_stack_pointer() {
+        use crate::generic64::storage::new_storage_manager;
+        use crate::RegAllocOrder;
+
+        let arena = bumpalo::Bump::new();
+        let env = crate::Env {
+            reg_alloc_order: Some(RegAllocOrder::Custom(&["rax", "rsp"])),
+            ..crate::Env::for_tests(&arena)
+        };
+        new_storage_manager::<X86_64GeneralReg, X86_64FloatReg, X86_64Assembler, X86_64SystemV>(
+            &env,
+            TargetInfo::default_x86_64(),
+        );
+    }
+
+    #[test]
+    fn test_rex_builder_sets_the_bits_of_its_fields() {
+        // the bit math the encoders used to do by hand
+        let extension = |reg: X86_64GeneralReg, bit: u8| if reg as u8 > 7 { bit } else { 0 };
+        for rm in ALL_GENERAL_REGS {
+            for reg in ALL_GENERAL_REGS {
+                for index in ALL_GENERAL_REGS {
+                    let bits = extension(*rm, REX_PREFIX_B)
+                        | extension(*reg, REX_PREFIX_R)
+                        | extension(*index, REX_PREFIX_X);
+                    let rex = RexBuilder::wide().rm(*rm).reg(*reg).index(*index);
+                    assert_eq!(rex.byte(), REX_W | bits);
+
+                    let rex = RexBuilder::new().rm(*rm).reg(*reg).index(*index);
+                    let expected = Some(REX | bits).filter(|byte| *byte != REX);
+                    assert_eq!(rex.byte_if_needed(), expected);
+                }
+            }
+        }
+        for reg in ALL_GENERAL_REGS {
+            let expected = REX | extension(*reg, REX_PREFIX_B);
+            assert_eq!(RexBuilder::new().opcode(*reg).byte(), expected);
+        }
+        for reg in ALL_FLOAT_REGS {
+            let expected = REX_W | if *reg as u8 > 7 { REX_PREFIX_R } else { 0 };
+            assert_eq!(RexBuilder::wide().reg(*reg).byte(), expected);
+        }
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "already has a register in the field 0b0001")]
+    fn test_rex_builder_rejects_two_registers_in_a_field() {
+        let _ = RexBuilder::wide()
+            .rm(X86_64GeneralReg::RAX)
+            .opcode(X86_64GeneralReg::R8);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "can not be used with a REX prefix")]
+    fn test_rex_builder_rejects_high_byte_regs() {
+        let _ = RexBuilder::new()
+            .reg(X86_64GeneralReg::R9)
+            .high_byte_reg()
+            .byte_if_needed();
+    }
+
+    #[test]
+    fn test_rex_builder_leaves_out_an_empty_prefix_with_high_byte_regs() {
+        let rex = RexBuilder::new().rm(X86_64GeneralReg::RBX).high_byte_reg();
+        assert_eq!(rex.byte_if_needed(), None);
+    }
+
+    #[test]
+    fn test_cost_model_sizes_match_the_encoders() {
+        use X86_64GeneralReg::*;
+
+        let arena = bumpalo::Bump::new();
+        let costs = &X86_64CostModel::DEFAULT;
+        let size_of = |emit: &dyn Fn(&mut Vec<'_, u8>)| {
+            let mut buf = bumpalo::vec![in &arena];
+            emit(&mut buf);
+            buf.len()
+        };
+        let size = |inst| costs.size_bytes(inst) as usize;
+
+        for (dst, src) in ALL_GENERAL_REGS.iter().zip(ALL_GENERAL_REGS.iter().rev()) {
+            let (dst, src) = (*dst, *src);
+            assert_eq!(
+                size_of(&|buf| raw_mov_reg64_reg64(buf, dst, src)),
+                size(PlannedInst::MovRegReg)
+            );
+            for imm in [0, -1, TEST_I32 as i64, TEST_I64, i64::MIN] {
+                assert_eq!(
+                    size_of(&|buf| mov_reg64_imm64(buf, dst, imm)),
+                    size(PlannedInst::MovImm(imm))
+                );
+            }
+            assert_eq!(size_of(&|buf| neg_reg64(buf, dst)), size(PlannedInst::Neg));
+            assert_eq!(
+                size_of(&|buf| shl_reg64_imm8(buf, dst, 3)),
+                size(PlannedInst::ShlImm)
+            );
+            assert_eq!(
+                size_of(&|buf| imul_reg64_reg64(buf, dst, src)),
+                size(PlannedInst::MulRegReg)
+            );
+            assert_eq!(
+                size_of(&|buf| cmovl_reg64_reg64(buf, dst, src)),
+                size(PlannedInst::Cmov)
+            );
+            if ![RBP, R13].contains(&dst) && src != RSP {
+                assert_eq!(
+                    size_of(&|buf| lea_reg64_base64_index64(buf, dst, dst, src, 8)),
+                    size(PlannedInst::AddShifted)
+                );
+            }
+        }
+        assert_eq!(
+            size_of(&|buf| jge_imm8(buf, 3)),
+            size(PlannedInst::ShortJcc)
+        );
+    }
+
+    #[test]
+    fn test_abs_picks_cmov_or_a_branch_by_cost() {
+        use X86_64GeneralReg::*;
+
+        let arena = bumpalo::Bump::new();
+        let (mut buf, cs) = setup_capstone_and_arena(&arena);
+
+        X86_64Assembler::abs_reg64_reg64(&mut buf, RAX, RCX);
+        assert_eq!(
+            disassemble_lines(&cs, &buf),
+            ["mov rax, rcx", "neg rax", "cmovl rax, rcx"]
+        );
+
+        let slow_cmov = X86_64CostModel {
+            latencies: LatencyTable {
+                cmov: 3,
+                ..X86_64CostModel::DEFAULT.latencies
+            },
+        };
+        buf.clear();
+        abs_reg64_reg64_with(&mut buf, RAX, RCX, &slow_cmov);
+        assert_eq!(
+            disassemble_lines(&cs, &buf),
+            ["mov rax, rcx", "neg rax", "jge 0xb", "mov rax, rcx"]
+        );
+    }
+
+    #[test]
+    fn test_constant_store_imm() {
+        use crate::generic64::storage::constant_store_imm;
+        use RegisterWidth::*;
+
+        // -1 as an I64, and 0xFFFF_FFFF as a U32 in an 8 byte slot
+        assert_eq!(constant_store_imm(-1, W64), Some(-1));
+        assert_eq!(constant_store_imm(0xFFFF_FFFF, W64), None);
+        assert_eq!(constant_store_imm(i32::MAX as i64, W64), Some(i32::MAX));
+        assert_eq!(constant_store_imm(i32::MAX as i64 + 1, W64), None);
+        assert_eq!(constant_store_imm(i32::MIN as i64, W64), Some(i32::MIN));
+        assert_eq!(constant_store_imm(i32::MIN as i64 - 1, W64), None);
+        assert_eq!(constant_store_imm(i64::MIN, W64), None);
+
+        // the narrower stores write all of their bytes, so the sign does not matter
+        assert_eq!(constant_store_imm(0xFFFF_FFFF, W32), Some(-1));
+        assert_eq!(constant_store_imm(-1, W32), Some(-1));
+        assert_eq!(constant_store_imm(0xFFFF, W16), Some(0xFFFF));
+        assert_eq!(constant_store_imm(-32768, W16), Some(-32768));
+        assert_eq!(constant_store_imm(255, W8), Some(255));
+        assert_eq!(constant_store_imm(-128, W8), Some(-128));
+    }
+
+    #[test]
+    #[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+    fn test_store_constant_to_stack_runs() {
+        use crate::generic64::storage::new_storage_manager;
+        use crate::test_utils::JitPage;
+        use RegisterWidth::*;
+        use X86_64GeneralReg::*;
+
+        type ASM = X86_64Assembler;
+        type CC = X86_64SystemV;
+
+        /// Stores `value` in the `width` bytes of a slot that is filled with a pattern first, and
+        /// returns all 8 bytes of the slot
+        fn store_and_load(value: i64, width: RegisterWidth) -> u64 {
+            let arena = bumpalo::Bump::new();
+            let env = crate::Env::for_tests(&arena);
+            let mut storage_manager =
+                new_storage_manager::<X86_64GeneralReg, X86_64FloatReg, ASM, CC>(
+                    &env,
+                    TargetInfo::default_x86_64(),
+                );
+            storage_manager.reset();
+
+            let mut body = bumpalo::vec![in &arena];
+            let offset = storage_manager.claim_stack_area(&Symbol::DEV_TMP, 8);
+            ASM::mov_reg64_imm64(&mut body, RAX, 0x5555_5555_5555_5555);
+            ASM::mov_base32_reg64(&mut body, offset, RAX);
+            storage_manager.store_constant_to_stack(&mut body, offset, value, width);
+            ASM::mov_reg64_base32(&mut body, RAX, offset);
+
+            // the temporary register may be callee saved
+            let saved_regs = storage_manager.general_used_callee_saved_regs();
+            let mut buf = bumpalo::vec![in &arena];
+            let stack_size = storage_manager.stack_size() as i32;
+            let aligned_stack_size = CC::setup_stack(&mut buf, &saved_regs, &[], stack_size, 0, 0);
+            buf.extend(body.iter().copied());
+            CC::cleanup_stack(&mut buf, &saved_regs, &[], aligned_stack_size, 0, 0);
+            ASM::ret(&mut buf);
+
+            let mut page = JitPage::new();
+            page.write(0, &buf);
+            page.make_executable();
+            unsafe { page.function::<extern "C" fn() -> u64>(0)() }
+        }
+
+        // 0xFFFF_FFFF as a U32 is zero extended, and as an I64 it is -1
+        assert_eq!(store_and_load(0xFFFF_FFFF, W64), 0xFFFF_FFFF);
+        assert_eq!(store_and_load(-1, W64), u64::MAX);
+        assert_eq!(store_and_load(i32::MIN as i64, W64), i32::MIN as i64 as u64);
+        assert_eq!(store_and_load(0x8000_0000, W64), 0x8000_0000);
+        assert_eq!(
+            store_and_load(0x1234_5678_9ABC_DEF0, W64),
+            0x1234_5678_9ABC_DEF0
+        );
+
+        // the narrower stores leave the bytes above them alone
+        assert_eq!(store_and_load(0xFFFF_FFFF, W32), 0x5555_5555_FFFF_FFFF);
+        assert_eq!(store_and_load(-2, W32), 0x5555_5555_FFFF_FFFE);
+        assert_eq!(store_and_load(0xFFFF, W16), 0x5555_5555_5555_FFFF);
+        assert_eq!(store_and_load(-32768, W16), 0x5555_5555_5555_8000);
+        assert_eq!(store_and_load(0x80, W8), 0x5555_5555_5555_5580);
+        assert_eq!(store_and_load(-1, W8), 0x5555_5555_5555_55FF);
+    }
 }
@@ -0,0 +1,517 @@
+//! Windows unwinds through a function with the unwind codes in its `.pdata` and `.xdata`,
+//! which can only describe a prologue of a restricted shape: the pushes of nonvolatile registers,
+//! one `sub rsp` that allocates the frame, setting up the frame pointer, and then the registers
+//! saved with `mov`, or `movaps` for the XMM registers. [`PrologueBuilder`] emits a prologue in
+//! that order, and records the unwind codes for it as it goes. [`unwind_info`] encodes them for
+//! `.xdata`, which the object builder writes along with `.pdata` for COFF objects.
+//! Details here: https://learn.microsoft.com/en-us/cpp/build/exception-handling-x64
+use super::{
+    lea_reg64_base64_offset32, mov_base64_offset32_reg64, movaps_base64_offset32_freg, push_reg64,
+    sub_reg64_imm32, X86_64FloatReg, X86_64GeneralReg,
+};
+use bumpalo::collections::Vec;
+use roc_error_macros::internal_error;
+
+/// The largest offset from `rsp` a frame pointer can have, since it is stored in 4 bits, in
+/// units of 16 bytes.
+pub(crate) const MAX_FRAME_POINTER_OFFSET: u32 = 15 * 16;
+
+/// What an instruction of the prologue does to the stack
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum UnwindOp {
+    /// `push reg`
+    PushNonvol(X86_64GeneralReg),
+    /// `sub rsp, size`
+    Alloc(u32),
+    /// `lea reg, [rsp + offset]`
+    SetFpReg { reg: X86_64GeneralReg, offset: u32 },
+    /// `mov [rsp + offset], reg`
+    SaveNonvol { reg: X86_64GeneralReg, offset: u32 },
+    /// `movaps [rsp + offset], reg`
+    SaveXmm128 { reg: X86_64FloatReg, offset: u32 },
+}
+
+/// An instruction of the prologue, along with the offset of the end of it in the prologue.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct UnwindCode {
+    pub code_offset: u8,
+    pub op: UnwindOp,
+}
+
+impl UnwindCode {
+    /// The slots of `UNWIND_CODE`s this takes up in the unwind info.
+    pub fn slots(&self) -> std::vec::Vec<u16> {
+        let slot = |op: u16, info: u8| self.code_offset as u16 | op << 8 | (info as u16) << 12;
+
+        match self.op {
+            UnwindOp::PushNonvol(reg) => vec![slot(0, reg as u8)],
+            UnwindOp::Alloc(size) if (8..=128).contains(&size) => {
+                vec![slot(2, ((size - 8) / 8) as u8)]
+            }
+            UnwindOp::Alloc(size) if size <= 0x7FFF8 => vec![slot(1, 0), (size / 8) as u16],
+            UnwindOp::Alloc(size) => vec![slot(1, 1), size as u16, (size >> 16) as u16],
+            UnwindOp::SetFpReg { .. } => vec![slot(3, 0)],
+            UnwindOp::SaveNonvol { reg, offset } if offset / 8 <= u16::MAX as u32 => {
+                vec![slot(4, reg as u8), (offset / 8) as u16]
+            }
+            UnwindOp::SaveNonvol { reg, offset } => {
+                vec![slot(5, reg as u8), offset as u16, (offset >> 16) as u16]
+            }
+            UnwindOp::SaveXmm128 { reg, offset } if offset / 16 <= u16::MAX as u32 => {
+                vec![slot(8, reg as u8), (offset / 16) as u16]
+            }
+            UnwindOp::SaveXmm128 { reg, offset } => {
+                vec![slot(9, reg as u8), offset as u16, (offset >> 16) as u16]
+            }
+        }
+    }
+}
+
+/// The `UNWIND_INFO` for a prologue, which goes in `.xdata`.
+pub fn unwind_info(codes: &[UnwindCode]) -> std::vec::Vec<u8> {
+    let prologue_size = codes.last().map_or(0, |code| code.code_offset);
+    let frame = codes.iter().find_map(|code| match code.op {
+        UnwindOp::SetFpReg { reg, offset } => Some(reg as u8 | ((offset / 16) as u8) << 4),
+        _ => None,
+    });
+
+    // The codes go from the end of the prologue to the start of it.
+    let mut slots: std::vec::Vec<u16> = codes.iter().rev().flat_map(UnwindCode::slots).collect();
+    let slot_count = slots.len() as u8;
+    if slots.len() % 2 == 1 {
+        slots.push(0);
+    }
+
+    let version = 1;
+    let mut info = vec![version, prologue_size, slot_count, frame.unwrap_or(0)];
+    for slot in slots {
+        info.extend(slot.to_le_bytes());
+    }
+
+    info
+}
+
+/// The parts of the prologue, in the order they have to come in
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Phase {
+    Push,
+    Alloc,
+    FramePointer,
+    Save,
+}
+
+pub(crate) struct PrologueBuilder {
+    start: usize,
+    end: usize,
+    phase: Phase,
+    stack_size: u32,
+    codes: std::vec::Vec<UnwindCode>,
+}
+
+impl PrologueBuilder {
+    /// Starts a prologue at the end of `buf`.
+    pub(crate) fn new(buf: &Vec<'_, u8>) -> Self {
+        PrologueBuilder {
+            start: buf.len(),
+            end: buf.len(),
+            phase: Phase::Push,
+            stack_size: 0,
+            codes: vec![],
+        }
+    }
+
+    pub(crate) fn push(&mut self, buf: &mut Vec<'_, u8>, reg: X86_64GeneralReg) {
+        self.enter(buf, Phase::Push);
+        push_reg64(buf, reg);
+        self.record(buf, UnwindOp::PushNonvol(reg));
+    }
+
+    pub(crate) fn alloc(&mut self, buf: &mut Vec<'_, u8>, size: u32) {
+        debug_assert!(
+            self.phase < Phase::Alloc,
+            "a Windows prologue can only allocate its frame once"
+        );
+        debug_assert!(size > 0 && size % 8 == 0, "can not allocate {} bytes", size);
+        self.enter(buf, Phase::Alloc);
+        sub_reg64_imm32(buf, X86_64GeneralReg::RSP, size as i32);
+        self.stack_size = size;
+        self.record(buf, UnwindOp::Alloc(size));
+    }
+
+    pub(crate) fn set_frame_pointer(
+        &mut self,
+        buf: &mut Vec<'_, u8>,
+        reg: X86_64GeneralReg,
+        offset: u32,
+    ) {
+        debug_assert!(
+            self.phase < Phase::FramePointer,
+            "a Windows prologue can only set up its frame pointer once"
+        );
+        debug_assert!(
+            offset <= MAX_FRAME_POINTER_OFFSET && offset % 16 == 0,
+            "a frame pointer can not be {} bytes above rsp",
+            offset
+        );
+        self.enter(buf, Phase::FramePointer);
+        lea_reg64_base64_offset32(buf, reg, X86_64GeneralReg::RSP, offset as i32);
+        self.record(buf, UnwindOp::SetFpReg { reg, offset });
+    }
+
+    /// Saves `reg` into the frame, `offset` bytes above `rsp`.
+    pub(crate) fn save(&mut self, buf: &mut Vec<'_, u8>, reg: X86_64GeneralReg, offset: u32) {
+        debug_assert!(
+            offset + 8 <= self.stack_size,
+            "a register can only be saved in the frame the prologue allocated"
+        );
+        self.enter(buf, Phase::Save);
+        mov_base64_offset32_reg64(buf, X86_64GeneralReg::RSP, offset as i32, reg);
+        self.record(buf, UnwindOp::SaveNonvol { reg, offset });
+    }
+
+    /// Saves all 128 bits of `reg` into the frame, `offset` bytes above `rsp`, which has to be
+    /// a multiple of 16.
+    pub(crate) fn save_xmm128(&mut self, buf: &mut Vec<'_, u8>, reg: X86_64FloatReg, offset: u32) {
+        debug_assert!(
+            offset + 16 <= self.stack_size,
+            "a register can only be saved in the frame the prologue allocated"
+        );
+        debug_assert!(
+            offset % 16 == 0,
+            "an XMM register can not be saved {} bytes above rsp",
+            offset
+        );
+        self.enter(buf, Phase::Save);
+        movaps_base64_offset32_freg(buf, X86_64GeneralReg::RSP, offset as i32, reg);
+        self.record(buf, UnwindOp::SaveXmm128 { reg, offset });
+    }
+
+    /// The unwind codes of the prologue, from its start to its end.
+    pub(crate) fn finish(self, buf: &Vec<'_, u8>) -> std::vec::Vec<UnwindCode> {
+        self.check_not_interleaved(buf);
+
+        self.codes
+    }
+
+    fn enter(&mut self, buf: &Vec<'_, u8>, phase: Phase) {
+        self.check_not_interleaved(buf);
+        debug_assert!(
+            self.phase <= phase,
+            "a Windows prologue can not do {:?} after {:?}",
+            phase,
+            self.phase
+        );
+        self.phase = phase;
+    }
+
+    fn check_not_interleaved(&self, buf: &Vec<'_, u8>) {
+        debug_assert_eq!(
+            buf.len(),
+            self.end,
+            "an instruction was put into the middle of a Windows prologue"
+        );
+    }
+
+    fn record(&mut self, buf: &Vec<'_, u8>, op: UnwindOp) {
+        self.end = buf.len();
+        let code_offset = match u8::try_from(self.end - self.start) {
+            Ok(code_offset) => code_offset,
+            Err(_) => internal_error!("A Windows prologue can be at most 255 bytes long"),
+        };
+        self.codes.push(UnwindCode { code_offset, op });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generic64::x86_64::X86_64WindowsFastcall;
+    use X86_64GeneralReg::*;
+
+    const SAVED_REGS: [X86_64GeneralReg; 5] = [RBX, RSI, RDI, R12, R13];
+
+    /// Emits the instructions the codes describe, checking they end where the codes say they do.
+    fn replay<'a>(arena: &'a bumpalo::Bump, codes: &[UnwindCode]) -> Vec<'a, u8> {
+        let mut buf = bumpalo::vec![in arena];
+        for code in codes {
+            match code.op {
+                UnwindOp::PushNonvol(reg) => push_reg64(&mut buf, reg),
+                UnwindOp::Alloc(size) => sub_reg64_imm32(&mut buf, RSP, size as i32),
+                UnwindOp::SetFpReg { reg, offset } => {
+                    lea_reg64_base64_offset32(&mut buf, reg, RSP, offset as i32)
+                }
+                UnwindOp::SaveNonvol { reg, offset } => {
+                    mov_base64_offset32_reg64(&mut buf, RSP, offset as i32, reg)
+                }
+                UnwindOp::SaveXmm128 { reg, offset } => {
+                    movaps_base64_offset32_freg(&mut buf, RSP, offset as i32, reg)
+                }
+            }
+            assert_eq!(buf.len(), code.code_offset as usize, "{:?}", code);
+        }
+
+        buf
+    }
+
+    fn check_prologue(saved_regs: usize, requested_stack_size: i32, frame_pointer: bool) {
+        let arena = bumpalo::Bump::new();
+        let mut buf = bumpalo::vec![in &arena];
+        let fn_call_stack_size = 32;
+        let (stack_size, codes) = X86_64WindowsFastcall::emit_prologue(
+            &mut buf,
+            &SAVED_REGS[..saved_regs],
+            &[],
+            requested_stack_size,
+            fn_call_stack_size,
+        );
+        let stack_size = stack_size as u32;
+
+        let mut expected = vec![UnwindOp::PushNonvol(RBP), UnwindOp::Alloc(stack_size)];
+        if frame_pointer {
+            expected.push(UnwindOp::SetFpReg {
+                reg: RBP,
+                offset: stack_size,
+            });
+        }
+        for (i, reg) in SAVED_REGS[..saved_regs].iter().enumerate() {
+            expected.push(UnwindOp::SaveNonvol {
+                reg: *reg,
+                offset: fn_call_stack_size as u32 + 8 * i as u32,
+            });
+        }
+        assert_eq!(
+            codes
+                .iter()
+                .map(|code| code.op)
+                .collect::<std::vec::Vec<_>>(),
+            expected
+        );
+
+        // The codes describe the whole prologue, and the function body comes right after it.
+        let prologue = replay(&arena, &codes);
+        assert_eq!(&buf[..prologue.len()], &prologue[..]);
+
+        let mut body = bumpalo::vec![in &arena];
+        if !frame_pointer {
+            lea_reg64_base64_offset32(&mut body, RBP, RSP, stack_size as i32);
+        }
+        assert_eq!(&buf[prologue.len()..], &body[..]);
+
+        let info = unwind_info(&codes);
+        let slots = codes.iter().map(|code| code.slots().len()).sum::<usize>();
+        assert_eq!(
+            &info[..4],
+            [
+                1,
+                prologue.len() as u8,
+                slots as u8,
+                if frame_pointer {
+                    RBP as u8 | (stack_size / 16) as u8 << 4
+                } else {
+                    0
+                }
+            ]
+        );
+        assert_eq!(info.len(), 4 + 2 * (slots + slots % 2));
+    }
+
+    #[test]
+    fn prologue_with_frame_pointer() {
+        for saved_regs in [0, 2, 5] {
+            check_prologue(saved_regs, 16, true);
+        }
+    }
+
+    #[test]
+    fn prologue_without_frame_pointer() {
+        for saved_regs in [0, 2, 5] {
+            check_prologue(saved_regs, 1024, false);
+        }
+    }
+
+    #[test]
+    fn prologue_saving_xmm_registers() {
+        use crate::generic64::CallConv;
+
+        let arena = bumpalo::Bump::new();
+        let mut buf = bumpalo::vec![in &arena];
+        let saved_float_regs = [X86_64FloatReg::XMM6, X86_64FloatReg::XMM9];
+        let (stack_size, codes) =
+            X86_64WindowsFastcall::emit_prologue(&mut buf, &[RBX], &saved_float_regs, 16, 32);
+
+        // RBX right above the shadow space, and the XMM registers in the aligned slots after it
+        assert_eq!(stack_size, 96);
+        #[rustfmt::skip]
+        assert_eq!(
+            &buf[..],
+            [
+                0x55, // push rbp
+                0x48, 0x81, 0xEC, 0x60, 0x00, 0x00, 0x00, // sub rsp, 0x60
+                0x48, 0x8D, 0xAC, 0x24, 0x60, 0x00, 0x00, 0x00, // lea rbp, [rsp + 0x60]
+                0x48, 0x89, 0x9C, 0x24, 0x20, 0x00, 0x00, 0x00, // mov [rsp + 0x20], rbx
+                0x0F, 0x29, 0xB4, 0x24, 0x30, 0x00, 0x00, 0x00, // movaps [rsp + 0x30], xmm6
+                0x44, 0x0F, 0x29, 0x8C, 0x24, 0x40, 0x00, 0x00, 0x00, // movaps [rsp + 0x40], xmm9
+            ]
+        );
+        assert_eq!(
+            codes,
+            [
+                UnwindCode {
+                    code_offset: 1,
+                    op: UnwindOp::PushNonvol(RBP)
+                },
+                UnwindCode {
+                    code_offset: 8,
+                    op: UnwindOp::Alloc(96)
+                },
+                UnwindCode {
+                    code_offset: 16,
+                    op: UnwindOp::SetFpReg {
+                        reg: RBP,
+                        offset: 96
+                    }
+                },
+                UnwindCode {
+                    code_offset: 24,
+                    op: UnwindOp::SaveNonvol {
+                        reg: RBX,
+                        offset: 32
+                    }
+                },
+                UnwindCode {
+                    code_offset: 32,
+                    op: UnwindOp::SaveXmm128 {
+                        reg: X86_64FloatReg::XMM6,
+                        offset: 48
+                    }
+                },
+                UnwindCode {
+                    code_offset: 41,
+                    op: UnwindOp::SaveXmm128 {
+                        reg: X86_64FloatReg::XMM9,
+                        offset: 64
+                    }
+                },
+            ]
+        );
+        assert_eq!(&replay(&arena, &codes)[..], &buf[..]);
+
+        #[rustfmt::skip]
+        assert_eq!(
+            unwind_info(&codes),
+            [
+                1, 41, 9, 0x65,
+                0x29, 0x98, 0x04, 0x00, // SAVE_XMM128 xmm9, 0x40 / 16
+                0x20, 0x68, 0x03, 0x00, // SAVE_XMM128 xmm6, 0x30 / 16
+                0x18, 0x34, 0x04, 0x00, // SAVE_NONVOL rbx, 0x20 / 8
+                0x10, 0x03, // SET_FPREG
+                0x08, 0xB2, // ALLOC_SMALL 0x60
+                0x01, 0x50, // PUSH_NONVOL rbp
+                0x00, 0x00,
+            ]
+        );
+
+        // The epilogue restores from the same slots, relative to rbp.
+        let mut epilogue = bumpalo::vec![in &arena];
+        X86_64WindowsFastcall::cleanup_stack(&mut epilogue, &[RBX], &saved_float_regs, 96, 32, 0);
+        #[rustfmt::skip]
+        assert_eq!(
+            &epilogue[..],
+            [
+                0x48, 0x8B, 0x9D, 0xC0, 0xFF, 0xFF, 0xFF, // mov rbx, [rbp - 0x40]
+                0x0F, 0x28, 0xB5, 0xD0, 0xFF, 0xFF, 0xFF, // movaps xmm6, [rbp - 0x30]
+                0x44, 0x0F, 0x28, 0x8D, 0xE0, 0xFF, 0xFF, 0xFF, // movaps xmm9, [rbp - 0x20]
+                0x48, 0x81, 0xC4, 0x60, 0x00, 0x00, 0x00, // add rsp, 0x60
+                0x5D, // pop rbp
+            ]
+        );
+    }
+
+    #[test]
+    fn xmm_save_slots_are_aligned() {
+        let arena = bumpalo::Bump::new();
+        let mut buf = bumpalo::vec![in &arena];
+        // an odd number of general registers leaves the float slots 8 bytes of padding
+        let (stack_size, codes) = X86_64WindowsFastcall::emit_prologue(
+            &mut buf,
+            &SAVED_REGS[..3],
+            &[X86_64FloatReg::XMM15],
+            0,
+            32,
+        );
+
+        assert_eq!(stack_size, 80);
+        assert_eq!(
+            codes.last().unwrap().op,
+            UnwindOp::SaveXmm128 {
+                reg: X86_64FloatReg::XMM15,
+                offset: 64
+            }
+        );
+    }
+
+    #[test]
+    fn unwind_code_slots() {
+        let code = |op| UnwindCode { code_offset: 7, op };
+
+        assert_eq!(code(UnwindOp::PushNonvol(RBP)).slots(), [0x5007]);
+        assert_eq!(code(UnwindOp::Alloc(48)).slots(), [0x5207]);
+        assert_eq!(code(UnwindOp::Alloc(1024)).slots(), [0x0107, 128]);
+        assert_eq!(code(UnwindOp::Alloc(0x100000)).slots(), [0x1107, 0, 0x10]);
+        assert_eq!(
+            code(UnwindOp::SetFpReg {
+                reg: RBP,
+                offset: 32
+            })
+            .slots(),
+            [0x0307]
+        );
+        assert_eq!(
+            code(UnwindOp::SaveNonvol {
+                reg: R12,
+                offset: 40
+            })
+            .slots(),
+            [0xC407, 5]
+        );
+        assert_eq!(
+            code(UnwindOp::SaveXmm128 {
+                reg: X86_64FloatReg::XMM6,
+                offset: 0x20
+            })
+            .slots(),
+            [0x6807, 2]
+        );
+        assert_eq!(
+            code(UnwindOp::SaveXmm128 {
+                reg: X86_64FloatReg::XMM9,
+                offset: 0x100000
+            })
+            .slots(),
+            [0x9907, 0, 0x10]
+        );
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "an instruction was put into the middle of a Windows prologue")]
+    fn interleaved_prologue() {
+        let arena = bumpalo::Bump::new();
+        let mut buf = bumpalo::vec![in &arena];
+        let mut prologue = PrologueBuilder::new(&buf);
+        prologue.push(&mut buf, RBP);
+        push_reg64(&mut buf, RBX);
+        prologue.alloc(&mut buf, 16);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "a Windows prologue can not do Push after Alloc")]
+    fn push_after_alloc() {
+        let arena = bumpalo::Bump::new();
+        let mut buf = bumpalo::vec![in &arena];
+        let mut prologue = PrologueBuilder::new(&buf);
+        prologue.alloc(&mut buf, 16);
+        prologue.push(&mut buf, RBX);
+    }
+}
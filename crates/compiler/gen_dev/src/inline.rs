@@ -0,0 +1,593 @@
+//! A conservative inliner, that runs over the procedures before the dev backend lowers them.
+//!
+//! Only procedures whose body is a short chain of `let`s ending in a `ret`, without calls to
+//! other procedures, are inlined. For those, the call overhead is most of the work.
+//! It is off by default (see `Env::inline_small_procs`), because the dev backend is all about
+//! compile speed.
+use bumpalo::collections::Vec;
+use bumpalo::Bump;
+use roc_collections::all::MutMap;
+use roc_error_macros::internal_error;
+use roc_module::symbol::{IdentIds, ModuleId, Symbol};
+use roc_mono::ir::{
+    Call, CallType, Expr, ListLiteralElement, ModifyRc, Proc, ProcLayout, SelfRecursive, Stmt,
+};
+use roc_mono::layout::InLayout;
+
+/// Procedures with more statements than this are always called.
+const MAX_INLINE_STATEMENTS: usize = 8;
+
+/// Replaces calls to small procedures with their bodies, and returns how many calls were inlined.
+/// The inlined procedures are left in place; the ones nothing calls anymore are dropped later.
+pub fn inline_small_procs<'a>(
+    arena: &'a Bump,
+    module_id: ModuleId,
+    ident_ids: &mut IdentIds,
+    procedures: &mut MutMap<(Symbol, ProcLayout<'a>), Proc<'a>>,
+) -> u32 {
+    let candidates: MutMap<_, _> = procedures
+        .iter()
+        .filter(|(_, proc)| is_inline_candidate(proc))
+        .map(|(key, proc)| (*key, (proc.args, proc.body.clone())))
+        .collect();
+
+    if candidates.is_empty() {
+        return 0;
+    }
+
+    let mut inliner = Inliner {
+        arena,
+        module_id,
+        ident_ids,
+        candidates,
+        inlined_calls: 0,
+    };
+
+    for proc in procedures.values_mut() {
+        proc.body = inliner.inline_calls(&proc.body);
+    }
+
+    inliner.inlined_calls
+}
+
+/// Whether `proc` is small enough, calls nothing, and is not recursive.
+fn is_inline_candidate(proc: &Proc<'_>) -> bool {
+    if !matches!(proc.is_self_recursive, SelfRecursive::NotSelfRecursive) {
+        return false;
+    }
+
+    // the backend lowers calls to these itself, to fewer instructions than their bodies
+    if matches!(
+        proc.name.name(),
+        Symbol::NUM_IS_ZERO | Symbol::NUM_IS_POSITIVE | Symbol::NUM_IS_NEGATIVE
+    ) {
+        return false;
+    }
+
+    let mut stmt = &proc.body;
+    for _ in 0..MAX_INLINE_STATEMENTS {
+        match stmt {
+            Stmt::Let(_, expr, _, continuation) => {
+                if !is_inlinable_expr(expr) {
+                    return false;
+                }
+                stmt = continuation;
+            }
+            Stmt::Refcounting(_, continuation) => stmt = continuation,
+            // the returned symbol becomes the caller's, so it can't be one of the arguments
+            Stmt::Ret(symbol) => return !proc.args.iter().any(|(_, arg)| arg == symbol),
+            _ => return false,
+        }
+    }
+
+    false
+}
+
+fn is_inlinable_expr(expr: &Expr<'_>) -> bool {
+    match expr {
+        Expr::Call(Call { call_type, .. }) => matches!(call_type, CallType::LowLevel { .. }),
+        Expr::Literal(_)
+        | Expr::Tag { .. }
+        | Expr::Struct(_)
+        | Expr::StructAtIndex { .. }
+        | Expr::GetTagId { .. }
+        | Expr::UnionAtIndex { .. }
+        | Expr::Array { .. }
+        | Expr::EmptyArray
+        | Expr::ExprBox { .. }
+        | Expr::ExprUnbox { .. } => true,
+        Expr::Reuse { .. } | Expr::Reset { .. } | Expr::RuntimeErrorFunction(_) => false,
+    }
+}
+
+/// A statement of an inlined body, without its continuation
+enum Spliced<'a> {
+    Let(Symbol, Expr<'a>, InLayout<'a>),
+    Refcounting(ModifyRc),
+}
+
+struct Inliner<'a, 'i> {
+    arena: &'a Bump,
+    module_id: ModuleId,
+    ident_ids: &'i mut IdentIds,
+    /// The arguments and body of every procedure that calls to can be inlined
+    candidates: MutMap<(Symbol, ProcLayout<'a>), (&'a [(InLayout<'a>, Symbol)], Stmt<'a>)>,
+    inlined_calls: u32,
+}
+
+impl<'a, 'i> Inliner<'a, 'i> {
+    fn inline_calls(&mut self, stmt: &Stmt<'a>) -> Stmt<'a> {
+        let arena = self.arena;
+
+        match stmt {
+            Stmt::Let(symbol, expr, layout, continuation) => {
+                let continuation = self.inline_calls(continuation);
+
+                match self.callee(expr) {
+                    Some((params, body, arguments)) => {
+                        self.inlined_calls += 1;
+                        self.splice(params, &body, arguments, *symbol, continuation)
+                    }
+                    None => Stmt::Let(*symbol, expr.clone(), *layout, arena.alloc(continuation)),
+                }
+            }
+            Stmt::Switch {
+                cond_symbol,
+                cond_layout,
+                branches,
+                default_branch,
+                ret_layout,
+            } => {
+                let new_branches = Vec::from_iter_in(
+                    branches
+                        .iter()
+                        .map(|(id, info, branch)| (*id, info.clone(), self.inline_calls(branch))),
+                    arena,
+                );
+                let default_stmt = self.inline_calls(default_branch.1);
+
+                Stmt::Switch {
+                    cond_symbol: *cond_symbol,
+                    cond_layout: *cond_layout,
+                    branches: new_branches.into_bump_slice(),
+                    default_branch: (default_branch.0.clone(), arena.alloc(default_stmt)),
+                    ret_layout: *ret_layout,
+                }
+            }
+            Stmt::Refcounting(modify, continuation) => {
+                Stmt::Refcounting(*modify, arena.alloc(self.inline_calls(continuation)))
+            }
+            Stmt::Expect {
+                condition,
+                region,
+                lookups,
+                variables,
+                remainder,
+            } => Stmt::Expect {
+                condition: *condition,
+                region: *region,
+                lookups: *lookups,
+                variables: *variables,
+                remainder: arena.alloc(self.inline_calls(remainder)),
+            },
+            Stmt::ExpectFx {
+                condition,
+                region,
+                lookups,
+                variables,
+                remainder,
+            } => Stmt::ExpectFx {
+                condition: *condition,
+                region: *region,
+                lookups: *lookups,
+                variables: *variables,
+                remainder: arena.alloc(self.inline_calls(remainder)),
+            },
+            Stmt::Dbg {
+                symbol,
+                variable,
+                remainder,
+            } => Stmt::Dbg {
+                symbol: *symbol,
+                variable: *variable,
+                remainder: arena.alloc(self.inline_calls(remainder)),
+            },
+            Stmt::Join {
+                id,
+                parameters,
+                body,
+                remainder,
+            } => {
+                let body = self.inline_calls(body);
+                let remainder = self.inline_calls(remainder);
+
+                Stmt::Join {
+                    id: *id,
+                    parameters: *parameters,
+                    body: arena.alloc(body),
+                    remainder: arena.alloc(remainder),
+                }
+            }
+            Stmt::Ret(_) | Stmt::Jump(_, _) | Stmt::Crash(..) => stmt.clone(),
+        }
+    }
+
+    /// The parameters and body of the procedure `expr` calls, if that call can be inlined,
+    /// along with the arguments of the call.
+    #[allow(clippy::type_complexity)]
+    fn callee(
+        &self,
+        expr: &Expr<'a>,
+    ) -> Option<(&'a [(InLayout<'a>, Symbol)], Stmt<'a>, &'a [Symbol])> {
+        match expr {
+            Expr::Call(Call {
+                call_type:
+                    CallType::ByName {
+                        name,
+                        ret_layout,
+                        arg_layouts,
+                        ..
+                    },
+                arguments,
+            }) => {
+                let key = (
+                    name.name(),
+                    ProcLayout {
+                        arguments: *arg_layouts,
+                        result: *ret_layout,
+                        niche: name.niche(),
+                    },
+                );
+
+                let (params, body) = self.candidates.get(&key)?;
+                Some((*params, body.clone(), *arguments))
+            }
+            _ => None,
+        }
+    }
+
+    /// The statements of `body`, with its parameters replaced by `arguments`,
+    /// its other symbols replaced by fresh ones, and its `ret` replaced by `let result`.
+    fn splice(
+        &mut self,
+        params: &'a [(InLayout<'a>, Symbol)],
+        body: &Stmt<'a>,
+        arguments: &'a [Symbol],
+        result: Symbol,
+        continuation: Stmt<'a>,
+    ) -> Stmt<'a> {
+        debug_assert_eq!(params.len(), arguments.len());
+
+        let mut renames: MutMap<Symbol, Symbol> = params
+            .iter()
+            .map(|(_, param)| *param)
+            .zip(arguments.iter().copied())
+            .collect();
+
+        let returned = {
+            let mut stmt = body;
+            loop {
+                match stmt {
+                    Stmt::Let(_, _, _, next) | Stmt::Refcounting(_, next) => stmt = next,
+                    Stmt::Ret(symbol) => break *symbol,
+                    _ => internal_error!("cannot inline {:?}", body),
+                }
+            }
+        };
+        renames.insert(returned, result);
+
+        let mut spliced = std::vec::Vec::new();
+        let mut stmt = body;
+        loop {
+            match stmt {
+                Stmt::Let(symbol, expr, layout, next) => {
+                    let new_symbol = match renames.get(symbol) {
+                        Some(new_symbol) => *new_symbol,
+                        None => {
+                            let new_symbol =
+                                Symbol::new(self.module_id, self.ident_ids.gen_unique());
+                            renames.insert(*symbol, new_symbol);
+                            new_symbol
+                        }
+                    };
+                    let expr = self.rename_expr(&renames, expr);
+                    spliced.push(Spliced::Let(new_symbol, expr, *layout));
+                    stmt = next;
+                }
+                Stmt::Refcounting(modify, next) => {
+                    let rename = |symbol: &Symbol| renames.get(symbol).copied().unwrap_or(*symbol);
+                    let modify = match modify {
+                        ModifyRc::Inc(symbol, count) => ModifyRc::Inc(rename(symbol), *count),
+                        ModifyRc::Dec(symbol) => ModifyRc::Dec(rename(symbol)),
+                        ModifyRc::DecRef(symbol) => ModifyRc::DecRef(rename(symbol)),
+                    };
+                    spliced.push(Spliced::Refcounting(modify));
+                    stmt = next;
+                }
+                _ => break,
+            }
+        }
+
+        spliced
+            .into_iter()
+            .rev()
+            .fold(continuation, |stmt, spliced| match spliced {
+                Spliced::Let(symbol, expr, layout) => {
+                    Stmt::Let(symbol, expr, layout, self.arena.alloc(stmt))
+                }
+                Spliced::Refcounting(modify) => Stmt::Refcounting(modify, self.arena.alloc(stmt)),
+            })
+    }
+
+    fn rename_expr(&self, renames: &MutMap<Symbol, Symbol>, expr: &Expr<'a>) -> Expr<'a> {
+        let arena = self.arena;
+        let rename = |symbol: &Symbol| renames.get(symbol).copied().unwrap_or(*symbol);
+        let rename_all = |symbols: &[Symbol]| {
+            Vec::from_iter_in(symbols.iter().map(rename), arena).into_bump_slice()
+        };
+
+        match expr {
+            Expr::Literal(_) | Expr::EmptyArray => expr.clone(),
+            Expr::Call(Call {
+                call_type,
+                arguments,
+            }) => Expr::Call(Call {
+                call_type: call_type.clone(),
+                arguments: rename_all(arguments),
+            }),
+            Expr::Tag {
+                tag_layout,
+                tag_id,
+                arguments,
+            } => Expr::Tag {
+                tag_layout: *tag_layout,
+                tag_id: *tag_id,
+                arguments: rename_all(arguments),
+            },
+            Expr::Struct(fields) => Expr::Struct(rename_all(fields)),
+            Expr::StructAtIndex {
+                index,
+                field_layouts,
+                structure,
+            } => Expr::StructAtIndex {
+                index: *index,
+                field_layouts: *field_layouts,
+                structure: rename(structure),
+            },
+            Expr::GetTagId {
+                structure,
+                union_layout,
+            } => Expr::GetTagId {
+                structure: rename(structure),
+                union_layout: *union_layout,
+            },
+            Expr::UnionAtIndex {
+                structure,
+                tag_id,
+                union_layout,
+                index,
+            } => Expr::UnionAtIndex {
+                structure: rename(structure),
+                tag_id: *tag_id,
+                union_layout: *union_layout,
+                index: *index,
+            },
+            Expr::Array { elem_layout, elems } => Expr::Array {
+                elem_layout: *elem_layout,
+                elems: Vec::from_iter_in(
+                    elems.iter().map(|elem| match elem {
+                        ListLiteralElement::Literal(literal) => {
+                            ListLiteralElement::Literal(*literal)
+                        }
+                        ListLiteralElement::Symbol(symbol) => {
+                            ListLiteralElement::Symbol(rename(symbol))
+                        }
+                    }),
+                    arena,
+                )
+                .into_bump_slice(),
+            },
+            Expr::ExprBox { symbol } => Expr::ExprBox {
+                symbol: rename(symbol),
+            },
+            Expr::ExprUnbox { symbol } => Expr::ExprUnbox {
+                symbol: rename(symbol),
+            },
+            Expr::Reuse { .. } | Expr::Reset { .. } | Expr::RuntimeErrorFunction(_) => {
+                internal_error!("cannot inline {:?}", expr)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use roc_module::low_level::LowLevel;
+    use roc_module::symbol::Interns;
+    use roc_mono::ir::{CallSpecId, HostExposedLayouts, Literal, UpdateModeId};
+    use roc_mono::layout::{LambdaName, Layout, Niche};
+
+    fn call_by_name<'a>(arena: &'a Bump, name: Symbol, arguments: &[Symbol]) -> Expr<'a> {
+        Expr::Call(Call {
+            call_type: CallType::ByName {
+                name: LambdaName::no_niche(name),
+                ret_layout: Layout::I64,
+                arg_layouts: arena.alloc_slice_fill_copy(arguments.len(), Layout::I64),
+                specialization_id: CallSpecId::BACKEND_DUMMY,
+            },
+            arguments: arena.alloc_slice_copy(arguments),
+        })
+    }
+
+    /// A procedure from `I64`s to an `I64`, with the given `let`s and `ret`
+    fn proc<'a>(
+        arena: &'a Bump,
+        name: Symbol,
+        args: &[Symbol],
+        lets: std::vec::Vec<(Symbol, Expr<'a>)>,
+        ret: Symbol,
+    ) -> ((Symbol, ProcLayout<'a>), Proc<'a>) {
+        let body = lets
+            .into_iter()
+            .rev()
+            .fold(Stmt::Ret(ret), |stmt, (symbol, expr)| {
+                Stmt::Let(symbol, expr, Layout::I64, arena.alloc(stmt))
+            });
+        let layout = ProcLayout {
+            arguments: arena.alloc_slice_fill_copy(args.len(), Layout::I64),
+            result: Layout::I64,
+            niche: Niche::NONE,
+        };
+        let proc = Proc {
+            name: LambdaName::no_niche(name),
+            args: arena.alloc_slice_fill_iter(args.iter().map(|arg| (Layout::I64, *arg))),
+            body,
+            closure_data_layout: None,
+            ret_layout: Layout::I64,
+            is_self_recursive: SelfRecursive::NotSelfRecursive,
+            must_own_arguments: false,
+            host_exposed_layouts: HostExposedLayouts::NotHostExposed,
+        };
+
+        ((name, layout), proc)
+    }
+
+    fn lets<'s, 'a>(mut stmt: &'s Stmt<'a>) -> std::vec::Vec<(Symbol, &'s Expr<'a>)> {
+        let mut lets = std::vec::Vec::new();
+        while let Stmt::Let(symbol, expr, _, continuation) = stmt {
+            lets.push((*symbol, expr));
+            stmt = continuation;
+        }
+        lets
+    }
+
+    fn returned(mut stmt: &Stmt<'_>) -> Symbol {
+        while let Stmt::Let(_, _, _, continuation) = stmt {
+            stmt = continuation;
+        }
+        match stmt {
+            Stmt::Ret(symbol) => *symbol,
+            _ => unreachable!(),
+        }
+    }
+
+    fn by_name_calls(stmt: &Stmt<'_>) -> usize {
+        lets(stmt)
+            .iter()
+            .filter(|(_, expr)| {
+                matches!(
+                    expr,
+                    Expr::Call(Call {
+                        call_type: CallType::ByName { .. },
+                        ..
+                    })
+                )
+            })
+            .count()
+    }
+
+    #[test]
+    fn calls_to_small_procs_are_inlined() {
+        let arena = Bump::new();
+        let mut interns = Interns::default();
+        let module_id = interns.module_id(&"Test".into());
+        let ident_ids = interns.all_ident_ids.get_or_insert(module_id);
+        let [main, double, quadruple, x, two, doubled, y, z, a, b, c] = [
+            "main",
+            "double",
+            "quadruple",
+            "x",
+            "two",
+            "doubled",
+            "y",
+            "z",
+            "a",
+            "b",
+            "c",
+        ]
+        .map(|name| Symbol::new(module_id, ident_ids.add_str(name)));
+
+        let mul = |lhs, rhs| {
+            Expr::Call(Call {
+                call_type: CallType::LowLevel {
+                    op: LowLevel::NumMul,
+                    update_mode: UpdateModeId::BACKEND_DUMMY,
+                },
+                arguments: arena.alloc([lhs, rhs]),
+            })
+        };
+
+        // double = \x -> x * 2
+        // quadruple = \x -> double (double x)
+        // main = quadruple (double 21)
+        let mut procedures: MutMap<_, _> = [
+            proc(
+                &arena,
+                double,
+                &[x],
+                vec![
+                    (two, Expr::Literal(Literal::Int(2i128.to_ne_bytes()))),
+                    (doubled, mul(x, two)),
+                ],
+                doubled,
+            ),
+            proc(
+                &arena,
+                quadruple,
+                &[x],
+                vec![
+                    (y, call_by_name(&arena, double, &[x])),
+                    (z, call_by_name(&arena, double, &[y])),
+                ],
+                z,
+            ),
+            proc(
+                &arena,
+                main,
+                &[],
+                vec![
+                    (a, Expr::Literal(Literal::Int(21i128.to_ne_bytes()))),
+                    (b, call_by_name(&arena, double, &[a])),
+                    (c, call_by_name(&arena, quadruple, &[b])),
+                ],
+                c,
+            ),
+        ]
+        .into_iter()
+        .collect();
+
+        let calls_before: usize = procedures
+            .values()
+            .map(|proc| by_name_calls(&proc.body))
+            .sum();
+        assert_eq!(calls_before, 4);
+
+        let inlined = inline_small_procs(&arena, module_id, ident_ids, &mut procedures);
+
+        // `quadruple` called `double` itself, so the call to it stays
+        assert_eq!(inlined, 3);
+        let body = |name| {
+            &procedures
+                .iter()
+                .find(|((symbol, _), _)| *symbol == name)
+                .unwrap()
+                .1
+                .body
+        };
+        assert_eq!(by_name_calls(body(main)), 1);
+        assert_eq!(by_name_calls(body(quadruple)), 0);
+
+        // every inlined `two` is a fresh symbol, and the result takes the name of the call
+        let quadruple_lets = lets(body(quadruple));
+        let symbols: std::vec::Vec<_> = quadruple_lets.iter().map(|(symbol, _)| *symbol).collect();
+        assert_eq!(symbols.len(), 4);
+        assert_eq!(symbols[1], y);
+        assert_eq!(symbols[3], z);
+        assert!(!symbols.contains(&two));
+        assert_ne!(symbols[0], symbols[2]);
+        assert_eq!(*quadruple_lets[1].1, mul(x, symbols[0]));
+        assert_eq!(*quadruple_lets[3].1, mul(y, symbols[2]));
+        assert_eq!(returned(body(quadruple)), z);
+    }
+}
@@ -5,35 +5,194 @@
 // See github.com/roc-lang/roc/issues/800 for discussion of the large_enum_variant check.
 #![allow(clippy::large_enum_variant, clippy::upper_case_acronyms)]
 
-use bumpalo::{collections::Vec, Bump};
+use bumpalo::Bump;
 use roc_builtins::bitcode::{self, FloatWidth, IntWidth};
+use roc_builtins::host_symbols::HostSymbols;
 use roc_collections::all::{MutMap, MutSet};
 use roc_error_macros::internal_error;
 use roc_module::ident::ModuleName;
 use roc_module::low_level::{LowLevel, LowLevelWrapperType};
 use roc_module::symbol::{Interns, ModuleId, Symbol};
-use roc_mono::code_gen_help::CodeGenHelp;
 use roc_mono::ir::{
     BranchInfo, CallType, Expr, JoinPointId, ListLiteralElement, Literal, Param, Proc, ProcLayout,
     SelfRecursive, Stmt,
 };
 use roc_mono::layout::{
-    Builtin, InLayout, Layout, LayoutId, LayoutIds, LayoutInterner, STLayoutInterner, TagIdIntType,
+    Builtin, InLayout, LambdaName, Layout, LayoutInterner, STLayoutInterner, TagIdIntType,
     UnionLayout,
 };
 use roc_mono::list_element_layout;
+use std::cell::{Cell, RefCell};
 
+mod cost_model;
+use cost_model::CostModel;
 mod generic64;
+use generic64::SignCondition;
+mod inline;
+mod listing;
+pub use listing::{ListingRegion, ProcListing, StatementMarker};
 mod object_builder;
-pub use object_builder::build_module;
+pub use object_builder::{
+    build_module, build_procedures, describe_unsupported, read_patchpoints, read_producer,
+    ObjectOutput, Patchpoint, ProcSize, UnsupportedError,
+};
+mod panic_cleanup;
+use panic_cleanup::CallCleanup;
+pub use panic_cleanup::{read_cleanup_sites, CleanupKind, CleanupSite, CleanupValue};
+mod peephole;
+mod refcounting;
+mod relocation;
+pub use relocation::{link_relocation, pc_relative_displacement, RelocationOutOfRange};
 mod run_roc;
+mod stats;
+pub use stats::{CodeGenStats, PhaseTimes, ProcStats};
+#[cfg(test)]
+mod test_utils;
+mod unsupported;
+use unsupported::BuildResult;
+pub use unsupported::UnsupportedConstruct;
 
 pub struct Env<'a> {
     pub arena: &'a Bump,
     pub module_id: ModuleId,
     pub exposed_to_host: MutSet<Symbol>,
+    /// Procedures the host calls by a name of its choosing, like `roc__mainForHost_1_exposed`.
+    /// They are exported just like the ones in `exposed_to_host`. Every procedure is built with
+    /// the calling convention of the target already, so the host can call them directly.
+    pub export_names: MutMap<Symbol, String>,
     pub lazy_literals: bool,
     pub generate_allocators: bool,
+    /// What the host calls its allocator and `roc_panic`. The calls that the backend generates
+    /// itself use these names, and so do the allocators of `generate_allocators`.
+    pub host_symbols: HostSymbols,
+    /// Also define the standard names that `host_symbols` renames, e.g. `roc_alloc`, as jumps to
+    /// the names of the host, so that the builtins call the functions of the host too.
+    pub alias_host_symbols: bool,
+    /// The size of the arena after building all procedures, in bytes, plus the sizes of the
+    /// arenas of the threads that built procedures. Arenas never shrink, so this is their peak size.
+    pub peak_arena_bytes: Cell<usize>,
+    /// Per-procedure register allocation statistics; see the `stats` feature. Made with
+    /// `CodeGenStats::timed`, they also have how long the phases of the build took.
+    pub stats: RefCell<CodeGenStats>,
+    /// The size of every procedure and wrapper in the object, in the order they were added to it.
+    /// Filled in by `build_module`.
+    pub proc_sizes: RefCell<std::vec::Vec<ProcSize>>,
+    /// Overrides the calling convention's default general purpose register order.
+    pub reg_alloc_order: Option<RegAllocOrder>,
+    /// Replace calls to small procedures that call nothing themselves with their bodies.
+    /// Off by default, because it costs compile time.
+    pub inline_small_procs: bool,
+    /// How many threads build procedures at the same time. With 1, all of them are built on the
+    /// calling thread. The object is the same for any number of threads.
+    pub codegen_threads: usize,
+    /// Put a canary on the stack of every procedure, and check in the epilogue that nothing wrote
+    /// over it. This catches code that writes past its frame, e.g. because of a bug in the backend.
+    pub stack_canary: Option<StackCanary>,
+    /// Start every procedure with a patchpoint, which a runtime can overwrite with a jump to a
+    /// newer version of the procedure, e.g. to reload code without linking again. The object
+    /// lists where the patchpoints are; see `read_patchpoints`.
+    pub patchpoints: bool,
+    /// List, for every call that can panic, the refcounted values the caller owns and where they
+    /// are in its frame, so a host whose `roc_panic` does not return can release them.
+    /// See `read_cleanup_sites` for the format.
+    pub panic_cleanup: bool,
+    /// Keep a pointer to a context of the host, e.g. per-thread scratch state, in the calling
+    /// convention's `RUNTIME_CONTEXT_REG`, which is then never handed out to values. The
+    /// procedures exposed to the host take the pointer as a hidden trailing argument, and the
+    /// `RuntimeContextLoad` and `RuntimeContextStore` lowlevels access it. Code that a builtin
+    /// calls back into, e.g. the function passed to `List.map`, can not count on the register.
+    pub runtime_context: bool,
+    /// Serialize the reads of the timestamp counter for the `MonotonicTicks` lowlevel, so the
+    /// instructions before a read finish before it. That makes short measurements precise, at
+    /// the cost of some speed.
+    pub serialize_ticks: bool,
+    /// Zero the stack slot of every struct and union before its fields are stored, so that its
+    /// padding is deterministic. Without it, only the layouts that are byte comparable are zeroed.
+    pub zero_padding: bool,
+    /// Where every procedure starts, as an alignment in bytes, which has to be a power of two.
+    /// 16 is what C compilers use. The gap after a procedure is filled with `Assembler::padding`,
+    /// which is not part of the size of its symbol.
+    pub proc_alignment: u64,
+    /// Record where the code of every statement of a procedure starts, so a listing can show
+    /// each statement above the instructions it became. See `listings`.
+    pub statement_markers: bool,
+    /// With `statement_markers`, the code and the statement markers of every procedure in the
+    /// object, in the order they were added to it. Filled in by `build_module`.
+    pub listings: RefCell<std::vec::Vec<ProcListing>>,
+}
+
+#[cfg(test)]
+impl<'a> Env<'a> {
+    /// An `Env` for unit tests, which exposes nothing and has every option off. A test that
+    /// needs something else can change those fields with struct update syntax.
+    pub(crate) fn for_tests(arena: &'a Bump) -> Self {
+        Env {
+            arena,
+            module_id: ModuleId::NUM,
+            exposed_to_host: Default::default(),
+            export_names: Default::default(),
+            lazy_literals: false,
+            generate_allocators: false,
+            host_symbols: Default::default(),
+            alias_host_symbols: false,
+            peak_arena_bytes: Default::default(),
+            stats: Default::default(),
+            proc_sizes: Default::default(),
+            reg_alloc_order: None,
+            inline_small_procs: false,
+            codegen_threads: 1,
+            stack_canary: None,
+            patchpoints: false,
+            panic_cleanup: false,
+            runtime_context: false,
+            serialize_ticks: false,
+            zero_padding: false,
+            proc_alignment: 16,
+            statement_markers: false,
+            listings: Default::default(),
+        }
+    }
+}
+
+/// Where the value of a stack canary comes from, and what happens when it was overwritten
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StackCanary {
+    /// Load `__stack_chk_guard` and call `__stack_chk_fail`, like C code that is built with
+    /// `-fstack-protector` does. This only links against C runtimes that define the guard as a
+    /// symbol, e.g. musl's and macOS's, but not glibc's.
+    Guard,
+    /// Use a value known at compile time, and call `roc_panic`. For tests, which can not count
+    /// on a C runtime.
+    Constant(u64),
+}
+
+/// The order in which general purpose registers are handed out.
+/// Which order spills least depends on the code: math kernels without calls do best with
+/// caller saved registers, while call heavy code avoids saving them around every call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegAllocOrder {
+    CallerSavedFirst,
+    CalleeSavedFirst,
+    /// Exactly these registers, by name and in the order they should be used.
+    /// They must all be allocatable in the calling convention's default order,
+    /// which e.g. never contains the stack or frame pointer.
+    Custom(&'static [&'static str]),
+}
+
+impl std::str::FromStr for RegAllocOrder {
+    type Err = String;
+
+    /// Parses the non-custom orders, e.g. from the `ROC_DEV_REG_ALLOC_ORDER` environment variable.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "caller-saved-first" => Ok(RegAllocOrder::CallerSavedFirst),
+            "callee-saved-first" => Ok(RegAllocOrder::CalleeSavedFirst),
+            _ => Err(format!(
+                "unknown register allocation order {:?}, expected caller-saved-first or callee-saved-first",
+                s
+            )),
+        }
+    }
 }
 
 // These relocations likely will need a length.
@@ -62,25 +221,61 @@ pub enum Relocation {
     },
 }
 
+/// What an unwinder needs to know about the prologue of a procedure, on the targets whose objects
+/// describe prologues in a table of their own, like `.pdata` and `.xdata` of COFF.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnwindInfo {
+    /// where the prologue starts, from the start of the procedure
+    pub prologue_offset: u64,
+    /// the info, encoded the way the table of the target has it
+    pub info: std::vec::Vec<u8>,
+}
+
+/// The name of the object file symbol for one specialization of a procedure,
+/// e.g. "UserApp_foo_3a5c0e11d0b6f2a9" for a symbol that interns to "foo".
+///
+/// Like the names `LayoutIds` hands out, it starts with the module and the identifier,
+/// but the suffix is a hash of the layout instead of a counter. That way the definition of a
+/// procedure and every call to it agree on the name, no matter the order they are built in.
+/// Platform specific prefixes, like the underscore on Mach-O, are added by `object` as the
+/// symbols go into the object file.
+pub fn proc_symbol_name(symbol: Symbol, layout: &ProcLayout<'_>, interns: &Interns) -> String {
+    use std::hash::{Hash, Hasher};
+
+    let mut hasher = roc_collections::all::FnvHasher::default();
+    layout.hash(&mut hasher);
+
+    let ident_string = symbol.as_str(interns);
+    let module_string = interns.module_ids.get_name(symbol.module_id()).unwrap();
+    format!(
+        "{}_{}_{:016x}",
+        module_string,
+        ident_string,
+        hasher.finish()
+    )
+}
+
 trait Backend<'a> {
     fn env(&self) -> &Env<'a>;
     fn interns(&self) -> &Interns;
     fn interner(&self) -> &STLayoutInterner<'a>;
 
-    // This method is suboptimal, but it seems to be the only way to make rust understand
-    // that all of these values can be mutable at the same time. By returning them together,
-    // rust understands that they are part of a single use of mutable self.
-    fn module_interns_helpers_mut(
-        &mut self,
-    ) -> (
-        ModuleId,
-        &mut STLayoutInterner<'a>,
-        &mut Interns,
-        &mut CodeGenHelp<'a>,
-    );
+    /// What the instructions of the target cost, for the choices between equivalent sequences.
+    fn cost_model(&self) -> &'static dyn CostModel;
 
-    fn symbol_to_string(&self, symbol: Symbol, layout_id: LayoutId) -> String {
-        layout_id.to_symbol_string(symbol, self.interns())
+    /// The name a call to this specialization of `name` has to use.
+    fn callee_symbol_name(
+        &self,
+        name: LambdaName<'a>,
+        arg_layouts: &'a [InLayout<'a>],
+        ret_layout: &InLayout<'a>,
+    ) -> String {
+        let layout = ProcLayout {
+            arguments: arg_layouts,
+            result: *ret_layout,
+            niche: name.niche(),
+        };
+        proc_symbol_name(name.name(), &layout, self.interns())
     }
 
     fn defined_in_app_module(&self, symbol: Symbol) -> bool {
@@ -89,12 +284,6 @@ trait Backend<'a> {
             .starts_with(ModuleName::APP)
     }
 
-    fn helper_proc_gen_mut(&mut self) -> &mut CodeGenHelp<'a>;
-
-    fn helper_proc_symbols_mut(&mut self) -> &mut Vec<'a, (Symbol, ProcLayout<'a>)>;
-
-    fn helper_proc_symbols(&self) -> &Vec<'a, (Symbol, ProcLayout<'a>)>;
-
     /// reset resets any registers or other values that may be occupied at the end of a procedure.
     /// It also passes basic procedure information to the builder for setup of the next function.
     fn reset(&mut self, name: String, is_self_recursive: SelfRecursive);
@@ -103,85 +292,98 @@ trait Backend<'a> {
     /// finalize does setup because things like stack size and jump locations are not know until the function is written.
     /// For example, this can store the frame pointer and setup stack space.
     /// finalize is run at the end of build_proc when all internal code is finalized.
-    fn finalize(&mut self) -> (Vec<u8>, Vec<Relocation>);
+    /// The returned buffers are reused by the next procedure, so the caller must copy them out.
+    fn finalize(&mut self) -> (&[u8], &[Relocation]);
+
+    /// With `Env::panic_cleanup`, the calls that can panic in the procedure `finalize` finished,
+    /// relative to its start.
+    fn cleanup_sites(&self) -> &[CallCleanup];
+
+    /// Records that the code of `stmt` starts at the end of the buffer, for
+    /// `Env::statement_markers`.
+    fn mark_statement(&mut self, stmt: &Stmt<'a>);
+
+    /// With `Env::statement_markers`, the statements of the procedure `finalize` finished,
+    /// relative to its start.
+    fn statement_markers(&self) -> &[StatementMarker];
+
+    /// The unwind info of the procedure `finalize` finished, if the calling convention of the
+    /// target has any.
+    fn unwind_info(&self) -> Option<&UnwindInfo>;
 
     // load_args is used to let the backend know what the args are.
     // The backend should track these args so it can use them as needed.
-    fn load_args(&mut self, args: &'a [(InLayout<'a>, Symbol)], ret_layout: &InLayout<'a>);
+    fn load_args(
+        &mut self,
+        args: &'a [(InLayout<'a>, Symbol)],
+        ret_layout: &InLayout<'a>,
+    ) -> BuildResult;
 
     /// Used for generating wrappers for malloc/realloc/free
     fn build_wrapped_jmp(&mut self) -> (&'a [u8], u64);
 
     /// build_proc creates a procedure and outputs it to the wrapped object writer.
-    /// Returns the procedure bytes, its relocations, and the names of the refcounting functions it references.
+    /// Returns the procedure bytes and its relocations.
+    /// Its refcounting statements must have been expanded already.
     fn build_proc(
         &mut self,
         proc: Proc<'a>,
-        layout_ids: &mut LayoutIds<'a>,
-    ) -> (Vec<u8>, Vec<Relocation>, Vec<'a, (Symbol, String)>) {
-        let layout_id = layout_ids.get(proc.name.name(), &proc.ret_layout);
-        let proc_name = self.symbol_to_string(proc.name.name(), layout_id);
+        layout: &ProcLayout<'a>,
+    ) -> BuildResult<(&[u8], &[Relocation])> {
+        let proc_name = proc_symbol_name(proc.name.name(), layout, self.interns());
         self.reset(proc_name, proc.is_self_recursive);
-        self.load_args(proc.args, &proc.ret_layout);
+
+        let sym = proc.name.name();
+        let exposed =
+            self.env().exposed_to_host.contains(&sym) || self.env().export_names.contains_key(&sym);
+        if self.env().runtime_context && exposed {
+            // the host passes its context after all other arguments
+            let context_arg = (
+                Layout::usize(self.interner().target_info()),
+                Symbol::DEV_TMP,
+            );
+            let args = self
+                .env()
+                .arena
+                .alloc_slice_fill_iter(proc.args.iter().copied().chain([context_arg]));
+            self.load_args(args, &proc.ret_layout)?;
+            self.load_runtime_context(&Symbol::DEV_TMP);
+        } else {
+            self.load_args(proc.args, &proc.ret_layout)?;
+        }
         for (layout, sym) in proc.args {
             self.set_layout_map(*sym, layout);
         }
         self.scan_ast(&proc.body);
         self.create_free_map();
-        self.build_stmt(&proc.body, &proc.ret_layout);
-        let mut helper_proc_names = bumpalo::vec![in self.env().arena];
-        helper_proc_names.reserve(self.helper_proc_symbols().len());
-        for (rc_proc_sym, rc_proc_layout) in self.helper_proc_symbols() {
-            let name = layout_ids
-                .get_toplevel(*rc_proc_sym, rc_proc_layout)
-                .to_symbol_string(*rc_proc_sym, self.interns());
-
-            helper_proc_names.push((*rc_proc_sym, name));
-        }
-        let (bytes, relocs) = self.finalize();
-        (bytes, relocs, helper_proc_names)
+        self.build_stmt(&proc.body, &proc.ret_layout)?;
+        Ok(self.finalize())
     }
 
     /// build_stmt builds a statement and outputs at the end of the buffer.
-    fn build_stmt(&mut self, stmt: &Stmt<'a>, ret_layout: &InLayout<'a>) {
+    fn build_stmt(&mut self, stmt: &Stmt<'a>, ret_layout: &InLayout<'a>) -> BuildResult {
+        if self.env().statement_markers {
+            self.mark_statement(stmt);
+        }
+
         match stmt {
             Stmt::Let(sym, expr, layout, following) => {
-                self.build_expr(sym, expr, layout);
+                self.hint_result_reg(sym, layout, following);
+                self.build_expr(sym, expr, layout)?;
                 self.set_layout_map(*sym, layout);
+                if self.env().panic_cleanup {
+                    self.track_owned_values(sym, expr, layout);
+                }
                 self.free_symbols(stmt);
-                self.build_stmt(following, ret_layout);
+                self.build_stmt(following, ret_layout)?;
             }
             Stmt::Ret(sym) => {
-                self.load_literal_symbols(&[*sym]);
-                self.return_symbol(sym, ret_layout);
+                self.load_literal_symbols(&[*sym])?;
+                self.return_symbol(sym, ret_layout)?;
                 self.free_symbols(stmt);
             }
-            Stmt::Refcounting(modify, following) => {
-                let sym = modify.get_symbol();
-                let layout = *self.layout_map().get(&sym).unwrap();
-
-                // Expand the Refcounting statement into more detailed IR with a function call
-                // If this layout requires a new RC proc, we get enough info to create a linker symbol
-                // for it. Here we don't create linker symbols at this time, but in Wasm backend, we do.
-                let (rc_stmt, new_specializations) = {
-                    let (module_id, layout_interner, interns, rc_proc_gen) =
-                        self.module_interns_helpers_mut();
-                    let ident_ids = interns.all_ident_ids.get_mut(&module_id).unwrap();
-
-                    rc_proc_gen.expand_refcount_stmt(
-                        ident_ids,
-                        layout_interner,
-                        layout,
-                        modify,
-                        following,
-                    )
-                };
-
-                for spec in new_specializations.into_iter() {
-                    self.helper_proc_symbols_mut().push(spec);
-                }
-
-                self.build_stmt(rc_stmt, ret_layout)
+            Stmt::Refcounting(..) => {
+                internal_error!("refcounting statements are expanded before procedures are built")
             }
             Stmt::Switch {
                 cond_symbol,
@@ -190,14 +392,14 @@ trait Backend<'a> {
                 default_branch,
                 ret_layout,
             } => {
-                self.load_literal_symbols(&[*cond_symbol]);
+                self.load_literal_symbols(&[*cond_symbol])?;
                 self.build_switch(
                     cond_symbol,
                     cond_layout,
                     branches,
                     default_branch,
                     ret_layout,
-                );
+                )?;
                 self.free_symbols(stmt);
             }
             Stmt::Join {
@@ -209,10 +411,11 @@ trait Backend<'a> {
                 for param in parameters.iter() {
                     self.set_layout_map(param.symbol, &param.layout);
                 }
-                self.build_join(id, parameters, body, remainder, ret_layout);
+                self.build_join(id, parameters, body, remainder, ret_layout)?;
                 self.free_symbols(stmt);
             }
             Stmt::Jump(id, args) => {
+                self.load_literal_symbols(args)?;
                 let mut arg_layouts: bumpalo::collections::Vec<InLayout<'a>> =
                     bumpalo::vec![in self.env().arena];
                 arg_layouts.reserve(args.len());
@@ -224,11 +427,15 @@ trait Backend<'a> {
                         internal_error!("the argument, {:?}, has no know layout", arg);
                     }
                 }
-                self.build_jump(id, args, arg_layouts.into_bump_slice(), ret_layout);
+                self.build_jump(id, args, arg_layouts.into_bump_slice(), ret_layout)?;
                 self.free_symbols(stmt);
             }
-            x => todo!("the statement, {:?}", x),
+            Stmt::Dbg { .. } => return Err(UnsupportedConstruct::Expr("dbg")),
+            Stmt::Expect { .. } => return Err(UnsupportedConstruct::Expr("expect")),
+            Stmt::ExpectFx { .. } => return Err(UnsupportedConstruct::Expr("expect-fx")),
+            Stmt::Crash(..) => return Err(UnsupportedConstruct::Expr("crash")),
         }
+        Ok(())
     }
     // build_switch generates a instructions for a switch statement.
     fn build_switch(
@@ -238,7 +445,7 @@ trait Backend<'a> {
         branches: &'a [(u64, BranchInfo<'a>, Stmt<'a>)],
         default_branch: &(BranchInfo<'a>, &'a Stmt<'a>),
         ret_layout: &InLayout<'a>,
-    );
+    ) -> BuildResult;
 
     // build_join generates a instructions for a join statement.
     fn build_join(
@@ -248,7 +455,7 @@ trait Backend<'a> {
         body: &'a Stmt<'a>,
         remainder: &'a Stmt<'a>,
         ret_layout: &InLayout<'a>,
-    );
+    ) -> BuildResult;
 
     // build_jump generates a instructions for a jump statement.
     fn build_jump(
@@ -257,17 +464,17 @@ trait Backend<'a> {
         args: &[Symbol],
         arg_layouts: &[InLayout<'a>],
         ret_layout: &InLayout<'a>,
-    );
+    ) -> BuildResult;
 
     /// build_expr builds the expressions for the specified symbol.
     /// The builder must keep track of the symbol because it may be referred to later.
-    fn build_expr(&mut self, sym: &Symbol, expr: &Expr<'a>, layout: &InLayout<'a>) {
+    fn build_expr(&mut self, sym: &Symbol, expr: &Expr<'a>, layout: &InLayout<'a>) -> BuildResult {
         match expr {
             Expr::Literal(lit) => {
                 if self.env().lazy_literals {
                     self.literal_map().insert(*sym, (lit, layout));
                 } else {
-                    self.load_literal(sym, layout, lit);
+                    self.load_literal(sym, layout, lit)?;
                 }
             }
             Expr::Call(roc_mono::ir::Call {
@@ -291,24 +498,45 @@ trait Backend<'a> {
                                 arg_layouts,
                                 ret_layout,
                             );
-                        } else if sym.is_builtin() {
+                        } else if sym.is_builtin()
+                            || SignCondition::of_predicate(func_sym.name()).is_some()
+                        {
                             // These builtins can be built through `build_fn_call` as well, but the
                             // implementation in `build_builtin` inlines some of the symbols.
                             return self.build_builtin(
                                 sym,
-                                func_sym.name(),
+                                *func_sym,
                                 arguments,
                                 arg_layouts,
                                 ret_layout,
                             );
                         }
 
-                        let layout_id = LayoutIds::default().get(func_sym.name(), layout);
-                        let fn_name = self.symbol_to_string(func_sym.name(), layout_id);
+                        let fn_name = self.callee_symbol_name(*func_sym, arg_layouts, ret_layout);
 
                         // Now that the arguments are needed, load them if they are literals.
-                        self.load_literal_symbols(arguments);
-                        self.build_fn_call(sym, fn_name, arguments, arg_layouts, ret_layout)
+                        self.load_literal_symbols(arguments)?;
+
+                        let callee = func_sym.name();
+                        let exposed = self.env().exposed_to_host.contains(&callee)
+                            || self.env().export_names.contains_key(&callee);
+                        if self.env().runtime_context && exposed {
+                            // a procedure exposed to the host takes the context as well
+                            let context_layout = Layout::usize(self.interner().target_info());
+                            let arena = self.env().arena;
+                            let arguments = arena.alloc_slice_fill_iter(
+                                arguments.iter().copied().chain([Symbol::DEV_TMP2]),
+                            );
+                            let arg_layouts = arena.alloc_slice_fill_iter(
+                                arg_layouts.iter().copied().chain([context_layout]),
+                            );
+
+                            self.build_runtime_context_arg(&Symbol::DEV_TMP2);
+                            self.build_fn_call(sym, fn_name, arguments, arg_layouts, ret_layout)?;
+                            self.free_symbol(&Symbol::DEV_TMP2);
+                        } else {
+                            self.build_fn_call(sym, fn_name, arguments, arg_layouts, ret_layout)?;
+                        }
                     }
 
                     CallType::LowLevel { op: lowlevel, .. } => {
@@ -329,9 +557,14 @@ trait Backend<'a> {
                             arguments,
                             arg_layouts.into_bump_slice(),
                             layout,
-                        )
+                        )?;
+                    }
+                    CallType::Foreign { .. } => {
+                        return Err(UnsupportedConstruct::Expr("calls of foreign functions"))
+                    }
+                    CallType::HigherOrder(..) => {
+                        return Err(UnsupportedConstruct::Expr("higher-order builtins"))
                     }
-                    x => todo!("the call type, {:?}", x),
                 }
             }
             Expr::EmptyArray => {
@@ -347,12 +580,12 @@ trait Backend<'a> {
                 }
                 // TODO: This could be a huge waste.
                 // We probably want to call this within create_array, one element at a time.
-                self.load_literal_symbols(syms.into_bump_slice());
-                self.create_array(sym, elem_layout, elems);
+                self.load_literal_symbols(syms.into_bump_slice())?;
+                self.create_array(sym, elem_layout, elems)?;
             }
             Expr::Struct(fields) => {
-                self.load_literal_symbols(fields);
-                self.create_struct(sym, layout, fields);
+                self.load_literal_symbols(fields)?;
+                self.create_struct(sym, layout, fields)?;
             }
             Expr::StructAtIndex {
                 index,
@@ -367,13 +600,13 @@ trait Backend<'a> {
                 union_layout,
                 index,
             } => {
-                self.load_union_at_index(sym, structure, *tag_id, *index, union_layout);
+                self.load_union_at_index(sym, structure, *tag_id, *index, union_layout)?;
             }
             Expr::GetTagId {
                 structure,
                 union_layout,
             } => {
-                self.get_tag_id(sym, structure, union_layout);
+                self.get_tag_id(sym, structure, union_layout)?;
             }
             Expr::Tag {
                 tag_layout,
@@ -381,8 +614,8 @@ trait Backend<'a> {
                 arguments,
                 ..
             } => {
-                self.load_literal_symbols(arguments);
-                self.tag(sym, arguments, tag_layout, *tag_id);
+                self.load_literal_symbols(arguments)?;
+                self.tag(sym, arguments, tag_layout, *tag_id)?;
             }
             Expr::ExprBox { symbol: value } => {
                 let element_layout = match self.interner().get(*layout) {
@@ -390,17 +623,23 @@ trait Backend<'a> {
                     _ => unreachable!("{:?}", self.interner().dbg(*layout)),
                 };
 
-                self.load_literal_symbols([*value].as_slice());
-                self.expr_box(*sym, *value, element_layout)
+                self.load_literal_symbols([*value].as_slice())?;
+                self.expr_box(*sym, *value, element_layout)?;
             }
             Expr::ExprUnbox { symbol: ptr } => {
                 let element_layout = *layout;
 
-                self.load_literal_symbols([*ptr].as_slice());
-                self.expr_unbox(*sym, *ptr, element_layout)
+                self.load_literal_symbols([*ptr].as_slice())?;
+                self.expr_unbox(*sym, *ptr, element_layout)?;
+            }
+            Expr::Reset { .. } | Expr::Reuse { .. } => {
+                return Err(UnsupportedConstruct::Expr("reuse of memory"))
+            }
+            Expr::RuntimeErrorFunction(_) => {
+                return Err(UnsupportedConstruct::Expr("runtime errors"))
             }
-            x => todo!("the expression, {:?}", x),
         }
+        Ok(())
     }
 
     /// build_run_low_level builds the low level opertation and outputs to the specified symbol.
@@ -412,9 +651,15 @@ trait Backend<'a> {
         args: &'a [Symbol],
         arg_layouts: &[InLayout<'a>],
         ret_layout: &InLayout<'a>,
-    ) {
+    ) -> BuildResult {
+        if self.env().lazy_literals
+            && self.build_peephole_low_level(sym, lowlevel, args, arg_layouts, ret_layout)?
+        {
+            return Ok(());
+        }
+
         // Now that the arguments are needed, load them if they are literals.
-        self.load_literal_symbols(args);
+        self.load_literal_symbols(args)?;
         match lowlevel {
             LowLevel::NumAbs => {
                 debug_assert_eq!(
@@ -500,6 +745,22 @@ trait Backend<'a> {
                 );
                 self.build_num_div(sym, &args[0], &args[1], ret_layout)
             }
+            LowLevel::NumRemUnchecked => {
+                debug_assert_eq!(
+                    2,
+                    args.len(),
+                    "NumRem: expected to have exactly two argument"
+                );
+                debug_assert_eq!(
+                    arg_layouts[0], arg_layouts[1],
+                    "NumRem: expected all arguments of to have the same layout"
+                );
+                debug_assert_eq!(
+                    arg_layouts[0], *ret_layout,
+                    "NumRem: expected to have the same argument and return layout"
+                );
+                self.build_num_rem(sym, &args[0], &args[1], ret_layout)
+            }
             LowLevel::NumNeg => {
                 debug_assert_eq!(
                     1,
@@ -572,6 +833,27 @@ trait Backend<'a> {
                     internal_error!("bitwise xor on a non-integer")
                 }
             }
+            LowLevel::And | LowLevel::Or => {
+                debug_assert_eq!(
+                    2,
+                    args.len(),
+                    "{:?}: expected exactly two arguments",
+                    lowlevel
+                );
+                debug_assert_eq!(
+                    Layout::BOOL,
+                    *ret_layout,
+                    "{:?}: expected to have return layout of type Bool",
+                    lowlevel
+                );
+                // Roc evaluates both arguments before the call, so there is nothing to skip, and
+                // the bitwise op of two bytes that are 0 or 1 is 0 or 1 again.
+                if *lowlevel == LowLevel::And {
+                    self.build_int_bitwise_and(sym, &args[0], &args[1], IntWidth::U8)
+                } else {
+                    self.build_int_bitwise_or(sym, &args[0], &args[1], IntWidth::U8)
+                }
+            }
             LowLevel::NumShiftLeftBy => {
                 if let Layout::Builtin(Builtin::Int(int_width)) = self.interner().get(*ret_layout) {
                     self.build_int_shift_left(sym, &args[0], &args[1], int_width)
@@ -670,6 +952,14 @@ trait Backend<'a> {
                 );
                 self.build_num_to_frac(sym, &args[0], &arg_layouts[0], ret_layout)
             }
+            LowLevel::NumToIntChecked => {
+                debug_assert_eq!(
+                    1,
+                    args.len(),
+                    "NumToIntChecked: expected to have exactly one argument"
+                );
+                self.build_num_to_int_checked(sym, &args[0], &arg_layouts[0], ret_layout)
+            }
             LowLevel::NumLte => {
                 debug_assert_eq!(
                     2,
@@ -717,7 +1007,8 @@ trait Backend<'a> {
                     args.len(),
                     "ListLen: expected to have exactly one argument"
                 );
-                self.build_list_len(sym, &args[0])
+                self.build_list_len(sym, &args[0]);
+                Ok(())
             }
             LowLevel::ListWithCapacity => {
                 debug_assert_eq!(
@@ -825,7 +1116,8 @@ trait Backend<'a> {
                     args.len(),
                     "RefCountGetPtr: expected to have exactly one argument"
                 );
-                self.build_ptr_cast(sym, &args[0])
+                self.build_ptr_cast(sym, &args[0]);
+                Ok(())
             }
             LowLevel::RefCountDec => self.build_fn_call(
                 sym,
@@ -841,7 +1133,32 @@ trait Backend<'a> {
                 arg_layouts,
                 ret_layout,
             ),
-            x => todo!("low level, {:?}", x),
+            LowLevel::RuntimeContextLoad => {
+                debug_assert_eq!(
+                    1,
+                    args.len(),
+                    "RuntimeContextLoad: expected to have exactly one argument"
+                );
+                self.build_runtime_context_load(sym, &args[0], ret_layout)
+            }
+            LowLevel::RuntimeContextStore => {
+                debug_assert_eq!(
+                    2,
+                    args.len(),
+                    "RuntimeContextStore: expected to have exactly two arguments"
+                );
+                self.build_runtime_context_store(sym, &args[0], &args[1], &arg_layouts[1])
+            }
+            LowLevel::MonotonicTicks => {
+                debug_assert_eq!(
+                    0,
+                    args.len(),
+                    "MonotonicTicks: expected to have no arguments"
+                );
+                self.build_monotonic_ticks(sym);
+                Ok(())
+            }
+            x => Err(UnsupportedConstruct::LowLevel(*x)),
         }
     }
 
@@ -850,61 +1167,69 @@ trait Backend<'a> {
     fn build_builtin(
         &mut self,
         sym: &Symbol,
-        func_sym: Symbol,
+        func_name: LambdaName<'a>,
         args: &'a [Symbol],
-        arg_layouts: &[InLayout<'a>],
+        arg_layouts: &'a [InLayout<'a>],
         ret_layout: &InLayout<'a>,
-    ) {
-        match func_sym {
-            Symbol::NUM_IS_ZERO => {
+    ) -> BuildResult {
+        match func_name.name() {
+            Symbol::NUM_IS_ZERO | Symbol::NUM_IS_POSITIVE | Symbol::NUM_IS_NEGATIVE => {
                 debug_assert_eq!(
                     1,
                     args.len(),
-                    "NumIsZero: expected to have exactly one argument"
+                    "{:?}: expected to have exactly one argument",
+                    func_name.name()
                 );
                 debug_assert_eq!(
                     Layout::BOOL,
                     *ret_layout,
-                    "NumIsZero: expected to have return layout of type Bool"
+                    "{:?}: expected to have return layout of type Bool",
+                    func_name.name()
                 );
 
-                self.load_literal_symbols(args);
-                self.load_literal(
-                    &Symbol::DEV_TMP,
-                    &arg_layouts[0],
-                    &Literal::Int(0i128.to_ne_bytes()),
-                );
-                self.build_eq(sym, &args[0], &Symbol::DEV_TMP, &arg_layouts[0]);
-                self.free_symbol(&Symbol::DEV_TMP)
+                self.load_literal_symbols(args)?;
+                match self.interner().get(arg_layouts[0]) {
+                    Layout::Builtin(Builtin::Int(IntWidth::I128 | IntWidth::U128))
+                    | Layout::Builtin(Builtin::Decimal) => {
+                        // these do not fit in a register, so they call the Roc definition
+                        let fn_name = self.callee_symbol_name(func_name, arg_layouts, ret_layout);
+                        self.build_fn_call(sym, fn_name, args, arg_layouts, ret_layout)
+                    }
+                    _ => {
+                        let condition = SignCondition::of_predicate(func_name.name()).unwrap();
+                        self.build_num_sign_test(sym, &args[0], &arg_layouts[0], condition);
+                        Ok(())
+                    }
+                }
             }
             Symbol::LIST_GET | Symbol::LIST_SET | Symbol::LIST_REPLACE | Symbol::LIST_APPEND => {
                 // TODO: This is probably simple enough to be worth inlining.
-                let layout_id = LayoutIds::default().get(func_sym, ret_layout);
-                let fn_name = self.symbol_to_string(func_sym, layout_id);
+                let fn_name = self.callee_symbol_name(func_name, arg_layouts, ret_layout);
                 // Now that the arguments are needed, load them if they are literals.
-                self.load_literal_symbols(args);
+                self.load_literal_symbols(args)?;
                 self.build_fn_call(sym, fn_name, args, arg_layouts, ret_layout)
             }
             Symbol::NUM_ADD_CHECKED => {
-                let layout_id = LayoutIds::default().get(func_sym, ret_layout);
-                let fn_name = self.symbol_to_string(func_sym, layout_id);
+                let fn_name = self.callee_symbol_name(func_name, arg_layouts, ret_layout);
                 // Now that the arguments are needed, load them if they are literals.
-                self.load_literal_symbols(args);
+                self.load_literal_symbols(args)?;
                 self.build_fn_call(sym, fn_name, args, arg_layouts, ret_layout)
             }
             Symbol::BOOL_TRUE => {
                 let bool_layout = Layout::BOOL;
-                self.load_literal(&Symbol::DEV_TMP, &bool_layout, &Literal::Bool(true));
-                self.return_symbol(&Symbol::DEV_TMP, &bool_layout);
-                self.free_symbol(&Symbol::DEV_TMP)
+                self.load_literal(&Symbol::DEV_TMP, &bool_layout, &Literal::Bool(true))?;
+                self.return_symbol(&Symbol::DEV_TMP, &bool_layout)?;
+                self.free_symbol(&Symbol::DEV_TMP);
+                Ok(())
             }
             Symbol::BOOL_FALSE => {
                 let bool_layout = Layout::BOOL;
-                self.load_literal(&Symbol::DEV_TMP, &bool_layout, &Literal::Bool(false));
-                self.return_symbol(&Symbol::DEV_TMP, &bool_layout);
-                self.free_symbol(&Symbol::DEV_TMP)
+                self.load_literal(&Symbol::DEV_TMP, &bool_layout, &Literal::Bool(false))?;
+                self.return_symbol(&Symbol::DEV_TMP, &bool_layout)?;
+                self.free_symbol(&Symbol::DEV_TMP);
+                Ok(())
             }
-            _ => todo!("the function, {:?}", func_sym),
+            _ => Err(UnsupportedConstruct::Expr("calls of this builtin")),
         }
     }
 
@@ -917,13 +1242,19 @@ trait Backend<'a> {
         args: &[Symbol],
         arg_layouts: &[InLayout<'a>],
         ret_layout: &InLayout<'a>,
-    );
+    ) -> BuildResult;
 
     /// build_num_abs stores the absolute value of src into dst.
-    fn build_num_abs(&mut self, dst: &Symbol, src: &Symbol, layout: &InLayout<'a>);
+    fn build_num_abs(&mut self, dst: &Symbol, src: &Symbol, layout: &InLayout<'a>) -> BuildResult;
 
     /// build_num_add stores the sum of src1 and src2 into dst.
-    fn build_num_add(&mut self, dst: &Symbol, src1: &Symbol, src2: &Symbol, layout: &InLayout<'a>);
+    fn build_num_add(
+        &mut self,
+        dst: &Symbol,
+        src1: &Symbol,
+        src2: &Symbol,
+        layout: &InLayout<'a>,
+    ) -> BuildResult;
 
     /// build_num_add_checked stores the sum of src1 and src2 into dst.
     fn build_num_add_checked(
@@ -933,19 +1264,46 @@ trait Backend<'a> {
         src2: &Symbol,
         num_layout: &InLayout<'a>,
         return_layout: &InLayout<'a>,
-    );
+    ) -> BuildResult;
 
     /// build_num_mul stores `src1 * src2` into dst.
-    fn build_num_mul(&mut self, dst: &Symbol, src1: &Symbol, src2: &Symbol, layout: &InLayout<'a>);
+    fn build_num_mul(
+        &mut self,
+        dst: &Symbol,
+        src1: &Symbol,
+        src2: &Symbol,
+        layout: &InLayout<'a>,
+    ) -> BuildResult;
 
     /// build_num_mul stores `src1 / src2` into dst.
-    fn build_num_div(&mut self, dst: &Symbol, src1: &Symbol, src2: &Symbol, layout: &InLayout<'a>);
+    fn build_num_div(
+        &mut self,
+        dst: &Symbol,
+        src1: &Symbol,
+        src2: &Symbol,
+        layout: &InLayout<'a>,
+    ) -> BuildResult;
+
+    /// build_num_rem stores `src1 % src2` into dst, which has the sign of `src1`.
+    fn build_num_rem(
+        &mut self,
+        dst: &Symbol,
+        src1: &Symbol,
+        src2: &Symbol,
+        layout: &InLayout<'a>,
+    ) -> BuildResult;
 
     /// build_num_neg stores the negated value of src into dst.
-    fn build_num_neg(&mut self, dst: &Symbol, src: &Symbol, layout: &InLayout<'a>);
+    fn build_num_neg(&mut self, dst: &Symbol, src: &Symbol, layout: &InLayout<'a>) -> BuildResult;
 
     /// build_num_sub stores the `src1 - src2` difference into dst.
-    fn build_num_sub(&mut self, dst: &Symbol, src1: &Symbol, src2: &Symbol, layout: &InLayout<'a>);
+    fn build_num_sub(
+        &mut self,
+        dst: &Symbol,
+        src1: &Symbol,
+        src2: &Symbol,
+        layout: &InLayout<'a>,
+    ) -> BuildResult;
 
     /// build_num_sub_wrap stores the `src1 - src2` difference into dst.
     fn build_num_sub_wrap(
@@ -954,7 +1312,7 @@ trait Backend<'a> {
         src1: &Symbol,
         src2: &Symbol,
         layout: &InLayout<'a>,
-    );
+    ) -> BuildResult;
 
     /// stores the `src1 & src2` into dst.
     fn build_int_bitwise_and(
@@ -963,7 +1321,7 @@ trait Backend<'a> {
         src1: &Symbol,
         src2: &Symbol,
         int_width: IntWidth,
-    );
+    ) -> BuildResult;
 
     /// stores the `src1 | src2` into dst.
     fn build_int_bitwise_or(
@@ -972,7 +1330,7 @@ trait Backend<'a> {
         src1: &Symbol,
         src2: &Symbol,
         int_width: IntWidth,
-    );
+    ) -> BuildResult;
 
     /// stores the `src1 ^ src2` into dst.
     fn build_int_bitwise_xor(
@@ -981,7 +1339,7 @@ trait Backend<'a> {
         src1: &Symbol,
         src2: &Symbol,
         int_width: IntWidth,
-    );
+    ) -> BuildResult;
 
     /// stores the `Num.shiftLeftBy src1 src2` into dst.
     fn build_int_shift_left(
@@ -990,7 +1348,7 @@ trait Backend<'a> {
         src1: &Symbol,
         src2: &Symbol,
         int_width: IntWidth,
-    );
+    ) -> BuildResult;
 
     /// stores the `Num.shiftRightBy src1 src2` into dst.
     fn build_int_shift_right(
@@ -999,7 +1357,7 @@ trait Backend<'a> {
         src1: &Symbol,
         src2: &Symbol,
         int_width: IntWidth,
-    );
+    ) -> BuildResult;
 
     /// stores the `Num.shiftRightZfBy src1 src2` into dst.
     fn build_int_shift_right_zero_fill(
@@ -1008,13 +1366,114 @@ trait Backend<'a> {
         src1: &Symbol,
         src2: &Symbol,
         int_width: IntWidth,
-    );
+    ) -> BuildResult;
+
+    /// build_int_move stores a copy of the integer in src into dst.
+    fn build_int_move(&mut self, dst: &Symbol, src: &Symbol);
+
+    /// build_int_shift_left_imm stores `src << shift` into dst.
+    fn build_int_shift_left_imm(&mut self, dst: &Symbol, src: &Symbol, shift: u8);
+
+    /// build_int_add_shifted stores `src + (src << shift)` into dst, for a shift of at most 3.
+    fn build_int_add_shifted(&mut self, dst: &Symbol, src: &Symbol, shift: u8);
+
+    /// build_int_div_pow2 stores `src / (1 << shift)`, rounded toward zero, into dst.
+    /// src must be a 64 bit integer.
+    fn build_int_div_pow2(&mut self, dst: &Symbol, src: &Symbol, shift: u8, signed: bool);
+
+    /// build_peephole_low_level folds or strength reduces integer arithmetic on literals that
+    /// were not loaded yet; see the `peephole` module.
+    /// It returns whether it built sym, because otherwise the low level still has to be built.
+    fn build_peephole_low_level(
+        &mut self,
+        sym: &Symbol,
+        lowlevel: &LowLevel,
+        args: &[Symbol],
+        arg_layouts: &[InLayout<'a>],
+        ret_layout: &InLayout<'a>,
+    ) -> BuildResult<bool> {
+        let (lhs, rhs) = match args {
+            [lhs, rhs] => (*lhs, *rhs),
+            _ => return Ok(false),
+        };
+        let int_width = match self.interner().get(arg_layouts[0]) {
+            Layout::Builtin(Builtin::Int(int_width))
+                if !matches!(int_width, IntWidth::I128 | IntWidth::U128) =>
+            {
+                int_width
+            }
+            _ => return Ok(false),
+        };
+
+        let mut operand = |symbol: Symbol| {
+            let value = self.literal_map().get(&symbol).and_then(|(lit, _)| {
+                // The literals live as long as the procedure; see `load_literal_symbols`.
+                match unsafe { **lit } {
+                    Literal::Int(bytes) => Some(i128::from_ne_bytes(bytes)),
+                    _ => None,
+                }
+            });
+            peephole::Operand { symbol, value }
+        };
+        let (lhs, rhs) = (operand(lhs), operand(rhs));
+
+        let costs = self.cost_model();
+        let rewrite = match peephole::rewrite_int_op(*lowlevel, int_width, lhs, rhs, costs) {
+            Some(rewrite) => rewrite,
+            None => return Ok(false),
+        };
+
+        let literal = match rewrite {
+            peephole::Rewrite::Int(value) => Literal::Int(value.to_ne_bytes()),
+            peephole::Rewrite::Bool(value) => Literal::Bool(value),
+            peephole::Rewrite::Move(src) => {
+                self.load_literal_symbols(&[src])?;
+                self.build_int_move(sym, &src);
+                return Ok(true);
+            }
+            peephole::Rewrite::ShiftLeft { src, shift } => {
+                self.load_literal_symbols(&[src])?;
+                self.build_int_shift_left_imm(sym, &src, shift);
+                return Ok(true);
+            }
+            peephole::Rewrite::AddShifted { src, shift } => {
+                self.load_literal_symbols(&[src])?;
+                self.build_int_add_shifted(sym, &src, shift);
+                return Ok(true);
+            }
+            peephole::Rewrite::DivPow2 { src, shift, signed } => {
+                self.load_literal_symbols(&[src])?;
+                self.build_int_div_pow2(sym, &src, shift, signed);
+                return Ok(true);
+            }
+        };
+
+        // The result is a literal itself, which only gets loaded if something needs it.
+        let arena = self.env().arena;
+        let literal = arena.alloc(literal);
+        let layout = arena.alloc(*ret_layout);
+        self.literal_map().insert(*sym, (literal, layout));
+
+        Ok(true)
+    }
 
     /// build_eq stores the result of `src1 == src2` into dst.
-    fn build_eq(&mut self, dst: &Symbol, src1: &Symbol, src2: &Symbol, arg_layout: &InLayout<'a>);
+    fn build_eq(
+        &mut self,
+        dst: &Symbol,
+        src1: &Symbol,
+        src2: &Symbol,
+        arg_layout: &InLayout<'a>,
+    ) -> BuildResult;
 
     /// build_neq stores the result of `src1 != src2` into dst.
-    fn build_neq(&mut self, dst: &Symbol, src1: &Symbol, src2: &Symbol, arg_layout: &InLayout<'a>);
+    fn build_neq(
+        &mut self,
+        dst: &Symbol,
+        src1: &Symbol,
+        src2: &Symbol,
+        arg_layout: &InLayout<'a>,
+    ) -> BuildResult;
 
     /// build_num_lt stores the result of `src1 < src2` into dst.
     fn build_num_lt(
@@ -1023,7 +1482,7 @@ trait Backend<'a> {
         src1: &Symbol,
         src2: &Symbol,
         arg_layout: &InLayout<'a>,
-    );
+    ) -> BuildResult;
 
     /// build_num_gt stores the result of `src1 > src2` into dst.
     fn build_num_gt(
@@ -1032,7 +1491,7 @@ trait Backend<'a> {
         src1: &Symbol,
         src2: &Symbol,
         arg_layout: &InLayout<'a>,
-    );
+    ) -> BuildResult;
 
     /// build_num_to_frac convert Number to Frac
     fn build_num_to_frac(
@@ -1041,7 +1500,17 @@ trait Backend<'a> {
         src: &Symbol,
         arg_layout: &InLayout<'a>,
         ret_layout: &InLayout<'a>,
-    );
+    ) -> BuildResult;
+
+    /// build_num_to_int_checked stores `src` converted to another integer into dst,
+    /// as the `{ value, out_of_bounds }` struct that `Num.toXChecked` makes its result from.
+    fn build_num_to_int_checked(
+        &mut self,
+        dst: &Symbol,
+        src: &Symbol,
+        arg_layout: &InLayout<'a>,
+        ret_layout: &InLayout<'a>,
+    ) -> BuildResult;
 
     /// build_num_lte stores the result of `src1 <= src2` into dst.
     fn build_num_lte(
@@ -1050,7 +1519,7 @@ trait Backend<'a> {
         src1: &Symbol,
         src2: &Symbol,
         arg_layout: &InLayout<'a>,
-    );
+    ) -> BuildResult;
 
     /// build_num_gte stores the result of `src1 >= src2` into dst.
     fn build_num_gte(
@@ -1059,6 +1528,16 @@ trait Backend<'a> {
         src1: &Symbol,
         src2: &Symbol,
         arg_layout: &InLayout<'a>,
+    ) -> BuildResult;
+
+    /// build_num_sign_test stores whether `src` meets `condition` into dst,
+    /// for `Num.isZero`, `Num.isPositive` and `Num.isNegative`.
+    fn build_num_sign_test(
+        &mut self,
+        dst: &Symbol,
+        src: &Symbol,
+        arg_layout: &InLayout<'a>,
+        condition: SignCondition,
     );
 
     /// build_list_len returns the length of a list.
@@ -1072,7 +1551,7 @@ trait Backend<'a> {
         capacity_layout: InLayout<'a>,
         elem_layout: InLayout<'a>,
         ret_layout: &InLayout<'a>,
-    );
+    ) -> BuildResult;
 
     /// build_list_reserve enlarges a list to at least accommodate the given capacity.
     fn build_list_reserve(
@@ -1081,7 +1560,7 @@ trait Backend<'a> {
         args: &'a [Symbol],
         arg_layouts: &[InLayout<'a>],
         ret_layout: &InLayout<'a>,
-    );
+    ) -> BuildResult;
 
     /// build_list_append_unsafe returns a new list with a given element appended.
     fn build_list_append_unsafe(
@@ -1090,7 +1569,7 @@ trait Backend<'a> {
         args: &'a [Symbol],
         arg_layouts: &[InLayout<'a>],
         ret_layout: &InLayout<'a>,
-    );
+    ) -> BuildResult;
 
     /// build_list_get_unsafe loads the element from the list at the index.
     fn build_list_get_unsafe(
@@ -1099,7 +1578,7 @@ trait Backend<'a> {
         list: &Symbol,
         index: &Symbol,
         ret_layout: &InLayout<'a>,
-    );
+    ) -> BuildResult;
 
     /// build_list_replace_unsafe returns the old element and new list with the list having the new element inserted.
     fn build_list_replace_unsafe(
@@ -1108,7 +1587,7 @@ trait Backend<'a> {
         args: &'a [Symbol],
         arg_layouts: &[InLayout<'a>],
         ret_layout: &InLayout<'a>,
-    );
+    ) -> BuildResult;
 
     /// build_list_concat returns a new list containing the two argument lists concatenated.
     fn build_list_concat(
@@ -1118,7 +1597,7 @@ trait Backend<'a> {
         arg_layouts: &[InLayout<'a>],
         elem_layout: InLayout<'a>,
         ret_layout: &InLayout<'a>,
-    );
+    ) -> BuildResult;
 
     /// build_list_prepend returns a new list with a given element prepended.
     fn build_list_prepend(
@@ -1127,15 +1606,41 @@ trait Backend<'a> {
         args: &'a [Symbol],
         arg_layouts: &[InLayout<'a>],
         ret_layout: &InLayout<'a>,
-    );
+    ) -> BuildResult;
 
     /// build_refcount_getptr loads the pointer to the reference count of src into dst.
     fn build_ptr_cast(&mut self, dst: &Symbol, src: &Symbol);
 
+    /// Moves the context pointer in `sym` to the register that holds it, see `Env::runtime_context`
+    fn load_runtime_context(&mut self, sym: &Symbol);
+
+    /// `sym` is the context pointer, to pass it on to a procedure exposed to the host
+    fn build_runtime_context_arg(&mut self, sym: &Symbol);
+
+    /// `dst` is the value at `offset` bytes from the context pointer
+    fn build_runtime_context_load(
+        &mut self,
+        dst: &Symbol,
+        offset: &Symbol,
+        layout: &InLayout<'a>,
+    ) -> BuildResult;
+
+    /// Stores `value` at `offset` bytes from the context pointer
+    fn build_runtime_context_store(
+        &mut self,
+        dst: &Symbol,
+        offset: &Symbol,
+        value: &Symbol,
+        layout: &InLayout<'a>,
+    ) -> BuildResult;
+
+    /// `dst` is the timestamp counter of the CPU, see `Env::serialize_ticks`
+    fn build_monotonic_ticks(&mut self, dst: &Symbol);
+
     /// literal_map gets the map from symbol to literal and layout, used for lazy loading and literal folding.
     fn literal_map(&mut self) -> &mut MutMap<Symbol, (*const Literal<'a>, *const InLayout<'a>)>;
 
-    fn load_literal_symbols(&mut self, syms: &[Symbol]) {
+    fn load_literal_symbols(&mut self, syms: &[Symbol]) -> BuildResult {
         if self.env().lazy_literals {
             for sym in syms {
                 if let Some((lit, layout)) = self.literal_map().remove(sym) {
@@ -1143,14 +1648,20 @@ trait Backend<'a> {
                     // The map is reset when building a procedure and then used for that single procedure.
                     // Since the lifetime is shorter than the entire backend, we need to use a pointer.
                     let (lit, layout) = unsafe { (*lit, *layout) };
-                    self.load_literal(sym, &layout, &lit);
+                    self.load_literal(sym, &layout, &lit)?;
                 }
             }
         }
+        Ok(())
     }
 
     /// load_literal sets a symbol to be equal to a literal.
-    fn load_literal(&mut self, sym: &Symbol, layout: &InLayout<'a>, lit: &Literal<'a>);
+    fn load_literal(
+        &mut self,
+        sym: &Symbol,
+        layout: &InLayout<'a>,
+        lit: &Literal<'a>,
+    ) -> BuildResult;
 
     /// create_empty_array creates an empty array with nullptr, zero length, and zero capacity.
     fn create_empty_array(&mut self, sym: &Symbol);
@@ -1161,10 +1672,15 @@ trait Backend<'a> {
         sym: &Symbol,
         elem_layout: &InLayout<'a>,
         elems: &'a [ListLiteralElement<'a>],
-    );
+    ) -> BuildResult;
 
     /// create_struct creates a struct with the elements specified loaded into it as data.
-    fn create_struct(&mut self, sym: &Symbol, layout: &InLayout<'a>, fields: &'a [Symbol]);
+    fn create_struct(
+        &mut self,
+        sym: &Symbol,
+        layout: &InLayout<'a>,
+        fields: &'a [Symbol],
+    ) -> BuildResult;
 
     /// load_struct_at_index loads into `sym` the value at `index` in `structure`.
     fn load_struct_at_index(
@@ -1183,10 +1699,15 @@ trait Backend<'a> {
         tag_id: TagIdIntType,
         index: u64,
         union_layout: &UnionLayout<'a>,
-    );
+    ) -> BuildResult;
 
     /// get_tag_id loads the tag id from a the union.
-    fn get_tag_id(&mut self, sym: &Symbol, structure: &Symbol, union_layout: &UnionLayout<'a>);
+    fn get_tag_id(
+        &mut self,
+        sym: &Symbol,
+        structure: &Symbol,
+        union_layout: &UnionLayout<'a>,
+    ) -> BuildResult;
 
     /// tag sets the tag for a union.
     fn tag(
@@ -1195,16 +1716,18 @@ trait Backend<'a> {
         args: &'a [Symbol],
         tag_layout: &UnionLayout<'a>,
         tag_id: TagIdIntType,
-    );
+    ) -> BuildResult;
 
     /// load a value from a pointer
-    fn expr_unbox(&mut self, sym: Symbol, ptr: Symbol, element_layout: InLayout<'a>);
+    fn expr_unbox(&mut self, sym: Symbol, ptr: Symbol, element_layout: InLayout<'a>)
+        -> BuildResult;
 
     /// store a refcounted value on the heap
-    fn expr_box(&mut self, sym: Symbol, value: Symbol, element_layout: InLayout<'a>);
+    fn expr_box(&mut self, sym: Symbol, value: Symbol, element_layout: InLayout<'a>)
+        -> BuildResult;
 
     /// return_symbol moves a symbol to the correct return location for the backend and adds a jump to the end of the function.
-    fn return_symbol(&mut self, sym: &Symbol, layout: &InLayout<'a>);
+    fn return_symbol(&mut self, sym: &Symbol, layout: &InLayout<'a>) -> BuildResult;
 
     /// free_symbols will free all symbols for the given statement.
     fn free_symbols(&mut self, stmt: &Stmt<'a>) {
@@ -1219,6 +1742,15 @@ trait Backend<'a> {
     /// free_symbol frees any registers or stack space used to hold a symbol.
     fn free_symbol(&mut self, sym: &Symbol);
 
+    /// With `Env::panic_cleanup`, keeps track of the refcounted values the procedure owns, now
+    /// that `sym` is defined as `expr`.
+    fn track_owned_values(&mut self, sym: &Symbol, expr: &Expr<'a>, layout: &InLayout<'a>);
+
+    /// Picks the register `sym` is built in, going by the statement that uses it next,
+    /// so the value does not have to be moved to the register it is returned or passed in.
+    /// This is only a hint, `sym` ends up in another register if that one is not free.
+    fn hint_result_reg(&mut self, sym: &Symbol, layout: &InLayout<'a>, following: &Stmt<'a>);
+
     /// set_last_seen sets the statement a symbol was last seen in.
     fn set_last_seen(&mut self, sym: Symbol, stmt: &Stmt<'a>) {
         self.last_seen_map().insert(sym, stmt);
@@ -1247,22 +1779,18 @@ trait Backend<'a> {
     fn layout_map(&mut self) -> &mut MutMap<Symbol, InLayout<'a>>;
 
     fn create_free_map(&mut self) {
-        let mut free_map = MutMap::default();
-        let arena = self.env().arena;
+        let mut free_map: MutMap<_, std::vec::Vec<_>> = MutMap::default();
         for (sym, stmt) in self.last_seen_map() {
-            let vals = free_map
-                .entry(*stmt)
-                .or_insert_with(|| bumpalo::vec![in arena]);
-            vals.push(*sym);
+            free_map.entry(*stmt).or_default().push(*sym);
         }
         self.set_free_map(free_map);
     }
 
     /// free_map gets the map statement to the symbols that are free after they run.
-    fn free_map(&mut self) -> &mut MutMap<*const Stmt<'a>, Vec<'a, Symbol>>;
+    fn free_map(&mut self) -> &mut MutMap<*const Stmt<'a>, std::vec::Vec<Symbol>>;
 
     /// set_free_map sets the free map to the given map.
-    fn set_free_map(&mut self, map: MutMap<*const Stmt<'a>, Vec<'a, Symbol>>);
+    fn set_free_map(&mut self, map: MutMap<*const Stmt<'a>, std::vec::Vec<Symbol>>);
 
     /// scan_ast runs through the ast and fill the last seen map.
     /// This must iterate through the ast in the same way that build_stmt does. i.e. then before else.
@@ -1374,11 +1902,8 @@ trait Backend<'a> {
                 }
             }
 
-            Stmt::Dbg { .. } => todo!("dbg not implemented in the dev backend"),
-            Stmt::Expect { .. } => todo!("expect is not implemented in the dev backend"),
-            Stmt::ExpectFx { .. } => todo!("expect-fx is not implemented in the dev backend"),
-
-            Stmt::Crash(..) => todo!("crash is not implemented in the dev backend"),
+            // `build_stmt` reports these as unsupported.
+            Stmt::Dbg { .. } | Stmt::Expect { .. } | Stmt::ExpectFx { .. } | Stmt::Crash(..) => {}
         }
     }
 
@@ -0,0 +1,176 @@
+//! Listings of the code of procedures, with every group of instructions under the statement of
+//! the IR it came from, for `Env::statement_markers`. That shows which statement produced which
+//! instructions, e.g. when looking for a miscompile.
+//!
+//! The backend records where the code of every statement starts, before it lowers the statement.
+//! The offsets move along with the code, like relocations do, so they are offsets into the
+//! finished procedure. A statement that needs no code, like a literal that is loaded lazily, has
+//! an empty region. A statement that contains others, like a switch, only covers the code up to
+//! where the first of those starts.
+use roc_module::symbol::{Interns, Symbol};
+use roc_mono::ir::{Call, CallType, Expr, Stmt};
+use std::fmt::Write;
+
+/// Where the code of a statement starts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatementMarker {
+    /// the offset from the start of the procedure
+    pub offset: u64,
+    /// the statement, without the statements that follow it or are nested in it
+    pub statement: String,
+}
+
+/// The code of a procedure, and where the code of each of its statements starts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcListing {
+    /// the symbol of the procedure, as it is in the symbol table
+    pub name: String,
+    pub code: std::vec::Vec<u8>,
+    /// in the order of their offsets
+    pub markers: std::vec::Vec<StatementMarker>,
+}
+
+/// The code between two markers
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListingRegion<'a> {
+    /// `None` for the code in front of the first statement, like the prologue
+    pub statement: Option<&'a str>,
+    pub offset: u64,
+    pub code: &'a [u8],
+}
+
+impl ProcListing {
+    /// The code of every statement, in order. The last region runs to the end of the procedure,
+    /// so it has the epilogue as well.
+    pub fn regions(&self) -> std::vec::Vec<ListingRegion<'_>> {
+        let starts = std::iter::once((None, 0))
+            .chain(
+                self.markers
+                    .iter()
+                    .map(|marker| (Some(marker.statement.as_str()), marker.offset)),
+            )
+            .collect::<std::vec::Vec<_>>();
+
+        starts
+            .iter()
+            .enumerate()
+            .map(|(index, &(statement, offset))| {
+                let end = starts
+                    .get(index + 1)
+                    .map_or(self.code.len() as u64, |(_, end)| *end);
+
+                ListingRegion {
+                    statement,
+                    offset,
+                    code: &self.code[offset as usize..end as usize],
+                }
+            })
+            .collect()
+    }
+
+    /// The listing as text, with every statement on a line that starts with `;`, followed by its
+    /// instructions. `disassemble` gets the code of a region and its offset, and returns its
+    /// instructions, one per line. The backend has no disassembler of its own.
+    pub fn render(
+        &self,
+        mut disassemble: impl FnMut(&[u8], u64) -> std::vec::Vec<String>,
+    ) -> String {
+        let mut listing = format!("{}:\n", self.name);
+        for region in self.regions() {
+            if let Some(statement) = region.statement {
+                writeln!(listing, "; {}", statement).unwrap();
+            }
+            for inst in disassemble(region.code, region.offset) {
+                writeln!(listing, "    {}", inst).unwrap();
+            }
+        }
+
+        listing
+    }
+}
+
+/// The name of `symbol`, or its number if the interns do not know it
+fn symbol_name(symbol: Symbol, interns: &Interns) -> String {
+    interns
+        .all_ident_ids
+        .get(&symbol.module_id())
+        .and_then(|ident_ids| ident_ids.get_name(symbol.ident_id()))
+        .map_or_else(|| format!("{:?}", symbol), |name| name.to_string())
+}
+
+fn symbol_names(symbols: &[Symbol], interns: &Interns) -> String {
+    symbols
+        .iter()
+        .map(|symbol| format!(" {}", symbol_name(*symbol, interns)))
+        .collect()
+}
+
+/// The first line of `stmt`, e.g. `let sum = lowlevel NumAdd x one`
+pub(crate) fn describe_statement(stmt: &Stmt<'_>, interns: &Interns) -> String {
+    match stmt {
+        Stmt::Let(symbol, expr, _, _) => format!(
+            "let {} = {}",
+            symbol_name(*symbol, interns),
+            describe_expr(expr, interns)
+        ),
+        Stmt::Switch { cond_symbol, .. } => {
+            format!("switch {}", symbol_name(*cond_symbol, interns))
+        }
+        Stmt::Ret(symbol) => format!("ret {}", symbol_name(*symbol, interns)),
+        Stmt::Refcounting(..) => "refcounting".to_string(),
+        Stmt::Join { id, parameters, .. } => {
+            let parameters: std::vec::Vec<_> =
+                parameters.iter().map(|param| param.symbol).collect();
+            format!(
+                "joinpoint {}{}",
+                symbol_name(id.0, interns),
+                symbol_names(&parameters, interns)
+            )
+        }
+        Stmt::Jump(id, arguments) => format!(
+            "jump {}{}",
+            symbol_name(id.0, interns),
+            symbol_names(arguments, interns)
+        ),
+        Stmt::Dbg { .. } => "dbg".to_string(),
+        Stmt::Expect { .. } => "expect".to_string(),
+        Stmt::ExpectFx { .. } => "expect-fx".to_string(),
+        Stmt::Crash(..) => "crash".to_string(),
+    }
+}
+
+/// Literals and calls as the IR prints them, and the kind of any other expression
+fn describe_expr(expr: &Expr<'_>, interns: &Interns) -> String {
+    match expr {
+        Expr::Literal(_) => expr.to_pretty(80, false).trim_end().to_string(),
+        Expr::Call(Call {
+            call_type,
+            arguments,
+        }) => {
+            let callee = match call_type {
+                CallType::ByName { name, .. } => {
+                    format!("CallByName {}", symbol_name(name.name(), interns))
+                }
+                CallType::LowLevel { op, .. } => format!("lowlevel {:?}", op),
+                CallType::HigherOrder(higher_order) => format!("lowlevel {:?}", higher_order.op),
+                CallType::Foreign { foreign_symbol, .. } => {
+                    format!("foreign {:?}", foreign_symbol.as_str())
+                }
+            };
+
+            format!("{}{}", callee, symbol_names(arguments, interns))
+        }
+        Expr::Tag { .. } => "Tag".to_string(),
+        Expr::Struct(_) => "Struct".to_string(),
+        Expr::StructAtIndex { .. } => "StructAtIndex".to_string(),
+        Expr::GetTagId { .. } => "GetTagId".to_string(),
+        Expr::UnionAtIndex { .. } => "UnionAtIndex".to_string(),
+        Expr::Array { .. } => "Array".to_string(),
+        Expr::EmptyArray => "Array []".to_string(),
+        Expr::ExprBox { .. } => "Box".to_string(),
+        Expr::ExprUnbox { .. } => "Unbox".to_string(),
+        Expr::Reuse { .. } => "Reuse".to_string(),
+        Expr::Reset { .. } => "Reset".to_string(),
+        Expr::RuntimeErrorFunction(_) => "RuntimeErrorFunction".to_string(),
+    }
+}
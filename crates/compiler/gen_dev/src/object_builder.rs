@@ -1,12 +1,18 @@
-use crate::generic64::{aarch64, new_backend_64bit, x86_64};
-use crate::{Backend, Env, Relocation};
-use bumpalo::collections::Vec;
+use crate::generic64::{aarch64, new_backend_64bit, x86_64, Assembler, CallConv, RegTrait};
+use crate::inline::inline_small_procs;
+use crate::listing::{ProcListing, StatementMarker};
+use crate::panic_cleanup::{emit_cleanup_table, CallCleanup};
+use crate::refcounting::expand_refcounting;
+use crate::unsupported::UnsupportedConstruct;
+use crate::{proc_symbol_name, Backend, CodeGenStats, Env, Relocation, UnwindInfo};
+use bumpalo::Bump;
 use object::write::{self, SectionId, SymbolId};
 use object::write::{Object, StandardSection, StandardSegment, Symbol, SymbolSection};
 use object::{
     Architecture, BinaryFormat, Endianness, RelocationEncoding, RelocationKind, SectionKind,
     SymbolFlags, SymbolKind, SymbolScope,
 };
+use roc_builtins::host_symbols::HostSymbols;
 use roc_collections::all::MutMap;
 use roc_error_macros::internal_error;
 use roc_module::symbol;
@@ -14,110 +20,499 @@ use roc_module::symbol::Interns;
 use roc_mono::ir::{Proc, ProcLayout};
 use roc_mono::layout::{LayoutIds, STLayoutInterner};
 use roc_target::TargetInfo;
+use std::cell::{Cell, RefCell};
+use std::time::Instant;
 use target_lexicon::{Architecture as TargetArch, BinaryFormat as TargetBF, Triple};
 
-// This is used by some code below which is currently commented out.
-// See that code for more details!
-// const VERSION: &str = env!("CARGO_PKG_VERSION");
-
 /// build_module is the high level builder/delegator.
 /// It takes the request to build a module and output the object file for the module.
+/// The procedures that use something the dev backend does not support yet are the error,
+/// see `describe_unsupported`.
 pub fn build_module<'a, 'r>(
     env: &'r Env<'a>,
     interns: &'r mut Interns,
     layout_interner: &'r mut STLayoutInterner<'a>,
     target: &Triple,
     procedures: MutMap<(symbol::Symbol, ProcLayout<'a>), Proc<'a>>,
-) -> Object<'a> {
-    match target {
+) -> Result<Object<'a>, std::vec::Vec<UnsupportedError<'a>>> {
+    let (output, _) = try_build_module(env, interns, layout_interner, target, procedures)?;
+    Ok(output)
+}
+
+/// An object file the dev backend built
+#[derive(Debug)]
+pub struct ObjectOutput {
+    pub bytes: std::vec::Vec<u8>,
+    /// the procedures the host can call, by their names in the symbol table
+    pub exposed_symbols: std::vec::Vec<String>,
+    /// a copy of `env.stats` after the build
+    pub stats: CodeGenStats,
+}
+
+/// A procedure that uses something the dev backend does not support yet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnsupportedError<'a> {
+    pub symbol: symbol::Symbol,
+    pub layout: ProcLayout<'a>,
+    /// the symbol the procedure would have had in the object
+    pub name: String,
+    /// the first thing in the procedure the backend could not build
+    pub construct: UnsupportedConstruct,
+}
+
+/// A report of every unsupported procedure. It starts with what the procedures use, each
+/// construct once with the number of procedures that use it, most common first. Every procedure
+/// follows on a line of its own.
+pub fn describe_unsupported(unsupported: &[UnsupportedError<'_>]) -> String {
+    let mut counts: std::vec::Vec<(&UnsupportedConstruct, usize)> = std::vec::Vec::new();
+    for proc in unsupported {
+        match counts
+            .iter_mut()
+            .find(|(construct, _)| **construct == proc.construct)
+        {
+            Some((_, count)) => *count += 1,
+            None => counts.push((&proc.construct, 1)),
+        }
+    }
+    // stable, so constructs that are used equally often stay in the order they came up
+    counts.sort_by(|a, b| b.1.cmp(&a.1));
+
+    let summary: std::vec::Vec<_> = counts
+        .iter()
+        .map(|(construct, count)| match count {
+            1 => format!("{} (1 proc)", construct),
+            _ => format!("{} ({} procs)", construct, count),
+        })
+        .collect();
+    let mut report = format!(
+        "the dev backend doesn't support yet: {}",
+        summary.join(", ")
+    );
+    for proc in unsupported {
+        report.push_str(&format!("\n    {}: {}", proc.name, proc.construct));
+    }
+
+    report
+}
+
+/// Builds the procedures of a module into an object file for `target`, like `build_procedures`
+/// in `roc_gen_llvm`. A procedure the backend cannot build does not stop it from building the
+/// others, and all of them are returned together. Only procedures the host can reach count.
+pub fn build_procedures<'a>(
+    env: &Env<'a>,
+    interns: &mut Interns,
+    layout_interner: &mut STLayoutInterner<'a>,
+    target: &Triple,
+    procedures: MutMap<(symbol::Symbol, ProcLayout<'a>), Proc<'a>>,
+) -> Result<ObjectOutput, std::vec::Vec<UnsupportedError<'a>>> {
+    let (output, exposed_symbols) =
+        try_build_module(env, interns, layout_interner, target, procedures)?;
+
+    let writing_started = env.stats.borrow().is_timed().then(Instant::now);
+    let bytes = match output.write() {
+        Ok(bytes) => bytes,
+        Err(error) => internal_error!("failed to write the object file: {:?}", error),
+    };
+    if let Some(started) = writing_started {
+        env.stats
+            .borrow_mut()
+            .record_object_writing_time(started.elapsed());
+    }
+
+    Ok(ObjectOutput {
+        bytes,
+        exposed_symbols,
+        stats: env.stats.borrow().clone(),
+    })
+}
+
+/// The object for `target`, along with the names of the exposed procedures
+type ModuleResult<'a> =
+    Result<(Object<'a>, std::vec::Vec<String>), std::vec::Vec<UnsupportedError<'a>>>;
+
+fn try_build_module<'a>(
+    env: &Env<'a>,
+    interns: &mut Interns,
+    layout_interner: &mut STLayoutInterner<'a>,
+    target: &Triple,
+    mut procedures: MutMap<(symbol::Symbol, ProcLayout<'a>), Proc<'a>>,
+) -> ModuleResult<'a> {
+    if env.inline_small_procs {
+        let ident_ids = interns.all_ident_ids.get_mut(&env.module_id).unwrap();
+        let inlined = inline_small_procs(env.arena, env.module_id, ident_ids, &mut procedures);
+        env.stats.borrow_mut().record_inlined_calls(inlined);
+    }
+
+    let built = match target {
         Triple {
             architecture: TargetArch::X86_64,
             binary_format: TargetBF::Elf,
             ..
-        } if cfg!(feature = "target-x86_64") => {
-            let backend = new_backend_64bit::<
-                x86_64::X86_64GeneralReg,
-                x86_64::X86_64FloatReg,
-                x86_64::X86_64Assembler,
-                x86_64::X86_64SystemV,
-            >(env, TargetInfo::default_x86_64(), interns, layout_interner);
-            build_object(
-                procedures,
-                backend,
-                Object::new(BinaryFormat::Elf, Architecture::X86_64, Endianness::Little),
-            )
-        }
+        } if cfg!(feature = "target-x86_64") => build_object::<
+            x86_64::X86_64GeneralReg,
+            x86_64::X86_64FloatReg,
+            x86_64::X86_64Assembler,
+            x86_64::X86_64SystemV,
+        >(
+            env,
+            TargetInfo::default_x86_64(),
+            interns,
+            layout_interner,
+            procedures,
+            new_object(
+                BinaryFormat::Elf,
+                Architecture::X86_64,
+                TargetInfo::default_x86_64(),
+            ),
+        ),
         Triple {
             architecture: TargetArch::X86_64,
             binary_format: TargetBF::Macho,
             ..
-        } if cfg!(feature = "target-x86_64") => {
-            let backend = new_backend_64bit::<
-                x86_64::X86_64GeneralReg,
-                x86_64::X86_64FloatReg,
-                x86_64::X86_64Assembler,
-                x86_64::X86_64SystemV,
-            >(env, TargetInfo::default_x86_64(), interns, layout_interner);
-            build_object(
-                procedures,
-                backend,
-                Object::new(
-                    BinaryFormat::MachO,
-                    Architecture::X86_64,
-                    Endianness::Little,
-                ),
-            )
-        }
+        } if cfg!(feature = "target-x86_64") => build_object::<
+            x86_64::X86_64GeneralReg,
+            x86_64::X86_64FloatReg,
+            x86_64::X86_64Assembler,
+            x86_64::X86_64SystemV,
+        >(
+            env,
+            TargetInfo::default_x86_64(),
+            interns,
+            layout_interner,
+            procedures,
+            new_object(
+                BinaryFormat::MachO,
+                Architecture::X86_64,
+                TargetInfo::default_x86_64(),
+            ),
+        ),
         Triple {
             architecture: TargetArch::Aarch64(_),
             binary_format: TargetBF::Elf,
             ..
-        } if cfg!(feature = "target-aarch64") => {
-            let backend =
-                new_backend_64bit::<
-                    aarch64::AArch64GeneralReg,
-                    aarch64::AArch64FloatReg,
-                    aarch64::AArch64Assembler,
-                    aarch64::AArch64Call,
-                >(env, TargetInfo::default_aarch64(), interns, layout_interner);
-            build_object(
-                procedures,
-                backend,
-                Object::new(BinaryFormat::Elf, Architecture::Aarch64, Endianness::Little),
-            )
-        }
+        } if cfg!(feature = "target-aarch64") => build_object::<
+            aarch64::AArch64GeneralReg,
+            aarch64::AArch64FloatReg,
+            aarch64::AArch64Assembler,
+            aarch64::AArch64Call,
+        >(
+            env,
+            TargetInfo::default_aarch64(),
+            interns,
+            layout_interner,
+            procedures,
+            new_object(
+                BinaryFormat::Elf,
+                Architecture::Aarch64,
+                TargetInfo::default_aarch64(),
+            ),
+        ),
         Triple {
             architecture: TargetArch::Aarch64(_),
             binary_format: TargetBF::Macho,
             ..
-        } if cfg!(feature = "target-aarch64") => {
-            let backend =
-                new_backend_64bit::<
-                    aarch64::AArch64GeneralReg,
-                    aarch64::AArch64FloatReg,
-                    aarch64::AArch64Assembler,
-                    aarch64::AArch64Call,
-                >(env, TargetInfo::default_aarch64(), interns, layout_interner);
-            build_object(
-                procedures,
-                backend,
-                Object::new(
-                    BinaryFormat::MachO,
-                    Architecture::Aarch64,
-                    Endianness::Little,
-                ),
-            )
-        }
+        } if cfg!(feature = "target-aarch64") => build_object::<
+            aarch64::AArch64GeneralReg,
+            aarch64::AArch64FloatReg,
+            aarch64::AArch64Assembler,
+            aarch64::AArch64Call,
+        >(
+            env,
+            TargetInfo::default_aarch64(),
+            interns,
+            layout_interner,
+            procedures,
+            new_object(
+                BinaryFormat::MachO,
+                Architecture::Aarch64,
+                TargetInfo::default_aarch64(),
+            ),
+        ),
         x => unimplemented!("the target, {:?}", x),
+    };
+
+    built.map(|(mut output, exposed_symbols)| {
+        emit_producer(&mut output, target);
+        (output, exposed_symbols)
+    })
+}
+
+/// An empty object, in the byte order of `target_info`
+fn new_object<'a>(
+    format: BinaryFormat,
+    architecture: Architecture,
+    target_info: TargetInfo,
+) -> Object<'a> {
+    let endianness = match target_info.endianness() {
+        roc_target::Endianness::Little => Endianness::Little,
+        roc_target::Endianness::Big => Endianness::Big,
+    };
+
+    Object::new(format, architecture, endianness)
+}
+
+/// How many bytes a procedure or wrapper takes up in the object
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcSize {
+    pub name: String,
+    /// the machine code, which is all there is in the section of a procedure
+    pub code_bytes: usize,
+    /// the literals it loads from the data section
+    pub data_bytes: usize,
+}
+
+/// Where a runtime can redirect a procedure to a newer version of it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Patchpoint {
+    /// the symbol of the procedure, as it is in the symbol table
+    pub name: String,
+    /// where the patchpoint is, from the start of the procedure
+    pub offset: u64,
+    pub size: u64,
+}
+
+/// The section of the patchpoint table. Mach-O does not allow section names that are this long.
+fn patchpoint_section_name(format: BinaryFormat) -> &'static str {
+    match format {
+        BinaryFormat::MachO => "__roc_patchpts",
+        _ => ".roc_patchpoints",
+    }
+}
+
+/// Lists the patchpoints of the procedures in a section of their own. Every entry is 16 bytes:
+/// the address of the patchpoint, which a relocation fills in, followed by its size.
+fn emit_patchpoint_table(
+    output: &mut Object,
+    procs: &[GeneratedProc<'_>],
+    size: usize,
+    endianness: roc_target::Endianness,
+) {
+    let section_id = output.add_section(
+        output.segment_name(StandardSegment::Data).to_vec(),
+        patchpoint_section_name(output.format()).as_bytes().to_vec(),
+        SectionKind::Data,
+    );
+
+    for proc in procs {
+        let mut entry = [0; 16];
+        entry[8..].copy_from_slice(&endianness.u64_bytes(size as u64));
+        let entry_offset = output.append_section_data(section_id, &entry, 8);
+
+        let symbol = match output.symbol_id(proc.fn_name.as_bytes()) {
+            Some(symbol) => symbol,
+            None => internal_error!("failed to find fn symbol for {:?}", proc.fn_name),
+        };
+        // the patchpoint is the first thing in a procedure
+        let reloc = write::Relocation {
+            offset: entry_offset,
+            size: 64,
+            kind: RelocationKind::Absolute,
+            encoding: RelocationEncoding::Generic,
+            symbol,
+            addend: 0,
+        };
+        if let Err(e) = output.add_relocation(section_id, reloc) {
+            internal_error!("{:?}", e);
+        }
+    }
+}
+
+/// Adds the `.pdata` and `.xdata` Windows unwinds through the procedures with. Every procedure
+/// with unwind info gets a `RUNTIME_FUNCTION` in `.pdata`: where the code it covers starts and
+/// ends, and where its `UNWIND_INFO` is in `.xdata`, all relative to the image base. The code
+/// starts at the prologue, since what comes before it, like a patchpoint, leaves the stack alone.
+fn emit_unwind_tables(output: &mut Object, procs: &[GeneratedProc<'_>]) {
+    if procs.iter().all(|proc| proc.unwind_info.is_none()) {
+        return;
+    }
+    if output.format() != BinaryFormat::Coff {
+        internal_error!(
+            "only COFF objects have unwind tables, not {:?}",
+            output.format()
+        );
+    }
+
+    let segment = output.segment_name(StandardSegment::Data).to_vec();
+    let xdata = output.add_section(
+        segment.clone(),
+        b".xdata".to_vec(),
+        SectionKind::ReadOnlyData,
+    );
+    let pdata = output.add_section(segment, b".pdata".to_vec(), SectionKind::ReadOnlyData);
+    let xdata_symbol = output.section_symbol(xdata);
+
+    for proc in procs {
+        let unwind_info = match proc.unwind_info {
+            Some(unwind_info) => unwind_info,
+            None => continue,
+        };
+        let proc_symbol = match output.symbol_id(proc.fn_name.as_bytes()) {
+            Some(symbol) => symbol,
+            None => internal_error!("failed to find fn symbol for {:?}", proc.fn_name),
+        };
+
+        let info_offset = output.append_section_data(xdata, &unwind_info.info, 4);
+        let entry_offset = output.append_section_data(pdata, &[0; 12], 4);
+        for (field_offset, symbol, addend) in [
+            (0, proc_symbol, unwind_info.prologue_offset),
+            (4, proc_symbol, proc.code.len() as u64),
+            (8, xdata_symbol, info_offset),
+        ] {
+            let reloc = write::Relocation {
+                offset: entry_offset + field_offset,
+                size: 32,
+                kind: RelocationKind::ImageOffset,
+                encoding: RelocationEncoding::Generic,
+                symbol,
+                addend: addend as i64,
+            };
+            if let Err(e) = output.add_relocation(pdata, reloc) {
+                internal_error!("{:?}", e);
+            }
+        }
+    }
+}
+
+/// The patchpoints in an object that was built with `Env::patchpoints`, in the order of the table
+pub fn read_patchpoints(bytes: &[u8]) -> Result<std::vec::Vec<Patchpoint>, object::Error> {
+    use object::{Object as _, ObjectSection, ObjectSymbol, RelocationTarget};
+
+    let file = object::File::parse(bytes)?;
+    let section = match file.section_by_name(patchpoint_section_name(file.format())) {
+        Some(section) => section,
+        None => return Ok(std::vec::Vec::new()),
+    };
+    let data = section.data()?;
+    let endianness = if file.is_little_endian() {
+        roc_target::Endianness::Little
+    } else {
+        roc_target::Endianness::Big
+    };
+    let word = |offset: u64| {
+        let start = offset as usize;
+        endianness.u64_from_bytes(data[start..start + 8].try_into().unwrap())
+    };
+
+    let mut patchpoints = std::vec::Vec::new();
+    for (entry_offset, reloc) in section.relocations() {
+        if let RelocationTarget::Symbol(index) = reloc.target() {
+            let offset = if reloc.has_implicit_addend() {
+                word(entry_offset)
+            } else {
+                reloc.addend() as u64
+            };
+
+            patchpoints.push(Patchpoint {
+                name: file.symbol_by_index(index)?.name()?.to_string(),
+                offset,
+                size: word(entry_offset + 8),
+            });
+        }
+    }
+
+    Ok(patchpoints)
+}
+
+/// The section that names the producer of the object. ELF has `.comment` for this, which linkers
+/// merge. The other formats have nothing the object crate can write, so they get one of their own.
+fn producer_section_name(format: BinaryFormat) -> &'static str {
+    match format {
+        BinaryFormat::MachO => "__roc_producer",
+        _ => ".comment",
+    }
+}
+
+/// Adds the producer of the object, as `roc_target::producer` has it, as a string that ends in 0
+fn emit_producer(output: &mut Object, target: &Triple) {
+    let section_id = output.add_section(
+        output.segment_name(StandardSegment::Data).to_vec(),
+        producer_section_name(output.format()).as_bytes().to_vec(),
+        SectionKind::OtherString,
+    );
+
+    let mut producer = roc_target::producer("dev", target).into_bytes();
+    producer.push(0);
+    output.append_section_data(section_id, &producer, 1);
+}
+
+/// The producer of an object the dev backend built, if it has one. The `.comment` of an ELF file
+/// that was linked has the strings of the other compilers too, so this is the first one of Roc.
+pub fn read_producer(bytes: &[u8]) -> Result<Option<String>, object::Error> {
+    use object::{Object as _, ObjectSection};
+
+    let file = object::File::parse(bytes)?;
+    let section = match file.section_by_name(producer_section_name(file.format())) {
+        Some(section) => section,
+        None => return Ok(None),
+    };
+
+    let producer = section
+        .data()?
+        .split(|byte| *byte == 0)
+        .map(String::from_utf8_lossy)
+        .find(|string| string.starts_with("roc "))
+        .map(|string| string.into_owned());
+
+    Ok(producer)
+}
+
+/// Where procedures start in their sections, and what goes between them
+#[derive(Clone, Copy)]
+struct ProcPadding<'a> {
+    arena: &'a Bump,
+    /// see `Env::proc_alignment`
+    alignment: u64,
+    /// `Assembler::padding` of the target
+    fill: fn(&mut bumpalo::collections::Vec<'_, u8>, usize),
+}
+
+impl<'a> ProcPadding<'a> {
+    fn new(
+        arena: &'a Bump,
+        alignment: u64,
+        fill: fn(&mut bumpalo::collections::Vec<'_, u8>, usize),
+    ) -> Self {
+        if !alignment.is_power_of_two() {
+            internal_error!("procedures can not be aligned to {} bytes", alignment);
+        }
+
+        ProcPadding {
+            arena,
+            alignment,
+            fill,
+        }
+    }
+
+    /// Adds `code` to the section as the data of `symbol`, followed by the padding up to where
+    /// the next procedure starts, and returns the offset of the code. Only the code is part of
+    /// the symbol, so relocations and the size of the symbol are the same as without padding.
+    fn add_code(
+        &self,
+        output: &mut Object,
+        symbol: SymbolId,
+        section_id: SectionId,
+        code: &[u8],
+    ) -> u64 {
+        let offset = output.add_symbol_data(symbol, section_id, code, self.alignment);
+        let end = (offset + code.len() as u64) % self.alignment;
+        if end != 0 {
+            let mut padding = bumpalo::collections::Vec::new_in(self.arena);
+            (self.fill)(&mut padding, (self.alignment - end) as usize);
+            output.append_section_data(section_id, &padding, 1);
+        }
+
+        offset
     }
 }
 
+/// Adds a wrapper that jumps to `wraps`, and returns the size of its code.
 fn generate_wrapper<'a, B: Backend<'a>>(
     backend: &mut B,
     output: &mut Object,
+    padding: &ProcPadding<'_>,
     wrapper_name: String,
     wraps: String,
-) {
+) -> usize {
     let text_section = output.section_id(StandardSection::Text);
     let proc_symbol = Symbol {
         name: wrapper_name.as_bytes().to_vec(),
@@ -131,21 +526,25 @@ fn generate_wrapper<'a, B: Backend<'a>>(
     };
     let proc_id = output.add_symbol(proc_symbol);
     let (proc_data, offset) = backend.build_wrapped_jmp();
-    let proc_offset = output.add_symbol_data(proc_id, text_section, proc_data, 16);
+    let code_bytes = proc_data.len();
+    let proc_offset = padding.add_code(output, proc_id, text_section, proc_data);
 
     let name = wraps.as_str().as_bytes();
-    // If the symbol is an undefined zig builtin, we need to add it here.
-    let symbol = Symbol {
-        name: name.to_vec(),
-        value: 0,
-        size: 0,
-        kind: SymbolKind::Text,
-        scope: SymbolScope::Dynamic,
-        weak: true,
-        section: SymbolSection::Undefined,
-        flags: SymbolFlags::None,
-    };
-    output.add_symbol(symbol);
+    // If the symbol is an undefined zig builtin, we need to add it here. It may also be another
+    // wrapper, e.g. an allocator that an alias jumps to.
+    if output.symbol_id(name).is_none() {
+        let symbol = Symbol {
+            name: name.to_vec(),
+            value: 0,
+            size: 0,
+            kind: SymbolKind::Text,
+            scope: SymbolScope::Dynamic,
+            weak: true,
+            section: SymbolSection::Undefined,
+            flags: SymbolFlags::None,
+        };
+        output.add_symbol(symbol);
+    }
     if let Some(sym_id) = output.symbol_id(name) {
         let reloc = write::Relocation {
             offset: offset + proc_offset,
@@ -163,149 +562,585 @@ fn generate_wrapper<'a, B: Backend<'a>>(
     } else {
         internal_error!("failed to find fn symbol for {:?}", wraps);
     }
+
+    code_bytes
 }
 
-fn build_object<'a, B: Backend<'a>>(
+fn build_object<'a, GeneralReg, FloatReg, ASM, CC>(
+    env: &Env<'a>,
+    target_info: TargetInfo,
+    interns: &mut Interns,
+    layout_interner: &mut STLayoutInterner<'a>,
     procedures: MutMap<(symbol::Symbol, ProcLayout<'a>), Proc<'a>>,
-    mut backend: B,
     mut output: Object<'a>,
-) -> Object<'a> {
+) -> ModuleResult<'a>
+where
+    GeneralReg: RegTrait,
+    FloatReg: RegTrait,
+    ASM: Assembler<GeneralReg, FloatReg>,
+    CC: CallConv<GeneralReg, FloatReg, ASM>,
+{
     let data_section = output.section_id(StandardSection::Data);
+    let padding = ProcPadding::new(env.arena, env.proc_alignment, ASM::padding);
+    // One aligned store has to overwrite the whole patchpoint.
+    if env.patchpoints && env.proc_alignment < ASM::PATCHPOINT_SIZE as u64 {
+        internal_error!(
+            "patchpoints need procedures that are aligned to at least {} bytes",
+            ASM::PATCHPOINT_SIZE
+        );
+    }
 
-    let arena = backend.env().arena;
-
-    /*
-    // Commented out because we couldn't figure out how to get it to work on mac - see https://github.com/roc-lang/roc/pull/1323
-    let comment = output.add_section(vec![], b".comment".to_vec(), SectionKind::OtherString);
-    output.append_section_data(
-        comment,
-        format!("\0roc dev backend version {} \0", VERSION).as_bytes(),
-        1,
-    );
-    */
+    if env.generate_allocators {
+        let mut backend = new_backend_64bit::<GeneralReg, FloatReg, ASM, CC>(
+            env,
+            target_info,
+            interns,
+            layout_interner,
+        );
+        let symbols = env.host_symbols;
+        for (wrapper_name, wraps) in [
+            (symbols.alloc, "malloc"),
+            (symbols.realloc, "realloc"),
+            (symbols.dealloc, "free"),
+            (symbols.panic, "roc_builtins.utils.test_panic"),
+        ] {
+            let code_bytes = generate_wrapper(
+                &mut backend,
+                &mut output,
+                &padding,
+                wrapper_name.into(),
+                wraps.into(),
+            );
+            env.proc_sizes.borrow_mut().push(ProcSize {
+                name: wrapper_name.into(),
+                code_bytes,
+                data_bytes: 0,
+            });
+        }
+    }
 
-    if backend.env().generate_allocators {
-        generate_wrapper(
-            &mut backend,
-            &mut output,
-            "roc_alloc".into(),
-            "malloc".into(),
+    if env.alias_host_symbols {
+        let mut backend = new_backend_64bit::<GeneralReg, FloatReg, ASM, CC>(
+            env,
+            target_info,
+            interns,
+            layout_interner,
         );
-        generate_wrapper(
-            &mut backend,
-            &mut output,
-            "roc_realloc".into(),
-            "realloc".into(),
+        for (standard_name, name) in env.host_symbols.renamed() {
+            let code_bytes = generate_wrapper(
+                &mut backend,
+                &mut output,
+                &padding,
+                standard_name.into(),
+                name.into(),
+            );
+            env.proc_sizes.borrow_mut().push(ProcSize {
+                name: standard_name.into(),
+                code_bytes,
+                data_bytes: 0,
+            });
+        }
+    }
+
+    // Generate IR for specialized helper procs (refcounting).
+    // After this, building the procedures only reads the interners.
+    let mut procedures: std::vec::Vec<_> = procedures.into_iter().collect();
+    let helper_procs = {
+        let ident_ids = interns.all_ident_ids.get_mut(&env.module_id).unwrap();
+        let helper_procs = expand_refcounting(
+            env.arena,
+            target_info,
+            env.module_id,
+            ident_ids,
+            layout_interner,
+            &mut procedures,
         );
-        generate_wrapper(
-            &mut backend,
+        env.module_id.register_debug_idents(ident_ids);
+
+        helper_procs
+    };
+
+    // The host looks up exposed procedures by the names `LayoutIds` gives them,
+    // unless it picked the names itself.
+    let mut layout_ids = LayoutIds::default();
+    let mut names = std::vec::Vec::with_capacity(procedures.len() + helper_procs.len());
+    for ((sym, layout), _) in procedures.iter() {
+        let export_name = env.export_names.get(sym);
+        let exposed = export_name.is_some() || env.exposed_to_host.contains(sym);
+        let fn_name = if let Some(export_name) = export_name {
+            if names.iter().any(|(name, _, _)| name == export_name) {
+                internal_error!(
+                    "{:?} has several specializations, which can not all be exported as {:?}",
+                    sym,
+                    export_name
+                );
+            }
+            export_name.clone()
+        } else if exposed {
+            layout_ids
+                .get_toplevel(*sym, layout)
+                .to_exposed_symbol_string(*sym, interns)
+        } else {
+            proc_symbol_name(*sym, layout, interns)
+        };
+
+        names.push((fn_name, *sym, exposed));
+    }
+    for ((sym, layout), _) in helper_procs.iter() {
+        names.push((proc_symbol_name(*sym, layout, interns), *sym, false));
+    }
+    let layouts: std::vec::Vec<_> = procedures
+        .iter()
+        .chain(helper_procs.iter())
+        .map(|((_, layout), _)| *layout)
+        .collect();
+
+    // Build procedures from user code, followed by the helpers
+    let (built, worker_arena_bytes) = build_procs::<GeneralReg, FloatReg, ASM, CC>(
+        env,
+        target_info,
+        interns,
+        layout_interner,
+        procedures
+            .into_iter()
+            .chain(helper_procs)
+            .map(|((_, layout), proc)| (layout, proc))
+            .collect(),
+    );
+
+    // A procedure that failed to build takes part as if it had no code, so that failures in
+    // procedures nothing calls do not stop the build.
+    let generated: std::vec::Vec<_> = names
+        .into_iter()
+        .zip(built.iter())
+        .map(|((fn_name, sym, exposed), built)| {
+            let (code, relocs, cleanup_sites, statement_markers, unwind_info): (
+                &[u8],
+                &[Relocation],
+                &[CallCleanup],
+                &[StatementMarker],
+                Option<&UnwindInfo>,
+            ) = match built {
+                Ok((code, relocs, cleanup_sites, statement_markers, unwind_info)) => (
+                    code.as_slice(),
+                    relocs.as_slice(),
+                    cleanup_sites.as_slice(),
+                    statement_markers.as_slice(),
+                    unwind_info.as_ref(),
+                ),
+                Err(_) => (&[], &[], &[], &[], None),
+            };
+
+            GeneratedProc {
+                fn_name,
+                sym,
+                exposed,
+                code,
+                relocs,
+                cleanup_sites,
+                statement_markers,
+                unwind_info,
+            }
+        })
+        .collect();
+
+    // Only procedures the host can reach end up in the object.
+    // Specialization often leaves behind procs that nothing calls anymore.
+    let reachable = reachable_procs(&generated);
+    let dropped = reachable.iter().filter(|reachable| !**reachable).count();
+    env.stats.borrow_mut().record_dropped_procs(dropped as u32);
+
+    let generated: std::vec::Vec<_> = generated
+        .into_iter()
+        .zip(built.iter().zip(layouts))
+        .zip(reachable)
+        .filter_map(|(entry, reachable)| reachable.then_some(entry))
+        .collect();
+
+    let unsupported: std::vec::Vec<_> = generated
+        .iter()
+        .filter_map(|(proc, (built, layout))| match built {
+            Ok(_) => None,
+            Err(construct) => Some(UnsupportedError {
+                symbol: proc.sym,
+                layout: *layout,
+                name: proc.fn_name.clone(),
+                construct: construct.clone(),
+            }),
+        })
+        .collect();
+    if !unsupported.is_empty() {
+        return Err(unsupported);
+    }
+
+    let generated: std::vec::Vec<_> = generated.into_iter().map(|(proc, _)| proc).collect();
+    let exposed_symbols = generated
+        .iter()
+        .filter(|proc| proc.exposed)
+        .map(|proc| proc.fn_name.clone())
+        .collect();
+
+    let relocations_started = env.stats.borrow().is_timed().then(Instant::now);
+    emit_procs(
+        &mut output,
+        data_section,
+        &padding,
+        &generated,
+        &env.host_symbols,
+    );
+    if let Some(started) = relocations_started {
+        env.stats
+            .borrow_mut()
+            .record_relocation_time(started.elapsed());
+    }
+    emit_unwind_tables(&mut output, &generated);
+    if env.patchpoints {
+        emit_patchpoint_table(
             &mut output,
-            "roc_dealloc".into(),
-            "free".into(),
+            &generated,
+            ASM::PATCHPOINT_SIZE,
+            target_info.endianness(),
         );
-        generate_wrapper(
-            &mut backend,
+    }
+    if env.panic_cleanup {
+        emit_cleanup_table(
             &mut output,
-            "roc_panic".into(),
-            "roc_builtins.utils.test_panic".into(),
+            generated
+                .iter()
+                .map(|proc| (proc.fn_name.as_str(), proc.cleanup_sites)),
+            target_info.endianness(),
         );
     }
+    env.proc_sizes
+        .borrow_mut()
+        .extend(generated.iter().map(|proc| {
+            ProcSize {
+                name: proc.fn_name.clone(),
+                code_bytes: proc.code.len(),
+                data_bytes: proc
+                    .relocs
+                    .iter()
+                    .map(|reloc| match reloc {
+                        Relocation::LocalData { data, .. } => data.len(),
+                        _ => 0,
+                    })
+                    .sum(),
+            }
+        }));
+    if env.statement_markers {
+        env.listings
+            .borrow_mut()
+            .extend(generated.iter().map(|proc| ProcListing {
+                name: proc.fn_name.clone(),
+                code: proc.code.to_vec(),
+                markers: proc.statement_markers.to_vec(),
+            }));
+    }
 
-    // Setup layout_ids for procedure calls.
-    let mut layout_ids = LayoutIds::default();
-    let mut procs = Vec::with_capacity_in(procedures.len(), arena);
+    env.peak_arena_bytes
+        .set(env.arena.allocated_bytes() + worker_arena_bytes);
 
-    // Names and linker data for user procedures
-    for ((sym, layout), proc) in procedures {
-        build_proc_symbol(
-            &mut output,
-            &mut layout_ids,
-            &mut procs,
-            &backend,
-            sym,
-            layout,
-            proc,
-        )
+    Ok((output, exposed_symbols))
+}
+
+/// A procedure with generated code, that has not been added to the object yet.
+struct GeneratedProc<'a> {
+    fn_name: String,
+    sym: roc_module::symbol::Symbol,
+    exposed: bool,
+    code: &'a [u8],
+    relocs: &'a [Relocation],
+    cleanup_sites: &'a [CallCleanup],
+    statement_markers: &'a [StatementMarker],
+    unwind_info: Option<&'a UnwindInfo>,
+}
+
+/// The code, relocations, cleanup sites, statement markers and unwind info of a procedure, or why
+/// the backend could not build it
+type BuiltProc = Result<
+    (
+        std::vec::Vec<u8>,
+        std::vec::Vec<Relocation>,
+        std::vec::Vec<CallCleanup>,
+        std::vec::Vec<StatementMarker>,
+        Option<UnwindInfo>,
+    ),
+    UnsupportedConstruct,
+>;
+
+/// Builds `procs`, on up to `env.codegen_threads` threads that each take a contiguous chunk of them.
+/// Returns the built procedures in the order of `procs`, along with the bytes the arenas of the
+/// threads took. The statistics all end up in `env.stats`, in that same order.
+fn build_procs<'a, GeneralReg, FloatReg, ASM, CC>(
+    env: &Env<'a>,
+    target_info: TargetInfo,
+    interns: &Interns,
+    layout_interner: &mut STLayoutInterner<'a>,
+    procs: std::vec::Vec<(ProcLayout<'a>, Proc<'a>)>,
+) -> (std::vec::Vec<BuiltProc>, usize)
+where
+    GeneralReg: RegTrait,
+    FloatReg: RegTrait,
+    ASM: Assembler<GeneralReg, FloatReg>,
+    CC: CallConv<GeneralReg, FloatReg, ASM>,
+{
+    let threads = env.codegen_threads.min(procs.len());
+    if threads <= 1 {
+        let built = build_chunk::<GeneralReg, FloatReg, ASM, CC>(
+            env,
+            target_info,
+            interns,
+            layout_interner,
+            procs,
+        );
+        return (built, 0);
     }
 
-    // Build procedures from user code
-    let mut relocations = bumpalo::vec![in arena];
-    for (fn_name, section_id, proc_id, proc) in procs {
-        build_proc(
-            &mut output,
-            &mut backend,
-            &mut relocations,
-            &mut layout_ids,
-            data_section,
-            fn_name,
-            section_id,
-            proc_id,
-            proc,
-        )
+    let chunk_size = (procs.len() + threads - 1) / threads;
+    let mut chunks = std::vec::Vec::with_capacity(threads);
+    let mut procs = procs.into_iter();
+    loop {
+        let chunk: std::vec::Vec<_> = procs.by_ref().take(chunk_size).collect();
+        if chunk.is_empty() {
+            break;
+        }
+        chunks.push(chunk);
     }
 
-    // Generate IR for specialized helper procs (refcounting & equality)
-    let helper_procs = {
-        let (module_id, _interner, interns, helper_proc_gen) = backend.module_interns_helpers_mut();
+    // The statistics in `Env` live in cells, so every thread gets an `Env` of its own.
+    let module_id = env.module_id;
+    let exposed_to_host = &env.exposed_to_host;
+    let export_names = &env.export_names;
+    let lazy_literals = env.lazy_literals;
+    let host_symbols = env.host_symbols;
+    let reg_alloc_order = env.reg_alloc_order;
+    let stack_canary = env.stack_canary;
+    let patchpoints = env.patchpoints;
+    let panic_cleanup = env.panic_cleanup;
+    let statement_markers = env.statement_markers;
+    let runtime_context = env.runtime_context;
+    let serialize_ticks = env.serialize_ticks;
+    let zero_padding = env.zero_padding;
+    let proc_alignment = env.proc_alignment;
+    let timed = env.stats.borrow().is_timed();
+    let layout_interner = &*layout_interner;
 
-        let ident_ids = interns.all_ident_ids.get_mut(&module_id).unwrap();
-        let helper_procs = helper_proc_gen.take_procs();
-        module_id.register_debug_idents(ident_ids);
+    let results: std::vec::Vec<_> = std::thread::scope(|scope| {
+        let workers: std::vec::Vec<_> = chunks
+            .into_iter()
+            .map(|chunk| {
+                scope.spawn(move || {
+                    let arena = Bump::new();
+                    let worker_env = Env {
+                        arena: &arena,
+                        module_id,
+                        exposed_to_host: exposed_to_host.clone(),
+                        export_names: export_names.clone(),
+                        lazy_literals,
+                        generate_allocators: false,
+                        host_symbols,
+                        alias_host_symbols: false,
+                        peak_arena_bytes: Cell::new(0),
+                        stats: RefCell::new(if timed {
+                            CodeGenStats::timed()
+                        } else {
+                            CodeGenStats::default()
+                        }),
+                        proc_sizes: RefCell::default(),
+                        reg_alloc_order,
+                        inline_small_procs: false,
+                        codegen_threads: 1,
+                        stack_canary,
+                        patchpoints,
+                        panic_cleanup,
+                        runtime_context,
+                        serialize_ticks,
+                        zero_padding,
+                        proc_alignment,
+                        statement_markers,
+                        listings: RefCell::default(),
+                    };
 
-        helper_procs
-    };
+                    // Layouts interned while building a procedure are only used by that procedure,
+                    // so a copy of the interner will do.
+                    let mut layout_interner = layout_interner.clone();
+                    let built = build_chunk::<GeneralReg, FloatReg, ASM, CC>(
+                        &worker_env,
+                        target_info,
+                        interns,
+                        &mut layout_interner,
+                        chunk,
+                    );
 
-    let empty = bumpalo::collections::Vec::new_in(arena);
-    let helper_symbols_and_layouts = std::mem::replace(backend.helper_proc_symbols_mut(), empty);
-    let mut helper_names_symbols_procs = Vec::with_capacity_in(helper_procs.len(), arena);
-
-    // Names and linker data for helpers
-    for ((sym, layout), proc) in helper_symbols_and_layouts.into_iter().zip(helper_procs) {
-        let layout_id = layout_ids.get_toplevel(sym, &layout);
-        let fn_name = backend.symbol_to_string(sym, layout_id);
-        if let Some(proc_id) = output.symbol_id(fn_name.as_bytes()) {
-            if let SymbolSection::Section(section_id) = output.symbol(proc_id).section {
-                helper_names_symbols_procs.push((fn_name, section_id, proc_id, proc));
-                continue;
+                    (
+                        built,
+                        worker_env.stats.into_inner(),
+                        arena.allocated_bytes(),
+                    )
+                })
+            })
+            .collect();
+
+        workers
+            .into_iter()
+            .map(|worker| match worker.join() {
+                Ok(result) => result,
+                Err(payload) => std::panic::resume_unwind(payload),
+            })
+            .collect()
+    });
+
+    let mut built =
+        std::vec::Vec::with_capacity(results.iter().map(|(procs, _, _)| procs.len()).sum());
+    let mut arena_bytes = 0;
+    for (procs, stats, bytes) in results {
+        built.extend(procs);
+        env.stats.borrow_mut().merge(stats);
+        arena_bytes += bytes;
+    }
+
+    (built, arena_bytes)
+}
+
+fn build_chunk<'a, GeneralReg, FloatReg, ASM, CC>(
+    env: &Env<'a>,
+    target_info: TargetInfo,
+    interns: &Interns,
+    layout_interner: &mut STLayoutInterner<'a>,
+    procs: std::vec::Vec<(ProcLayout<'a>, Proc<'a>)>,
+) -> std::vec::Vec<BuiltProc>
+where
+    GeneralReg: RegTrait,
+    FloatReg: RegTrait,
+    ASM: Assembler<GeneralReg, FloatReg>,
+    CC: CallConv<GeneralReg, FloatReg, ASM>,
+{
+    let mut backend = new_backend_64bit::<GeneralReg, FloatReg, ASM, CC>(
+        env,
+        target_info,
+        interns,
+        layout_interner,
+    );
+
+    // What the backend does not support yet only ends the procedure it was building.
+    let mut built = std::vec::Vec::with_capacity(procs.len());
+    for (layout, proc) in procs {
+        let result = backend
+            .build_proc(proc, &layout)
+            .map(|(code, relocs)| (code.to_vec(), relocs.to_vec()));
+        match result {
+            Ok((code, relocs)) => {
+                built.push(Ok((
+                    code,
+                    relocs,
+                    backend.cleanup_sites().to_vec(),
+                    backend.statement_markers().to_vec(),
+                    backend.unwind_info().cloned(),
+                )));
             }
-        } else {
-            // The symbol isn't defined yet and will just be used by other rc procs.
-            let section_id = output.add_section(
-                output.segment_name(StandardSegment::Text).to_vec(),
-                format!(".text.{:x}", sym.as_u64()).as_bytes().to_vec(),
-                SectionKind::Text,
-            );
+            Err(unsupported) => {
+                built.push(Err(unsupported));
 
-            let rc_symbol = Symbol {
-                name: fn_name.as_bytes().to_vec(),
-                value: 0,
-                size: 0,
-                kind: SymbolKind::Text,
-                scope: SymbolScope::Linkage,
-                weak: false,
-                section: SymbolSection::Section(section_id),
-                flags: SymbolFlags::None,
-            };
-            let proc_id = output.add_symbol(rc_symbol);
-            helper_names_symbols_procs.push((fn_name, section_id, proc_id, proc));
-            continue;
+                // the backend may have stopped halfway through the procedure
+                drop(backend);
+                backend = new_backend_64bit::<GeneralReg, FloatReg, ASM, CC>(
+                    env,
+                    target_info,
+                    interns,
+                    layout_interner,
+                );
+            }
         }
-        internal_error!("failed to create rc fn for symbol {:?}", sym);
     }
 
-    // Build helpers
-    for (fn_name, section_id, proc_id, proc) in helper_names_symbols_procs {
-        build_proc(
-            &mut output,
-            &mut backend,
+    built
+}
+
+/// Marks which procedures can be reached from the procedures exposed to the host,
+/// by following the calls recorded in their relocations.
+fn reachable_procs(procs: &[GeneratedProc<'_>]) -> std::vec::Vec<bool> {
+    let by_name: MutMap<&str, usize> = procs
+        .iter()
+        .enumerate()
+        .map(|(index, proc)| (proc.fn_name.as_str(), index))
+        .collect();
+
+    let mut reachable = vec![false; procs.len()];
+    let mut stack = std::vec::Vec::new();
+    for (index, proc) in procs.iter().enumerate() {
+        if proc.exposed {
+            reachable[index] = true;
+            stack.push(index);
+        }
+    }
+
+    while let Some(index) = stack.pop() {
+        for reloc in procs[index].relocs {
+            if let Relocation::LinkedFunction { name, .. } = reloc {
+                // calls to builtins and the platform are not in the map
+                if let Some(&callee) = by_name.get(name.as_str()) {
+                    if !reachable[callee] {
+                        reachable[callee] = true;
+                        stack.push(callee);
+                    }
+                }
+            }
+        }
+    }
+
+    reachable
+}
+
+/// Adds the procedures to the object, along with their data and relocations.
+/// The names are used as they are: on Mach-O, `object` prepends the underscore itself.
+fn emit_procs(
+    output: &mut Object,
+    data_section: SectionId,
+    padding: &ProcPadding<'_>,
+    procs: &[GeneratedProc<'_>],
+    host_symbols: &HostSymbols,
+) {
+    // All symbols have to exist before the relocations that refer to them are added.
+    let mut proc_ids = std::vec::Vec::with_capacity(procs.len());
+    for proc in procs {
+        let section_id = output.add_section(
+            output.segment_name(StandardSegment::Text).to_vec(),
+            format!(".text.{:x}", proc.sym.as_u64()).as_bytes().to_vec(),
+            SectionKind::Text,
+        );
+
+        let proc_symbol = Symbol {
+            name: proc.fn_name.as_bytes().to_vec(),
+            value: 0,
+            size: 0,
+            kind: SymbolKind::Text,
+            // TODO: Depending on whether we are building a static or dynamic lib, this should change.
+            // We should use Dynamic -> anyone, Linkage -> static link, Compilation -> this module only.
+            scope: if proc.exposed {
+                SymbolScope::Dynamic
+            } else {
+                SymbolScope::Linkage
+            },
+            weak: false,
+            section: SymbolSection::Section(section_id),
+            flags: SymbolFlags::None,
+        };
+        let proc_id = output.add_symbol(proc_symbol);
+        proc_ids.push((section_id, proc_id));
+    }
+
+    let mut relocations = std::vec::Vec::new();
+    for (proc, (section_id, proc_id)) in procs.iter().zip(proc_ids) {
+        emit_proc(
+            output,
             &mut relocations,
-            &mut layout_ids,
             data_section,
-            fn_name,
+            padding,
+            proc,
             section_id,
             proc_id,
-            proc,
-        )
+            host_symbols,
+        );
     }
 
     // Relocations for all procedures (user code & helpers)
@@ -315,75 +1150,26 @@ fn build_object<'a, B: Backend<'a>>(
             Err(e) => internal_error!("{:?}", e),
         }
     }
-    output
-}
-
-fn build_proc_symbol<'a, B: Backend<'a>>(
-    output: &mut Object<'a>,
-    layout_ids: &mut LayoutIds<'a>,
-    procs: &mut Vec<'a, (String, SectionId, SymbolId, Proc<'a>)>,
-    backend: &B,
-    sym: roc_module::symbol::Symbol,
-    layout: ProcLayout<'a>,
-    proc: Proc<'a>,
-) {
-    let layout_id = layout_ids.get_toplevel(sym, &layout);
-    let base_name = backend.symbol_to_string(sym, layout_id);
-
-    let fn_name = if backend.env().exposed_to_host.contains(&sym) {
-        layout_ids
-            .get_toplevel(sym, &layout)
-            .to_exposed_symbol_string(sym, backend.interns())
-    } else {
-        base_name
-    };
-
-    let section_id = output.add_section(
-        output.segment_name(StandardSegment::Text).to_vec(),
-        format!(".text.{:x}", sym.as_u64()).as_bytes().to_vec(),
-        SectionKind::Text,
-    );
-
-    let proc_symbol = Symbol {
-        name: fn_name.as_bytes().to_vec(),
-        value: 0,
-        size: 0,
-        kind: SymbolKind::Text,
-        // TODO: Depending on whether we are building a static or dynamic lib, this should change.
-        // We should use Dynamic -> anyone, Linkage -> static link, Compilation -> this module only.
-        scope: if backend.env().exposed_to_host.contains(&sym) {
-            SymbolScope::Dynamic
-        } else {
-            SymbolScope::Linkage
-        },
-        weak: false,
-        section: SymbolSection::Section(section_id),
-        flags: SymbolFlags::None,
-    };
-    let proc_id = output.add_symbol(proc_symbol);
-    procs.push((fn_name, section_id, proc_id, proc));
 }
 
 #[allow(clippy::too_many_arguments)]
-fn build_proc<'a, B: Backend<'a>>(
+fn emit_proc(
     output: &mut Object,
-    backend: &mut B,
-    relocations: &mut Vec<'a, (SectionId, object::write::Relocation)>,
-    layout_ids: &mut LayoutIds<'a>,
+    relocations: &mut std::vec::Vec<(SectionId, object::write::Relocation)>,
     data_section: SectionId,
-    fn_name: String,
+    padding: &ProcPadding<'_>,
+    proc: &GeneratedProc<'_>,
     section_id: SectionId,
     proc_id: SymbolId,
-    proc: Proc<'a>,
+    host_symbols: &HostSymbols,
 ) {
     let mut local_data_index = 0;
-    let (proc_data, relocs, rc_proc_names) = backend.build_proc(proc, layout_ids);
-    let proc_offset = output.add_symbol_data(proc_id, section_id, &proc_data, 16);
-    for reloc in relocs.iter() {
+    let proc_offset = padding.add_code(output, proc_id, section_id, proc.code);
+    for reloc in proc.relocs.iter() {
         let elfreloc = match reloc {
             Relocation::LocalData { offset, data } => {
                 let data_symbol = write::Symbol {
-                    name: format!("{}.data{}", fn_name, local_data_index)
+                    name: format!("{}.data{}", proc.fn_name, local_data_index)
                         .as_bytes()
                         .to_vec(),
                     value: 0,
@@ -407,6 +1193,19 @@ fn build_proc<'a, B: Backend<'a>>(
                 }
             }
             Relocation::LinkedData { offset, name } => {
+                // The C runtime defines the guard stack canaries are compared with.
+                if output.symbol_id(name.as_bytes()).is_none() && name == "__stack_chk_guard" {
+                    output.add_symbol(Symbol {
+                        name: name.as_bytes().to_vec(),
+                        value: 0,
+                        size: 0,
+                        kind: SymbolKind::Data,
+                        scope: SymbolScope::Linkage,
+                        weak: false,
+                        section: SymbolSection::Undefined,
+                        flags: SymbolFlags::None,
+                    });
+                }
                 if let Some(sym_id) = output.symbol_id(name.as_bytes()) {
                     write::Relocation {
                         offset: offset + proc_offset,
@@ -421,8 +1220,17 @@ fn build_proc<'a, B: Backend<'a>>(
                 }
             }
             Relocation::LinkedFunction { offset, name } => {
-                // If the symbol is an undefined roc function, we need to add it here.
-                if output.symbol_id(name.as_bytes()).is_none() && name.starts_with("roc_") {
+                // If the symbol is an undefined roc or host function, we need to add it here.
+                let host_fn = [
+                    host_symbols.alloc,
+                    host_symbols.realloc,
+                    host_symbols.dealloc,
+                    host_symbols.panic,
+                ]
+                .contains(&name.as_str());
+                if output.symbol_id(name.as_bytes()).is_none()
+                    && (name.starts_with("roc_") || name == "__stack_chk_fail" || host_fn)
+                {
                     let builtin_symbol = Symbol {
                         name: name.as_bytes().to_vec(),
                         value: 0,
@@ -435,30 +1243,6 @@ fn build_proc<'a, B: Backend<'a>>(
                     };
                     output.add_symbol(builtin_symbol);
                 }
-                // If the symbol is an undefined reference counting procedure, we need to add it here.
-                if output.symbol_id(name.as_bytes()).is_none() {
-                    for (sym, rc_name) in rc_proc_names.iter() {
-                        if name == rc_name {
-                            let section_id = output.add_section(
-                                output.segment_name(StandardSegment::Text).to_vec(),
-                                format!(".text.{:x}", sym.as_u64()).as_bytes().to_vec(),
-                                SectionKind::Text,
-                            );
-
-                            let rc_symbol = Symbol {
-                                name: name.as_bytes().to_vec(),
-                                value: 0,
-                                size: 0,
-                                kind: SymbolKind::Text,
-                                scope: SymbolScope::Linkage,
-                                weak: false,
-                                section: SymbolSection::Section(section_id),
-                                flags: SymbolFlags::None,
-                            };
-                            output.add_symbol(rc_symbol);
-                        }
-                    }
-                }
                 if let Some(sym_id) = output.symbol_id(name.as_bytes()) {
                     write::Relocation {
                         offset: offset + proc_offset,
@@ -477,3 +1261,1948 @@ fn build_proc<'a, B: Backend<'a>>(
         relocations.push((section_id, elfreloc));
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+    use crate::panic_cleanup::CleanupValue;
+    use crate::panic_cleanup::{read_cleanup_sites, CleanupKind};
+    #[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+    use crate::test_utils::JitPage;
+    use object::{Object as _, ObjectSection, ObjectSymbol, RelocationTarget};
+    use roc_module::low_level::LowLevel;
+    use roc_module::symbol::ModuleId;
+    use roc_mono::ir::{Call, CallSpecId, CallType, Expr, HostExposedLayouts, Literal};
+    use roc_mono::ir::{SelfRecursive, Stmt, UpdateModeId};
+    use roc_mono::layout::{LambdaName, Layout, LayoutInterner, Niche};
+
+    fn x86_64_padding(arena: &Bump) -> ProcPadding<'_> {
+        ProcPadding::new(arena, 16, x86_64::X86_64Assembler::padding)
+    }
+
+    #[test]
+    fn unreachable_procs_are_not_emitted() {
+        let call_to = |name: &str| {
+            std::vec![Relocation::LinkedFunction {
+                offset: 1,
+                name: name.to_string(),
+            }]
+        };
+        let main_relocs = call_to("live_helper");
+        let dead_relocs = call_to("live_helper");
+        let builtin_relocs = call_to("roc_builtins.str.concat");
+
+        // `call rel32` with the offset filled in by the relocation
+        let code: [u8; 5] = [0xE8, 0, 0, 0, 0];
+        let proc = |index, name: &str, exposed, relocs| GeneratedProc {
+            fn_name: name.to_string(),
+            sym: Interns::from_index(ModuleId::NUM, 1000 + index),
+            exposed,
+            code: &code,
+            relocs,
+            cleanup_sites: &[],
+            statement_markers: &[],
+            unwind_info: None,
+        };
+        let procs = [
+            proc(0, "main", true, &main_relocs[..]),
+            proc(1, "live_helper", false, &builtin_relocs[..]),
+            proc(2, "dead_helper", false, &dead_relocs[..]),
+        ];
+
+        let reachable = reachable_procs(&procs);
+        assert_eq!(reachable, [true, true, false]);
+
+        let mut output = Object::new(BinaryFormat::Elf, Architecture::X86_64, Endianness::Little);
+        let data_section = output.section_id(StandardSection::Data);
+        let kept: std::vec::Vec<_> = procs
+            .into_iter()
+            .zip(reachable)
+            .filter_map(|(proc, reachable)| reachable.then_some(proc))
+            .collect();
+        let arena = Bump::new();
+        emit_procs(
+            &mut output,
+            data_section,
+            &x86_64_padding(&arena),
+            &kept,
+            &HostSymbols::STANDARD,
+        );
+
+        assert!(output.symbol_id(b"main").is_some());
+        assert!(output.symbol_id(b"live_helper").is_some());
+        assert!(output.symbol_id(b"dead_helper").is_none());
+
+        let bytes = output.write().unwrap();
+        let contains = |needle: &[u8]| bytes.windows(needle.len()).any(|window| window == needle);
+        assert!(contains(b"live_helper"));
+        assert!(!contains(b"dead_helper"));
+    }
+
+    #[test]
+    fn unwind_tables_cover_procs_from_their_prologue() {
+        use x86_64::{X86_64Assembler, X86_64GeneralReg, X86_64WindowsFastcall};
+
+        // a patchpoint, and then a prologue and an epilogue around an empty body
+        let arena = Bump::new();
+        let mut code = bumpalo::vec![in &arena];
+        X86_64Assembler::patchpoint(&mut code);
+        let prologue_offset = code.len() as u64;
+        let saved_regs = [X86_64GeneralReg::RBX];
+        let (stack_size, info) = X86_64WindowsFastcall::setup_stack_with_unwind_info(
+            &mut code,
+            &saved_regs,
+            &[],
+            16,
+            32,
+            0,
+        );
+        X86_64WindowsFastcall::cleanup_stack(&mut code, &saved_regs, &[], stack_size, 32, 0);
+        X86_64Assembler::ret(&mut code);
+        let unwind_info = UnwindInfo {
+            prologue_offset,
+            info: info.unwrap(),
+        };
+
+        let procs = [GeneratedProc {
+            fn_name: "main".to_string(),
+            sym: Interns::from_index(ModuleId::NUM, 1000),
+            exposed: true,
+            code: &code,
+            relocs: &[],
+            cleanup_sites: &[],
+            statement_markers: &[],
+            unwind_info: Some(&unwind_info),
+        }];
+
+        let mut output = Object::new(BinaryFormat::Coff, Architecture::X86_64, Endianness::Little);
+        let data_section = output.section_id(StandardSection::Data);
+        emit_procs(
+            &mut output,
+            data_section,
+            &x86_64_padding(&arena),
+            &procs,
+            &HostSymbols::STANDARD,
+        );
+        emit_unwind_tables(&mut output, &procs);
+        let bytes = output.write().unwrap();
+
+        let file = object::File::parse(&*bytes).unwrap();
+        let xdata = file.section_by_name(".xdata").unwrap();
+        assert_eq!(xdata.data().unwrap(), &unwind_info.info[..]);
+
+        // The relocations add the image base to where the prologue starts, where the code ends,
+        // and where the info is.
+        let pdata = file.section_by_name(".pdata").unwrap();
+        let entry = pdata.data().unwrap();
+        let field =
+            |offset: usize| u32::from_le_bytes(entry[offset..offset + 4].try_into().unwrap());
+        assert_eq!(field(0) as u64, prologue_offset);
+        assert_eq!(field(4) as usize, code.len());
+        assert_eq!(field(8), 0);
+
+        let targets: std::vec::Vec<_> = pdata
+            .relocations()
+            .map(|(offset, reloc)| {
+                assert_eq!(reloc.kind(), RelocationKind::ImageOffset);
+                let target = match reloc.target() {
+                    RelocationTarget::Symbol(index) => file.symbol_by_index(index).unwrap(),
+                    other => panic!("{:?}", other),
+                };
+                (offset, target.name().unwrap().to_string())
+            })
+            .collect();
+        assert_eq!(
+            targets,
+            [
+                (0, "main".to_string()),
+                (4, "main".to_string()),
+                (8, ".xdata".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn data_tables_follow_the_target_byte_order() {
+        use roc_target::Endianness as TargetEndianness;
+
+        let code: [u8; 8] = [0x90; 8];
+        let sites = [CallCleanup {
+            return_offset: 5,
+            values: vec![crate::panic_cleanup::CleanupValue {
+                offset: -16,
+                kind: CleanupKind::List,
+            }],
+        }];
+        let procs = [GeneratedProc {
+            fn_name: "main".to_string(),
+            sym: Interns::from_index(ModuleId::NUM, 1000),
+            exposed: true,
+            code: &code,
+            relocs: &[],
+            cleanup_sites: &sites,
+            statement_markers: &[],
+            unwind_info: None,
+        }];
+
+        let emit = |endianness: TargetEndianness| {
+            let object_endianness = match endianness {
+                TargetEndianness::Little => Endianness::Little,
+                TargetEndianness::Big => Endianness::Big,
+            };
+            let mut output =
+                Object::new(BinaryFormat::Elf, Architecture::X86_64, object_endianness);
+            let data_section = output.section_id(StandardSection::Data);
+            let arena = Bump::new();
+            emit_procs(
+                &mut output,
+                data_section,
+                &x86_64_padding(&arena),
+                &procs,
+                &HostSymbols::STANDARD,
+            );
+            emit_patchpoint_table(&mut output, &procs, 8, endianness);
+            emit_cleanup_table(
+                &mut output,
+                procs
+                    .iter()
+                    .map(|proc| (proc.fn_name.as_str(), proc.cleanup_sites)),
+                endianness,
+            );
+            output.write().unwrap()
+        };
+        let section = |bytes: &[u8], name: &str| {
+            let file = object::File::parse(bytes).unwrap();
+            let data = file.section_by_name(name).unwrap().data().unwrap();
+            data.to_vec()
+        };
+        let little = emit(TargetEndianness::Little);
+        let big = emit(TargetEndianness::Big);
+
+        // the size of the patchpoint
+        let little_patchpoints = section(&little, ".roc_patchpoints");
+        let big_patchpoints = section(&big, ".roc_patchpoints");
+        assert_eq!(little_patchpoints[8..16], 8u64.to_le_bytes());
+        assert_eq!(big_patchpoints[8..16], 8u64.to_be_bytes());
+
+        // the number of values, and where the value is and what it is
+        let little_sites = section(&little, ".roc_cleanup");
+        let big_sites = section(&big, ".roc_cleanup");
+        for range in [8..12, 16..20, 20..24] {
+            let mut reversed = little_sites[range.clone()].to_vec();
+            reversed.reverse();
+            assert_eq!(big_sites[range], reversed[..]);
+        }
+        assert_eq!(little_sites[16..20], (-16i32).to_le_bytes());
+
+        // both read back the same
+        assert_eq!(
+            read_patchpoints(&little).unwrap(),
+            read_patchpoints(&big).unwrap()
+        );
+        assert_eq!(
+            read_cleanup_sites(&little).unwrap(),
+            read_cleanup_sites(&big).unwrap()
+        );
+        assert_eq!(read_cleanup_sites(&big).unwrap()[0].values, sites[0].values);
+    }
+
+    #[test]
+    fn call_relocations_name_defined_procs() {
+        let arena = bumpalo::Bump::new();
+        let mut interns = Interns::default();
+        let module_id = interns.module_id(&"Test".into());
+        let ident_ids = interns.all_ident_ids.get_or_insert(module_id);
+        let main = symbol::Symbol::new(module_id, ident_ids.add_str("main"));
+        let double = symbol::Symbol::new(module_id, ident_ids.add_str("double"));
+
+        let specialization = |layout| ProcLayout {
+            arguments: arena.alloc([layout]),
+            result: layout,
+            niche: Niche::NONE,
+        };
+
+        // the caller names the specializations independently of where they are defined
+        let callee_names = [Layout::F64, Layout::I64]
+            .map(|layout| proc_symbol_name(double, &specialization(layout), &interns));
+        assert_ne!(callee_names[0], callee_names[1]);
+        let main_relocs = [1, 6].map(|offset| Relocation::LinkedFunction {
+            offset,
+            name: callee_names[offset as usize / 5].clone(),
+        });
+
+        // two `call rel32`s
+        let code: [u8; 10] = [0xE8, 0, 0, 0, 0, 0xE8, 0, 0, 0, 0];
+        let proc = |sym, layout: &ProcLayout, relocs| GeneratedProc {
+            fn_name: proc_symbol_name(sym, layout, &interns),
+            sym,
+            exposed: false,
+            code: &code,
+            relocs,
+            cleanup_sites: &[],
+            statement_markers: &[],
+            unwind_info: None,
+        };
+        let procs = [
+            proc(double, &specialization(Layout::I64), &[][..]),
+            proc(double, &specialization(Layout::F64), &[][..]),
+            proc(main, &specialization(Layout::UNIT), &main_relocs[..]),
+        ];
+
+        let mut output = Object::new(BinaryFormat::Elf, Architecture::X86_64, Endianness::Little);
+        let data_section = output.section_id(StandardSection::Data);
+        emit_procs(
+            &mut output,
+            data_section,
+            &x86_64_padding(&arena),
+            &procs,
+            &HostSymbols::STANDARD,
+        );
+        let bytes = output.write().unwrap();
+
+        let file = object::File::parse(&bytes[..]).unwrap();
+        let mut targets = std::vec::Vec::new();
+        for section in file.sections() {
+            for (_, reloc) in section.relocations() {
+                if let RelocationTarget::Symbol(index) = reloc.target() {
+                    let target = file.symbol_by_index(index).unwrap();
+                    assert!(target.is_definition(), "{:?} is not defined", target.name());
+                    targets.push(target.name().unwrap().to_string());
+                }
+            }
+        }
+
+        let mut expected = callee_names.to_vec();
+        expected.sort();
+        targets.sort();
+        assert_eq!(targets, expected);
+    }
+
+    /// The symbols of `main` and of `count` procedures `step_i`,
+    /// along with the symbols their bodies need
+    fn chain_symbols(interns: &mut Interns, count: usize) -> std::vec::Vec<[symbol::Symbol; 5]> {
+        let module_id = interns.module_id(&"Test".into());
+        let ident_ids = interns.all_ident_ids.get_or_insert(module_id);
+
+        (0..=count)
+            .map(|i| {
+                let name = if i == count {
+                    "main".to_string()
+                } else {
+                    format!("step_{}", i)
+                };
+                [name.as_str(), "x", "one", "sum", "result"]
+                    .map(|ident| symbol::Symbol::new(module_id, ident_ids.add_str(ident)))
+            })
+            .collect()
+    }
+
+    /// `step_0 = \x -> x + 1`, every other `step_i = \x -> step_(i - 1) (x + 1)`,
+    /// and `main = step_(count - 1) 1`
+    fn chain_of_procs<'a>(
+        arena: &'a Bump,
+        symbols: &[[symbol::Symbol; 5]],
+    ) -> MutMap<(symbol::Symbol, ProcLayout<'a>), Proc<'a>> {
+        let count = symbols.len() - 1;
+        let i64s = |count| &*arena.alloc_slice_fill_copy(count, Layout::I64);
+        let call = |name, argument| {
+            Expr::Call(Call {
+                call_type: CallType::ByName {
+                    name: LambdaName::no_niche(name),
+                    ret_layout: Layout::I64,
+                    arg_layouts: i64s(1),
+                    specialization_id: CallSpecId::BACKEND_DUMMY,
+                },
+                arguments: arena.alloc([argument]),
+            })
+        };
+
+        symbols
+            .iter()
+            .enumerate()
+            .map(|(i, &[name, x, one, sum, result])| {
+                let is_main = i == count;
+                let ret = if i == 0 {
+                    Stmt::Ret(sum)
+                } else {
+                    let callee = symbols[if is_main { count - 1 } else { i - 1 }][0];
+                    let argument = if is_main { one } else { sum };
+                    Stmt::Let(
+                        result,
+                        call(callee, argument),
+                        Layout::I64,
+                        arena.alloc(Stmt::Ret(result)),
+                    )
+                };
+                let add = Expr::Call(Call {
+                    call_type: CallType::LowLevel {
+                        op: LowLevel::NumAdd,
+                        update_mode: UpdateModeId::BACKEND_DUMMY,
+                    },
+                    arguments: arena.alloc([x, one]),
+                });
+                let body = if is_main {
+                    ret
+                } else {
+                    Stmt::Let(sum, add, Layout::I64, arena.alloc(ret))
+                };
+                let body = Stmt::Let(
+                    one,
+                    Expr::Literal(Literal::Int(1i128.to_ne_bytes())),
+                    Layout::I64,
+                    arena.alloc(body),
+                );
+
+                let args: &[_] = if is_main {
+                    &[]
+                } else {
+                    arena.alloc([(Layout::I64, x)])
+                };
+                let layout = ProcLayout {
+                    arguments: i64s(args.len()),
+                    result: Layout::I64,
+                    niche: Niche::NONE,
+                };
+                let proc = Proc {
+                    name: LambdaName::no_niche(name),
+                    args,
+                    body,
+                    closure_data_layout: None,
+                    ret_layout: Layout::I64,
+                    is_self_recursive: SelfRecursive::NotSelfRecursive,
+                    must_own_arguments: false,
+                    host_exposed_layouts: HostExposedLayouts::NotHostExposed,
+                };
+
+                ((name, layout), proc)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn object_does_not_depend_on_codegen_threads() {
+        let mut interns = Interns::default();
+        let symbols = chain_symbols(&mut interns, 40);
+        let main = symbols[40][0];
+
+        let build = |interns: &mut Interns, codegen_threads| {
+            let arena = Bump::new();
+            let env = Env {
+                codegen_threads,
+                ..env_exposing(&arena, &[main])
+            };
+            let mut layout_interner =
+                STLayoutInterner::with_capacity(4, TargetInfo::default_x86_64());
+
+            let (output, _) = build_object::<
+                x86_64::X86_64GeneralReg,
+                x86_64::X86_64FloatReg,
+                x86_64::X86_64Assembler,
+                x86_64::X86_64SystemV,
+            >(
+                &env,
+                TargetInfo::default_x86_64(),
+                interns,
+                &mut layout_interner,
+                chain_of_procs(&arena, &symbols),
+                Object::new(BinaryFormat::Elf, Architecture::X86_64, Endianness::Little),
+            )
+            .unwrap();
+
+            (output.write().unwrap(), env.stats.into_inner())
+        };
+
+        let (sequential, sequential_stats) = build(&mut interns, 1);
+        let (parallel, parallel_stats) = build(&mut interns, 8);
+
+        assert_eq!(sequential_stats.procs.len(), 41);
+        assert_eq!(sequential_stats, parallel_stats);
+        assert!(sequential == parallel, "the objects differ");
+    }
+
+    #[test]
+    fn results_are_built_in_the_reg_they_are_returned_or_passed_in() {
+        let mut interns = Interns::default();
+        let symbols = chain_symbols(&mut interns, 2);
+        let main = symbols[2][0];
+
+        let arena = Bump::new();
+        let env = env_exposing(&arena, &[main]);
+        let mut layout_interner = STLayoutInterner::with_capacity(4, TargetInfo::default_x86_64());
+
+        build_object::<
+            x86_64::X86_64GeneralReg,
+            x86_64::X86_64FloatReg,
+            x86_64::X86_64Assembler,
+            x86_64::X86_64SystemV,
+        >(
+            &env,
+            TargetInfo::default_x86_64(),
+            &mut interns,
+            &mut layout_interner,
+            chain_of_procs(&arena, &symbols),
+            Object::new(BinaryFormat::Elf, Architecture::X86_64, Endianness::Little),
+        )
+        .unwrap();
+
+        let stats = env.stats.into_inner();
+        let proc_stats = |name: &str| {
+            stats
+                .procs
+                .iter()
+                .find(|proc| proc.name.contains(name))
+                .unwrap()
+                .clone()
+        };
+
+        // `x + 1` is added up in the return reg
+        let step_0 = proc_stats("step_0");
+        assert_eq!((step_0.moves, step_0.spills), (0, 0));
+
+        // the `1` is loaded into the first param reg, and kept there during the call,
+        // and the result is left in the return reg
+        let main = proc_stats("main");
+        assert_eq!((main.moves, main.spills), (0, 0));
+    }
+
+    #[test]
+    fn proc_sizes_add_up_to_the_text_sections() {
+        let mut interns = Interns::default();
+        let symbols = chain_symbols(&mut interns, 3);
+        let main = symbols[3][0];
+
+        let arena = Bump::new();
+        let env = env_exposing(&arena, &[main]);
+        let mut layout_interner = STLayoutInterner::with_capacity(4, TargetInfo::default_x86_64());
+
+        let (output, _) = build_object::<
+            x86_64::X86_64GeneralReg,
+            x86_64::X86_64FloatReg,
+            x86_64::X86_64Assembler,
+            x86_64::X86_64SystemV,
+        >(
+            &env,
+            TargetInfo::default_x86_64(),
+            &mut interns,
+            &mut layout_interner,
+            chain_of_procs(&arena, &symbols),
+            Object::new(BinaryFormat::Elf, Architecture::X86_64, Endianness::Little),
+        )
+        .unwrap();
+        let bytes = output.write().unwrap();
+
+        let file = object::File::parse(&bytes[..]).unwrap();
+        let text_bytes: u64 = file
+            .sections()
+            .filter(|section| section.kind() == SectionKind::Text)
+            .map(|section| section.size())
+            .sum();
+
+        // every procedure is padded up to where the next one can start
+        let proc_sizes = env.proc_sizes.into_inner();
+        assert_eq!(proc_sizes.len(), 4);
+        assert!(proc_sizes.iter().all(|size| size.code_bytes > 0));
+        assert_eq!(
+            proc_sizes
+                .iter()
+                .map(|size| (size.code_bytes as u64 + 15) / 16 * 16)
+                .sum::<u64>(),
+            text_bytes
+        );
+    }
+
+    #[test]
+    fn procs_start_aligned_and_calls_skip_the_padding() {
+        let mut interns = Interns::default();
+        let symbols = chain_symbols(&mut interns, 3);
+        let main = symbols[3][0];
+
+        let arena = Bump::new();
+        let env = Env {
+            generate_allocators: true,
+            proc_alignment: 32,
+            ..env_exposing(&arena, &[main])
+        };
+        let mut layout_interner = STLayoutInterner::with_capacity(4, TargetInfo::default_x86_64());
+
+        let (output, _) = build_object::<
+            x86_64::X86_64GeneralReg,
+            x86_64::X86_64FloatReg,
+            x86_64::X86_64Assembler,
+            x86_64::X86_64SystemV,
+        >(
+            &env,
+            TargetInfo::default_x86_64(),
+            &mut interns,
+            &mut layout_interner,
+            chain_of_procs(&arena, &symbols),
+            Object::new(BinaryFormat::Elf, Architecture::X86_64, Endianness::Little),
+        )
+        .unwrap();
+        let bytes = output.write().unwrap();
+        let file = object::File::parse(&bytes[..]).unwrap();
+        let proc_sizes = env.proc_sizes.into_inner();
+
+        let mut padding = bumpalo::vec![in &arena];
+        let mut procs = 0;
+        for symbol in file.symbols() {
+            if symbol.kind() != SymbolKind::Text || !symbol.is_definition() {
+                continue;
+            }
+            procs += 1;
+            let section = file
+                .section_by_index(symbol.section_index().unwrap())
+                .unwrap();
+            let start = symbol.address() - section.address();
+            let end = start + symbol.size();
+            let data = section.data().unwrap();
+
+            // the symbol covers the code, which is followed by padding up to the next boundary
+            let name = symbol.name().unwrap();
+            let size = proc_sizes.iter().find(|size| size.name == name).unwrap();
+            assert_eq!(symbol.size(), size.code_bytes as u64, "{}", name);
+            assert_eq!(start % 32, 0, "{} starts at {}", name, start);
+            let padded_end = (end + 31) / 32 * 32;
+            assert!(padded_end <= data.len() as u64);
+            padding.clear();
+            x86_64::X86_64Assembler::padding(&mut padding, (padded_end - end) as usize);
+            assert_eq!(&data[end as usize..padded_end as usize], &padding[..]);
+        }
+        // the procedures, and the 4 allocator wrappers that share a section
+        assert_eq!(procs, 8);
+
+        // a relocation still points at the rel32 of its `jmp` or `call`
+        for section in file.sections() {
+            if section.kind() != SectionKind::Text {
+                continue;
+            }
+            let data = section.data().unwrap();
+            for (offset, _) in section.relocations() {
+                let opcode = data[offset as usize - 1];
+                assert!(
+                    opcode == 0xE8 || opcode == 0xE9,
+                    "the relocation at {} of {} is not the operand of a call or jmp",
+                    offset,
+                    section.name().unwrap(),
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn host_symbols_rename_generated_calls_and_get_aliases() {
+        let mut interns = Interns::default();
+        let symbols = chain_symbols(&mut interns, 2);
+        let main = symbols[2][0];
+
+        let host_symbols = HostSymbols {
+            alloc: "arena_alloc",
+            panic: "host_crash",
+            ..HostSymbols::STANDARD
+        };
+        let arena = Bump::new();
+        let env = Env {
+            generate_allocators: true,
+            host_symbols,
+            alias_host_symbols: true,
+            stack_canary: Some(crate::StackCanary::Constant(0x5AFE_C0DE)),
+            ..env_exposing(&arena, &[main])
+        };
+        let mut layout_interner = STLayoutInterner::with_capacity(4, TargetInfo::default_x86_64());
+
+        let (output, _) = build_object::<
+            x86_64::X86_64GeneralReg,
+            x86_64::X86_64FloatReg,
+            x86_64::X86_64Assembler,
+            x86_64::X86_64SystemV,
+        >(
+            &env,
+            TargetInfo::default_x86_64(),
+            &mut interns,
+            &mut layout_interner,
+            chain_of_procs(&arena, &symbols),
+            Object::new(BinaryFormat::Elf, Architecture::X86_64, Endianness::Little),
+        )
+        .unwrap();
+        let bytes = output.write().unwrap();
+        let file = object::File::parse(&bytes[..]).unwrap();
+
+        // the allocators of the host, and the standard names that jump to them
+        for name in [
+            "arena_alloc",
+            "roc_realloc",
+            "host_crash",
+            "roc_alloc",
+            "roc_panic",
+        ] {
+            assert!(
+                file.symbols()
+                    .any(|symbol| symbol.name() == Ok(name) && symbol.is_definition()),
+                "{} is not defined",
+                name
+            );
+        }
+
+        let mut targets = std::vec::Vec::new();
+        for section in file.sections() {
+            for (_, reloc) in section.relocations() {
+                if let RelocationTarget::Symbol(index) = reloc.target() {
+                    let target = file.symbol_by_index(index).unwrap();
+                    let name = target.name().unwrap();
+                    if ["arena_alloc", "host_crash"].contains(&name) {
+                        // the wrapper, not an undefined symbol of the same name
+                        assert!(target.is_definition(), "{} is not defined", name);
+                    }
+                    targets.push(name.to_string());
+                }
+            }
+        }
+
+        // the canaries fail into the host, and so do the aliases
+        assert!(targets.iter().filter(|name| *name == "host_crash").count() > 1);
+        assert!(targets.iter().any(|name| name == "arena_alloc"));
+        assert!(!targets.iter().any(|name| name == "roc_panic"));
+    }
+
+    /// Builds `\x -> x + 1`, `\x -> x + 2`, and so on, for every `[name, x, n, sum]`
+    #[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+    fn add_procs<'a>(
+        arena: &'a Bump,
+        symbols: &[[symbol::Symbol; 4]],
+    ) -> std::vec::Vec<(ProcLayout<'a>, Proc<'a>)> {
+        symbols
+            .iter()
+            .enumerate()
+            .map(|(i, &[name, x, n, sum])| {
+                let add = Expr::Call(Call {
+                    call_type: CallType::LowLevel {
+                        op: LowLevel::NumAdd,
+                        update_mode: UpdateModeId::BACKEND_DUMMY,
+                    },
+                    arguments: arena.alloc([x, n]),
+                });
+                let body = Stmt::Let(
+                    n,
+                    Expr::Literal(Literal::Int((i as i128 + 1).to_ne_bytes())),
+                    Layout::I64,
+                    arena.alloc(Stmt::Let(
+                        sum,
+                        add,
+                        Layout::I64,
+                        arena.alloc(Stmt::Ret(sum)),
+                    )),
+                );
+                let layout = ProcLayout {
+                    arguments: arena.alloc([Layout::I64]),
+                    result: Layout::I64,
+                    niche: Niche::NONE,
+                };
+                let proc = Proc {
+                    name: LambdaName::no_niche(name),
+                    args: arena.alloc([(Layout::I64, x)]),
+                    body,
+                    closure_data_layout: None,
+                    ret_layout: Layout::I64,
+                    is_self_recursive: SelfRecursive::NotSelfRecursive,
+                    must_own_arguments: false,
+                    host_exposed_layouts: HostExposedLayouts::NotHostExposed,
+                };
+
+                (layout, proc)
+            })
+            .collect()
+    }
+
+    fn build_chain(interns: &mut Interns, patchpoints: bool) -> std::vec::Vec<u8> {
+        let symbols = chain_symbols(interns, 3);
+        let main = symbols[3][0];
+
+        let arena = Bump::new();
+        let env = Env {
+            patchpoints,
+            ..env_exposing(&arena, &[main])
+        };
+        let mut layout_interner = STLayoutInterner::with_capacity(4, TargetInfo::default_x86_64());
+
+        let (output, _) = build_object::<
+            x86_64::X86_64GeneralReg,
+            x86_64::X86_64FloatReg,
+            x86_64::X86_64Assembler,
+            x86_64::X86_64SystemV,
+        >(
+            &env,
+            TargetInfo::default_x86_64(),
+            interns,
+            &mut layout_interner,
+            chain_of_procs(&arena, &symbols),
+            Object::new(BinaryFormat::Elf, Architecture::X86_64, Endianness::Little),
+        )
+        .unwrap();
+
+        output.write().unwrap()
+    }
+
+    /// The first bytes of every procedure in the object, by the name of its symbol
+    fn proc_starts(bytes: &[u8], len: usize) -> std::vec::Vec<(String, std::vec::Vec<u8>)> {
+        let file = object::File::parse(bytes).unwrap();
+        let mut starts: std::vec::Vec<_> = file
+            .symbols()
+            .filter(|symbol| symbol.kind() == SymbolKind::Text && symbol.is_definition())
+            .map(|symbol| {
+                let section = file
+                    .section_by_index(symbol.section_index().unwrap())
+                    .unwrap();
+                let start = (symbol.address() - section.address()) as usize;
+                let code = section.data().unwrap()[start..start + len].to_vec();
+
+                (symbol.name().unwrap().to_string(), code)
+            })
+            .collect();
+        starts.sort();
+
+        starts
+    }
+
+    #[test]
+    fn patchpoints_start_every_proc_and_are_listed() {
+        let mut interns = Interns::default();
+        let bytes = build_chain(&mut interns, true);
+
+        let nops = [0x0F, 0x1F, 0x44, 0x00, 0x00, 0x0F, 0x1F, 0x00];
+        let starts = proc_starts(&bytes, nops.len());
+        assert_eq!(starts.len(), 4);
+        for (name, start) in starts.iter() {
+            assert_eq!(start, &nops, "{} does not start with a patchpoint", name);
+        }
+
+        let mut patchpoints = read_patchpoints(&bytes).unwrap();
+        patchpoints.sort_by(|a, b| a.name.cmp(&b.name));
+        assert_eq!(
+            patchpoints,
+            starts
+                .into_iter()
+                .map(|(name, _)| Patchpoint {
+                    name,
+                    offset: 0,
+                    size: x86_64::X86_64Assembler::PATCHPOINT_SIZE as u64,
+                })
+                .collect::<std::vec::Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn no_patchpoints_by_default() {
+        let mut interns = Interns::default();
+        let bytes = build_chain(&mut interns, false);
+
+        assert!(read_patchpoints(&bytes).unwrap().is_empty());
+        // the code starts with the prologue, which pushes the frame pointer
+        for (name, start) in proc_starts(&bytes, 1) {
+            assert_eq!(start, [0x55], "{} does not start with its prologue", name);
+        }
+    }
+
+    /// Does what a runtime that reloads code would do: it runs a procedure, points its patchpoint
+    /// at a newer version, and runs it again.
+    #[test]
+    #[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+    fn patchpoint_redirects_a_proc() {
+        let mut interns = Interns::default();
+        let module_id = interns.module_id(&"Test".into());
+        let ident_ids = interns.all_ident_ids.get_or_insert(module_id);
+        let symbols: std::vec::Vec<_> = ["add_one", "add_two"]
+            .iter()
+            .map(|name| {
+                [*name, "x", "n", "sum"]
+                    .map(|ident| symbol::Symbol::new(module_id, ident_ids.add_str(ident)))
+            })
+            .collect();
+
+        let arena = Bump::new();
+        let env = Env {
+            module_id,
+            lazy_literals: true,
+            patchpoints: true,
+            ..Env::for_tests(&arena)
+        };
+        let mut layout_interner = STLayoutInterner::with_capacity(4, TargetInfo::default_x86_64());
+        let mut backend = new_backend_64bit::<
+            x86_64::X86_64GeneralReg,
+            x86_64::X86_64FloatReg,
+            x86_64::X86_64Assembler,
+            x86_64::X86_64SystemV,
+        >(
+            &env,
+            TargetInfo::default_x86_64(),
+            &interns,
+            &mut layout_interner,
+        );
+        let [add_one, add_two]: [std::vec::Vec<u8>; 2] = add_procs(&arena, &symbols)
+            .into_iter()
+            .map(|(layout, proc)| {
+                let (code, relocs) = backend.build_proc(proc, &layout).unwrap();
+                assert!(relocs.is_empty());
+                code.to_vec()
+            })
+            .collect::<std::vec::Vec<_>>()
+            .try_into()
+            .unwrap();
+
+        // both versions go on one page, each at an aligned address
+        let newer = 64;
+        assert!(add_one.len() <= newer && newer + add_two.len() <= JitPage::SIZE);
+
+        let mut page = JitPage::new();
+        page.write(0, &add_one);
+        page.write(newer, &add_two);
+        page.make_executable();
+
+        let proc: extern "C" fn(i64) -> i64 = unsafe { page.function(0) };
+        assert_eq!(proc(40), 41);
+
+        // `jmp rel32` to the newer version, followed by the nop that was already there
+        let mut patch = [0; 8];
+        patch.copy_from_slice(&add_one[..8]);
+        patch[0] = 0xE9;
+        patch[1..5].copy_from_slice(&(newer as i32 - 5).to_le_bytes());
+        page.make_writable();
+        page.write(0, &patch);
+        page.make_executable();
+
+        assert_eq!(proc(40), 42);
+    }
+
+    /// `step = \x -> x + 1`, and `main = \n -> b = Box n; r = step n; r + Unbox b`,
+    /// so `main` owns `b` while it calls `step`
+    fn boxing_procs<'a>(
+        arena: &'a Bump,
+        layout_interner: &mut STLayoutInterner<'a>,
+        interns: &mut Interns,
+    ) -> std::vec::Vec<(ProcLayout<'a>, Proc<'a>)> {
+        let module_id = interns.module_id(&"Test".into());
+        let ident_ids = interns.all_ident_ids.get_or_insert(module_id);
+        let [step, x, one, sum, main, n, b, r, u, s] =
+            ["step", "x", "one", "sum", "main", "n", "b", "r", "u", "s"]
+                .map(|ident| symbol::Symbol::new(module_id, ident_ids.add_str(ident)));
+
+        let boxed = layout_interner.insert(Layout::Boxed(Layout::I64));
+        let i64s = &*arena.alloc([Layout::I64]);
+        let layout = ProcLayout {
+            arguments: i64s,
+            result: Layout::I64,
+            niche: Niche::NONE,
+        };
+        let add = |lhs, rhs| {
+            Expr::Call(Call {
+                call_type: CallType::LowLevel {
+                    op: LowLevel::NumAdd,
+                    update_mode: UpdateModeId::BACKEND_DUMMY,
+                },
+                arguments: arena.alloc([lhs, rhs]),
+            })
+        };
+        let proc = |name, arg, body| Proc {
+            name: LambdaName::no_niche(name),
+            args: arena.alloc([(Layout::I64, arg)]),
+            body,
+            closure_data_layout: None,
+            ret_layout: Layout::I64,
+            is_self_recursive: SelfRecursive::NotSelfRecursive,
+            must_own_arguments: false,
+            host_exposed_layouts: HostExposedLayouts::NotHostExposed,
+        };
+
+        let step_body = Stmt::Let(
+            one,
+            Expr::Literal(Literal::Int(1i128.to_ne_bytes())),
+            Layout::I64,
+            arena.alloc(Stmt::Let(
+                sum,
+                add(x, one),
+                Layout::I64,
+                arena.alloc(Stmt::Ret(sum)),
+            )),
+        );
+
+        let call_step = Expr::Call(Call {
+            call_type: CallType::ByName {
+                name: LambdaName::no_niche(step),
+                ret_layout: Layout::I64,
+                arg_layouts: i64s,
+                specialization_id: CallSpecId::BACKEND_DUMMY,
+            },
+            arguments: arena.alloc([n]),
+        });
+        let main_body = Stmt::Let(
+            b,
+            Expr::ExprBox { symbol: n },
+            boxed,
+            arena.alloc(Stmt::Let(
+                r,
+                call_step,
+                Layout::I64,
+                arena.alloc(Stmt::Let(
+                    u,
+                    Expr::ExprUnbox { symbol: b },
+                    Layout::I64,
+                    arena.alloc(Stmt::Let(
+                        s,
+                        add(r, u),
+                        Layout::I64,
+                        arena.alloc(Stmt::Ret(s)),
+                    )),
+                )),
+            )),
+        );
+
+        std::vec![
+            (layout, proc(step, x, step_body)),
+            (layout, proc(main, n, main_body)),
+        ]
+    }
+
+    fn build_boxing(interns: &mut Interns, panic_cleanup: bool) -> std::vec::Vec<u8> {
+        let arena = Bump::new();
+        let mut layout_interner = STLayoutInterner::with_capacity(4, TargetInfo::default_x86_64());
+        let procs = boxing_procs(&arena, &mut layout_interner, interns);
+        let main = procs[1].1.name.name();
+        let env = Env {
+            panic_cleanup,
+            ..env_exposing(&arena, &[main])
+        };
+
+        let (output, _) = build_object::<
+            x86_64::X86_64GeneralReg,
+            x86_64::X86_64FloatReg,
+            x86_64::X86_64Assembler,
+            x86_64::X86_64SystemV,
+        >(
+            &env,
+            TargetInfo::default_x86_64(),
+            interns,
+            &mut layout_interner,
+            procs
+                .into_iter()
+                .map(|(layout, proc)| ((proc.name.name(), layout), proc))
+                .collect(),
+            Object::new(BinaryFormat::Elf, Architecture::X86_64, Endianness::Little),
+        )
+        .unwrap();
+
+        output.write().unwrap()
+    }
+
+    #[test]
+    fn cleanup_sites_list_the_values_owned_during_calls() {
+        let mut interns = Interns::default();
+        let bytes = build_boxing(&mut interns, true);
+
+        // the call to `step` is the only one that can panic, `b` is the only value `main` owns,
+        // and the allocation of `b` is not a site
+        let sites = read_cleanup_sites(&bytes).unwrap();
+        assert_eq!(sites.len(), 1, "{:?}", sites);
+        assert!(sites[0].name.contains("main"), "{:?}", sites);
+        assert_eq!(sites[0].values.len(), 1);
+        assert_eq!(sites[0].values[0].kind, CleanupKind::Box);
+        assert!(sites[0].values[0].offset < 0);
+
+        // the return address follows the `call rel32` to `step`
+        let file = object::File::parse(&bytes[..]).unwrap();
+        let main = file
+            .symbols()
+            .find(|symbol| symbol.name().ok() == Some(sites[0].name.as_str()))
+            .unwrap();
+        let text = file
+            .section_by_index(main.section_index().unwrap())
+            .unwrap();
+        let main_start = main.address() - text.address();
+        let step_call = text
+            .relocations()
+            .find(|(_, reloc)| match reloc.target() {
+                RelocationTarget::Symbol(index) => file
+                    .symbol_by_index(index)
+                    .unwrap()
+                    .name()
+                    .unwrap()
+                    .contains("step"),
+                _ => false,
+            })
+            .map(|(offset, _)| offset)
+            .unwrap();
+        assert_eq!(main_start + sites[0].return_offset, step_call + 4);
+    }
+
+    #[test]
+    fn no_cleanup_sites_by_default() {
+        let mut interns = Interns::default();
+        let bytes = build_boxing(&mut interns, false);
+
+        assert!(read_cleanup_sites(&bytes).unwrap().is_empty());
+    }
+
+    /// The x86_64 instructions in `code`, which starts at `offset`, in Intel syntax. The code
+    /// must start and end between instructions.
+    fn disassemble(code: &[u8], offset: u64) -> std::vec::Vec<String> {
+        use capstone::prelude::*;
+
+        let cs = Capstone::new()
+            .x86()
+            .mode(arch::x86::ArchMode::Mode64)
+            .syntax(arch::x86::ArchSyntax::Intel)
+            .build()
+            .expect("Failed to create Capstone object");
+        let insts = cs.disasm_all(code, offset).expect("Failed to disassemble");
+        let bytes: usize = insts.iter().map(|inst| inst.bytes().len()).sum();
+        assert_eq!(bytes, code.len(), "{:x?} at {}", code, offset);
+
+        insts
+            .iter()
+            .map(|inst| format!("{} {}", inst.mnemonic().unwrap(), inst.op_str().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn listings_put_every_statement_above_its_instructions() {
+        let mut interns = Interns::default();
+        let symbols = chain_symbols(&mut interns, 1);
+        let main = symbols[1][0];
+
+        let build = |interns: &mut Interns, codegen_threads| {
+            let arena = Bump::new();
+            let env = Env {
+                statement_markers: true,
+                codegen_threads,
+                ..env_exposing(&arena, &[main])
+            };
+            let mut layout_interner =
+                STLayoutInterner::with_capacity(4, TargetInfo::default_x86_64());
+
+            build_object::<
+                x86_64::X86_64GeneralReg,
+                x86_64::X86_64FloatReg,
+                x86_64::X86_64Assembler,
+                x86_64::X86_64SystemV,
+            >(
+                &env,
+                TargetInfo::default_x86_64(),
+                interns,
+                &mut layout_interner,
+                chain_of_procs(&arena, &symbols),
+                Object::new(BinaryFormat::Elf, Architecture::X86_64, Endianness::Little),
+            )
+            .unwrap();
+
+            env.listings.into_inner()
+        };
+        let listings = build(&mut interns, 1);
+        assert_eq!(listings, build(&mut interns, 2));
+        assert_eq!(listings.len(), 2);
+
+        let listing = |name: &str| {
+            let listing = listings
+                .iter()
+                .find(|listing| listing.name.contains(name))
+                .unwrap();
+            let statements: std::vec::Vec<_> = listing
+                .markers
+                .iter()
+                .map(|marker| marker.statement.as_str())
+                .collect();
+            let regions: std::vec::Vec<_> = listing
+                .regions()
+                .iter()
+                .map(|region| disassemble(region.code, region.offset))
+                .collect();
+
+            (listing, statements, regions)
+        };
+
+        let (step, statements, regions) = listing("step_0");
+        assert_eq!(
+            statements,
+            [
+                "let one = 1i64",
+                "let sum = lowlevel NumAdd x one",
+                "ret sum"
+            ]
+        );
+        assert_eq!(regions[0][..2], ["push rbp", "mov rbp, rsp"]);
+        // the literal is loaded lazily, by the addition
+        assert!(regions[1].is_empty(), "{:?}", regions);
+        assert!(!regions[2].is_empty(), "{:?}", regions);
+        assert_eq!(regions[3].last().unwrap(), "ret ");
+
+        let rendered = step.render(disassemble);
+        let headings: std::vec::Vec<_> = rendered
+            .lines()
+            .filter_map(|line| line.strip_prefix("; "))
+            .collect();
+        assert_eq!(headings, statements);
+        assert_eq!(
+            rendered.lines().count(),
+            1 + statements.len() + regions.iter().map(|region| region.len()).sum::<usize>()
+        );
+
+        let (_, statements, regions) = listing("main");
+        assert_eq!(
+            statements,
+            [
+                "let one = 1i64",
+                "let result = CallByName step_0 one",
+                "ret result"
+            ]
+        );
+        assert!(
+            regions[2].iter().any(|inst| inst.starts_with("call")),
+            "{:?}",
+            regions
+        );
+    }
+
+    /// The instructions of `pair = \x -> { x, x }`, which builds its result on the stack and
+    /// calls nothing, when built for `target_info`
+    fn build_leaf_pair(target_info: TargetInfo) -> std::vec::Vec<String> {
+        let mut interns = Interns::default();
+        let module_id = interns.module_id(&"Test".into());
+        let ident_ids = interns.all_ident_ids.get_or_insert(module_id);
+        let [pair, x, s] = ["pair", "x", "s"]
+            .map(|ident| symbol::Symbol::new(module_id, ident_ids.add_str(ident)));
+
+        let arena = Bump::new();
+        let mut layout_interner = STLayoutInterner::with_capacity(4, target_info);
+        let fields = &*arena.alloc([Layout::I64, Layout::I64]);
+        let pair_layout = layout_interner.insert(Layout::struct_no_name_order(fields));
+        let proc = Proc {
+            name: LambdaName::no_niche(pair),
+            args: arena.alloc([(Layout::I64, x)]),
+            body: Stmt::Let(
+                s,
+                Expr::Struct(arena.alloc([x, x])),
+                pair_layout,
+                arena.alloc(Stmt::Ret(s)),
+            ),
+            closure_data_layout: None,
+            ret_layout: pair_layout,
+            is_self_recursive: SelfRecursive::NotSelfRecursive,
+            must_own_arguments: false,
+            host_exposed_layouts: HostExposedLayouts::NotHostExposed,
+        };
+        let layout = ProcLayout {
+            arguments: &fields[..1],
+            result: pair_layout,
+            niche: Niche::NONE,
+        };
+        let mut procs = MutMap::default();
+        procs.insert((pair, layout), proc);
+
+        let env = Env {
+            statement_markers: true,
+            ..env_exposing(&arena, &[pair])
+        };
+        build_object::<
+            x86_64::X86_64GeneralReg,
+            x86_64::X86_64FloatReg,
+            x86_64::X86_64Assembler,
+            x86_64::X86_64SystemV,
+        >(
+            &env,
+            target_info,
+            &mut interns,
+            &mut layout_interner,
+            procs,
+            Object::new(BinaryFormat::Elf, Architecture::X86_64, Endianness::Little),
+        )
+        .unwrap();
+
+        let listings = env.listings.into_inner();
+
+        disassemble(&listings[0].code, 0)
+    }
+
+    #[test]
+    fn leaf_procs_keep_their_frame_in_the_red_zone() {
+        let moves_rsp = |insts: &[String]| insts.iter().any(|inst| inst.ends_with(" rsp, 0x10"));
+
+        let with_red_zone = build_leaf_pair(TargetInfo::default_x86_64());
+        assert!(!moves_rsp(&with_red_zone), "{:?}", with_red_zone);
+        assert!(
+            with_red_zone.iter().any(|inst| inst.contains("[rbp - ")),
+            "{:?}",
+            with_red_zone
+        );
+
+        let without_red_zone = build_leaf_pair(TargetInfo::default_x86_64().without_red_zone());
+        assert!(
+            without_red_zone.contains(&"sub rsp, 0x10".to_string()),
+            "{:?}",
+            without_red_zone
+        );
+        assert!(
+            without_red_zone.contains(&"add rsp, 0x10".to_string()),
+            "{:?}",
+            without_red_zone
+        );
+    }
+
+    #[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+    std::thread_local! {
+        static ALLOCATED: std::cell::RefCell<std::vec::Vec<usize>> = Default::default();
+        static RELEASED: std::cell::RefCell<std::vec::Vec<usize>> = Default::default();
+        static SITES: std::cell::RefCell<std::vec::Vec<(usize, std::vec::Vec<CleanupValue>)>> =
+            Default::default();
+    }
+
+    #[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+    extern "C" fn test_allocate(size: usize, _alignment: u32) -> *mut u8 {
+        assert!(size <= 32);
+        let ptr = Box::into_raw(Box::new([0u64; 4])) as *mut u8;
+        ALLOCATED.with(|allocated| allocated.borrow_mut().push(ptr as usize));
+
+        ptr
+    }
+
+    /// Stands in for a `step` that panics, and for a host that releases what the frame of its
+    /// caller owns when it does. So that the test can go on, it returns `x + 1` after all.
+    #[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+    extern "C" fn test_panicking_step(x: i64, frame_pointer: *const u8, ret: usize) -> i64 {
+        SITES.with(|sites| {
+            let sites = sites.borrow();
+            let (_, values) = sites
+                .iter()
+                .find(|(address, _)| *address == ret)
+                .expect("the caller has no cleanup site");
+
+            for value in values {
+                assert_eq!(value.kind, CleanupKind::Box);
+                let ptr = unsafe { *(frame_pointer.offset(value.offset as isize) as *const usize) };
+                RELEASED.with(|released| released.borrow_mut().push(ptr));
+            }
+        });
+
+        x + 1
+    }
+
+    /// Runs `main` with a `step` that panics, and checks that the cleanup site for the call
+    /// releases every allocation `main` made, i.e. that the refcounts balance.
+    #[test]
+    #[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+    fn cleanup_site_releases_what_a_panicking_call_leaves_behind() {
+        let mut interns = Interns::default();
+        let arena = Bump::new();
+        let mut layout_interner = STLayoutInterner::with_capacity(4, TargetInfo::default_x86_64());
+        let mut procs = boxing_procs(&arena, &mut layout_interner, &mut interns);
+        let (layout, main) = procs.pop().unwrap();
+        let env = Env {
+            panic_cleanup: true,
+            ..env_exposing(&arena, &[main.name.name()])
+        };
+
+        let mut backend = new_backend_64bit::<
+            x86_64::X86_64GeneralReg,
+            x86_64::X86_64FloatReg,
+            x86_64::X86_64Assembler,
+            x86_64::X86_64SystemV,
+        >(
+            &env,
+            TargetInfo::default_x86_64(),
+            &interns,
+            &mut layout_interner,
+        );
+        let (code, relocs) = backend.build_proc(main, &layout).unwrap();
+        let (mut code, relocs) = (code.to_vec(), relocs.to_vec());
+        let sites = backend.cleanup_sites().to_vec();
+        assert_eq!(sites.len(), 1);
+
+        // `main` goes at the start of the page, followed by a stub for every procedure it calls
+        let stubs = 2048;
+        assert!(code.len() <= stubs);
+
+        // The stub of `step` passes the frame pointer and the return address on to the handler.
+        // `mov rsi, rbp; mov rdx, [rsp]; movabs rax, handler; jmp rax`
+        let stub_to = |handler: usize, pass_frame: bool| {
+            let mut stub = std::vec::Vec::new();
+            if pass_frame {
+                stub.extend([0x48, 0x89, 0xEE, 0x48, 0x8B, 0x14, 0x24]);
+            }
+            stub.extend([0x48, 0xB8]);
+            stub.extend((handler as u64).to_le_bytes());
+            stub.extend([0xFF, 0xE0]);
+            stub
+        };
+
+        let mut page = JitPage::new();
+        let base = page.base;
+        let mut stub_offset = stubs;
+        for reloc in relocs.iter() {
+            let name = match reloc {
+                Relocation::LinkedFunction { name, .. } => name,
+                other => panic!("unexpected relocation {:?}", other),
+            };
+            let stub = if name == roc_builtins::bitcode::UTILS_ALLOCATE_WITH_REFCOUNT {
+                stub_to(test_allocate as usize, false)
+            } else {
+                assert!(name.contains("step"), "{}", name);
+                stub_to(test_panicking_step as usize, true)
+            };
+            page.write(stub_offset, &stub);
+
+            // the stubs are on the same page as `main`, so its calls reach them
+            let stub_address = base as u64 + stub_offset as u64;
+            crate::link_relocation(&mut code, base as u64, "main", reloc, stub_address).unwrap();
+            stub_offset += 32;
+        }
+        page.write(0, &code);
+        page.make_executable();
+
+        SITES.with(|cells| {
+            *cells.borrow_mut() = sites
+                .iter()
+                .map(|site| {
+                    (
+                        base as usize + site.return_offset as usize,
+                        site.values.clone(),
+                    )
+                })
+                .collect()
+        });
+
+        let main: extern "C" fn(i64) -> i64 = unsafe { page.function(0) };
+        assert_eq!(main(20), 41);
+        drop(page);
+
+        let allocated = ALLOCATED.with(|allocated| allocated.take());
+        let released = RELEASED.with(|released| released.take());
+        assert_eq!(allocated.len(), 1);
+        assert_eq!(allocated, released);
+        for ptr in allocated {
+            drop(unsafe { Box::from_raw(ptr as *mut [u64; 4]) });
+        }
+    }
+
+    /// Builds `update : I64, I64 -> I64`, which multiplies, and `render : F64, F64 -> F64`,
+    /// which adds, and exports them by names the test picks
+    #[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+    fn entry_point_procs<'a>(
+        arena: &'a Bump,
+        interns: &mut Interns,
+    ) -> (
+        std::vec::Vec<(symbol::Symbol, &'static str)>,
+        MutMap<(symbol::Symbol, ProcLayout<'a>), Proc<'a>>,
+    ) {
+        let module_id = interns.module_id(&"Test".into());
+        let ident_ids = interns.all_ident_ids.get_or_insert(module_id);
+        let entry_points = [
+            ("update", LowLevel::NumMul, Layout::I64),
+            ("render", LowLevel::NumAdd, Layout::F64),
+        ];
+
+        let mut exports = std::vec::Vec::new();
+        let mut procedures = MutMap::default();
+        for (name, op, layout) in entry_points {
+            let [sym, a, b, result] = [name, "a", "b", "result"]
+                .map(|ident| symbol::Symbol::new(module_id, ident_ids.add_str(ident)));
+            let body = Stmt::Let(
+                result,
+                Expr::Call(Call {
+                    call_type: CallType::LowLevel {
+                        op,
+                        update_mode: UpdateModeId::BACKEND_DUMMY,
+                    },
+                    arguments: arena.alloc([a, b]),
+                }),
+                layout,
+                arena.alloc(Stmt::Ret(result)),
+            );
+            let proc_layout = ProcLayout {
+                arguments: arena.alloc([layout, layout]),
+                result: layout,
+                niche: Niche::NONE,
+            };
+            let proc = Proc {
+                name: LambdaName::no_niche(sym),
+                args: arena.alloc([(layout, a), (layout, b)]),
+                body,
+                closure_data_layout: None,
+                ret_layout: layout,
+                is_self_recursive: SelfRecursive::NotSelfRecursive,
+                must_own_arguments: false,
+                host_exposed_layouts: HostExposedLayouts::NotHostExposed,
+            };
+
+            let export_name = match name {
+                "update" => "roc__update_1_exposed",
+                _ => "roc__render_1_exposed",
+            };
+            exports.push((sym, export_name));
+            procedures.insert((sym, proc_layout), proc);
+        }
+
+        (exports, procedures)
+    }
+
+    /// Does what a platform with several entry points does: it looks up each of them by the name
+    /// it picked, and calls it with its own signature.
+    #[test]
+    #[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+    fn several_entry_points_are_exported_by_their_names() {
+        let mut interns = Interns::default();
+        let arena = Bump::new();
+        let (exports, procedures) = entry_point_procs(&arena, &mut interns);
+        let env = Env {
+            exposed_to_host: Default::default(),
+            export_names: exports
+                .iter()
+                .map(|(sym, name)| (*sym, name.to_string()))
+                .collect(),
+            ..env_exposing(&arena, &[exports[0].0])
+        };
+        let mut layout_interner = STLayoutInterner::with_capacity(4, TargetInfo::default_x86_64());
+
+        let output = build_procedures(
+            &env,
+            &mut interns,
+            &mut layout_interner,
+            &"x86_64-unknown-linux-gnu".parse().unwrap(),
+            procedures,
+        )
+        .unwrap();
+        let mut exposed_symbols = output.exposed_symbols.clone();
+        exposed_symbols.sort();
+        assert_eq!(
+            exposed_symbols,
+            ["roc__render_1_exposed", "roc__update_1_exposed"]
+        );
+
+        // Every procedure has a section of its own. They all go on one page, like a loader would
+        // put them, and neither of them calls anything or loads data.
+        let file = object::File::parse(&output.bytes[..]).unwrap();
+        let mut page = std::vec::Vec::new();
+        let mut section_starts = MutMap::default();
+        for section in file.sections() {
+            if section.kind() == SectionKind::Text && section.size() > 0 {
+                assert_eq!(section.relocations().count(), 0);
+                page.resize((page.len() + 15) / 16 * 16, 0);
+                section_starts.insert(section.index(), page.len());
+                page.extend_from_slice(section.data().unwrap());
+            }
+        }
+        assert!(page.len() <= JitPage::SIZE);
+
+        let exported = |name: &str| {
+            let symbol = file
+                .symbols()
+                .find(|symbol| {
+                    symbol
+                        .name()
+                        .map_or(false, |symbol_name| symbol_name == name)
+                })
+                .unwrap();
+            assert_eq!(symbol.kind(), SymbolKind::Text);
+            assert!(symbol.is_global(), "{} is not global", name);
+
+            let section = file
+                .section_by_index(symbol.section_index().unwrap())
+                .unwrap();
+            section_starts[&section.index()] + (symbol.address() - section.address()) as usize
+        };
+        let update_offset = exported("roc__update_1_exposed");
+        let render_offset = exported("roc__render_1_exposed");
+
+        let mut jit = JitPage::new();
+        jit.write(0, &page);
+        jit.make_executable();
+
+        let update: extern "C" fn(i64, i64) -> i64 = unsafe { jit.function(update_offset) };
+        let render: extern "C" fn(f64, f64) -> f64 = unsafe { jit.function(render_offset) };
+        assert_eq!(update(6, 7), 42);
+        assert_eq!(render(1.5, 2.25), 3.75);
+    }
+
+    /// `main x` stores `x` in the first word of the host's context, and returns its second word.
+    /// The host passes the context after the argument.
+    #[test]
+    #[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+    fn exposed_procs_take_the_runtime_context() {
+        let mut interns = Interns::default();
+        let module_id = interns.module_id(&"Test".into());
+        let ident_ids = interns.all_ident_ids.get_or_insert(module_id);
+        let [main, x, first, second, stored, result] =
+            ["main", "x", "first", "second", "stored", "result"]
+                .map(|ident| symbol::Symbol::new(module_id, ident_ids.add_str(ident)));
+
+        let arena = Bump::new();
+        let context_op = |op, arguments: &[symbol::Symbol]| {
+            Expr::Call(Call {
+                call_type: CallType::LowLevel {
+                    op,
+                    update_mode: UpdateModeId::BACKEND_DUMMY,
+                },
+                arguments: arena.alloc_slice_copy(arguments),
+            })
+        };
+        let body = Stmt::Let(
+            first,
+            Expr::Literal(Literal::Int(0i128.to_ne_bytes())),
+            Layout::U64,
+            arena.alloc(Stmt::Let(
+                second,
+                Expr::Literal(Literal::Int(8i128.to_ne_bytes())),
+                Layout::U64,
+                arena.alloc(Stmt::Let(
+                    stored,
+                    context_op(LowLevel::RuntimeContextStore, &[first, x]),
+                    Layout::UNIT,
+                    arena.alloc(Stmt::Let(
+                        result,
+                        context_op(LowLevel::RuntimeContextLoad, &[second]),
+                        Layout::I64,
+                        arena.alloc(Stmt::Ret(result)),
+                    )),
+                )),
+            )),
+        );
+        let proc_layout = ProcLayout {
+            arguments: arena.alloc([Layout::I64]),
+            result: Layout::I64,
+            niche: Niche::NONE,
+        };
+        let proc = Proc {
+            name: LambdaName::no_niche(main),
+            args: arena.alloc([(Layout::I64, x)]),
+            body,
+            closure_data_layout: None,
+            ret_layout: Layout::I64,
+            is_self_recursive: SelfRecursive::NotSelfRecursive,
+            must_own_arguments: false,
+            host_exposed_layouts: HostExposedLayouts::NotHostExposed,
+        };
+        let mut procedures = MutMap::default();
+        procedures.insert((main, proc_layout), proc);
+
+        let env = Env {
+            export_names: [(main, "roc__main_1_exposed".to_string())]
+                .into_iter()
+                .collect(),
+            runtime_context: true,
+            ..env_exposing(&arena, &[main])
+        };
+        let mut layout_interner = STLayoutInterner::with_capacity(4, TargetInfo::default_x86_64());
+        let output = build_procedures(
+            &env,
+            &mut interns,
+            &mut layout_interner,
+            &"x86_64-unknown-linux-gnu".parse().unwrap(),
+            procedures,
+        )
+        .unwrap();
+
+        let file = object::File::parse(&output.bytes[..]).unwrap();
+        let symbol = file
+            .symbols()
+            .find(|symbol| symbol.name() == Ok("roc__main_1_exposed"))
+            .unwrap();
+        let section = file
+            .section_by_index(symbol.section_index().unwrap())
+            .unwrap();
+        assert_eq!(section.relocations().count(), 0);
+        let code = section.data().unwrap();
+        let offset = (symbol.address() - section.address()) as usize;
+
+        let mut page = JitPage::new();
+        page.write(0, code);
+        page.make_executable();
+
+        let main: extern "C" fn(i64, *mut u64) -> i64 = unsafe { page.function(offset) };
+        let mut context = [1u64, 99];
+        assert_eq!(main(5, context.as_mut_ptr()), 99);
+        assert_eq!(context, [5, 99]);
+    }
+
+    /// `main` reads the timestamp counter twice, and returns whether the second read is at least
+    /// the first. The live first read must survive the second one clobbering RAX and RDX.
+    #[test]
+    #[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+    fn monotonic_ticks_do_not_go_down() {
+        let mut interns = Interns::default();
+        let module_id = interns.module_id(&"Test".into());
+        let ident_ids = interns.all_ident_ids.get_or_insert(module_id);
+        let [main, first, second, result] = ["main", "first", "second", "result"]
+            .map(|ident| symbol::Symbol::new(module_id, ident_ids.add_str(ident)));
+
+        let arena = Bump::new();
+        let lowlevel = |op, arguments: &[symbol::Symbol]| {
+            Expr::Call(Call {
+                call_type: CallType::LowLevel {
+                    op,
+                    update_mode: UpdateModeId::BACKEND_DUMMY,
+                },
+                arguments: arena.alloc_slice_copy(arguments),
+            })
+        };
+
+        for serialize_ticks in [false, true] {
+            let body = Stmt::Let(
+                first,
+                lowlevel(LowLevel::MonotonicTicks, &[]),
+                Layout::U64,
+                arena.alloc(Stmt::Let(
+                    second,
+                    lowlevel(LowLevel::MonotonicTicks, &[]),
+                    Layout::U64,
+                    arena.alloc(Stmt::Let(
+                        result,
+                        lowlevel(LowLevel::NumGte, &[second, first]),
+                        Layout::BOOL,
+                        arena.alloc(Stmt::Ret(result)),
+                    )),
+                )),
+            );
+            let proc_layout = ProcLayout {
+                arguments: &[],
+                result: Layout::BOOL,
+                niche: Niche::NONE,
+            };
+            let proc = Proc {
+                name: LambdaName::no_niche(main),
+                args: &[],
+                body,
+                closure_data_layout: None,
+                ret_layout: Layout::BOOL,
+                is_self_recursive: SelfRecursive::NotSelfRecursive,
+                must_own_arguments: false,
+                host_exposed_layouts: HostExposedLayouts::NotHostExposed,
+            };
+            let mut procedures = MutMap::default();
+            procedures.insert((main, proc_layout), proc);
+
+            let env = Env {
+                export_names: [(main, "roc__main_1_exposed".to_string())]
+                    .into_iter()
+                    .collect(),
+                serialize_ticks,
+                ..env_exposing(&arena, &[main])
+            };
+            let mut layout_interner =
+                STLayoutInterner::with_capacity(4, TargetInfo::default_x86_64());
+            let output = build_procedures(
+                &env,
+                &mut interns,
+                &mut layout_interner,
+                &"x86_64-unknown-linux-gnu".parse().unwrap(),
+                procedures,
+            )
+            .unwrap();
+
+            let file = object::File::parse(&output.bytes[..]).unwrap();
+            let symbol = file
+                .symbols()
+                .find(|symbol| symbol.name() == Ok("roc__main_1_exposed"))
+                .unwrap();
+            let section = file
+                .section_by_index(symbol.section_index().unwrap())
+                .unwrap();
+            assert_eq!(section.relocations().count(), 0);
+            let code = section.data().unwrap();
+            let offset = (symbol.address() - section.address()) as usize;
+
+            let mut page = JitPage::new();
+            page.write(0, code);
+            page.make_executable();
+
+            let main: extern "C" fn() -> bool = unsafe { page.function(offset) };
+            for _ in 0..100 {
+                assert!(main(), "serialize_ticks: {}", serialize_ticks);
+            }
+        }
+    }
+
+    fn env_exposing(arena: &Bump, exposed: &[symbol::Symbol]) -> Env<'_> {
+        Env {
+            module_id: exposed[0].module_id(),
+            exposed_to_host: exposed.iter().copied().collect(),
+            lazy_literals: true,
+            ..Env::for_tests(arena)
+        }
+    }
+
+    #[test]
+    fn phases_are_timed_on_every_thread() {
+        let mut interns = Interns::default();
+        let symbols = chain_symbols(&mut interns, 8);
+        let main = symbols[8][0];
+
+        let arena = Bump::new();
+        let mut env = env_exposing(&arena, &[main]);
+        env.stats = RefCell::new(CodeGenStats::timed());
+        env.codegen_threads = 3;
+        let mut layout_interner = STLayoutInterner::with_capacity(4, TargetInfo::default_x86_64());
+
+        let output = build_procedures(
+            &env,
+            &mut interns,
+            &mut layout_interner,
+            &"x86_64-unknown-linux-gnu".parse().unwrap(),
+            chain_of_procs(&arena, &symbols),
+        )
+        .unwrap();
+
+        let stats = output.stats;
+        assert_eq!(stats.procs.len(), 9);
+        assert!(stats.procs.iter().all(|proc| proc.lowering_time.is_some()));
+        assert_eq!(
+            stats.times.unwrap().lowering,
+            stats
+                .procs
+                .iter()
+                .filter_map(|proc| proc.lowering_time)
+                .sum::<std::time::Duration>()
+        );
+        assert!(stats.summary().contains("slowest procs:"));
+    }
+
+    #[test]
+    fn build_procedures_for_elf_and_macho() {
+        for (target, format) in [
+            ("x86_64-unknown-linux-gnu", BinaryFormat::Elf),
+            ("x86_64-apple-darwin", BinaryFormat::MachO),
+        ] {
+            let mut interns = Interns::default();
+            let symbols = chain_symbols(&mut interns, 3);
+            let main = symbols[3][0];
+
+            let arena = Bump::new();
+            let env = env_exposing(&arena, &[main]);
+            let mut layout_interner =
+                STLayoutInterner::with_capacity(4, TargetInfo::default_x86_64());
+
+            let triple = target.parse().unwrap();
+            let output = build_procedures(
+                &env,
+                &mut interns,
+                &mut layout_interner,
+                &triple,
+                chain_of_procs(&arena, &symbols),
+            )
+            .unwrap();
+
+            let file = object::File::parse(&output.bytes[..]).unwrap();
+            assert_eq!(file.format(), format, "{}", target);
+            assert_eq!(
+                read_producer(&output.bytes).unwrap(),
+                Some(format!(
+                    "roc dev backend {} target {}",
+                    roc_target::COMPILER_VERSION.trim(),
+                    target
+                ))
+            );
+            if format == BinaryFormat::Elf {
+                assert!(file.section_by_name(".comment").is_some());
+            }
+            assert_eq!(output.stats.procs.len(), 4, "{}", target);
+
+            // Mach-O puts an underscore in front of every name
+            let defined: std::vec::Vec<_> = file
+                .symbols()
+                .filter(|symbol| symbol.kind() == SymbolKind::Text && symbol.is_definition())
+                .map(|symbol| symbol.name().unwrap().trim_start_matches('_').to_string())
+                .collect();
+            assert_eq!(output.exposed_symbols.len(), 1, "{}", target);
+            assert!(
+                defined.contains(&output.exposed_symbols[0]),
+                "{} is not defined for {}",
+                output.exposed_symbols[0],
+                target
+            );
+        }
+    }
+
+    #[test]
+    fn unsupported_procs_are_reported_together() {
+        let mut interns = Interns::default();
+        let symbols = chain_symbols(&mut interns, 3);
+        let [step_0, step_2, main] = [symbols[0][0], symbols[2][0], symbols[3][0]];
+
+        // `main` calls `step_2`, and `step_0` is exposed, so both of them have to be built.
+        // `step_1` is only called by `step_2`, so it never gets a chance to fail.
+        let arena = Bump::new();
+        let env = env_exposing(&arena, &[main, step_0]);
+        let mut layout_interner = STLayoutInterner::with_capacity(4, TargetInfo::default_x86_64());
+        let mut procedures = chain_of_procs(&arena, &symbols);
+        for ((name, _), proc) in procedures.iter_mut() {
+            if [step_0, step_2].contains(name) {
+                proc.body = Stmt::Crash(proc.args[0].1, roc_mono::ir::CrashTag::Roc, None);
+            }
+        }
+
+        let unsupported = build_procedures(
+            &env,
+            &mut interns,
+            &mut layout_interner,
+            &"x86_64-unknown-linux-gnu".parse().unwrap(),
+            procedures,
+        )
+        .unwrap_err();
+
+        let names: std::vec::Vec<_> = unsupported.iter().map(|proc| proc.name.as_str()).collect();
+        assert_eq!(names.len(), 2, "{:?}", names);
+        for step in ["step_0", "step_2"] {
+            assert!(names.iter().any(|name| name.contains(step)), "{:?}", names);
+        }
+        assert!(unsupported
+            .iter()
+            .all(|proc| proc.construct == UnsupportedConstruct::Expr("crash")));
+    }
+
+    #[test]
+    fn unsupported_constructs_are_counted_once() {
+        let mut interns = Interns::default();
+        let symbols = chain_symbols(&mut interns, 3);
+        let [step_0, step_1, step_2, main] = [0, 1, 2, 3].map(|i| symbols[i][0]);
+
+        // `step_0` and `step_1` crash, and `step_2`, which `main` calls, has a runtime error
+        let arena = Bump::new();
+        let env = env_exposing(&arena, &[main, step_0, step_1]);
+        let mut layout_interner = STLayoutInterner::with_capacity(4, TargetInfo::default_x86_64());
+        let mut procedures = chain_of_procs(&arena, &symbols);
+        for ((name, _), proc) in procedures.iter_mut() {
+            if [step_0, step_1].contains(name) {
+                proc.body = Stmt::Crash(proc.args[0].1, roc_mono::ir::CrashTag::Roc, None);
+            } else if *name == step_2 {
+                let error = symbols[2][4];
+                proc.body = Stmt::Let(
+                    error,
+                    Expr::RuntimeErrorFunction("a hole in the program"),
+                    Layout::I64,
+                    arena.alloc(Stmt::Ret(error)),
+                );
+            }
+        }
+
+        let unsupported = build_procedures(
+            &env,
+            &mut interns,
+            &mut layout_interner,
+            &"x86_64-unknown-linux-gnu".parse().unwrap(),
+            procedures,
+        )
+        .unwrap_err();
+        assert_eq!(unsupported.len(), 3);
+
+        let report = describe_unsupported(&unsupported);
+        let summary = report.lines().next().unwrap();
+        assert_eq!(
+            summary,
+            "the dev backend doesn't support yet: crash (2 procs), runtime errors (1 proc)"
+        );
+        // followed by every procedure
+        assert_eq!(report.lines().count(), 4);
+        for step in ["step_0", "step_1", "step_2"] {
+            assert!(
+                report.lines().skip(1).any(|line| line.contains(step)),
+                "{}",
+                report
+            );
+        }
+    }
+}
@@ -0,0 +1,240 @@
+//! Tables of the refcounted values a procedure owns at every call that can panic, for
+//! `Env::panic_cleanup`. A host whose `roc_panic` does not return, e.g. because it longjmps back
+//! into the host, would leak those values otherwise.
+//!
+//! The table is a section of its own, in the byte order of the target. Every site in it is
+//! 8-byte aligned, and consists of
+//! - the return address of the call, which a relocation fills in, as a `u64`
+//! - the number of values, as a `u32`, followed by 4 bytes of padding
+//! - for every value, where it is relative to the frame pointer as an `i32`, followed by its
+//!   [CleanupKind] as a `u32`
+//!
+//! To release the values, the host walks the frame pointers up from its `roc_panic`. The return
+//! address of each frame is the key of its site, if the frame belongs to a procedure built by the
+//! dev backend. Frames of the builtins and of the host have no sites.
+//!
+//! Only values a procedure got from a call, a list literal or a box are in the table, and only
+//! until their first use, which may hand them on. Arguments of the procedure or of a join point
+//! are left out, because they may be borrowed. So a panic can still leak, but it never releases
+//! a value that someone else owns.
+use object::write::{self, Object, StandardSegment};
+use object::{BinaryFormat, RelocationEncoding, RelocationKind, SectionKind};
+use roc_error_macros::internal_error;
+use roc_module::symbol::Symbol;
+use roc_mono::ir::{Expr, ListLiteralElement};
+use roc_mono::layout::{Builtin, InLayout, Layout, LayoutInterner, STLayoutInterner};
+use roc_target::Endianness;
+
+/// What a value that has to be released is, which tells the host where its allocation is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[repr(u32)]
+pub enum CleanupKind {
+    /// A `Str`, whose first word points to the allocation, unless it is a small string
+    Str = 0,
+    /// A `List`, whose first word points to the allocation, or is null for an empty list
+    List = 1,
+    /// A `Box`, which is a pointer to the allocation
+    Box = 2,
+}
+
+impl CleanupKind {
+    pub(crate) fn of_layout(interner: &STLayoutInterner<'_>, layout: InLayout<'_>) -> Option<Self> {
+        match interner.get(layout) {
+            Layout::Builtin(Builtin::Str) => Some(CleanupKind::Str),
+            Layout::Builtin(Builtin::List(_)) => Some(CleanupKind::List),
+            Layout::Boxed(_) => Some(CleanupKind::Box),
+            _ => None,
+        }
+    }
+
+    fn from_u32(kind: u32) -> Option<Self> {
+        match kind {
+            0 => Some(CleanupKind::Str),
+            1 => Some(CleanupKind::List),
+            2 => Some(CleanupKind::Box),
+            _ => None,
+        }
+    }
+}
+
+/// A value the frame of a procedure owns during a call
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CleanupValue {
+    /// where the value is, relative to the frame pointer
+    pub offset: i32,
+    pub kind: CleanupKind,
+}
+
+/// A call in a procedure, as the backend recorded it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct CallCleanup {
+    /// the offset of the return address, from the start of the procedure
+    pub return_offset: u64,
+    pub values: std::vec::Vec<CleanupValue>,
+}
+
+/// A call that can panic, as it is in the table of an object
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CleanupSite {
+    /// the symbol of the procedure, as it is in the symbol table
+    pub name: String,
+    /// the offset of the return address, from the start of the procedure
+    pub return_offset: u64,
+    pub values: std::vec::Vec<CleanupValue>,
+}
+
+/// Whether a call to `fn_name` can end up in `roc_panic`. The allocator and the helpers that
+/// change refcounts or compare values never do, and those make up many of the calls.
+pub(crate) fn call_can_panic(fn_name: &str) -> bool {
+    let never_panics = fn_name.starts_with("roc_builtins.utils.")
+        || ["roc_alloc", "roc_realloc", "roc_dealloc", "roc_memcpy"].contains(&fn_name)
+        || fn_name.contains("#help");
+
+    !never_panics
+}
+
+/// Whether `expr` makes a value the procedure owns, as opposed to one it borrows from another value
+pub(crate) fn expr_makes_owned_value(expr: &Expr<'_>) -> bool {
+    matches!(
+        expr,
+        Expr::Call(_) | Expr::Array { .. } | Expr::ExprBox { .. }
+    )
+}
+
+/// The symbols `expr` uses in a way that may hand them on, e.g. to a procedure that consumes them
+pub(crate) fn symbols_handed_on(expr: &Expr<'_>) -> std::vec::Vec<Symbol> {
+    match expr {
+        Expr::Call(call) => call.arguments.to_vec(),
+        Expr::Tag { arguments, .. } | Expr::Struct(arguments) => arguments.to_vec(),
+        Expr::Reuse {
+            symbol, arguments, ..
+        } => std::iter::once(*symbol)
+            .chain(arguments.iter().copied())
+            .collect(),
+        Expr::Reset { symbol, .. } | Expr::ExprBox { symbol } => vec![*symbol],
+        Expr::Array { elems, .. } => elems
+            .iter()
+            .filter_map(|elem| match elem {
+                ListLiteralElement::Symbol(symbol) => Some(*symbol),
+                ListLiteralElement::Literal(_) => None,
+            })
+            .collect(),
+        // these borrow from the structure
+        Expr::StructAtIndex { .. }
+        | Expr::UnionAtIndex { .. }
+        | Expr::GetTagId { .. }
+        | Expr::ExprUnbox { .. } => vec![],
+        Expr::Literal(_) | Expr::EmptyArray | Expr::RuntimeErrorFunction(_) => vec![],
+    }
+}
+
+/// The section of the cleanup table. Mach-O does not allow section names that are this long.
+fn cleanup_section_name(format: BinaryFormat) -> &'static str {
+    match format {
+        BinaryFormat::MachO => "__roc_cleanup",
+        _ => ".roc_cleanup",
+    }
+}
+
+/// Lists the call sites of the procedures, by the names of their symbols, in the cleanup table
+pub(crate) fn emit_cleanup_table<'p>(
+    output: &mut Object,
+    procs: impl Iterator<Item = (&'p str, &'p [CallCleanup])>,
+    endianness: Endianness,
+) {
+    let section_id = output.add_section(
+        output.segment_name(StandardSegment::Data).to_vec(),
+        cleanup_section_name(output.format()).as_bytes().to_vec(),
+        SectionKind::Data,
+    );
+
+    for (fn_name, sites) in procs {
+        let symbol = match output.symbol_id(fn_name.as_bytes()) {
+            Some(symbol) => symbol,
+            None => internal_error!("failed to find fn symbol for {:?}", fn_name),
+        };
+
+        for site in sites {
+            let mut entry = vec![0; 16 + 8 * site.values.len()];
+            entry[8..12].copy_from_slice(&endianness.u32_bytes(site.values.len() as u32));
+            for (value, bytes) in site.values.iter().zip(entry[16..].chunks_mut(8)) {
+                bytes[..4].copy_from_slice(&endianness.i32_bytes(value.offset));
+                bytes[4..].copy_from_slice(&endianness.u32_bytes(value.kind as u32));
+            }
+            let entry_offset = output.append_section_data(section_id, &entry, 8);
+
+            let reloc = write::Relocation {
+                offset: entry_offset,
+                size: 64,
+                kind: RelocationKind::Absolute,
+                encoding: RelocationEncoding::Generic,
+                symbol,
+                addend: site.return_offset as i64,
+            };
+            if let Err(e) = output.add_relocation(section_id, reloc) {
+                internal_error!("{:?}", e);
+            }
+        }
+    }
+}
+
+/// The call sites in an object that was built with `Env::panic_cleanup`, in the order of the table
+pub fn read_cleanup_sites(bytes: &[u8]) -> Result<std::vec::Vec<CleanupSite>, object::Error> {
+    use object::{Object as _, ObjectSection, ObjectSymbol, RelocationTarget};
+
+    let file = object::File::parse(bytes)?;
+    let section = match file.section_by_name(cleanup_section_name(file.format())) {
+        Some(section) => section,
+        None => return Ok(std::vec::Vec::new()),
+    };
+    let data = section.data()?;
+    let endianness = if file.is_little_endian() {
+        Endianness::Little
+    } else {
+        Endianness::Big
+    };
+    let word =
+        |offset: usize| endianness.u64_from_bytes(data[offset..offset + 8].try_into().unwrap());
+    let half =
+        |offset: usize| endianness.u32_from_bytes(data[offset..offset + 4].try_into().unwrap());
+
+    let mut relocations: std::vec::Vec<_> = section.relocations().collect();
+    relocations.sort_by_key(|(offset, _)| *offset);
+
+    let mut sites = std::vec::Vec::with_capacity(relocations.len());
+    for (entry_offset, reloc) in relocations {
+        let index = match reloc.target() {
+            RelocationTarget::Symbol(index) => index,
+            _ => continue,
+        };
+        let start = entry_offset as usize;
+        let return_offset = if reloc.has_implicit_addend() {
+            word(start)
+        } else {
+            reloc.addend() as u64
+        };
+
+        let count = half(start + 8) as usize;
+        let mut values = std::vec::Vec::with_capacity(count);
+        for value in 0..count {
+            let value_start = start + 16 + 8 * value;
+            let kind = match CleanupKind::from_u32(half(value_start + 4)) {
+                Some(kind) => kind,
+                None => internal_error!("unknown kind of value at {} in the cleanup table", start),
+            };
+
+            values.push(CleanupValue {
+                offset: half(value_start) as i32,
+                kind,
+            });
+        }
+
+        sites.push(CleanupSite {
+            name: file.symbol_by_index(index)?.name()?.to_string(),
+            return_offset,
+            values,
+        });
+    }
+
+    Ok(sites)
+}
@@ -0,0 +1,409 @@
+//! Constant folding and strength reduction of integer arithmetic.
+//!
+//! The dev backend does not run an optimizer, so `x * 8` would become an `imul`
+//! and `2 + 3` an `add` of two registers. Instead, the low-level calls below are rewritten
+//! right before they are built. An argument counts as known when it is a literal that was
+//! not loaded yet, so this only applies with lazy literals.
+//! A call that would overflow is never folded, and gets built as usual. A multiplication only
+//! becomes a shift or an add when the [CostModel] of the target says that is not slower.
+use crate::cost_model::{CostModel, PlannedInst};
+use roc_builtins::bitcode::IntWidth;
+use roc_module::low_level::LowLevel;
+use roc_module::symbol::Symbol;
+
+/// An argument of a low-level call, with its value if that is known
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Operand {
+    pub symbol: Symbol,
+    pub value: Option<i128>,
+}
+
+/// What to build instead of a low-level call
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Rewrite {
+    /// The result is known to be this integer
+    Int(i128),
+    /// The result is known to be this boolean
+    Bool(bool),
+    /// The result is `src` itself
+    Move(Symbol),
+    /// `src << shift`
+    ShiftLeft { src: Symbol, shift: u8 },
+    /// `src + (src << shift)`, for multiplying by 3, 5, or 9
+    AddShifted { src: Symbol, shift: u8 },
+    /// `src / (1 << shift)`, rounding toward zero
+    DivPow2 {
+        src: Symbol,
+        shift: u8,
+        signed: bool,
+    },
+}
+
+/// The rewrite of `lowlevel` applied to `lhs` and `rhs`, both integers of `int_width`,
+/// if there is one.
+pub fn rewrite_int_op(
+    lowlevel: LowLevel,
+    int_width: IntWidth,
+    lhs: Operand,
+    rhs: Operand,
+    costs: &dyn CostModel,
+) -> Option<Rewrite> {
+    if let (Some(a), Some(b)) = (lhs.value, rhs.value) {
+        return fold(lowlevel, int_width, a, b);
+    }
+
+    if lhs.symbol == rhs.symbol {
+        match lowlevel {
+            LowLevel::NumLt | LowLevel::NumGt | LowLevel::NotEq => {
+                return Some(Rewrite::Bool(false))
+            }
+            LowLevel::NumLte | LowLevel::NumGte | LowLevel::Eq => return Some(Rewrite::Bool(true)),
+            _ => {}
+        }
+    }
+
+    match (lowlevel, lhs.value, rhs.value) {
+        (LowLevel::NumAdd, Some(0), _) => Some(Rewrite::Move(rhs.symbol)),
+        (LowLevel::NumAdd | LowLevel::NumSub | LowLevel::NumSubWrap, _, Some(0)) => {
+            Some(Rewrite::Move(lhs.symbol))
+        }
+        (LowLevel::NumMul, Some(factor), None) => reduce_mul(int_width, rhs.symbol, factor, costs),
+        (LowLevel::NumMul, None, Some(factor)) => reduce_mul(int_width, lhs.symbol, factor, costs),
+        (LowLevel::NumDivTruncUnchecked, None, Some(divisor)) => {
+            reduce_div(int_width, lhs.symbol, divisor)
+        }
+        // nothing unsigned is below zero
+        (LowLevel::NumLt, _, Some(0)) | (LowLevel::NumGt, Some(0), _) if !int_width.is_signed() => {
+            Some(Rewrite::Bool(false))
+        }
+        (LowLevel::NumGte, _, Some(0)) | (LowLevel::NumLte, Some(0), _)
+            if !int_width.is_signed() =>
+        {
+            Some(Rewrite::Bool(true))
+        }
+        _ => None,
+    }
+}
+
+fn fold(lowlevel: LowLevel, int_width: IntWidth, a: i128, b: i128) -> Option<Rewrite> {
+    // the values fit in 64 bits, so only a multiplication can overflow an i128
+    let result = match lowlevel {
+        LowLevel::NumAdd => a + b,
+        LowLevel::NumSub => a - b,
+        LowLevel::NumSubWrap => return Some(Rewrite::Int(wrap(int_width, a - b))),
+        LowLevel::NumMul => a.checked_mul(b)?,
+        LowLevel::NumDivTruncUnchecked if b != 0 => a / b,
+        LowLevel::NumLt => return Some(Rewrite::Bool(a < b)),
+        LowLevel::NumGt => return Some(Rewrite::Bool(a > b)),
+        LowLevel::NumLte => return Some(Rewrite::Bool(a <= b)),
+        LowLevel::NumGte => return Some(Rewrite::Bool(a >= b)),
+        LowLevel::Eq => return Some(Rewrite::Bool(a == b)),
+        LowLevel::NotEq => return Some(Rewrite::Bool(a != b)),
+        _ => return None,
+    };
+
+    let (min, max) = bounds(int_width);
+    (min..=max)
+        .contains(&result)
+        .then_some(Rewrite::Int(result))
+}
+
+fn reduce_mul(
+    int_width: IntWidth,
+    src: Symbol,
+    factor: i128,
+    costs: &dyn CostModel,
+) -> Option<Rewrite> {
+    let (rewrite, insts): (Rewrite, &[PlannedInst]) = match factor {
+        0 => return Some(Rewrite::Int(0)),
+        1 => return Some(Rewrite::Move(src)),
+        3 | 5 | 9 => {
+            let shift = (factor - 1).trailing_zeros() as u8;
+            (
+                Rewrite::AddShifted { src, shift },
+                &[PlannedInst::AddShifted],
+            )
+        }
+        _ => {
+            let shift = power_of_two(int_width, factor)?;
+            (
+                Rewrite::ShiftLeft { src, shift },
+                &[PlannedInst::MovRegReg, PlannedInst::ShlImm],
+            )
+        }
+    };
+
+    // what the multiplication would be: the factor in a register, and `imul` of a copy of `src`
+    let mul = [
+        PlannedInst::MovImm(factor as i64),
+        PlannedInst::MovRegReg,
+        PlannedInst::MulRegReg,
+    ];
+    (costs.cost(insts) <= costs.cost(&mul)).then_some(rewrite)
+}
+
+fn reduce_div(int_width: IntWidth, src: Symbol, divisor: i128) -> Option<Rewrite> {
+    if divisor == 1 {
+        return Some(Rewrite::Move(src));
+    }
+
+    // the shifts work on all 64 bits, so a narrower integer could have the wrong sign bit
+    match int_width {
+        IntWidth::I64 | IntWidth::U64 => {
+            power_of_two(int_width, divisor).map(|shift| Rewrite::DivPow2 {
+                src,
+                shift,
+                signed: int_width.is_signed(),
+            })
+        }
+        _ => None,
+    }
+}
+
+/// `k` if `value` is `2^k` for a `k` of at least 1, and `2^k` fits in `int_width`
+fn power_of_two(int_width: IntWidth, value: i128) -> Option<u8> {
+    let (_, max) = bounds(int_width);
+    (value > 1 && value <= max && value.count_ones() == 1).then(|| value.trailing_zeros() as u8)
+}
+
+fn bounds(int_width: IntWidth) -> (i128, i128) {
+    let bits = int_width.stack_size() * 8;
+    if int_width.is_signed() {
+        (-(1 << (bits - 1)), (1 << (bits - 1)) - 1)
+    } else {
+        (0, (1 << bits) - 1)
+    }
+}
+
+fn wrap(int_width: IntWidth, value: i128) -> i128 {
+    let bits = int_width.stack_size() * 8;
+    let low_bits = value & ((1 << bits) - 1);
+    if int_width.is_signed() && low_bits >> (bits - 1) == 1 {
+        low_bits - (1 << bits)
+    } else {
+        low_bits
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cost_model::LatencyTable;
+    use crate::generic64::x86_64::X86_64CostModel;
+
+    const X: Symbol = Symbol::DEV_TMP;
+    const Y: Symbol = Symbol::DEV_TMP2;
+
+    fn unknown(symbol: Symbol) -> Operand {
+        Operand {
+            symbol,
+            value: None,
+        }
+    }
+
+    fn known(value: i128) -> Operand {
+        Operand {
+            symbol: Symbol::DEV_TMP3,
+            value: Some(value),
+        }
+    }
+
+    /// The rewrite for the default x86-64 target
+    fn rewrite_int_op(
+        lowlevel: LowLevel,
+        int_width: IntWidth,
+        lhs: Operand,
+        rhs: Operand,
+    ) -> Option<Rewrite> {
+        super::rewrite_int_op(lowlevel, int_width, lhs, rhs, &X86_64CostModel::DEFAULT)
+    }
+
+    #[test]
+    fn literals_are_folded() {
+        use IntWidth::*;
+        use LowLevel::*;
+
+        let rewrite = rewrite_int_op;
+
+        assert_eq!(
+            rewrite(NumAdd, I64, known(2), known(3)),
+            Some(Rewrite::Int(5))
+        );
+        assert_eq!(
+            rewrite(NumMul, U8, known(15), known(17)),
+            Some(Rewrite::Int(255))
+        );
+        assert_eq!(
+            rewrite(NumDivTruncUnchecked, I64, known(-7), known(2)),
+            Some(Rewrite::Int(-3))
+        );
+        assert_eq!(
+            rewrite(NumLt, I64, known(-1), known(0)),
+            Some(Rewrite::Bool(true))
+        );
+        assert_eq!(
+            rewrite(NumSubWrap, U8, known(0), known(1)),
+            Some(Rewrite::Int(255))
+        );
+        assert_eq!(
+            rewrite(NumSubWrap, I8, known(-128), known(1)),
+            Some(Rewrite::Int(127))
+        );
+
+        // overflow is left to the generated code
+        assert_eq!(rewrite(NumAdd, I8, known(100), known(100)), None);
+        assert_eq!(rewrite(NumSub, U64, known(0), known(1)), None);
+        assert_eq!(
+            rewrite(
+                NumMul,
+                U64,
+                known(u64::MAX as i128),
+                known(u64::MAX as i128)
+            ),
+            None
+        );
+        assert_eq!(
+            rewrite(
+                NumDivTruncUnchecked,
+                I64,
+                known(i64::MIN as i128),
+                known(-1)
+            ),
+            None
+        );
+        assert_eq!(rewrite(NumDivTruncUnchecked, I64, known(1), known(0)), None);
+    }
+
+    #[test]
+    fn multiplications_are_strength_reduced() {
+        let mul =
+            |factor| rewrite_int_op(LowLevel::NumMul, IntWidth::I64, unknown(X), known(factor));
+
+        assert_eq!(mul(0), Some(Rewrite::Int(0)));
+        assert_eq!(mul(1), Some(Rewrite::Move(X)));
+        assert_eq!(mul(8), Some(Rewrite::ShiftLeft { src: X, shift: 3 }));
+        assert_eq!(mul(1 << 62), Some(Rewrite::ShiftLeft { src: X, shift: 62 }));
+        assert_eq!(mul(9), Some(Rewrite::AddShifted { src: X, shift: 3 }));
+        assert_eq!(mul(6), None);
+        assert_eq!(mul(-4), None);
+
+        // the factor can be on either side
+        assert_eq!(
+            rewrite_int_op(LowLevel::NumMul, IntWidth::U32, known(4), unknown(X)),
+            Some(Rewrite::ShiftLeft { src: X, shift: 2 })
+        );
+    }
+
+    #[test]
+    fn multiplications_stay_when_the_target_multiplies_fast() {
+        let fast_mul = X86_64CostModel {
+            latencies: LatencyTable {
+                mul: 0,
+                shift: 3,
+                lea: 3,
+                ..X86_64CostModel::DEFAULT.latencies
+            },
+        };
+        let mul = |factor| {
+            super::rewrite_int_op(
+                LowLevel::NumMul,
+                IntWidth::I64,
+                unknown(X),
+                known(factor),
+                &fast_mul,
+            )
+        };
+
+        assert_eq!(mul(8), None);
+        assert_eq!(mul(9), None);
+        // nothing beats no instructions at all
+        assert_eq!(mul(0), Some(Rewrite::Int(0)));
+        assert_eq!(mul(1), Some(Rewrite::Move(X)));
+    }
+
+    #[test]
+    fn divisions_are_strength_reduced() {
+        let div = |int_width, divisor| {
+            rewrite_int_op(
+                LowLevel::NumDivTruncUnchecked,
+                int_width,
+                unknown(X),
+                known(divisor),
+            )
+        };
+
+        assert_eq!(div(IntWidth::I32, 1), Some(Rewrite::Move(X)));
+        assert_eq!(
+            div(IntWidth::I64, 4),
+            Some(Rewrite::DivPow2 {
+                src: X,
+                shift: 2,
+                signed: true
+            })
+        );
+        assert_eq!(
+            div(IntWidth::U64, 1 << 63),
+            Some(Rewrite::DivPow2 {
+                src: X,
+                shift: 63,
+                signed: false
+            })
+        );
+        assert_eq!(div(IntWidth::I32, 4), None);
+        assert_eq!(div(IntWidth::I64, -2), None);
+
+        // a known dividend alone does not help
+        assert_eq!(
+            rewrite_int_op(
+                LowLevel::NumDivTruncUnchecked,
+                IntWidth::I64,
+                known(4),
+                unknown(X)
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn identities_become_moves() {
+        use LowLevel::*;
+
+        let rewrite = |lowlevel, a, b| rewrite_int_op(lowlevel, IntWidth::I64, a, b);
+
+        assert_eq!(
+            rewrite(NumAdd, known(0), unknown(X)),
+            Some(Rewrite::Move(X))
+        );
+        assert_eq!(
+            rewrite(NumSub, unknown(X), known(0)),
+            Some(Rewrite::Move(X))
+        );
+        assert_eq!(rewrite(NumSub, known(0), unknown(X)), None);
+        assert_eq!(rewrite(NumAdd, unknown(X), unknown(Y)), None);
+    }
+
+    #[test]
+    fn comparisons_with_known_outcomes() {
+        use LowLevel::*;
+
+        let rewrite = rewrite_int_op;
+
+        assert_eq!(
+            rewrite(NumLt, IntWidth::U64, unknown(X), known(0)),
+            Some(Rewrite::Bool(false))
+        );
+        assert_eq!(
+            rewrite(NumLte, IntWidth::U8, known(0), unknown(X)),
+            Some(Rewrite::Bool(true))
+        );
+        assert_eq!(rewrite(NumLt, IntWidth::I64, unknown(X), known(0)), None);
+        assert_eq!(
+            rewrite(NumGte, IntWidth::I64, unknown(X), unknown(X)),
+            Some(Rewrite::Bool(true))
+        );
+        assert_eq!(
+            rewrite(NotEq, IntWidth::I64, unknown(X), unknown(X)),
+            Some(Rewrite::Bool(false))
+        );
+    }
+}
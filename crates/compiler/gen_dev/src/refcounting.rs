@@ -0,0 +1,173 @@
+//! Expands the refcounting statements of all procedures into calls to specialized refcounting
+//! procedures, before any code is generated. This is the only part of building a procedure that
+//! adds idents and layouts, so once it is done the procedures can be built on several threads.
+use bumpalo::collections::Vec;
+use bumpalo::Bump;
+use roc_collections::all::MutMap;
+use roc_error_macros::internal_error;
+use roc_module::symbol::{IdentIds, ModuleId, Symbol};
+use roc_mono::code_gen_help::CodeGenHelp;
+use roc_mono::ir::{Proc, ProcLayout, Stmt};
+use roc_mono::layout::{InLayout, STLayoutInterner};
+use roc_target::TargetInfo;
+
+/// Rewrites the bodies of `procs` without refcounting statements, and returns the
+/// refcounting procedures they call now, in the order they were first needed.
+pub fn expand_refcounting<'a>(
+    arena: &'a Bump,
+    target_info: TargetInfo,
+    module_id: ModuleId,
+    ident_ids: &mut IdentIds,
+    layout_interner: &mut STLayoutInterner<'a>,
+    procs: &mut [((Symbol, ProcLayout<'a>), Proc<'a>)],
+) -> std::vec::Vec<((Symbol, ProcLayout<'a>), Proc<'a>)> {
+    let mut expander = Expander {
+        arena,
+        ident_ids,
+        layout_interner,
+        helper_proc_gen: CodeGenHelp::new(arena, target_info, module_id),
+        helper_proc_symbols: Vec::new_in(arena),
+        layout_map: MutMap::default(),
+    };
+
+    for (_, proc) in procs.iter_mut() {
+        expander.layout_map.clear();
+        for (layout, symbol) in proc.args {
+            expander.layout_map.insert(*symbol, *layout);
+        }
+        proc.body = expander.expand(&proc.body);
+    }
+
+    let helper_procs = expander.helper_proc_gen.take_procs();
+    expander
+        .helper_proc_symbols
+        .into_iter()
+        .zip(helper_procs)
+        .collect()
+}
+
+struct Expander<'a, 'i> {
+    arena: &'a Bump,
+    ident_ids: &'i mut IdentIds,
+    layout_interner: &'i mut STLayoutInterner<'a>,
+    helper_proc_gen: CodeGenHelp<'a>,
+    helper_proc_symbols: Vec<'a, (Symbol, ProcLayout<'a>)>,
+    /// The layouts of the symbols defined so far in the current procedure
+    layout_map: MutMap<Symbol, InLayout<'a>>,
+}
+
+impl<'a, 'i> Expander<'a, 'i> {
+    /// Visits the statements in the order the backend builds them,
+    /// so the helpers end up in the same order as when they were expanded while building.
+    fn expand(&mut self, stmt: &Stmt<'a>) -> Stmt<'a> {
+        let arena = self.arena;
+
+        match stmt {
+            Stmt::Let(symbol, expr, layout, continuation) => {
+                self.layout_map.insert(*symbol, *layout);
+                let continuation = self.expand(continuation);
+
+                Stmt::Let(*symbol, expr.clone(), *layout, arena.alloc(continuation))
+            }
+            Stmt::Refcounting(modify, following) => {
+                let symbol = modify.get_symbol();
+                let layout = match self.layout_map.get(&symbol) {
+                    Some(layout) => *layout,
+                    None => internal_error!("the symbol, {:?}, has no known layout", symbol),
+                };
+
+                let (rc_stmt, new_specializations) = self.helper_proc_gen.expand_refcount_stmt(
+                    self.ident_ids,
+                    self.layout_interner,
+                    layout,
+                    modify,
+                    following,
+                );
+                self.helper_proc_symbols.extend(new_specializations);
+
+                // The expanded statement ends in `following`, which may refcount more symbols.
+                self.expand(rc_stmt)
+            }
+            Stmt::Switch {
+                cond_symbol,
+                cond_layout,
+                branches,
+                default_branch,
+                ret_layout,
+            } => {
+                let new_branches = Vec::from_iter_in(
+                    branches
+                        .iter()
+                        .map(|(id, info, branch)| (*id, info.clone(), self.expand(branch))),
+                    arena,
+                );
+                let default_stmt = self.expand(default_branch.1);
+
+                Stmt::Switch {
+                    cond_symbol: *cond_symbol,
+                    cond_layout: *cond_layout,
+                    branches: new_branches.into_bump_slice(),
+                    default_branch: (default_branch.0.clone(), arena.alloc(default_stmt)),
+                    ret_layout: *ret_layout,
+                }
+            }
+            Stmt::Expect {
+                condition,
+                region,
+                lookups,
+                variables,
+                remainder,
+            } => Stmt::Expect {
+                condition: *condition,
+                region: *region,
+                lookups: *lookups,
+                variables: *variables,
+                remainder: arena.alloc(self.expand(remainder)),
+            },
+            Stmt::ExpectFx {
+                condition,
+                region,
+                lookups,
+                variables,
+                remainder,
+            } => Stmt::ExpectFx {
+                condition: *condition,
+                region: *region,
+                lookups: *lookups,
+                variables: *variables,
+                remainder: arena.alloc(self.expand(remainder)),
+            },
+            Stmt::Dbg {
+                symbol,
+                variable,
+                remainder,
+            } => Stmt::Dbg {
+                symbol: *symbol,
+                variable: *variable,
+                remainder: arena.alloc(self.expand(remainder)),
+            },
+            Stmt::Join {
+                id,
+                parameters,
+                body,
+                remainder,
+            } => {
+                for param in parameters.iter() {
+                    self.layout_map.insert(param.symbol, param.layout);
+                }
+
+                // `build_join` builds the remainder first
+                let remainder = self.expand(remainder);
+                let body = self.expand(body);
+
+                Stmt::Join {
+                    id: *id,
+                    parameters: *parameters,
+                    body: arena.alloc(body),
+                    remainder: arena.alloc(remainder),
+                }
+            }
+            Stmt::Ret(_) | Stmt::Jump(_, _) | Stmt::Crash(..) => stmt.clone(),
+        }
+    }
+}
@@ -0,0 +1,161 @@
+//! Filling in the pc-relative relocations of the code the backend emits, for anything that links
+//! it by hand, like a runtime that loads procedures into memory. A displacement only has the width
+//! of its relocation, so a target that ends up too far away is an error instead of a displacement
+//! that wrapped around.
+use crate::Relocation;
+use roc_error_macros::internal_error;
+
+/// A relocation whose target is too far away for the displacement to fit in it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RelocationOutOfRange {
+    /// the procedure the relocation is in
+    pub source: String,
+    /// what the relocation refers to
+    pub target: String,
+    /// the width of the displacement, in bits
+    pub bits: u32,
+    /// how far the target is from the place of the relocation, addend included
+    pub distance: i128,
+}
+
+impl std::fmt::Display for RelocationOutOfRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "the {}-bit relocation in {} to {} is out of range: {} is {} bytes away",
+            self.bits, self.source, self.target, self.target, self.distance
+        )
+    }
+}
+
+impl std::error::Error for RelocationOutOfRange {}
+
+/// The displacement from `place` to `target_address`, plus `addend`, if it fits in `bits` bits
+pub fn pc_relative_displacement(
+    source: &str,
+    target: &str,
+    place: u64,
+    target_address: u64,
+    addend: i64,
+    bits: u32,
+) -> Result<i64, RelocationOutOfRange> {
+    let distance = target_address as i128 - place as i128 + addend as i128;
+    let limit = 1i128 << (bits - 1);
+
+    if (-limit..limit).contains(&distance) {
+        Ok(distance as i64)
+    } else {
+        Err(RelocationOutOfRange {
+            source: source.to_string(),
+            target: target.to_string(),
+            bits,
+            distance,
+        })
+    }
+}
+
+/// Writes the `rel32` of `reloc`, which is in the code of `source`, for a target at
+/// `target_address`. Like the relocations in the object, the displacement is relative to the end
+/// of its 4 bytes, and `code` is loaded at `code_address`.
+pub fn link_relocation(
+    code: &mut [u8],
+    code_address: u64,
+    source: &str,
+    reloc: &Relocation,
+    target_address: u64,
+) -> Result<(), RelocationOutOfRange> {
+    let (offset, target) = match reloc {
+        Relocation::LocalData { offset, .. } => (*offset, "its local data"),
+        Relocation::LinkedFunction { offset, name } | Relocation::LinkedData { offset, name } => {
+            (*offset, name.as_str())
+        }
+        Relocation::JmpToReturn { .. } => {
+            internal_error!(
+                "jumps to the return of {} are resolved by the backend",
+                source
+            )
+        }
+    };
+
+    let place = code_address.wrapping_add(offset);
+    let displacement = pc_relative_displacement(source, target, place, target_address, -4, 32)?;
+    let offset = offset as usize;
+    code[offset..offset + 4].copy_from_slice(&(displacement as i32).to_le_bytes());
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call_to(name: &str) -> Relocation {
+        Relocation::LinkedFunction {
+            offset: 1,
+            name: name.to_string(),
+        }
+    }
+
+    #[test]
+    fn in_range_relocations_patch_the_displacement() {
+        // `call rel32; ret`
+        let mut code = [0xE8, 0, 0, 0, 0, 0xC3];
+        link_relocation(&mut code, 0x1000, "main", &call_to("step"), 0x1040).unwrap();
+        assert_eq!(code, [0xE8, 0x3B, 0, 0, 0, 0xC3]);
+
+        link_relocation(&mut code, 0x1000, "main", &call_to("step"), 0x1000).unwrap();
+        assert_eq!(code, [0xE8, 0xFB, 0xFF, 0xFF, 0xFF, 0xC3]);
+
+        // the farthest targets that still fit, in both directions
+        let place = 0x1000_0000 + 5;
+        let farthest = place + i32::MAX as u64;
+        link_relocation(&mut code, 0x1000_0000, "main", &call_to("step"), farthest).unwrap();
+        assert_eq!(code, [0xE8, 0xFF, 0xFF, 0xFF, 0x7F, 0xC3]);
+
+        let nearest = place - (1 << 31);
+        link_relocation(&mut code, 0x1000_0000, "main", &call_to("step"), nearest).unwrap();
+        assert_eq!(code, [0xE8, 0, 0, 0, 0x80, 0xC3]);
+    }
+
+    #[test]
+    fn out_of_range_relocations_are_errors() {
+        // data that ends up one byte past what a rel32 can reach
+        let mut code = [0x48, 0x8B, 0x05, 0, 0, 0, 0];
+        let reloc = Relocation::LinkedData {
+            offset: 3,
+            name: "roc_globals".to_string(),
+        };
+        let code_address = 0x40_0000;
+        let data_address = code_address + 7 + (1 << 31);
+
+        let error = link_relocation(&mut code, code_address, "main", &reloc, data_address);
+        assert_eq!(
+            error,
+            Err(RelocationOutOfRange {
+                source: "main".to_string(),
+                target: "roc_globals".to_string(),
+                bits: 32,
+                distance: 1 << 31,
+            })
+        );
+        // nothing was written
+        assert_eq!(code, [0x48, 0x8B, 0x05, 0, 0, 0, 0]);
+
+        // a target before the code, like a builtin in a library loaded far below it
+        let error =
+            link_relocation(&mut code, u64::MAX - 0xFFF, "main", &call_to("step"), 0).unwrap_err();
+        assert_eq!(error.target, "step");
+        assert_eq!(error.distance, -(u64::MAX as i128 - 0xFFA));
+    }
+
+    #[test]
+    fn addends_that_overflow_are_errors() {
+        let result = pc_relative_displacement("main", "step", 0, i32::MAX as u64, 1, 32);
+        assert_eq!(result.unwrap_err().distance, 1 << 31);
+
+        assert_eq!(
+            pc_relative_displacement("main", "step", 0, 0, i64::MIN, 64),
+            Ok(i64::MIN)
+        );
+    }
+}
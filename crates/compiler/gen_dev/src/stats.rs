@@ -0,0 +1,471 @@
+//! Register allocation statistics, for tuning the dev backend, and how long its phases take.
+//!
+//! They are only collected with the `stats` feature (and in this crate's tests).
+//! Otherwise the types below are zero-sized and every `record_*` call compiles to nothing.
+//! Even with the feature, the phases are only timed in statistics made by `CodeGenStats::timed`.
+
+#[cfg(any(test, feature = "stats"))]
+pub use enabled::{CodeGenStats, PhaseTimes, ProcStats};
+
+#[cfg(not(any(test, feature = "stats")))]
+pub use disabled::{CodeGenStats, PhaseTimes, ProcStats};
+
+#[cfg(any(test, feature = "stats"))]
+mod enabled {
+    use std::fmt::Write;
+    use std::time::{Duration, Instant};
+
+    /// The statistics of a single procedure
+    #[derive(Debug, Clone, Default, PartialEq, Eq)]
+    pub struct ProcStats {
+        pub name: String,
+        /// size of the finished procedure, including the prologue and epilogue
+        pub code_bytes: usize,
+        pub general_regs_used: u32,
+        pub float_regs_used: u32,
+        /// values that were moved out of a register to make room for another value
+        pub spills: u32,
+        /// values that had to be loaded back into a register after being spilled
+        pub reloads: u32,
+        /// values that were moved to the register they are returned or passed in
+        pub moves: u32,
+        /// the aligned size of the stack frame
+        pub frame_size: u32,
+        /// how long lowering the procedure took, if it was timed
+        pub lowering_time: Option<Duration>,
+        // bitsets of the registers used so far, indexed by `RegTrait::to_index`
+        general_regs_seen: u64,
+        float_regs_seen: u64,
+        lowering_started: Option<Instant>,
+    }
+
+    impl ProcStats {
+        pub fn reset(&mut self, name: &str) {
+            *self = Self::default();
+            self.name.push_str(name);
+        }
+
+        pub fn record_general_reg(&mut self, index: usize) {
+            let bit = 1u64 << index;
+            if self.general_regs_seen & bit == 0 {
+                self.general_regs_seen |= bit;
+                self.general_regs_used += 1;
+            }
+        }
+
+        pub fn record_float_reg(&mut self, index: usize) {
+            let bit = 1u64 << index;
+            if self.float_regs_seen & bit == 0 {
+                self.float_regs_seen |= bit;
+                self.float_regs_used += 1;
+            }
+        }
+
+        pub fn record_spill(&mut self) {
+            self.spills += 1;
+        }
+
+        pub fn record_reload(&mut self) {
+            self.reloads += 1;
+        }
+
+        pub fn record_move(&mut self) {
+            self.moves += 1;
+        }
+
+        /// Times lowering the procedure, until `record_finished`
+        pub fn start_timer(&mut self) {
+            self.lowering_started = Some(Instant::now());
+        }
+
+        pub fn record_finished(&mut self, code_bytes: usize, frame_size: u32) {
+            self.code_bytes = code_bytes;
+            self.frame_size = frame_size;
+            self.lowering_time = self
+                .lowering_started
+                .take()
+                .map(|started| started.elapsed());
+        }
+    }
+
+    /// How long the phases of building a module took
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+    pub struct PhaseTimes {
+        /// lowering the procedures, summed over all threads that built some
+        pub lowering: Duration,
+        /// adding the procedures to the object, and patching their relocations
+        pub relocations: Duration,
+        /// writing the object file
+        pub object_writing: Duration,
+    }
+
+    impl PhaseTimes {
+        fn add(&mut self, other: &PhaseTimes) {
+            self.lowering += other.lowering;
+            self.relocations += other.relocations;
+            self.object_writing += other.object_writing;
+        }
+    }
+
+    /// The statistics of all procedures in a module, in the order they were built
+    #[derive(Debug, Clone, Default, PartialEq, Eq)]
+    pub struct CodeGenStats {
+        pub procs: Vec<ProcStats>,
+        /// procedures that were built, but left out of the object because nothing calls them
+        pub dropped_procs: u32,
+        /// calls that were replaced with the body of the called procedure
+        pub inlined_calls: u32,
+        /// how long the phases took, if they were timed
+        pub times: Option<PhaseTimes>,
+    }
+
+    impl CodeGenStats {
+        /// Statistics that also time the phases of the build
+        pub fn timed() -> Self {
+            CodeGenStats {
+                times: Some(PhaseTimes::default()),
+                ..Default::default()
+            }
+        }
+
+        pub fn is_timed(&self) -> bool {
+            self.times.is_some()
+        }
+
+        pub fn record_proc(&mut self, stats: &ProcStats) {
+            if let (Some(times), Some(lowering_time)) = (&mut self.times, stats.lowering_time) {
+                times.lowering += lowering_time;
+            }
+            self.procs.push(stats.clone());
+        }
+
+        pub fn record_relocation_time(&mut self, time: Duration) {
+            if let Some(times) = &mut self.times {
+                times.relocations += time;
+            }
+        }
+
+        pub fn record_object_writing_time(&mut self, time: Duration) {
+            if let Some(times) = &mut self.times {
+                times.object_writing += time;
+            }
+        }
+
+        pub fn record_dropped_procs(&mut self, count: u32) {
+            self.dropped_procs += count;
+        }
+
+        pub fn record_inlined_calls(&mut self, count: u32) {
+            self.inlined_calls += count;
+        }
+
+        /// Adds the statistics of another thread, which built the procedures after these
+        pub fn merge(&mut self, other: CodeGenStats) {
+            self.procs.extend(other.procs);
+            self.dropped_procs += other.dropped_procs;
+            self.inlined_calls += other.inlined_calls;
+            match (&mut self.times, other.times) {
+                (Some(times), Some(other_times)) => times.add(&other_times),
+                (None, other_times) => self.times = other_times,
+                (Some(_), None) => {}
+            }
+        }
+
+        /// How long the phases took in total, followed by the 10 procedures that took longest
+        /// to lower
+        pub fn summary(&self) -> String {
+            let times = match &self.times {
+                Some(times) => times,
+                None => return String::from("the phases of the dev backend were not timed\n"),
+            };
+
+            let mut out = String::new();
+            let total = times.lowering + times.relocations + times.object_writing;
+            for (phase, time) in [
+                ("lowering", times.lowering),
+                ("relocations", times.relocations),
+                ("object writing", times.object_writing),
+                ("total", total),
+            ] {
+                writeln!(out, "{:<14} {:>12}", phase, format!("{:?}", time)).unwrap();
+            }
+
+            let mut slowest: Vec<_> = self
+                .procs
+                .iter()
+                .filter_map(|proc| Some((proc.lowering_time?, proc.name.as_str())))
+                .collect();
+            // the slowest first, and procedures that took as long by name
+            slowest.sort_by(|(a_time, a_name), (b_time, b_name)| {
+                b_time.cmp(a_time).then(a_name.cmp(b_name))
+            });
+            if !slowest.is_empty() {
+                out.push_str("slowest procs:\n");
+            }
+            for (time, name) in slowest.into_iter().take(10) {
+                writeln!(out, "  {:>12} {}", format!("{:?}", time), name).unwrap();
+            }
+
+            out
+        }
+
+        /// A table with one row per procedure, followed by the totals
+        pub fn report(&self) -> String {
+            let name_width = self
+                .procs
+                .iter()
+                .map(|proc| proc.name.len())
+                .chain(["proc".len()])
+                .max()
+                .unwrap_or_default();
+
+            let mut out = String::new();
+            let mut row = |name: &str, stats: [u64; 7]| {
+                write!(out, "{:<width$}", name, width = name_width).unwrap();
+                for stat in stats {
+                    write!(out, " {:>10}", stat).unwrap();
+                }
+                out.push('\n');
+            };
+
+            let mut header = format!("{:<width$}", "proc", width = name_width);
+            for title in [
+                "bytes",
+                "gen regs",
+                "float regs",
+                "spills",
+                "reloads",
+                "moves",
+                "frame",
+            ] {
+                write!(header, " {:>10}", title).unwrap();
+            }
+            header.push('\n');
+
+            let mut totals = [0; 7];
+            for proc in self.procs.iter() {
+                let stats = [
+                    proc.code_bytes as u64,
+                    proc.general_regs_used as u64,
+                    proc.float_regs_used as u64,
+                    proc.spills as u64,
+                    proc.reloads as u64,
+                    proc.moves as u64,
+                    proc.frame_size as u64,
+                ];
+                for (total, stat) in totals.iter_mut().zip(stats) {
+                    *total += stat;
+                }
+                row(&proc.name, stats);
+            }
+            row("total", totals);
+
+            if self.dropped_procs > 0 {
+                writeln!(out, "dropped {} unreachable procs", self.dropped_procs).unwrap();
+            }
+
+            if self.inlined_calls > 0 {
+                writeln!(out, "inlined {} calls", self.inlined_calls).unwrap();
+            }
+
+            header + &out
+        }
+    }
+}
+
+#[cfg(not(any(test, feature = "stats")))]
+mod disabled {
+    use std::time::Duration;
+
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+    pub struct ProcStats;
+
+    impl ProcStats {
+        #[inline(always)]
+        pub fn reset(&mut self, _name: &str) {}
+        #[inline(always)]
+        pub fn record_general_reg(&mut self, _index: usize) {}
+        #[inline(always)]
+        pub fn record_float_reg(&mut self, _index: usize) {}
+        #[inline(always)]
+        pub fn record_spill(&mut self) {}
+        #[inline(always)]
+        pub fn record_reload(&mut self) {}
+        #[inline(always)]
+        pub fn record_move(&mut self) {}
+        #[inline(always)]
+        pub fn start_timer(&mut self) {}
+        #[inline(always)]
+        pub fn record_finished(&mut self, _code_bytes: usize, _frame_size: u32) {}
+    }
+
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+    pub struct PhaseTimes;
+
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+    pub struct CodeGenStats;
+
+    impl CodeGenStats {
+        #[inline(always)]
+        pub fn timed() -> Self {
+            CodeGenStats
+        }
+        #[inline(always)]
+        pub fn is_timed(&self) -> bool {
+            false
+        }
+        #[inline(always)]
+        pub fn record_proc(&mut self, _stats: &ProcStats) {}
+        #[inline(always)]
+        pub fn record_relocation_time(&mut self, _time: Duration) {}
+        #[inline(always)]
+        pub fn record_object_writing_time(&mut self, _time: Duration) {}
+        #[inline(always)]
+        pub fn record_dropped_procs(&mut self, _count: u32) {}
+        #[inline(always)]
+        pub fn record_inlined_calls(&mut self, _count: u32) {}
+        #[inline(always)]
+        pub fn merge(&mut self, _other: CodeGenStats) {}
+
+        pub fn report(&self) -> String {
+            String::from("dev backend statistics are only collected with the `stats` feature\n")
+        }
+
+        pub fn summary(&self) -> String {
+            self.report()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CodeGenStats, PhaseTimes, ProcStats};
+    use std::time::Duration;
+
+    #[test]
+    fn report_totals() {
+        let mut stats = CodeGenStats::default();
+        let mut proc = ProcStats::default();
+
+        proc.reset("main");
+        proc.record_general_reg(0);
+        proc.record_general_reg(3);
+        proc.record_general_reg(0);
+        proc.record_float_reg(1);
+        proc.record_spill();
+        proc.record_reload();
+        proc.record_move();
+        proc.record_finished(42, 16);
+        stats.record_proc(&proc);
+
+        proc.reset("helper");
+        proc.record_general_reg(0);
+        proc.record_finished(8, 0);
+        stats.record_proc(&proc);
+
+        assert_eq!(stats.procs[0].general_regs_used, 2);
+        assert_eq!(stats.procs[0].float_regs_used, 1);
+        assert_eq!(stats.procs[1].spills, 0);
+        assert_eq!(
+            stats.report(),
+            [
+                "proc        bytes   gen regs float regs     spills    reloads      moves      frame",
+                "main           42          2          1          1          1          1         16",
+                "helper          8          1          0          0          0          0          0",
+                "total          50          3          1          1          1          1         16",
+                "",
+            ]
+            .join("\n")
+        );
+    }
+
+    fn timed_proc(name: &str, millis: u64) -> ProcStats {
+        let mut proc = ProcStats::default();
+        proc.reset(name);
+        proc.record_finished(8, 0);
+        proc.lowering_time = Some(Duration::from_millis(millis));
+        proc
+    }
+
+    #[test]
+    fn timings_are_only_recorded_when_enabled() {
+        let mut stats = CodeGenStats::timed();
+        let mut proc = ProcStats::default();
+        proc.reset("main");
+        proc.start_timer();
+        proc.record_finished(42, 16);
+        stats.record_proc(&proc);
+        stats.record_relocation_time(Duration::from_millis(2));
+        stats.record_object_writing_time(Duration::from_millis(3));
+
+        let lowering_time = stats.procs[0].lowering_time.unwrap();
+        assert_eq!(
+            stats.times,
+            Some(PhaseTimes {
+                lowering: lowering_time,
+                relocations: Duration::from_millis(2),
+                object_writing: Duration::from_millis(3),
+            })
+        );
+
+        let mut stats = CodeGenStats::default();
+        proc.reset("main");
+        proc.record_finished(42, 16);
+        stats.record_proc(&proc);
+        stats.record_relocation_time(Duration::from_millis(2));
+        assert_eq!(stats.procs[0].lowering_time, None);
+        assert_eq!(stats.times, None);
+        assert_eq!(
+            stats.summary(),
+            "the phases of the dev backend were not timed\n"
+        );
+    }
+
+    #[test]
+    fn merged_timings_add_up() {
+        let mut stats = CodeGenStats::timed();
+        stats.record_proc(&timed_proc("main", 5));
+        stats.record_relocation_time(Duration::from_millis(1));
+
+        let mut other = CodeGenStats::timed();
+        for (i, millis) in [3, 12, 1, 7, 2, 9, 4, 11, 6, 8, 10].into_iter().enumerate() {
+            other.record_proc(&timed_proc(&format!("step_{}", i), millis));
+        }
+        other.record_object_writing_time(Duration::from_millis(4));
+
+        stats.merge(other);
+        assert_eq!(stats.procs.len(), 12);
+        assert_eq!(
+            stats.times,
+            Some(PhaseTimes {
+                lowering: Duration::from_millis(78),
+                relocations: Duration::from_millis(1),
+                object_writing: Duration::from_millis(4),
+            })
+        );
+
+        // only the 10 slowest are listed, so step_2 and step_4 are left out
+        assert_eq!(
+            stats.summary(),
+            [
+                "lowering               78ms",
+                "relocations             1ms",
+                "object writing          4ms",
+                "total                  83ms",
+                "slowest procs:",
+                "          12ms step_1",
+                "          11ms step_7",
+                "          10ms step_10",
+                "           9ms step_5",
+                "           8ms step_9",
+                "           7ms step_3",
+                "           6ms step_8",
+                "           5ms main",
+                "           4ms step_6",
+                "           3ms step_0",
+                "",
+            ]
+            .join("\n")
+        );
+    }
+}
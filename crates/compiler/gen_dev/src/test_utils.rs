@@ -0,0 +1,76 @@
+//! Helpers that the unit tests of several modules share
+
+/// A xorshift generator, so the sequences are random but the same on every run
+pub(crate) struct Rng(pub(crate) u64);
+
+impl Rng {
+    pub(crate) fn below(&mut self, bound: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % bound as u64) as usize
+    }
+}
+
+/// A page the tests copy machine code into, and then run. It is unmapped when dropped.
+#[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+pub(crate) struct JitPage {
+    base: *mut u8,
+}
+
+#[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+impl JitPage {
+    pub(crate) const SIZE: usize = 4096;
+
+    /// A writable page
+    pub(crate) fn new() -> Self {
+        let memory = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                Self::SIZE,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        };
+        assert_ne!(memory, libc::MAP_FAILED);
+
+        Self {
+            base: memory as *mut u8,
+        }
+    }
+
+    /// Copies `code` to `offset`, while the page is writable
+    pub(crate) fn write(&mut self, offset: usize, code: &[u8]) {
+        assert!(offset + code.len() <= Self::SIZE);
+        unsafe { std::ptr::copy_nonoverlapping(code.as_ptr(), self.base.add(offset), code.len()) }
+    }
+
+    fn protect(&self, protection: libc::c_int) {
+        let result =
+            unsafe { libc::mprotect(self.base as *mut libc::c_void, Self::SIZE, protection) };
+        assert_eq!(result, 0);
+    }
+
+    pub(crate) fn make_executable(&self) {
+        self.protect(libc::PROT_READ | libc::PROT_EXEC);
+    }
+
+    pub(crate) fn make_writable(&self) {
+        self.protect(libc::PROT_READ | libc::PROT_WRITE | libc::PROT_EXEC);
+    }
+
+    /// The function at `offset`. `F` must be the `extern "C" fn` type of the code there.
+    pub(crate) unsafe fn function<F: Copy>(&self, offset: usize) -> F {
+        assert_eq!(std::mem::size_of::<F>(), std::mem::size_of::<*const u8>());
+        std::mem::transmute_copy(&self.base.add(offset))
+    }
+}
+
+#[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+impl Drop for JitPage {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.base as *mut libc::c_void, Self::SIZE) };
+    }
+}
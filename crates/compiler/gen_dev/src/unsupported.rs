@@ -0,0 +1,87 @@
+//! What the dev backend does not support yet. Lowering gives up on a procedure by returning an
+//! [UnsupportedConstruct], which `build_procedures` reports for that procedure alone. A panic
+//! is a bug of the backend, like any other `internal_error!`.
+use roc_module::low_level::LowLevel;
+use roc_mono::layout::{Builtin, InLayout, Layout, LayoutInterner, STLayoutInterner, UnionLayout};
+
+/// The result of lowering something that the backend might not support yet
+pub(crate) type BuildResult<T = ()> = Result<T, UnsupportedConstruct>;
+
+/// Something a procedure uses that the dev backend cannot build yet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UnsupportedConstruct {
+    /// Values of a layout, as the layout interner prints it. A `Layout` would borrow from the
+    /// arena of the build, which the report outlives.
+    Layout(String),
+    Expr(&'static str),
+    /// An expression with values of a layout, e.g. a list literal of records
+    ExprWith(&'static str, String),
+    LowLevel(LowLevel),
+    /// A low level with values of a layout, e.g. `NumAbs` of `I128`s
+    LowLevelWith(LowLevel, String),
+    CallConvDetail(&'static str),
+}
+
+impl std::fmt::Display for UnsupportedConstruct {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UnsupportedConstruct::Layout(layout) => write!(f, "{} layouts", layout),
+            UnsupportedConstruct::Expr(expr) => write!(f, "{}", expr),
+            UnsupportedConstruct::ExprWith(expr, layout) => {
+                write!(f, "{} with {} values", expr, layout)
+            }
+            UnsupportedConstruct::LowLevel(op) => write!(f, "the low level {:?}", op),
+            UnsupportedConstruct::LowLevelWith(op, layout) => {
+                write!(f, "the low level {:?} with {} values", op, layout)
+            }
+            UnsupportedConstruct::CallConvDetail(detail) => {
+                write!(f, "{} in the calling convention", detail)
+            }
+        }
+    }
+}
+
+impl UnsupportedConstruct {
+    /// Values of `layout`, by the kind of layout it is, e.g. `List` or `recursive tag union`
+    pub(crate) fn layout<'a>(interner: &STLayoutInterner<'a>, layout: InLayout<'a>) -> Self {
+        Self::of_layout(&interner.get(layout))
+    }
+
+    pub(crate) fn of_layout(layout: &Layout<'_>) -> Self {
+        UnsupportedConstruct::Layout(Self::layout_name(layout))
+    }
+
+    pub(crate) fn expr_with(expr: &'static str, layout: &Layout<'_>) -> Self {
+        UnsupportedConstruct::ExprWith(expr, Self::layout_name(layout))
+    }
+
+    pub(crate) fn low_level_with(op: LowLevel, layout: &Layout<'_>) -> Self {
+        UnsupportedConstruct::LowLevelWith(op, Self::layout_name(layout))
+    }
+
+    fn layout_name(layout: &Layout<'_>) -> String {
+        match layout {
+            Layout::Builtin(Builtin::Int(width)) => format!("{:?}", width),
+            Layout::Builtin(Builtin::Float(width)) => format!("{:?}", width),
+            Layout::Builtin(Builtin::Bool) => "Bool".to_string(),
+            Layout::Builtin(Builtin::Decimal) => "Dec".to_string(),
+            Layout::Builtin(Builtin::Str) => "Str".to_string(),
+            Layout::Builtin(Builtin::List(_)) => "List".to_string(),
+            Layout::Struct { .. } => "record".to_string(),
+            Layout::Boxed(_) => "Box".to_string(),
+            Layout::Union(union_layout) => Self::union_name(union_layout).to_string(),
+            Layout::LambdaSet(_) => "closure".to_string(),
+            Layout::RecursivePointer(_) => "recursive pointer".to_string(),
+        }
+    }
+
+    fn union_name(union_layout: &UnionLayout<'_>) -> &'static str {
+        match union_layout {
+            UnionLayout::NonRecursive(_) => "tag union",
+            UnionLayout::Recursive(_) => "recursive tag union",
+            UnionLayout::NonNullableUnwrapped(_) => "single tag recursive union",
+            UnionLayout::NullableWrapped { .. } => "nullable tag union",
+            UnionLayout::NullableUnwrapped { .. } => "nullable single tag union",
+        }
+    }
+}
@@ -8,6 +8,101 @@
 // Not a useful lint for us
 #![allow(clippy::too_many_arguments)]
 
+use roc_mono::ir::OptLevel;
+use target_lexicon::Triple;
+
 pub mod llvm;
 
 pub mod run_roc;
+
+mod stats;
+pub use stats::{count_ir_instructions, CompilationStats};
+
+/// The flags that decide how the LLVM backend generates code. `Env` carries them around,
+/// so that a new flag does not have to be passed to every function that generates code.
+#[derive(Debug, Clone)]
+pub struct CodegenOptions {
+    pub opt_level: OptLevel,
+    pub target_triple: Triple,
+    /// Annotate the LLVM IR with debug info, so errors point at its lines.
+    pub emit_debug_info: bool,
+    /// Instrument the module with these, in this order.
+    pub sanitizers: Vec<Sanitizer>,
+    /// Sum lists of `F64` with compensated summation, which is more precise but slower.
+    pub precise_float_reduction: bool,
+    /// Crash when a float operation is invalid, divides by zero, or overflows,
+    /// instead of giving NaN or an infinity.
+    pub strict_fp_exceptions: bool,
+    /// Zero the memory of every struct and union before its fields are stored, so that its padding
+    /// is deterministic. Without it, only the layouts that are byte comparable are zeroed.
+    /// On by default, unless optimizing.
+    pub zero_padding: bool,
+}
+
+impl CodegenOptions {
+    /// Generate code for `target_triple` at `opt_level`, without any of the extras.
+    pub fn new(opt_level: OptLevel, target_triple: Triple) -> Self {
+        CodegenOptions {
+            opt_level,
+            target_triple,
+            emit_debug_info: false,
+            sanitizers: Vec::new(),
+            precise_float_reduction: false,
+            strict_fp_exceptions: false,
+            zero_padding: !matches!(opt_level, OptLevel::Size | OptLevel::Optimize),
+        }
+    }
+}
+
+/// A sanitizer, or the coverage instrumentation a fuzzer needs, which `opt` adds to the module
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sanitizer {
+    Address,
+    Memory,
+    Thread,
+    /// Coverage for `cargo fuzz`
+    CargoFuzz,
+    /// Coverage for `cargo afl` and afl++
+    AflRs,
+}
+
+impl Sanitizer {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "address" => Some(Sanitizer::Address),
+            "memory" => Some(Sanitizer::Memory),
+            "thread" => Some(Sanitizer::Thread),
+            "cargo-fuzz" => Some(Sanitizer::CargoFuzz),
+            "afl.rs" => Some(Sanitizer::AflRs),
+            _ => None,
+        }
+    }
+
+    /// The module pass that `opt` runs for this sanitizer
+    pub fn opt_pass(self) -> &'static str {
+        match self {
+            Sanitizer::Address => "asan-module",
+            Sanitizer::Memory => "msan-module",
+            Sanitizer::Thread => "tsan-module",
+            Sanitizer::CargoFuzz | Sanitizer::AflRs => "sancov-module",
+        }
+    }
+
+    /// The arguments that `opt` needs on top of the pass
+    pub fn opt_args(self) -> &'static [&'static str] {
+        match self {
+            Sanitizer::Address | Sanitizer::Memory | Sanitizer::Thread => &[],
+            Sanitizer::CargoFuzz => &[
+                "-sanitizer-coverage-level=3",
+                "-sanitizer-coverage-prune-blocks=0",
+                "-sanitizer-coverage-inline-8bit-counters",
+                "-sanitizer-coverage-pc-table",
+            ],
+            Sanitizer::AflRs => &[
+                "-sanitizer-coverage-level=3",
+                "-sanitizer-coverage-prune-blocks=0",
+                "-sanitizer-coverage-trace-pc-guard",
+            ],
+        }
+    }
+}
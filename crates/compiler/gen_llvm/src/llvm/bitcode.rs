@@ -1,27 +1,33 @@
 /// Helpers for interacting with the zig that generates bitcode
 use crate::debug_info_init;
 use crate::llvm::build::{
-    complex_bitcast_check_size, load_roc_value, struct_from_fields, to_cc_return, CCReturn, Env,
-    C_CALL_CONV, FAST_CALL_CONV,
+    cast_if_necessary_for_opaque_recursive_pointers, complex_bitcast_check_size, load_roc_value,
+    struct_from_fields, to_cc_return, CCReturn, Env, C_CALL_CONV, FAST_CALL_CONV,
 };
-use crate::llvm::convert::basic_type_from_layout;
+use crate::llvm::convert::{basic_type_from_layout, int_type_from_int_width};
 use crate::llvm::refcounting::{
     decrement_refcount_layout, increment_n_refcount_layout, increment_refcount_layout,
 };
+use crate::llvm::verify::record_fn_origin;
 use inkwell::attributes::{Attribute, AttributeLoc};
 use inkwell::types::{BasicType, BasicTypeEnum, StructType};
 use inkwell::values::{
-    BasicValueEnum, CallSiteValue, FunctionValue, InstructionValue, IntValue, PointerValue,
-    StructValue,
+    BasicValue, BasicValueEnum, CallSiteValue, FunctionValue, InstructionValue, IntValue,
+    PointerValue, StructValue,
+};
+use inkwell::{AddressSpace, IntPredicate};
+use roc_builtins::repr::{
+    CompareResult, BOOL_TRUE, BOOL_WIDTH, COMPARE_RESULT_WIDTH, ORDERING_TAGS,
 };
-use inkwell::AddressSpace;
 use roc_error_macros::internal_error;
+use roc_module::ident::TagName;
 use roc_module::symbol::Symbol;
 use roc_mono::layout::{
-    Builtin, InLayout, LambdaSet, Layout, LayoutIds, LayoutInterner, STLayoutInterner,
+    Builtin, InLayout, LambdaSet, LambdaSetResolver, Layout, LayoutIds, LayoutInterner,
+    STLayoutInterner, SimplifiedLambdaSet,
 };
 
-use super::build::{create_entry_block_alloca, BuilderExt};
+use super::build::{create_entry_block_alloca, entry_block_alloca_zerofill, BuilderExt};
 use super::convert::zig_list_type;
 
 pub fn call_bitcode_fn<'a, 'ctx, 'env>(
@@ -243,36 +249,31 @@ fn build_transform_caller_help<'a, 'ctx, 'env>(
         bumpalo::collections::Vec::with_capacity_in(arguments.len(), env.arena);
 
     for (argument_ptr, layout) in arguments.iter().zip(argument_layouts) {
-        let basic_type =
-            basic_type_from_layout(env, layout_interner, *layout).ptr_type(AddressSpace::default());
-
-        let cast_ptr = env.builder.build_pointer_cast(
-            argument_ptr.into_pointer_value(),
-            basic_type,
-            "cast_ptr_to_tag_build_transform_caller_help",
-        );
+        // the parameter stays, so the signature is the same for every layout,
+        // but there is nothing behind it to load
+        if is_zero_sized(layout_interner, *layout) {
+            let basic_type = basic_type_from_layout(env, layout_interner, *layout);
+            arguments_cast.push(basic_type.const_zero());
+            continue;
+        }
 
-        let argument = load_roc_value(
+        // the arguments are list elements, which can be as small as a byte
+        let argument = load_opaque(
             env,
             layout_interner,
             *layout,
-            cast_ptr,
-            "zig_helper_load_opaque",
+            WrapperPointers::Interior,
+            argument_ptr.into_pointer_value(),
         );
 
         arguments_cast.push(argument);
     }
 
-    match (
-        closure_data_layout
-            .is_represented(layout_interner)
-            .is_some(),
-        closure_data_layout.runtime_representation(),
-    ) {
-        (false, _) => {
+    match LambdaSetResolver::simplify(layout_interner, &closure_data_layout) {
+        SimplifiedLambdaSet::FunctionPointer(_) | SimplifiedLambdaSet::NoClosureData => {
             // the function doesn't expect a closure argument, nothing to add
         }
-        (true, layout) => {
+        SimplifiedLambdaSet::Closure(layout) => {
             let closure_type = basic_type_from_layout(env, layout_interner, layout)
                 .ptr_type(AddressSpace::default());
 
@@ -300,13 +301,16 @@ fn build_transform_caller_help<'a, 'ctx, 'env>(
         .unwrap()
         .into_pointer_value();
 
-    crate::llvm::build::store_roc_value_opaque(
-        env,
-        layout_interner,
-        result_layout,
-        result_u8_ptr,
-        result,
-    );
+    if !is_zero_sized(layout_interner, result_layout) {
+        store_opaque(
+            env,
+            layout_interner,
+            result_layout,
+            WrapperPointers::Interior,
+            result_u8_ptr,
+            result,
+        );
+    }
     env.builder.build_return(None);
 
     env.builder.position_at_end(block);
@@ -315,6 +319,12 @@ fn build_transform_caller_help<'a, 'ctx, 'env>(
     function_value
 }
 
+/// Whether a value of `layout` takes up no bytes, e.g. an empty record or a tag union with one
+/// tag and no payload. There is nothing to load, store, or compare for such a value.
+fn is_zero_sized<'a>(layout_interner: &STLayoutInterner<'a>, layout: InLayout<'a>) -> bool {
+    layout_interner.stack_size(layout) == 0
+}
+
 enum Mode {
     Inc,
     IncN,
@@ -350,6 +360,21 @@ pub fn build_dec_wrapper<'a, 'ctx, 'env>(
     build_rc_wrapper(env, layout_interner, layout_ids, layout, Mode::Dec)
 }
 
+/// The wrapper called `fn_name`, if it is built already or is being built right now. In the latter
+/// case a call to it is a forward reference, which is how a recursive layout calls itself.
+fn existing_wrapper<'ctx>(env: &Env<'_, 'ctx, '_>, fn_name: &str) -> Option<FunctionValue<'ctx>> {
+    let function_value = env.module.get_function(fn_name)?;
+
+    debug_assert!(
+        function_value.count_basic_blocks() > 0
+            || env.wrappers_in_progress.borrow().contains(fn_name),
+        "the wrapper {} was declared, but never built",
+        fn_name
+    );
+
+    Some(function_value)
+}
+
 fn build_rc_wrapper<'a, 'ctx, 'env>(
     env: &Env<'a, 'ctx, 'env>,
     layout_interner: &mut STLayoutInterner<'a>,
@@ -371,7 +396,7 @@ fn build_rc_wrapper<'a, 'ctx, 'env>(
         Mode::Dec => format!("{}_dec", fn_name),
     };
 
-    let function_value = match env.module.get_function(fn_name.as_str()) {
+    let function_value = match existing_wrapper(env, &fn_name) {
         Some(function_value) => function_value,
         None => {
             let arg_type = env.context.i8_type().ptr_type(AddressSpace::default());
@@ -390,6 +415,14 @@ fn build_rc_wrapper<'a, 'ctx, 'env>(
                     &[arg_type.into(), env.ptr_int().into()],
                 ),
             };
+            env.wrappers_in_progress
+                .borrow_mut()
+                .insert(fn_name.clone());
+            record_fn_origin(
+                env,
+                &fn_name,
+                format!("the refcount wrapper for {}", layout_interner.dbg(layout)),
+            );
 
             // called from zig, must use C calling convention
             function_value.set_call_conventions(C_CALL_CONV);
@@ -404,44 +437,66 @@ fn build_rc_wrapper<'a, 'ctx, 'env>(
 
             debug_info_init!(env, function_value);
 
-            let mut it = function_value.get_param_iter();
-            let generic_value_ptr = it.next().unwrap().into_pointer_value();
-
-            generic_value_ptr.set_name(Symbol::ARG_1.as_str(&env.interns));
-
-            let value_type = basic_type_from_layout(env, layout_interner, layout);
-            let value_ptr_type = value_type.ptr_type(AddressSpace::default());
-            let value_ptr =
-                env.builder
-                    .build_pointer_cast(generic_value_ptr, value_ptr_type, "load_opaque");
-
-            // even though this looks like a `load_roc_value`, that gives segfaults in practice.
-            // I suspect it has something to do with the lifetime of the alloca that is created by
-            // `load_roc_value`
-            let value = if layout_interner.is_passed_by_reference(layout) {
-                value_ptr.into()
-            } else {
-                env.builder
-                    .new_build_load(value_type, value_ptr, "load_opaque")
-            };
-
-            match rc_operation {
-                Mode::Inc => {
-                    let n = 1;
-                    increment_refcount_layout(env, layout_interner, layout_ids, n, value, layout);
-                }
-                Mode::IncN => {
-                    let n = it.next().unwrap().into_int_value();
-                    n.set_name(Symbol::ARG_2.as_str(&env.interns));
-
-                    increment_n_refcount_layout(env, layout_interner, layout_ids, n, value, layout);
-                }
-                Mode::Dec => {
-                    decrement_refcount_layout(env, layout_interner, layout_ids, value, layout);
+            // without any bytes, there is no refcount either
+            let has_refcount = !is_zero_sized(layout_interner, layout)
+                || layout_interner.contains_refcounted(layout);
+            if has_refcount {
+                let mut it = function_value.get_param_iter();
+                let generic_value_ptr = it.next().unwrap().into_pointer_value();
+
+                generic_value_ptr.set_name(Symbol::ARG_1.as_str(&env.interns));
+
+                let value_type = basic_type_from_layout(env, layout_interner, layout);
+                let value_ptr_type = value_type.ptr_type(AddressSpace::default());
+                let value_ptr = env.builder.build_pointer_cast(
+                    generic_value_ptr,
+                    value_ptr_type,
+                    "load_opaque",
+                );
+
+                // even though this looks like a `load_roc_value`, that gives segfaults in practice.
+                // I suspect it has something to do with the lifetime of the alloca that is created
+                // by `load_roc_value`
+                let value = if layout_interner.is_passed_by_reference(layout) {
+                    value_ptr.into()
+                } else {
+                    env.builder
+                        .new_build_load(value_type, value_ptr, "load_opaque")
+                };
+
+                match rc_operation {
+                    Mode::Inc => {
+                        let n = 1;
+                        increment_refcount_layout(
+                            env,
+                            layout_interner,
+                            layout_ids,
+                            n,
+                            value,
+                            layout,
+                        );
+                    }
+                    Mode::IncN => {
+                        let n = it.next().unwrap().into_int_value();
+                        n.set_name(Symbol::ARG_2.as_str(&env.interns));
+
+                        increment_n_refcount_layout(
+                            env,
+                            layout_interner,
+                            layout_ids,
+                            n,
+                            value,
+                            layout,
+                        );
+                    }
+                    Mode::Dec => {
+                        decrement_refcount_layout(env, layout_interner, layout_ids, value, layout);
+                    }
                 }
             }
 
             env.builder.build_return(None);
+            env.wrappers_in_progress.borrow_mut().remove(&fn_name);
 
             function_value
         }
@@ -453,31 +508,189 @@ fn build_rc_wrapper<'a, 'ctx, 'env>(
     function_value
 }
 
+/// What the opaque pointers that zig passes to an eq or compare wrapper point to. Each kind gets
+/// a wrapper of its own, with the kind encoded in its name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WrapperPointers {
+    /// The start of an allocation of its own, like the contents of a `Box`. The pointers are
+    /// aligned like an allocation, and the refcount is right before them.
+    Standalone,
+    /// An element in the middle of a list's buffer, when zig's list code calls the wrapper
+    /// element by element. The pointers are only aligned like the element layout, and what is
+    /// right before them is the previous element, so there is no refcount to reach.
+    Interior,
+}
+
+impl WrapperPointers {
+    fn name_suffix(self) -> &'static str {
+        match self {
+            WrapperPointers::Standalone => "",
+            WrapperPointers::Interior => "_interior",
+        }
+    }
+
+    /// The alignment that loads of a `layout` value behind the pointers can count on
+    pub fn alignment_bytes<'a>(
+        self,
+        layout_interner: &STLayoutInterner<'a>,
+        layout: InLayout<'a>,
+    ) -> u32 {
+        match self {
+            WrapperPointers::Standalone => layout_interner.allocation_alignment_bytes(layout),
+            WrapperPointers::Interior => layout_interner.alignment_bytes(layout),
+        }
+    }
+}
+
+/// Loads the `layout` value behind an opaque pointer that zig passed to a wrapper, like
+/// `load_roc_value` but only counting on the alignment that `pointers` allows.
+fn load_opaque<'a, 'ctx, 'env>(
+    env: &Env<'a, 'ctx, 'env>,
+    layout_interner: &mut STLayoutInterner<'a>,
+    layout: InLayout<'a>,
+    pointers: WrapperPointers,
+    opaque_ptr: PointerValue<'ctx>,
+) -> BasicValueEnum<'ctx> {
+    let basic_type = basic_type_from_layout(env, layout_interner, layout);
+    let source = env.builder.build_pointer_cast(
+        opaque_ptr,
+        basic_type.ptr_type(AddressSpace::default()),
+        "load_opaque",
+    );
+    let source_alignment = pointers.alignment_bytes(layout_interner, layout);
+
+    if layout_interner.is_passed_by_reference(layout) {
+        let alloca = entry_block_alloca_zerofill(env, basic_type, "load_opaque");
+
+        if source_alignment > 0 {
+            let size = env
+                .ptr_int()
+                .const_int(layout_interner.stack_size(layout) as u64, false);
+
+            env.builder
+                .build_memcpy(
+                    alloca,
+                    layout_interner.alignment_bytes(layout),
+                    source,
+                    source_alignment,
+                    size,
+                )
+                .unwrap();
+        }
+
+        alloca.into()
+    } else {
+        let value = env
+            .builder
+            .new_build_load(basic_type, source, "load_opaque");
+        set_load_alignment(value, source_alignment);
+
+        value
+    }
+}
+
+/// Stores a `layout` value behind an opaque pointer that zig passed to a wrapper, writing the
+/// bytes of the value and nothing around them, like `load_opaque` reads them.
+fn store_opaque<'a, 'ctx, 'env>(
+    env: &Env<'a, 'ctx, 'env>,
+    layout_interner: &mut STLayoutInterner<'a>,
+    layout: InLayout<'a>,
+    pointers: WrapperPointers,
+    opaque_ptr: PointerValue<'ctx>,
+    value: BasicValueEnum<'ctx>,
+) {
+    let basic_type = basic_type_from_layout(env, layout_interner, layout);
+    let destination = env.builder.build_pointer_cast(
+        opaque_ptr,
+        basic_type.ptr_type(AddressSpace::default()),
+        "store_opaque",
+    );
+    let destination_alignment = pointers.alignment_bytes(layout_interner, layout);
+
+    if layout_interner.is_passed_by_reference(layout) {
+        debug_assert!(value.is_pointer_value());
+
+        if destination_alignment > 0 {
+            let size = env
+                .ptr_int()
+                .const_int(layout_interner.stack_size(layout) as u64, false);
+
+            env.builder
+                .build_memcpy(
+                    destination,
+                    destination_alignment,
+                    value.into_pointer_value(),
+                    layout_interner.alignment_bytes(layout),
+                    size,
+                )
+                .unwrap();
+        }
+    } else {
+        let value = cast_if_necessary_for_opaque_recursive_pointers(env.builder, value, basic_type);
+        let store = env.builder.build_store(destination, value);
+
+        if destination_alignment > 0 {
+            store
+                .set_alignment(destination_alignment)
+                .expect("a store to an opaque pointer");
+        }
+    }
+}
+
+fn set_load_alignment(load: BasicValueEnum<'_>, alignment: u32) {
+    // zero-sized values have no alignment, and LLVM picks one for the load
+    if alignment > 0 {
+        load.as_instruction_value()
+            .and_then(|instruction| instruction.set_alignment(alignment).ok())
+            .expect("a load of an opaque pointer");
+    }
+}
+
+/// A function that zig calls to compare two values of `layout` for equality. Like every `Bool`
+/// that zig gets to see, the result is a whole byte of 0 or 1, see [roc_builtins::repr].
 pub fn build_eq_wrapper<'a, 'ctx, 'env>(
     env: &Env<'a, 'ctx, 'env>,
     layout_interner: &mut STLayoutInterner<'a>,
     layout_ids: &mut LayoutIds<'a>,
     layout: InLayout<'a>,
+    pointers: WrapperPointers,
 ) -> FunctionValue<'ctx> {
     let block = env.builder.get_insert_block().expect("to be in a function");
     let di_location = env.builder.get_current_debug_location().unwrap();
 
     let symbol = Symbol::GENERIC_EQ_REF;
-    let fn_name = layout_ids
-        .get(symbol, &layout)
-        .to_symbol_string(symbol, &env.interns);
+    let fn_name = format!(
+        "{}{}",
+        layout_ids
+            .get(symbol, &layout)
+            .to_symbol_string(symbol, &env.interns),
+        pointers.name_suffix()
+    );
 
-    let function_value = match env.module.get_function(fn_name.as_str()) {
+    let function_value = match existing_wrapper(env, &fn_name) {
         Some(function_value) => function_value,
         None => {
             let arg_type = env.context.i8_type().ptr_type(AddressSpace::default());
 
+            let bool_type = int_type_from_int_width(env, BOOL_WIDTH);
             let function_value = crate::llvm::refcounting::build_header_help(
                 env,
                 &fn_name,
-                env.context.bool_type().into(),
+                bool_type.into(),
                 &[arg_type.into(), arg_type.into()],
             );
+            env.wrappers_in_progress
+                .borrow_mut()
+                .insert(fn_name.clone());
+            record_fn_origin(
+                env,
+                &fn_name,
+                format!(
+                    "the equality wrapper for {} behind {:?} pointers",
+                    layout_interner.dbg(layout),
+                    pointers
+                ),
+            );
 
             // called from zig, must use C calling convention
             function_value.set_call_conventions(C_CALL_CONV);
@@ -492,39 +705,40 @@ pub fn build_eq_wrapper<'a, 'ctx, 'env>(
 
             debug_info_init!(env, function_value);
 
-            let mut it = function_value.get_param_iter();
-            let value_ptr1 = it.next().unwrap().into_pointer_value();
-            let value_ptr2 = it.next().unwrap().into_pointer_value();
-
-            value_ptr1.set_name(Symbol::ARG_1.as_str(&env.interns));
-            value_ptr2.set_name(Symbol::ARG_2.as_str(&env.interns));
-
-            let value_type = basic_type_from_layout(env, layout_interner, layout)
-                .ptr_type(AddressSpace::default());
-
-            let value_cast1 = env
-                .builder
-                .build_pointer_cast(value_ptr1, value_type, "load_opaque");
+            // all values without any bytes are the same
+            let result = if is_zero_sized(layout_interner, layout) {
+                bool_type.const_int(BOOL_TRUE as u64, false)
+            } else {
+                let mut it = function_value.get_param_iter();
+                let value_ptr1 = it.next().unwrap().into_pointer_value();
+                let value_ptr2 = it.next().unwrap().into_pointer_value();
 
-            let value_cast2 = env
-                .builder
-                .build_pointer_cast(value_ptr2, value_type, "load_opaque");
+                value_ptr1.set_name(Symbol::ARG_1.as_str(&env.interns));
+                value_ptr2.set_name(Symbol::ARG_2.as_str(&env.interns));
 
-            // load_roc_value(env, *element_layout, elem_ptr, "get_elem")
-            let value1 = load_roc_value(env, layout_interner, layout, value_cast1, "load_opaque");
-            let value2 = load_roc_value(env, layout_interner, layout, value_cast2, "load_opaque");
+                let value1 = load_opaque(env, layout_interner, layout, pointers, value_ptr1);
+                let value2 = load_opaque(env, layout_interner, layout, pointers, value_ptr2);
 
-            let result = crate::llvm::compare::generic_eq(
-                env,
-                layout_interner,
-                layout_ids,
-                value1,
-                value2,
-                layout,
-                layout,
-            );
+                let is_eq = crate::llvm::compare::generic_eq(
+                    env,
+                    layout_interner,
+                    layout_ids,
+                    value1,
+                    value2,
+                    layout,
+                    layout,
+                );
+
+                // the upper bits of the byte are zero, not whatever an `i1` leaves there
+                env.builder.build_int_z_extend(
+                    is_eq.into_int_value(),
+                    bool_type,
+                    "zero_extend_bool",
+                )
+            };
 
             env.builder.build_return(Some(&result));
+            env.wrappers_in_progress.borrow_mut().remove(&fn_name);
 
             function_value
         }
@@ -536,19 +750,51 @@ pub fn build_eq_wrapper<'a, 'ctx, 'env>(
     function_value
 }
 
+/// The tag ids of the `[LT, EQ, GT]` that a Roc comparator returns
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OrderingTagIds {
+    pub eq: u8,
+    pub gt: u8,
+    pub lt: u8,
+}
+
+impl OrderingTagIds {
+    /// The tag ids that mono gives an `Ordering`, whose tags it sorts by name
+    pub fn of_roc_ordering() -> Self {
+        let mut tags = ORDERING_TAGS.map(TagName::from);
+        tags.sort();
+        let tag_id = |result: CompareResult| {
+            let name = TagName::from(ORDERING_TAGS[result as usize]);
+            tags.iter().position(|tag| *tag == name).unwrap() as u8
+        };
+
+        OrderingTagIds {
+            eq: tag_id(CompareResult::Eq),
+            gt: tag_id(CompareResult::Gt),
+            lt: tag_id(CompareResult::Lt),
+        }
+    }
+}
+
+/// A function that zig calls to sort values of `layout` with `roc_function`. It translates the
+/// tag id that `roc_function` returns, one of `ordering`, to a [CompareResult], which is what
+/// zig expects and not a `Bool`.
 pub fn build_compare_wrapper<'a, 'ctx, 'env>(
     env: &Env<'a, 'ctx, 'env>,
     layout_interner: &mut STLayoutInterner<'a>,
     roc_function: FunctionValue<'ctx>,
     closure_data_layout: LambdaSet<'a>,
     layout: InLayout<'a>,
+    pointers: WrapperPointers,
+    ordering: OrderingTagIds,
 ) -> FunctionValue<'ctx> {
     let block = env.builder.get_insert_block().expect("to be in a function");
     let di_location = env.builder.get_current_debug_location().unwrap();
 
     let fn_name: &str = &format!(
-        "{}_compare_wrapper",
-        roc_function.get_name().to_string_lossy()
+        "{}_compare_wrapper{}",
+        roc_function.get_name().to_string_lossy(),
+        pointers.name_suffix()
     );
 
     let function_value = match env.module.get_function(fn_name) {
@@ -559,9 +805,19 @@ pub fn build_compare_wrapper<'a, 'ctx, 'env>(
             let function_value = crate::llvm::refcounting::build_header_help(
                 env,
                 fn_name,
-                env.context.i8_type().into(),
+                int_type_from_int_width(env, COMPARE_RESULT_WIDTH).into(),
                 &[arg_type.into(), arg_type.into(), arg_type.into()],
             );
+            record_fn_origin(
+                env,
+                fn_name,
+                format!(
+                    "the compare wrapper around `{}` for {} behind {:?} pointers",
+                    roc_function.get_name().to_string_lossy(),
+                    layout_interner.dbg(layout),
+                    pointers
+                ),
+            );
 
             // called from zig, must use C calling convention
             function_value.set_call_conventions(C_CALL_CONV);
@@ -606,6 +862,10 @@ pub fn build_compare_wrapper<'a, 'ctx, 'env>(
                 .builder
                 .new_build_load(value_type, value_cast2, "load_opaque");
 
+            let alignment = pointers.alignment_bytes(layout_interner, layout);
+            set_load_alignment(value1, alignment);
+            set_load_alignment(value2, alignment);
+
             let default = [value1.into(), value2.into()];
 
             let closure_data_repr = closure_data_layout.runtime_representation();
@@ -644,11 +904,38 @@ pub fn build_compare_wrapper<'a, 'ctx, 'env>(
                 "call_user_defined_compare_function",
             );
 
-            let result = call.try_as_basic_value().left().unwrap();
+            let tag_id = call.try_as_basic_value().left().unwrap().into_int_value();
 
             // IMPORTANT! we call a user function, so it has the fast calling convention
             call.set_call_convention(FAST_CALL_CONV);
 
+            let tag_id_type = tag_id.get_type();
+            let is_tag = |id: u8, name| {
+                env.builder.build_int_compare(
+                    IntPredicate::EQ,
+                    tag_id,
+                    tag_id_type.const_int(id as u64, false),
+                    name,
+                )
+            };
+            let result_type = int_type_from_int_width(env, COMPARE_RESULT_WIDTH);
+            let compare_result =
+                |result: CompareResult| result_type.const_int(result as u64, false);
+
+            // with the tag ids of today, this is the identity, and LLVM removes it
+            let gt_or_lt = env.builder.build_select(
+                is_tag(ordering.gt, "is_gt"),
+                compare_result(CompareResult::Gt),
+                compare_result(CompareResult::Lt),
+                "gt_or_lt",
+            );
+            let result = env.builder.build_select(
+                is_tag(ordering.eq, "is_eq"),
+                compare_result(CompareResult::Eq),
+                gt_or_lt.into_int_value(),
+                "compare_result",
+            );
+
             env.builder.build_return(Some(&result));
 
             function_value
@@ -988,3 +1275,580 @@ pub(crate) fn call_list_bitcode_fn<'a, 'ctx, 'env>(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bumpalo::Bump;
+    use inkwell::context::Context;
+    use inkwell::module::Module;
+    use inkwell::values::InstructionOpcode;
+    use roc_mono::layout::{NeedsRecursionPointerFixup, UnionLayout};
+    use roc_target::TargetInfo;
+
+    fn opcodes(function: FunctionValue<'_>) -> std::vec::Vec<InstructionOpcode> {
+        let mut opcodes = std::vec::Vec::new();
+        for block in function.get_basic_blocks() {
+            let mut instruction = block.get_first_instruction();
+            while let Some(current) = instruction {
+                opcodes.push(current.get_opcode());
+                instruction = current.get_next_instruction();
+            }
+        }
+
+        opcodes
+    }
+
+    #[test]
+    fn wrappers_of_zero_sized_layouts_are_trivial() {
+        let arena = Bump::new();
+        let context = Context::create();
+        let module: &Module = arena.alloc(context.create_module("test"));
+        let builder = context.create_builder();
+        let debug_info = Env::new_debug_info(module, &target_lexicon::Triple::host());
+        let env = Env::for_tests(&arena, &context, module, &builder, &debug_info);
+        let mut layout_interner = STLayoutInterner::with_capacity(4, TargetInfo::default_x86_64());
+        let mut layout_ids = LayoutIds::default();
+
+        // the wrappers are built from inside of a function
+        env.add_test_host_function();
+
+        // an empty record, records of those, and a function that captures nothing
+        let pair = layout_interner.insert(Layout::struct_no_name_order(
+            arena.alloc([Layout::UNIT, Layout::UNIT]),
+        ));
+        let nested = layout_interner.insert(Layout::struct_no_name_order(
+            arena.alloc([Layout::UNIT, pair]),
+        ));
+        let args: &[InLayout] = arena.alloc([Layout::UNIT]);
+        let set: &[(Symbol, &[InLayout])] = arena.alloc([(Symbol::ATTR_ATTR, &[] as &[_])]);
+        let lambda_set = layout_interner.insert_lambda_set(
+            &arena,
+            arena.alloc(args),
+            Layout::UNIT,
+            arena.alloc(set),
+            NeedsRecursionPointerFixup(false),
+            Layout::UNIT,
+        );
+        let zero_sized = [Layout::UNIT, pair, nested, lambda_set.full_layout];
+
+        for layout in zero_sized {
+            assert_eq!(layout_interner.stack_size(layout), 0);
+
+            for rc_wrapper in [
+                build_inc_wrapper(&env, &mut layout_interner, &mut layout_ids, layout),
+                build_inc_n_wrapper(&env, &mut layout_interner, &mut layout_ids, layout),
+                build_dec_wrapper(&env, &mut layout_interner, &mut layout_ids, layout),
+            ] {
+                assert_eq!(opcodes(rc_wrapper), [InstructionOpcode::Return]);
+            }
+
+            let eq_wrapper = build_eq_wrapper(
+                &env,
+                &mut layout_interner,
+                &mut layout_ids,
+                layout,
+                WrapperPointers::Standalone,
+            );
+            assert_eq!(opcodes(eq_wrapper), [InstructionOpcode::Return]);
+            let returned = eq_wrapper
+                .get_first_basic_block()
+                .unwrap()
+                .get_first_instruction()
+                .unwrap()
+                .get_operand(0)
+                .unwrap()
+                .left()
+                .unwrap();
+            assert_eq!(
+                returned.into_int_value().get_zero_extended_constant(),
+                Some(1)
+            );
+
+            // a Roc function of two zero-sized values, which returns one
+            let value_type = basic_type_from_layout(&env, &mut layout_interner, layout);
+            let roc_fn = module.add_function(
+                "roc_function",
+                value_type.fn_type(&[value_type.into(), value_type.into()], false),
+                None,
+            );
+            roc_fn.set_call_conventions(FAST_CALL_CONV);
+            let roc_fn_block = builder.get_insert_block().unwrap();
+            builder.position_at_end(context.append_basic_block(roc_fn, "entry"));
+            builder.build_return(Some(&value_type.const_zero()));
+            builder.position_at_end(roc_fn_block);
+
+            let caller = build_transform_caller(
+                &env,
+                &mut layout_interner,
+                roc_fn,
+                lambda_set,
+                &[layout, layout],
+                layout,
+            );
+            // the parameters are still there: the closure, the arguments, and the result
+            assert_eq!(caller.count_params(), 4);
+            assert_eq!(
+                opcodes(caller),
+                [InstructionOpcode::Call, InstructionOpcode::Return]
+            );
+        }
+    }
+
+    #[test]
+    fn wrappers_hand_zig_whole_bytes() {
+        let arena = Bump::new();
+        let context = Context::create();
+        let module: &Module = arena.alloc(context.create_module("test"));
+        let builder = context.create_builder();
+        let debug_info = Env::new_debug_info(module, &target_lexicon::Triple::host());
+        let env = Env::for_tests(&arena, &context, module, &builder, &debug_info);
+        let mut layout_interner = STLayoutInterner::with_capacity(4, TargetInfo::default_x86_64());
+        let mut layout_ids = LayoutIds::default();
+
+        env.add_test_host_function();
+
+        let byte: BasicTypeEnum = context.i8_type().into();
+        for layout in [Layout::I64, Layout::BOOL, Layout::U8] {
+            let eq_wrapper = build_eq_wrapper(
+                &env,
+                &mut layout_interner,
+                &mut layout_ids,
+                layout,
+                WrapperPointers::Standalone,
+            );
+            assert_eq!(eq_wrapper.get_type().get_return_type(), Some(byte));
+
+            // the `i1` of the comparison is zero extended, rather than stored in a byte as is
+            let opcodes = opcodes(eq_wrapper);
+            assert_eq!(opcodes.last(), Some(&InstructionOpcode::Return));
+            assert_eq!(
+                opcodes[opcodes.len() - 2],
+                InstructionOpcode::ZExt,
+                "{:?}",
+                opcodes
+            );
+        }
+
+        // stands in for `\a, b -> Num.compare a b`, which returns the tag id of an `Ordering`
+        let args: &[InLayout] = arena.alloc([Layout::I64, Layout::I64]);
+        let set: &[(Symbol, &[InLayout])] = arena.alloc([(Symbol::ATTR_ATTR, &[] as &[_])]);
+        let lambda_set = layout_interner.insert_lambda_set(
+            &arena,
+            arena.alloc(args),
+            Layout::U8,
+            arena.alloc(set),
+            NeedsRecursionPointerFixup(false),
+            Layout::UNIT,
+        );
+        let i64_type = context.i64_type();
+        let roc_fn = module.add_function(
+            "roc_compare",
+            context
+                .i8_type()
+                .fn_type(&[i64_type.into(), i64_type.into()], false),
+            None,
+        );
+        roc_fn.set_call_conventions(FAST_CALL_CONV);
+        let host_block = builder.get_insert_block().unwrap();
+        builder.position_at_end(context.append_basic_block(roc_fn, "entry"));
+        builder.build_return(Some(
+            &context
+                .i8_type()
+                .const_int(roc_builtins::repr::CompareResult::Lt as u64, false),
+        ));
+        builder.position_at_end(host_block);
+
+        let compare_wrapper = build_compare_wrapper(
+            &env,
+            &mut layout_interner,
+            roc_fn,
+            lambda_set,
+            Layout::I64,
+            WrapperPointers::Standalone,
+            OrderingTagIds::of_roc_ordering(),
+        );
+        assert_eq!(compare_wrapper.get_type().get_return_type(), Some(byte));
+    }
+
+    #[test]
+    fn compare_wrappers_translate_tag_ids_to_compare_results() {
+        use inkwell::passes::PassManager;
+
+        // nothing to translate today
+        assert_eq!(
+            OrderingTagIds::of_roc_ordering(),
+            OrderingTagIds {
+                eq: CompareResult::Eq as u8,
+                gt: CompareResult::Gt as u8,
+                lt: CompareResult::Lt as u8,
+            }
+        );
+
+        let arena = Bump::new();
+        let context = Context::create();
+        let module: &Module = arena.alloc(context.create_module("test"));
+        let builder = context.create_builder();
+        let debug_info = Env::new_debug_info(module, &target_lexicon::Triple::host());
+        let env = Env::for_tests(&arena, &context, module, &builder, &debug_info);
+        let mut layout_interner = STLayoutInterner::with_capacity(4, TargetInfo::default_x86_64());
+
+        env.add_test_host_function();
+
+        let args: &[InLayout] = arena.alloc([Layout::I64, Layout::I64]);
+        let set: &[(Symbol, &[InLayout])] = arena.alloc([(Symbol::ATTR_ATTR, &[] as &[_])]);
+        let lambda_set = layout_interner.insert_lambda_set(
+            &arena,
+            arena.alloc(args),
+            Layout::U8,
+            arena.alloc(set),
+            NeedsRecursionPointerFixup(false),
+            Layout::UNIT,
+        );
+
+        // an `Ordering` whose tag ids are numbered differently
+        let permuted = OrderingTagIds {
+            eq: 2,
+            gt: 0,
+            lt: 1,
+        };
+
+        let i8_type = context.i8_type();
+        let i64_type = context.i64_type();
+        let mut wrappers = std::vec::Vec::new();
+        for (tag_id, expected) in [
+            (permuted.eq, CompareResult::Eq),
+            (permuted.gt, CompareResult::Gt),
+            (permuted.lt, CompareResult::Lt),
+        ] {
+            // a comparator that always returns the tag
+            let roc_fn = module.add_function(
+                &format!("roc_compare_{:?}", expected),
+                i8_type.fn_type(&[i64_type.into(), i64_type.into()], false),
+                None,
+            );
+            roc_fn.set_call_conventions(FAST_CALL_CONV);
+            let host_block = builder.get_insert_block().unwrap();
+            builder.position_at_end(context.append_basic_block(roc_fn, "entry"));
+            builder.build_return(Some(&i8_type.const_int(tag_id as u64, false)));
+            builder.position_at_end(host_block);
+
+            let wrapper = build_compare_wrapper(
+                &env,
+                &mut layout_interner,
+                roc_fn,
+                lambda_set,
+                Layout::I64,
+                WrapperPointers::Standalone,
+                permuted,
+            );
+            // keeps the unused wrapper around
+            wrapper.set_linkage(inkwell::module::Linkage::External);
+            wrappers.push((wrapper, expected));
+        }
+
+        // after inlining the comparator, every wrapper returns a constant
+        module.strip_debug_info();
+        let pass_manager: PassManager<Module> = PassManager::create(());
+        pass_manager.add_function_inlining_pass();
+        pass_manager.add_instruction_combining_pass();
+        pass_manager.run_on(module);
+
+        for (wrapper, expected) in wrappers {
+            let ret = wrapper
+                .get_last_basic_block()
+                .unwrap()
+                .get_terminator()
+                .unwrap();
+            let returned = ret.get_operand(0).unwrap().left().unwrap().into_int_value();
+            assert_eq!(
+                returned.get_zero_extended_constant(),
+                Some(expected as u64),
+                "{}",
+                wrapper.print_to_string()
+            );
+        }
+    }
+
+    #[test]
+    fn interior_wrappers_only_count_on_the_element_alignment() {
+        let arena = Bump::new();
+        let context = Context::create();
+        let triple = target_lexicon::Triple::host();
+        let module: &Module = arena.alloc(crate::llvm::build::module_from_builtins(
+            &triple, &context, "test",
+        ));
+        let builder = context.create_builder();
+        let debug_info = Env::new_debug_info(module, &triple);
+        let env = Env::for_tests(&arena, &context, module, &builder, &debug_info);
+        let mut layout_interner = STLayoutInterner::with_capacity(4, TargetInfo::default_x86_64());
+        let mut layout_ids = LayoutIds::default();
+
+        env.add_test_host_function();
+
+        let mut variants = |layout| {
+            [WrapperPointers::Standalone, WrapperPointers::Interior].map(|pointers| {
+                build_eq_wrapper(
+                    &env,
+                    &mut layout_interner,
+                    &mut layout_ids,
+                    layout,
+                    pointers,
+                )
+            })
+        };
+        let [str_standalone, str_interior] = variants(Layout::STR);
+        let [u8_standalone, u8_interior] = variants(Layout::U8);
+        module.strip_debug_info();
+
+        let standalone_name = str_standalone.get_name().to_str().unwrap();
+        let interior_name = str_interior.get_name().to_str().unwrap();
+        assert_eq!(interior_name, format!("{}_interior", standalone_name));
+
+        // a `Str` is aligned like an allocation anyway, so the only difference is the name. In
+        // particular, the interior wrapper reads nothing but the two elements.
+        assert_eq!(
+            str_interior.print_to_string().to_string(),
+            str_standalone
+                .print_to_string()
+                .to_string()
+                .replace(standalone_name, interior_name)
+        );
+
+        let load_alignments = |function: FunctionValue<'_>| {
+            let mut alignments = std::vec::Vec::new();
+            for block in function.get_basic_blocks() {
+                let mut instruction = block.get_first_instruction();
+                while let Some(current) = instruction {
+                    if current.get_opcode() == InstructionOpcode::Load {
+                        alignments.push(current.get_alignment().unwrap());
+                    }
+                    instruction = current.get_next_instruction();
+                }
+            }
+
+            alignments
+        };
+        assert_eq!(load_alignments(u8_standalone), [8, 8]);
+        assert_eq!(load_alignments(u8_interior), [1, 1]);
+    }
+
+    #[test]
+    fn wrappers_of_small_numbers_access_exactly_their_bytes() {
+        let arena = Bump::new();
+        let context = Context::create();
+        let module: &Module = arena.alloc(context.create_module("test"));
+        let builder = context.create_builder();
+        let debug_info = Env::new_debug_info(module, &target_lexicon::Triple::host());
+        let env = Env::for_tests(&arena, &context, module, &builder, &debug_info);
+        let mut layout_interner = STLayoutInterner::with_capacity(4, TargetInfo::default_x86_64());
+        let mut layout_ids = LayoutIds::default();
+
+        env.add_test_host_function();
+
+        // the opcode, the bit width of the value, and the alignment of every load and store
+        let accesses = |function: FunctionValue<'_>| {
+            let mut accesses = std::vec::Vec::new();
+            for block in function.get_basic_blocks() {
+                let mut instruction = block.get_first_instruction();
+                while let Some(current) = instruction {
+                    let bit_width = match current.get_opcode() {
+                        InstructionOpcode::Load => {
+                            Some(current.get_type().into_int_type().get_bit_width())
+                        }
+                        InstructionOpcode::Store => current
+                            .get_operand(0)
+                            .and_then(|operand| operand.left())
+                            .map(|value| value.get_type().into_int_type().get_bit_width()),
+                        _ => None,
+                    };
+                    if let Some(bit_width) = bit_width {
+                        accesses.push((
+                            current.get_opcode(),
+                            bit_width,
+                            current.get_alignment().unwrap(),
+                        ));
+                    }
+                    instruction = current.get_next_instruction();
+                }
+            }
+
+            accesses
+        };
+
+        for (layout, bits) in [
+            (Layout::U8, 8),
+            (Layout::I8, 8),
+            (Layout::U16, 16),
+            (Layout::I16, 16),
+        ] {
+            let alignment = bits / 8;
+
+            let eq_wrapper = build_eq_wrapper(
+                &env,
+                &mut layout_interner,
+                &mut layout_ids,
+                layout,
+                WrapperPointers::Interior,
+            );
+            assert_eq!(
+                accesses(eq_wrapper),
+                [
+                    (InstructionOpcode::Load, bits, alignment),
+                    (InstructionOpcode::Load, bits, alignment),
+                ]
+            );
+
+            // stands in for `\b -> b + 1`
+            let args: &[InLayout] = arena.alloc([layout]);
+            let set: &[(Symbol, &[InLayout])] = arena.alloc([(Symbol::ATTR_ATTR, &[] as &[_])]);
+            let lambda_set = layout_interner.insert_lambda_set(
+                &arena,
+                arena.alloc(args),
+                layout,
+                arena.alloc(set),
+                NeedsRecursionPointerFixup(false),
+                Layout::UNIT,
+            );
+            let int_type = context.custom_width_int_type(bits);
+            let roc_fn = module.add_function(
+                &format!("roc_increment_{}", layout_interner.dbg(layout)),
+                int_type.fn_type(&[int_type.into()], false),
+                None,
+            );
+            roc_fn.set_call_conventions(FAST_CALL_CONV);
+            let host_block = builder.get_insert_block().unwrap();
+            builder.position_at_end(context.append_basic_block(roc_fn, "entry"));
+            let argument = roc_fn.get_first_param().unwrap().into_int_value();
+            let incremented =
+                builder.build_int_add(argument, int_type.const_int(1, false), "incremented");
+            builder.build_return(Some(&incremented));
+            builder.position_at_end(host_block);
+
+            let caller = build_transform_caller(
+                &env,
+                &mut layout_interner,
+                roc_fn,
+                lambda_set,
+                &[layout],
+                layout,
+            );
+            // the element is read, and the result written, without touching its neighbours
+            assert_eq!(
+                accesses(caller),
+                [
+                    (InstructionOpcode::Load, bits, alignment),
+                    (InstructionOpcode::Store, bits, alignment),
+                ],
+                "{}",
+                caller.print_to_string()
+            );
+        }
+    }
+
+    /// The functions that `function` calls directly
+    fn callees<'ctx>(
+        module: &Module<'ctx>,
+        function: FunctionValue<'ctx>,
+    ) -> std::vec::Vec<FunctionValue<'ctx>> {
+        let mut callees = std::vec::Vec::new();
+        for block in function.get_basic_blocks() {
+            let mut instruction = block.get_first_instruction();
+            while let Some(current) = instruction {
+                if current.get_opcode() == InstructionOpcode::Call {
+                    // the callee is the last operand of a call
+                    let callee = current
+                        .get_operand(current.get_num_operands() - 1)
+                        .and_then(|operand| operand.left());
+                    if let Some(callee) = callee {
+                        let name = callee.into_pointer_value().get_name().to_str().unwrap();
+                        callees.extend(module.get_function(name));
+                    }
+                }
+                instruction = current.get_next_instruction();
+            }
+        }
+
+        callees
+    }
+
+    #[test]
+    fn wrappers_of_recursive_layouts_call_themselves() {
+        let arena = Bump::new();
+        let context = Context::create();
+        let triple = target_lexicon::Triple::host();
+        let module: &Module = arena.alloc(crate::llvm::build::module_from_builtins(
+            &triple, &context, "test",
+        ));
+        let builder = context.create_builder();
+        let debug_info = Env::new_debug_info(module, &target_lexicon::Triple::host());
+        let env = Env::for_tests(&arena, &context, module, &builder, &debug_info);
+        let mut layout_interner = STLayoutInterner::with_capacity(4, TargetInfo::default_x86_64());
+        let mut layout_ids = LayoutIds::default();
+
+        env.add_test_host_function();
+
+        // `ConsList : [Nil, Cons ConsList ConsList]`, a cons list of cons lists
+        let rec_ptr = layout_interner.insert(Layout::RecursivePointer(Layout::VOID));
+        let fields: &[InLayout] = arena.alloc([rec_ptr, rec_ptr]);
+        let cons_list = layout_interner.insert_recursive(
+            &arena,
+            Layout::Union(UnionLayout::NullableUnwrapped {
+                nullable_id: false,
+                other_fields: fields,
+            }),
+        );
+
+        let wrappers = [
+            build_inc_wrapper(&env, &mut layout_interner, &mut layout_ids, cons_list),
+            build_dec_wrapper(&env, &mut layout_interner, &mut layout_ids, cons_list),
+            build_eq_wrapper(
+                &env,
+                &mut layout_interner,
+                &mut layout_ids,
+                cons_list,
+                WrapperPointers::Standalone,
+            ),
+        ];
+        assert!(env.wrappers_in_progress.borrow().is_empty());
+
+        for wrapper in wrappers {
+            assert!(wrapper.count_basic_blocks() > 0);
+
+            // the wrapper hands the value to a helper, which calls itself for the tail
+            let mut reachable = std::vec::Vec::new();
+            let mut stack = vec![wrapper];
+            while let Some(function) = stack.pop() {
+                if !reachable.contains(&function) {
+                    reachable.push(function);
+                    stack.extend(callees(module, function));
+                }
+            }
+
+            let recursive = reachable
+                .iter()
+                .any(|function| callees(module, *function).contains(function));
+            assert!(
+                recursive,
+                "nothing that {:?} calls is recursive",
+                wrapper.get_name()
+            );
+        }
+
+        // asking again gives the same functions
+        assert_eq!(
+            build_inc_wrapper(&env, &mut layout_interner, &mut layout_ids, cons_list),
+            wrappers[0]
+        );
+        assert_eq!(
+            build_eq_wrapper(
+                &env,
+                &mut layout_interner,
+                &mut layout_ids,
+                cons_list,
+                WrapperPointers::Standalone,
+            ),
+            wrappers[2]
+        );
+    }
+}
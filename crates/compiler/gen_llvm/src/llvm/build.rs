@@ -4,9 +4,13 @@ use crate::llvm::convert::{
     argument_type_from_layout, basic_type_from_builtin, basic_type_from_layout, zig_str_type,
 };
 use crate::llvm::expect::{clone_to_shared_memory, SharedMemoryPointer};
+use crate::llvm::host_exposed::{HostCallConv, HostExposed};
+use crate::llvm::memory_model::{alloc_function, dealloc_function, panic_function, MemoryModel};
 use crate::llvm::refcounting::{
     build_reset, decrement_refcount_layout, increment_refcount_layout, PointerToRefcount,
 };
+use crate::llvm::verify::record_fn_origin;
+use crate::CodegenOptions;
 use bumpalo::collections::Vec;
 use bumpalo::Bump;
 use inkwell::attributes::{Attribute, AttributeLoc};
@@ -24,32 +28,36 @@ use inkwell::types::{
 };
 use inkwell::values::BasicValueEnum::{self, *};
 use inkwell::values::{
-    BasicMetadataValueEnum, CallSiteValue, FunctionValue, InstructionValue, IntValue, PhiValue,
-    PointerValue, StructValue,
+    BasicMetadataValueEnum, BasicValue, CallSiteValue, FunctionValue, GlobalValue,
+    InstructionValue, IntValue, PhiValue, PointerValue, StructValue,
 };
 use inkwell::OptimizationLevel;
-use inkwell::{AddressSpace, IntPredicate};
+use inkwell::{AddressSpace, AtomicOrdering, IntPredicate};
 use morphic_lib::{
     CalleeSpecVar, FuncName, FuncSpec, FuncSpecSolutions, ModSolutions, UpdateMode, UpdateModeVar,
 };
 use roc_builtins::bitcode::{self, FloatWidth, IntWidth};
+use roc_builtins::rc_layout::{allocation_padding, constant_refcount_le_bytes};
 use roc_collections::all::{ImMap, MutMap, MutSet};
 use roc_debug_flags::dbg_do;
 #[cfg(debug_assertions)]
 use roc_debug_flags::ROC_PRINT_LLVM_FN_VERIFICATION;
+use roc_error_macros::internal_error;
 use roc_module::symbol::{Interns, ModuleId, Symbol};
 use roc_mono::ir::{
-    BranchInfo, CallType, CrashTag, EntryPoint, JoinPointId, ListLiteralElement, ModifyRc,
-    OptLevel, ProcLayout, SingleEntryPoint,
+    BranchInfo, CallType, CrashRegion, CrashTag, EntryPoint, JoinPointId, ListLiteralElement,
+    ModifyRc, OptLevel, ProcLayout, SingleEntryPoint,
 };
 use roc_mono::layout::{
-    Builtin, InLayout, LambdaName, LambdaSet, Layout, LayoutIds, LayoutInterner, Niche,
-    RawFunctionLayout, STLayoutInterner, TagIdIntType, UnionLayout,
+    closure_env_size_bytes, field_offsets, layout_to_type_name, round_up_to_alignment, Builtin,
+    InLayout, LambdaName, LambdaSet, Layout, LayoutIds, LayoutInterner, Niche, RawFunctionLayout,
+    STLayoutInterner, TagIdIntType, UnionLayout,
 };
 use roc_std::RocDec;
-use roc_target::{PtrWidth, TargetInfo};
+use roc_target::{Endianness, PtrWidth, TargetInfo};
+use std::cell::RefCell;
 use std::convert::TryInto;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use target_lexicon::{Architecture, OperatingSystem, Triple};
 
 use super::convert::{struct_type_from_union_layout, RocUnion};
@@ -248,10 +256,35 @@ pub struct Env<'a, 'ctx, 'env> {
     pub interns: Interns,
     pub target_info: TargetInfo,
     pub mode: LlvmBackendMode,
+    /// How to generate code. `mode` is separate, because it also says what to generate.
+    pub options: CodegenOptions,
+    /// The allocator that the generated code calls
+    pub memory_model: &'env dyn MemoryModel,
     pub exposed_to_host: MutSet<Symbol>,
+    pub host_exposed: RefCell<HostExposed<'a>>,
+    /// The names of the wrappers (see `bitcode.rs`) whose bodies are being built right now.
+    /// The name includes the id of the layout, so when a recursive layout asks for a wrapper
+    /// that is in here, it gets a call to the declaration instead of a second build.
+    pub wrappers_in_progress: RefCell<MutSet<String>>,
+    /// What each proc and wrapper was generated from, by the name of its function. When the module
+    /// fails verification, this says which layouts and symbols the broken functions came from.
+    pub fn_origins: RefCell<MutMap<String, String>>,
+    /// The path and source of each module, so a crash can tell the host where it is. A crash in
+    /// a module that is not in here is reported without its location.
+    pub sources: MutMap<ModuleId, (PathBuf, Box<str>)>,
 }
 
 impl<'a, 'ctx, 'env> Env<'a, 'ctx, 'env> {
+    /// Whether to zero a value of `layout` before its fields are stored, see
+    /// `CodegenOptions::zero_padding`
+    pub fn zeroes_padding(
+        &self,
+        layout_interner: &STLayoutInterner<'a>,
+        layout: &Layout<'a>,
+    ) -> bool {
+        self.options.zero_padding || layout.is_byte_comparable(layout_interner)
+    }
+
     /// The integer type representing a pointer
     ///
     /// on 64-bit systems, this is i64
@@ -282,6 +315,19 @@ impl<'a, 'ctx, 'env> Env<'a, 'ctx, 'env> {
         self.target_info.ptr_width() as u32 * 3
     }
 
+    /// Records a function that the host can call, for use by glue generation.
+    pub fn register_host_exposed(
+        &self,
+        name: &str,
+        arguments: &[InLayout<'a>],
+        result: InLayout<'a>,
+        call_conv: HostCallConv,
+    ) {
+        self.host_exposed
+            .borrow_mut()
+            .register(name, arguments, result, call_conv)
+    }
+
     pub fn build_intrinsic_call(
         &self,
         intrinsic_name: &'static str,
@@ -347,13 +393,14 @@ impl<'a, 'ctx, 'env> Env<'a, 'ctx, 'env> {
         number_of_bytes: IntValue<'ctx>,
         alignment: u32,
     ) -> PointerValue<'ctx> {
-        let function = self.module.get_function("roc_alloc").unwrap();
+        let function = alloc_function(self);
         let alignment = self.alignment_const(alignment);
-        let call = self.builder.build_call(
-            function,
-            &[number_of_bytes.into(), alignment.into()],
-            "roc_alloc",
-        );
+        let mut arguments: Vec<BasicMetadataValueEnum> =
+            bumpalo::vec![in self.arena; number_of_bytes.into()];
+        if self.memory_model.takes_alignment() {
+            arguments.push(alignment.into());
+        }
+        let call = self.builder.build_call(function, &arguments, "roc_alloc");
 
         call.set_call_convention(C_CALL_CONV);
 
@@ -365,11 +412,13 @@ impl<'a, 'ctx, 'env> Env<'a, 'ctx, 'env> {
     }
 
     pub fn call_dealloc(&self, ptr: PointerValue<'ctx>, alignment: u32) -> InstructionValue<'ctx> {
-        let function = self.module.get_function("roc_dealloc").unwrap();
+        let function = dealloc_function(self);
         let alignment = self.alignment_const(alignment);
-        let call =
-            self.builder
-                .build_call(function, &[ptr.into(), alignment.into()], "roc_dealloc");
+        let mut arguments: Vec<BasicMetadataValueEnum> = bumpalo::vec![in self.arena; ptr.into()];
+        if self.memory_model.takes_alignment() {
+            arguments.push(alignment.into());
+        }
+        let call = self.builder.build_call(function, &arguments, "roc_dealloc");
 
         call.set_call_convention(C_CALL_CONV);
 
@@ -400,15 +449,26 @@ impl<'a, 'ctx, 'env> Env<'a, 'ctx, 'env> {
         )
     }
 
+    /// Calls `roc_panic`, or what the memory model calls it. The host gets a pointer to the
+    /// [SourceInfo] of the crash, or null when there is none.
     pub fn call_panic(
         &self,
         env: &Env<'a, 'ctx, 'env>,
         message: BasicValueEnum<'ctx>,
         tag: CrashTag,
+        source: Option<PointerValue<'ctx>>,
     ) {
-        let function = self.module.get_function("roc_panic").unwrap();
+        let function = panic_function(self);
         let tag_id = self.context.i32_type().const_int(tag as u32 as u64, false);
 
+        let source_type = function.get_type().get_param_types()[2].into_pointer_type();
+        let source = match source {
+            Some(ptr) => self
+                .builder
+                .build_pointer_cast(ptr, source_type, "to_source_info_ptr"),
+            None => source_type.const_null(),
+        };
+
         let msg = match env.target_info.ptr_width() {
             PtrWidth::Bytes4 => {
                 // we need to pass the message by reference, but we currently hold the value.
@@ -424,20 +484,39 @@ impl<'a, 'ctx, 'env> Env<'a, 'ctx, 'env> {
             }
         };
 
-        let call = self
-            .builder
-            .build_call(function, &[msg.into(), tag_id.into()], "roc_panic");
+        let call = self.builder.build_call(
+            function,
+            &[msg.into(), tag_id.into(), source.into()],
+            "roc_panic",
+        );
 
         call.set_call_convention(C_CALL_CONV);
+
+        // the host must never return from roc_panic
+        let kind_id = Attribute::get_named_enum_kind_id("noreturn");
+        let noreturn = self.context.create_enum_attribute(kind_id, 0);
+        call.add_attribute(AttributeLoc::Function, noreturn);
     }
 
-    pub fn new_debug_info(module: &Module<'ctx>) -> (DebugInfoBuilder<'ctx>, DICompileUnit<'ctx>) {
+    /// The debug info of `module`. Its producer, which is also in the named metadata
+    /// `PRODUCER_METADATA`, says which backend and compiler built the module for `target`.
+    pub fn new_debug_info(
+        module: &Module<'ctx>,
+        target: &Triple,
+    ) -> (DebugInfoBuilder<'ctx>, DICompileUnit<'ctx>) {
+        let producer = roc_target::producer("llvm", target);
+        let context = module.get_context();
+        let producer_node = context.metadata_node(&[context.metadata_string(&producer).into()]);
+        if let Err(error) = module.add_global_metadata(PRODUCER_METADATA, &producer_node) {
+            internal_error!("failed to add the producer to the module: {}", error);
+        }
+
         module.create_debug_info_builder(
             true,
             /* language */ inkwell::debug_info::DWARFSourceLanguage::C,
             /* filename */ "roc_app",
             /* directory */ ".",
-            /* producer */ "my llvm compiler frontend",
+            /* producer */ &producer,
             /* is_optimized */ false,
             /* compiler command line flags */ "",
             /* runtime_ver */ 0,
@@ -487,6 +566,51 @@ impl<'a, 'ctx, 'env> Env<'a, 'ctx, 'env> {
     }
 }
 
+#[cfg(test)]
+impl<'a, 'ctx, 'env> Env<'a, 'ctx, 'env> {
+    /// An `Env` for unit tests, which builds for x86_64 and allocates with libc. A test that
+    /// needs something else can change those fields with struct update syntax.
+    pub(crate) fn for_tests(
+        arena: &'a Bump,
+        context: &'ctx Context,
+        module: &'ctx Module<'ctx>,
+        builder: &'env Builder<'ctx>,
+        (dibuilder, compile_unit): &'env (DebugInfoBuilder<'ctx>, DICompileUnit<'ctx>),
+    ) -> Self {
+        Env {
+            arena,
+            context,
+            builder,
+            dibuilder,
+            compile_unit,
+            module,
+            interns: Default::default(),
+            target_info: TargetInfo::default_x86_64(),
+            mode: LlvmBackendMode::GenTest,
+            options: CodegenOptions::new(OptLevel::Normal, Triple::host()),
+            memory_model: &crate::llvm::memory_model::LibcMemoryModel,
+            exposed_to_host: Default::default(),
+            host_exposed: Default::default(),
+            wrappers_in_progress: Default::default(),
+            fn_origins: Default::default(),
+            sources: Default::default(),
+        }
+    }
+
+    /// Adds `void host()` with debug info, and puts the builder at its start, since most code
+    /// can only be built inside of a function
+    pub(crate) fn add_test_host_function(&self) -> FunctionValue<'ctx> {
+        let fn_type = self.context.void_type().fn_type(&[], false);
+        let host_fn = self.module.add_function("host", fn_type, None);
+        host_fn.set_subprogram(self.new_subprogram("host"));
+        self.builder
+            .position_at_end(self.context.append_basic_block(host_fn, "entry"));
+        debug_info_init!(self, host_fn);
+
+        host_fn
+    }
+}
+
 pub fn module_from_builtins<'ctx>(
     target: &target_lexicon::Triple,
     ctx: &'ctx Context,
@@ -706,6 +830,8 @@ fn promote_to_wasm_test_wrapper<'a, 'ctx, 'env>(
             Linkage::External,
         );
 
+        env.register_host_exposed(main_fn_name, &[], top_level.result, HostCallConv::C);
+
         let subprogram = env.new_subprogram(main_fn_name);
         c_function.set_subprogram(subprogram);
 
@@ -1035,35 +1161,56 @@ where
     struct_value.into_struct_value()
 }
 
-fn struct_pointer_from_fields<'a, 'ctx, 'env, I>(
+/// Stores `fields`, in order, into the struct at `input_pointer`.
+fn struct_pointer_from_fields<'a, 'ctx, 'env>(
     env: &Env<'a, 'ctx, 'env>,
     layout_interner: &mut STLayoutInterner<'a>,
-    struct_type: StructType<'ctx>,
     input_pointer: PointerValue<'ctx>,
-    values: I,
-) where
-    I: Iterator<Item = (usize, (InLayout<'a>, BasicValueEnum<'ctx>))>,
-{
-    let struct_ptr = env
-        .builder
-        .build_bitcast(
-            input_pointer,
-            struct_type.ptr_type(AddressSpace::default()),
-            "struct_ptr",
-        )
-        .into_pointer_value();
+    fields: &[(InLayout<'a>, BasicValueEnum<'ctx>)],
+) {
+    let field_layouts: std::vec::Vec<_> = fields.iter().map(|(layout, _)| *layout).collect();
+    let offsets = field_offsets(layout_interner, &field_layouts);
 
-    // Insert field exprs into struct_val
-    for (index, (field_layout, field_value)) in values {
-        let field_ptr = env
-            .builder
-            .new_build_struct_gep(struct_type, struct_ptr, index as u32, "field_struct_gep")
-            .unwrap();
+    for ((offset, field_layout), (_, field_value)) in offsets.into_iter().zip(fields) {
+        let field_ptr = struct_field_ptr(env, layout_interner, input_pointer, offset, field_layout);
 
-        store_roc_value(env, layout_interner, field_layout, field_ptr, field_value);
+        store_roc_value(env, layout_interner, field_layout, field_ptr, *field_value);
     }
 }
 
+/// A pointer to the field of the struct at `struct_ptr` that starts `offset` bytes in, as given
+/// by [`field_offsets`]. The dev backend locates fields with the same offsets, so both backends
+/// agree on where every field lives.
+fn struct_field_ptr<'a, 'ctx, 'env>(
+    env: &Env<'a, 'ctx, 'env>,
+    layout_interner: &mut STLayoutInterner<'a>,
+    struct_ptr: PointerValue<'ctx>,
+    offset: u32,
+    field_layout: InLayout<'a>,
+) -> PointerValue<'ctx> {
+    let i8_type = env.context.i8_type();
+    let bytes = env.builder.build_pointer_cast(
+        struct_ptr,
+        i8_type.ptr_type(AddressSpace::default()),
+        "struct_bytes",
+    );
+    let field_bytes = unsafe {
+        env.builder.new_build_in_bounds_gep(
+            i8_type,
+            bytes,
+            &[env.ptr_int().const_int(offset as u64, false)],
+            "field_bytes",
+        )
+    };
+
+    let field_type = basic_type_from_layout(env, layout_interner, field_layout);
+    env.builder.build_pointer_cast(
+        field_bytes,
+        field_type.ptr_type(AddressSpace::default()),
+        "field_ptr",
+    )
+}
+
 pub fn build_exp_expr<'a, 'ctx, 'env>(
     env: &Env<'a, 'ctx, 'env>,
     layout_interner: &mut STLayoutInterner<'a>,
@@ -1130,17 +1277,8 @@ pub fn build_exp_expr<'a, 'ctx, 'env>(
 
         ExprBox { symbol } => {
             let (value, layout) = load_symbol_and_layout(scope, symbol);
-            let basic_type = basic_type_from_layout(env, layout_interner, layout);
-            let allocation = reserve_with_refcount_help(
-                env,
-                basic_type,
-                layout_interner.stack_size(layout),
-                layout_interner.alignment_bytes(layout),
-            );
 
-            store_roc_value(env, layout_interner, layout, allocation, value);
-
-            allocation.into()
+            build_box(env, layout_interner, layout, value).into()
         }
 
         ExprUnbox { symbol } => {
@@ -1292,10 +1430,6 @@ pub fn build_exp_expr<'a, 'ctx, 'env>(
 
                     let field_layouts = tag_layouts[*tag_id as usize];
 
-                    let struct_layout =
-                        layout_interner.insert(Layout::struct_no_name_order(field_layouts));
-                    let struct_type = basic_type_from_layout(env, layout_interner, struct_layout);
-
                     let opaque_data_ptr = env
                         .builder
                         .new_build_struct_gep(
@@ -1307,26 +1441,20 @@ pub fn build_exp_expr<'a, 'ctx, 'env>(
                         )
                         .unwrap();
 
-                    let data_ptr = env.builder.build_pointer_cast(
+                    let (offset, field_layout) =
+                        field_offsets(layout_interner, field_layouts)[*index as usize];
+                    let element_ptr = struct_field_ptr(
+                        env,
+                        layout_interner,
                         opaque_data_ptr,
-                        struct_type.ptr_type(AddressSpace::default()),
-                        "to_data_pointer",
+                        offset,
+                        field_layout,
                     );
 
-                    let element_ptr = env
-                        .builder
-                        .new_build_struct_gep(
-                            struct_type.into_struct_type(),
-                            data_ptr,
-                            *index as _,
-                            "get_opaque_data_ptr",
-                        )
-                        .unwrap();
-
                     load_roc_value(
                         env,
                         layout_interner,
-                        field_layouts[*index as usize],
+                        field_layout,
                         element_ptr,
                         "load_element",
                     )
@@ -1347,22 +1475,14 @@ pub fn build_exp_expr<'a, 'ctx, 'env>(
                         ptr,
                     )
                 }
-                UnionLayout::NonNullableUnwrapped(field_layouts) => {
-                    let struct_layout =
-                        layout_interner.insert(Layout::struct_no_name_order(field_layouts));
-
-                    let struct_type = basic_type_from_layout(env, layout_interner, struct_layout);
-
-                    lookup_at_index_ptr(
-                        env,
-                        layout_interner,
-                        union_layout,
-                        field_layouts,
-                        *index as usize,
-                        argument.into_pointer_value(),
-                        struct_type.into_struct_type(),
-                    )
-                }
+                UnionLayout::NonNullableUnwrapped(field_layouts) => lookup_at_index_ptr(
+                    env,
+                    layout_interner,
+                    union_layout,
+                    field_layouts,
+                    *index as usize,
+                    argument.into_pointer_value(),
+                ),
                 UnionLayout::NullableWrapped {
                     nullable_id,
                     other_tags,
@@ -1395,21 +1515,14 @@ pub fn build_exp_expr<'a, 'ctx, 'env>(
                     debug_assert!(argument.is_pointer_value());
                     debug_assert_ne!(*tag_id != 0, *nullable_id);
 
-                    let field_layouts = other_fields;
-                    let struct_layout =
-                        layout_interner.insert(Layout::struct_no_name_order(field_layouts));
-
-                    let struct_type = basic_type_from_layout(env, layout_interner, struct_layout);
-
                     lookup_at_index_ptr(
                         env,
                         layout_interner,
                         union_layout,
-                        field_layouts,
+                        other_fields,
                         // the tag id is not stored
                         *index as usize,
                         argument.into_pointer_value(),
-                        struct_type.into_struct_type(),
                     )
                 }
             }
@@ -1443,8 +1556,7 @@ fn build_wrapped_tag<'a, 'ctx, 'env>(
 
     let tag_id_layout = union_layout.tag_id_layout();
 
-    let (field_types, field_values) =
-        build_tag_fields(env, layout_interner, scope, tag_field_layouts, arguments);
+    let field_values = build_tag_fields(env, layout_interner, scope, tag_field_layouts, arguments);
 
     let union_struct_type = struct_type_from_union_layout(env, layout_interner, union_layout);
 
@@ -1457,7 +1569,6 @@ fn build_wrapped_tag<'a, 'ctx, 'env>(
         union_layout,
         tags,
     );
-    let struct_type = env.context.struct_type(&field_types, false);
 
     if union_layout.stores_tag_id_as_data(env.target_info) {
         let tag_id_ptr = builder
@@ -1484,28 +1595,29 @@ fn build_wrapped_tag<'a, 'ctx, 'env>(
             )
             .unwrap();
 
-        struct_pointer_from_fields(
-            env,
-            layout_interner,
-            struct_type,
-            opaque_struct_ptr,
-            field_values.into_iter().enumerate(),
-        );
+        struct_pointer_from_fields(env, layout_interner, opaque_struct_ptr, &field_values);
 
         raw_data_ptr.into()
     } else {
-        struct_pointer_from_fields(
-            env,
-            layout_interner,
-            struct_type,
-            raw_data_ptr,
-            field_values.into_iter().enumerate(),
-        );
+        struct_pointer_from_fields(env, layout_interner, raw_data_ptr, &field_values);
 
         tag_pointer_set_tag_id(env, tag_id, raw_data_ptr).into()
     }
 }
 
+/// Sets every byte of the `basic_type` at `ptr` to zero, padding included
+pub(crate) fn build_zero_fill<'ctx>(
+    env: &Env<'_, 'ctx, '_>,
+    ptr: PointerValue<'ctx>,
+    basic_type: BasicTypeEnum<'ctx>,
+) {
+    let size = basic_type.size_of().unwrap();
+
+    env.builder
+        .build_memset(ptr, 1, env.context.i8_type().const_zero(), size)
+        .unwrap();
+}
+
 pub fn entry_block_alloca_zerofill<'a, 'ctx, 'env>(
     env: &Env<'a, 'ctx, 'env>,
     basic_type: BasicTypeEnum<'ctx>,
@@ -1558,27 +1670,19 @@ fn build_tag_fields<'a, 'r, 'ctx, 'env>(
     scope: &'r Scope<'a, 'ctx>,
     fields: &[InLayout<'a>],
     arguments: &[Symbol],
-) -> (
-    std::vec::Vec<BasicTypeEnum<'ctx>>,
-    std::vec::Vec<(InLayout<'a>, BasicValueEnum<'ctx>)>,
-) {
+) -> std::vec::Vec<(InLayout<'a>, BasicValueEnum<'ctx>)> {
     debug_assert_eq!(fields.len(), arguments.len());
 
-    let capacity = fields.len();
-    let mut field_types = std::vec::Vec::with_capacity(capacity);
-    let mut field_values = std::vec::Vec::with_capacity(capacity);
+    let mut field_values = std::vec::Vec::with_capacity(fields.len());
 
     for (field_symbol, tag_field_layout) in arguments.iter().zip(fields.iter()) {
-        let field_type = basic_type_from_layout(env, layout_interner, *tag_field_layout);
-        field_types.push(field_type);
-
         let raw_value: BasicValueEnum<'ctx> = load_symbol(scope, field_symbol);
         let field_value = build_tag_field_value(env, layout_interner, raw_value, *tag_field_layout);
 
         field_values.push((*tag_field_layout, field_value));
     }
 
-    (field_types, field_values)
+    field_values
 }
 
 fn build_struct<'a, 'ctx, 'env>(
@@ -1643,7 +1747,8 @@ fn build_tag<'a, 'ctx, 'env>(
 
             let roc_union =
                 RocUnion::tagged_from_slices(layout_interner, env.context, tags, env.target_info);
-            let value = roc_union.as_struct_value(env, data, Some(tag_id as _));
+            let zero_fill = env.zeroes_padding(layout_interner, &Layout::Union(*union_layout));
+            let value = roc_union.as_struct_value(env, data, Some(tag_id as _), zero_fill);
 
             let alloca = create_entry_block_alloca(
                 env,
@@ -1712,8 +1817,7 @@ fn build_tag<'a, 'ctx, 'env>(
             debug_assert_eq!(tag_id, 0);
             debug_assert_eq!(arguments.len(), fields.len());
 
-            let (field_types, field_values) =
-                build_tag_fields(env, layout_interner, scope, fields, arguments);
+            let field_values = build_tag_fields(env, layout_interner, scope, fields, arguments);
 
             // Create the struct_type
             let data_ptr = reserve_with_refcount_union_as_block_of_memory(
@@ -1723,18 +1827,7 @@ fn build_tag<'a, 'ctx, 'env>(
                 &[fields],
             );
 
-            let struct_type = env.context.struct_type(
-                env.arena.alloc_slice_fill_iter(field_types.into_iter()),
-                false,
-            );
-
-            struct_pointer_from_fields(
-                env,
-                layout_interner,
-                struct_type,
-                data_ptr,
-                field_values.into_iter().enumerate(),
-            );
+            struct_pointer_from_fields(env, layout_interner, data_ptr, &field_values);
 
             data_ptr.into()
         }
@@ -1773,7 +1866,8 @@ fn build_tag<'a, 'ctx, 'env>(
 
             let data = build_struct(env, layout_interner, scope, arguments);
 
-            let value = roc_union.as_struct_value(env, data, None);
+            let zero_fill = env.zeroes_padding(layout_interner, &Layout::Union(*union_layout));
+            let value = roc_union.as_struct_value(env, data, None, zero_fill);
 
             env.builder.build_store(data_ptr, value);
 
@@ -1996,21 +2090,12 @@ fn lookup_at_index_ptr<'a, 'ctx, 'env>(
     field_layouts: &[InLayout<'a>],
     index: usize,
     value: PointerValue<'ctx>,
-    struct_type: StructType<'ctx>,
 ) -> BasicValueEnum<'ctx> {
     let builder = env.builder;
 
-    let ptr = env.builder.build_pointer_cast(
-        value,
-        struct_type.ptr_type(AddressSpace::default()),
-        "cast_lookup_at_index_ptr",
-    );
-
-    let elem_ptr = builder
-        .new_build_struct_gep(struct_type, ptr, index as u32, "at_index_struct_gep")
-        .unwrap();
+    let (offset, field_layout) = field_offsets(layout_interner, field_layouts)[index];
+    let elem_ptr = struct_field_ptr(env, layout_interner, value, offset, field_layout);
 
-    let field_layout = field_layouts[index];
     let result = load_roc_value(
         env,
         layout_interner,
@@ -2051,26 +2136,9 @@ fn lookup_at_index_ptr2<'a, 'ctx, 'env>(
 ) -> BasicValueEnum<'ctx> {
     let builder = env.builder;
 
-    let struct_layout = layout_interner.insert(Layout::struct_no_name_order(field_layouts));
-    let struct_type =
-        basic_type_from_layout(env, layout_interner, struct_layout).into_struct_type();
+    let (offset, field_layout) = field_offsets(layout_interner, field_layouts)[index];
+    let elem_ptr = struct_field_ptr(env, layout_interner, value, offset, field_layout);
 
-    let data_ptr = env.builder.build_pointer_cast(
-        value,
-        struct_type.ptr_type(AddressSpace::default()),
-        "cast_lookup_at_index_ptr",
-    );
-
-    let elem_ptr = builder
-        .new_build_struct_gep(
-            struct_type,
-            data_ptr,
-            index as u32,
-            "at_index_struct_gep_data",
-        )
-        .unwrap();
-
-    let field_layout = field_layouts[index];
     let result = load_roc_value(
         env,
         layout_interner,
@@ -2186,231 +2254,594 @@ pub fn allocate_with_refcount_help<'a, 'ctx, 'env>(
         .build_pointer_cast(ptr, ptr_type, "alloc_cast_to_desired")
 }
 
-fn list_literal<'a, 'ctx, 'env>(
+/// Suspends the current procedure at an effect, so that the scheduler can resume it later.
+///
+/// The values the rest of the procedure needs are moved to the heap, behind a pointer to
+/// `resume_fn`, and the procedure returns this continuation to the scheduler. The values are the
+/// fields of the struct `env_layout`, in order. Once the effect is done, the scheduler calls
+/// `resume_fn` with the continuation, which gets the values back with [`load_effect_resume_env`].
+///
+/// The returned instruction is the `ret`, so nothing can be added to the current block after it.
+pub fn build_effect_resume_point<'a, 'ctx, 'env>(
     env: &Env<'a, 'ctx, 'env>,
     layout_interner: &mut STLayoutInterner<'a>,
-    parent: FunctionValue<'ctx>,
-    scope: &Scope<'a, 'ctx>,
-    element_layout: InLayout<'a>,
-    elems: &[ListLiteralElement],
-) -> BasicValueEnum<'ctx> {
-    let ctx = env.context;
-    let builder = env.builder;
+    resume_fn: FunctionValue<'ctx>,
+    env_layout: InLayout<'a>,
+    live_values: &[BasicValueEnum<'ctx>],
+) -> InstructionValue<'ctx> {
+    let field_layouts = effect_env_field_layouts(layout_interner, env_layout);
+    debug_assert_eq!(field_layouts.len(), live_values.len());
+
+    let continuation_type = effect_continuation_type(env, layout_interner, env_layout);
+    let ptr_bytes = env.target_info.ptr_width() as u32;
+    let env_alignment = layout_interner.alignment_bytes(env_layout);
+    let continuation_bytes =
+        round_up_to_alignment(ptr_bytes, env_alignment) + layout_interner.stack_size(env_layout);
+    let continuation = allocate_with_refcount_help(
+        env,
+        continuation_type,
+        env_alignment.max(ptr_bytes),
+        env.ptr_int().const_int(continuation_bytes as u64, false),
+    );
 
-    let element_type = basic_type_from_layout(env, layout_interner, element_layout);
+    let i8_ptr_type = env.context.i8_type().ptr_type(AddressSpace::default());
+    let resume_fn_ptr = env
+        .builder
+        .new_build_struct_gep(continuation_type, continuation, 0, "resume_fn_ptr")
+        .unwrap();
+    let resume_fn_value = env.builder.build_pointer_cast(
+        resume_fn.as_global_value().as_pointer_value(),
+        i8_ptr_type,
+        "resume_fn_to_opaque",
+    );
+    env.builder.build_store(resume_fn_ptr, resume_fn_value);
 
-    let list_length = elems.len();
-    let list_length_intval = env.ptr_int().const_int(list_length as _, false);
+    // Zero-sized values have no runtime representation, and no field in the environment.
+    let env_ptr = env
+        .builder
+        .new_build_struct_gep(continuation_type, continuation, 1, "effect_env_ptr")
+        .unwrap();
+    let offsets = field_offsets(layout_interner, field_layouts);
+    for ((offset, field_layout), value) in offsets.into_iter().zip(live_values) {
+        if !layout_interner.get(field_layout).is_dropped_because_empty() {
+            let field_ptr = struct_field_ptr(env, layout_interner, env_ptr, offset, field_layout);
 
-    // TODO re-enable, currently causes morphic segfaults because it tries to update
-    // constants in-place...
-    // if element_type.is_int_type() {
-    if false {
-        let element_type = element_type.into_int_type();
-        let element_width = layout_interner.stack_size(element_layout);
-        let size = list_length * element_width as usize;
-        let alignment = layout_interner
-            .alignment_bytes(element_layout)
-            .max(env.target_info.ptr_width() as u32);
+            store_roc_value(env, layout_interner, field_layout, field_ptr, *value);
+        }
+    }
 
-        let mut is_all_constant = true;
-        let zero_elements =
-            (env.target_info.ptr_width() as u8 as f64 / element_width as f64).ceil() as usize;
+    let opaque_continuation =
+        env.builder
+            .build_pointer_cast(continuation, i8_ptr_type, "continuation_to_opaque");
 
-        // runtime-evaluated elements
-        let mut runtime_evaluated_elements = Vec::with_capacity_in(list_length, env.arena);
+    env.builder.build_return(Some(&opaque_continuation))
+}
 
-        // set up a global that contains all the literal elements of the array
-        // any variables or expressions are represented as `undef`
-        let global = {
-            let mut global_elements = Vec::with_capacity_in(list_length, env.arena);
+/// The values [`build_effect_resume_point`] saved in `continuation`, for the `resume_fn` it
+/// stored there. Zero-sized values come back as their zero value.
+pub fn load_effect_resume_env<'a, 'ctx, 'env>(
+    env: &Env<'a, 'ctx, 'env>,
+    layout_interner: &mut STLayoutInterner<'a>,
+    continuation: PointerValue<'ctx>,
+    env_layout: InLayout<'a>,
+) -> Vec<'a, BasicValueEnum<'ctx>> {
+    let field_layouts = effect_env_field_layouts(layout_interner, env_layout);
+    let continuation_type = effect_continuation_type(env, layout_interner, env_layout);
+
+    let continuation = env.builder.build_pointer_cast(
+        continuation,
+        continuation_type.ptr_type(AddressSpace::default()),
+        "opaque_to_continuation",
+    );
+    let env_ptr = env
+        .builder
+        .new_build_struct_gep(continuation_type, continuation, 1, "effect_env_ptr")
+        .unwrap();
 
-            // Add zero bytes that represent the refcount
-            //
-            // - if all elements are const, then we store the whole list as a constant.
-            //      It then needs a refcount before the first element.
-            // - but if the list is not all constants, then we will just copy the constant values,
-            //      and we do not need that refcount at the start
-            //
-            // In the latter case, we won't store the zeros in the globals
-            // (we slice them off again below)
-            for _ in 0..zero_elements {
-                global_elements.push(element_type.const_zero());
-            }
+    let mut values = Vec::with_capacity_in(field_layouts.len(), env.arena);
+    for (offset, field_layout) in field_offsets(layout_interner, field_layouts) {
+        let value = if layout_interner.get(field_layout).is_dropped_because_empty() {
+            basic_type_from_layout(env, layout_interner, field_layout).const_zero()
+        } else {
+            let field_ptr = struct_field_ptr(env, layout_interner, env_ptr, offset, field_layout);
 
-            // Copy the elements from the list literal into the array
-            for (index, element) in elems.iter().enumerate() {
-                match element {
-                    ListLiteralElement::Literal(literal) => {
-                        let val = build_exp_literal(
-                            env,
-                            layout_interner,
-                            parent,
-                            element_layout,
-                            literal,
-                        );
-                        global_elements.push(val.into_int_value());
-                    }
-                    ListLiteralElement::Symbol(symbol) => {
-                        let val = load_symbol(scope, symbol);
+            load_roc_value(
+                env,
+                layout_interner,
+                field_layout,
+                field_ptr,
+                "load_effect_env_field",
+            )
+        };
+        values.push(value);
+    }
 
-                        // here we'd like to furthermore check for intval.is_const().
-                        // if all elements are const for LLVM, we could make the array a constant.
-                        // BUT morphic does not know about this, and could allow us to modify that
-                        // array in-place. That would cause a segfault. So, we'll have to find
-                        // constants ourselves and cannot lean on LLVM here.
+    values
+}
 
-                        is_all_constant = false;
+fn effect_env_field_layouts<'a>(
+    layout_interner: &STLayoutInterner<'a>,
+    env_layout: InLayout<'a>,
+) -> &'a [InLayout<'a>] {
+    match layout_interner.get(env_layout) {
+        Layout::Struct { field_layouts, .. } => field_layouts,
+        other => internal_error!(
+            "The environment of an effect resume point must be a struct, not {:?}",
+            other
+        ),
+    }
+}
 
-                        runtime_evaluated_elements.push((index, val));
+/// `{ resume_fn, env }`
+fn effect_continuation_type<'a, 'ctx, 'env>(
+    env: &Env<'a, 'ctx, 'env>,
+    layout_interner: &mut STLayoutInterner<'a>,
+    env_layout: InLayout<'a>,
+) -> StructType<'ctx> {
+    let i8_ptr_type = env.context.i8_type().ptr_type(AddressSpace::default());
+    let env_type = basic_type_from_layout(env, layout_interner, env_layout).into_struct_type();
 
-                        global_elements.push(element_type.get_undef());
-                    }
-                };
-            }
+    env.context
+        .struct_type(&[i8_ptr_type.into(), env_type.into()], false)
+}
 
-            let const_elements = if is_all_constant {
-                global_elements.into_bump_slice()
-            } else {
-                &global_elements[zero_elements..]
-            };
+/// Allocates a `Box` and moves `value` into it.
+fn build_box<'a, 'ctx, 'env>(
+    env: &Env<'a, 'ctx, 'env>,
+    layout_interner: &mut STLayoutInterner<'a>,
+    layout: InLayout<'a>,
+    value: BasicValueEnum<'ctx>,
+) -> PointerValue<'ctx> {
+    let basic_type = basic_type_from_layout(env, layout_interner, layout);
+    let allocation = reserve_with_refcount_help(
+        env,
+        basic_type,
+        layout_interner.stack_size(layout),
+        layout_interner.alignment_bytes(layout),
+    );
 
-            // use None for the address space (e.g. Const does not work)
-            let typ = element_type.array_type(const_elements.len() as u32);
-            let global = env.module.add_global(typ, None, "roc__list_literal");
+    store_roc_value(env, layout_interner, layout, allocation, value);
 
-            global.set_constant(true);
-            global.set_alignment(alignment);
-            global.set_unnamed_addr(true);
-            global.set_linkage(inkwell::module::Linkage::Private);
+    allocation
+}
 
-            global.set_initializer(&element_type.const_array(const_elements));
-            global.as_pointer_value()
-        };
+/// `Box.map`: calls `fn_val` on the value in the box, and boxes the result.
+///
+/// The box is consumed, and the function gets to own the value. When the box is unique and
+/// the result fits in the same allocation, the result is written back into the box, which saves
+/// a deallocation and an allocation.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn build_box_map<'a, 'ctx, 'env>(
+    env: &Env<'a, 'ctx, 'env>,
+    layout_interner: &mut STLayoutInterner<'a>,
+    layout_ids: &mut LayoutIds<'a>,
+    box_ptr: PointerValue<'ctx>,
+    fn_val: FunctionValue<'ctx>,
+    closure_data: Option<BasicValueEnum<'ctx>>,
+    inner_layout: InLayout<'a>,
+    result_layout: InLayout<'a>,
+) -> PointerValue<'ctx> {
+    let builder = env.builder;
+    let context = env.context;
+    let parent = builder
+        .get_insert_block()
+        .and_then(|b| b.get_parent())
+        .unwrap();
 
-        if is_all_constant {
-            // all elements are constants, so we can use the memory in the constants section directly
-            // here we make a pointer to the first actual element (skipping the 0 bytes that
-            // represent the refcount)
-            let zero = env.ptr_int().const_zero();
-            let offset = env.ptr_int().const_int(zero_elements as _, false);
-
-            let ptr = unsafe {
-                env.builder.new_build_in_bounds_gep(
-                    element_type,
-                    global,
-                    &[zero, offset],
-                    "first_element_pointer",
-                )
-            };
+    let box_layout = layout_interner.insert(Layout::Boxed(inner_layout));
+    let refcount_ptr = PointerToRefcount::from_ptr_to_data(env, box_ptr);
+    let value = load_roc_value(
+        env,
+        layout_interner,
+        inner_layout,
+        box_ptr,
+        "load_boxed_value",
+    );
 
-            super::build_list::store_list(env, ptr, list_length_intval).into()
-        } else {
-            // some of our elements are non-constant, so we must allocate space on the heap
-            let ptr = allocate_list(env, layout_interner, element_layout, list_length_intval);
+    let call = |layout_interner: &mut STLayoutInterner<'a>| {
+        let mut arguments = Vec::with_capacity_in(2, env.arena);
+        arguments.push(value);
+        arguments.extend(closure_data);
 
-            // then, copy the relevant segment from the constant section into the heap
-            env.builder
-                .build_memcpy(
-                    ptr,
-                    alignment,
-                    global,
-                    alignment,
-                    env.ptr_int().const_int(size as _, false),
-                )
-                .unwrap();
+        call_roc_function(env, layout_interner, fn_val, result_layout, &arguments)
+    };
 
-            // then replace the `undef`s with the values that we evaluate at runtime
-            for (index, val) in runtime_evaluated_elements {
-                let index_val = ctx.i64_type().const_int(index as u64, false);
-                let elem_ptr = unsafe {
-                    builder.new_build_in_bounds_gep(element_type, ptr, &[index_val], "index")
-                };
+    let fits_in_place = layout_interner.stack_size(inner_layout)
+        == layout_interner.stack_size(result_layout)
+        && layout_interner.allocation_alignment_bytes(inner_layout)
+            == layout_interner.allocation_alignment_bytes(result_layout);
 
-                builder.build_store(elem_ptr, val);
-            }
+    let unique_block = context.append_basic_block(parent, "box_map_unique");
+    let shared_block = context.append_basic_block(parent, "box_map_shared");
+    let cont_block = context.append_basic_block(parent, "box_map_cont");
 
-            super::build_list::store_list(env, ptr, list_length_intval).into()
-        }
-    } else {
-        let ptr = allocate_list(env, layout_interner, element_layout, list_length_intval);
+    builder.build_conditional_branch(refcount_ptr.is_1(env), unique_block, shared_block);
 
-        // Copy the elements from the list literal into the array
-        for (index, element) in elems.iter().enumerate() {
-            let val = match element {
-                ListLiteralElement::Literal(literal) => {
-                    build_exp_literal(env, layout_interner, parent, element_layout, literal)
-                }
-                ListLiteralElement::Symbol(symbol) => load_symbol(scope, symbol),
-            };
-            let index_val = ctx.i64_type().const_int(index as u64, false);
-            let elem_ptr = unsafe {
-                builder.new_build_in_bounds_gep(element_type, ptr, &[index_val], "index")
-            };
+    // nobody else can see the value anymore, so it is moved into the function
+    builder.position_at_end(unique_block);
+    let result = call(layout_interner);
+    let unique_box = if fits_in_place {
+        let result_ptr_type = basic_type_from_layout(env, layout_interner, result_layout)
+            .ptr_type(AddressSpace::default());
+        let result_ptr = builder.build_pointer_cast(box_ptr, result_ptr_type, "box_map_in_place");
+        store_roc_value(env, layout_interner, result_layout, result_ptr, result);
 
-            store_roc_value(env, layout_interner, element_layout, elem_ptr, val);
-        }
+        result_ptr
+    } else {
+        let new_box = build_box(env, layout_interner, result_layout, result);
+        refcount_ptr.decrement(env, layout_interner, box_layout);
 
-        super::build_list::store_list(env, ptr, list_length_intval).into()
-    }
+        new_box
+    };
+    let unique_end = builder.get_insert_block().unwrap();
+    builder.build_unconditional_branch(cont_block);
+
+    // the box keeps its value, so the function needs its own reference to it
+    builder.position_at_end(shared_block);
+    increment_refcount_layout(env, layout_interner, layout_ids, 1, value, inner_layout);
+    let result = call(layout_interner);
+    let shared_box = build_box(env, layout_interner, result_layout, result);
+    refcount_ptr.decrement(env, layout_interner, box_layout);
+    let shared_end = builder.get_insert_block().unwrap();
+    builder.build_unconditional_branch(cont_block);
+
+    builder.position_at_end(cont_block);
+    let phi = builder.build_phi(shared_box.get_type(), "mapped_box");
+    phi.add_incoming(&[(&unique_box, unique_end), (&shared_box, shared_end)]);
+
+    phi.as_basic_value().into_pointer_value()
 }
 
-pub fn load_roc_value<'a, 'ctx, 'env>(
+/// Awaits a task: runs `task_val`, and passes what it produced to `continuation_fn`,
+/// followed by `continuation_env`, the continuation's captured environment of layout `env_layout`.
+///
+/// For now this is a synchronous trampoline: the task runs to completion in `run_task`,
+/// and the continuation is called right after. A version that suspends the task, e.g. by switching
+/// fibers, only has to change `run_task`, and resume with the same call to the continuation.
+/// It will need `env_layout` to keep the environment alive while the task is suspended.
+#[allow(clippy::too_many_arguments)]
+pub fn build_task_await<'a, 'ctx, 'env>(
     env: &Env<'a, 'ctx, 'env>,
     layout_interner: &mut STLayoutInterner<'a>,
-    layout: InLayout<'a>,
-    source: PointerValue<'ctx>,
-    name: &str,
+    task_val: FunctionValue<'ctx>,
+    task_layout: InLayout<'a>,
+    continuation_fn: FunctionValue<'ctx>,
+    continuation_env: Option<BasicValueEnum<'ctx>>,
+    env_layout: InLayout<'a>,
+    ret_layout: InLayout<'a>,
 ) -> BasicValueEnum<'ctx> {
-    let basic_type = basic_type_from_layout(env, layout_interner, layout);
+    debug_assert!(continuation_env.map_or(true, |captured| {
+        captured.get_type() == basic_type_from_layout(env, layout_interner, env_layout)
+    }));
 
-    if layout_interner.is_passed_by_reference(layout) {
-        let alloca = entry_block_alloca_zerofill(env, basic_type, name);
+    let task_result = run_task(env, layout_interner, task_val, task_layout);
 
-        store_roc_value(env, layout_interner, layout, alloca, source.into());
+    let mut arguments = Vec::with_capacity_in(2, env.arena);
+    arguments.push(task_result);
+    arguments.extend(continuation_env);
 
-        alloca.into()
-    } else {
-        env.builder.new_build_load(basic_type, source, name)
-    }
-}
+    call_roc_function(
+        env,
+        layout_interner,
+        continuation_fn,
+        ret_layout,
+        &arguments,
+    )
+}
 
-pub fn use_roc_value<'a, 'ctx, 'env>(
+/// Runs a task, which takes no arguments, to completion and returns what it produced.
+fn run_task<'a, 'ctx, 'env>(
     env: &Env<'a, 'ctx, 'env>,
     layout_interner: &mut STLayoutInterner<'a>,
+    task_val: FunctionValue<'ctx>,
+    task_layout: InLayout<'a>,
+) -> BasicValueEnum<'ctx> {
+    call_roc_function(env, layout_interner, task_val, task_layout, &[])
+}
+
+/// Emit an immutable, module-internal global holding `value`. The global is aligned for `layout`;
+/// `value` must be a constant of that layout, or an array of them.
+pub fn build_global_constant<'a, 'ctx, 'env>(
+    env: &Env<'a, 'ctx, 'env>,
+    layout_interner: &STLayoutInterner<'a>,
+    name: &str,
+    value: BasicValueEnum<'ctx>,
     layout: InLayout<'a>,
-    source: BasicValueEnum<'ctx>,
+) -> GlobalValue<'ctx> {
+    // use None for the address space (e.g. Const does not work)
+    let global = env.module.add_global(value.get_type(), None, name);
+
+    global.set_constant(true);
+    global.set_alignment(layout_interner.alignment_bytes(layout));
+    global.set_unnamed_addr(true);
+    global.set_linkage(Linkage::Internal);
+
+    global.set_initializer(&value);
+
+    global
+}
+
+/// Emit a mutable, module-internal global of `layout`, for state that outlives a call, like the
+/// state of a platform's FFI callbacks. It starts out as `initial_value`, which must be a
+/// constant, or as all zeroes. Access it with [`build_global_load`] and [`build_global_store`].
+pub fn build_global_mutable<'a, 'ctx, 'env>(
+    env: &Env<'a, 'ctx, 'env>,
+    layout_interner: &mut STLayoutInterner<'a>,
     name: &str,
+    initial_value: Option<BasicValueEnum<'ctx>>,
+    layout: InLayout<'a>,
+) -> GlobalValue<'ctx> {
+    let basic_type = basic_type_from_layout(env, layout_interner, layout);
+    let global = env.module.add_global(basic_type, None, name);
+
+    global.set_constant(false);
+    global.set_alignment(layout_interner.alignment_bytes(layout));
+    global.set_linkage(Linkage::Internal);
+
+    match initial_value {
+        Some(value) => global.set_initializer(&value),
+        None => global.set_initializer(&basic_type.const_zero()),
+    }
+
+    global
+}
+
+/// Reads a global from [`build_global_mutable`]. A value that fits in a register is read with an
+/// atomic load, so other threads see either the old or the new value; anything bigger is copied
+/// without synchronization.
+pub fn build_global_load<'a, 'ctx, 'env>(
+    env: &Env<'a, 'ctx, 'env>,
+    layout_interner: &mut STLayoutInterner<'a>,
+    global: GlobalValue<'ctx>,
+    layout: InLayout<'a>,
 ) -> BasicValueEnum<'ctx> {
-    if layout_interner.is_passed_by_reference(layout) {
-        let alloca = entry_block_alloca_zerofill(
+    let value = load_roc_value(
+        env,
+        layout_interner,
+        layout,
+        global.as_pointer_value(),
+        "load_global",
+    );
+
+    if is_atomically_accessible(env, layout_interner, layout) {
+        make_atomic(
+            value.as_instruction_value().unwrap(),
+            layout_interner.alignment_bytes(layout),
+        );
+    }
+
+    value
+}
+
+/// Writes a global from [`build_global_mutable`], atomically if it fits in a register.
+/// See [`build_global_load`].
+pub fn build_global_store<'a, 'ctx, 'env>(
+    env: &Env<'a, 'ctx, 'env>,
+    layout_interner: &mut STLayoutInterner<'a>,
+    global: GlobalValue<'ctx>,
+    layout: InLayout<'a>,
+    value: BasicValueEnum<'ctx>,
+) {
+    if is_atomically_accessible(env, layout_interner, layout) {
+        let store = env.builder.build_store(global.as_pointer_value(), value);
+
+        make_atomic(store, layout_interner.alignment_bytes(layout));
+    } else {
+        store_roc_value(
             env,
-            basic_type_from_layout(env, layout_interner, layout),
-            name,
+            layout_interner,
+            layout,
+            global.as_pointer_value(),
+            value,
         );
+    }
+}
 
-        env.builder.build_store(alloca, source);
+/// LLVM can only load and store integers of at least a byte, floats, and pointers atomically.
+fn is_atomically_accessible<'a, 'ctx, 'env>(
+    env: &Env<'a, 'ctx, 'env>,
+    layout_interner: &mut STLayoutInterner<'a>,
+    layout: InLayout<'a>,
+) -> bool {
+    let stack_size = layout_interner.stack_size(layout);
+    if layout_interner.is_passed_by_reference(layout)
+        || !stack_size.is_power_of_two()
+        || stack_size > env.target_info.ptr_width() as u32
+    {
+        return false;
+    }
+
+    match basic_type_from_layout(env, layout_interner, layout) {
+        BasicTypeEnum::IntType(int_type) => int_type.get_bit_width() >= 8,
+        BasicTypeEnum::FloatType(_) | BasicTypeEnum::PointerType(_) => true,
+        _ => false,
+    }
+}
+
+fn make_atomic(instruction: InstructionValue<'_>, alignment: u32) {
+    // Atomic accesses must be aligned to at least their size, which these values always are.
+    instruction.set_alignment(alignment).unwrap();
+    instruction
+        .set_atomic_ordering(AtomicOrdering::SequentiallyConsistent)
+        .unwrap();
+}
+
+fn list_literal<'a, 'ctx, 'env>(
+    env: &Env<'a, 'ctx, 'env>,
+    layout_interner: &mut STLayoutInterner<'a>,
+    parent: FunctionValue<'ctx>,
+    scope: &Scope<'a, 'ctx>,
+    element_layout: InLayout<'a>,
+    elems: &[ListLiteralElement],
+) -> BasicValueEnum<'ctx> {
+    let ctx = env.context;
+    let builder = env.builder;
+
+    let element_type = basic_type_from_layout(env, layout_interner, element_layout);
+
+    let list_length = elems.len();
+    let list_length_intval = env.ptr_int().const_int(list_length as _, false);
+
+    let is_scalar = match element_type {
+        BasicTypeEnum::IntType(int_type) => int_type.get_bit_width() % 8 == 0,
+        BasicTypeEnum::FloatType(_) => true,
+        _ => false,
+    };
+
+    let has_literals = elems
+        .iter()
+        .any(|element| matches!(element, ListLiteralElement::Literal(_)));
+
+    if is_scalar && has_literals {
+        // The literal elements are emitted into a global constant, and copied onto the heap in one
+        // go. We cannot hand out the global itself: morphic does not know about it, and could
+        // allow the list to be updated in-place.
+        let element_width = layout_interner.stack_size(element_layout);
+        let size = list_length * element_width as usize;
+        let element_alignment = layout_interner.alignment_bytes(element_layout);
+        let alignment = element_alignment.max(env.target_info.ptr_width() as u32);
+
+        // runtime-evaluated elements
+        let mut runtime_evaluated_elements = Vec::with_capacity_in(list_length, env.arena);
+
+        // any variables or expressions are represented as `undef` in the global
+        let mut global_elements = Vec::with_capacity_in(list_length, env.arena);
+
+        for (index, element) in elems.iter().enumerate() {
+            match element {
+                ListLiteralElement::Literal(literal) => {
+                    let val =
+                        build_exp_literal(env, layout_interner, parent, element_layout, literal);
+                    global_elements.push(val);
+                }
+                ListLiteralElement::Symbol(symbol) => {
+                    let val = load_symbol(scope, symbol);
+                    runtime_evaluated_elements.push((index, val));
+
+                    let undef = match element_type {
+                        BasicTypeEnum::IntType(int_type) => int_type.get_undef().into(),
+                        BasicTypeEnum::FloatType(float_type) => float_type.get_undef().into(),
+                        _ => unreachable!(),
+                    };
+                    global_elements.push(undef);
+                }
+            };
+        }
+
+        let const_array = match element_type {
+            BasicTypeEnum::IntType(int_type) => {
+                let elements = Vec::from_iter_in(
+                    global_elements.iter().map(|val| val.into_int_value()),
+                    env.arena,
+                );
+                int_type.const_array(&elements)
+            }
+            BasicTypeEnum::FloatType(float_type) => {
+                let elements = Vec::from_iter_in(
+                    global_elements.iter().map(|val| val.into_float_value()),
+                    env.arena,
+                );
+                float_type.const_array(&elements)
+            }
+            _ => unreachable!(),
+        };
+
+        let global = build_global_constant(
+            env,
+            layout_interner,
+            "roc__list_literal",
+            const_array.into(),
+            element_layout,
+        );
+
+        let ptr = allocate_list(env, layout_interner, element_layout, list_length_intval);
+
+        // copy the constant elements onto the heap
+        env.builder
+            .build_memcpy(
+                ptr,
+                alignment,
+                global.as_pointer_value(),
+                element_alignment,
+                env.ptr_int().const_int(size as _, false),
+            )
+            .unwrap();
+
+        // then replace the `undef`s with the values that we evaluate at runtime
+        for (index, val) in runtime_evaluated_elements {
+            let index_val = ctx.i64_type().const_int(index as u64, false);
+            let elem_ptr = unsafe {
+                builder.new_build_in_bounds_gep(element_type, ptr, &[index_val], "index")
+            };
+
+            builder.build_store(elem_ptr, val);
+        }
+
+        super::build_list::store_list(env, ptr, list_length_intval).into()
+    } else {
+        let ptr = allocate_list(env, layout_interner, element_layout, list_length_intval);
+
+        // Copy the elements from the list literal into the array
+        for (index, element) in elems.iter().enumerate() {
+            let val = match element {
+                ListLiteralElement::Literal(literal) => {
+                    build_exp_literal(env, layout_interner, parent, element_layout, literal)
+                }
+                ListLiteralElement::Symbol(symbol) => load_symbol(scope, symbol),
+            };
+            let index_val = ctx.i64_type().const_int(index as u64, false);
+            let elem_ptr = unsafe {
+                builder.new_build_in_bounds_gep(element_type, ptr, &[index_val], "index")
+            };
+
+            store_roc_value(env, layout_interner, element_layout, elem_ptr, val);
+        }
+
+        super::build_list::store_list(env, ptr, list_length_intval).into()
+    }
+}
+
+pub fn load_roc_value<'a, 'ctx, 'env>(
+    env: &Env<'a, 'ctx, 'env>,
+    layout_interner: &mut STLayoutInterner<'a>,
+    layout: InLayout<'a>,
+    source: PointerValue<'ctx>,
+    name: &str,
+) -> BasicValueEnum<'ctx> {
+    let basic_type = basic_type_from_layout(env, layout_interner, layout);
+
+    if layout_interner.is_passed_by_reference(layout) {
+        let alloca = entry_block_alloca_zerofill(env, basic_type, name);
+
+        store_roc_value(env, layout_interner, layout, alloca, source.into());
 
         alloca.into()
     } else {
-        source
+        env.builder.new_build_load(basic_type, source, name)
     }
 }
 
-pub fn store_roc_value_opaque<'a, 'ctx, 'env>(
+pub fn use_roc_value<'a, 'ctx, 'env>(
     env: &Env<'a, 'ctx, 'env>,
     layout_interner: &mut STLayoutInterner<'a>,
     layout: InLayout<'a>,
-    opaque_destination: PointerValue<'ctx>,
-    value: BasicValueEnum<'ctx>,
-) {
-    let target_type =
-        basic_type_from_layout(env, layout_interner, layout).ptr_type(AddressSpace::default());
-    let destination =
-        env.builder
-            .build_pointer_cast(opaque_destination, target_type, "store_roc_value_opaque");
+    source: BasicValueEnum<'ctx>,
+    name: &str,
+) -> BasicValueEnum<'ctx> {
+    if layout_interner.is_passed_by_reference(layout) {
+        let basic_type = basic_type_from_layout(env, layout_interner, layout);
+        let alloca = entry_block_alloca_zerofill(env, basic_type, name);
 
-    store_roc_value(env, layout_interner, layout, destination, value)
+        // a store of the value leaves its padding as it was
+        if env.zeroes_padding(layout_interner, &layout_interner.get(layout)) {
+            build_zero_fill(env, alloca, basic_type);
+        }
+        env.builder.build_store(alloca, source);
+
+        alloca.into()
+    } else {
+        source
+    }
 }
 
 pub fn store_roc_value<'a, 'ctx, 'env>(
@@ -3011,8 +3442,14 @@ pub fn build_exp_stmt<'a, 'ctx, 'env>(
             )
         }
 
-        Crash(sym, tag) => {
-            throw_exception(env, scope, sym, *tag);
+        Crash(sym, tag, region) => {
+            match region.and_then(|region| crash_source_info(env, region)) {
+                Some(info) => {
+                    let msg_val = load_symbol(scope, sym);
+                    build_crash_with_source_info(env, msg_val, *tag, info);
+                }
+                None => throw_exception(env, scope, sym, *tag),
+            }
 
             // unused value (must return a BasicValue)
             let zero = env.context.i64_type().const_zero();
@@ -3603,6 +4040,8 @@ fn expose_function_to_host_help_c_abi_generic<'a, 'ctx, 'env>(
         Linkage::External,
     );
 
+    env.register_host_exposed(c_function_name, arguments, return_layout, HostCallConv::C);
+
     let subprogram = env.new_subprogram(c_function_name);
     c_function.set_subprogram(subprogram);
 
@@ -3745,6 +4184,8 @@ fn expose_function_to_host_help_c_abi_gen_test<'a, 'ctx, 'env>(
         Linkage::External,
     );
 
+    env.register_host_exposed(c_function_name, arguments, return_layout, HostCallConv::C);
+
     let subprogram = env.new_subprogram(c_function_name);
     c_function.set_subprogram(subprogram);
 
@@ -3851,6 +4292,8 @@ fn expose_function_to_host_help_c_abi_gen_test<'a, 'ctx, 'env>(
         Linkage::External,
     );
 
+    env.register_host_exposed(&size_function_name, &[], Layout::U64, HostCallConv::C);
+
     let subprogram = env.new_subprogram(&size_function_name);
     size_function.set_subprogram(subprogram);
 
@@ -3894,6 +4337,8 @@ fn expose_function_to_host_help_c_abi_v2<'a, 'ctx, 'env>(
         Linkage::External,
     );
 
+    env.register_host_exposed(c_function_name, arguments, return_layout, HostCallConv::C);
+
     let c_abi_roc_str_type = env.context.struct_type(
         &[
             env.context
@@ -3972,7 +4417,7 @@ fn expose_function_to_host_help_c_abi_v2<'a, 'ctx, 'env>(
                 &param_types[..param_types.len().saturating_sub(1)],
             )
         }
-        (RocReturn::Return | RocReturn::ByPointer, CCReturn::Void) => {
+        (RocReturn::ByPointer, CCReturn::Void) => {
             // the roc function returns a unit value. like `{}` or `{ { {}, {} }, {} }`.
             // In C, this is modelled as a function returning void
             (
@@ -3980,6 +4425,11 @@ fn expose_function_to_host_help_c_abi_v2<'a, 'ctx, 'env>(
                 &param_types[..param_types.len().saturating_sub(1)],
             )
         }
+        (RocReturn::Return, CCReturn::Void) => {
+            // the unit value is returned directly, so the roc function has no return pointer to
+            // drop; every one of its parameters is an argument of the C function
+            (&params[..], &param_types[..])
+        }
         _ => (&params[..], &param_types[..]),
     };
 
@@ -4158,6 +4608,8 @@ fn expose_function_to_host_help_c_abi<'a, 'ctx, 'env>(
         Linkage::External,
     );
 
+    env.register_host_exposed(&size_function_name, &[], Layout::U64, HostCallConv::C);
+
     let subprogram = env.new_subprogram(&size_function_name);
     size_function.set_subprogram(subprogram);
 
@@ -4604,7 +5056,6 @@ pub fn build_proc_headers<'a, 'r, 'ctx, 'env>(
 pub fn build_procedures<'a, 'ctx, 'env>(
     env: &Env<'a, 'ctx, 'env>,
     layout_interner: &mut STLayoutInterner<'a>,
-    opt_level: OptLevel,
     procedures: MutMap<(Symbol, ProcLayout<'a>), roc_mono::ir::Proc<'a>>,
     entry_point: EntryPoint<'a>,
     debug_output_file: Option<&Path>,
@@ -4612,67 +5063,71 @@ pub fn build_procedures<'a, 'ctx, 'env>(
     build_procedures_help(
         env,
         layout_interner,
-        opt_level,
         procedures,
         entry_point,
         debug_output_file,
     );
+
+    apply_red_zone_option(env);
 }
 
 pub fn build_wasm_test_wrapper<'a, 'ctx, 'env>(
     env: &Env<'a, 'ctx, 'env>,
     layout_interner: &mut STLayoutInterner<'a>,
-    opt_level: OptLevel,
     procedures: MutMap<(Symbol, ProcLayout<'a>), roc_mono::ir::Proc<'a>>,
     entry_point: SingleEntryPoint<'a>,
 ) -> (&'static str, FunctionValue<'ctx>) {
     let mod_solutions = build_procedures_help(
         env,
         layout_interner,
-        opt_level,
         procedures,
         EntryPoint::Single(entry_point),
         Some(&std::env::temp_dir().join("test.ll")),
     );
 
-    promote_to_wasm_test_wrapper(
+    let wrapper = promote_to_wasm_test_wrapper(
         env,
         layout_interner,
         mod_solutions,
         entry_point.symbol,
         entry_point.layout,
-    )
+    );
+
+    apply_red_zone_option(env);
+
+    wrapper
 }
 
 pub fn build_procedures_return_main<'a, 'ctx, 'env>(
     env: &Env<'a, 'ctx, 'env>,
     layout_interner: &mut STLayoutInterner<'a>,
-    opt_level: OptLevel,
     procedures: MutMap<(Symbol, ProcLayout<'a>), roc_mono::ir::Proc<'a>>,
     entry_point: SingleEntryPoint<'a>,
 ) -> (&'static str, FunctionValue<'ctx>) {
     let mod_solutions = build_procedures_help(
         env,
         layout_interner,
-        opt_level,
         procedures,
         EntryPoint::Single(entry_point),
         Some(&std::env::temp_dir().join("test.ll")),
     );
 
-    promote_to_main_function(
+    let main = promote_to_main_function(
         env,
         layout_interner,
         mod_solutions,
         entry_point.symbol,
         entry_point.layout,
-    )
+    );
+
+    apply_red_zone_option(env);
+
+    main
 }
 
 pub fn build_procedures_expose_expects<'a, 'ctx, 'env>(
     env: &Env<'a, 'ctx, 'env>,
     layout_interner: &mut STLayoutInterner<'a>,
-    opt_level: OptLevel,
     expects: &'a [Symbol],
     procedures: MutMap<(Symbol, ProcLayout<'a>), roc_mono::ir::Proc<'a>>,
 ) -> Vec<'a, &'a str> {
@@ -4681,7 +5136,6 @@ pub fn build_procedures_expose_expects<'a, 'ctx, 'env>(
     let mod_solutions = build_procedures_help(
         env,
         layout_interner,
-        opt_level,
         procedures,
         entry_point,
         Some(&std::env::temp_dir().join("test.ll")),
@@ -4737,13 +5191,126 @@ pub fn build_procedures_expose_expects<'a, 'ctx, 'env>(
         );
     }
 
+    apply_red_zone_option(env);
+
     expect_names
 }
 
+/// Builds `procedures` for an object that is linked with one from the dev backend, to provide
+/// the procedures in `foreign` that the dev backend could not build. Each of those gets a
+/// wrapper with the C calling convention, under the name the dev backend calls it by.
+/// Every other procedure stays internal, so nothing clashes with what the dev backend built.
+pub fn build_procedures_for_dev_backend<'a, 'ctx, 'env>(
+    env: &Env<'a, 'ctx, 'env>,
+    layout_interner: &mut STLayoutInterner<'a>,
+    procedures: MutMap<(Symbol, ProcLayout<'a>), roc_mono::ir::Proc<'a>>,
+    foreign: &[(Symbol, ProcLayout<'a>, String)],
+) {
+    // Without optimizations, alias analysis gives every procedure a single specialization,
+    // whether or not an entry point reaches it.
+    debug_assert!(matches!(
+        env.options.opt_level,
+        OptLevel::Development | OptLevel::Normal
+    ));
+
+    let mod_solutions = build_procedures_help(
+        env,
+        layout_interner,
+        procedures,
+        EntryPoint::Expects { symbols: &[] },
+        None,
+    );
+
+    for (symbol, layout, name) in foreign {
+        let it = layout.arguments.iter().copied();
+        let bytes =
+            roc_alias_analysis::func_name_bytes_help(*symbol, it, layout.niche, layout.result);
+        let func_solutions = mod_solutions.func_solutions(FuncName(&bytes)).unwrap();
+
+        let func_spec = match func_solutions.specs().next() {
+            Some(spec) => *spec,
+            None => internal_error!("no specialization for {:?}", symbol),
+        };
+        let roc_function = function_value_by_func_spec(
+            env,
+            func_spec,
+            *symbol,
+            layout.arguments,
+            layout.niche,
+            layout.result,
+        );
+
+        expose_function_to_dev_backend(env, layout_interner, roc_function, *layout, name);
+    }
+
+    // What the wrappers do not call can go, now that it is all internal.
+    let pass_manager: PassManager<Module> = PassManager::create(());
+    pass_manager.add_global_dce_pass();
+    pass_manager.run_on(env.module);
+
+    apply_red_zone_option(env);
+}
+
+/// Keeps every function the module defines from using the stack below the stack pointer, when
+/// the target disables the red zone, so that the code agrees with what the dev backend builds.
+fn apply_red_zone_option<'a, 'ctx, 'env>(env: &Env<'a, 'ctx, 'env>) {
+    if !env.target_info.disable_red_zone {
+        return;
+    }
+
+    let kind_id = Attribute::get_named_enum_kind_id("noredzone");
+    debug_assert!(kind_id > 0);
+    let noredzone = env.context.create_enum_attribute(kind_id, 0);
+
+    for function in env.module.get_functions() {
+        if function.count_basic_blocks() > 0 {
+            function.add_attribute(AttributeLoc::Function, noredzone);
+        }
+    }
+}
+
+/// A wrapper that takes the arguments and returns the result like the dev backend does for
+/// calls between procedures, which is the C calling convention of the target.
+fn expose_function_to_dev_backend<'a, 'ctx, 'env>(
+    env: &Env<'a, 'ctx, 'env>,
+    layout_interner: &mut STLayoutInterner<'a>,
+    roc_function: FunctionValue<'ctx>,
+    layout: ProcLayout<'a>,
+    name: &str,
+) {
+    let mut argument_types = Vec::with_capacity_in(layout.arguments.len(), env.arena);
+    for argument in layout.arguments.iter() {
+        argument_types.push(argument_type_from_layout(env, layout_interner, *argument));
+    }
+    let return_type = basic_type_from_layout(env, layout_interner, layout.result);
+
+    let fn_spec = FunctionSpec::cconv(env, CCReturn::Return, Some(return_type), &argument_types);
+    let wrapper = add_func(env.context, env.module, name, fn_spec, Linkage::External);
+
+    let subprogram = env.new_subprogram(name);
+    wrapper.set_subprogram(subprogram);
+
+    let builder = env.builder;
+    let entry = env.context.append_basic_block(wrapper, "entry");
+    builder.position_at_end(entry);
+
+    debug_info_init!(env, wrapper);
+
+    let arguments: std::vec::Vec<_> = wrapper.get_param_iter().collect();
+    let result = call_roc_function(
+        env,
+        layout_interner,
+        roc_function,
+        layout.result,
+        &arguments,
+    );
+
+    builder.build_return(Some(&result));
+}
+
 fn build_procedures_help<'a, 'ctx, 'env>(
     env: &Env<'a, 'ctx, 'env>,
     layout_interner: &mut STLayoutInterner<'a>,
-    opt_level: OptLevel,
     procedures: MutMap<(Symbol, ProcLayout<'a>), roc_mono::ir::Proc<'a>>,
     entry_point: EntryPoint<'a>,
     debug_output_file: Option<&Path>,
@@ -4756,7 +5323,7 @@ fn build_procedures_help<'a, 'ctx, 'env>(
     let solutions = match roc_alias_analysis::spec_program(
         env.arena,
         layout_interner,
-        opt_level,
+        env.options.opt_level,
         entry_point,
         it,
     ) {
@@ -4782,7 +5349,7 @@ fn build_procedures_help<'a, 'ctx, 'env>(
         &mut layout_ids,
     );
 
-    let (_, function_pass) = construct_optimization_passes(env.module, opt_level);
+    let (_, function_pass) = construct_optimization_passes(env.module, env.options.opt_level);
 
     for (proc, fn_vals) in headers {
         for (func_spec_solutions, fn_val) in fn_vals {
@@ -4901,6 +5468,19 @@ fn build_proc_header<'a, 'ctx, 'env>(
     let subprogram = env.new_subprogram(&fn_name);
     fn_val.set_subprogram(subprogram);
 
+    let arg_layouts: std::vec::Vec<String> = args
+        .iter()
+        .map(|(layout, _)| layout_interner.dbg(*layout))
+        .collect();
+    let origin = format!(
+        "the proc `{}.{}` : ({}) -> {}",
+        symbol.module_string(&env.interns),
+        symbol.as_str(&env.interns),
+        arg_layouts.join(", "),
+        layout_interner.dbg(proc.ret_layout),
+    );
+    record_fn_origin(env, &fn_name, origin);
+
     if env.exposed_to_host.contains(&symbol) {
         let arguments = Vec::from_iter_in(proc.args.iter().map(|(layout, _)| *layout), env.arena);
         expose_function_to_host(
@@ -5012,7 +5592,7 @@ fn expose_alias_to_host<'a, 'ctx, 'env>(
                 &fn_name,
                 alias_symbol,
                 Some("result"),
-                result_type,
+                result_type.size_of().unwrap(),
             );
         }
     }
@@ -5074,6 +5654,20 @@ fn build_closure_caller<'a, 'ctx, 'env>(
         Linkage::External,
     );
 
+    let exposed_arguments = Vec::from_iter_in(
+        arguments
+            .iter()
+            .copied()
+            .chain(std::iter::once(lambda_set.runtime_representation())),
+        env.arena,
+    );
+    env.register_host_exposed(
+        &function_name,
+        &exposed_arguments,
+        return_layout,
+        HostCallConv::C,
+    );
+
     // STEP 2: build function body
 
     let entry = context.append_basic_block(function_value, "entry");
@@ -5142,32 +5736,23 @@ fn build_closure_caller<'a, 'ctx, 'env>(
     builder.build_return(None);
 
     // STEP 3: build a {} -> u64 function that gives the size of the return type
-    build_host_exposed_alias_size_help(env, def_name, alias_symbol, Some("result"), result_type);
-
-    // STEP 4: build a {} -> u64 function that gives the size of the closure
-    build_host_exposed_alias_size(
+    build_host_exposed_alias_size_help(
         env,
-        layout_interner,
         def_name,
         alias_symbol,
-        lambda_set.runtime_representation(),
+        Some("result"),
+        result_type.size_of().unwrap(),
     );
-}
 
-fn build_host_exposed_alias_size<'a, 'r, 'ctx, 'env>(
-    env: &'r Env<'a, 'ctx, 'env>,
-    layout_interner: &'r mut STLayoutInterner<'a>,
-    def_name: &str,
-    alias_symbol: Symbol,
-    layout: InLayout<'a>,
-) {
+    // STEP 4: build a {} -> u64 function that gives the size of the closure
+    let closure_size = closure_env_size_bytes(layout_interner, &lambda_set);
     build_host_exposed_alias_size_help(
         env,
         def_name,
         alias_symbol,
         None,
-        basic_type_from_layout(env, layout_interner, layout),
-    )
+        env.context.i64_type().const_int(closure_size as u64, false),
+    );
 }
 
 fn build_host_exposed_alias_size_help<'a, 'ctx, 'env>(
@@ -5175,7 +5760,7 @@ fn build_host_exposed_alias_size_help<'a, 'ctx, 'env>(
     def_name: &str,
     alias_symbol: Symbol,
     opt_label: Option<&str>,
-    basic_type: BasicTypeEnum<'ctx>,
+    size: IntValue<'ctx>,
 ) {
     let builder = env.builder;
     let context = env.context;
@@ -5207,11 +5792,12 @@ fn build_host_exposed_alias_size_help<'a, 'ctx, 'env>(
         Linkage::External,
     );
 
+    env.register_host_exposed(&size_function_name, &[], Layout::U64, HostCallConv::C);
+
     let entry = context.append_basic_block(size_function, "entry");
 
     builder.position_at_end(entry);
 
-    let size: BasicValueEnum = basic_type.size_of().unwrap().into();
     builder.build_return(Some(&size));
 }
 
@@ -5482,6 +6068,9 @@ pub const C_CALL_CONV: u32 = 0;
 pub const FAST_CALL_CONV: u32 = 8;
 pub const COLD_CALL_CONV: u32 = 9;
 
+/// The named metadata with the producer of a module, as `roc_target::producer` has it
+pub const PRODUCER_METADATA: &str = "roc.producer";
+
 pub struct RocFunctionCall<'ctx> {
     pub caller: PointerValue<'ctx>,
     pub data: PointerValue<'ctx>,
@@ -5979,13 +6568,12 @@ fn define_global_str_literal_ptr<'a, 'ctx, 'env>(
     );
 
     // a pointer to the first actual data (skipping over the refcount)
+    let padding = allocation_padding(env.target_info.ptr_width(), 1);
     let ptr = unsafe {
         env.builder.new_build_in_bounds_gep(
             env.context.i8_type(),
             ptr,
-            &[env
-                .ptr_int()
-                .const_int(env.target_info.ptr_width() as u64, false)],
+            &[env.ptr_int().const_int(padding as u64, false)],
             "get_rc_ptr",
         )
     };
@@ -6001,10 +6589,10 @@ fn define_global_str_literal<'a, 'ctx, 'env>(
 
     // hash the name so we don't re-define existing messages
     let name = {
-        use std::collections::hash_map::DefaultHasher;
+        use roc_collections::all::FnvHasher;
         use std::hash::{Hash, Hasher};
 
-        let mut hasher = DefaultHasher::new();
+        let mut hasher = FnvHasher::default();
         message.hash(&mut hasher);
         let hash = hasher.finish();
 
@@ -6015,12 +6603,23 @@ fn define_global_str_literal<'a, 'ctx, 'env>(
         Some(current) => current,
 
         None => {
-            let size = message.bytes().len() + env.target_info.ptr_width() as usize;
+            let ptr_width = env.target_info.ptr_width();
+            let padding = allocation_padding(ptr_width, 1) as usize;
+            let size = message.bytes().len() + padding;
             let mut bytes = Vec::with_capacity_in(size, env.arena);
 
-            // insert NULL bytes for the refcount
-            for _ in 0..env.target_info.ptr_width() as usize {
-                bytes.push(env.context.i8_type().const_zero());
+            // the refcount of a constant, which takes up all of the padding
+            let refcount = match env.target_info.endianness() {
+                Endianness::Little => constant_refcount_le_bytes(ptr_width).to_vec(),
+                Endianness::Big => constant_refcount_le_bytes(ptr_width)
+                    .iter()
+                    .rev()
+                    .copied()
+                    .collect(),
+            };
+            debug_assert_eq!(refcount.len(), padding);
+            for b in refcount {
+                bytes.push(env.context.i8_type().const_int(b as u64, false));
             }
 
             // then add the data bytes
@@ -6035,7 +6634,7 @@ fn define_global_str_literal<'a, 'ctx, 'env>(
             global.set_initializer(&env.context.i8_type().const_array(bytes.into_bump_slice()));
 
             // mimic the `global_string` function; we cannot use it directly because it assumes
-            // strings are NULL-terminated, which means we can't store the refcount (which is 8
+            // strings are NULL-terminated, which means we can't store the refcount (which is
             // NULL bytes)
             global.set_constant(true);
             global.set_alignment(env.target_info.ptr_width() as u32);
@@ -6047,6 +6646,40 @@ fn define_global_str_literal<'a, 'ctx, 'env>(
     }
 }
 
+/// Builds a `Str` that is always backed by a (big) global string literal, even when it would fit
+/// in a small string. That way e.g. every check that panics with the same message shares one
+/// global, instead of each call site materializing its own small string.
+fn build_global_str_literal<'a, 'ctx, 'env>(
+    env: &Env<'a, 'ctx, 'env>,
+    parent: FunctionValue<'ctx>,
+    message: &str,
+) -> BasicValueEnum<'ctx> {
+    let ptr = define_global_str_literal_ptr(env, message);
+    let number_of_elements = env.ptr_int().const_int(message.len() as u64, false);
+
+    let alloca = const_str_alloca_ptr(env, parent, ptr, number_of_elements, number_of_elements);
+
+    match env.target_info.ptr_width() {
+        PtrWidth::Bytes4 => env
+            .builder
+            .new_build_load(zig_str_type(env), alloca, "load_panic_msg"),
+        PtrWidth::Bytes8 => alloca.into(),
+    }
+}
+
+/// The implementation of `Inspect.typeName`: a `Str` with the canonical name of the layout,
+/// backed by a global constant.
+pub fn build_type_name<'a, 'ctx, 'env>(
+    env: &Env<'a, 'ctx, 'env>,
+    layout_interner: &STLayoutInterner<'a>,
+    parent: FunctionValue<'ctx>,
+    layout: InLayout<'a>,
+) -> BasicValueEnum<'ctx> {
+    let type_name = layout_to_type_name(layout_interner, layout_interner.get(layout));
+
+    build_global_str_literal(env, parent, &type_name)
+}
+
 pub(crate) fn throw_internal_exception<'a, 'ctx, 'env>(
     env: &Env<'a, 'ctx, 'env>,
     parent: FunctionValue<'ctx>,
@@ -6054,9 +6687,9 @@ pub(crate) fn throw_internal_exception<'a, 'ctx, 'env>(
 ) {
     let builder = env.builder;
 
-    let str = build_string_literal(env, parent, message);
+    let str = build_global_str_literal(env, parent, message);
 
-    env.call_panic(env, str, CrashTag::Roc);
+    env.call_panic(env, str, CrashTag::Roc, None);
 
     builder.build_unreachable();
 }
@@ -6069,7 +6702,121 @@ pub(crate) fn throw_exception<'a, 'ctx, 'env>(
 ) {
     let msg_val = load_symbol(scope, message);
 
-    env.call_panic(env, msg_val, tag);
+    env.call_panic(env, msg_val, tag, None);
+
+    env.builder.build_unreachable();
+}
+
+/// Where a crash is in the source of the program, which the host gets along with the message.
+/// The layout matches `SourceInfo` in the builtins' `panic.zig`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SourceInfo<'a> {
+    pub file: &'a str,
+    /// 1-based
+    pub line: u32,
+    /// 1-based
+    pub column: u32,
+}
+
+/// The `SourceInfo` of a crash at `region`, if the source of its module is in `env.sources`
+pub fn crash_source_info<'a>(env: &Env<'a, '_, '_>, region: CrashRegion) -> Option<SourceInfo<'a>> {
+    let (path, src) = env.sources.get(&region.module)?;
+    let position = roc_region::all::LineInfo::new(src).convert_pos(region.region.start());
+
+    Some(SourceInfo {
+        file: env.arena.alloc_str(&path.to_string_lossy()),
+        line: position.line + 1,
+        column: position.column + 1,
+    })
+}
+
+/// The LLVM type of `SourceInfo`: the file as a C string, then the line and column
+pub(crate) fn source_info_type<'ctx>(env: &Env<'_, 'ctx, '_>) -> StructType<'ctx> {
+    let i32_type = env.context.i32_type();
+
+    env.context.struct_type(
+        &[
+            env.context
+                .i8_type()
+                .ptr_type(AddressSpace::default())
+                .into(),
+            i32_type.into(),
+            i32_type.into(),
+        ],
+        false,
+    )
+}
+
+/// A constant global with the `SourceInfo` of a crash. Crashes at the same place share one.
+pub fn build_source_info_global<'a, 'ctx, 'env>(
+    env: &Env<'a, 'ctx, 'env>,
+    info: SourceInfo<'_>,
+) -> PointerValue<'ctx> {
+    let module = env.module;
+    let i8_type = env.context.i8_type();
+    let i32_type = env.context.i32_type();
+
+    let hash = {
+        use roc_collections::all::FnvHasher;
+        use std::hash::{Hash, Hasher};
+
+        let mut hasher = FnvHasher::default();
+        info.hash(&mut hasher);
+        hasher.finish()
+    };
+    let name = format!("_source_info_{}", hash);
+
+    let global = match module.get_global(&name) {
+        Some(current) => current,
+        None => {
+            // the file name is a NUL-terminated C string
+            let mut bytes = Vec::with_capacity_in(info.file.len() + 1, env.arena);
+            for b in info.file.bytes().chain([0]) {
+                bytes.push(i8_type.const_int(b as u64, false));
+            }
+
+            let file_type = i8_type.array_type(bytes.len() as u32);
+            let file = module.add_global(file_type, None, &format!("_source_file_{}", hash));
+            file.set_initializer(&i8_type.const_array(bytes.into_bump_slice()));
+            file.set_constant(true);
+            file.set_unnamed_addr(true);
+            file.set_linkage(Linkage::Private);
+
+            let file_ptr = file
+                .as_pointer_value()
+                .const_cast(i8_type.ptr_type(AddressSpace::default()));
+
+            let info_type = source_info_type(env);
+            let value = info_type.const_named_struct(&[
+                file_ptr.into(),
+                i32_type.const_int(info.line as u64, false).into(),
+                i32_type.const_int(info.column as u64, false).into(),
+            ]);
+
+            let global = module.add_global(info_type, None, &name);
+            global.set_initializer(&value);
+            global.set_constant(true);
+            global.set_unnamed_addr(true);
+            global.set_linkage(Linkage::Private);
+
+            global
+        }
+    };
+
+    global.as_pointer_value()
+}
+
+/// Like a crash with `message`, but the host also learns where in the source the crash is,
+/// so it can report e.g. `Roc panicked at Main.roc:12:5`.
+pub fn build_crash_with_source_info<'a, 'ctx, 'env>(
+    env: &Env<'a, 'ctx, 'env>,
+    message: BasicValueEnum<'ctx>,
+    tag: CrashTag,
+    info: SourceInfo<'_>,
+) {
+    let source = build_source_info_global(env, info);
+
+    env.call_panic(env, message, tag, Some(source));
 
     env.builder.build_unreachable();
 }
@@ -6119,3 +6866,513 @@ pub fn add_func<'ctx>(
 
     fn_val
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bumpalo::Bump;
+    use inkwell::values::InstructionOpcode;
+
+    /// Inlines and simplifies everything, so that a value that goes through memory and back
+    /// becomes a constant
+    fn optimize(module: &Module<'_>) {
+        let pass_manager: PassManager<Module> = PassManager::create(());
+        pass_manager.add_function_inlining_pass();
+        pass_manager.add_promote_memory_to_register_pass();
+        pass_manager.add_instruction_combining_pass();
+        pass_manager.add_gvn_pass();
+        pass_manager.add_instruction_combining_pass();
+        pass_manager.add_cfg_simplification_pass();
+        pass_manager.run_on(module);
+    }
+
+    /// The constant that `function` returns, if it is one
+    fn returned_int(function: FunctionValue<'_>) -> Option<i64> {
+        let ret = function.get_last_basic_block()?.get_terminator()?;
+
+        ret.get_operand(0)?
+            .left()?
+            .into_int_value()
+            .get_sign_extended_constant()
+    }
+
+    /// An internal function of `fn_type`, with the builder at its start
+    fn add_test_function<'ctx>(
+        env: &Env<'_, 'ctx, '_>,
+        name: &str,
+        fn_type: FunctionType<'ctx>,
+    ) -> FunctionValue<'ctx> {
+        let function = env
+            .module
+            .add_function(name, fn_type, Some(Linkage::Internal));
+        env.builder
+            .position_at_end(env.context.append_basic_block(function, "entry"));
+
+        function
+    }
+
+    #[test]
+    fn an_effect_resumes_with_the_values_it_saved() {
+        let arena = Bump::new();
+        let context = Context::create();
+        let triple = Triple::host();
+        let module: &Module = arena.alloc(module_from_builtins(&triple, &context, "test"));
+        let builder = context.create_builder();
+        let debug_info = Env::new_debug_info(module, &triple);
+        let env = Env::for_tests(&arena, &context, module, &builder, &debug_info);
+        let mut layout_interner = STLayoutInterner::with_capacity(4, TargetInfo::default_x86_64());
+
+        // the zero-sized value has no field in the environment, but still comes back
+        let env_layout = layout_interner.insert(Layout::struct_no_name_order(arena.alloc([
+            Layout::I64,
+            Layout::UNIT,
+            Layout::F64,
+        ])));
+        let i64_type = context.i64_type();
+        let f64_type = context.f64_type();
+        let i8_ptr_type = context.i8_type().ptr_type(AddressSpace::default());
+        let unit = basic_type_from_layout(&env, &mut layout_interner, Layout::UNIT).const_zero();
+
+        // i64 resume(i8* continuation), which adds up the integer and the float it gets back
+        let resume_fn = add_test_function(
+            &env,
+            "resume",
+            i64_type.fn_type(&[i8_ptr_type.into()], false),
+        );
+        let values = load_effect_resume_env(
+            &env,
+            &mut layout_interner,
+            resume_fn.get_nth_param(0).unwrap().into_pointer_value(),
+            env_layout,
+        );
+        assert_eq!(values.len(), 3);
+        assert_eq!(values[1], unit);
+        let float_as_int =
+            builder.build_float_to_signed_int(values[2].into_float_value(), i64_type, "to_int");
+        let sum = builder.build_int_add(values[0].into_int_value(), float_as_int, "sum");
+        builder.build_return(Some(&sum));
+
+        // i8* suspend(i64, f64), which suspends right away
+        let suspend_fn = add_test_function(
+            &env,
+            "suspend",
+            i8_ptr_type.fn_type(&[i64_type.into(), f64_type.into()], false),
+        );
+        let live_values = [
+            suspend_fn.get_nth_param(0).unwrap(),
+            unit,
+            suspend_fn.get_nth_param(1).unwrap(),
+        ];
+        build_effect_resume_point(
+            &env,
+            &mut layout_interner,
+            resume_fn,
+            env_layout,
+            &live_values,
+        );
+
+        // the scheduler: suspend, then resume with the function in the continuation
+        let main_fn = add_test_function(&env, "main", i64_type.fn_type(&[], false));
+        let continuation = builder
+            .build_call(
+                suspend_fn,
+                &[
+                    i64_type.const_int(41, false).into(),
+                    f64_type.const_float(2.5).into(),
+                ],
+                "suspend",
+            )
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_pointer_value();
+        let resumed = builder
+            .build_call(resume_fn, &[continuation.into()], "resume")
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_int_value();
+
+        let continuation_type = effect_continuation_type(&env, &mut layout_interner, env_layout);
+        let continuation = builder.build_pointer_cast(
+            continuation,
+            continuation_type.ptr_type(AddressSpace::default()),
+            "to_continuation",
+        );
+        let stored_fn_ptr = builder
+            .new_build_struct_gep(continuation_type, continuation, 0, "resume_fn_ptr")
+            .unwrap();
+        let stored_fn = builder
+            .new_build_load(i8_ptr_type, stored_fn_ptr, "resume_fn")
+            .into_pointer_value();
+        let expected_fn = builder.build_pointer_cast(
+            resume_fn.as_global_value().as_pointer_value(),
+            i8_ptr_type,
+            "expected_fn",
+        );
+        let is_resume_fn = builder.build_int_compare(
+            IntPredicate::EQ,
+            builder.build_ptr_to_int(stored_fn, i64_type, "stored"),
+            builder.build_ptr_to_int(expected_fn, i64_type, "expected"),
+            "is_resume_fn",
+        );
+        let result =
+            builder.build_select(is_resume_fn, resumed, i64_type.const_all_ones(), "result");
+        builder.build_return(Some(&result));
+
+        optimize(module);
+
+        assert_eq!(
+            returned_int(main_fn),
+            Some(41 + 2),
+            "{}",
+            main_fn.print_to_string()
+        );
+    }
+
+    #[test]
+    fn crashes_at_the_same_place_share_their_source_info() {
+        let arena = Bump::new();
+        let context = Context::create();
+        let module: &Module = arena.alloc(context.create_module("test"));
+        let builder = context.create_builder();
+        let debug_info = Env::new_debug_info(module, &Triple::host());
+        let env = Env::for_tests(&arena, &context, module, &builder, &debug_info);
+
+        let info = SourceInfo {
+            file: "Main.roc",
+            line: 12,
+            column: 5,
+        };
+        let first = build_source_info_global(&env, info);
+        let second = build_source_info_global(&env, info);
+        let elsewhere = build_source_info_global(&env, SourceInfo { line: 13, ..info });
+
+        assert_eq!(first, second);
+        assert_ne!(first, elsewhere);
+
+        let ir = module.print_to_string().to_string();
+        assert!(ir.contains(r#"c"Main.roc\00""#), "{}", ir);
+        assert!(ir.contains("i32 12, i32 5"), "{}", ir);
+        assert!(ir.contains("i32 13, i32 5"), "{}", ir);
+    }
+
+    #[test]
+    fn a_crash_region_is_found_in_the_source_of_its_module() {
+        use roc_region::all::{Position, Region};
+
+        let arena = Bump::new();
+        let context = Context::create();
+        let module: &Module = arena.alloc(context.create_module("test"));
+        let builder = context.create_builder();
+        let debug_info = Env::new_debug_info(module, &Triple::host());
+        let mut env = Env::for_tests(&arena, &context, module, &builder, &debug_info);
+
+        let src = "main =\n    crash \"oops\"\n";
+        env.sources.insert(
+            ModuleId::NUM,
+            (PathBuf::from("Main.roc"), src.to_string().into_boxed_str()),
+        );
+
+        // the region of the message, `"oops"`
+        let start = src.find('"').unwrap() as u32;
+        let region = Region::new(Position::new(start), Position::new(start + 6));
+
+        assert_eq!(
+            crash_source_info(
+                &env,
+                CrashRegion {
+                    module: ModuleId::NUM,
+                    region
+                }
+            ),
+            Some(SourceInfo {
+                file: "Main.roc",
+                line: 2,
+                column: 11,
+            })
+        );
+
+        // without the source of its module, a crash does not know where it is
+        assert_eq!(
+            crash_source_info(
+                &env,
+                CrashRegion {
+                    module: ModuleId::STR,
+                    region
+                }
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn the_producer_is_in_the_debug_info_and_the_metadata() {
+        let context = Context::create();
+        let module = context.create_module("test");
+        let target: Triple = "x86_64-unknown-linux-gnu".parse().unwrap();
+        let (dibuilder, _) = Env::new_debug_info(&module, &target);
+        dibuilder.finalize();
+
+        let producer = format!(
+            "roc llvm backend {} target x86_64-unknown-linux-gnu",
+            roc_target::COMPILER_VERSION.trim()
+        );
+        let ir = module.print_to_string().to_string();
+        assert!(
+            ir.contains(&format!(r#"producer: "{}""#, producer)),
+            "{}",
+            ir
+        );
+        assert!(
+            ir.contains(&format!("!{} = !{{!", PRODUCER_METADATA)),
+            "{}",
+            ir
+        );
+        assert!(ir.contains(&format!(r#"!{{!"{}"}}"#, producer)), "{}", ir);
+    }
+
+    #[test]
+    fn functions_go_without_the_red_zone_when_the_target_disables_it() {
+        let build = |target_info: TargetInfo| {
+            let arena = Bump::new();
+            let context = Context::create();
+            let module: &Module = arena.alloc(context.create_module("test"));
+            let builder = context.create_builder();
+            let debug_info = Env::new_debug_info(module, &Triple::host());
+            let env = Env {
+                target_info,
+                ..Env::for_tests(&arena, &context, module, &builder, &debug_info)
+            };
+
+            let fn_type = context.i64_type().fn_type(&[], false);
+            let defined = add_func(
+                &context,
+                module,
+                "defined",
+                FunctionSpec::known_fastcc(fn_type),
+                Linkage::Internal,
+            );
+            builder.position_at_end(context.append_basic_block(defined, "entry"));
+            builder.build_return(Some(&context.i64_type().const_int(42, false)));
+            add_func(
+                &context,
+                module,
+                "declared",
+                FunctionSpec::intrinsic(fn_type),
+                Linkage::External,
+            );
+
+            apply_red_zone_option(&env);
+
+            let kind_id = Attribute::get_named_enum_kind_id("noredzone");
+            let has_attribute = |name| {
+                module
+                    .get_function(name)
+                    .unwrap()
+                    .get_enum_attribute(AttributeLoc::Function, kind_id)
+                    .is_some()
+            };
+
+            (has_attribute("defined"), has_attribute("declared"))
+        };
+
+        assert_eq!(build(TargetInfo::default_x86_64()), (false, false));
+        assert_eq!(
+            build(TargetInfo::default_x86_64().without_red_zone()),
+            (true, false)
+        );
+    }
+
+    #[test]
+    fn a_global_that_fits_in_a_register_is_accessed_atomically() {
+        let arena = Bump::new();
+        let context = Context::create();
+        let module = arena.alloc(context.create_module("test"));
+        let builder = context.create_builder();
+        let debug_info = Env::new_debug_info(module, &Triple::host());
+        let env = Env::for_tests(&arena, &context, module, &builder, &debug_info);
+        let mut layout_interner = STLayoutInterner::with_capacity(4, TargetInfo::default_x86_64());
+
+        let i64_type = context.i64_type();
+        let global = build_global_mutable(
+            &env,
+            &mut layout_interner,
+            "counter",
+            Some(i64_type.const_int(1, false).into()),
+            Layout::I64,
+        );
+        assert_eq!(
+            global.get_initializer(),
+            Some(i64_type.const_int(1, false).into())
+        );
+
+        let function = add_test_function(&env, "bump", i64_type.fn_type(&[], false));
+        build_global_store(
+            &env,
+            &mut layout_interner,
+            global,
+            Layout::I64,
+            i64_type.const_int(42, false).into(),
+        );
+        let value = build_global_load(&env, &mut layout_interner, global, Layout::I64);
+        builder.build_return(Some(&value));
+
+        let instructions: std::vec::Vec<_> = std::iter::successors(
+            function
+                .get_first_basic_block()
+                .unwrap()
+                .get_first_instruction(),
+            |i| i.get_next_instruction(),
+        )
+        .collect();
+        assert_eq!(instructions.len(), 3, "{}", function.print_to_string());
+        let (store, load) = (instructions[0], instructions[1]);
+        assert_eq!(store.get_opcode(), InstructionOpcode::Store);
+        assert_eq!(load.get_opcode(), InstructionOpcode::Load);
+        for access in [store, load] {
+            assert_eq!(
+                access.get_atomic_ordering(),
+                Ok(AtomicOrdering::SequentiallyConsistent)
+            );
+            assert_eq!(access.get_alignment(), Ok(8));
+        }
+        assert!(module.verify().is_ok(), "{}", module.print_to_string());
+    }
+
+    #[test]
+    fn a_global_reads_back_what_was_stored_in_it() {
+        let arena = Bump::new();
+        let context = Context::create();
+        let module = arena.alloc(context.create_module("test"));
+        let builder = context.create_builder();
+        let debug_info = Env::new_debug_info(module, &Triple::host());
+        let env = Env::for_tests(&arena, &context, module, &builder, &debug_info);
+        let mut layout_interner = STLayoutInterner::with_capacity(4, TargetInfo::default_x86_64());
+
+        // too big for one register, so it is copied without synchronization
+        let pair_layout = layout_interner.insert(Layout::struct_no_name_order(
+            arena.alloc([Layout::I64, Layout::I64]),
+        ));
+        assert!(!is_atomically_accessible(
+            &env,
+            &mut layout_interner,
+            pair_layout
+        ));
+        let global = build_global_mutable(&env, &mut layout_interner, "pair", None, pair_layout);
+
+        let i64_type = context.i64_type();
+        let function = add_test_function(&env, "swap", i64_type.fn_type(&[], false));
+        let pair_type = basic_type_from_layout(&env, &mut layout_interner, pair_layout);
+        let pair = pair_type.into_struct_type().const_named_struct(&[
+            i64_type.const_int(1, false).into(),
+            i64_type.const_int(2, false).into(),
+        ]);
+        build_global_store(&env, &mut layout_interner, global, pair_layout, pair.into());
+        let loaded = build_global_load(&env, &mut layout_interner, global, pair_layout);
+        let second = builder
+            .build_extract_value(loaded.into_struct_value(), 1, "second")
+            .unwrap();
+        builder.build_return(Some(&second));
+
+        optimize(module);
+
+        assert_eq!(
+            returned_int(function),
+            Some(2),
+            "{}",
+            function.print_to_string()
+        );
+    }
+
+    #[test]
+    fn awaiting_a_task_passes_its_result_to_the_continuation() {
+        let arena = Bump::new();
+        let context = Context::create();
+        let module = arena.alloc(context.create_module("test"));
+        let builder = context.create_builder();
+        let debug_info = Env::new_debug_info(module, &Triple::host());
+        let env = Env::for_tests(&arena, &context, module, &builder, &debug_info);
+        let mut layout_interner = STLayoutInterner::with_capacity(4, TargetInfo::default_x86_64());
+
+        let i64_type = context.i64_type();
+
+        // the task produces 41
+        let task_fn = add_test_function(&env, "task", i64_type.fn_type(&[], false));
+        task_fn.set_call_conventions(FAST_CALL_CONV);
+        builder.build_return(Some(&i64_type.const_int(41, false)));
+
+        // the continuation adds what it captured to what the task produced
+        let continuation_fn = add_test_function(
+            &env,
+            "continuation",
+            i64_type.fn_type(&[i64_type.into(), i64_type.into()], false),
+        );
+        continuation_fn.set_call_conventions(FAST_CALL_CONV);
+        let sum = builder.build_int_add(
+            continuation_fn.get_nth_param(0).unwrap().into_int_value(),
+            continuation_fn.get_nth_param(1).unwrap().into_int_value(),
+            "sum",
+        );
+        builder.build_return(Some(&sum));
+
+        let main_fn = add_test_function(&env, "main", i64_type.fn_type(&[], false));
+        let result = build_task_await(
+            &env,
+            &mut layout_interner,
+            task_fn,
+            Layout::I64,
+            continuation_fn,
+            Some(i64_type.const_int(1, false).into()),
+            Layout::I64,
+            Layout::I64,
+        );
+        builder.build_return(Some(&result));
+
+        assert!(module.verify().is_ok(), "{}", module.print_to_string());
+
+        optimize(module);
+
+        assert_eq!(
+            returned_int(main_fn),
+            Some(42),
+            "{}",
+            main_fn.print_to_string()
+        );
+    }
+
+    #[test]
+    fn a_type_name_is_a_constant_str() {
+        let arena = Bump::new();
+        let context = Context::create();
+        let module = arena.alloc(context.create_module("test"));
+        let builder = context.create_builder();
+        let debug_info = Env::new_debug_info(module, &Triple::host());
+        let env = Env::for_tests(&arena, &context, module, &builder, &debug_info);
+        let mut layout_interner = STLayoutInterner::with_capacity(4, TargetInfo::default_x86_64());
+
+        let list_i64 = layout_interner.insert(Layout::Builtin(Builtin::List(Layout::I64)));
+        let record = layout_interner.insert(Layout::struct_no_name_order(
+            arena.alloc([Layout::STR, list_i64]),
+        ));
+        let type_name = "{ Str, List I64 }";
+
+        let function =
+            add_test_function(&env, "type_name", context.void_type().fn_type(&[], false));
+        build_type_name(&env, &layout_interner, function, record);
+        builder.build_return(None);
+
+        assert!(module.verify().is_ok(), "{}", module.print_to_string());
+
+        // the bytes of the name come right after the constant refcount, and the Str is a
+        // big string of exactly that many bytes
+        let ir = module.print_to_string().to_string();
+        assert!(ir.contains(&format!("{}\"", type_name)), "{}", ir);
+        let len = type_name.len();
+        assert!(
+            ir.contains(&format!("i64 {}, i64 {} }}", len, len)),
+            "{}",
+            ir
+        );
+    }
+}
@@ -0,0 +1,344 @@
+//! Primitives for writing numbers into byte buffers and reading them back out,
+//! e.g. when encoding values to bytes and decoding them again.
+//!
+//! Encoded numbers are always little-endian, and are packed back to back in the buffer,
+//! so every load and store here uses an alignment of 1.
+use crate::llvm::build::{BuilderExt, Env};
+use crate::llvm::build_list::{list_reserve, load_list};
+use crate::llvm::build_num::{build_bits_to_num, build_num_to_bits, float_bits_type};
+use crate::llvm::convert::{basic_type_from_layout, float_type_from_float_width};
+use crate::llvm::intrinsics::LLVM_BSWAP;
+use inkwell::types::{FloatType, IntType};
+use inkwell::values::{BasicValueEnum, FloatValue, IntValue, PointerValue, StructValue};
+use inkwell::{AddressSpace, IntPredicate};
+use morphic_lib::UpdateMode;
+use roc_builtins::bitcode::{FloatWidth, IntWidth};
+use roc_error_macros::internal_error;
+use roc_mono::layout::{Builtin, InLayout, Layout, LayoutInterner, STLayoutInterner};
+
+/// Convert between the target's byte order and little-endian; this is its own inverse
+fn swap_bytes_if_big_endian<'a, 'ctx, 'env>(
+    env: &Env<'a, 'ctx, 'env>,
+    value: IntValue<'ctx>,
+) -> IntValue<'ctx> {
+    if !env.target_info.architecture.is_big_endian() {
+        return value;
+    }
+
+    let int_width = match value.get_type().get_bit_width() {
+        // a single byte has no byte order
+        8 => return value,
+        16 => IntWidth::U16,
+        32 => IntWidth::U32,
+        64 => IntWidth::U64,
+        128 => IntWidth::U128,
+        other => unreachable!("cannot convert the byte order of a {}-bit integer", other),
+    };
+
+    env.call_intrinsic(&LLVM_BSWAP[int_width], &[value.into()])
+        .into_int_value()
+}
+
+/// Store `value` as little-endian bytes at `ptr`, which may be unaligned
+pub fn build_write_int_le<'a, 'ctx, 'env>(
+    env: &Env<'a, 'ctx, 'env>,
+    ptr: PointerValue<'ctx>,
+    value: IntValue<'ctx>,
+) {
+    let int_type = value.get_type();
+    let value = swap_bytes_if_big_endian(env, value);
+
+    let ptr = env.builder.build_pointer_cast(
+        ptr,
+        int_type.ptr_type(AddressSpace::default()),
+        "to_int_ptr",
+    );
+
+    let store = env.builder.build_store(ptr, value);
+    store.set_alignment(1).unwrap();
+}
+
+/// Store the bits of `value` as little-endian bytes at `ptr`, which may be unaligned
+pub fn build_write_float_le<'a, 'ctx, 'env>(
+    env: &Env<'a, 'ctx, 'env>,
+    ptr: PointerValue<'ctx>,
+    value: FloatValue<'ctx>,
+) {
+    build_write_int_le(env, ptr, build_num_to_bits(env, value))
+}
+
+pub fn build_write_i32_le<'a, 'ctx, 'env>(
+    env: &Env<'a, 'ctx, 'env>,
+    ptr: PointerValue<'ctx>,
+    value: IntValue<'ctx>,
+) {
+    debug_assert_eq!(value.get_type().get_bit_width(), 32);
+
+    build_write_int_le(env, ptr, value)
+}
+
+pub fn build_write_i64_le<'a, 'ctx, 'env>(
+    env: &Env<'a, 'ctx, 'env>,
+    ptr: PointerValue<'ctx>,
+    value: IntValue<'ctx>,
+) {
+    debug_assert_eq!(value.get_type().get_bit_width(), 64);
+
+    build_write_int_le(env, ptr, value)
+}
+
+pub fn build_write_f32_le<'a, 'ctx, 'env>(
+    env: &Env<'a, 'ctx, 'env>,
+    ptr: PointerValue<'ctx>,
+    value: FloatValue<'ctx>,
+) {
+    debug_assert_eq!(value.get_type(), env.context.f32_type());
+
+    build_write_float_le(env, ptr, value)
+}
+
+pub fn build_write_f64_le<'a, 'ctx, 'env>(
+    env: &Env<'a, 'ctx, 'env>,
+    ptr: PointerValue<'ctx>,
+    value: FloatValue<'ctx>,
+) {
+    debug_assert_eq!(value.get_type(), env.context.f64_type());
+
+    build_write_float_le(env, ptr, value)
+}
+
+/// Load an integer from the little-endian bytes at `ptr`, which may be unaligned
+pub fn build_read_int_le<'a, 'ctx, 'env>(
+    env: &Env<'a, 'ctx, 'env>,
+    ptr: PointerValue<'ctx>,
+    int_type: IntType<'ctx>,
+) -> IntValue<'ctx> {
+    let ptr = env.builder.build_pointer_cast(
+        ptr,
+        int_type.ptr_type(AddressSpace::default()),
+        "to_int_ptr",
+    );
+
+    let value = env
+        .builder
+        .new_build_load(int_type, ptr, "read_int_le")
+        .into_int_value();
+    value
+        .as_instruction_value()
+        .unwrap()
+        .set_alignment(1)
+        .unwrap();
+
+    swap_bytes_if_big_endian(env, value)
+}
+
+/// Load a float from the little-endian bytes at `ptr`, which may be unaligned
+pub fn build_read_float_le<'a, 'ctx, 'env>(
+    env: &Env<'a, 'ctx, 'env>,
+    ptr: PointerValue<'ctx>,
+    float_type: FloatType<'ctx>,
+) -> FloatValue<'ctx> {
+    let bits = build_read_int_le(env, ptr, float_bits_type(env, float_type));
+
+    build_bits_to_num(env, bits)
+}
+
+pub fn build_read_i32_le<'a, 'ctx, 'env>(
+    env: &Env<'a, 'ctx, 'env>,
+    ptr: PointerValue<'ctx>,
+) -> IntValue<'ctx> {
+    build_read_int_le(env, ptr, env.context.i32_type())
+}
+
+pub fn build_read_i64_le<'a, 'ctx, 'env>(
+    env: &Env<'a, 'ctx, 'env>,
+    ptr: PointerValue<'ctx>,
+) -> IntValue<'ctx> {
+    build_read_int_le(env, ptr, env.context.i64_type())
+}
+
+pub fn build_read_f32_le<'a, 'ctx, 'env>(
+    env: &Env<'a, 'ctx, 'env>,
+    ptr: PointerValue<'ctx>,
+) -> FloatValue<'ctx> {
+    build_read_float_le(env, ptr, env.context.f32_type())
+}
+
+pub fn build_read_f64_le<'a, 'ctx, 'env>(
+    env: &Env<'a, 'ctx, 'env>,
+    ptr: PointerValue<'ctx>,
+) -> FloatValue<'ctx> {
+    build_read_float_le(env, ptr, env.context.f64_type())
+}
+
+/// Read an integer from the little-endian bytes of `bytes` (a `List U8`), starting at `offset`.
+///
+/// Returns the value and an `i1` that is false when the list is too short, i.e. when
+/// `offset + size > List.len bytes`. In that case nothing is read and the value is zero;
+/// callers turn this into an `Err TooShort`.
+pub fn build_read_int_le_checked<'a, 'ctx, 'env>(
+    env: &Env<'a, 'ctx, 'env>,
+    bytes: StructValue<'ctx>,
+    offset: IntValue<'ctx>,
+    int_type: IntType<'ctx>,
+) -> (IntValue<'ctx>, IntValue<'ctx>) {
+    let builder = env.builder;
+    let ctx = env.context;
+
+    let byte_type = ctx.i8_type();
+    let (len, ptr) = load_list(builder, bytes, byte_type.ptr_type(AddressSpace::default()));
+    let size = env
+        .ptr_int()
+        .const_int(int_type.get_bit_width() as u64 / 8, false);
+
+    // `offset + size` can overflow, so compare `size` with the bytes remaining after `offset`
+    let offset_in_bounds = builder.build_int_compare(IntPredicate::ULE, offset, len, "offset_ok");
+    let remaining = builder.build_int_sub(len, offset, "remaining");
+    let size_in_bounds = builder.build_int_compare(IntPredicate::ULE, size, remaining, "size_ok");
+    let in_bounds = builder.build_and(offset_in_bounds, size_in_bounds, "in_bounds");
+
+    let entry_block = builder.get_insert_block().unwrap();
+    let parent = entry_block.get_parent().unwrap();
+    let read_block = ctx.append_basic_block(parent, "read_in_bounds");
+    let cont_block = ctx.append_basic_block(parent, "read_cont");
+
+    builder.build_conditional_branch(in_bounds, read_block, cont_block);
+
+    builder.position_at_end(read_block);
+    let value_ptr =
+        unsafe { builder.new_build_in_bounds_gep(byte_type, ptr, &[offset], "value_ptr") };
+    let value = build_read_int_le(env, value_ptr, int_type);
+    // reading may have added blocks, e.g. for a byte swap
+    let read_end_block = builder.get_insert_block().unwrap();
+    builder.build_unconditional_branch(cont_block);
+
+    builder.position_at_end(cont_block);
+    let phi = builder.build_phi(int_type, "read_value");
+    phi.add_incoming(&[
+        (&value, read_end_block),
+        (&int_type.const_zero(), entry_block),
+    ]);
+
+    (phi.as_basic_value().into_int_value(), in_bounds)
+}
+
+/// Like [build_read_int_le_checked], for floats
+pub fn build_read_float_le_checked<'a, 'ctx, 'env>(
+    env: &Env<'a, 'ctx, 'env>,
+    bytes: StructValue<'ctx>,
+    offset: IntValue<'ctx>,
+    float_type: FloatType<'ctx>,
+) -> (FloatValue<'ctx>, IntValue<'ctx>) {
+    let bits_type = float_bits_type(env, float_type);
+    let (bits, in_bounds) = build_read_int_le_checked(env, bytes, offset, bits_type);
+
+    (build_bits_to_num(env, bits), in_bounds)
+}
+
+/// Encode.appendNumLe : List U8, Num * -> List U8
+///
+/// Appends the little-endian bytes of `number` to `bytes`
+pub(crate) fn list_append_num_le<'a, 'ctx, 'env>(
+    env: &Env<'a, 'ctx, 'env>,
+    layout_interner: &mut STLayoutInterner<'a>,
+    bytes: BasicValueEnum<'ctx>,
+    number: BasicValueEnum<'ctx>,
+    number_layout: InLayout<'a>,
+    update_mode: UpdateMode,
+) -> BasicValueEnum<'ctx> {
+    let builder = env.builder;
+    let byte_type = env.context.i8_type();
+
+    let size = env
+        .ptr_int()
+        .const_int(layout_interner.stack_size(number_layout) as u64, false);
+    let bytes = list_reserve(
+        env,
+        layout_interner,
+        bytes,
+        size.into(),
+        Layout::U8,
+        update_mode,
+    )
+    .into_struct_value();
+
+    let (len, ptr) = load_list(builder, bytes, byte_type.ptr_type(AddressSpace::default()));
+    let end_ptr = unsafe { builder.new_build_in_bounds_gep(byte_type, ptr, &[len], "end_ptr") };
+
+    match layout_interner.get(number_layout) {
+        Layout::Builtin(Builtin::Int(IntWidth::I32 | IntWidth::U32)) => {
+            build_write_i32_le(env, end_ptr, number.into_int_value())
+        }
+        Layout::Builtin(Builtin::Int(IntWidth::I64 | IntWidth::U64)) => {
+            build_write_i64_le(env, end_ptr, number.into_int_value())
+        }
+        // a Dec is an i128 too
+        Layout::Builtin(Builtin::Int(_) | Builtin::Decimal) => {
+            build_write_int_le(env, end_ptr, number.into_int_value())
+        }
+        Layout::Builtin(Builtin::Float(FloatWidth::F32)) => {
+            build_write_f32_le(env, end_ptr, number.into_float_value())
+        }
+        Layout::Builtin(Builtin::Float(FloatWidth::F64)) => {
+            build_write_f64_le(env, end_ptr, number.into_float_value())
+        }
+        other => internal_error!("appendNumLe on a non-number layout {:?}", other),
+    }
+
+    let new_len = builder.build_int_add(len, size, "new_len");
+
+    builder
+        .build_insert_value(bytes, new_len, Builtin::WRAPPER_LEN, "set_len")
+        .unwrap()
+        .into_struct_value()
+        .into()
+}
+
+/// Decode.numFromBytesLeLowlevel : List U8, Nat -> { aresult : Num *, binbounds : Bool }
+///
+/// Reads a number from the little-endian bytes of `bytes`, starting at `offset`.
+/// `Decode.numFromBytesLe` turns this into an `Err TooShort` when it is not in bounds.
+pub(crate) fn num_from_bytes_le<'a, 'ctx, 'env>(
+    env: &Env<'a, 'ctx, 'env>,
+    layout_interner: &mut STLayoutInterner<'a>,
+    bytes: BasicValueEnum<'ctx>,
+    offset: BasicValueEnum<'ctx>,
+    layout: InLayout<'a>,
+) -> BasicValueEnum<'ctx> {
+    let number_layout = match layout_interner.get(layout) {
+        Layout::Struct { field_layouts, .. } => field_layouts[0],
+        other => internal_error!("numFromBytesLe returns a record, not {:?}", other),
+    };
+
+    let bytes = bytes.into_struct_value();
+    let offset = offset.into_int_value();
+
+    let (value, in_bounds): (BasicValueEnum, _) = match layout_interner.get(number_layout) {
+        Layout::Builtin(Builtin::Float(float_width)) => {
+            let float_type = float_type_from_float_width(env, float_width);
+            let (value, in_bounds) = build_read_float_le_checked(env, bytes, offset, float_type);
+
+            (value.into(), in_bounds)
+        }
+        Layout::Builtin(Builtin::Int(_) | Builtin::Decimal) => {
+            let int_type = basic_type_from_layout(env, layout_interner, number_layout);
+            let (value, in_bounds) =
+                build_read_int_le_checked(env, bytes, offset, int_type.into_int_type());
+
+            (value.into(), in_bounds)
+        }
+        other => internal_error!("numFromBytesLe on a non-number layout {:?}", other),
+    };
+
+    let struct_type = basic_type_from_layout(env, layout_interner, layout).into_struct_type();
+    let result = env
+        .builder
+        .build_insert_value(struct_type.get_undef(), value, 0, "insert_value")
+        .unwrap();
+
+    env.builder
+        .build_insert_value(result, in_bounds, 1, "insert_in_bounds")
+        .unwrap()
+        .into_struct_value()
+        .into()
+}
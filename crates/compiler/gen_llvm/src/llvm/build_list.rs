@@ -3,15 +3,20 @@ use crate::llvm::build::{
     allocate_with_refcount_help, cast_basic_basic, Env, RocFunctionCall, Scope,
 };
 use crate::llvm::convert::basic_type_from_layout;
+use crate::llvm::intrinsics::{LLVM_MAXNUM, LLVM_MINNUM};
+use crate::llvm::lowlevel::build_num_binop;
 use crate::llvm::refcounting::increment_refcount_layout;
 use inkwell::builder::Builder;
 use inkwell::types::{BasicType, PointerType};
 use inkwell::values::{BasicValueEnum, FunctionValue, IntValue, PointerValue, StructValue};
 use inkwell::{AddressSpace, IntPredicate};
 use morphic_lib::UpdateMode;
-use roc_builtins::bitcode;
+use roc_builtins::bitcode::{self, IntrinsicName};
+use roc_error_macros::internal_error;
+use roc_module::low_level::LowLevel;
 use roc_module::symbol::Symbol;
-use roc_mono::layout::{Builtin, InLayout, LayoutIds, LayoutInterner, STLayoutInterner};
+use roc_mono::layout::{Builtin, InLayout, Layout, LayoutIds, LayoutInterner, STLayoutInterner};
+use roc_std::RocDec;
 
 use super::bitcode::{call_list_bitcode_fn, BitcodeReturns};
 use super::build::{
@@ -622,6 +627,436 @@ pub(crate) fn list_concat<'a, 'ctx, 'env>(
     )
 }
 
+/// List.join : List (List elem) -> List elem
+///
+/// The first pass sums up the lengths of the inner lists, so the result is allocated once.
+/// The second pass copies the elements of every inner list into it. The outer list is
+/// borrowed, so the result shares its elements with the inner lists, and increments them.
+pub(crate) fn build_list_join<'a, 'ctx, 'env>(
+    env: &Env<'a, 'ctx, 'env>,
+    layout_interner: &mut STLayoutInterner<'a>,
+    layout_ids: &mut LayoutIds<'a>,
+    parent: FunctionValue<'ctx>,
+    outer_list: StructValue<'ctx>,
+    element_layout: InLayout<'a>,
+) -> BasicValueEnum<'ctx> {
+    let builder = env.builder;
+    let ctx = env.context;
+
+    let list_type = zig_list_type(env);
+    let inner_list_layout = layout_interner.insert(Layout::Builtin(Builtin::List(element_layout)));
+    let (outer_len, outer_ptr) = load_list(
+        builder,
+        outer_list,
+        list_type.ptr_type(AddressSpace::default()),
+    );
+
+    let total_ptr = create_entry_block_alloca(env, parent, env.ptr_int().into(), "join_total");
+    builder.build_store(total_ptr, env.ptr_int().const_zero());
+
+    incrementing_elem_loop(
+        env,
+        layout_interner,
+        parent,
+        inner_list_layout,
+        outer_ptr,
+        outer_len,
+        "join_length_index",
+        |_, _, inner_list| {
+            let total = builder
+                .new_build_load(env.ptr_int(), total_ptr, "join_total_current")
+                .into_int_value();
+            let len = list_len(builder, inner_list.into_struct_value());
+
+            builder.build_store(
+                total_ptr,
+                builder.build_int_add(total, len, "join_total_next"),
+            );
+        },
+    );
+
+    let total = builder
+        .new_build_load(env.ptr_int(), total_ptr, "join_total")
+        .into_int_value();
+
+    // without any elements, the result is the empty list, which does not allocate
+    let empty_block = ctx.append_basic_block(parent, "join_empty");
+    let fill_block = ctx.append_basic_block(parent, "join_fill");
+    let cont_block = ctx.append_basic_block(parent, "join_cont");
+    let is_empty = builder.build_int_compare(
+        IntPredicate::EQ,
+        total,
+        env.ptr_int().const_zero(),
+        "join_is_empty",
+    );
+    builder.build_conditional_branch(is_empty, empty_block, fill_block);
+
+    builder.position_at_end(empty_block);
+    let empty = empty_polymorphic_list(env);
+    builder.build_unconditional_branch(cont_block);
+
+    builder.position_at_end(fill_block);
+
+    let element_type = basic_type_from_layout(env, layout_interner, element_layout);
+    let element_ptr_type = element_type.ptr_type(AddressSpace::default());
+    let element_width = env
+        .ptr_int()
+        .const_int(layout_interner.stack_size(element_layout) as u64, false);
+    let alignment = layout_interner.alignment_bytes(element_layout);
+
+    let joined_ptr = allocate_list(env, layout_interner, element_layout, total);
+    let offset_ptr = create_entry_block_alloca(env, parent, env.ptr_int().into(), "join_offset");
+    builder.build_store(offset_ptr, env.ptr_int().const_zero());
+
+    incrementing_elem_loop(
+        env,
+        layout_interner,
+        parent,
+        inner_list_layout,
+        outer_ptr,
+        outer_len,
+        "join_copy_index",
+        |_, _, inner_list| {
+            let (len, elements) =
+                load_list(builder, inner_list.into_struct_value(), element_ptr_type);
+            let offset = builder
+                .new_build_load(env.ptr_int(), offset_ptr, "join_offset_current")
+                .into_int_value();
+
+            let destination = unsafe {
+                builder.new_build_in_bounds_gep(
+                    element_type,
+                    joined_ptr,
+                    &[offset],
+                    "join_destination",
+                )
+            };
+            let bytes = builder.build_int_mul(len, element_width, "join_bytes");
+            builder
+                .build_memcpy(destination, alignment, elements, alignment, bytes)
+                .unwrap();
+
+            builder.build_store(
+                offset_ptr,
+                builder.build_int_add(offset, len, "join_offset_next"),
+            );
+        },
+    );
+
+    if layout_interner.contains_refcounted(element_layout) {
+        incrementing_elem_loop(
+            env,
+            layout_interner,
+            parent,
+            element_layout,
+            joined_ptr,
+            total,
+            "join_inc_index",
+            |layout_interner, _, element| {
+                increment_refcount_layout(
+                    env,
+                    layout_interner,
+                    layout_ids,
+                    1,
+                    element,
+                    element_layout,
+                );
+            },
+        );
+    }
+
+    let joined: BasicValueEnum<'ctx> = store_list(env, joined_ptr, total).into();
+    let fill_end_block = builder.get_insert_block().unwrap();
+    builder.build_unconditional_branch(cont_block);
+
+    builder.position_at_end(cont_block);
+    let phi = builder.build_phi(list_type, "joined");
+    phi.add_incoming(&[(&empty, empty_block), (&joined, fill_end_block)]);
+
+    phi.as_basic_value()
+}
+
+/// List.sum : List (Num a) -> Num a
+pub(crate) fn build_list_sum<'a, 'ctx, 'env>(
+    env: &Env<'a, 'ctx, 'env>,
+    layout_interner: &mut STLayoutInterner<'a>,
+    parent: FunctionValue<'ctx>,
+    list: StructValue<'ctx>,
+    element_layout: InLayout<'a>,
+) -> BasicValueEnum<'ctx> {
+    if env.options.precise_float_reduction && element_layout == Layout::F64 {
+        return build_list_sum_f64_kahan(env, layout_interner, parent, list);
+    }
+
+    let builder = env.builder;
+
+    let element_type = basic_type_from_layout(env, layout_interner, element_layout);
+    let zero: BasicValueEnum<'ctx> = match layout_interner.get(element_layout) {
+        Layout::Builtin(Builtin::Int(_) | Builtin::Decimal) => {
+            element_type.into_int_type().const_zero().into()
+        }
+        Layout::Builtin(Builtin::Float(_)) => element_type.into_float_type().const_zero().into(),
+        other => internal_error!("List.sum on a list of {:?}", other),
+    };
+
+    // the sum of an empty list is 0
+    let accum = create_entry_block_alloca(env, parent, element_type, "sum_accum");
+    builder.build_store(accum, zero);
+
+    let ptr_type = element_type.ptr_type(AddressSpace::default());
+    let (len, ptr) = load_list(builder, list, ptr_type);
+
+    incrementing_elem_loop(
+        env,
+        layout_interner,
+        parent,
+        element_layout,
+        ptr,
+        len,
+        "sum_index",
+        |layout_interner, _, elem| {
+            let current = builder.new_build_load(element_type, accum, "sum_current");
+
+            // add like Num.add does, which panics on overflow
+            let sum = build_num_binop(
+                env,
+                layout_interner,
+                parent,
+                current,
+                element_layout,
+                elem,
+                element_layout,
+                LowLevel::NumAdd,
+            );
+
+            builder.build_store(accum, sum);
+        },
+    );
+
+    builder.new_build_load(element_type, accum, "sum")
+}
+
+/// List.sum on a `List F64`, with Kahan compensated summation.
+///
+/// Next to the sum, this keeps the low-order bits that got lost in the previous addition,
+/// and adds them back in with the next element. The error of the result then does not
+/// grow with the length of the list.
+pub(crate) fn build_list_sum_f64_kahan<'a, 'ctx, 'env>(
+    env: &Env<'a, 'ctx, 'env>,
+    layout_interner: &mut STLayoutInterner<'a>,
+    parent: FunctionValue<'ctx>,
+    list: StructValue<'ctx>,
+) -> BasicValueEnum<'ctx> {
+    let builder = env.builder;
+
+    let f64_type = env.context.f64_type();
+    let sum_ptr = create_entry_block_alloca(env, parent, f64_type.into(), "sum_accum");
+    let compensation_ptr = create_entry_block_alloca(env, parent, f64_type.into(), "compensation");
+    builder.build_store(sum_ptr, f64_type.const_zero());
+    builder.build_store(compensation_ptr, f64_type.const_zero());
+
+    let ptr_type = f64_type.ptr_type(AddressSpace::default());
+    let (len, ptr) = load_list(builder, list, ptr_type);
+
+    incrementing_elem_loop(
+        env,
+        layout_interner,
+        parent,
+        Layout::F64,
+        ptr,
+        len,
+        "sum_index",
+        |_, _, elem| {
+            let sum = builder
+                .new_build_load(f64_type, sum_ptr, "sum_current")
+                .into_float_value();
+            let compensation = builder
+                .new_build_load(f64_type, compensation_ptr, "compensation_current")
+                .into_float_value();
+
+            // y = elem - compensation
+            // t = sum + y
+            // compensation = (t - sum) - y
+            // sum = t
+            let y = builder.build_float_sub(elem.into_float_value(), compensation, "y");
+            let t = builder.build_float_add(sum, y, "t");
+            let high_bits = builder.build_float_sub(t, sum, "high_bits");
+            let new_compensation = builder.build_float_sub(high_bits, y, "new_compensation");
+
+            builder.build_store(compensation_ptr, new_compensation);
+            builder.build_store(sum_ptr, t);
+        },
+    );
+
+    builder.new_build_load(f64_type, sum_ptr, "sum")
+}
+
+/// List.product : List (Num a) -> Num a
+pub(crate) fn build_list_product<'a, 'ctx, 'env>(
+    env: &Env<'a, 'ctx, 'env>,
+    layout_interner: &mut STLayoutInterner<'a>,
+    parent: FunctionValue<'ctx>,
+    list: StructValue<'ctx>,
+    element_layout: InLayout<'a>,
+) -> BasicValueEnum<'ctx> {
+    let builder = env.builder;
+
+    let element_type = basic_type_from_layout(env, layout_interner, element_layout);
+    let one: BasicValueEnum<'ctx> = match layout_interner.get(element_layout) {
+        Layout::Builtin(Builtin::Int(_)) => element_type.into_int_type().const_int(1, false).into(),
+        Layout::Builtin(Builtin::Float(_)) => {
+            element_type.into_float_type().const_float(1.0).into()
+        }
+        Layout::Builtin(Builtin::Decimal) => element_type
+            .into_int_type()
+            .const_int(RocDec::from_str_to_i128_unsafe("1") as u64, false)
+            .into(),
+        other => internal_error!("List.product on a list of {:?}", other),
+    };
+
+    // the product of an empty list is 1
+    let accum = create_entry_block_alloca(env, parent, element_type, "product_accum");
+    builder.build_store(accum, one);
+
+    let ptr_type = element_type.ptr_type(AddressSpace::default());
+    let (len, ptr) = load_list(builder, list, ptr_type);
+
+    incrementing_elem_loop(
+        env,
+        layout_interner,
+        parent,
+        element_layout,
+        ptr,
+        len,
+        "product_index",
+        |layout_interner, _, elem| {
+            let current = builder.new_build_load(element_type, accum, "product_current");
+
+            // multiply like Num.mul does, which panics on overflow
+            let product = build_num_binop(
+                env,
+                layout_interner,
+                parent,
+                current,
+                element_layout,
+                elem,
+                element_layout,
+                LowLevel::NumMul,
+            );
+
+            builder.build_store(accum, product);
+        },
+    );
+
+    builder.new_build_load(element_type, accum, "product")
+}
+
+/// List.minUnsafe : List (Num a) -> Num a
+///
+/// The list must not be empty.
+pub(crate) fn build_list_min<'a, 'ctx, 'env>(
+    env: &Env<'a, 'ctx, 'env>,
+    layout_interner: &mut STLayoutInterner<'a>,
+    parent: FunctionValue<'ctx>,
+    list: StructValue<'ctx>,
+    element_layout: InLayout<'a>,
+) -> BasicValueEnum<'ctx> {
+    list_extremum(
+        env,
+        layout_interner,
+        parent,
+        list,
+        element_layout,
+        (IntPredicate::SLT, IntPredicate::ULT),
+        &LLVM_MINNUM,
+    )
+}
+
+/// List.maxUnsafe : List (Num a) -> Num a
+///
+/// The list must not be empty.
+pub(crate) fn build_list_max<'a, 'ctx, 'env>(
+    env: &Env<'a, 'ctx, 'env>,
+    layout_interner: &mut STLayoutInterner<'a>,
+    parent: FunctionValue<'ctx>,
+    list: StructValue<'ctx>,
+    element_layout: InLayout<'a>,
+) -> BasicValueEnum<'ctx> {
+    list_extremum(
+        env,
+        layout_interner,
+        parent,
+        list,
+        element_layout,
+        (IntPredicate::SGT, IntPredicate::UGT),
+        &LLVM_MAXNUM,
+    )
+}
+
+/// Keeps the element for which `(signed, unsigned)` holds against the best one so far.
+/// Floats use `float_intrinsic` instead, so a NaN is only kept when all elements are NaN.
+fn list_extremum<'a, 'ctx, 'env>(
+    env: &Env<'a, 'ctx, 'env>,
+    layout_interner: &mut STLayoutInterner<'a>,
+    parent: FunctionValue<'ctx>,
+    list: StructValue<'ctx>,
+    element_layout: InLayout<'a>,
+    (signed, unsigned): (IntPredicate, IntPredicate),
+    float_intrinsic: &'static IntrinsicName,
+) -> BasicValueEnum<'ctx> {
+    let builder = env.builder;
+
+    let element_type = basic_type_from_layout(env, layout_interner, element_layout);
+    let ptr_type = element_type.ptr_type(AddressSpace::default());
+    let (len, ptr) = load_list(builder, list, ptr_type);
+
+    let first = load_roc_value(env, layout_interner, element_layout, ptr, "first_element");
+    let accum = create_entry_block_alloca(env, parent, element_type, "extremum_accum");
+    builder.build_store(accum, first);
+
+    let layout = layout_interner.get(element_layout);
+    let select = |predicate, current: BasicValueEnum<'ctx>, elem: BasicValueEnum<'ctx>| {
+        let is_better = builder.build_int_compare(
+            predicate,
+            elem.into_int_value(),
+            current.into_int_value(),
+            "is_better",
+        );
+
+        builder.build_select(is_better, elem, current, "best")
+    };
+
+    incrementing_elem_loop(
+        env,
+        layout_interner,
+        parent,
+        element_layout,
+        ptr,
+        len,
+        "extremum_index",
+        |_, _, elem| {
+            let current = builder.new_build_load(element_type, accum, "extremum_current");
+
+            let best = match layout {
+                Layout::Builtin(Builtin::Int(int_width)) if int_width.is_signed() => {
+                    select(signed, current, elem)
+                }
+                Layout::Builtin(Builtin::Int(_)) => select(unsigned, current, elem),
+                // a Dec is an i128 with a fixed number of decimal places
+                Layout::Builtin(Builtin::Decimal) => select(signed, current, elem),
+                Layout::Builtin(Builtin::Float(float_width)) => {
+                    env.call_intrinsic(&float_intrinsic[float_width], &[current, elem])
+                }
+                other => internal_error!("List.min or List.max on a list of {:?}", other),
+            };
+
+            builder.build_store(accum, best);
+        },
+    );
+
+    builder.new_build_load(element_type, accum, "extremum")
+}
+
 pub(crate) fn incrementing_elem_loop<'a, 'r, 'ctx, 'env, LoopFn>(
     env: &Env<'a, 'ctx, 'env>,
     layout_interner: &'r mut STLayoutInterner<'a>,
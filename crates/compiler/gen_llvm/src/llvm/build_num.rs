@@ -0,0 +1,564 @@
+//! Number operations that are generated inline, instead of calling into the zig bitcode.
+use crate::llvm::build::throw_internal_exception;
+use crate::llvm::build::{create_entry_block_alloca, BuilderExt, Env};
+use crate::llvm::convert::int_type_from_int_width;
+use crate::llvm::intrinsics::{LLVM_ADD_WITH_OVERFLOW, LLVM_MUL_WITH_OVERFLOW};
+use crate::llvm::lowlevel::throw_on_overflow;
+use inkwell::module::Linkage;
+use inkwell::types::{FloatType, IntType, StructType};
+use inkwell::values::{FloatValue, FunctionValue, IntValue, PointerValue, StructValue};
+use inkwell::IntPredicate;
+use roc_builtins::bitcode::IntWidth;
+use roc_module::low_level::LowLevel;
+use roc_target::Architecture;
+
+/// The greatest common divisor of two `U64`s, using the binary GCD algorithm.
+/// It only needs shifts and subtractions, so it stays fast on targets with slow division.
+/// `gcd(0, b)` is `b`, so `gcd(0, 0)` is 0.
+pub fn build_gcd_u64<'a, 'ctx, 'env>(
+    env: &Env<'a, 'ctx, 'env>,
+    a: IntValue<'ctx>,
+    b: IntValue<'ctx>,
+) -> IntValue<'ctx> {
+    let builder = env.builder;
+    let context = env.context;
+    let i64_type = context.i64_type();
+    let zero = i64_type.const_zero();
+    let one = i64_type.const_int(1, false);
+
+    let parent = builder
+        .get_insert_block()
+        .and_then(|b| b.get_parent())
+        .unwrap();
+
+    let a_ptr = create_entry_block_alloca(env, parent, i64_type.into(), "gcd_a");
+    let b_ptr = create_entry_block_alloca(env, parent, i64_type.into(), "gcd_b");
+    let shift_ptr = create_entry_block_alloca(env, parent, i64_type.into(), "gcd_shift");
+
+    let load = |ptr: PointerValue<'ctx>, name: &str| {
+        builder.new_build_load(i64_type, ptr, name).into_int_value()
+    };
+    let is_even = |value: IntValue<'ctx>| {
+        let low_bit = builder.build_and(value, one, "low_bit");
+        builder.build_int_compare(IntPredicate::EQ, low_bit, zero, "is_even")
+    };
+    let shift_right = |ptr: PointerValue<'ctx>| {
+        let value = load(ptr, "value");
+        builder.build_store(ptr, builder.build_right_shift(value, one, false, "halved"));
+    };
+
+    let search_block = context.append_basic_block(parent, "gcd_search");
+    let done_block = context.append_basic_block(parent, "gcd_done");
+
+    // if either is zero, the gcd is the other one
+    let a_is_zero = builder.build_int_compare(IntPredicate::EQ, a, zero, "a_is_zero");
+    let b_is_zero = builder.build_int_compare(IntPredicate::EQ, b, zero, "b_is_zero");
+    let either_is_zero = builder.build_or(a_is_zero, b_is_zero, "either_is_zero");
+    let trivial_result = builder.build_or(a, b, "trivial_gcd");
+    let trivial_block = builder.get_insert_block().unwrap();
+    builder.build_conditional_branch(either_is_zero, done_block, search_block);
+
+    builder.position_at_end(search_block);
+    builder.build_store(a_ptr, a);
+    builder.build_store(b_ptr, b);
+    builder.build_store(shift_ptr, zero);
+
+    // gcd(2a, 2b) = 2 * gcd(a, b), so take out the factors of two they have in common
+    build_while(
+        env,
+        parent,
+        "gcd_common_twos",
+        || is_even(builder.build_or(load(a_ptr, "a"), load(b_ptr, "b"), "a_or_b")),
+        || {
+            shift_right(a_ptr);
+            shift_right(b_ptr);
+            let shift = load(shift_ptr, "shift");
+            builder.build_store(shift_ptr, builder.build_int_add(shift, one, "shift"));
+        },
+    );
+
+    // now at least one of them is odd, and gcd(2a, b) = gcd(a, b) for an odd b
+    build_while(
+        env,
+        parent,
+        "gcd_a_twos",
+        || is_even(load(a_ptr, "a")),
+        || shift_right(a_ptr),
+    );
+
+    // a is odd from here on, and b is nonzero until the loop ends
+    let subtract_block = context.append_basic_block(parent, "gcd_subtract");
+    builder.build_unconditional_branch(subtract_block);
+    builder.position_at_end(subtract_block);
+
+    build_while(
+        env,
+        parent,
+        "gcd_b_twos",
+        || is_even(load(b_ptr, "b")),
+        || shift_right(b_ptr),
+    );
+
+    // both are odd, so their difference is even: gcd(a, b) = gcd(min(a, b), max(a, b) - min(a, b))
+    let a = load(a_ptr, "a");
+    let b = load(b_ptr, "b");
+    let a_is_smaller = builder.build_int_compare(IntPredicate::ULT, a, b, "a_is_smaller");
+    let min = builder
+        .build_select(a_is_smaller, a, b, "min")
+        .into_int_value();
+    let max = builder
+        .build_select(a_is_smaller, b, a, "max")
+        .into_int_value();
+    let difference = builder.build_int_sub(max, min, "difference");
+    builder.build_store(a_ptr, min);
+    builder.build_store(b_ptr, difference);
+
+    let finished = builder.build_int_compare(IntPredicate::EQ, difference, zero, "finished");
+    let after_subtract_block = context.append_basic_block(parent, "gcd_shift_back");
+    builder.build_conditional_branch(finished, after_subtract_block, subtract_block);
+
+    builder.position_at_end(after_subtract_block);
+    let shift = load(shift_ptr, "shift");
+    let search_result = builder.build_left_shift(load(a_ptr, "a"), shift, "gcd");
+    builder.build_unconditional_branch(done_block);
+
+    builder.position_at_end(done_block);
+    let phi = builder.build_phi(i64_type, "gcd_result");
+    phi.add_incoming(&[
+        (&trivial_result, trivial_block),
+        (&search_result, after_subtract_block),
+    ]);
+
+    phi.as_basic_value().into_int_value()
+}
+
+/// The least common multiple of two `U64`s, as `a / gcd(a, b) * b`.
+/// Dividing first means only a result that does not fit in a `U64` overflows, which throws.
+/// `lcm(0, b)` is 0.
+pub fn build_lcm_u64<'a, 'ctx, 'env>(
+    env: &Env<'a, 'ctx, 'env>,
+    parent: FunctionValue<'ctx>,
+    a: IntValue<'ctx>,
+    b: IntValue<'ctx>,
+) -> IntValue<'ctx> {
+    let builder = env.builder;
+    let i64_type = env.context.i64_type();
+
+    let gcd = build_gcd_u64(env, a, b);
+
+    // the gcd is only zero when both are, and then a / 1 * b is the expected 0
+    let gcd_is_zero =
+        builder.build_int_compare(IntPredicate::EQ, gcd, i64_type.const_zero(), "gcd_is_zero");
+    let divisor = builder
+        .build_select(gcd_is_zero, i64_type.const_int(1, false), gcd, "divisor")
+        .into_int_value();
+    let a_part = builder.build_int_unsigned_div(a, divisor, "a_part");
+
+    let result = env
+        .call_intrinsic(
+            &LLVM_MUL_WITH_OVERFLOW[IntWidth::U64],
+            &[a_part.into(), b.into()],
+        )
+        .into_struct_value();
+
+    throw_on_overflow(env, parent, result, "integer multiplication overflowed!").into_int_value()
+}
+
+/// The sum of two `I64`s, wrapped around on overflow, and whether it overflowed
+pub fn build_add_with_overflow_i64<'a, 'ctx, 'env>(
+    env: &Env<'a, 'ctx, 'env>,
+    lhs: IntValue<'ctx>,
+    rhs: IntValue<'ctx>,
+) -> (IntValue<'ctx>, IntValue<'ctx>) {
+    let builder = env.builder;
+
+    let result = env
+        .call_intrinsic(
+            &LLVM_ADD_WITH_OVERFLOW[IntWidth::I64],
+            &[lhs.into(), rhs.into()],
+        )
+        .into_struct_value();
+
+    let value = builder
+        .build_extract_value(result, 0, "wrapped_sum")
+        .unwrap()
+        .into_int_value();
+    let overflowed = builder
+        .build_extract_value(result, 1, "has_overflowed")
+        .unwrap()
+        .into_int_value();
+
+    (value, overflowed)
+}
+
+/// `base` to the power of `exp`, using exponentiation by squaring.
+/// A negative exponent gives 0, and a result that does not fit in the integer type throws.
+pub fn build_integer_pow<'a, 'ctx, 'env>(
+    env: &Env<'a, 'ctx, 'env>,
+    parent: FunctionValue<'ctx>,
+    int_width: IntWidth,
+    base: IntValue<'ctx>,
+    exp: IntValue<'ctx>,
+) -> IntValue<'ctx> {
+    let builder = env.builder;
+    let context = env.context;
+    let int_type = base.get_type();
+    let zero = int_type.const_zero();
+    let one = int_type.const_int(1, false);
+
+    let result_ptr = create_entry_block_alloca(env, parent, int_type.into(), "pow_result");
+    let base_ptr = create_entry_block_alloca(env, parent, int_type.into(), "pow_base");
+    let exp_ptr = create_entry_block_alloca(env, parent, int_type.into(), "pow_exp");
+
+    let load = |ptr: PointerValue<'ctx>, name: &str| {
+        builder.new_build_load(int_type, ptr, name).into_int_value()
+    };
+    let checked_mul = |lhs: IntValue<'ctx>, rhs: IntValue<'ctx>| {
+        let result = env
+            .call_intrinsic(
+                &LLVM_MUL_WITH_OVERFLOW[int_width],
+                &[lhs.into(), rhs.into()],
+            )
+            .into_struct_value();
+
+        throw_on_overflow(env, parent, result, "integer exponentiation overflowed!")
+            .into_int_value()
+    };
+
+    builder.build_store(result_ptr, one);
+    builder.build_store(base_ptr, base);
+
+    // a negative exponent would need a fraction, so there are no iterations and the result is 0
+    if int_width.is_signed() {
+        let is_negative = builder.build_int_compare(IntPredicate::SLT, exp, zero, "is_negative");
+        let initial_result = builder
+            .build_select(is_negative, zero, one, "initial_result")
+            .into_int_value();
+        let initial_exp = builder
+            .build_select(is_negative, zero, exp, "initial_exp")
+            .into_int_value();
+        builder.build_store(result_ptr, initial_result);
+        builder.build_store(exp_ptr, initial_exp);
+    } else {
+        builder.build_store(exp_ptr, exp);
+    }
+
+    build_while(
+        env,
+        parent,
+        "pow",
+        || {
+            let exp = load(exp_ptr, "exp");
+            builder.build_int_compare(IntPredicate::NE, exp, zero, "exp_is_not_zero")
+        },
+        || {
+            let exp = load(exp_ptr, "exp");
+
+            // multiply in the base for every set bit of the exponent
+            let multiply_block = context.append_basic_block(parent, "pow_multiply");
+            let shift_block = context.append_basic_block(parent, "pow_shift");
+            let low_bit = builder.build_and(exp, one, "low_bit");
+            let is_odd = builder.build_int_compare(IntPredicate::NE, low_bit, zero, "is_odd");
+            builder.build_conditional_branch(is_odd, multiply_block, shift_block);
+
+            builder.position_at_end(multiply_block);
+            let product = checked_mul(load(result_ptr, "result"), load(base_ptr, "base"));
+            builder.build_store(result_ptr, product);
+            builder.build_unconditional_branch(shift_block);
+
+            builder.position_at_end(shift_block);
+            let exp = builder.build_right_shift(exp, one, false, "exp");
+            builder.build_store(exp_ptr, exp);
+
+            // only square the base when it is used again, so the last squaring cannot overflow
+            let square_block = context.append_basic_block(parent, "pow_square");
+            let next_block = context.append_basic_block(parent, "pow_next");
+            let more_bits = builder.build_int_compare(IntPredicate::NE, exp, zero, "more_bits");
+            builder.build_conditional_branch(more_bits, square_block, next_block);
+
+            builder.position_at_end(square_block);
+            let base = load(base_ptr, "base");
+            builder.build_store(base_ptr, checked_mul(base, base));
+            builder.build_unconditional_branch(next_block);
+
+            builder.position_at_end(next_block);
+        },
+    );
+
+    load(result_ptr, "pow")
+}
+
+/// Whether `op` on floats can set the `FE_INVALID`, `FE_DIVBYZERO`, or `FE_OVERFLOW` flag,
+/// which `CodegenOptions::strict_fp_exceptions` turns into a crash.
+/// The checked operations report those cases in their result instead.
+pub(crate) fn can_raise_fp_exception(op: LowLevel) -> bool {
+    use LowLevel::*;
+
+    matches!(
+        op,
+        NumAdd | NumSub | NumMul | NumDivFrac | NumPow | NumSqrtUnchecked | NumLogUnchecked
+    )
+}
+
+/// The `fenv.h` flags `build_check_fp_exception` checks, along with the message for each.
+/// Wasm has no floating-point status flags, so there is nothing to check.
+fn fp_exception_flags(architecture: Architecture) -> Option<[(u64, &'static str); 3]> {
+    match architecture {
+        Architecture::X86_64 | Architecture::X86_32 => Some([
+            (0x01, "float operation was invalid!"),
+            (0x04, "float division by zero!"),
+            (0x08, "float operation overflowed!"),
+        ]),
+        Architecture::Aarch64 | Architecture::Aarch32 => Some([
+            (0x01, "float operation was invalid!"),
+            (0x02, "float division by zero!"),
+            (0x04, "float operation overflowed!"),
+        ]),
+        Architecture::Wasm32 => None,
+    }
+}
+
+/// Calls `name`, which is `feclearexcept` or `fetestexcept` from the C standard library,
+/// with the flags `build_check_fp_exception` checks.
+fn call_fenv_function<'a, 'ctx, 'env>(
+    env: &Env<'a, 'ctx, 'env>,
+    name: &str,
+    flags: &[(u64, &'static str); 3],
+) -> IntValue<'ctx> {
+    let i32_type = env.context.i32_type();
+    let function = env.module.get_function(name).unwrap_or_else(|| {
+        let fn_type = i32_type.fn_type(&[i32_type.into()], false);
+        env.module
+            .add_function(name, fn_type, Some(Linkage::External))
+    });
+
+    let mask = flags.iter().fold(0, |mask, (flag, _)| mask | flag);
+    env.builder
+        .build_call(function, &[i32_type.const_int(mask, false).into()], name)
+        .try_as_basic_value()
+        .left()
+        .unwrap()
+        .into_int_value()
+}
+
+/// Clears the floating-point status flags, so that `build_check_fp_exception` only sees
+/// the ones set by the operations built in between.
+pub fn build_clear_fp_exceptions<'a, 'ctx, 'env>(env: &Env<'a, 'ctx, 'env>) {
+    if let Some(flags) = fp_exception_flags(env.target_info.architecture) {
+        call_fenv_function(env, "feclearexcept", &flags);
+    }
+}
+
+/// Calls `roc_panic` if a floating-point operation since `build_clear_fp_exceptions` was invalid,
+/// like `sqrt(-1.0)`, divided by zero, or overflowed. The message says which one it was.
+pub fn build_check_fp_exception<'a, 'ctx, 'env>(
+    env: &Env<'a, 'ctx, 'env>,
+    parent: FunctionValue<'ctx>,
+) {
+    let builder = env.builder;
+    let context = env.context;
+
+    let flags = match fp_exception_flags(env.target_info.architecture) {
+        Some(flags) => flags,
+        None => return,
+    };
+    let raised = call_fenv_function(env, "fetestexcept", &flags);
+
+    for (flag, message) in flags {
+        let flag = raised.get_type().const_int(flag, false);
+        let is_raised = builder.build_int_compare(
+            IntPredicate::NE,
+            builder.build_and(raised, flag, "fp_flag"),
+            raised.get_type().const_zero(),
+            "fp_flag_is_raised",
+        );
+
+        let throw_block = context.append_basic_block(parent, "fp_exception");
+        let next_block = context.append_basic_block(parent, "no_fp_exception");
+        builder.build_conditional_branch(is_raised, throw_block, next_block);
+
+        builder.position_at_end(throw_block);
+        throw_internal_exception(env, parent, message);
+
+        builder.position_at_end(next_block);
+    }
+}
+
+/// The constant `value` of `int_type`, which `value` has to fit in
+fn const_int_of_type(int_type: IntType<'_>, value: i128) -> IntValue<'_> {
+    // LLVM drops the words and bits the type does not have
+    int_type.const_int_arbitrary_precision(&[value as u64, (value >> 64) as u64])
+}
+
+/// `Num.toXChecked` between integers, as the `{ value, out_of_bounds }` struct of `return_type`.
+/// The value is zero if it is out of bounds, like it is in the Zig builtins.
+pub fn build_int_to_int_checked<'a, 'ctx, 'env>(
+    env: &Env<'a, 'ctx, 'env>,
+    arg: IntValue<'ctx>,
+    arg_width: IntWidth,
+    target_width: IntWidth,
+    return_type: StructType<'ctx>,
+) -> StructValue<'ctx> {
+    let builder = env.builder;
+    let arg_type = arg.get_type();
+    let (min, max) = arg_width.conversion_bounds(target_width);
+
+    let mut out_of_bounds = env.context.bool_type().const_zero();
+    if let Some(min) = min {
+        // only a signed argument can be below the minimum of the target
+        let below_min = builder.build_int_compare(
+            IntPredicate::SLT,
+            arg,
+            const_int_of_type(arg_type, min),
+            "below_min",
+        );
+        out_of_bounds = builder.build_or(out_of_bounds, below_min, "out_of_bounds");
+    }
+    if let Some(max) = max {
+        let predicate = if arg_width.is_signed() {
+            IntPredicate::SGT
+        } else {
+            IntPredicate::UGT
+        };
+        let above_max = builder.build_int_compare(
+            predicate,
+            arg,
+            const_int_of_type(arg_type, max as i128),
+            "above_max",
+        );
+        out_of_bounds = builder.build_or(out_of_bounds, above_max, "out_of_bounds");
+    }
+
+    // the sign of the argument decides how it is extended, e.g. 200u8 is 200i16
+    let target_type = int_type_from_int_width(env, target_width);
+    let converted =
+        builder.build_int_cast_sign_flag(arg, target_type, arg_width.is_signed(), "int_cast");
+    let value = builder.build_select(
+        out_of_bounds,
+        target_type.const_zero(),
+        converted,
+        "converted_int",
+    );
+
+    let result = builder
+        .build_insert_value(return_type.const_zero(), value, 0, "insert_value")
+        .unwrap();
+    let result = builder
+        .build_insert_value(result, out_of_bounds, 1, "insert_out_of_bounds")
+        .unwrap();
+
+    result.into_struct_value()
+}
+
+/// The integer type with the same size as `float_type`
+pub(crate) fn float_bits_type<'a, 'ctx, 'env>(
+    env: &Env<'a, 'ctx, 'env>,
+    float_type: FloatType<'ctx>,
+) -> IntType<'ctx> {
+    if float_type == env.context.f32_type() {
+        env.context.i32_type()
+    } else if float_type == env.context.f64_type() {
+        env.context.i64_type()
+    } else {
+        unreachable!(
+            "there is no integer type with the bits of a {:?}",
+            float_type
+        )
+    }
+}
+
+/// The bits of a float, as the integer of the same size.
+/// Nothing is converted, so this keeps the sign and payload of a NaN.
+pub fn build_num_to_bits<'a, 'ctx, 'env>(
+    env: &Env<'a, 'ctx, 'env>,
+    value: FloatValue<'ctx>,
+) -> IntValue<'ctx> {
+    let bits_type = float_bits_type(env, value.get_type());
+
+    env.builder
+        .build_bitcast(value, bits_type, "float_to_bits")
+        .into_int_value()
+}
+
+/// The float with the given bits; the inverse of [build_num_to_bits]
+pub fn build_bits_to_num<'a, 'ctx, 'env>(
+    env: &Env<'a, 'ctx, 'env>,
+    bits: IntValue<'ctx>,
+) -> FloatValue<'ctx> {
+    let float_type = match bits.get_type().get_bit_width() {
+        32 => env.context.f32_type(),
+        64 => env.context.f64_type(),
+        other => unreachable!("there is no float with the bits of a {}-bit integer", other),
+    };
+
+    env.builder
+        .build_bitcast(bits, float_type, "bits_to_float")
+        .into_float_value()
+}
+
+/// `Num.toF32Bits : F32 -> U32`
+pub fn build_f32_to_bits<'a, 'ctx, 'env>(
+    env: &Env<'a, 'ctx, 'env>,
+    value: FloatValue<'ctx>,
+) -> IntValue<'ctx> {
+    debug_assert_eq!(value.get_type(), env.context.f32_type());
+
+    build_num_to_bits(env, value)
+}
+
+/// `Num.toF64Bits : F64 -> U64`
+pub fn build_f64_to_bits<'a, 'ctx, 'env>(
+    env: &Env<'a, 'ctx, 'env>,
+    value: FloatValue<'ctx>,
+) -> IntValue<'ctx> {
+    debug_assert_eq!(value.get_type(), env.context.f64_type());
+
+    build_num_to_bits(env, value)
+}
+
+/// `Num.fromF32Bits : U32 -> F32`
+pub fn build_bits_to_f32<'a, 'ctx, 'env>(
+    env: &Env<'a, 'ctx, 'env>,
+    bits: IntValue<'ctx>,
+) -> FloatValue<'ctx> {
+    debug_assert_eq!(bits.get_type().get_bit_width(), 32);
+
+    build_bits_to_num(env, bits)
+}
+
+/// `Num.fromF64Bits : U64 -> F64`
+pub fn build_bits_to_f64<'a, 'ctx, 'env>(
+    env: &Env<'a, 'ctx, 'env>,
+    bits: IntValue<'ctx>,
+) -> FloatValue<'ctx> {
+    debug_assert_eq!(bits.get_type().get_bit_width(), 64);
+
+    build_bits_to_num(env, bits)
+}
+
+/// Generates `while cond() { body() }`, and leaves the builder after the loop.
+fn build_while<'a, 'ctx, 'env>(
+    env: &Env<'a, 'ctx, 'env>,
+    parent: FunctionValue<'ctx>,
+    name: &str,
+    cond: impl Fn() -> IntValue<'ctx>,
+    body: impl Fn(),
+) {
+    let builder = env.builder;
+    let context = env.context;
+
+    let cond_block = context.append_basic_block(parent, &format!("{}_cond", name));
+    let body_block = context.append_basic_block(parent, &format!("{}_body", name));
+    let after_block = context.append_basic_block(parent, &format!("{}_after", name));
+
+    builder.build_unconditional_branch(cond_block);
+
+    builder.position_at_end(cond_block);
+    builder.build_conditional_branch(cond(), body_block, after_block);
+
+    builder.position_at_end(body_block);
+    body();
+    builder.build_unconditional_branch(cond_block);
+
+    builder.position_at_end(after_block);
+}
@@ -1,11 +1,14 @@
-use crate::llvm::build::Env;
-use inkwell::values::{BasicValueEnum, PointerValue, StructValue};
-use inkwell::AddressSpace;
-use roc_builtins::bitcode;
+use crate::llvm::build::{create_entry_block_alloca, struct_from_fields, Env};
+use inkwell::module::Linkage;
+use inkwell::values::{
+    BasicValueEnum, FunctionValue, GlobalValue, IntValue, PointerValue, StructValue,
+};
+use inkwell::{AddressSpace, IntPredicate};
+use roc_builtins::bitcode::{self, IntWidth};
 use roc_mono::layout::{InLayout, Layout};
 use roc_target::PtrWidth;
 
-use super::bitcode::{call_str_bitcode_fn, BitcodeReturns};
+use super::bitcode::{call_list_bitcode_fn, call_str_bitcode_fn, BitcodeReturns};
 use super::build::BuilderExt;
 
 pub static CHAR_LAYOUT: InLayout = Layout::U8;
@@ -47,6 +50,302 @@ pub(crate) fn decode_from_utf8_result<'a, 'ctx, 'env>(
     }
 }
 
+/// Str.fromInt : I64 -> Str
+///
+/// The numbers from 0 to 99 are small strings of one or two digits, which are built inline:
+/// one digit is an offset from `'0'`, and two digits are looked up in a table of digit pairs.
+/// Any other number, including every negative one, goes through the division loop in zig.
+pub(crate) fn build_str_from_i64_fast<'a, 'ctx, 'env>(
+    env: &Env<'a, 'ctx, 'env>,
+    val: IntValue<'ctx>,
+) -> BasicValueEnum<'ctx> {
+    let builder = env.builder;
+    let ctx = env.context;
+
+    let parent = builder
+        .get_insert_block()
+        .and_then(|b| b.get_parent())
+        .unwrap();
+
+    let small_block = ctx.append_basic_block(parent, "str_from_int_small");
+    let large_block = ctx.append_basic_block(parent, "str_from_int_large");
+    let cont_block = ctx.append_basic_block(parent, "str_from_int_cont");
+
+    // a negative number is huge when it is compared as an unsigned one
+    let is_small = builder.build_int_compare(
+        IntPredicate::ULT,
+        val,
+        ctx.i64_type().const_int(100, false),
+        "is_below_100",
+    );
+    builder.build_conditional_branch(is_small, small_block, large_block);
+
+    builder.position_at_end(small_block);
+    let small_str = str_below_100(env, parent, val);
+    builder.build_unconditional_branch(cont_block);
+
+    builder.position_at_end(large_block);
+    let large_str = call_str_bitcode_fn(
+        env,
+        &[],
+        &[val.into()],
+        BitcodeReturns::Str,
+        &bitcode::STR_FROM_INT[IntWidth::I64],
+    );
+    let large_end = builder.get_insert_block().unwrap();
+    builder.build_unconditional_branch(cont_block);
+
+    builder.position_at_end(cont_block);
+    let phi = builder.build_phi(small_str.get_type(), "str_from_int");
+    phi.add_incoming(&[(&small_str, small_block), (&large_str, large_end)]);
+
+    phi.as_basic_value()
+}
+
+/// The small string of a number that is known to be below 100
+fn str_below_100<'a, 'ctx, 'env>(
+    env: &Env<'a, 'ctx, 'env>,
+    parent: FunctionValue<'ctx>,
+    val: IntValue<'ctx>,
+) -> BasicValueEnum<'ctx> {
+    let builder = env.builder;
+    let i8_type = env.context.i8_type();
+    let ptr_int = env.ptr_int();
+
+    let val = builder.build_int_truncate(val, i8_type, "below_100");
+    let is_one_digit = builder.build_int_compare(
+        IntPredicate::ULT,
+        val,
+        i8_type.const_int(10, false),
+        "is_one_digit",
+    );
+
+    let digit = builder.build_int_add(val, i8_type.const_int(b'0' as u64, false), "digit");
+    let digit = builder.build_int_z_extend(digit, ptr_int, "digit_word");
+
+    let table = builder.build_pointer_cast(
+        digit_pairs_table(env).as_pointer_value(),
+        i8_type.ptr_type(AddressSpace::default()),
+        "digit_pairs",
+    );
+    let tens_index = builder.build_int_mul(
+        builder.build_int_z_extend(val, ptr_int, "index"),
+        ptr_int.const_int(2, false),
+        "tens_index",
+    );
+    let ones_index = builder.build_int_add(tens_index, ptr_int.const_int(1, false), "ones_index");
+    let [tens, ones] = [tens_index, ones_index].map(|index| {
+        let ptr = unsafe { builder.new_build_in_bounds_gep(i8_type, table, &[index], "digit_ptr") };
+        let digit = builder
+            .new_build_load(i8_type, ptr, "digit")
+            .into_int_value();
+        builder.build_int_z_extend(digit, ptr_int, "digit_word")
+    });
+    let ones = builder.build_left_shift(ones, ptr_int.const_int(8, false), "ones_byte");
+    let two_digits = builder.build_or(tens, ones, "two_digits");
+
+    // the bytes go first, and the length with the small string flag goes in the last byte
+    let bytes = builder
+        .build_select(is_one_digit, digit, two_digits, "bytes")
+        .into_int_value();
+    let length = builder
+        .build_select(
+            is_one_digit,
+            ptr_int.const_int(1, false),
+            ptr_int.const_int(2, false),
+            "small_str_length",
+        )
+        .into_int_value();
+
+    small_str(env, parent, bytes, length)
+}
+
+/// A small string of the `length` bytes in the word `bytes`, the first of them in the lowest byte
+fn small_str<'a, 'ctx, 'env>(
+    env: &Env<'a, 'ctx, 'env>,
+    parent: FunctionValue<'ctx>,
+    bytes: IntValue<'ctx>,
+    length: IntValue<'ctx>,
+) -> BasicValueEnum<'ctx> {
+    let builder = env.builder;
+    let i8_type = env.context.i8_type();
+    let ptr_int = env.ptr_int();
+
+    let length = builder.build_or(
+        length,
+        ptr_int.const_int(roc_std::RocStr::MASK as u64, false),
+        "small_str_length",
+    );
+    let last_byte_shift = (env.target_info.ptr_width() as u64 - 1) * 8;
+    let capacity = builder.build_left_shift(
+        length,
+        ptr_int.const_int(last_byte_shift, false),
+        "capacity",
+    );
+
+    let ptr = builder.build_int_to_ptr(
+        bytes,
+        i8_type.ptr_type(AddressSpace::default()),
+        "to_u8_ptr",
+    );
+    let str_type = super::convert::zig_str_type(env);
+    let value = struct_from_fields(
+        env,
+        str_type,
+        [
+            (0, ptr.into()),
+            (1, ptr_int.const_zero().into()),
+            (2, capacity.into()),
+        ]
+        .into_iter(),
+    );
+
+    match env.target_info.ptr_width() {
+        PtrWidth::Bytes4 => value.into(),
+        PtrWidth::Bytes8 => {
+            let alloca = create_entry_block_alloca(env, parent, str_type.into(), "small_str");
+            builder.build_store(alloca, value);
+
+            alloca.into()
+        }
+    }
+}
+
+/// The UTF-8 encoding of a Unicode scalar value, as a small string of 1 to 4 bytes.
+/// A `U32` that is not a scalar value, like a surrogate, is encoded as U+FFFD instead.
+///
+/// Not used by `Str.appendScalar` yet, which still calls into zig.
+pub fn build_encode_utf8_scalar<'a, 'ctx, 'env>(
+    env: &Env<'a, 'ctx, 'env>,
+    scalar: IntValue<'ctx>,
+) -> BasicValueEnum<'ctx> {
+    let builder = env.builder;
+    let ptr_int = env.ptr_int();
+
+    let parent = builder
+        .get_insert_block()
+        .and_then(|b| b.get_parent())
+        .unwrap();
+
+    let (bytes, length) = encode_utf8_scalar(env, scalar);
+    let bytes = builder.build_int_z_extend(bytes, ptr_int, "utf8_bytes_word");
+    let length = builder.build_int_z_extend(length, ptr_int, "utf8_length_word");
+
+    small_str(env, parent, bytes, length)
+}
+
+/// The UTF-8 bytes of `scalar` packed into a `u32`, the first of them in the lowest byte,
+/// and how many of them there are
+fn encode_utf8_scalar<'ctx>(
+    env: &Env<'_, 'ctx, '_>,
+    scalar: IntValue<'ctx>,
+) -> (IntValue<'ctx>, IntValue<'ctx>) {
+    let builder = env.builder;
+    let u32_type = env.context.i32_type();
+    let int = |value: u64| u32_type.const_int(value, false);
+    let select =
+        |condition: IntValue<'ctx>, then: IntValue<'ctx>, otherwise: IntValue<'ctx>, name: &str| {
+            builder
+                .build_select(condition, then, otherwise, name)
+                .into_int_value()
+        };
+
+    // surrogates and anything above U+10FFFF become U+FFFD
+    let is_surrogate = builder.build_int_compare(
+        IntPredicate::EQ,
+        builder.build_and(scalar, int(0xFFFF_F800), "surrogate_bits"),
+        int(0xD800),
+        "is_surrogate",
+    );
+    let is_too_big =
+        builder.build_int_compare(IntPredicate::UGT, scalar, int(0x10FFFF), "is_too_big");
+    let is_invalid = builder.build_or(is_surrogate, is_too_big, "is_invalid");
+    let scalar = select(is_invalid, int(0xFFFD), scalar, "scalar");
+
+    // six bits of the scalar, starting at `shift`, as a continuation byte at `byte`
+    let continuation = |shift: u64, byte: u64, name: &str| {
+        let bits = builder.build_right_shift(scalar, int(shift), false, name);
+        let bits = builder.build_and(bits, int(0x3F), name);
+        let bits = builder.build_or(bits, int(0x80), name);
+        builder.build_left_shift(bits, int(byte * 8), name)
+    };
+    // the bits of the scalar from `shift` up, below the marker of the leading byte
+    let leading = |shift: u64, marker: u64, name: &str| {
+        let bits = builder.build_right_shift(scalar, int(shift), false, name);
+        builder.build_or(bits, int(marker), name)
+    };
+
+    let two_bytes = builder.build_or(
+        leading(6, 0xC0, "two_bytes_0"),
+        continuation(0, 1, "two_bytes_1"),
+        "two_bytes",
+    );
+    let three_bytes = builder.build_or(
+        builder.build_or(
+            leading(12, 0xE0, "three_bytes_0"),
+            continuation(6, 1, "three_bytes_1"),
+            "three_bytes_01",
+        ),
+        continuation(0, 2, "three_bytes_2"),
+        "three_bytes",
+    );
+    let four_bytes = builder.build_or(
+        builder.build_or(
+            leading(18, 0xF0, "four_bytes_0"),
+            continuation(12, 1, "four_bytes_1"),
+            "four_bytes_01",
+        ),
+        builder.build_or(
+            continuation(6, 2, "four_bytes_2"),
+            continuation(0, 3, "four_bytes_3"),
+            "four_bytes_23",
+        ),
+        "four_bytes",
+    );
+
+    let below =
+        |limit: u64, name| builder.build_int_compare(IntPredicate::ULT, scalar, int(limit), name);
+    let is_one_byte = below(0x80, "is_one_byte");
+    let is_two_bytes = below(0x800, "is_two_bytes");
+    let is_three_bytes = below(0x10000, "is_three_bytes");
+
+    let bytes = select(is_three_bytes, three_bytes, four_bytes, "utf8_bytes");
+    let bytes = select(is_two_bytes, two_bytes, bytes, "utf8_bytes");
+    let bytes = select(is_one_byte, scalar, bytes, "utf8_bytes");
+
+    let length = select(is_three_bytes, int(3), int(4), "utf8_length");
+    let length = select(is_two_bytes, int(2), length, "utf8_length");
+    let length = select(is_one_byte, int(1), length, "utf8_length");
+
+    (bytes, length)
+}
+
+/// "000102...9899", the two digits of every number below 100
+fn digit_pairs_table<'ctx>(env: &Env<'_, 'ctx, '_>) -> GlobalValue<'ctx> {
+    let name = "_str_digit_pairs";
+
+    match env.module.get_global(name) {
+        Some(global) => global,
+        None => {
+            let i8_type = env.context.i8_type();
+            let bytes: Vec<_> = (0..100u8)
+                .flat_map(|n| [b'0' + n / 10, b'0' + n % 10])
+                .map(|b| i8_type.const_int(b as u64, false))
+                .collect();
+
+            let global = env
+                .module
+                .add_global(i8_type.array_type(bytes.len() as u32), None, name);
+            global.set_initializer(&i8_type.const_array(&bytes));
+            global.set_constant(true);
+            global.set_unnamed_addr(true);
+            global.set_linkage(Linkage::Private);
+
+            global
+        }
+    }
+}
+
 /// Dec.toStr : Dec -> Str
 
 /// Str.equal : Str, Str -> Bool
@@ -63,3 +362,102 @@ pub(crate) fn str_equal<'a, 'ctx, 'env>(
         bitcode::STR_EQUAL,
     )
 }
+
+/// Str.joinWith : List Str, Str -> Str
+///
+/// The zig builtin sums up the length of the result first, so that the output is allocated once,
+/// and then copies the elements and separators in. Stay away from folding over `Str.concat`.
+pub(crate) fn str_join_with<'a, 'ctx, 'env>(
+    env: &Env<'a, 'ctx, 'env>,
+    list: BasicValueEnum<'ctx>,
+    separator: BasicValueEnum<'ctx>,
+) -> BasicValueEnum<'ctx> {
+    match env.target_info.ptr_width() {
+        PtrWidth::Bytes4 => {
+            // list and string are both stored as structs on the stack on 32-bit targets
+            call_str_bitcode_fn(
+                env,
+                &[list, separator],
+                &[],
+                BitcodeReturns::Str,
+                bitcode::STR_JOIN_WITH,
+            )
+        }
+        PtrWidth::Bytes8 => {
+            // on 64-bit targets, strings are stored as pointers, but that is not what zig expects
+            call_list_bitcode_fn(
+                env,
+                &[list.into_struct_value()],
+                &[separator],
+                BitcodeReturns::Str,
+                bitcode::STR_JOIN_WITH,
+            )
+        }
+    }
+}
+
+/// Str.nfcNormalize : Str -> Str
+///
+/// Not exposed by `Str` yet. The zig builtin only composes the Latin-1 letters for now.
+pub fn build_str_nfc_normalize<'a, 'ctx, 'env>(
+    env: &Env<'a, 'ctx, 'env>,
+    str_val: BasicValueEnum<'ctx>,
+) -> BasicValueEnum<'ctx> {
+    call_str_bitcode_fn(
+        env,
+        &[str_val],
+        &[],
+        BitcodeReturns::Str,
+        bitcode::STR_NFC_NORMALIZE,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bumpalo::Bump;
+    use inkwell::context::Context;
+    use inkwell::module::Module;
+
+    #[test]
+    fn encode_utf8_scalars() {
+        let arena = Bump::new();
+        let context = Context::create();
+        let module: &Module = arena.alloc(context.create_module("test"));
+        let builder = context.create_builder();
+        let debug_info = Env::new_debug_info(module, &target_lexicon::Triple::host());
+        let env = Env::for_tests(&arena, &context, module, &builder, &debug_info);
+
+        let fn_type = context.void_type().fn_type(&[], false);
+        let function = module.add_function("encode", fn_type, None);
+        builder.position_at_end(context.append_basic_block(function, "entry"));
+
+        // the builder folds constants, so the bytes of a constant scalar are constants as well
+        for (scalar, expected) in [
+            (0x41, "A"),
+            (0xE9, "é"),
+            (0x4E2D, "中"),
+            (0x1F600, "😀"),
+            (0xD800, "\u{FFFD}"),
+            (0x110000, "\u{FFFD}"),
+        ] {
+            let scalar = context.i32_type().const_int(scalar, false);
+            let (bytes, length) = encode_utf8_scalar(&env, scalar);
+
+            let mut word = [0; 4];
+            word[..expected.len()].copy_from_slice(expected.as_bytes());
+            assert_eq!(
+                bytes.get_zero_extended_constant(),
+                Some(u32::from_le_bytes(word) as u64),
+                "{}",
+                expected
+            );
+            assert_eq!(
+                length.get_zero_extended_constant(),
+                Some(expected.len() as u64),
+                "{}",
+                expected
+            );
+        }
+    }
+}
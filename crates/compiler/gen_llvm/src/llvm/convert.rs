@@ -1,4 +1,4 @@
-use crate::llvm::build::{BuilderExt, Env};
+use crate::llvm::build::{build_zero_fill, BuilderExt, Env};
 use bumpalo::collections::Vec;
 use inkwell::context::Context;
 use inkwell::types::{BasicType, BasicTypeEnum, FloatType, IntType, StructType};
@@ -360,10 +360,14 @@ impl<'ctx> RocUnion<'ctx> {
         env: &Env<'a, 'ctx, 'env>,
         data: StructValue<'ctx>,
         tag_id: Option<usize>,
+        zero_fill: bool,
     ) -> StructValue<'ctx> {
         debug_assert_eq!(tag_id.is_some(), self.tag_type.is_some());
 
         let tag_alloca = env.builder.build_alloca(self.struct_type(), "tag_alloca");
+        if zero_fill {
+            build_zero_fill(env, tag_alloca, self.struct_type().into());
+        }
 
         let data_buffer = env
             .builder
@@ -381,9 +385,8 @@ impl<'ctx> RocUnion<'ctx> {
             "to_data_ptr",
         );
 
-        // NOTE: the data may be smaller than the buffer, so there might be uninitialized
-        // bytes in the buffer. We should never touch those, but e.g. valgrind might not
-        // realize that. If that comes up, the solution is to just fill it with zeros
+        // NOTE: the data may be smaller than the buffer, so without `zero_fill` there might be
+        // uninitialized bytes in the buffer, which end up in hashes and byte-wise comparisons.
         env.builder.build_store(cast_pointer, data);
 
         // set the tag id
@@ -456,20 +459,61 @@ pub fn zig_num_parse_result_type<'a, 'ctx, 'env>(
     }
 }
 
-pub fn zig_to_int_checked_result_type<'a, 'ctx, 'env>(
-    env: &Env<'a, 'ctx, 'env>,
-    type_name: &str,
-) -> StructType<'ctx> {
-    let name = format!("num.ToIntCheckedResult({type_name})");
-
-    match env.module.get_struct_type(&name) {
-        Some(zig_type) => zig_type,
-        None => panic!("zig does not define the `{name}` type!"),
-    }
-}
-
 pub fn zig_with_overflow_roc_dec<'a, 'ctx, 'env>(env: &Env<'a, 'ctx, 'env>) -> StructType<'ctx> {
     env.module
         .get_struct_type("utils.WithOverflow(dec.RocDec)")
         .unwrap()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bumpalo::Bump;
+    use inkwell::targets::TargetData;
+    use roc_mono::layout::field_offsets;
+    use target_lexicon::Triple;
+
+    const X86_64_DATA_LAYOUT: &str = "e-m:e-i64:64-i128:128-f80:128-n8:16:32:64-S128";
+
+    #[test]
+    fn field_offsets_agree_with_llvm() {
+        let arena = Bump::new();
+        let context = Context::create();
+        let module = arena.alloc(context.create_module("test"));
+        let builder = context.create_builder();
+        let debug_info = Env::new_debug_info(module, &Triple::host());
+        let env = Env::for_tests(&arena, &context, module, &builder, &debug_info);
+        let target_data = TargetData::create(X86_64_DATA_LAYOUT);
+        let mut interner = STLayoutInterner::with_capacity(4, TargetInfo::default_x86_64());
+
+        let inner = interner.insert(Layout::struct_no_name_order(&[Layout::U32, Layout::U8]));
+
+        let corpus: &[&[InLayout]] = &[
+            &[Layout::I64, Layout::U8],
+            &[Layout::STR, Layout::U32, Layout::U16, Layout::BOOL],
+            &[Layout::I128, Layout::F64, Layout::F32, Layout::U8],
+            &[Layout::DEC, Layout::U8],
+            &[Layout::U8, Layout::I64, Layout::U16],
+            &[inner, Layout::U16, Layout::U8],
+            &[Layout::F64, inner, Layout::BOOL],
+        ];
+
+        for fields in corpus {
+            let struct_layout =
+                interner.insert(Layout::struct_no_name_order(arena.alloc_slice_copy(fields)));
+            let struct_type =
+                basic_type_from_layout(&env, &mut interner, struct_layout).into_struct_type();
+            let offsets = field_offsets(&interner, fields);
+
+            for (index, (offset, _)) in offsets.into_iter().enumerate() {
+                assert_eq!(
+                    target_data.offset_of_element(&struct_type, index as u32),
+                    Some(offset as u64),
+                    "offset of field {} in {:?}",
+                    index,
+                    fields
+                );
+            }
+        }
+    }
+}
@@ -1,9 +1,11 @@
 use crate::llvm::bitcode::call_void_bitcode_fn;
 use crate::llvm::build::{add_func, get_panic_msg_ptr, get_panic_tag_ptr, BuilderExt, C_CALL_CONV};
-use crate::llvm::build::{CCReturn, Env, FunctionSpec};
+use crate::llvm::build::{source_info_type, CCReturn, Env, FunctionSpec};
 use crate::llvm::convert::zig_str_type;
+use crate::llvm::memory_model::{LibcMemoryModel, MemoryModel};
 use inkwell::module::Linkage;
 use inkwell::types::BasicType;
+use inkwell::values::{BasicMetadataValueEnum, FunctionValue};
 use inkwell::AddressSpace;
 use roc_builtins::bitcode;
 
@@ -78,7 +80,13 @@ pub fn add_default_roc_externs(env: &Env<'_, '_, '_>) {
                         usize_type.into(),
                     ],
                 );
-                let fn_val = add_func(env.context, module, "realloc", fn_spec, Linkage::External);
+                let fn_val = add_func(
+                    env.context,
+                    module,
+                    LibcMemoryModel.fn_name_realloc(),
+                    fn_spec,
+                    Linkage::External,
+                );
 
                 let mut params = fn_val.get_param_iter();
                 let ptr_arg = params.next().unwrap();
@@ -199,6 +207,9 @@ pub fn add_sjlj_roc_panic(env: &Env<'_, '_, '_>) {
 
         let tag_id_arg = params.next().unwrap();
 
+        // where the crash is in the source, or null if the program does not know
+        let source_arg = params.next().unwrap();
+
         debug_assert!(params.next().is_none());
 
         let subprogram = env.new_subprogram("roc_panic");
@@ -211,6 +222,50 @@ pub fn add_sjlj_roc_panic(env: &Env<'_, '_, '_>) {
 
         builder.position_at_end(entry);
 
+        // print where the crash is in the source, if we know it
+        {
+            let source_ptr = source_arg.into_pointer_value();
+            let print_block = ctx.append_basic_block(fn_val, "print_source");
+            let store_block = ctx.append_basic_block(fn_val, "store_panic");
+
+            let is_null = builder.build_is_null(source_ptr, "source_is_null");
+            builder.build_conditional_branch(is_null, store_block, print_block);
+
+            builder.position_at_end(print_block);
+
+            let info_type = source_info_type(env);
+            let info_ptr = builder.build_pointer_cast(
+                source_ptr,
+                info_type.ptr_type(AddressSpace::default()),
+                "to_source_info",
+            );
+
+            let mut fields: [BasicMetadataValueEnum; 3] = [ctx.i32_type().const_zero().into(); 3];
+            for (index, field) in fields.iter_mut().enumerate() {
+                let field_ptr = builder
+                    .new_build_struct_gep(info_type, info_ptr, index as u32, "source_field_ptr")
+                    .unwrap();
+                let field_type = info_type.get_field_type_at_index(index as u32).unwrap();
+                *field = builder
+                    .new_build_load(field_type, field_ptr, "source_field")
+                    .into();
+            }
+
+            let format = builder
+                .build_global_string_ptr("Roc crashed at %s:%u:%u\n", "roc_panic_source_format")
+                .as_pointer_value();
+            let [file, line, column] = fields;
+
+            builder.build_call(
+                printf_function(env),
+                &[format.into(), file, line, column],
+                "print_source",
+            );
+            builder.build_unconditional_branch(store_block);
+
+            builder.position_at_end(store_block);
+        }
+
         // write our error message to the RocStr pointer
         {
             let loaded_roc_str = match env.target_info.ptr_width() {
@@ -259,6 +314,20 @@ pub fn add_sjlj_roc_panic(env: &Env<'_, '_, '_>) {
     }
 }
 
+/// libc `int printf(const char *format, ...)`
+fn printf_function<'ctx>(env: &Env<'_, 'ctx, '_>) -> FunctionValue<'ctx> {
+    match env.module.get_function("printf") {
+        Some(function) => function,
+        None => {
+            let i8_ptr_type = env.context.i8_type().ptr_type(AddressSpace::default());
+            let fn_type = env.context.i32_type().fn_type(&[i8_ptr_type.into()], true);
+
+            env.module
+                .add_function("printf", fn_type, Some(Linkage::External))
+        }
+    }
+}
+
 pub fn build_longjmp_call(env: &Env) {
     let jmp_buf = get_sjlj_buffer(env);
     if cfg!(target_arch = "aarch64") {
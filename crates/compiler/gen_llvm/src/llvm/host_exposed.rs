@@ -0,0 +1,88 @@
+//! A description of the functions that gen_llvm makes callable from the host.
+//!
+//! Glue generators use this instead of recovering the entry points from symbol names. Wrappers
+//! that are only ever passed to zig by pointer (e.g. the compare and transform wrappers) are not
+//! part of the interface.
+use roc_mono::layout::{layout_to_type_name, InLayout, LayoutInterner};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HostCallConv {
+    C,
+    Fast,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HostExposedFunction<'a> {
+    pub name: String,
+    pub arguments: Vec<InLayout<'a>>,
+    pub result: InLayout<'a>,
+    pub call_conv: HostCallConv,
+}
+
+/// Collects every externally-callable function while the module is being built.
+#[derive(Debug, Default)]
+pub struct HostExposed<'a> {
+    functions: Vec<HostExposedFunction<'a>>,
+}
+
+impl<'a> HostExposed<'a> {
+    pub fn register(
+        &mut self,
+        name: &str,
+        arguments: &[InLayout<'a>],
+        result: InLayout<'a>,
+        call_conv: HostCallConv,
+    ) {
+        debug_assert!(
+            self.functions.iter().all(|f| f.name != name),
+            "{} was exposed to the host twice",
+            name
+        );
+
+        self.functions.push(HostExposedFunction {
+            name: name.to_string(),
+            arguments: arguments.to_vec(),
+            result,
+            call_conv,
+        });
+    }
+
+    pub fn functions(&self) -> &[HostExposedFunction<'a>] {
+        &self.functions
+    }
+
+    /// A self-contained version of the collected functions, with the layouts replaced by their
+    /// type names, that the CLI can serialize to whatever format it needs.
+    pub fn to_interface<I>(&self, interner: &I) -> HostInterface
+    where
+        I: LayoutInterner<'a>,
+    {
+        let type_name = |layout: InLayout<'a>| layout_to_type_name(interner, interner.get(layout));
+
+        let functions = self
+            .functions
+            .iter()
+            .map(|function| HostInterfaceFunction {
+                name: function.name.clone(),
+                arguments: function.arguments.iter().copied().map(type_name).collect(),
+                result: type_name(function.result),
+                call_conv: function.call_conv,
+            })
+            .collect();
+
+        HostInterface { functions }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HostInterface {
+    pub functions: Vec<HostInterfaceFunction>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HostInterfaceFunction {
+    pub name: String,
+    pub arguments: Vec<String>,
+    pub result: String,
+    pub call_conv: HostCallConv,
+}
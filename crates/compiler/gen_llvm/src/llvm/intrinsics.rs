@@ -111,6 +111,13 @@ pub(crate) fn add_intrinsics<'ctx>(ctx: &'ctx Context, module: &Module<'ctx>) {
         i8_ptr_type.fn_type(&[], false),
     );
 
+    add_intrinsic(
+        ctx,
+        module,
+        LLVM_READ_CYCLE_COUNTER,
+        ctx.i64_type().fn_type(&[], false),
+    );
+
     add_float_intrinsic(ctx, module, &LLVM_LOG, |t| t.fn_type(&[t.into()], false));
     add_float_intrinsic(ctx, module, &LLVM_POW, |t| {
         t.fn_type(&[t.into(), t.into()], false)
@@ -122,6 +129,12 @@ pub(crate) fn add_intrinsics<'ctx>(ctx: &'ctx Context, module: &Module<'ctx>) {
         t.fn_type(&[t.into()], false)
     });
     add_float_intrinsic(ctx, module, &LLVM_FLOOR, |t| t.fn_type(&[t.into()], false));
+    add_float_intrinsic(ctx, module, &LLVM_MINNUM, |t| {
+        t.fn_type(&[t.into(), t.into()], false)
+    });
+    add_float_intrinsic(ctx, module, &LLVM_MAXNUM, |t| {
+        t.fn_type(&[t.into(), t.into()], false)
+    });
 
     add_int_intrinsic(ctx, module, &LLVM_ADD_WITH_OVERFLOW, |t| {
         let fields = [t.into(), i1_type.into()];
@@ -148,6 +161,21 @@ pub(crate) fn add_intrinsics<'ctx>(ctx: &'ctx Context, module: &Module<'ctx>) {
     add_int_intrinsic(ctx, module, &LLVM_SUB_SATURATED, |t| {
         t.fn_type(&[t.into(), t.into()], false)
     });
+
+    // bswap is only defined for integers with an even number of bytes, so there is no i8 variant
+    for (int_width, int_type) in [
+        (IntWidth::U16, ctx.i16_type()),
+        (IntWidth::U32, ctx.i32_type()),
+        (IntWidth::U64, ctx.i64_type()),
+        (IntWidth::U128, ctx.i128_type()),
+    ] {
+        add_intrinsic(
+            ctx,
+            module,
+            &LLVM_BSWAP[int_width],
+            int_type.fn_type(&[int_type.into()], false),
+        );
+    }
 }
 
 pub const LLVM_POW: IntrinsicName = float_intrinsic!("llvm.pow");
@@ -160,12 +188,15 @@ pub static LLVM_COS: IntrinsicName = float_intrinsic!("llvm.cos");
 pub static LLVM_CEILING: IntrinsicName = float_intrinsic!("llvm.ceil");
 pub static LLVM_FLOOR: IntrinsicName = float_intrinsic!("llvm.floor");
 pub static LLVM_ROUND: IntrinsicName = float_intrinsic!("llvm.round");
+pub static LLVM_MINNUM: IntrinsicName = float_intrinsic!("llvm.minnum");
+pub static LLVM_MAXNUM: IntrinsicName = float_intrinsic!("llvm.maxnum");
 
 pub static LLVM_MEMSET_I64: &str = "llvm.memset.p0i8.i64";
 pub static LLVM_MEMSET_I32: &str = "llvm.memset.p0i8.i32";
 
 pub static LLVM_FRAME_ADDRESS: &str = "llvm.frameaddress.p0i8";
 pub static LLVM_STACK_SAVE: &str = "llvm.stacksave";
+pub static LLVM_READ_CYCLE_COUNTER: &str = "llvm.readcyclecounter";
 
 pub static LLVM_SETJMP: &str = "llvm.eh.sjlj.setjmp";
 pub static LLVM_LONGJMP: &str = "llvm.eh.sjlj.longjmp";
@@ -179,6 +210,7 @@ pub const LLVM_MUL_WITH_OVERFLOW: IntrinsicName =
 
 pub const LLVM_ADD_SATURATED: IntrinsicName = llvm_int_intrinsic!("llvm.sadd.sat", "llvm.uadd.sat");
 pub const LLVM_SUB_SATURATED: IntrinsicName = llvm_int_intrinsic!("llvm.ssub.sat", "llvm.usub.sat");
+pub const LLVM_BSWAP: IntrinsicName = llvm_int_intrinsic!("llvm.bswap", "llvm.bswap");
 
 fn add_intrinsic<'ctx>(
     context: &Context,
@@ -24,24 +24,31 @@ use crate::llvm::{
         BitcodeReturns,
     },
     build::{
-        complex_bitcast_check_size, create_entry_block_alloca, function_value_by_func_spec,
-        load_roc_value, roc_function_call, BuilderExt, RocReturn,
+        build_box_map, complex_bitcast_check_size, create_entry_block_alloca,
+        function_value_by_func_spec, load_roc_value, roc_function_call, BuilderExt, RocReturn,
     },
+    build_encode::{list_append_num_le, num_from_bytes_le},
     build_list::{
+        build_list_join, build_list_max, build_list_min, build_list_product, build_list_sum,
         list_append_unsafe, list_capacity, list_concat, list_drop_at, list_get_unsafe, list_len,
         list_map, list_map2, list_map3, list_map4, list_prepend, list_replace_unsafe, list_reserve,
         list_sort_with, list_sublist, list_swap, list_symbol_to_c_abi, list_with_capacity,
         pass_update_mode,
     },
-    compare::{generic_eq, generic_neq},
-    convert::{
-        self, basic_type_from_layout, zig_num_parse_result_type, zig_to_int_checked_result_type,
+    build_num::{
+        build_add_with_overflow_i64, build_bits_to_f32, build_bits_to_f64,
+        build_check_fp_exception, build_clear_fp_exceptions, build_f32_to_bits, build_f64_to_bits,
+        build_gcd_u64, build_int_to_int_checked, build_integer_pow, build_lcm_u64,
+        can_raise_fp_exception,
     },
+    compare::{generic_eq, generic_neq},
+    convert::{self, basic_type_from_layout, zig_num_parse_result_type},
     intrinsics::{
         LLVM_ADD_SATURATED, LLVM_ADD_WITH_OVERFLOW, LLVM_CEILING, LLVM_COS, LLVM_FABS, LLVM_FLOOR,
-        LLVM_LOG, LLVM_MUL_WITH_OVERFLOW, LLVM_POW, LLVM_ROUND, LLVM_SIN, LLVM_SQRT,
-        LLVM_SUB_SATURATED, LLVM_SUB_WITH_OVERFLOW,
+        LLVM_LOG, LLVM_MUL_WITH_OVERFLOW, LLVM_POW, LLVM_READ_CYCLE_COUNTER, LLVM_ROUND, LLVM_SIN,
+        LLVM_SQRT, LLVM_SUB_SATURATED, LLVM_SUB_WITH_OVERFLOW,
     },
+    refcounting::increment_refcount_layout,
 };
 
 use super::{build::throw_internal_exception, convert::zig_with_overflow_roc_dec};
@@ -104,41 +111,19 @@ pub(crate) fn run_low_level<'a, 'ctx, 'env>(
             // Str.concat : Str, Str -> Str
             arguments!(string1, string2);
 
-            call_str_bitcode_fn(
-                env,
-                &[string1, string2],
-                &[],
-                BitcodeReturns::Str,
-                bitcode::STR_CONCAT,
-            )
+            // when the first string is unique, the builtin does not have to check its refcount
+            let fn_name = match update_mode {
+                UpdateMode::InPlace => bitcode::STR_CONCAT_IN_PLACE,
+                UpdateMode::Immutable => bitcode::STR_CONCAT,
+            };
+
+            call_str_bitcode_fn(env, &[string1, string2], &[], BitcodeReturns::Str, fn_name)
         }
         StrJoinWith => {
             // Str.joinWith : List Str, Str -> Str
             arguments!(list, string);
 
-            match env.target_info.ptr_width() {
-                PtrWidth::Bytes4 => {
-                    // list and string are both stored as structs on the stack on 32-bit targets
-                    call_str_bitcode_fn(
-                        env,
-                        &[list, string],
-                        &[],
-                        BitcodeReturns::Str,
-                        bitcode::STR_JOIN_WITH,
-                    )
-                }
-                PtrWidth::Bytes8 => {
-                    // on 64-bit targets, strings are stored as pointers, but that is not what zig expects
-
-                    call_list_bitcode_fn(
-                        env,
-                        &[list.into_struct_value()],
-                        &[string],
-                        BitcodeReturns::Str,
-                        bitcode::STR_JOIN_WITH,
-                    )
-                }
-            }
+            crate::llvm::build_str::str_join_with(env, list, string)
         }
         StrToScalars => {
             // Str.toScalars : Str -> List U32
@@ -188,8 +173,9 @@ pub(crate) fn run_low_level<'a, 'ctx, 'env>(
                 bitcode::STR_ENDS_WITH,
             )
         }
-        StrToNum => {
+        StrToNum | StrToU64Hex => {
             // Str.toNum : Str -> Result (Num *) {}
+            // Str.toU64Hex : Str -> Result U64 [InvalidHexStr]
             arguments!(string);
 
             let number_layout = match layout_interner.get(layout) {
@@ -198,10 +184,13 @@ pub(crate) fn run_low_level<'a, 'ctx, 'env>(
             };
 
             // match on the return layout to figure out which zig builtin we need
-            let intrinsic = match layout_interner.get(number_layout) {
-                Layout::Builtin(Builtin::Int(int_width)) => &bitcode::STR_TO_INT[int_width],
-                Layout::Builtin(Builtin::Float(float_width)) => &bitcode::STR_TO_FLOAT[float_width],
-                Layout::Builtin(Builtin::Decimal) => bitcode::DEC_FROM_STR,
+            let intrinsic = match (op, layout_interner.get(number_layout)) {
+                (StrToU64Hex, _) => bitcode::STR_TO_U64_HEX,
+                (_, Layout::Builtin(Builtin::Int(int_width))) => &bitcode::STR_TO_INT[int_width],
+                (_, Layout::Builtin(Builtin::Float(float_width))) => {
+                    &bitcode::STR_TO_FLOAT[float_width]
+                }
+                (_, Layout::Builtin(Builtin::Decimal)) => bitcode::DEC_FROM_STR,
                 _ => unreachable!(),
             };
 
@@ -318,12 +307,84 @@ pub(crate) fn run_low_level<'a, 'ctx, 'env>(
                 _ => unreachable!(),
             };
 
+            match int_width {
+                IntWidth::I64 => crate::llvm::build_str::build_str_from_i64_fast(env, int),
+                _ => call_str_bitcode_fn(
+                    env,
+                    &[],
+                    &[int.into()],
+                    BitcodeReturns::Str,
+                    &bitcode::STR_FROM_INT[int_width],
+                ),
+            }
+        }
+        NumGcd => {
+            // Num.gcd : U64, U64 -> U64
+            arguments!(a, b);
+
+            build_gcd_u64(env, a.into_int_value(), b.into_int_value()).into()
+        }
+        NumLcm => {
+            // Num.lcm : U64, U64 -> U64
+            arguments!(a, b);
+
+            build_lcm_u64(env, parent, a.into_int_value(), b.into_int_value()).into()
+        }
+        NumAddWithOverflow => {
+            // Num.addWithOverflow : I64, I64 -> { value : I64, didOverflow : Bool }
+            arguments!(lhs, rhs);
+
+            let (value, overflowed) =
+                build_add_with_overflow_i64(env, lhs.into_int_value(), rhs.into_int_value());
+
+            // the fields are sorted by alignment, so the value comes first
+            let struct_type =
+                basic_type_from_layout(env, layout_interner, layout).into_struct_type();
+            let with_value = env
+                .builder
+                .build_insert_value(struct_type.const_zero(), value, 0, "set_value")
+                .unwrap();
+
+            env.builder
+                .build_insert_value(with_value, overflowed, 1, "set_did_overflow")
+                .unwrap()
+                .into_struct_value()
+                .into()
+        }
+        NumToF32Bits => {
+            // Num.toF32Bits : F32 -> U32
+            arguments!(float);
+
+            build_f32_to_bits(env, float.into_float_value()).into()
+        }
+        NumToF64Bits => {
+            // Num.toF64Bits : F64 -> U64
+            arguments!(float);
+
+            build_f64_to_bits(env, float.into_float_value()).into()
+        }
+        NumFromF32Bits => {
+            // Num.fromF32Bits : U32 -> F32
+            arguments!(bits);
+
+            build_bits_to_f32(env, bits.into_int_value()).into()
+        }
+        NumFromF64Bits => {
+            // Num.fromF64Bits : U64 -> F64
+            arguments!(bits);
+
+            build_bits_to_f64(env, bits.into_int_value()).into()
+        }
+        NumToHexStr => {
+            // Num.toHexStr : U64 -> Str
+            arguments!(num);
+
             call_str_bitcode_fn(
                 env,
                 &[],
-                &[int.into()],
+                &[num],
                 BitcodeReturns::Str,
-                &bitcode::STR_FROM_INT[int_width],
+                bitcode::NUM_TO_HEX_STR,
             )
         }
         StrFromFloat => {
@@ -637,6 +698,45 @@ pub(crate) fn run_low_level<'a, 'ctx, 'env>(
 
             list_capacity(env.builder, list.into_struct_value()).into()
         }
+        ListSum | ListProduct | ListMinUnsafe | ListMaxUnsafe => {
+            // List.sum : List (Num a) -> Num a
+            // List.product : List (Num a) -> Num a
+            // List.minUnsafe : List (Num a) -> Num a
+            // List.maxUnsafe : List (Num a) -> Num a
+            arguments_with_layouts!((list, list_layout));
+
+            let element_layout = list_element_layout!(layout_interner, list_layout);
+            let build = match op {
+                ListSum => build_list_sum,
+                ListProduct => build_list_product,
+                ListMinUnsafe => build_list_min,
+                _ => build_list_max,
+            };
+
+            build(
+                env,
+                layout_interner,
+                parent,
+                list.into_struct_value(),
+                element_layout,
+            )
+        }
+        ListJoin => {
+            // List.join : List (List elem) -> List elem
+            arguments_with_layouts!((list, list_layout));
+
+            let inner_list_layout = list_element_layout!(layout_interner, list_layout);
+            let element_layout = list_element_layout!(layout_interner, inner_list_layout);
+
+            build_list_join(
+                env,
+                layout_interner,
+                layout_ids,
+                parent,
+                list.into_struct_value(),
+                element_layout,
+            )
+        }
         ListWithCapacity => {
             // List.withCapacity : Nat -> List a
             arguments!(list_len);
@@ -863,7 +963,6 @@ pub(crate) fn run_low_level<'a, 'ctx, 'env>(
                             build_int_unary_op(
                                 env,
                                 layout_interner,
-                                parent,
                                 arg.into_int_value(),
                                 int_width,
                                 int_type,
@@ -871,14 +970,16 @@ pub(crate) fn run_low_level<'a, 'ctx, 'env>(
                                 layout,
                             )
                         }
-                        Float(float_width) => build_float_unary_op(
-                            env,
-                            layout_interner,
-                            layout,
-                            arg.into_float_value(),
-                            op,
-                            float_width,
-                        ),
+                        Float(float_width) => checking_fp_exceptions(env, parent, op, || {
+                            build_float_unary_op(
+                                env,
+                                layout_interner,
+                                layout,
+                                arg.into_float_value(),
+                                op,
+                                float_width,
+                            )
+                        }),
                         _ => {
                             unreachable!("Compiler bug: tried to run numeric operation {:?} on invalid builtin layout: ({:?})", op, arg_layout);
                         }
@@ -914,6 +1015,25 @@ pub(crate) fn run_low_level<'a, 'ctx, 'env>(
                 bitcode::NUM_BYTES_TO_U32,
             )
         }
+        EncodeAppendNumLe => {
+            // Encode.appendNumLe : List U8, Num * -> List U8
+            arguments_with_layouts!((bytes, _bytes_layout), (number, number_layout));
+
+            list_append_num_le(
+                env,
+                layout_interner,
+                bytes,
+                number,
+                number_layout,
+                update_mode,
+            )
+        }
+        DecodeNumFromBytesLe => {
+            // Decode.numFromBytesLeLowlevel : List U8, Nat -> { aresult : Num *, binbounds : Bool }
+            arguments!(bytes, offset);
+
+            num_from_bytes_le(env, layout_interner, bytes, offset, layout)
+        }
         NumCompare => {
             arguments_with_layouts!((lhs_arg, lhs_layout), (rhs_arg, rhs_layout));
 
@@ -1175,7 +1295,7 @@ pub(crate) fn run_low_level<'a, 'ctx, 'env>(
             unimplemented!()
         }
 
-        ListMap | ListMap2 | ListMap3 | ListMap4 | ListSortWith => {
+        ListMap | ListMap2 | ListMap3 | ListMap4 | ListSortWith | BoxMap => {
             unreachable!("these are higher order, and are handled elsewhere")
         }
 
@@ -1183,10 +1303,16 @@ pub(crate) fn run_low_level<'a, 'ctx, 'env>(
             unreachable!("The {:?} operation is turned into mono Expr", op)
         }
 
-        PtrCast | RefCountInc | RefCountDec => {
+        PtrCast | RefCountInc | RefCountDec | RuntimeContextLoad | RuntimeContextStore => {
             unreachable!("Not used in LLVM backend: {:?}", op);
         }
 
+        MonotonicTicks => {
+            arguments!();
+
+            env.call_intrinsic(LLVM_READ_CYCLE_COUNTER, &[])
+        }
+
         Unreachable => match RocReturn::from_layout(env, layout_interner, layout) {
             RocReturn::Return => {
                 let basic_type = basic_type_from_layout(env, layout_interner, layout);
@@ -1394,11 +1520,7 @@ fn build_int_binop<'a, 'ctx, 'env>(
                 phi.as_basic_value()
             }
         }
-        NumPowInt => call_bitcode_fn(
-            env,
-            &[lhs.into(), rhs.into()],
-            &bitcode::NUM_POW_INT[int_width],
-        ),
+        NumPowInt => build_integer_pow(env, parent, int_width, lhs, rhs).into(),
         NumDivTruncUnchecked => {
             if int_width.is_signed() {
                 bd.build_int_signed_div(lhs, rhs, "div_int").into()
@@ -1457,13 +1579,15 @@ pub fn build_num_binop<'a, 'ctx, 'env>(
                     op,
                 ),
 
-                Float(float_width) => build_float_binop(
-                    env,
-                    float_width,
-                    lhs_arg.into_float_value(),
-                    rhs_arg.into_float_value(),
-                    op,
-                ),
+                Float(float_width) => checking_fp_exceptions(env, parent, op, || {
+                    build_float_binop(
+                        env,
+                        float_width,
+                        lhs_arg.into_float_value(),
+                        rhs_arg.into_float_value(),
+                        op,
+                    )
+                }),
 
                 Decimal => {
                     build_dec_binop(env, parent, lhs_arg, lhs_layout, rhs_arg, rhs_layout, op)
@@ -1592,7 +1716,26 @@ fn build_float_binop<'a, 'ctx, 'env>(
     }
 }
 
-fn throw_on_overflow<'a, 'ctx, 'env>(
+/// Builds a float operation, which crashes on a floating-point exception
+/// with `CodegenOptions::strict_fp_exceptions`.
+fn checking_fp_exceptions<'a, 'ctx, 'env>(
+    env: &Env<'a, 'ctx, 'env>,
+    parent: FunctionValue<'ctx>,
+    op: LowLevel,
+    build_op: impl FnOnce() -> BasicValueEnum<'ctx>,
+) -> BasicValueEnum<'ctx> {
+    if !(env.options.strict_fp_exceptions && can_raise_fp_exception(op)) {
+        return build_op();
+    }
+
+    build_clear_fp_exceptions(env);
+    let result = build_op();
+    build_check_fp_exception(env, parent);
+
+    result
+}
+
+pub(crate) fn throw_on_overflow<'a, 'ctx, 'env>(
     env: &Env<'a, 'ctx, 'env>,
     parent: FunctionValue<'ctx>,
     result: StructValue<'ctx>, // of the form { value: T, has_overflowed: bool }
@@ -1868,7 +2011,6 @@ fn int_type_signed_min(int_type: IntType) -> IntValue {
 fn build_int_unary_op<'a, 'ctx, 'env>(
     env: &Env<'a, 'ctx, 'env>,
     layout_interner: &mut STLayoutInterner<'a>,
-    parent: FunctionValue<'ctx>,
     arg: IntValue<'ctx>,
     arg_width: IntWidth,
     arg_int_type: IntType<'ctx>,
@@ -1919,131 +2061,10 @@ fn build_int_unary_op<'a, 'ctx, 'env>(
                 ),
             };
 
-            let arg_always_fits_in_target = (arg_width.stack_size() < target_int_width.stack_size()
-                && (
-                    // If the arg is unsigned, it will always fit in either a signed or unsigned
-                    // int of a larger width.
-                    !arg_width.is_signed()
-                    ||
-                    // Otherwise if the arg is signed, it will always fit in a signed int of a
-                    // larger width.
-                    (target_int_width.is_signed() )
-                )                )
-                || // Or if the two types are the same, they trivially fit.
-                arg_width == target_int_width;
-
             let return_type = convert::basic_type_from_layout(env, layout_interner, return_layout)
                 .into_struct_type();
 
-            if arg_always_fits_in_target {
-                // This is guaranteed to succeed so we can just make it an int cast and let LLVM
-                // optimize it away.
-                let target_int_type = convert::int_type_from_int_width(env, target_int_width);
-                let target_int_val: BasicValueEnum<'ctx> = env
-                    .builder
-                    .build_int_cast_sign_flag(
-                        arg,
-                        target_int_type,
-                        target_int_width.is_signed(),
-                        "int_cast",
-                    )
-                    .into();
-
-                let r = return_type.const_zero();
-                let r = bd
-                    .build_insert_value(r, target_int_val, 0, "converted_int")
-                    .unwrap();
-                let r = bd
-                    .build_insert_value(r, env.context.bool_type().const_zero(), 1, "out_of_bounds")
-                    .unwrap();
-
-                r.into_struct_value().into()
-            } else {
-                let intrinsic = if !arg_width.is_signed() {
-                    // We are trying to convert from unsigned to signed/unsigned of same or lesser width, e.g.
-                    // u16 -> i16, u16 -> i8, or u16 -> u8. We only need to check that the argument
-                    // value fits in the MAX target type value.
-                    &bitcode::NUM_INT_TO_INT_CHECKING_MAX[target_int_width][arg_width]
-                } else {
-                    // We are trying to convert from signed to signed/unsigned of same or lesser width, e.g.
-                    // i16 -> u16, i16 -> i8, or i16 -> u8. We need to check that the argument value fits in
-                    // the MAX and MIN target type.
-                    &bitcode::NUM_INT_TO_INT_CHECKING_MAX_AND_MIN[target_int_width][arg_width]
-                };
-
-                let result = match env.target_info.ptr_width() {
-                    PtrWidth::Bytes4 => {
-                        let zig_function = env.module.get_function(intrinsic).unwrap();
-                        let zig_function_type = zig_function.get_type();
-
-                        match zig_function_type.get_return_type() {
-                            Some(_) => call_str_bitcode_fn(
-                                env,
-                                &[],
-                                &[arg.into()],
-                                BitcodeReturns::Basic,
-                                intrinsic,
-                            ),
-                            None => {
-                                let return_type = zig_to_int_checked_result_type(
-                                    env,
-                                    target_int_width.type_name(),
-                                );
-
-                                let zig_return_alloca = create_entry_block_alloca(
-                                    env,
-                                    parent,
-                                    return_type.into(),
-                                    "num_to_int",
-                                );
-
-                                call_void_bitcode_fn(
-                                    env,
-                                    &[zig_return_alloca.into(), arg.into()],
-                                    intrinsic,
-                                );
-
-                                let roc_return_type =
-                                    basic_type_from_layout(env, layout_interner, return_layout)
-                                        .ptr_type(AddressSpace::default());
-
-                                let roc_return_alloca = env.builder.build_pointer_cast(
-                                    zig_return_alloca,
-                                    roc_return_type,
-                                    "cast_to_roc",
-                                );
-
-                                load_roc_value(
-                                    env,
-                                    layout_interner,
-                                    return_layout,
-                                    roc_return_alloca,
-                                    "num_to_int",
-                                )
-                            }
-                        }
-                    }
-                    PtrWidth::Bytes8 => {
-                        if target_int_width.stack_size() as usize > env.target_info.ptr_size() {
-                            let bitcode_return_type =
-                                zig_to_int_checked_result_type(env, target_int_width.type_name());
-
-                            call_bitcode_fn_fixing_for_convention(
-                                env,
-                                layout_interner,
-                                bitcode_return_type,
-                                &[arg.into()],
-                                return_layout,
-                                intrinsic,
-                            )
-                        } else {
-                            call_bitcode_fn(env, &[arg.into()], intrinsic)
-                        }
-                    }
-                };
-
-                complex_bitcast_check_size(env, result, return_type.into(), "cast_bitpacked")
-            }
+            build_int_to_int_checked(env, arg, arg_width, target_int_width, return_type).into()
         }
         _ => {
             unreachable!("Unrecognized int unary operation: {:?}", op);
@@ -2522,16 +2543,21 @@ pub(crate) fn run_higher_order_low_level<'a, 'ctx, 'env>(
 
             match layout_interner.get(list_layout) {
                 Layout::Builtin(Builtin::List(element_layout)) => {
-                    use crate::llvm::bitcode::build_compare_wrapper;
+                    use crate::llvm::bitcode::{
+                        build_compare_wrapper, OrderingTagIds, WrapperPointers,
+                    };
 
                     let argument_layouts = &[element_layout, element_layout];
 
+                    // the sort hands the wrapper pointers into the list's buffer
                     let compare_wrapper = build_compare_wrapper(
                         env,
                         layout_interner,
                         function,
                         closure_layout,
                         element_layout,
+                        WrapperPointers::Interior,
+                        OrderingTagIds::of_roc_ordering(),
                     )
                     .as_global_value()
                     .as_pointer_value();
@@ -2560,6 +2586,46 @@ pub(crate) fn run_higher_order_low_level<'a, 'ctx, 'env>(
                 _ => unreachable!("invalid list layout"),
             }
         }
+        BoxMap { boxed } => {
+            // Box.map : Box a, (a -> b) -> Box b
+            let (box_value, box_layout) = load_symbol_and_layout(scope, boxed);
+
+            let (function, closure, closure_layout) = function_details!();
+
+            match layout_interner.get(box_layout) {
+                Layout::Boxed(inner_layout) => {
+                    // the closure data is passed last, when there is any
+                    let closure_data = (argument_layouts.len() > 1).then(|| {
+                        if function_owns_closure_data {
+                            // this lowlevel only borrows the closure data
+                            increment_refcount_layout(
+                                env,
+                                layout_interner,
+                                layout_ids,
+                                1,
+                                closure,
+                                closure_layout.runtime_representation(),
+                            );
+                        }
+
+                        closure
+                    });
+
+                    build_box_map(
+                        env,
+                        layout_interner,
+                        layout_ids,
+                        box_value.into_pointer_value(),
+                        function,
+                        closure_data,
+                        inner_layout,
+                        result_layout,
+                    )
+                    .into()
+                }
+                _ => unreachable!("invalid box layout"),
+            }
+        }
     }
 }
 
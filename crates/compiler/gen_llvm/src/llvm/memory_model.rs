@@ -0,0 +1,293 @@
+//! Which functions the generated code calls to allocate and free memory, and to crash.
+//!
+//! The builtins always call `roc_alloc`, `roc_realloc`, `roc_dealloc` and `roc_panic`,
+//! so this only changes the calls in the code that the LLVM backend generates itself,
+//! unless `add_host_symbol_aliases` forwards the standard names too.
+use crate::llvm::build::{add_func, CCReturn, Env, FunctionSpec, C_CALL_CONV};
+use inkwell::module::Linkage;
+use inkwell::types::{BasicType, BasicTypeEnum};
+use inkwell::values::{BasicMetadataValueEnum, FunctionValue};
+use inkwell::AddressSpace;
+use roc_builtins::host_symbols::HostSymbols;
+use roc_error_macros::internal_error;
+
+pub trait MemoryModel {
+    fn fn_name_alloc(&self) -> &'static str;
+    fn fn_name_realloc(&self) -> &'static str;
+    fn fn_name_dealloc(&self) -> &'static str;
+    /// Takes the same arguments as `roc_panic`, and never returns
+    fn fn_name_panic(&self) -> &'static str;
+
+    /// Whether the functions take an alignment, like `roc_alloc` does.
+    /// Otherwise they are called like `malloc`, `realloc`, and `free`.
+    fn takes_alignment(&self) -> bool;
+}
+
+/// The functions the host provides, or that `add_default_roc_externs` defines without a host
+pub struct RocRuntimeMemoryModel;
+
+impl MemoryModel for RocRuntimeMemoryModel {
+    fn fn_name_alloc(&self) -> &'static str {
+        HostSymbols::STANDARD.alloc
+    }
+
+    fn fn_name_realloc(&self) -> &'static str {
+        HostSymbols::STANDARD.realloc
+    }
+
+    fn fn_name_dealloc(&self) -> &'static str {
+        HostSymbols::STANDARD.dealloc
+    }
+
+    fn fn_name_panic(&self) -> &'static str {
+        HostSymbols::STANDARD.panic
+    }
+
+    fn takes_alignment(&self) -> bool {
+        true
+    }
+}
+
+/// Functions that the host provides under other names, which take the same arguments as the
+/// standard ones
+impl MemoryModel for HostSymbols {
+    fn fn_name_alloc(&self) -> &'static str {
+        self.alloc
+    }
+
+    fn fn_name_realloc(&self) -> &'static str {
+        self.realloc
+    }
+
+    fn fn_name_dealloc(&self) -> &'static str {
+        self.dealloc
+    }
+
+    fn fn_name_panic(&self) -> &'static str {
+        self.panic
+    }
+
+    fn takes_alignment(&self) -> bool {
+        true
+    }
+}
+
+/// libc's allocator, for tests that do not link a host or the Roc runtime
+pub struct LibcMemoryModel;
+
+impl MemoryModel for LibcMemoryModel {
+    fn fn_name_alloc(&self) -> &'static str {
+        "malloc"
+    }
+
+    fn fn_name_realloc(&self) -> &'static str {
+        "realloc"
+    }
+
+    fn fn_name_dealloc(&self) -> &'static str {
+        "free"
+    }
+
+    fn fn_name_panic(&self) -> &'static str {
+        // libc has nothing like it, the tests define `roc_panic` themselves
+        HostSymbols::STANDARD.panic
+    }
+
+    fn takes_alignment(&self) -> bool {
+        false
+    }
+}
+
+/// The function that allocates memory in `env.memory_model`: `(size[, alignment]) -> ptr`
+pub(crate) fn alloc_function<'ctx>(env: &Env<'_, 'ctx, '_>) -> FunctionValue<'ctx> {
+    let i8_ptr_type = env.context.i8_type().ptr_type(AddressSpace::default());
+    let mut params = vec![env.ptr_int().into()];
+    if env.memory_model.takes_alignment() {
+        params.push(env.alignment_type().into());
+    }
+
+    get_or_declare(
+        env,
+        env.memory_model.fn_name_alloc(),
+        Some(i8_ptr_type.as_basic_type_enum()),
+        &params,
+    )
+}
+
+/// The function that frees memory in `env.memory_model`: `(ptr[, alignment]) -> void`
+pub(crate) fn dealloc_function<'ctx>(env: &Env<'_, 'ctx, '_>) -> FunctionValue<'ctx> {
+    let i8_ptr_type = env.context.i8_type().ptr_type(AddressSpace::default());
+    let mut params = vec![i8_ptr_type.as_basic_type_enum()];
+    if env.memory_model.takes_alignment() {
+        params.push(env.alignment_type().into());
+    }
+
+    get_or_declare(env, env.memory_model.fn_name_dealloc(), None, &params)
+}
+
+/// The function that crashes in `env.memory_model`, which is declared like `roc_panic`
+pub(crate) fn panic_function<'ctx>(env: &Env<'_, 'ctx, '_>) -> FunctionValue<'ctx> {
+    declare_like_standard(
+        env,
+        env.memory_model.fn_name_panic(),
+        HostSymbols::STANDARD.panic,
+    )
+}
+
+/// Defines the standard functions that `symbols` renames, e.g. `roc_alloc`, so that they call the
+/// functions of the host. That way the builtins, which call the standard names, use the functions
+/// of the host too. This takes the place of the definitions of `add_default_roc_externs`.
+pub fn add_host_symbol_aliases(env: &Env<'_, '_, '_>, symbols: &HostSymbols) {
+    for (standard_name, name) in symbols.renamed() {
+        let alias = env.module.get_function(standard_name).unwrap();
+        if alias.count_basic_blocks() > 0 {
+            internal_error!(
+                "{} is already defined, so it can not call {}",
+                standard_name,
+                name
+            );
+        }
+
+        let target = declare_like_standard(env, name, standard_name);
+
+        let entry = env.context.append_basic_block(alias, "entry");
+        env.builder.position_at_end(entry);
+
+        let arguments: std::vec::Vec<BasicMetadataValueEnum> =
+            alias.get_param_iter().map(|param| param.into()).collect();
+        let call = env.builder.build_call(target, &arguments, "call_host");
+        call.set_call_convention(C_CALL_CONV);
+
+        match call.try_as_basic_value().left() {
+            Some(value) => env.builder.build_return(Some(&value)),
+            None => env.builder.build_return(None),
+        };
+    }
+}
+
+/// `name`, declared with the type that the builtins declare `standard_name` with
+fn declare_like_standard<'ctx>(
+    env: &Env<'_, 'ctx, '_>,
+    name: &str,
+    standard_name: &str,
+) -> FunctionValue<'ctx> {
+    match env.module.get_function(name) {
+        Some(function) => function,
+        None => {
+            let standard = env.module.get_function(standard_name).unwrap();
+            let function =
+                env.module
+                    .add_function(name, standard.get_type(), Some(Linkage::External));
+            function.set_call_conventions(C_CALL_CONV);
+
+            function
+        }
+    }
+}
+
+/// The builtins already declare the Roc functions, but the libc ones may be missing.
+fn get_or_declare<'ctx>(
+    env: &Env<'_, 'ctx, '_>,
+    name: &str,
+    return_type: Option<BasicTypeEnum<'ctx>>,
+    params: &[BasicTypeEnum<'ctx>],
+) -> FunctionValue<'ctx> {
+    match env.module.get_function(name) {
+        Some(function) => function,
+        None => {
+            let cc_return = if return_type.is_some() {
+                CCReturn::Return
+            } else {
+                CCReturn::Void
+            };
+            let fn_spec = FunctionSpec::cconv(env, cc_return, return_type, params);
+
+            add_func(env.context, env.module, name, fn_spec, Linkage::External)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llvm::build::{module_from_builtins, LlvmBackendMode};
+    use bumpalo::Bump;
+    use inkwell::context::Context;
+    use inkwell::module::Module;
+    use inkwell::values::InstructionOpcode;
+    use target_lexicon::Triple;
+
+    /// The names of the functions that `function` calls
+    fn callees(function: FunctionValue<'_>) -> std::vec::Vec<String> {
+        let mut names = std::vec::Vec::new();
+        for block in function.get_basic_blocks() {
+            let mut instruction = block.get_first_instruction();
+            while let Some(current) = instruction {
+                if current.get_opcode() == InstructionOpcode::Call {
+                    // the callee is the last operand
+                    let callee = current
+                        .get_operand(current.get_num_operands() - 1)
+                        .unwrap()
+                        .left()
+                        .unwrap()
+                        .into_pointer_value();
+                    names.push(callee.get_name().to_string_lossy().into_owned());
+                }
+                instruction = current.get_next_instruction();
+            }
+        }
+
+        names
+    }
+
+    #[test]
+    fn generated_calls_and_aliases_use_the_host_names() {
+        let symbols = HostSymbols {
+            alloc: "arena_alloc",
+            dealloc: "arena_free",
+            panic: "host_crash",
+            ..HostSymbols::STANDARD
+        };
+
+        let arena = Bump::new();
+        let context = Context::create();
+        let triple = Triple::host();
+        let module: &Module = arena.alloc(module_from_builtins(&triple, &context, "test"));
+        let builder = context.create_builder();
+        let debug_info = Env::new_debug_info(module, &triple);
+        let env = Env {
+            mode: LlvmBackendMode::Binary,
+            memory_model: &symbols,
+            ..Env::for_tests(&arena, &context, module, &builder, &debug_info)
+        };
+
+        let host_fn = module.add_function("host", context.void_type().fn_type(&[], false), None);
+        builder.position_at_end(context.append_basic_block(host_fn, "entry"));
+        let size = env.ptr_int().const_int(16, false);
+        let ptr = env.call_alloc(size, 8);
+        env.call_dealloc(ptr, 8);
+        builder.build_return(None);
+
+        assert_eq!(callees(host_fn), ["arena_alloc", "arena_free"]);
+
+        // the host function crashes like `roc_panic` does
+        let panic = panic_function(&env);
+        assert_eq!(panic.get_name().to_str(), Ok("host_crash"));
+        assert_eq!(
+            panic.get_type(),
+            module.get_function("roc_panic").unwrap().get_type()
+        );
+
+        add_host_symbol_aliases(&env, &symbols);
+
+        for (standard_name, name) in symbols.renamed() {
+            let alias = module.get_function(standard_name).unwrap();
+            assert_eq!(callees(alias), [name]);
+            assert!(alias.verify(true), "{}", standard_name);
+        }
+
+        // `roc_realloc` keeps its name, so it is left to the host
+        let realloc = module.get_function("roc_realloc").unwrap();
+        assert_eq!(realloc.count_basic_blocks(), 0);
+    }
+}
@@ -1,11 +1,16 @@
 pub mod bitcode;
 pub mod build;
+pub mod build_encode;
 pub mod build_list;
+pub mod build_num;
 pub mod build_str;
 pub mod compare;
 pub mod convert;
 mod expect;
 pub mod externs;
+pub mod host_exposed;
 mod intrinsics;
 mod lowlevel;
+pub mod memory_model;
 pub mod refcounting;
+pub mod verify;
@@ -2,10 +2,12 @@ use crate::debug_info_init;
 use crate::llvm::bitcode::call_void_bitcode_fn;
 use crate::llvm::build::BuilderExt;
 use crate::llvm::build::{
-    add_func, cast_basic_basic, get_tag_id, tag_pointer_clear_tag_id, use_roc_value, Env,
-    FAST_CALL_CONV,
+    add_func, build_global_constant, cast_basic_basic, create_entry_block_alloca, get_tag_id,
+    tag_pointer_clear_tag_id, use_roc_value, Env, FAST_CALL_CONV,
+};
+use crate::llvm::build_list::{
+    incrementing_elem_loop, incrementing_index_loop, list_capacity, load_list,
 };
-use crate::llvm::build_list::{incrementing_elem_loop, list_capacity, load_list};
 use crate::llvm::convert::{basic_type_from_layout, zig_str_type, RocUnion};
 use bumpalo::collections::Vec;
 use inkwell::basic_block::BasicBlock;
@@ -13,10 +15,12 @@ use inkwell::module::Linkage;
 use inkwell::types::{AnyTypeEnum, BasicMetadataTypeEnum, BasicType, BasicTypeEnum};
 use inkwell::values::{BasicValueEnum, FunctionValue, IntValue, PointerValue, StructValue};
 use inkwell::{AddressSpace, IntPredicate};
+use roc_builtins::rc_layout::{refcount_offset, refcount_one};
 use roc_module::symbol::Interns;
 use roc_module::symbol::Symbol;
 use roc_mono::layout::{
-    Builtin, InLayout, Layout, LayoutIds, LayoutInterner, STLayoutInterner, UnionLayout,
+    field_offsets, Builtin, InLayout, Layout, LayoutIds, LayoutInterner, STLayoutInterner,
+    UnionLayout,
 };
 
 use super::build::{cast_if_necessary_for_opaque_recursive_pointers, load_roc_value, FunctionSpec};
@@ -50,27 +54,26 @@ impl<'ctx> PointerToRefcount<'ctx> {
         data_ptr: PointerValue<'ctx>,
     ) -> Self {
         let builder = env.builder;
-        // pointer to usize
-        let refcount_type = env.ptr_int();
-        let refcount_ptr_type = refcount_type.ptr_type(AddressSpace::default());
+        let i8_ptr_type = env.context.i8_type().ptr_type(AddressSpace::default());
 
-        let ptr_as_usize_ptr =
-            builder.build_pointer_cast(data_ptr, refcount_ptr_type, "as_usize_ptr");
+        let ptr_as_i8_ptr = builder.build_pointer_cast(data_ptr, i8_ptr_type, "as_i8_ptr");
 
-        // get a pointer to index -1
-        let index_intvalue = refcount_type.const_int(-1_i64 as u64, false);
+        let offset = refcount_offset(env.target_info.ptr_width());
+        let offset_intvalue = env.ptr_int().const_int(offset as i64 as u64, true);
         let refcount_ptr = unsafe {
             builder.new_build_in_bounds_gep(
-                env.ptr_int(),
-                ptr_as_usize_ptr,
-                &[index_intvalue],
+                env.context.i8_type(),
+                ptr_as_i8_ptr,
+                &[offset_intvalue],
                 "get_rc_ptr",
             )
         };
 
-        Self {
-            value: refcount_ptr,
-        }
+        // pointer to usize
+        let refcount_ptr_type = env.ptr_int().ptr_type(AddressSpace::default());
+        let value = builder.build_pointer_cast(refcount_ptr, refcount_ptr_type, "as_usize_ptr");
+
+        Self { value }
     }
 
     fn from_list_wrapper(env: &Env<'_, 'ctx, '_>, list_wrapper: StructValue<'ctx>) -> Self {
@@ -85,14 +88,9 @@ impl<'ctx> PointerToRefcount<'ctx> {
 
     pub fn is_1<'a, 'env>(&self, env: &Env<'a, 'ctx, 'env>) -> IntValue<'ctx> {
         let current = self.get_refcount(env);
-        let one = match env.target_info.ptr_width() {
-            roc_target::PtrWidth::Bytes4 => {
-                env.context.i32_type().const_int(i32::MIN as u64, false)
-            }
-            roc_target::PtrWidth::Bytes8 => {
-                env.context.i64_type().const_int(i64::MIN as u64, false)
-            }
-        };
+        let one = env
+            .ptr_int()
+            .const_int(refcount_one(env.target_info.ptr_width()) as u64, true);
 
         env.builder
             .build_int_compare(IntPredicate::EQ, current, one, "is_one")
@@ -341,8 +339,48 @@ fn modify_refcount_struct_help<'a, 'ctx, 'env>(
 
     let wrapper_struct = arg_val.into_struct_value();
 
+    // the refcounted fields, grouped by their layout
+    let mut groups: std::vec::Vec<(InLayout<'a>, std::vec::Vec<usize>)> = std::vec::Vec::new();
     for (i, field_layout) in layouts.iter().enumerate() {
         if layout_interner.contains_refcounted(*field_layout) {
+            match groups.iter_mut().find(|(layout, _)| layout == field_layout) {
+                Some((_, indices)) => indices.push(i),
+                None => groups.push((*field_layout, vec![i])),
+            }
+        }
+    }
+
+    let mut struct_ptr = None;
+
+    for (field_layout, indices) in groups {
+        if indices.len() >= FIELD_LOOP_MIN_FIELDS {
+            let struct_ptr = *struct_ptr.get_or_insert_with(|| {
+                let struct_type = wrapper_struct.get_type();
+                let alloca =
+                    create_entry_block_alloca(env, fn_val, struct_type.into(), "struct_fields");
+                builder.build_store(alloca, wrapper_struct);
+
+                alloca
+            });
+
+            let offsets = field_offsets(layout_interner, layouts);
+            let offsets: std::vec::Vec<u32> = indices.iter().map(|i| offsets[*i].0).collect();
+
+            modify_refcount_fields_in_loop(
+                env,
+                layout_interner,
+                layout_ids,
+                mode,
+                fn_val,
+                struct_ptr,
+                field_layout,
+                &offsets,
+            );
+
+            continue;
+        }
+
+        for i in indices {
             let raw_value = env
                 .builder
                 .build_extract_value(wrapper_struct, i as u32, "decrement_struct_field")
@@ -351,7 +389,7 @@ fn modify_refcount_struct_help<'a, 'ctx, 'env>(
             let field_value = use_roc_value(
                 env,
                 layout_interner,
-                *field_layout,
+                field_layout,
                 raw_value,
                 "load_struct_tag_field_for_decrement",
             );
@@ -362,7 +400,7 @@ fn modify_refcount_struct_help<'a, 'ctx, 'env>(
                 layout_ids,
                 mode.to_call_mode(fn_val),
                 field_value,
-                *field_layout,
+                field_layout,
             );
         }
     }
@@ -370,6 +408,96 @@ fn modify_refcount_struct_help<'a, 'ctx, 'env>(
     builder.build_return(None);
 }
 
+/// Structs with at least this many refcounted fields of one layout modify the refcounts of those
+/// fields in a loop, rather than with a call for every field
+const FIELD_LOOP_MIN_FIELDS: usize = 4;
+
+/// Modifies the refcounts of the fields of `struct_ptr` at the byte `offsets`, which all have
+/// `field_layout`. The offsets are put in the data section, and the loop goes over them.
+#[allow(clippy::too_many_arguments)]
+fn modify_refcount_fields_in_loop<'a, 'ctx, 'env>(
+    env: &Env<'a, 'ctx, 'env>,
+    layout_interner: &mut STLayoutInterner<'a>,
+    layout_ids: &mut LayoutIds<'a>,
+    mode: Mode,
+    fn_val: FunctionValue<'ctx>,
+    struct_ptr: PointerValue<'ctx>,
+    field_layout: InLayout<'a>,
+    offsets: &[u32],
+) {
+    let builder = env.builder;
+    let i8_type = env.context.i8_type();
+    let i32_type = env.context.i32_type();
+
+    let offset_values: std::vec::Vec<_> = offsets
+        .iter()
+        .map(|offset| i32_type.const_int(*offset as u64, false))
+        .collect();
+    let offsets_array = i32_type.const_array(&offset_values);
+    let offsets_global = build_global_constant(
+        env,
+        layout_interner,
+        &format!("{}_field_offsets", fn_val.get_name().to_string_lossy()),
+        offsets_array.into(),
+        Layout::U32,
+    );
+
+    let bytes_ptr = builder.build_pointer_cast(
+        struct_ptr,
+        i8_type.ptr_type(AddressSpace::default()),
+        "struct_bytes",
+    );
+    let field_type = basic_type_from_layout(env, layout_interner, field_layout);
+    let len = env.ptr_int().const_int(offsets.len() as u64, false);
+
+    incrementing_index_loop(
+        env,
+        layout_interner,
+        fn_val,
+        len,
+        "field_index",
+        |layout_interner, index| {
+            let offset_ptr = unsafe {
+                builder.new_build_in_bounds_gep(
+                    offsets_array.get_type(),
+                    offsets_global.as_pointer_value(),
+                    &[env.ptr_int().const_zero(), index],
+                    "field_offset_ptr",
+                )
+            };
+            let offset = builder
+                .new_build_load(i32_type, offset_ptr, "field_offset")
+                .into_int_value();
+            let offset = builder.build_int_z_extend(offset, env.ptr_int(), "field_offset");
+
+            let field_ptr = unsafe {
+                builder.new_build_in_bounds_gep(i8_type, bytes_ptr, &[offset], "field_bytes")
+            };
+            let field_ptr = builder.build_pointer_cast(
+                field_ptr,
+                field_type.ptr_type(AddressSpace::default()),
+                "field_ptr",
+            );
+
+            // the field is only read, so it can be passed from where it is
+            let field_value = if layout_interner.is_passed_by_reference(field_layout) {
+                field_ptr.into()
+            } else {
+                builder.new_build_load(field_type, field_ptr, "load_field")
+            };
+
+            modify_refcount_layout_help(
+                env,
+                layout_interner,
+                layout_ids,
+                mode.to_call_mode(fn_val),
+                field_value,
+                field_layout,
+            );
+        },
+    );
+}
+
 pub fn increment_refcount_layout<'a, 'ctx, 'env>(
     env: &Env<'a, 'ctx, 'env>,
     layout_interner: &mut STLayoutInterner<'a>,
@@ -1797,3 +1925,101 @@ fn modify_refcount_nonrecursive_help<'a, 'ctx, 'env>(
     // this function returns void
     builder.build_return(None);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llvm::build::module_from_builtins;
+    use bumpalo::Bump;
+    use inkwell::context::Context;
+    use inkwell::module::Module;
+    use inkwell::values::InstructionOpcode;
+    use roc_target::TargetInfo;
+    use target_lexicon::Triple;
+
+    fn count_calls(function: FunctionValue<'_>) -> usize {
+        let mut calls = 0;
+        for block in function.get_basic_blocks() {
+            let mut instruction = block.get_first_instruction();
+            while let Some(current) = instruction {
+                if current.get_opcode() == InstructionOpcode::Call {
+                    calls += 1;
+                }
+                instruction = current.get_next_instruction();
+            }
+        }
+
+        calls
+    }
+
+    #[test]
+    fn many_fields_of_one_layout_are_modified_in_a_loop() {
+        let arena = Bump::new();
+        let context = Context::create();
+        let triple = Triple::host();
+        let module: &Module = arena.alloc(module_from_builtins(&triple, &context, "test"));
+        let builder = context.create_builder();
+        let debug_info = Env::new_debug_info(module, &triple);
+        let env = Env::for_tests(&arena, &context, module, &builder, &debug_info);
+        let mut layout_interner = STLayoutInterner::with_capacity(4, TargetInfo::default_x86_64());
+        let mut layout_ids = LayoutIds::default();
+
+        // the refcount functions are built from inside of a function
+        env.add_test_host_function();
+
+        let list_of_strs = layout_interner.insert(Layout::Builtin(Builtin::List(Layout::STR)));
+        let two_strs =
+            layout_interner.insert(Layout::struct_no_name_order(arena.alloc([Layout::STR; 2])));
+        let eight_strs =
+            layout_interner.insert(Layout::struct_no_name_order(arena.alloc([Layout::STR; 8])));
+        // four strings among other fields, and a struct of those
+        let mixed = layout_interner.insert(Layout::struct_no_name_order(arena.alloc([
+            Layout::STR,
+            Layout::U8,
+            Layout::STR,
+            list_of_strs,
+            Layout::STR,
+            Layout::I64,
+            Layout::STR,
+        ])));
+        let nested = layout_interner.insert(Layout::struct_no_name_order(arena.alloc([
+            mixed,
+            two_strs,
+            Layout::U16,
+        ])));
+
+        // whether the function loops, and the calls it makes
+        let expected = [
+            (two_strs, false, 2),
+            (eight_strs, true, 1),
+            (mixed, true, 2),
+            (nested, false, 2),
+        ];
+
+        for (layout, loops, calls) in expected {
+            for mode in [Mode::Inc, Mode::Dec] {
+                let function = modify_refcount_layout_build_function(
+                    &env,
+                    &mut layout_interner,
+                    &mut layout_ids,
+                    mode,
+                    layout,
+                )
+                .unwrap();
+
+                let has_loop = function
+                    .get_basic_blocks()
+                    .iter()
+                    .any(|block| block.get_name().to_bytes() == b"loop");
+                assert_eq!(has_loop, loops, "{}", layout_interner.dbg(layout));
+                assert_eq!(
+                    count_calls(function),
+                    calls,
+                    "{}",
+                    layout_interner.dbg(layout)
+                );
+                assert!(function.verify(true), "{}", layout_interner.dbg(layout));
+            }
+        }
+    }
+}
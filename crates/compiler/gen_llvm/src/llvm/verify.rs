@@ -0,0 +1,212 @@
+//! What to do when a module fails LLVM verification. The errors of the module say what is wrong,
+//! but the IR that comes with them is the whole module. Verifying every function on its own finds
+//! the ones that are broken, so a report can show just those, along with what they were generated
+//! from. Optionally, their bodies are replaced with a crash, so the rest of the module can still
+//! be emitted and looked at.
+use crate::llvm::build::{throw_internal_exception, Env};
+use inkwell::values::FunctionValue;
+use roc_debug_flags::dbg_do;
+#[cfg(debug_assertions)]
+use roc_debug_flags::ROC_STUB_BROKEN_LLVM_FNS;
+use std::fmt;
+
+/// Whether to replace the bodies of broken functions with a crash, see `ROC_STUB_BROKEN_LLVM_FNS`
+pub fn stub_broken_functions() -> bool {
+    dbg_do!(ROC_STUB_BROKEN_LLVM_FNS, {
+        return true;
+    });
+    false
+}
+
+/// Remembers that the function `fn_name` was generated from `origin`, for [VerificationReport]
+pub(crate) fn record_fn_origin(env: &Env<'_, '_, '_>, fn_name: &str, origin: String) {
+    env.fn_origins
+        .borrow_mut()
+        .insert(fn_name.to_string(), origin);
+}
+
+/// A function that fails verification on its own
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BrokenFunction {
+    pub name: String,
+    /// The proc or wrapper the function was generated from, if codegen recorded it
+    pub origin: Option<String>,
+    /// The IR of the function, before its body was replaced
+    pub ir: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerificationReport {
+    /// What the verification of the whole module said
+    pub module_errors: String,
+    pub broken_functions: Vec<BrokenFunction>,
+    /// Whether the bodies of the broken functions were replaced with a crash
+    pub stubbed: bool,
+}
+
+impl fmt::Display for VerificationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.module_errors.trim_end())?;
+
+        if self.broken_functions.is_empty() {
+            // the problem is outside of the functions, e.g. in a global
+            return writeln!(f, "\nEvery function verifies on its own.");
+        }
+
+        writeln!(
+            f,
+            "\n{} function(s) fail verification on their own:",
+            self.broken_functions.len()
+        )?;
+
+        for broken in self.broken_functions.iter() {
+            let origin = broken.origin.as_deref().unwrap_or("an unknown origin");
+            writeln!(f, "\n`{}`, generated from {}:\n", broken.name, origin)?;
+            writeln!(f, "{}", broken.ir.trim_end())?;
+        }
+
+        if self.stubbed {
+            writeln!(f, "\nTheir bodies were replaced with a crash.")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Verifies every function of the module on its own, after the module as a whole failed with
+/// `module_errors`. With `stub`, every broken function gets a body that calls `roc_panic`, which
+/// needs `roc_panic` to be declared.
+pub fn isolate_broken_functions<'a, 'ctx, 'env>(
+    env: &Env<'a, 'ctx, 'env>,
+    module_errors: String,
+    stub: bool,
+) -> VerificationReport {
+    let broken: Vec<FunctionValue<'ctx>> = env
+        .module
+        .get_functions()
+        .filter(|fn_val| fn_val.count_basic_blocks() > 0 && !fn_val.verify(false))
+        .collect();
+
+    let mut broken_functions = Vec::with_capacity(broken.len());
+
+    for fn_val in broken {
+        let name = fn_val.get_name().to_string_lossy().into_owned();
+
+        broken_functions.push(BrokenFunction {
+            origin: env.fn_origins.borrow().get(&name).cloned(),
+            ir: fn_val.print_to_string().to_string(),
+            name,
+        });
+
+        if stub {
+            replace_with_crash(env, fn_val);
+        }
+    }
+
+    VerificationReport {
+        module_errors,
+        broken_functions,
+        stubbed: stub,
+    }
+}
+
+/// Swaps `fn_val` for a function of the same name and type that only crashes. A broken body can
+/// not be taken apart instruction by instruction, because its values may be used in other
+/// blocks, so the whole function goes.
+fn replace_with_crash<'a, 'ctx, 'env>(env: &Env<'a, 'ctx, 'env>, fn_val: FunctionValue<'ctx>) {
+    let name = fn_val.get_name().to_string_lossy().into_owned();
+
+    let stub = env.module.add_function(
+        &format!("{}_stub", name),
+        fn_val.get_type(),
+        Some(fn_val.get_linkage()),
+    );
+    stub.set_call_conventions(fn_val.get_call_conventions());
+
+    fn_val.replace_all_uses_with(stub);
+
+    unsafe {
+        fn_val.delete();
+    }
+
+    stub.as_global_value().as_pointer_value().set_name(&name);
+
+    let entry = env.context.append_basic_block(stub, "entry");
+    env.builder.position_at_end(entry);
+
+    // a location left over from another function would not belong to this one
+    env.builder.unset_current_debug_location();
+
+    let message = format!(
+        "`{}` failed LLVM verification, so its body was removed",
+        name
+    );
+    throw_internal_exception(env, stub, &message);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llvm::externs::add_default_roc_externs;
+    use bumpalo::Bump;
+    use inkwell::context::Context;
+    use inkwell::module::{Linkage, Module};
+    use target_lexicon::Triple;
+
+    #[test]
+    fn report_isolates_the_broken_function_and_its_stub_verifies() {
+        let arena = Bump::new();
+        let context = Context::create();
+        let triple = Triple::host();
+        let module: &Module = arena.alloc(crate::llvm::build::module_from_builtins(
+            &triple, &context, "test",
+        ));
+        let builder = context.create_builder();
+        let debug_info = Env::new_debug_info(module, &triple);
+
+        let env = Env::for_tests(&arena, &context, module, &builder, &debug_info);
+
+        add_default_roc_externs(&env);
+        module.strip_debug_info();
+
+        let i64_type = context.i64_type();
+        let fn_type = i64_type.fn_type(&[], false);
+
+        // returns nothing, even though it should return an i64
+        let broken = module.add_function("broken", fn_type, Some(Linkage::Internal));
+        builder.position_at_end(context.append_basic_block(broken, "entry"));
+        builder.build_return(None);
+        record_fn_origin(&env, "broken", "the proc `#UserApp.broken`".to_string());
+
+        let caller = module.add_function("caller", fn_type, None);
+        builder.position_at_end(context.append_basic_block(caller, "entry"));
+        let call = builder.build_call(broken, &[], "call_broken");
+        builder.build_return(Some(&call.try_as_basic_value().left().unwrap()));
+
+        let errors = module
+            .verify()
+            .expect_err("the module has a broken function");
+        let report = isolate_broken_functions(&env, errors.to_string(), true);
+
+        let names: Vec<_> = report
+            .broken_functions
+            .iter()
+            .map(|broken| broken.name.as_str())
+            .collect();
+        assert_eq!(names, ["broken"]);
+        assert_eq!(
+            report.broken_functions[0].origin.as_deref(),
+            Some("the proc `#UserApp.broken`")
+        );
+        assert!(report.broken_functions[0].ir.contains("ret void"));
+        assert!(report
+            .to_string()
+            .contains("generated from the proc `#UserApp.broken`"));
+
+        // the stub took the place of the broken function, and the module is whole again
+        let stub = module.get_function("broken").unwrap();
+        assert!(stub.verify(false));
+        assert!(caller.verify(false));
+        assert!(module.verify().is_ok(), "{}", module.print_to_string());
+    }
+}
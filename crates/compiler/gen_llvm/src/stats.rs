@@ -0,0 +1,147 @@
+//! Metrics collected while generating LLVM IR, reported by `roc build --stats`.
+use inkwell::module::Module;
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Default)]
+pub struct CompilationStats {
+    /// finished phases, in the order they were started
+    phases: Vec<(&'static str, Duration)>,
+    /// phases that have been started but not yet ended
+    open_phases: Vec<(&'static str, Instant)>,
+    procs_compiled: usize,
+    ir_instructions: usize,
+    peak_memory_bytes: usize,
+}
+
+impl CompilationStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record_phase_start(&mut self, name: &'static str) {
+        self.open_phases.push((name, Instant::now()));
+    }
+
+    pub fn record_phase_end(&mut self, name: &'static str) {
+        match self.open_phases.iter().rposition(|(open, _)| *open == name) {
+            Some(index) => {
+                let (_, start) = self.open_phases.remove(index);
+                self.phases.push((name, start.elapsed()));
+            }
+            None => debug_assert!(false, "phase {:?} was ended but never started", name),
+        }
+    }
+
+    pub fn record_procs_compiled(&mut self, n: usize) {
+        self.procs_compiled += n;
+    }
+
+    pub fn record_ir_instructions(&mut self, n: usize) {
+        self.ir_instructions += n;
+    }
+
+    /// Memory usage is sampled at convenient points; we only keep the highest sample.
+    pub fn record_memory_usage(&mut self, bytes: usize) {
+        self.peak_memory_bytes = self.peak_memory_bytes.max(bytes);
+    }
+
+    pub fn procs_compiled(&self) -> usize {
+        self.procs_compiled
+    }
+
+    pub fn ir_instructions(&self) -> usize {
+        self.ir_instructions
+    }
+
+    pub fn peak_memory_bytes(&self) -> usize {
+        self.peak_memory_bytes
+    }
+
+    pub fn phases(&self) -> &[(&'static str, Duration)] {
+        &self.phases
+    }
+
+    pub fn to_json(&self) -> String {
+        let mut buf = String::with_capacity(256);
+
+        write!(
+            buf,
+            "{{\"procs_compiled\":{},\"ir_instructions\":{},\"peak_memory_bytes\":{},\"phases\":[",
+            self.procs_compiled, self.ir_instructions, self.peak_memory_bytes
+        )
+        .unwrap();
+
+        for (i, (name, duration)) in self.phases.iter().enumerate() {
+            if i > 0 {
+                buf.push(',');
+            }
+
+            // phase names are compiler-provided identifiers, so they never need escaping
+            debug_assert!(!name.contains(['"', '\\']));
+
+            write!(
+                buf,
+                "{{\"name\":\"{}\",\"micros\":{}}}",
+                name,
+                duration.as_micros()
+            )
+            .unwrap();
+        }
+
+        buf.push_str("]}");
+
+        buf
+    }
+}
+
+/// Count the instructions in all function bodies of the module
+pub fn count_ir_instructions(module: &Module<'_>) -> usize {
+    let mut count = 0;
+
+    for function in module.get_functions() {
+        for block in function.get_basic_blocks() {
+            let mut next = block.get_first_instruction();
+
+            while let Some(instruction) = next {
+                count += 1;
+                next = instruction.get_next_instruction();
+            }
+        }
+    }
+
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CompilationStats;
+
+    #[test]
+    fn stats_to_json() {
+        let mut stats = CompilationStats::new();
+
+        stats.record_phase_start("generate");
+        stats.record_procs_compiled(1);
+        stats.record_procs_compiled(1);
+        stats.record_ir_instructions(40);
+        stats.record_ir_instructions(2);
+        stats.record_memory_usage(1024);
+        stats.record_memory_usage(512);
+        stats.record_phase_end("generate");
+
+        assert_eq!(stats.procs_compiled(), 2);
+        assert_eq!(stats.ir_instructions(), 42);
+        assert_eq!(stats.peak_memory_bytes(), 1024);
+        assert_eq!(stats.phases().len(), 1);
+
+        let micros = stats.phases()[0].1.as_micros();
+        assert_eq!(
+            stats.to_json(),
+            format!(
+                "{{\"procs_compiled\":2,\"ir_instructions\":42,\"peak_memory_bytes\":1024,\"phases\":[{{\"name\":\"generate\",\"micros\":{}}}]}}",
+                micros
+            )
+        );
+    }
+}
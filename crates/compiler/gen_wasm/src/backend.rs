@@ -2,11 +2,14 @@ use bitvec::vec::BitVec;
 use bumpalo::collections::{String, Vec};
 
 use roc_builtins::bitcode::{FloatWidth, IntWidth};
+use roc_builtins::rc_layout::{
+    allocation_padding, constant_refcount_le_bytes, refcount_offset, refcount_one,
+};
 use roc_collections::all::MutMap;
 use roc_error_macros::internal_error;
 use roc_module::low_level::{LowLevel, LowLevelWrapperType};
 use roc_module::symbol::{Interns, Symbol};
-use roc_mono::code_gen_help::{CodeGenHelp, HelperOp, REFCOUNT_MAX};
+use roc_mono::code_gen_help::{CodeGenHelp, HelperOp};
 use roc_mono::ir::{
     BranchInfo, CallType, CrashTag, Expr, JoinPointId, ListLiteralElement, Literal, ModifyRc,
     Param, Proc, ProcLayout, Stmt,
@@ -727,7 +730,7 @@ impl<'a, 'r> WasmBackend<'a, 'r> {
             Stmt::Expect { .. } => todo!("expect is not implemented in the wasm backend"),
             Stmt::ExpectFx { .. } => todo!("expect-fx is not implemented in the wasm backend"),
 
-            Stmt::Crash(sym, tag) => self.stmt_crash(*sym, *tag),
+            Stmt::Crash(sym, tag, _) => self.stmt_crash(*sym, *tag),
         }
     }
 
@@ -1026,7 +1029,9 @@ impl<'a, 'r> WasmBackend<'a, 'r> {
         // load the pointer
         self.storage.load_symbols(&mut self.code_builder, &[msg]);
         self.code_builder.i32_const(tag as _);
-        self.call_host_fn_after_loading_args("roc_panic", 2, false);
+        // no source info
+        self.code_builder.i32_const(0);
+        self.call_host_fn_after_loading_args("roc_panic", 3, false);
 
         self.code_builder.unreachable_();
     }
@@ -1213,8 +1218,9 @@ impl<'a, 'r> WasmBackend<'a, 'r> {
     fn store_bytes_in_data_section(&mut self, bytes: &[u8]) -> u32 {
         // Place the segment at a 4-byte aligned offset
         let segment_addr = round_up_to_alignment!(self.module.data.end_addr, PTR_SIZE);
-        let elements_addr = segment_addr + PTR_SIZE;
-        let length_with_refcount = 4 + bytes.len();
+        let padding = allocation_padding(TARGET_INFO.ptr_width(), 1);
+        let elements_addr = segment_addr + padding;
+        let length_with_refcount = padding as usize + bytes.len();
         self.module.data.end_addr = segment_addr + length_with_refcount as u32;
 
         let mut segment = DataSegment {
@@ -1222,9 +1228,10 @@ impl<'a, 'r> WasmBackend<'a, 'r> {
             init: Vec::with_capacity_in(length_with_refcount, self.env.arena),
         };
 
-        // Prefix the string bytes with "infinite" refcount
-        let refcount_max_bytes: [u8; 4] = (REFCOUNT_MAX as i32).to_le_bytes();
-        segment.init.extend_from_slice(&refcount_max_bytes);
+        // Prefix the string bytes with the refcount of a constant
+        segment
+            .init
+            .extend_from_slice(constant_refcount_le_bytes(TARGET_INFO.ptr_width()));
         segment.init.extend_from_slice(bytes);
 
         self.module.data.append_segment(segment);
@@ -1950,7 +1957,7 @@ impl<'a, 'r> WasmBackend<'a, 'r> {
             panic!("The app tries to allocate heap memory but the host doesn't support that. It needs to export __heap_base");
         }
         // Add extra bytes for the refcount
-        let extra_bytes = alignment_bytes.max(PTR_SIZE);
+        let extra_bytes = allocation_padding(TARGET_INFO.ptr_width(), alignment_bytes);
 
         if let Some(data_size) = comptime_data_size {
             // Data size known at compile time and passed as an argument
@@ -1973,10 +1980,11 @@ impl<'a, 'r> WasmBackend<'a, 'r> {
         self.code_builder.tee_local(local_id);
 
         // Write the initial refcount
-        let refcount_offset = extra_bytes - PTR_SIZE;
-        let encoded_refcount = (initial_refcount as i32) - 1 + i32::MIN;
+        let rc_offset = (extra_bytes as i32 + refcount_offset(TARGET_INFO.ptr_width())) as u32;
+        let encoded_refcount =
+            refcount_one(TARGET_INFO.ptr_width()) as i32 + (initial_refcount as i32 - 1);
         self.code_builder.i32_const(encoded_refcount);
-        self.code_builder.i32_store(Align::Bytes4, refcount_offset);
+        self.code_builder.i32_store(Align::Bytes4, rc_offset);
 
         // Put the data address on the VM stack
         self.code_builder.get_local(local_id);
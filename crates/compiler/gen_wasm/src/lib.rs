@@ -285,7 +285,7 @@ mod dummy_platform_functions {
     }
 
     #[no_mangle]
-    pub unsafe extern "C" fn roc_panic(_c_ptr: *mut c_void, _tag_id: u32) {
+    pub unsafe extern "C" fn roc_panic(_c_ptr: *mut c_void, _tag_id: u32, _source: *const c_void) {
         unimplemented!("It is not valid to call roc panic from within the compiler. Please use the \"platform\" feature if this is a platform.")
     }
 
@@ -1,7 +1,7 @@
 use bumpalo::collections::Vec;
 use bumpalo::Bump;
 use roc_builtins::bitcode::{self, FloatWidth, IntWidth};
-use roc_error_macros::internal_error;
+use roc_error_macros::{internal_error, user_error};
 use roc_module::low_level::LowLevel;
 use roc_module::symbol::Symbol;
 use roc_mono::code_gen_help::HelperOp;
@@ -12,7 +12,7 @@ use roc_mono::low_level::HigherOrder;
 use crate::backend::{ProcLookupData, ProcSource, WasmBackend};
 use crate::layout::{CallConv, StackMemoryFormat, WasmLayout};
 use crate::storage::{AddressValue, StackMemoryLocation, StoredValue};
-use crate::{PTR_TYPE, TARGET_INFO};
+use crate::{copy_memory, CopyMemoryConfig, PTR_TYPE, TARGET_INFO};
 use roc_wasm_module::{Align, LocalId, ValueType};
 
 /// Number types used for Wasm code gen
@@ -193,6 +193,350 @@ impl<'a> LowLevelCall<'a> {
         }
     }
 
+    /// List.sum, List.product, List.minUnsafe, and List.maxUnsafe
+    /// A loop over the elements, keeping the result so far in a local
+    fn list_reduce_numbers(&self, backend: &mut WasmBackend<'a, '_>) {
+        use CodeGenNumType::*;
+
+        let elem_layout = self.ret_layout;
+        let value_type = match CodeGenNumType::from(elem_layout) {
+            I32 => ValueType::I32,
+            I64 => ValueType::I64,
+            F32 => ValueType::F32,
+            F64 => ValueType::F64,
+            I128 | Decimal => todo!("{:?} for 128-bit numbers", self.lowlevel),
+        };
+        let elem_size = backend.layout_interner.stack_size(elem_layout);
+
+        let (list_local, list_offset) = match backend.storage.get(&self.arguments[0]) {
+            StoredValue::StackMemory { location, .. } => {
+                location.local_and_offset(backend.storage.stack_frame_pointer)
+            }
+            _ => internal_error!("Lists are always stored in stack memory"),
+        };
+        let elem_ptr = backend.storage.create_anonymous_local(PTR_TYPE);
+        let end_ptr = backend.storage.create_anonymous_local(PTR_TYPE);
+        let accum = backend.storage.create_anonymous_local(value_type);
+
+        // elem_ptr = list.ptr
+        // end_ptr = list.ptr + list.len * elem_size
+        backend.code_builder.get_local(list_local);
+        backend.code_builder.i32_load(Align::Bytes4, list_offset);
+        backend.code_builder.tee_local(elem_ptr);
+        backend.code_builder.get_local(list_local);
+        backend
+            .code_builder
+            .i32_load(Align::Bytes4, list_offset + (4 * Builtin::WRAPPER_LEN));
+        backend.code_builder.i32_const(elem_size as i32);
+        backend.code_builder.i32_mul();
+        backend.code_builder.i32_add();
+        backend.code_builder.set_local(end_ptr);
+
+        // The sum starts at 0 and the product at 1. For the minimum and maximum, the list is
+        // not empty, so they start at the first element (which then gets compared with itself).
+        match (self.lowlevel, value_type) {
+            (LowLevel::ListSum, ValueType::I32) => backend.code_builder.i32_const(0),
+            (LowLevel::ListSum, ValueType::I64) => backend.code_builder.i64_const(0),
+            (LowLevel::ListSum, ValueType::F32) => backend.code_builder.f32_const(0.0),
+            (LowLevel::ListSum, ValueType::F64) => backend.code_builder.f64_const(0.0),
+            (LowLevel::ListProduct, ValueType::I32) => backend.code_builder.i32_const(1),
+            (LowLevel::ListProduct, ValueType::I64) => backend.code_builder.i64_const(1),
+            (LowLevel::ListProduct, ValueType::F32) => backend.code_builder.f32_const(1.0),
+            (LowLevel::ListProduct, ValueType::F64) => backend.code_builder.f64_const(1.0),
+            _ => Self::load_number(backend, elem_ptr, elem_layout),
+        }
+        backend.code_builder.set_local(accum);
+
+        backend.code_builder.block();
+        backend.code_builder.loop_();
+
+        // break if elem_ptr >= end_ptr
+        backend.code_builder.get_local(elem_ptr);
+        backend.code_builder.get_local(end_ptr);
+        backend.code_builder.i32_ge_u();
+        backend.code_builder.br_if(1);
+
+        match (self.lowlevel, self.ret_layout_raw) {
+            (LowLevel::ListSum, Layout::Builtin(Builtin::Int(width))) => {
+                // the same addition as Num.add, which panics on overflow
+                backend.code_builder.get_local(accum);
+                Self::load_number(backend, elem_ptr, elem_layout);
+                backend.call_host_fn_after_loading_args(
+                    &bitcode::NUM_ADD_OR_PANIC_INT[width],
+                    2,
+                    true,
+                );
+            }
+            (LowLevel::ListSum, _) => {
+                backend.code_builder.get_local(accum);
+                Self::load_number(backend, elem_ptr, elem_layout);
+                if value_type == ValueType::F32 {
+                    backend.code_builder.f32_add();
+                } else {
+                    backend.code_builder.f64_add();
+                }
+            }
+            (LowLevel::ListProduct, Layout::Builtin(Builtin::Int(width))) => {
+                // the same multiplication as Num.mul, which panics on overflow
+                backend.code_builder.get_local(accum);
+                Self::load_number(backend, elem_ptr, elem_layout);
+                backend.call_host_fn_after_loading_args(
+                    &bitcode::NUM_MUL_OR_PANIC_INT[width],
+                    2,
+                    true,
+                );
+            }
+            (LowLevel::ListProduct, _) => {
+                backend.code_builder.get_local(accum);
+                Self::load_number(backend, elem_ptr, elem_layout);
+                if value_type == ValueType::F32 {
+                    backend.code_builder.f32_mul();
+                } else {
+                    backend.code_builder.f64_mul();
+                }
+            }
+            (lowlevel, Layout::Builtin(Builtin::Float(width))) => {
+                backend.code_builder.get_local(accum);
+                Self::load_number(backend, elem_ptr, elem_layout);
+                match (lowlevel, width) {
+                    (LowLevel::ListMinUnsafe, FloatWidth::F32) => backend.code_builder.f32_min(),
+                    (LowLevel::ListMinUnsafe, FloatWidth::F64) => backend.code_builder.f64_min(),
+                    (_, FloatWidth::F32) => backend.code_builder.f32_max(),
+                    (_, FloatWidth::F64) => backend.code_builder.f64_max(),
+                }
+            }
+            (lowlevel, _) => {
+                // select(elem, accum, elem < accum), or with > for the maximum
+                Self::load_number(backend, elem_ptr, elem_layout);
+                backend.code_builder.get_local(accum);
+                Self::load_number(backend, elem_ptr, elem_layout);
+                backend.code_builder.get_local(accum);
+                let is_min = lowlevel == LowLevel::ListMinUnsafe;
+                match (value_type, layout_is_signed_int(elem_layout), is_min) {
+                    (ValueType::I32, true, true) => backend.code_builder.i32_lt_s(),
+                    (ValueType::I32, true, false) => backend.code_builder.i32_gt_s(),
+                    (ValueType::I32, false, true) => backend.code_builder.i32_lt_u(),
+                    (ValueType::I32, false, false) => backend.code_builder.i32_gt_u(),
+                    (_, true, true) => backend.code_builder.i64_lt_s(),
+                    (_, true, false) => backend.code_builder.i64_gt_s(),
+                    (_, false, true) => backend.code_builder.i64_lt_u(),
+                    (_, false, false) => backend.code_builder.i64_gt_u(),
+                }
+                backend.code_builder.select();
+            }
+        }
+        backend.code_builder.set_local(accum);
+
+        // elem_ptr += elem_size
+        backend.code_builder.get_local(elem_ptr);
+        backend.code_builder.i32_const(elem_size as i32);
+        backend.code_builder.i32_add();
+        backend.code_builder.set_local(elem_ptr);
+        backend.code_builder.br(0);
+
+        backend.code_builder.end();
+        backend.code_builder.end();
+
+        backend.code_builder.get_local(accum);
+    }
+
+    /// List.join
+    /// Sums up the lengths of the inner lists so the result is allocated once, and then copies
+    /// the elements of every inner list into it. The result shares the elements with the inner
+    /// lists, so refcounted elements get incremented.
+    fn list_join(&self, backend: &mut WasmBackend<'a, '_>) {
+        let elem_layout = unwrap_list_elem_layout(self.ret_layout_raw);
+        let (elem_width, elem_align) = backend
+            .layout_interner
+            .get(elem_layout)
+            .stack_size_and_alignment(backend.layout_interner, TARGET_INFO);
+        // the inner lists have the same layout as the result
+        let inner_size = backend.layout_interner.stack_size(self.ret_layout);
+
+        let (outer_local, outer_offset) = match backend.storage.get(&self.arguments[0]) {
+            StoredValue::StackMemory { location, .. } => {
+                location.local_and_offset(backend.storage.stack_frame_pointer)
+            }
+            _ => internal_error!("Lists are always stored in stack memory"),
+        };
+        let (ret_local, ret_offset) = match &self.ret_storage {
+            StoredValue::StackMemory { location, .. } => {
+                location.local_and_offset(backend.storage.stack_frame_pointer)
+            }
+            _ => internal_error!("Lists are always stored in stack memory"),
+        };
+        let inner_ptr = backend.storage.create_anonymous_local(PTR_TYPE);
+        let outer_end = backend.storage.create_anonymous_local(PTR_TYPE);
+        let total = backend.storage.create_anonymous_local(ValueType::I32);
+        let elem_ptr = backend.storage.create_anonymous_local(PTR_TYPE);
+        let elem_end = backend.storage.create_anonymous_local(PTR_TYPE);
+        let dst_ptr = backend.storage.create_anonymous_local(PTR_TYPE);
+
+        // outer_end = outer.ptr + outer.len * inner_size
+        backend.code_builder.get_local(outer_local);
+        backend.code_builder.i32_load(Align::Bytes4, outer_offset);
+        backend.code_builder.get_local(outer_local);
+        backend
+            .code_builder
+            .i32_load(Align::Bytes4, outer_offset + (4 * Builtin::WRAPPER_LEN));
+        backend.code_builder.i32_const(inner_size as i32);
+        backend.code_builder.i32_mul();
+        backend.code_builder.i32_add();
+        backend.code_builder.set_local(outer_end);
+
+        // First pass: total = the sum of inner.len
+        backend.code_builder.i32_const(0);
+        backend.code_builder.set_local(total);
+        backend.code_builder.get_local(outer_local);
+        backend.code_builder.i32_load(Align::Bytes4, outer_offset);
+        backend.code_builder.set_local(inner_ptr);
+
+        backend.code_builder.block();
+        backend.code_builder.loop_();
+        backend.code_builder.get_local(inner_ptr);
+        backend.code_builder.get_local(outer_end);
+        backend.code_builder.i32_ge_u();
+        backend.code_builder.br_if(1);
+
+        backend.code_builder.get_local(total);
+        backend.code_builder.get_local(inner_ptr);
+        backend
+            .code_builder
+            .i32_load(Align::Bytes4, 4 * Builtin::WRAPPER_LEN);
+        backend.code_builder.i32_add();
+        backend.code_builder.set_local(total);
+
+        backend.code_builder.get_local(inner_ptr);
+        backend.code_builder.i32_const(inner_size as i32);
+        backend.code_builder.i32_add();
+        backend.code_builder.set_local(inner_ptr);
+        backend.code_builder.br(0);
+        backend.code_builder.end();
+        backend.code_builder.end();
+
+        // The result is List.withCapacity total, with its length set to total
+        // Zig arguments              Wasm types
+        //  (return pointer)           i32
+        //  capacity: usize            i32
+        //  alignment: u32             i32
+        //  element_width: usize       i32
+        backend
+            .storage
+            .load_symbols(&mut backend.code_builder, &[self.ret_symbol]);
+        backend.code_builder.get_local(total);
+        backend.code_builder.i32_const(elem_align as i32);
+        backend.code_builder.i32_const(elem_width as i32);
+        backend.call_host_fn_after_loading_args(bitcode::LIST_WITH_CAPACITY, 4, false);
+
+        backend.code_builder.get_local(ret_local);
+        backend.code_builder.get_local(total);
+        backend
+            .code_builder
+            .i32_store(Align::Bytes4, ret_offset + (4 * Builtin::WRAPPER_LEN));
+
+        // Second pass: copy the elements, one at a time
+        let inc_fn = if backend.layout_interner.contains_refcounted(elem_layout) {
+            Some(backend.get_refcount_fn_index(elem_layout, HelperOp::Inc))
+        } else {
+            None
+        };
+        let elem_is_primitive = matches!(
+            WasmLayout::new(backend.layout_interner, elem_layout),
+            WasmLayout::Primitive(..)
+        );
+
+        backend.code_builder.get_local(ret_local);
+        backend.code_builder.i32_load(Align::Bytes4, ret_offset);
+        backend.code_builder.set_local(dst_ptr);
+        backend.code_builder.get_local(outer_local);
+        backend.code_builder.i32_load(Align::Bytes4, outer_offset);
+        backend.code_builder.set_local(inner_ptr);
+
+        backend.code_builder.block();
+        backend.code_builder.loop_();
+        backend.code_builder.get_local(inner_ptr);
+        backend.code_builder.get_local(outer_end);
+        backend.code_builder.i32_ge_u();
+        backend.code_builder.br_if(1);
+
+        // elem_ptr = inner.ptr
+        // elem_end = inner.ptr + inner.len * elem_width
+        backend.code_builder.get_local(inner_ptr);
+        backend.code_builder.i32_load(Align::Bytes4, 0);
+        backend.code_builder.tee_local(elem_ptr);
+        backend.code_builder.get_local(inner_ptr);
+        backend
+            .code_builder
+            .i32_load(Align::Bytes4, 4 * Builtin::WRAPPER_LEN);
+        backend.code_builder.i32_const(elem_width as i32);
+        backend.code_builder.i32_mul();
+        backend.code_builder.i32_add();
+        backend.code_builder.set_local(elem_end);
+
+        backend.code_builder.block();
+        backend.code_builder.loop_();
+        backend.code_builder.get_local(elem_ptr);
+        backend.code_builder.get_local(elem_end);
+        backend.code_builder.i32_ge_u();
+        backend.code_builder.br_if(1);
+
+        copy_memory(
+            &mut backend.code_builder,
+            CopyMemoryConfig {
+                from_ptr: elem_ptr,
+                from_offset: 0,
+                to_ptr: dst_ptr,
+                to_offset: 0,
+                size: elem_width,
+                alignment_bytes: elem_align,
+            },
+        );
+
+        if let Some(inc_fn) = inc_fn {
+            // Primitives are passed by value, and everything else by reference
+            backend.code_builder.get_local(dst_ptr);
+            if elem_is_primitive {
+                backend.code_builder.i32_load(Align::from(elem_align), 0);
+            }
+            backend.code_builder.i32_const(1);
+            backend.code_builder.call(inc_fn, 2, false);
+        }
+
+        for ptr in [elem_ptr, dst_ptr] {
+            backend.code_builder.get_local(ptr);
+            backend.code_builder.i32_const(elem_width as i32);
+            backend.code_builder.i32_add();
+            backend.code_builder.set_local(ptr);
+        }
+        backend.code_builder.br(0);
+        backend.code_builder.end();
+        backend.code_builder.end();
+
+        backend.code_builder.get_local(inner_ptr);
+        backend.code_builder.i32_const(inner_size as i32);
+        backend.code_builder.i32_add();
+        backend.code_builder.set_local(inner_ptr);
+        backend.code_builder.br(0);
+        backend.code_builder.end();
+        backend.code_builder.end();
+    }
+
+    /// Push the number of type `layout` that `ptr` points to
+    fn load_number(backend: &mut WasmBackend<'a, '_>, ptr: LocalId, layout: InLayout<'a>) {
+        backend.code_builder.get_local(ptr);
+        match layout {
+            Layout::I8 => backend.code_builder.i32_load8_s(Align::Bytes1, 0),
+            Layout::U8 => backend.code_builder.i32_load8_u(Align::Bytes1, 0),
+            Layout::I16 => backend.code_builder.i32_load16_s(Align::Bytes2, 0),
+            Layout::U16 => backend.code_builder.i32_load16_u(Align::Bytes2, 0),
+            Layout::I32 | Layout::U32 => backend.code_builder.i32_load(Align::Bytes4, 0),
+            Layout::I64 | Layout::U64 => backend.code_builder.i64_load(Align::Bytes8, 0),
+            Layout::F32 => backend.code_builder.f32_load(Align::Bytes4, 0),
+            Layout::F64 => backend.code_builder.f64_load(Align::Bytes8, 0),
+            _ => internal_error!("Cannot load a {:?} into a Wasm value", layout),
+        }
+    }
+
     ///  Main entrypoint from WasmBackend
     pub fn generate(&self, backend: &mut WasmBackend<'a, '_>) {
         use CodeGenNumType::*;
@@ -296,6 +640,7 @@ impl<'a> LowLevelCall<'a> {
             }
             StrWithCapacity => self.load_args_and_call_zig(backend, bitcode::STR_WITH_CAPACITY),
             StrGraphemes => self.load_args_and_call_zig(backend, bitcode::STR_GRAPHEMES),
+            StrToU64Hex => self.load_args_and_call_zig(backend, bitcode::STR_TO_U64_HEX),
 
             // List
             ListLen => match backend.storage.get(&self.arguments[0]) {
@@ -340,7 +685,13 @@ impl<'a> LowLevelCall<'a> {
 
             ListIsUnique => self.load_args_and_call_zig(backend, bitcode::LIST_IS_UNIQUE),
 
-            ListMap | ListMap2 | ListMap3 | ListMap4 | ListSortWith => {
+            ListSum | ListProduct | ListMinUnsafe | ListMaxUnsafe => {
+                self.list_reduce_numbers(backend)
+            }
+
+            ListJoin => self.list_join(backend),
+
+            ListMap | ListMap2 | ListMap3 | ListMap4 | ListSortWith | BoxMap => {
                 internal_error!("HigherOrder lowlevels should not be handled here")
             }
 
@@ -845,6 +1196,63 @@ impl<'a> LowLevelCall<'a> {
             },
 
             NumToStr => self.num_to_str(backend),
+            NumToHexStr => self.load_args_and_call_zig(backend, bitcode::NUM_TO_HEX_STR),
+            NumGcd => {
+                // Num.gcd : U64, U64 -> U64
+                let (a, b) = self.load_u64_args_to_locals(backend);
+                let gcd = num_gcd_u64(backend, a, b);
+                backend.code_builder.get_local(gcd);
+            }
+            NumLcm => {
+                // Num.lcm : U64, U64 -> U64
+                let (a, b) = self.load_u64_args_to_locals(backend);
+                let gcd = num_gcd_u64(backend, a, b);
+
+                // a / gcd, where the gcd is only 0 when both numbers are (and then so is a)
+                backend.code_builder.get_local(a);
+                backend.code_builder.get_local(gcd);
+                backend.code_builder.i64_const(1);
+                backend.code_builder.get_local(gcd);
+                backend.code_builder.i64_const(0);
+                backend.code_builder.i64_ne();
+                backend.code_builder.select();
+                backend.code_builder.i64_div_u();
+
+                // (a / gcd) * b, the same multiplication as Num.mul, which panics on overflow
+                backend.code_builder.get_local(b);
+                backend.call_host_fn_after_loading_args(
+                    &bitcode::NUM_MUL_OR_PANIC_INT[IntWidth::U64],
+                    2,
+                    true,
+                );
+            }
+            NumAddWithOverflow => {
+                // Num.addWithOverflow : I64, I64 -> { value : I64, didOverflow : Bool }
+                // Zig's `WithOverflow(i64)` has the same layout as the record
+                self.load_args_and_call_zig(backend, &bitcode::NUM_ADD_CHECKED_INT[IntWidth::I64])
+            }
+            NumToF32Bits => {
+                // Num.toF32Bits : F32 -> U32
+                self.load_args(backend);
+                backend.code_builder.i32_reinterpret_f32();
+            }
+            NumToF64Bits => {
+                // Num.toF64Bits : F64 -> U64
+                self.load_args(backend);
+                backend.code_builder.i64_reinterpret_f64();
+            }
+            NumFromF32Bits => {
+                // Num.fromF32Bits : U32 -> F32
+                self.load_args(backend);
+                backend.code_builder.f32_reinterpret_i32();
+            }
+            NumFromF64Bits => {
+                // Num.fromF64Bits : U64 -> F64
+                self.load_args(backend);
+                backend.code_builder.f64_reinterpret_i64();
+            }
+            EncodeAppendNumLe => self.list_append_num_le(backend),
+            DecodeNumFromBytesLe => self.num_from_bytes_le(backend),
             NumAddChecked => {
                 let arg_layout = backend.storage.symbol_layouts[&self.arguments[0]];
                 match backend.layout_interner.get(arg_layout) {
@@ -1896,6 +2304,17 @@ impl<'a> LowLevelCall<'a> {
 
             Hash => todo!("{:?}", self.lowlevel),
 
+            RuntimeContextLoad | RuntimeContextStore => {
+                internal_error!("{:?} is only supported by the native dev backend", self.lowlevel)
+            }
+
+            MonotonicTicks => {
+                internal_error!(
+                    "Wasm has no timestamp counter to read for {:?}",
+                    self.lowlevel
+                )
+            }
+
             Eq | NotEq => self.eq_or_neq(backend),
 
             BoxExpr | UnboxExpr => {
@@ -2096,6 +2515,167 @@ impl<'a> LowLevelCall<'a> {
             x => internal_error!("NumToStr is not defined for {:?}", x),
         }
     }
+
+    /// Num.gcd and Num.lcm read their arguments more than once, so keep them in locals
+    fn load_u64_args_to_locals(&self, backend: &mut WasmBackend<'a, '_>) -> (LocalId, LocalId) {
+        let a = backend.storage.create_anonymous_local(ValueType::I64);
+        let b = backend.storage.create_anonymous_local(ValueType::I64);
+        self.load_args(backend);
+        backend.code_builder.set_local(b);
+        backend.code_builder.set_local(a);
+        (a, b)
+    }
+
+    /// Encode.appendNumLe : List U8, Num * -> List U8
+    /// Wasm memory is little-endian, so the bytes of the number are copied as they are
+    fn list_append_num_le(&self, backend: &mut WasmBackend<'a, '_>) {
+        let list: Symbol = self.arguments[0];
+        let number: Symbol = self.arguments[1];
+
+        let number_layout = backend.storage.symbol_layouts[&number];
+        let size = backend.layout_interner.stack_size(number_layout);
+        let (number_local, number_offset, _) =
+            ensure_symbol_is_in_memory(backend, number, number_layout, backend.env.arena);
+
+        // Zig arguments              Wasm types
+        //  (return pointer)           i32
+        //  list: RocList              i64, i32
+        //  alignment: u32             i32
+        //  spare: usize               i32
+        //  element_width: usize       i32
+        //  update_mode: UpdateMode    i32
+
+        // return pointer and list
+        backend.storage.load_symbols_for_call(
+            backend.env.arena,
+            &mut backend.code_builder,
+            &[list],
+            self.ret_symbol,
+            &WasmLayout::new(backend.layout_interner, self.ret_layout),
+            CallConv::Zig,
+        );
+        backend.code_builder.i32_const(1);
+        backend.code_builder.i32_const(size as i32);
+        backend.code_builder.i32_const(1);
+        backend.code_builder.i32_const(UPDATE_MODE_IMMUTABLE);
+        backend.call_host_fn_after_loading_args(bitcode::LIST_RESERVE, 7, false);
+
+        let (ret_local, ret_offset) = match &self.ret_storage {
+            StoredValue::StackMemory { location, .. } => {
+                location.local_and_offset(backend.storage.stack_frame_pointer)
+            }
+            _ => internal_error!("Lists are always stored in stack memory"),
+        };
+        let len_offset = ret_offset + 4 * Builtin::WRAPPER_LEN;
+
+        // dst_ptr = list.ptr + list.len
+        let dst_ptr = backend.storage.create_anonymous_local(PTR_TYPE);
+        backend.code_builder.get_local(ret_local);
+        backend.code_builder.i32_load(Align::Bytes4, ret_offset);
+        backend.code_builder.get_local(ret_local);
+        backend.code_builder.i32_load(Align::Bytes4, len_offset);
+        backend.code_builder.i32_add();
+        backend.code_builder.set_local(dst_ptr);
+
+        copy_memory(
+            &mut backend.code_builder,
+            CopyMemoryConfig {
+                from_ptr: number_local,
+                from_offset: number_offset,
+                to_ptr: dst_ptr,
+                to_offset: 0,
+                size,
+                alignment_bytes: 1,
+            },
+        );
+
+        // list.len += size (the reserve made room for it)
+        backend.code_builder.get_local(ret_local);
+        backend.code_builder.get_local(ret_local);
+        backend.code_builder.i32_load(Align::Bytes4, len_offset);
+        backend.code_builder.i32_const(size as i32);
+        backend.code_builder.i32_add();
+        backend.code_builder.i32_store(Align::Bytes4, len_offset);
+    }
+
+    /// Decode.numFromBytesLeLowlevel : List U8, Nat -> { aresult : Num *, binbounds : Bool }
+    /// The number is only read when all of its bytes are in the list
+    fn num_from_bytes_le(&self, backend: &mut WasmBackend<'a, '_>) {
+        let list: Symbol = self.arguments[0];
+        let offset: Symbol = self.arguments[1];
+
+        // The fields are sorted by alignment, so the number comes first and the Bool after it
+        let number_layout = match self.ret_layout_raw {
+            Layout::Struct { field_layouts, .. } => field_layouts[0],
+            other => internal_error!("numFromBytesLe returns a record, not {:?}", other),
+        };
+        let size = backend.layout_interner.stack_size(number_layout);
+
+        let (list_local, list_offset) = match backend.storage.get(&list) {
+            StoredValue::StackMemory { location, .. } => {
+                location.local_and_offset(backend.storage.stack_frame_pointer)
+            }
+            _ => internal_error!("Lists are always stored in stack memory"),
+        };
+        let (ret_local, ret_offset) = match &self.ret_storage {
+            StoredValue::StackMemory { location, .. } => {
+                location.local_and_offset(backend.storage.stack_frame_pointer)
+            }
+            _ => internal_error!("Invalid return value storage for DecodeNumFromBytesLe"),
+        };
+        let len_offset = list_offset + 4 * Builtin::WRAPPER_LEN;
+
+        let offset_local = backend.storage.create_anonymous_local(PTR_TYPE);
+        let in_bounds = backend.storage.create_anonymous_local(ValueType::I32);
+        let src_ptr = backend.storage.create_anonymous_local(PTR_TYPE);
+
+        backend
+            .storage
+            .load_symbols(&mut backend.code_builder, &[offset]);
+        backend.code_builder.set_local(offset_local);
+
+        // in_bounds = list.len >= size && list.len - size >= offset
+        backend.code_builder.get_local(list_local);
+        backend.code_builder.i32_load(Align::Bytes4, len_offset);
+        backend.code_builder.i32_const(size as i32);
+        backend.code_builder.i32_ge_u();
+        backend.code_builder.get_local(list_local);
+        backend.code_builder.i32_load(Align::Bytes4, len_offset);
+        backend.code_builder.i32_const(size as i32);
+        backend.code_builder.i32_sub();
+        backend.code_builder.get_local(offset_local);
+        backend.code_builder.i32_ge_u();
+        backend.code_builder.i32_and();
+        backend.code_builder.set_local(in_bounds);
+
+        // ret.binbounds = in_bounds
+        backend.code_builder.get_local(ret_local);
+        backend.code_builder.get_local(in_bounds);
+        backend
+            .code_builder
+            .i32_store8(Align::Bytes1, ret_offset + size);
+
+        // if in_bounds { ret.aresult = the bytes at list.ptr + offset }
+        backend.code_builder.get_local(in_bounds);
+        backend.code_builder.if_();
+        backend.code_builder.get_local(list_local);
+        backend.code_builder.i32_load(Align::Bytes4, list_offset);
+        backend.code_builder.get_local(offset_local);
+        backend.code_builder.i32_add();
+        backend.code_builder.set_local(src_ptr);
+        copy_memory(
+            &mut backend.code_builder,
+            CopyMemoryConfig {
+                from_ptr: src_ptr,
+                from_offset: 0,
+                to_ptr: ret_local,
+                to_offset: ret_offset,
+                size,
+                alignment_bytes: 1,
+            },
+        );
+        backend.code_builder.end();
+    }
 }
 
 /// Helper for NumIsFinite op, and also part of Eq/NotEq
@@ -2141,6 +2721,39 @@ fn num_is_finite(backend: &mut WasmBackend<'_, '_>, argument: Symbol) {
     }
 }
 
+/// Euclid's algorithm for Num.gcd and Num.lcm. Returns a new local holding the result.
+/// As in the LLVM backend, gcd 0 b is b, so gcd 0 0 is 0.
+fn num_gcd_u64(backend: &mut WasmBackend<'_, '_>, a: LocalId, b: LocalId) -> LocalId {
+    let x = backend.storage.create_anonymous_local(ValueType::I64);
+    let y = backend.storage.create_anonymous_local(ValueType::I64);
+    let rem = backend.storage.create_anonymous_local(ValueType::I64);
+
+    backend.code_builder.get_local(a);
+    backend.code_builder.set_local(x);
+    backend.code_builder.get_local(b);
+    backend.code_builder.set_local(y);
+
+    // while y != 0 { (x, y) = (y, x % y) }
+    backend.code_builder.block();
+    backend.code_builder.loop_();
+    backend.code_builder.get_local(y);
+    backend.code_builder.i64_eqz();
+    backend.code_builder.br_if(1);
+    backend.code_builder.get_local(x);
+    backend.code_builder.get_local(y);
+    backend.code_builder.i64_rem_u();
+    backend.code_builder.set_local(rem);
+    backend.code_builder.get_local(y);
+    backend.code_builder.set_local(x);
+    backend.code_builder.get_local(rem);
+    backend.code_builder.set_local(y);
+    backend.code_builder.br(0);
+    backend.code_builder.end();
+    backend.code_builder.end();
+
+    x
+}
+
 pub fn call_higher_order_lowlevel<'a>(
     backend: &mut WasmBackend<'a, '_>,
     return_sym: Symbol,
@@ -2155,6 +2768,10 @@ pub fn call_higher_order_lowlevel<'a>(
         ..
     } = higher_order;
 
+    if let BoxMap { .. } = op {
+        user_error!("Box.map is not supported by the wasm backend yet");
+    }
+
     let PassedFunction {
         name: fn_name,
         argument_layouts,
@@ -2258,6 +2875,7 @@ pub fn call_higher_order_lowlevel<'a>(
             ListMap { .. } | ListMap2 { .. } | ListMap3 { .. } | ListMap4 { .. } => {
                 ProcSource::HigherOrderMapper(passed_proc_index)
             }
+            BoxMap { .. } => internal_error!("{:?} was rejected above", op),
         }
     };
     let wrapper_sym = backend.create_symbol(&format!("#wrap#{:?}", fn_name));
@@ -2411,6 +3029,8 @@ pub fn call_higher_order_lowlevel<'a>(
 
             backend.call_host_fn_after_loading_args(bitcode::LIST_SORT_WITH, 9, false);
         }
+
+        BoxMap { .. } => internal_error!("{:?} was rejected above", op),
     }
 }
 
@@ -32,6 +32,7 @@ pub enum LowLevel {
     StrGetCapacity,
     StrWithCapacity,
     StrGraphemes,
+    StrToU64Hex,
     ListLen,
     ListWithCapacity,
     ListReserve,
@@ -50,6 +51,11 @@ pub enum LowLevel {
     ListSwap,
     ListIsUnique,
     ListGetCapacity,
+    ListSum,
+    ListProduct,
+    ListMinUnsafe,
+    ListMaxUnsafe,
+    ListJoin,
     NumAdd,
     NumAddWrap,
     NumAddChecked,
@@ -101,6 +107,16 @@ pub enum LowLevel {
     NumToIntChecked,
     NumToFloatChecked,
     NumToStr,
+    NumToHexStr,
+    NumGcd,
+    NumLcm,
+    NumAddWithOverflow,
+    NumToF32Bits,
+    NumToF64Bits,
+    NumFromF32Bits,
+    NumFromF64Bits,
+    EncodeAppendNumLe,
+    DecodeNumFromBytesLe,
     Eq,
     NotEq,
     And,
@@ -110,14 +126,23 @@ pub enum LowLevel {
     PtrCast,
     RefCountInc,
     RefCountDec,
+    /// Loads the 8 bytes at an offset from the host's context, see `Env::runtime_context` of the
+    /// dev backend
+    RuntimeContextLoad,
+    /// Stores 8 bytes at an offset from the host's context
+    RuntimeContextStore,
+    /// The timestamp counter of the CPU, a `U64` that never goes down, for profiling without
+    /// external tools. A platform's builtins emit it; it is not tied to a symbol.
+    MonotonicTicks,
     BoxExpr,
     UnboxExpr,
+    BoxMap,
     Unreachable,
 }
 
 macro_rules! higher_order {
     () => {
-        ListMap | ListMap2 | ListMap3 | ListMap4 | ListSortWith
+        ListMap | ListMap2 | ListMap3 | ListMap4 | ListSortWith | BoxMap
     };
 }
 
@@ -139,6 +164,7 @@ impl LowLevel {
             ListMap3 => 3,
             ListMap4 => 4,
             ListSortWith => 1,
+            BoxMap => 1,
             _ => unreachable!(),
         }
     }
@@ -197,6 +223,7 @@ macro_rules! map_symbol_to_lowlevel {
                 LowLevel::ListMap3 => unreachable!(),
                 LowLevel::ListMap4 => unreachable!(),
                 LowLevel::ListSortWith => unreachable!(),
+                LowLevel::BoxMap => unreachable!(),
 
                 // (un)boxing is handled in a custom way
                 LowLevel::BoxExpr => unreachable!(),
@@ -214,6 +241,9 @@ macro_rules! map_symbol_to_lowlevel {
                 LowLevel::PtrCast => unimplemented!(),
                 LowLevel::RefCountInc => unimplemented!(),
                 LowLevel::RefCountDec => unimplemented!(),
+                LowLevel::RuntimeContextLoad => unimplemented!(),
+                LowLevel::RuntimeContextStore => unimplemented!(),
+                LowLevel::MonotonicTicks => unimplemented!(),
 
                 // these are not implemented, not sure why
                 LowLevel::StrFromInt => unimplemented!(),
@@ -253,8 +283,14 @@ map_symbol_to_lowlevel! {
     StrGetCapacity <= STR_CAPACITY,
     StrWithCapacity <= STR_WITH_CAPACITY,
     StrGraphemes <= STR_GRAPHEMES,
+    StrToU64Hex <= STR_TO_U64_HEX_LOWLEVEL,
     ListLen <= LIST_LEN,
     ListGetCapacity <= LIST_CAPACITY,
+    ListSum <= LIST_SUM,
+    ListProduct <= LIST_PRODUCT,
+    ListMinUnsafe <= LIST_MIN_UNSAFE,
+    ListMaxUnsafe <= LIST_MAX_UNSAFE,
+    ListJoin <= LIST_JOIN,
     ListWithCapacity <= LIST_WITH_CAPACITY,
     ListReserve <= LIST_RESERVE,
     ListIsUnique <= LIST_IS_UNIQUE,
@@ -312,6 +348,16 @@ map_symbol_to_lowlevel! {
     NumShiftRightBy <= NUM_SHIFT_RIGHT,
     NumShiftRightZfBy <= NUM_SHIFT_RIGHT_ZERO_FILL,
     NumToStr <= NUM_TO_STR,
+    NumToHexStr <= NUM_TO_HEX_STR,
+    NumGcd <= NUM_GCD,
+    NumLcm <= NUM_LCM,
+    NumAddWithOverflow <= NUM_ADD_WITH_OVERFLOW,
+    NumToF32Bits <= NUM_TO_F32_BITS,
+    NumToF64Bits <= NUM_TO_F64_BITS,
+    NumFromF32Bits <= NUM_FROM_F32_BITS,
+    NumFromF64Bits <= NUM_FROM_F64_BITS,
+    EncodeAppendNumLe <= ENCODE_APPEND_NUM_LE,
+    DecodeNumFromBytesLe <= DECODE_NUM_FROM_BYTES_LE_LOWLEVEL,
     Eq <= BOOL_STRUCTURAL_EQ,
     NotEq <= BOOL_STRUCTURAL_NOT_EQ,
     And <= BOOL_AND,
@@ -1246,6 +1246,14 @@ define_builtins! {
         145 NUM_MUL_CHECKED_LOWLEVEL: "mulCheckedLowlevel"
         146 NUM_BYTES_TO_U16_LOWLEVEL: "bytesToU16Lowlevel"
         147 NUM_BYTES_TO_U32_LOWLEVEL: "bytesToU32Lowlevel"
+        148 NUM_TO_HEX_STR: "toHexStr"
+        149 NUM_GCD: "gcd"
+        150 NUM_LCM: "lcm"
+        151 NUM_TO_F32_BITS: "toF32Bits"
+        152 NUM_TO_F64_BITS: "toF64Bits"
+        153 NUM_FROM_F32_BITS: "fromF32Bits"
+        154 NUM_FROM_F64_BITS: "fromF64Bits"
+        155 NUM_ADD_WITH_OVERFLOW: "addWithOverflow"
     }
     4 BOOL: "Bool" => {
         0 BOOL_BOOL: "Bool" exposed_type=true // the Bool.Bool type alias
@@ -1319,6 +1327,8 @@ define_builtins! {
         53 STR_WITH_CAPACITY: "withCapacity"
         54 STR_WITH_PREFIX: "withPrefix"
         55 STR_GRAPHEMES: "graphemes"
+        56 STR_TO_U64_HEX: "toU64Hex"
+        57 STR_TO_U64_HEX_LOWLEVEL: "toU64HexLowlevel"
     }
     6 LIST: "List" => {
         0 LIST_LIST: "List" exposed_apply_type=true // the List.List type alias
@@ -1401,6 +1411,8 @@ define_builtins! {
         77 LIST_COUNT_IF: "countIf"
         78 LIST_WALK_FROM: "walkFrom"
         79 LIST_WALK_FROM_UNTIL: "walkFromUntil"
+        80 LIST_MIN_UNSAFE: "minUnsafe"
+        81 LIST_MAX_UNSAFE: "maxUnsafe"
     }
     7 RESULT: "Result" => {
         0 RESULT_RESULT: "Result" exposed_type=true // the Result.Result type alias
@@ -1466,6 +1478,7 @@ define_builtins! {
         0 BOX_BOX_TYPE: "Box" exposed_apply_type=true // the Box.Box opaque type
         1 BOX_BOX_FUNCTION: "box" // Box.box
         2 BOX_UNBOX: "unbox"
+        3 BOX_MAP: "map"
     }
     11 ENCODE: "Encode" => {
         0 ENCODE_ENCODER: "Encoder" exposed_type=true
@@ -1494,6 +1507,7 @@ define_builtins! {
         23 ENCODE_APPEND_WITH: "appendWith"
         24 ENCODE_APPEND: "append"
         25 ENCODE_TO_BYTES: "toBytes"
+        26 ENCODE_APPEND_NUM_LE: "appendNumLe"
     }
     12 DECODE: "Decode" => {
         0 DECODE_DECODE_ERROR: "DecodeError" exposed_type=true
@@ -1524,6 +1538,8 @@ define_builtins! {
         25 DECODE_FROM_BYTES_PARTIAL: "fromBytesPartial"
         26 DECODE_FROM_BYTES: "fromBytes"
         27 DECODE_MAP_RESULT: "mapResult"
+        28 DECODE_NUM_FROM_BYTES_LE: "numFromBytesLe"
+        29 DECODE_NUM_FROM_BYTES_LE_LOWLEVEL: "numFromBytesLeLowlevel"
     }
     13 HASH: "Hash" => {
         0 HASH_HASH_ABILITY: "Hash" exposed_type=true
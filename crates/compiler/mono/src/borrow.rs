@@ -649,6 +649,10 @@ impl<'a> BorrowInfState<'a> {
                         // always own the input list
                         self.own_var(*xs);
                     }
+                    BoxMap { boxed } => {
+                        // always own the box, so it can be updated in place
+                        self.own_var(*boxed);
+                    }
                 }
 
                 // own the closure environment if the function needs to own it
@@ -658,6 +662,18 @@ impl<'a> BorrowInfState<'a> {
                 {
                     self.own_var(passed_function.captured_environment);
                 }
+
+                // Box.map moves the value out of a unique box, so the function must own it
+                if let BoxMap { .. } = op {
+                    let ParamOffset(index) =
+                        param_map.get_param_offset(passed_function.name.name(), closure_layout);
+                    let param = &mut param_map.declarations[index];
+
+                    if param.ownership != Ownership::Owned {
+                        param.ownership = Ownership::Owned;
+                        self.modified = true;
+                    }
+                }
             }
 
             Foreign { .. } => {
@@ -878,7 +894,7 @@ impl<'a> BorrowInfState<'a> {
 
             Refcounting(_, _) => unreachable!("these have not been introduced yet"),
 
-            Crash(msg, _) => {
+            Crash(msg, _, _) => {
                 // Crash is a foreign call, so we must own the argument.
                 self.own_var(*msg);
             }
@@ -944,9 +960,8 @@ pub fn lowlevel_borrow_signature(arena: &Bump, op: LowLevel) -> &[bool] {
     match op {
         Unreachable => arena.alloc_slice_copy(&[irrelevant]),
         ListLen | StrIsEmpty | StrToScalars | StrCountGraphemes | StrGraphemes
-        | StrCountUtf8Bytes | StrGetCapacity | ListGetCapacity => {
-            arena.alloc_slice_copy(&[borrowed])
-        }
+        | StrCountUtf8Bytes | StrGetCapacity | ListGetCapacity | ListSum | ListProduct
+        | ListMinUnsafe | ListMaxUnsafe | ListJoin => arena.alloc_slice_copy(&[borrowed]),
         ListWithCapacity | StrWithCapacity => arena.alloc_slice_copy(&[irrelevant]),
         ListReplaceUnsafe => arena.alloc_slice_copy(&[owned, irrelevant, irrelevant]),
         StrGetUnsafe | ListGetUnsafe => arena.alloc_slice_copy(&[borrowed, irrelevant]),
@@ -960,7 +975,7 @@ pub fn lowlevel_borrow_signature(arena: &Bump, op: LowLevel) -> &[bool] {
         StrTrimLeft => arena.alloc_slice_copy(&[owned]),
         StrTrimRight => arena.alloc_slice_copy(&[owned]),
         StrSplit => arena.alloc_slice_copy(&[borrowed, borrowed]),
-        StrToNum => arena.alloc_slice_copy(&[borrowed]),
+        StrToNum | StrToU64Hex => arena.alloc_slice_copy(&[borrowed]),
         ListPrepend => arena.alloc_slice_copy(&[owned, owned]),
         StrJoinWith => arena.alloc_slice_copy(&[borrowed, borrowed]),
         ListMap => arena.alloc_slice_copy(&[owned, function, closure_data]),
@@ -968,6 +983,7 @@ pub fn lowlevel_borrow_signature(arena: &Bump, op: LowLevel) -> &[bool] {
         ListMap3 => arena.alloc_slice_copy(&[owned, owned, owned, function, closure_data]),
         ListMap4 => arena.alloc_slice_copy(&[owned, owned, owned, owned, function, closure_data]),
         ListSortWith => arena.alloc_slice_copy(&[owned, function, closure_data]),
+        BoxMap => arena.alloc_slice_copy(&[owned, function, closure_data]),
 
         ListAppendUnsafe => arena.alloc_slice_copy(&[owned, owned]),
         ListReserve => arena.alloc_slice_copy(&[owned, irrelevant]),
@@ -982,21 +998,26 @@ pub fn lowlevel_borrow_signature(arena: &Bump, op: LowLevel) -> &[bool] {
         | NumMulChecked | NumGt | NumGte | NumLt | NumLte | NumCompare | NumDivFrac
         | NumDivTruncUnchecked | NumDivCeilUnchecked | NumRemUnchecked | NumIsMultipleOf
         | NumPow | NumPowInt | NumBitwiseAnd | NumBitwiseXor | NumBitwiseOr | NumShiftLeftBy
-        | NumShiftRightBy | NumShiftRightZfBy => arena.alloc_slice_copy(&[irrelevant, irrelevant]),
+        | NumShiftRightBy | NumShiftRightZfBy | NumGcd | NumLcm | NumAddWithOverflow => {
+            arena.alloc_slice_copy(&[irrelevant, irrelevant])
+        }
 
         NumToStr | NumAbs | NumNeg | NumSin | NumCos | NumSqrtUnchecked | NumLogUnchecked
         | NumRound | NumCeiling | NumFloor | NumToFrac | Not | NumIsFinite | NumAtan | NumAcos
-        | NumAsin | NumIntCast | NumToIntChecked | NumToFloatCast | NumToFloatChecked => {
+        | NumAsin | NumIntCast | NumToIntChecked | NumToFloatCast | NumToFloatChecked
+        | NumToF32Bits | NumToF64Bits | NumFromF32Bits | NumFromF64Bits => {
             arena.alloc_slice_copy(&[irrelevant])
         }
         NumBytesToU16 => arena.alloc_slice_copy(&[borrowed, irrelevant]),
         NumBytesToU32 => arena.alloc_slice_copy(&[borrowed, irrelevant]),
+        EncodeAppendNumLe => arena.alloc_slice_copy(&[owned, irrelevant]),
+        DecodeNumFromBytesLe => arena.alloc_slice_copy(&[borrowed, irrelevant]),
         StrStartsWith | StrEndsWith => arena.alloc_slice_copy(&[borrowed, borrowed]),
         StrStartsWithScalar => arena.alloc_slice_copy(&[borrowed, irrelevant]),
         StrFromUtf8Range => arena.alloc_slice_copy(&[owned, irrelevant, irrelevant]),
         StrToUtf8 => arena.alloc_slice_copy(&[owned]),
         StrRepeat => arena.alloc_slice_copy(&[borrowed, irrelevant]),
-        StrFromInt | StrFromFloat => arena.alloc_slice_copy(&[irrelevant]),
+        StrFromInt | StrFromFloat | NumToHexStr => arena.alloc_slice_copy(&[irrelevant]),
         Hash => arena.alloc_slice_copy(&[borrowed, irrelevant]),
 
         ListIsUnique => arena.alloc_slice_copy(&[borrowed]),
@@ -1008,6 +1029,12 @@ pub fn lowlevel_borrow_signature(arena: &Bump, op: LowLevel) -> &[bool] {
         PtrCast | RefCountInc | RefCountDec => {
             unreachable!("Only inserted *after* borrow checking: {:?}", op);
         }
+
+        // the offset, and the value to store
+        RuntimeContextLoad => arena.alloc_slice_copy(&[irrelevant]),
+        RuntimeContextStore => arena.alloc_slice_copy(&[irrelevant, irrelevant]),
+
+        MonotonicTicks => &[],
     }
 }
 
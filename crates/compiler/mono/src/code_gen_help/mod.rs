@@ -21,10 +21,6 @@ const LAYOUT_UNIT: InLayout = Layout::UNIT;
 const ARG_1: Symbol = Symbol::ARG_1;
 const ARG_2: Symbol = Symbol::ARG_2;
 
-/// "Infinite" reference count, for static values
-/// Ref counts are encoded as negative numbers where isize::MIN represents 1
-pub const REFCOUNT_MAX: usize = 0;
-
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum HelperOp {
     Inc,
@@ -1,9 +1,9 @@
 #![allow(clippy::too_many_arguments)]
 
 use bumpalo::collections::vec::Vec;
+use roc_builtins::rc_layout::{refcount_offset, refcount_one};
 use roc_module::low_level::{LowLevel, LowLevel::*};
 use roc_module::symbol::{IdentIds, Symbol};
-use roc_target::PtrWidth;
 
 use crate::borrow::Ownership;
 use crate::code_gen_help::let_lowlevel;
@@ -369,11 +369,7 @@ pub fn refcount_reset_proc_body<'a>(
     };
 
     // Constant for unique refcount
-    let refcount_1_encoded = match root.target_info.ptr_width() {
-        PtrWidth::Bytes4 => i32::MIN as i128,
-        PtrWidth::Bytes8 => i64::MIN as i128,
-    }
-    .to_ne_bytes();
+    let refcount_1_encoded = (refcount_one(root.target_info.ptr_width()) as i128).to_ne_bytes();
     let refcount_1_expr = Expr::Literal(Literal::Int(refcount_1_encoded));
     let refcount_1_stmt = Stmt::Let(
         refcount_1,
@@ -436,7 +432,7 @@ fn refcount_args<'a>(root: &CodeGenHelp<'a>, ctx: &Context<'a>, structure: Symbo
     }
 }
 
-// Subtract a constant from a pointer to find the refcount
+// Subtract a constant from a pointer to find the refcount, see `roc_builtins::rc_layout`
 // Also does some type casting, so that we have different Symbols and Layouts
 // for the 'pointer' and 'integer' versions of the address.
 // This helps to avoid issues with the backends Symbol->Layout mapping.
@@ -506,10 +502,12 @@ pub fn rc_ptr_from_data_ptr_help<'a>(
     });
     let and_stmt = |next| Stmt::Let(addr_sym, and_expr, root.layout_isize, next);
 
-    // Pointer size constant
+    // How far the refcount is in front of the data
     let ptr_size_sym = root.create_symbol(ident_ids, "ptr_size");
     let ptr_size_expr = Expr::Literal(Literal::Int(
-        (root.target_info.ptr_width() as i128).to_ne_bytes(),
+        (refcount_offset(root.target_info.ptr_width()) as i128)
+            .neg()
+            .to_ne_bytes(),
     ));
     let ptr_size_stmt = |next| Stmt::Let(ptr_size_sym, ptr_size_expr, root.layout_isize, next);
 
@@ -383,7 +383,7 @@ impl<'a, 'r> Ctx<'a, 'r> {
                     self.problem(ProblemKind::NoJoinPoint { id });
                 }
             }
-            &Stmt::Crash(sym, _) => self.check_sym_layout(sym, Layout::STR, UseKind::CrashArg),
+            &Stmt::Crash(sym, _, _) => self.check_sym_layout(sym, Layout::STR, UseKind::CrashArg),
         }
     }
 
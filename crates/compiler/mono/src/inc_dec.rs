@@ -165,7 +165,7 @@ pub fn occurring_variables(stmt: &Stmt<'_>) -> (MutSet<Symbol>, MutSet<Symbol>)
                 stack.push(default_branch.1);
             }
 
-            Crash(sym, _) => {
+            Crash(sym, _, _) => {
                 result.insert(*sym);
             }
         }
@@ -783,6 +783,21 @@ impl<'a, 'i> Context<'a, 'i> {
 
                 handle_ownerships_pre!(Stmt::Let(z, v, l, b), ownerships)
             }
+            BoxMap { boxed } => {
+                // the box is consumed: its value is either moved into the function, or
+                // incremented and then passed to it when the box is shared
+                debug_assert_eq!(function_ps[0].ownership, Ownership::Owned);
+
+                let b = self.add_dec_after_lowlevel(after_arguments, &borrows, b, b_live_vars);
+
+                let v = create_call!(function_ps.get(1));
+
+                self.add_inc_before_consume_all(
+                    &[boxed],
+                    self.arena.alloc(Stmt::Let(z, v, l, b)),
+                    b_live_vars,
+                )
+            }
             ListSortWith { xs } => {
                 // NOTE: we may apply the function to the same argument multiple times. for that to
                 // be valid, the function must borrow its argument. This is not enforced at the
@@ -1277,7 +1292,7 @@ impl<'a, 'i> Context<'a, 'i> {
                 (expect, b_live_vars)
             }
 
-            Crash(x, _) => {
+            Crash(x, _, _) => {
                 let info = self.get_var_info(*x);
 
                 let mut live_vars = MutSet::default();
@@ -1468,7 +1483,7 @@ pub fn collect_stmt(
             vars
         }
 
-        Crash(m, _) => {
+        Crash(m, _, _) => {
             vars.insert(*m);
             vars
         }
@@ -77,7 +77,7 @@ fn runtime_error<'a>(env: &mut Env<'a, '_>, msg: &'a str) -> Stmt<'a> {
         sym,
         Expr::Literal(Literal::Str(msg)),
         Layout::STR,
-        env.arena.alloc(Stmt::Crash(sym, CrashTag::Roc)),
+        env.arena.alloc(Stmt::Crash(sym, CrashTag::Roc, None)),
     )
 }
 
@@ -1584,7 +1584,15 @@ pub enum Stmt<'a> {
         remainder: &'a Stmt<'a>,
     },
     Jump(JoinPointId, &'a [Symbol]),
-    Crash(Symbol, CrashTag),
+    /// Crash with the message in the symbol, from where the region says if it comes from source
+    Crash(Symbol, CrashTag, Option<CrashRegion>),
+}
+
+/// Where a crash is in the source: the region of its message, in the module it is written in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CrashRegion {
+    pub module: ModuleId,
+    pub region: Region,
 }
 
 /// Source of crash, and its runtime representation to roc_panic.
@@ -2254,7 +2262,7 @@ impl<'a> Stmt<'a> {
                 }
             }
 
-            Crash(s, _src) => alloc
+            Crash(s, _src, _) => alloc
                 .text("Crash ")
                 .append(symbol_to_doc(alloc, *s, pretty)),
 
@@ -5670,6 +5678,11 @@ pub fn with_hole<'a>(
 
                     match_on_closure_argument!(ListMap4, [xs, ys, zs, ws])
                 }
+                BoxMap => {
+                    debug_assert_eq!(arg_symbols.len(), 2);
+                    let boxed = arg_symbols[0];
+                    match_on_closure_argument!(BoxMap, [boxed])
+                }
                 BoxExpr => {
                     debug_assert_eq!(arg_symbols.len(), 1);
                     let x = arg_symbols[0];
@@ -5712,7 +5725,11 @@ pub fn with_hole<'a>(
                 &msg.value,
                 Variable::STR,
             );
-            let stmt = Stmt::Crash(msg_sym, CrashTag::User);
+            let crash_region = CrashRegion {
+                module: env.home,
+                region: msg.region,
+            };
+            let stmt = Stmt::Crash(msg_sym, CrashTag::User, Some(crash_region));
 
             assign_to_symbol(env, procs, layout_cache, Variable::STR, *msg, msg_sym, stmt)
         }
@@ -7268,7 +7285,9 @@ fn substitute_in_stmt_help<'a>(
                 None
             }
         }
-        Crash(msg, tag) => substitute(subs, *msg).map(|new| &*arena.alloc(Crash(new, *tag))),
+        Crash(msg, tag, region) => {
+            substitute(subs, *msg).map(|new| &*arena.alloc(Crash(new, *tag, *region)))
+        }
     }
 }
 
@@ -28,7 +28,8 @@ use ven_pretty::{DocAllocator, DocBuilder};
 
 mod intern;
 pub use intern::{
-    GlobalLayoutInterner, InLayout, LayoutInterner, STLayoutInterner, TLLayoutInterner,
+    GlobalLayoutInterner, InLayout, LayoutInterner, NeedsRecursionPointerFixup, STLayoutInterner,
+    TLLayoutInterner,
 };
 
 // if your changes cause this number to go down, great!
@@ -1413,6 +1414,35 @@ pub enum ClosureCallOptions<'a> {
     EnumDispatch(EnumDispatch),
 }
 
+/// What a caller that already knows which function of a lambda set it calls has to pass
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SimplifiedLambdaSet<'a> {
+    /// The set has one function that captures nothing, so the closure is zero-sized and
+    /// the function can be called like a plain function pointer.
+    FunctionPointer(LambdaName<'a>),
+    /// The functions capture nothing, and the tag that tells them apart is not needed either.
+    NoClosureData,
+    /// The function takes the closure data, which has this runtime representation.
+    Closure(InLayout<'a>),
+}
+
+pub struct LambdaSetResolver;
+
+impl LambdaSetResolver {
+    pub fn simplify<'a, I>(interner: &I, lambda_set: &LambdaSet<'a>) -> SimplifiedLambdaSet<'a>
+    where
+        I: LayoutInterner<'a>,
+    {
+        match lambda_set.is_represented(interner) {
+            Some(representation) => SimplifiedLambdaSet::Closure(representation),
+            None if lambda_set.len() == 1 => {
+                SimplifiedLambdaSet::FunctionPointer(lambda_set.iter_set().next().unwrap())
+            }
+            None => SimplifiedLambdaSet::NoClosureData,
+        }
+    }
+}
+
 impl<'a> LambdaSet<'a> {
     pub fn runtime_representation(&self) -> InLayout<'a> {
         self.representation
@@ -1960,6 +1990,15 @@ impl<'a> LambdaSet<'a> {
     }
 }
 
+/// The number of bytes to allocate for the captured environment of a closure in this lambda set.
+/// Unlike asking a backend for the size of the lowered type, this needs no code generation context.
+pub fn closure_env_size_bytes<'a, I>(interner: &I, lambda_set: &LambdaSet<'a>) -> u32
+where
+    I: LayoutInterner<'a>,
+{
+    lambda_set.stack_size(interner, interner.target_info())
+}
+
 enum ResolvedLambdaSet {
     Set(
         std::vec::Vec<(Symbol, std::vec::Vec<Variable>)>,
@@ -2475,6 +2514,32 @@ impl<'a> Layout<'a> {
         }
     }
 
+    /// Whether two values of this layout are equal exactly when their bytes are, padding aside.
+    /// Backends zero the padding of these, so that their bytes can be compared or hashed as a whole.
+    pub fn is_byte_comparable<I>(&self, interner: &I) -> bool
+    where
+        I: LayoutInterner<'a>,
+    {
+        use Layout::*;
+
+        match self {
+            Builtin(builtin) => builtin.is_byte_comparable(),
+            Struct { field_layouts, .. } => field_layouts
+                .iter()
+                .all(|field_layout| interner.get(*field_layout).is_byte_comparable(interner)),
+            Union(UnionLayout::NonRecursive(tags)) => tags.iter().all(|tag_layout| {
+                tag_layout
+                    .iter()
+                    .all(|field| interner.get(*field).is_byte_comparable(interner))
+            }),
+            // the pointers differ, even when what they point to is equal
+            Union(_) | Boxed(_) | RecursivePointer(_) => false,
+            LambdaSet(lambda_set) => interner
+                .get(lambda_set.runtime_representation())
+                .is_byte_comparable(interner),
+        }
+    }
+
     pub fn is_dropped_because_empty(&self) -> bool {
         // For this calculation, we don't need an accurate
         // stack size, we just need to know whether it's zero,
@@ -2864,6 +2929,18 @@ impl<'a> Builtin<'a> {
         }
     }
 
+    /// Floats are not, because `0.0 == -0.0` and `NaN != NaN`.
+    /// `Str` and `List` are not, because they point to their bytes.
+    pub fn is_byte_comparable(&self) -> bool {
+        use Builtin::*;
+
+        match self {
+            Int(_) | Bool | Decimal => true,
+
+            Float(_) | Str | List(_) => false,
+        }
+    }
+
     // Question: does is_refcounted exactly correspond with the "safe to memcpy" property?
     pub fn is_refcounted(&self) -> bool {
         use Builtin::*;
@@ -4419,6 +4496,175 @@ impl<'a> LayoutIds<'a> {
     }
 }
 
+/// The canonical name of a layout, e.g. `List (Box I64)` or `{ Str, U8 }`, as returned by
+/// `Inspect.typeName`.
+///
+/// Layouts do not retain record field names or tag names, so structs are printed by their field
+/// layouts and tags are printed by their tag id (e.g. `[#0 I64, #1]`). Recursive pointers are
+/// printed as `*`.
+pub fn layout_to_type_name<'a, I>(interner: &I, layout: Layout<'a>) -> String
+where
+    I: LayoutInterner<'a>,
+{
+    let mut buf = String::new();
+
+    write_type_name(interner, layout, false, &mut buf);
+
+    buf
+}
+
+fn write_type_name<'a, I>(interner: &I, layout: Layout<'a>, in_type_param: bool, buf: &mut String)
+where
+    I: LayoutInterner<'a>,
+{
+    let write_interned = |layout: InLayout<'a>, in_type_param: bool, buf: &mut String| {
+        write_type_name(interner, interner.get(layout), in_type_param, buf)
+    };
+
+    match layout {
+        Layout::Builtin(builtin) => match builtin {
+            Builtin::Int(int_width) => buf.push_str(match int_width {
+                IntWidth::I8 => "I8",
+                IntWidth::I16 => "I16",
+                IntWidth::I32 => "I32",
+                IntWidth::I64 => "I64",
+                IntWidth::I128 => "I128",
+                IntWidth::U8 => "U8",
+                IntWidth::U16 => "U16",
+                IntWidth::U32 => "U32",
+                IntWidth::U64 => "U64",
+                IntWidth::U128 => "U128",
+            }),
+            Builtin::Float(float_width) => buf.push_str(match float_width {
+                FloatWidth::F32 => "F32",
+                FloatWidth::F64 => "F64",
+            }),
+            Builtin::Bool => buf.push_str("Bool"),
+            Builtin::Decimal => buf.push_str("Dec"),
+            Builtin::Str => buf.push_str("Str"),
+            Builtin::List(elem) => {
+                if in_type_param {
+                    buf.push('(');
+                }
+                buf.push_str("List ");
+                write_interned(elem, true, buf);
+                if in_type_param {
+                    buf.push(')');
+                }
+            }
+        },
+        Layout::Struct { field_layouts, .. } => {
+            if field_layouts.is_empty() {
+                buf.push_str("{}");
+            } else {
+                buf.push_str("{ ");
+                for (i, field) in field_layouts.iter().enumerate() {
+                    if i > 0 {
+                        buf.push_str(", ");
+                    }
+                    write_interned(*field, false, buf);
+                }
+                buf.push_str(" }");
+            }
+        }
+        Layout::Boxed(inner) => {
+            if in_type_param {
+                buf.push('(');
+            }
+            buf.push_str("Box ");
+            write_interned(inner, true, buf);
+            if in_type_param {
+                buf.push(')');
+            }
+        }
+        Layout::Union(union_layout) => {
+            let empty: &[InLayout<'a>] = &[];
+
+            match union_layout {
+                UnionLayout::NonRecursive(tags) | UnionLayout::Recursive(tags) => {
+                    write_tags_type_name(interner, tags, buf)
+                }
+                UnionLayout::NonNullableUnwrapped(fields) => {
+                    write_tags_type_name(interner, &[fields], buf)
+                }
+                UnionLayout::NullableWrapped {
+                    nullable_id,
+                    other_tags,
+                } => {
+                    let mut tags = std::vec::Vec::with_capacity(other_tags.len() + 1);
+                    tags.extend_from_slice(other_tags);
+                    tags.insert(nullable_id as usize, empty);
+
+                    write_tags_type_name(interner, &tags, buf)
+                }
+                UnionLayout::NullableUnwrapped {
+                    nullable_id,
+                    other_fields,
+                } => {
+                    let tags = if nullable_id {
+                        [other_fields, empty]
+                    } else {
+                        [empty, other_fields]
+                    };
+
+                    write_tags_type_name(interner, &tags, buf)
+                }
+            }
+        }
+        Layout::LambdaSet(lambda_set) => {
+            write_interned(lambda_set.representation, in_type_param, buf)
+        }
+        Layout::RecursivePointer(_) => buf.push('*'),
+    }
+}
+
+fn write_tags_type_name<'a, I>(interner: &I, tags: &[&[InLayout<'a>]], buf: &mut String)
+where
+    I: LayoutInterner<'a>,
+{
+    buf.push('[');
+    for (tag_id, fields) in tags.iter().enumerate() {
+        if tag_id > 0 {
+            buf.push_str(", ");
+        }
+        buf.push('#');
+        buf.push_str(&tag_id.to_string());
+        for field in fields.iter() {
+            buf.push(' ');
+            write_type_name(interner, interner.get(*field), true, buf);
+        }
+    }
+    buf.push(']');
+}
+
+/// The byte offset of each field in a struct with the given (sorted) field layouts.
+///
+/// Every field is placed at the first offset after the previous field that satisfies its
+/// alignment. These are the same rules LLVM applies to the non-packed struct types that gen_llvm
+/// builds for a struct layout, so the dev backend should use this to locate fields rather than
+/// re-deriving the offsets itself.
+pub fn field_offsets<'a, I>(
+    interner: &I,
+    field_layouts: &[InLayout<'a>],
+) -> std::vec::Vec<(u32, InLayout<'a>)>
+where
+    I: LayoutInterner<'a>,
+{
+    let mut offset = 0;
+
+    field_layouts
+        .iter()
+        .map(|field_layout| {
+            let (size, alignment) = interner.stack_size_and_alignment(*field_layout);
+
+            let field_offset = round_up_to_alignment(offset, alignment);
+            offset = field_offset + size;
+
+            (field_offset, *field_layout)
+        })
+        .collect()
+}
+
 /// Compare two fields when sorting them for code gen.
 /// This is called by both code gen and glue, so that
 /// their field orderings agree.
@@ -4467,6 +4713,59 @@ mod test {
         assert_eq!(layout.alignment_bytes(&interner, target_info), 1);
     }
 
+    #[test]
+    fn single_function_lambda_sets_are_function_pointers() {
+        let mut interner = STLayoutInterner::with_capacity(4, TargetInfo::default_x86_64());
+
+        let no_captures = LambdaSet {
+            args: &(&[] as &[InLayout]),
+            ret: Layout::VOID,
+            set: &(&[(Symbol::LIST_MAP, &[] as &[InLayout])] as &[(Symbol, &[InLayout])]),
+            representation: Layout::UNIT,
+            full_layout: Layout::VOID,
+        };
+        assert_eq!(
+            LambdaSetResolver::simplify(&interner, &no_captures),
+            SimplifiedLambdaSet::FunctionPointer(LambdaName::no_niche(Symbol::LIST_MAP))
+        );
+
+        let captures = interner.insert(Layout::struct_no_name_order(&[Layout::STR, Layout::U8]));
+        let with_captures = LambdaSet {
+            set: &(&[(Symbol::LIST_MAP, &[Layout::STR, Layout::U8] as &[InLayout])]
+                as &[(Symbol, &[InLayout])]),
+            representation: captures,
+            ..no_captures
+        };
+        assert_eq!(
+            LambdaSetResolver::simplify(&interner, &with_captures),
+            SimplifiedLambdaSet::Closure(captures)
+        );
+    }
+
+    #[test]
+    fn closure_env_size_is_aligned() {
+        let sizes = [
+            (TargetInfo::default_x86_64(), 32),
+            (TargetInfo::default_wasm32(), 16),
+        ];
+        for (target_info, expected) in sizes {
+            let mut interner = STLayoutInterner::with_capacity(4, target_info);
+            let captures =
+                interner.insert(Layout::struct_no_name_order(&[Layout::STR, Layout::U8]));
+
+            let lambda_set = LambdaSet {
+                args: &(&[] as &[InLayout]),
+                ret: Layout::VOID,
+                set: &(&[(Symbol::LIST_MAP, &[Layout::STR, Layout::U8] as &[InLayout])]
+                    as &[(Symbol, &[InLayout])]),
+                representation: captures,
+                full_layout: Layout::VOID,
+            };
+
+            assert_eq!(closure_env_size_bytes(&interner, &lambda_set), expected);
+        }
+    }
+
     #[test]
     fn memcpy_size_result_u32_unit() {
         let mut interner = STLayoutInterner::with_capacity(4, TargetInfo::default_x86_64());
@@ -4484,6 +4783,86 @@ mod test {
         );
     }
 
+    #[test]
+    fn field_offsets_are_aligned() {
+        let interner = STLayoutInterner::with_capacity(4, TargetInfo::default_x86_64());
+
+        let fields = [
+            Layout::STR,
+            Layout::I64,
+            Layout::U32,
+            Layout::U8,
+            Layout::U16,
+        ];
+
+        assert_eq!(
+            field_offsets(&interner, &fields),
+            vec![
+                (0, Layout::STR),
+                (24, Layout::I64),
+                (32, Layout::U32),
+                (36, Layout::U8),
+                (38, Layout::U16),
+            ]
+        );
+    }
+
+    #[test]
+    fn type_name_primitives() {
+        let interner = STLayoutInterner::with_capacity(4, TargetInfo::default_x86_64());
+
+        let name = |layout: InLayout| layout_to_type_name(&interner, interner.get(layout));
+
+        assert_eq!(name(Layout::I64), "I64");
+        assert_eq!(name(Layout::U8), "U8");
+        assert_eq!(name(Layout::F32), "F32");
+        assert_eq!(name(Layout::DEC), "Dec");
+        assert_eq!(name(Layout::BOOL), "Bool");
+        assert_eq!(name(Layout::STR), "Str");
+        assert_eq!(name(Layout::UNIT), "{}");
+    }
+
+    #[test]
+    fn type_name_list() {
+        let mut interner = STLayoutInterner::with_capacity(4, TargetInfo::default_x86_64());
+
+        let list_i64 = interner.insert(Layout::Builtin(Builtin::List(Layout::I64)));
+        let list_list_i64 = Layout::Builtin(Builtin::List(list_i64));
+
+        assert_eq!(
+            layout_to_type_name(&interner, interner.get(list_i64)),
+            "List I64"
+        );
+        assert_eq!(
+            layout_to_type_name(&interner, list_list_i64),
+            "List (List I64)"
+        );
+    }
+
+    #[test]
+    fn type_name_record() {
+        let interner = STLayoutInterner::with_capacity(4, TargetInfo::default_x86_64());
+
+        let layout = Layout::struct_no_name_order(&[Layout::STR, Layout::U8]);
+
+        assert_eq!(layout_to_type_name(&interner, layout), "{ Str, U8 }");
+    }
+
+    #[test]
+    fn type_name_tag_union() {
+        let interner = STLayoutInterner::with_capacity(4, TargetInfo::default_x86_64());
+
+        let ok_tag = &[Layout::U32];
+        let err_tag = &[Layout::STR, Layout::I64];
+        let tags = [ok_tag as &[_], err_tag as &[_], &[]];
+        let layout = Layout::Union(UnionLayout::NonRecursive(&tags as &[_]));
+
+        assert_eq!(
+            layout_to_type_name(&interner, layout),
+            "[#0 U32, #1 Str I64, #2]"
+        );
+    }
+
     #[test]
     fn void_stack_size() {
         let interner = STLayoutInterner::with_capacity(4, TargetInfo::default_x86_64());
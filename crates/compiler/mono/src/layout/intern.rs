@@ -229,6 +229,10 @@ pub trait LayoutInterner<'a>: Sized {
         self.get(layout).safe_to_memcpy(self)
     }
 
+    fn is_byte_comparable(&self, layout: InLayout<'a>) -> bool {
+        self.get(layout).is_byte_comparable(self)
+    }
+
     /// Checks if two layouts are equivalent up to isomorphism.
     ///
     /// This is only to be used when layouts need to be compared across statements and depths,
@@ -454,7 +458,7 @@ pub struct TLLayoutInterner<'a> {
 ///
 /// The only way to construct such an interner is to collapse a shared [GlobalLayoutInterner] into
 /// a [STLayoutInterner], via [GlobalLayoutInterner::unwrap].
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct STLayoutInterner<'a> {
     map: BumpMap<Layout<'a>, InLayout<'a>>,
     normalized_lambda_set_map: BumpMap<LambdaSet<'a>, LambdaSet<'a>>,
@@ -1690,3 +1694,60 @@ mod insert_recursive_layout {
         assert_eq!(in1, in2);
     }
 }
+
+#[cfg(test)]
+mod insert_struct {
+    use bumpalo::Bump;
+    use roc_target::TargetInfo;
+
+    use crate::layout::{Builtin, InLayout, Layout};
+
+    use super::{GlobalLayoutInterner, LayoutInterner};
+
+    const TARGET_INFO: TargetInfo = TargetInfo::default_x86_64();
+
+    /// `{ a : U8, b : List Str }`, built from scratch in `arena`
+    fn make_record<'a>(arena: &'a Bump, interner: &mut impl LayoutInterner<'a>) -> InLayout<'a> {
+        let list = interner.insert(Layout::Builtin(Builtin::List(Layout::STR)));
+        let fields = &*arena.alloc([Layout::U8, list]);
+
+        interner.insert(Layout::struct_no_name_order(fields))
+    }
+
+    #[test]
+    fn equal_structs_in_different_arenas_are_one_layout() {
+        let global = GlobalLayoutInterner::with_capacity(2, TARGET_INFO);
+        let mut interner = global.fork();
+        let (arena1, arena2) = (Bump::new(), Bump::new());
+
+        let in1 = make_record(&arena1, &mut interner);
+        let in2 = make_record(&arena2, &mut interner);
+        assert_eq!(in1, in2);
+
+        drop(interner);
+        let mut st_interner = global.unwrap().unwrap();
+        let in3 = make_record(&arena2, &mut st_interner);
+        assert_eq!(in1, in3);
+    }
+
+    #[test]
+    fn structs_with_other_fields_are_other_layouts() {
+        let arena = &Bump::new();
+        let global = GlobalLayoutInterner::with_capacity(2, TARGET_INFO);
+        let mut interner = global.fork();
+
+        let record = make_record(arena, &mut interner);
+        let swapped = {
+            let list = interner.insert(Layout::Builtin(Builtin::List(Layout::STR)));
+            interner.insert(Layout::struct_no_name_order(
+                &*arena.alloc([list, Layout::U8]),
+            ))
+        };
+        let narrower = interner.insert(Layout::struct_no_name_order(
+            &*arena.alloc([Layout::U8, Layout::STR]),
+        ));
+
+        assert_ne!(record, swapped);
+        assert_ne!(record, narrower);
+    }
+}
@@ -23,6 +23,9 @@ pub enum HigherOrder {
     ListSortWith {
         xs: Symbol,
     },
+    BoxMap {
+        boxed: Symbol,
+    },
 }
 
 impl HigherOrder {
@@ -33,6 +36,7 @@ impl HigherOrder {
             HigherOrder::ListMap3 { .. } => 3,
             HigherOrder::ListMap4 { .. } => 4,
             HigherOrder::ListSortWith { .. } => 2,
+            HigherOrder::BoxMap { .. } => 1,
         }
     }
 
@@ -42,7 +46,7 @@ impl HigherOrder {
         use HigherOrder::*;
 
         match self {
-            ListMap { .. } | ListSortWith { .. } => 2,
+            ListMap { .. } | ListSortWith { .. } | BoxMap { .. } => 2,
             ListMap2 { .. } => 3,
             ListMap3 { .. } => 4,
             ListMap4 { .. } => 5,
@@ -832,7 +832,7 @@ fn has_live_var<'a>(jp_live_vars: &JPLiveVarMap, stmt: &'a Stmt<'a>, needle: Sym
         Jump(id, arguments) => {
             arguments.iter().any(|s| *s == needle) || jp_live_vars[id].contains(&needle)
         }
-        Crash(m, _) => *m == needle,
+        Crash(m, _, _) => *m == needle,
     }
 }
 
@@ -5,6 +5,20 @@
 
 use strum_macros::{EnumCount, EnumIter};
 
+/// The version of the compiler, which both backends put in the objects they emit
+pub const COMPILER_VERSION: &str = include_str!("../../../../version.txt");
+
+/// Who produced an object, like "roc dev backend 0.0.1 target x86_64-unknown-linux-gnu", so a
+/// crash in its code can be traced back to the backend and the compiler that built it
+pub fn producer(backend: &str, target: &target_lexicon::Triple) -> String {
+    format!(
+        "roc {} backend {} target {}",
+        backend,
+        COMPILER_VERSION.trim(),
+        target
+    )
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum OperatingSystem {
     Windows,
@@ -53,6 +67,9 @@ impl From<target_lexicon::OperatingSystem> for OperatingSystem {
 pub struct TargetInfo {
     pub architecture: Architecture,
     pub operating_system: OperatingSystem,
+    /// Keep the generated code from using the stack below the stack pointer, which hosts that
+    /// run Roc code in signal handlers need, because the red zone may be clobbered there.
+    pub disable_red_zone: bool,
 }
 
 impl TargetInfo {
@@ -71,10 +88,15 @@ impl TargetInfo {
         self.architecture.ptr_alignment_bytes()
     }
 
+    pub const fn endianness(&self) -> Endianness {
+        self.architecture.endianness()
+    }
+
     pub const fn default_aarch64() -> Self {
         TargetInfo {
             architecture: Architecture::Aarch64,
             operating_system: OperatingSystem::Unix,
+            disable_red_zone: false,
         }
     }
 
@@ -82,6 +104,7 @@ impl TargetInfo {
         TargetInfo {
             architecture: Architecture::X86_64,
             operating_system: OperatingSystem::Unix,
+            disable_red_zone: false,
         }
     }
 
@@ -89,6 +112,14 @@ impl TargetInfo {
         TargetInfo {
             architecture: Architecture::Wasm32,
             operating_system: OperatingSystem::Wasi,
+            disable_red_zone: false,
+        }
+    }
+
+    pub const fn without_red_zone(self) -> Self {
+        TargetInfo {
+            disable_red_zone: true,
+            ..self
         }
     }
 }
@@ -101,10 +132,51 @@ impl From<&target_lexicon::Triple> for TargetInfo {
         Self {
             architecture,
             operating_system,
+            disable_red_zone: false,
         }
     }
 }
 
+/// The order of the bytes of a scalar in the memory of the target, which is not necessarily
+/// the order on the machine that runs the compiler.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Endianness {
+    Little,
+    Big,
+}
+
+macro_rules! scalar_bytes {
+    ($($to_bytes:ident, $from_bytes:ident: $scalar:ty;)*) => {
+        impl Endianness {
+            $(
+                pub fn $to_bytes(self, value: $scalar) -> [u8; std::mem::size_of::<$scalar>()] {
+                    match self {
+                        Endianness::Little => value.to_le_bytes(),
+                        Endianness::Big => value.to_be_bytes(),
+                    }
+                }
+
+                pub fn $from_bytes(self, bytes: [u8; std::mem::size_of::<$scalar>()]) -> $scalar {
+                    match self {
+                        Endianness::Little => <$scalar>::from_le_bytes(bytes),
+                        Endianness::Big => <$scalar>::from_be_bytes(bytes),
+                    }
+                }
+            )*
+        }
+    };
+}
+
+scalar_bytes! {
+    u16_bytes, u16_from_bytes: u16;
+    u32_bytes, u32_from_bytes: u32;
+    u64_bytes, u64_from_bytes: u64;
+    i32_bytes, i32_from_bytes: i32;
+    i64_bytes, i64_from_bytes: i64;
+    f32_bytes, f32_from_bytes: f32;
+    f64_bytes, f64_from_bytes: f64;
+}
+
 #[repr(u8)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum PtrWidth {
@@ -136,6 +208,22 @@ impl Architecture {
     pub const fn ptr_alignment_bytes(&self) -> usize {
         self.ptr_width() as usize
     }
+
+    pub const fn is_big_endian(&self) -> bool {
+        use Architecture::*;
+
+        match self {
+            X86_64 | X86_32 | Aarch64 | Aarch32 | Wasm32 => false,
+        }
+    }
+
+    pub const fn endianness(&self) -> Endianness {
+        if self.is_big_endian() {
+            Endianness::Big
+        } else {
+            Endianness::Little
+        }
+    }
 }
 
 impl From<target_lexicon::Architecture> for Architecture {
@@ -54,6 +54,7 @@ fn roc_function<'a, 'b>(
         ignore_problems: false,
         add_debug_info: true,
         opt_level: OptLevel::Optimize,
+        strict_fp_exceptions: false,
     };
 
     let context = inkwell::context::Context::create();
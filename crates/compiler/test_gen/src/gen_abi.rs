@@ -0,0 +1,262 @@
+//! The native backends, the Zig builtins, and `roc_std` all have to agree on the bytes of a value:
+//! where the padding of a struct goes, where a union keeps its tag id, how a small string is
+//! marked, and where the capacity of a list is. Every case below has each enabled backend build
+//! a canonical value, and compares its bytes with an image written out by hand. With the LLVM and
+//! dev backends both enabled, that also means they agree with each other, padding aside.
+
+#[cfg(feature = "gen-dev")]
+use crate::helpers::dev;
+#[cfg(feature = "gen-llvm")]
+use crate::helpers::llvm;
+
+#[allow(unused_imports)]
+use indoc::indoc;
+#[allow(unused_imports)]
+use roc_std::{RocList, RocStr};
+
+/// The bytes in `image`, like "07 00 __", where `__` is padding that can be anything
+#[allow(dead_code)]
+fn parse_image(image: &str) -> Vec<Option<u8>> {
+    image
+        .split_whitespace()
+        .map(|byte| match byte {
+            "__" => None,
+            _ => Some(u8::from_str_radix(byte, 16).unwrap()),
+        })
+        .collect()
+}
+
+#[allow(dead_code)]
+fn show_bytes(bytes: &[u8]) -> String {
+    let bytes: Vec<_> = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+    bytes.join(" ")
+}
+
+/// The bytes of `value`. It is leaked, so nothing can free memory a backend allocated.
+#[allow(dead_code)]
+fn bytes_of<T>(value: T) -> Vec<u8> {
+    let value = std::mem::ManuallyDrop::new(value);
+    let ptr = &*value as *const T as *const u8;
+
+    unsafe { std::slice::from_raw_parts(ptr, std::mem::size_of::<T>()) }.to_vec()
+}
+
+#[cfg(feature = "gen-llvm")]
+fn llvm_bytes<T>(src: &str) -> Vec<u8> {
+    use roc_gen_llvm::llvm::build::LlvmBackendMode;
+
+    let arena = bumpalo::Bump::new();
+    let context = inkwell::context::Context::create();
+    let config = llvm::HelperConfig {
+        mode: LlvmBackendMode::GenTest,
+        add_debug_info: false,
+        ignore_problems: false,
+        opt_level: llvm::OPT_LEVEL,
+        strict_fp_exceptions: false,
+    };
+
+    let (main_fn_name, errors, lib) = llvm::helper(&arena, config, src, &context);
+    assert!(errors.is_empty(), "Encountered errors:\n{}", errors);
+
+    match llvm::try_run_lib_function::<T>(main_fn_name, &lib) {
+        Ok(value) => bytes_of(value),
+        Err((msg, _)) => panic!(r#"Roc failed with message: "{}""#, msg),
+    }
+}
+
+#[cfg(feature = "gen-dev")]
+fn dev_bytes<T>(src: &str) -> Vec<u8> {
+    let arena = bumpalo::Bump::new();
+    let (main_fn_name, errors, lib) = dev::helper(&arena, src, true, true, false);
+    assert!(errors.is_empty(), "Encountered errors: {:?}", errors);
+
+    unsafe {
+        let main: libloading::Symbol<unsafe extern "C" fn() -> T> = lib
+            .get(main_fn_name.as_bytes())
+            .expect("the main function is missing");
+
+        bytes_of(main())
+    }
+}
+
+/// Checks that every enabled backend evaluates `src` to a `T` with the bytes in `image`
+#[allow(dead_code)]
+fn assert_layout_image<T>(src: &str, image: &str) {
+    let expected = parse_image(image);
+    assert_eq!(
+        expected.len(),
+        std::mem::size_of::<T>(),
+        "the image for {:?} does not have the size of {}",
+        src,
+        std::any::type_name::<T>()
+    );
+
+    #[allow(unused_mut)]
+    let mut results: Vec<(&str, Vec<u8>)> = Vec::new();
+    #[cfg(feature = "gen-llvm")]
+    results.push(("LLVM", llvm_bytes::<T>(src)));
+    #[cfg(feature = "gen-dev")]
+    results.push(("dev", dev_bytes::<T>(src)));
+
+    for (backend, bytes) in results {
+        let matches = expected
+            .iter()
+            .zip(bytes.iter())
+            .all(|(expected, byte)| expected.map_or(true, |expected| expected == *byte));
+
+        assert!(
+            matches,
+            "the {} backend lays out {:?} as\n    {}\nbut it should be\n    {}",
+            backend,
+            src,
+            show_bytes(&bytes),
+            image
+        );
+    }
+}
+
+/// A `{ a : U8, b : U64 }`, which Roc sorts by alignment
+#[allow(dead_code)]
+#[repr(C)]
+struct Padded {
+    b: u64,
+    a: u8,
+}
+
+/// A `[Left U8, Right U64]`, with the tag id after the payload
+#[allow(dead_code)]
+#[repr(C)]
+struct TwoTags {
+    payload: u64,
+    tag_id: u8,
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-dev"))]
+fn scalars() {
+    assert_layout_image::<u8>("7u8", "07");
+    assert_layout_image::<i16>(
+        indoc!(
+            r#"
+            x : I16
+            x = -2
+
+            x
+            "#
+        ),
+        "fe ff",
+    );
+    assert_layout_image::<u64>(
+        indoc!(
+            r#"
+            x : U64
+            x = 72623859790382856
+
+            x
+            "#
+        ),
+        "08 07 06 05 04 03 02 01",
+    );
+    assert_layout_image::<f64>(
+        indoc!(
+            r#"
+            x : F64
+            x = 1.5
+
+            x
+            "#
+        ),
+        "00 00 00 00 00 00 f8 3f",
+    );
+    assert_layout_image::<bool>("Bool.true", "01");
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-dev"))]
+fn padded_struct() {
+    assert_layout_image::<Padded>(
+        indoc!(
+            r#"
+            x : { a : U8, b : U64 }
+            x = { a: 7, b: 72623859790382856 }
+
+            x
+            "#
+        ),
+        "08 07 06 05 04 03 02 01 07 __ __ __ __ __ __ __",
+    );
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-dev"))]
+fn two_tag_union() {
+    assert_layout_image::<TwoTags>(
+        indoc!(
+            r#"
+            x : [Left U8, Right U64]
+            x = Left 9
+
+            x
+            "#
+        ),
+        "09 __ __ __ __ __ __ __ 00 __ __ __ __ __ __ __",
+    );
+    assert_layout_image::<TwoTags>(
+        indoc!(
+            r#"
+            x : [Left U8, Right U64]
+            x = Right 72623859790382856
+
+            x
+            "#
+        ),
+        "08 07 06 05 04 03 02 01 01 __ __ __ __ __ __ __",
+    );
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-dev"))]
+fn small_str() {
+    // the bytes, zeroes up to the last byte, and the length with the small string flag set
+    assert_layout_image::<RocStr>(
+        r#""hello""#,
+        "68 65 6c 6c 6f 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 85",
+    );
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-dev"))]
+fn list() {
+    // the pointer to the elements, the length, and the capacity
+    assert_layout_image::<RocList<u8>>(
+        indoc!(
+            r#"
+            x : List U8
+            x = [1, 2, 3]
+
+            x
+            "#
+        ),
+        "__ __ __ __ __ __ __ __ 03 00 00 00 00 00 00 00 03 00 00 00 00 00 00 00",
+    );
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-dev"))]
+fn padding_is_zeroed() {
+    // the smaller tag leaves a word of the union unused, which is zeroed like padding,
+    // so the value has the same bytes every time and can be hashed or compared byte-wise
+    for _ in 0..3 {
+        assert_layout_image::<[u64; 3]>(
+            indoc!(
+                r#"
+                x : [Left U64, Right { a : U64, b : U64 }]
+                x = Left 7
+
+                x
+                "#
+            ),
+            "07 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00",
+        );
+    }
+}
@@ -1180,6 +1180,156 @@ fn decode_record_of_record() {
     )
 }
 
+#[cfg(all(test, any(feature = "gen-llvm", feature = "gen-wasm")))]
+mod bytes_le {
+    #[cfg(feature = "gen-llvm")]
+    use crate::helpers::llvm::assert_evals_to;
+
+    #[cfg(feature = "gen-wasm")]
+    use crate::helpers::wasm::assert_evals_to;
+
+    use indoc::indoc;
+    use roc_std::RocList;
+
+    #[test]
+    fn append_int() {
+        assert_evals_to!(
+            indoc!(
+                r#"
+                app "test" imports [Encode] provides [main] to "./platform"
+
+                main = Encode.appendNumLe [0xFF] 0x01020304u32
+                "#
+            ),
+            RocList::from_slice(&[0xFF, 0x04, 0x03, 0x02, 0x01]),
+            RocList<u8>
+        )
+    }
+
+    #[test]
+    fn append_numbers_of_every_width() {
+        let mut expected = vec![0xFF];
+        expected.extend_from_slice(&(-2i16).to_le_bytes());
+        expected.extend_from_slice(&0x0102_0304_0506_0708u64.to_le_bytes());
+        expected.extend_from_slice(&(-3i128).to_le_bytes());
+        expected.extend_from_slice(&1.5f32.to_le_bytes());
+        expected.extend_from_slice(&(-0.25f64).to_le_bytes());
+
+        assert_evals_to!(
+            indoc!(
+                r#"
+                app "test" imports [Encode] provides [main] to "./platform"
+
+                main =
+                    [0xFF]
+                    |> Encode.appendNumLe -2i16
+                    |> Encode.appendNumLe 0x0102030405060708u64
+                    |> Encode.appendNumLe -3i128
+                    |> Encode.appendNumLe 1.5f32
+                    |> Encode.appendNumLe -0.25f64
+                "#
+            ),
+            RocList::from_slice(&expected),
+            RocList<u8>
+        )
+    }
+
+    #[test]
+    fn read_int() {
+        assert_evals_to!(
+            indoc!(
+                r#"
+                app "test" imports [Decode] provides [main] to "./platform"
+
+                main =
+                    result : Result U32 [TooShort]
+                    result = Decode.numFromBytesLe [0xFF, 0x04, 0x03, 0x02, 0x01] 1
+
+                    when result is
+                        Ok n -> n
+                        Err TooShort -> 0
+                "#
+            ),
+            0x01020304,
+            u32
+        )
+    }
+
+    #[test]
+    fn read_too_short() {
+        assert_evals_to!(
+            indoc!(
+                r#"
+                app "test" imports [Decode] provides [main] to "./platform"
+
+                main =
+                    result : Result U32 [TooShort]
+                    result = Decode.numFromBytesLe [0x04, 0x03, 0x02, 0x01] 1
+
+                    when result is
+                        Ok _ -> "read past the end"
+                        Err TooShort -> "too short"
+                "#
+            ),
+            roc_std::RocStr::from("too short"),
+            roc_std::RocStr
+        )
+    }
+
+    #[test]
+    fn read_past_the_end() {
+        assert_evals_to!(
+            indoc!(
+                r#"
+                app "test" imports [Decode] provides [main] to "./platform"
+
+                main =
+                    result : Result U8 [TooShort]
+                    result = Decode.numFromBytesLe [0x01] 2
+
+                    when result is
+                        Ok _ -> "read past the end"
+                        Err TooShort -> "too short"
+                "#
+            ),
+            roc_std::RocStr::from("too short"),
+            roc_std::RocStr
+        )
+    }
+
+    #[test]
+    fn round_trip() {
+        assert_evals_to!(
+            indoc!(
+                r#"
+                app "test" imports [Encode, Decode] provides [main] to "./platform"
+
+                main =
+                    bytes =
+                        []
+                        |> Encode.appendNumLe -12345678i64
+                        |> Encode.appendNumLe 2.5f64
+
+                    int : Result I64 [TooShort]
+                    int = Decode.numFromBytesLe bytes 0
+
+                    float : Result F64 [TooShort]
+                    float = Decode.numFromBytesLe bytes 8
+
+                    when int is
+                        Ok i ->
+                            when float is
+                                Ok f -> Num.toF64 i + f
+                                Err TooShort -> 0
+                        Err TooShort -> 0
+                "#
+            ),
+            -12345675.5,
+            f64
+        )
+    }
+}
+
 #[cfg(all(test, any(feature = "gen-llvm", feature = "gen-wasm")))]
 mod hash {
     #[cfg(feature = "gen-llvm")]
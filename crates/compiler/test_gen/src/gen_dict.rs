@@ -550,3 +550,74 @@ fn walk_sum_keys() {
         i64
     );
 }
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
+fn update_missing_inserts() {
+    assert_evals_to!(
+        indoc!(
+            r#"
+            alter : [Present I64, Missing] -> [Present I64, Missing]
+            alter = \possibleValue ->
+                when possibleValue is
+                    Missing -> Present 100
+                    Present value -> Present (value + 1)
+
+            Dict.empty {}
+                |> Dict.insert 1 1
+                |> Dict.update 2 alter
+                |> Dict.values
+            "#
+        ),
+        RocList::from_slice(&[1, 100]),
+        RocList<i64>
+    );
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
+fn update_present_updates() {
+    assert_evals_to!(
+        indoc!(
+            r#"
+            alter : [Present I64, Missing] -> [Present I64, Missing]
+            alter = \possibleValue ->
+                when possibleValue is
+                    Missing -> Present 100
+                    Present value -> Present (value + 1)
+
+            Dict.empty {}
+                |> Dict.insert 1 1
+                |> Dict.insert 2 2
+                |> Dict.update 2 alter
+                |> Dict.values
+            "#
+        ),
+        RocList::from_slice(&[1, 3]),
+        RocList<i64>
+    );
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
+fn update_present_removes() {
+    assert_evals_to!(
+        indoc!(
+            r#"
+            alter : [Present I64, Missing] -> [Present I64, Missing]
+            alter = \possibleValue ->
+                when possibleValue is
+                    Missing -> Present 100
+                    Present _ -> Missing
+
+            Dict.empty {}
+                |> Dict.insert 1 1
+                |> Dict.insert 2 2
+                |> Dict.update 1 alter
+                |> Dict.keys
+            "#
+        ),
+        RocList::from_slice(&[2]),
+        RocList<i64>
+    );
+}
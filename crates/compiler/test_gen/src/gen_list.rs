@@ -51,6 +51,36 @@ fn int_list_literal() {
     );
 }
 
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
+fn list_literal_constants_and_runtime_values() {
+    assert_evals_to!(
+        indoc!(
+            r#"
+               x : U8
+               x = 7
+
+               [1, x, 3, x]
+               "#
+        ),
+        RocList::from_slice(&[1u8, 7, 3, 7]),
+        RocList<u8>
+    );
+
+    assert_evals_to!(
+        indoc!(
+            r#"
+               y : F64
+               y = 2.5
+
+               [y, 0.5, 1.5]
+               "#
+        ),
+        RocList::from_slice(&[2.5f64, 0.5, 1.5]),
+        RocList<f64>
+    );
+}
+
 #[test]
 #[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
 fn bool_list_literal() {
@@ -1253,6 +1283,71 @@ fn list_map_changes_input() {
     );
 }
 
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
+fn list_map_increments_each_byte() {
+    // the input is still used afterwards, so its bytes must be left as they are
+    assert_evals_to!(
+        indoc!(
+            r#"
+            bytes : List U8
+            bytes = [0, 1, 254, 7, 128]
+
+            (bytes, List.map bytes (\b -> b + 1))
+            "#
+        ),
+        (
+            RocList::from_slice(&[0, 1, 254, 7, 128]),
+            RocList::from_slice(&[1, 2, 255, 8, 129])
+        ),
+        (RocList<u8>, RocList<u8>)
+    );
+
+    assert_evals_to!(
+        indoc!(
+            r#"
+            List.map [0u8, 1, 254, 7, 128] (\b -> b + 1)
+            "#
+        ),
+        RocList::from_slice(&[1, 2, 255, 8, 129]),
+        RocList<u8>
+    );
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
+fn list_map_on_small_numbers() {
+    assert_evals_to!(
+        indoc!(
+            r#"
+            List.map [-127i8, -1, 0, 126] (\n -> n + 1)
+            "#
+        ),
+        RocList::from_slice(&[-126, 0, 1, 127]),
+        RocList<i8>
+    );
+
+    assert_evals_to!(
+        indoc!(
+            r#"
+            List.map [0u16, 255, 256, 65534] (\n -> n + 1)
+            "#
+        ),
+        RocList::from_slice(&[1, 256, 257, 65535]),
+        RocList<u16>
+    );
+
+    assert_evals_to!(
+        indoc!(
+            r#"
+            List.map [-32767i16, -1, 255, 32766] (\n -> n + 1)
+            "#
+        ),
+        RocList::from_slice(&[-32766, 0, 256, 32767]),
+        RocList<i16>
+    );
+}
+
 #[test]
 #[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
 fn list_map_on_big_list() {
@@ -1550,6 +1645,43 @@ fn list_join_one_empty_list() {
     );
 }
 
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
+fn list_join_shares_refcounted_elements() {
+    assert_evals_to!(
+        indoc!(
+            r#"
+            x = ["a string that is too long to be small", "another string that is not small"]
+
+            List.join [x, [], x]
+            "#
+        ),
+        RocList::from_slice(&[
+            RocStr::from("a string that is too long to be small"),
+            RocStr::from("another string that is not small"),
+            RocStr::from("a string that is too long to be small"),
+            RocStr::from("another string that is not small"),
+        ]),
+        RocList<RocStr>
+    );
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
+fn list_join_nested_lists() {
+    assert_evals_to!(
+        indoc!(
+            r#"
+            x = [[1, 2], []]
+
+            List.join [x, [[3]], x] |> List.map List.len
+            "#
+        ),
+        RocList::from_slice(&[2, 0, 1, 2, 0]),
+        RocList<usize>
+    );
+}
+
 #[test]
 #[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
 fn list_single() {
@@ -2769,6 +2901,13 @@ fn list_sum() {
     assert_evals_to!("List.sum [1.1, 2.2, 3.3]", 6.6, f64);
 }
 
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
+#[should_panic(expected = r#"Roc failed with message: "integer addition overflowed!"#)]
+fn list_sum_overflow() {
+    assert_evals_to!("List.sum [200u8, 50, 6]", 0, u8);
+}
+
 #[test]
 #[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
 fn list_product() {
@@ -2777,6 +2916,97 @@ fn list_product() {
     assert_evals_to!("List.product [1.1, 2.2, 3.3]", 1.1 * 2.2 * 3.3, f64);
 }
 
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
+fn list_product_edge_cases() {
+    assert_evals_to!("List.product [-2, 3, -4]", 24, i64);
+    assert_evals_to!("List.product [5, 0, 7]", 0, i64);
+    assert_evals_to!("List.product [-7]", -7, i64);
+    assert_evals_to!("List.product [2u8, 3, 40]", 240, u8);
+    assert_evals_to!("List.product [3u32, 5, 7]", 105, u32);
+    assert_evals_to!("List.product [0.5f32, 4, -2]", -4.0, f32);
+    assert_evals_to!("List.product [Num.maxI64, 1, -1]", -i64::MAX, i64);
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
+#[should_panic(expected = r#"Roc failed with message: "integer multiplication overflowed!"#)]
+fn list_product_overflow() {
+    assert_evals_to!("List.product [16u8, 2, 8]", 0, u8);
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
+fn list_min_max_edge_cases() {
+    // a single element is both the minimum and the maximum
+    assert_evals_to!("List.min [7]", RocResult::ok(7), RocResult<i64, ()>);
+    assert_evals_to!("List.max [7]", RocResult::ok(7), RocResult<i64, ()>);
+
+    assert_evals_to!(
+        "List.min [4, -3, 9, -8, 0]",
+        RocResult::ok(-8),
+        RocResult<i64, ()>
+    );
+    assert_evals_to!(
+        "List.max [-4, -3, -9, -8]",
+        RocResult::ok(-3),
+        RocResult<i64, ()>
+    );
+    assert_evals_to!(
+        "List.min [Num.maxI64, Num.minI64, 0]",
+        RocResult::ok(i64::MIN),
+        RocResult<i64, ()>
+    );
+
+    // unsigned elements are compared as unsigned
+    assert_evals_to!(
+        "List.max [1u8, 255, 128]",
+        RocResult::ok(255),
+        RocResult<u8, ()>
+    );
+    assert_evals_to!(
+        "List.min [200u8, 100, 201]",
+        RocResult::ok(100),
+        RocResult<u8, ()>
+    );
+    assert_evals_to!(
+        "List.max [3u64, Num.maxU64, 0]",
+        RocResult::ok(u64::MAX),
+        RocResult<u64, ()>
+    );
+
+    assert_evals_to!(
+        "List.min [2.5, -1.5, 0.25]",
+        RocResult::ok(-1.5),
+        RocResult<f64, ()>
+    );
+    assert_evals_to!(
+        "List.max [2.5, -1.5, 0.25]",
+        RocResult::ok(2.5),
+        RocResult<f64, ()>
+    );
+    assert_evals_to!(
+        "List.max [-2.5f32, -1.5]",
+        RocResult::ok(-1.5),
+        RocResult<f32, ()>
+    );
+
+    assert_evals_to!(
+        indoc!(
+            r#"
+            empty : List U8
+            empty = []
+
+            when List.max empty is
+                Ok _ -> Bool.false
+                Err ListWasEmpty -> Bool.true
+            "#
+        ),
+        true,
+        bool
+    );
+}
+
 #[test]
 #[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
 fn list_keep_void() {
@@ -2898,6 +3128,42 @@ fn list_sort_with() {
     );
 }
 
+#[test]
+#[cfg(any(feature = "gen-llvm"))]
+fn list_sort_with_shared_list_of_strs() {
+    // the compare wrapper gets pointers into the middle of the list's buffer, with no refcount
+    // in front of them, and the original list has to stay as it was
+    assert_evals_to!(
+        indoc!(
+            r#"
+            original =
+                [
+                    "a string that is long enough to be on the heap, and the longest one",
+                    "short",
+                    "another string that is long enough to be on the heap",
+                ]
+
+            sorted = List.sortWith original \a, b -> Num.compare (Str.countUtf8Bytes a) (Str.countUtf8Bytes b)
+
+            { original, sorted }
+            "#
+        ),
+        (
+            RocList::from_slice(&[
+                RocStr::from("a string that is long enough to be on the heap, and the longest one"),
+                RocStr::from("short"),
+                RocStr::from("another string that is long enough to be on the heap"),
+            ]),
+            RocList::from_slice(&[
+                RocStr::from("short"),
+                RocStr::from("another string that is long enough to be on the heap"),
+                RocStr::from("a string that is long enough to be on the heap, and the longest one"),
+            ])
+        ),
+        (RocList<RocStr>, RocList<RocStr>)
+    );
+}
+
 #[test]
 #[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
 fn list_sort_asc() {
@@ -1176,6 +1176,27 @@ fn gen_unsigned_mul_quadword_and_lower() {
     assert_evals_to!("2u8 * 4 * 6", 48, u8);
 }
 
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-dev", feature = "gen-wasm"))]
+fn gen_mul_u64_keeps_live_values() {
+    // unsigned multiplication puts the upper half of the product in RDX, where `c` arrives with
+    // the x86_64 SysV calling convention
+    assert_evals_to!(
+        indoc!(
+            r#"
+            app "test" provides [main] to "./platform"
+
+            f : U64, U64, U64, U64 -> U64
+            f = \a, b, c, d -> a * b + c * 10 + d
+
+            main = f 3000000000 5 3 4
+            "#
+        ),
+        15000000034,
+        u64
+    );
+}
+
 #[test]
 #[cfg(any(feature = "gen-llvm", feature = "gen-dev", feature = "gen-wasm"))]
 fn gen_mul_f64() {
@@ -1200,6 +1221,259 @@ fn gen_div_u64() {
     assert_evals_to!("1000u64 // 10", 100, u64);
 }
 
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
+fn gen_div_keeps_live_values() {
+    // with the x86_64 SysV calling convention `c` arrives in RDX, which division overwrites
+    assert_evals_to!(
+        indoc!(
+            r#"
+            app "test" provides [main] to "./platform"
+
+            f : I64, I64, I64, I64 -> I64
+            f = \a, b, c, d -> (a // b) * 1000 + c * 10 + d
+
+            main = f 100 7 3 4
+            "#
+        ),
+        14034,
+        i64
+    );
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
+fn gen_div_u64_keeps_live_values() {
+    assert_evals_to!(
+        indoc!(
+            r#"
+            app "test" provides [main] to "./platform"
+
+            f : U64, U64, U64, U64 -> U64
+            f = \a, b, c, d -> (a // b) * 1000 + c * 10 + d
+
+            main = f 100 7 3 4
+            "#
+        ),
+        14034,
+        u64
+    );
+}
+
+/// Divides `a` by `b`, both of `int_type`, in a procedure of its own, so the division does not
+/// get folded
+fn div_of(int_type: &str, a: &str, b: &str) -> String {
+    format!(
+        indoc!(
+            r#"
+            app "test" provides [main] to "./platform"
+
+            f : {0}, {0} -> {0}
+            f = \a, b -> a // b
+
+            main = f ({1}) ({2})
+            "#
+        ),
+        int_type, a, b
+    )
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
+fn gen_div_narrow_signed() {
+    assert_evals_to!(&div_of("I32", "-7", "2"), -3, i32);
+    assert_evals_to!(&div_of("I32", "-2147483648", "3"), -715827882, i32);
+    assert_evals_to!(&div_of("I32", "2147483647", "-1"), -2147483647, i32);
+    // the neighbours of the one division that overflows, which traps
+    assert_evals_to!(&div_of("I32", "-2147483647", "-1"), 2147483647, i32);
+    assert_evals_to!(&div_of("I32", "-2147483648", "-2"), 1073741824, i32);
+    assert_evals_to!(&div_of("I16", "-32768", "7"), -4681, i16);
+    assert_evals_to!(&div_of("I16", "-32768", "-2"), 16384, i16);
+    assert_evals_to!(&div_of("I8", "-128", "3"), -42, i8);
+    assert_evals_to!(&div_of("I8", "100", "-7"), -14, i8);
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
+fn gen_div_narrow_unsigned() {
+    assert_evals_to!(&div_of("U32", "4294967295", "16"), 268435455, u32);
+    assert_evals_to!(&div_of("U32", "4294967295", "4294967294"), 1, u32);
+    assert_evals_to!(&div_of("U16", "65535", "255"), 257, u16);
+    assert_evals_to!(&div_of("U8", "255", "2"), 127, u8);
+    assert_evals_to!(&div_of("U8", "200", "201"), 0, u8);
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
+fn gen_div_narrow_quotient_keeps_its_sign() {
+    assert_evals_to!(
+        indoc!(
+            r#"
+            app "test" provides [main] to "./platform"
+
+            f : I32, I32 -> Bool
+            f = \a, b -> a // b < 0
+
+            main = f -7 2
+            "#
+        ),
+        true,
+        bool
+    );
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
+fn fold_literal_arithmetic() {
+    assert_evals_to!(
+        indoc!(
+            r#"
+            x = (2 + 3) * (10 - 4) // 3
+
+            if x < 11 then x else 0
+            "#
+        ),
+        10,
+        i64
+    );
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
+fn mul_by_power_of_two() {
+    assert_evals_to!(
+        indoc!(
+            r#"
+            app "test" provides [main] to "./platform"
+
+            f : I64 -> I64
+            f = \x -> x * 8 + x * 1024
+
+            main = f 5 + 10000 * f (-3)
+            "#
+        ),
+        -30954840,
+        i64
+    );
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
+fn mul_by_three_five_and_nine() {
+    assert_evals_to!(
+        indoc!(
+            r#"
+            app "test" provides [main] to "./platform"
+
+            f : I64 -> I64
+            f = \x -> x * 3 + 100 * (x * 5) + 10000 * (9 * x)
+
+            main = f 2 + f (-1)
+            "#
+        ),
+        90503,
+        i64
+    );
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
+fn arithmetic_identities() {
+    assert_evals_to!(
+        indoc!(
+            r#"
+            app "test" provides [main] to "./platform"
+
+            f : I64 -> I64
+            f = \x -> (0 + x + 0) * 1 - 0 + x // 1 + x * 0
+
+            main = f 21
+            "#
+        ),
+        42,
+        i64
+    );
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
+fn signed_div_by_power_of_two_rounds_toward_zero() {
+    assert_evals_to!(
+        indoc!(
+            r#"
+            app "test" provides [main] to "./platform"
+
+            f : I64 -> I64
+            f = \x -> x // 4
+
+            main = f 7 + 10 * f (-7) + 100 * f (-8) + 1000 * f (-1)
+            "#
+        ),
+        -209,
+        i64
+    );
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
+fn signed_div_by_power_of_two_at_the_bounds() {
+    assert_evals_to!(
+        indoc!(
+            r#"
+            app "test" provides [main] to "./platform"
+
+            half : I64 -> I64
+            half = \x -> x // 2
+
+            quarter : I64 -> I64
+            quarter = \x -> x // 4
+
+            main = half Num.minI64 + quarter Num.maxI64
+            "#
+        ),
+        i64::MIN / 2 + i64::MAX / 4,
+        i64
+    );
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
+fn unsigned_div_by_power_of_two() {
+    assert_evals_to!(
+        indoc!(
+            r#"
+            app "test" provides [main] to "./platform"
+
+            f : U64 -> U64
+            f = \x -> x // 8
+
+            main = f Num.maxU64
+            "#
+        ),
+        u64::MAX / 8,
+        u64
+    );
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
+fn unsigned_comparisons_with_zero() {
+    assert_evals_to!(
+        indoc!(
+            r#"
+            app "test" provides [main] to "./platform"
+
+            f : U64 -> I64
+            f = \x -> if x < 0 then 1 else if 0 <= x then 2 else 3
+
+            main = f 3
+            "#
+        ),
+        2,
+        i64
+    );
+}
+
 #[test]
 #[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
 fn gen_div_checked_i64() {
@@ -1233,7 +1507,67 @@ fn gen_div_checked_by_zero_i64() {
 }
 
 #[test]
-#[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
+fn gen_div_u64_with_the_top_bit_set() {
+    // not a negative dividend, so the upper half of it must be zero rather than all ones
+    assert_evals_to!(
+        &div_of("U64", "18446744073709551615", "7"),
+        2635249153387078802,
+        u64
+    );
+    assert_evals_to!(
+        &div_of("U64", "9223372036854775808", "2"),
+        4611686018427387904,
+        u64
+    );
+}
+
+/// The remainder of `a` divided by `b`, both of `int_type`, in a procedure of its own
+fn rem_of(int_type: &str, a: &str, b: &str) -> String {
+    format!(
+        indoc!(
+            r#"
+            app "test" provides [main] to "./platform"
+
+            f : {0}, {0} -> {0}
+            f = \a, b -> a % b
+
+            main = f ({1}) ({2})
+            "#
+        ),
+        int_type, a, b
+    )
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
+fn gen_div_and_rem_of_the_same_operands() {
+    assert_evals_to!("93 // 7", 13, i64);
+    assert_evals_to!("93 % 7", 2, i64);
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
+fn gen_rem_has_the_sign_of_the_dividend() {
+    assert_evals_to!(&rem_of("I64", "-93", "7"), -2, i64);
+    assert_evals_to!(&rem_of("I64", "93", "-7"), 2, i64);
+    assert_evals_to!(&rem_of("I32", "-2147483648", "3"), -2, i32);
+    assert_evals_to!(&rem_of("I16", "-32768", "7"), -1, i16);
+    assert_evals_to!(&rem_of("I8", "-128", "3"), -2, i8);
+    assert_evals_to!(&rem_of("I8", "127", "-10"), 7, i8);
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
+fn gen_rem_unsigned() {
+    assert_evals_to!(&rem_of("U64", "18446744073709551615", "7"), 1, u64);
+    assert_evals_to!(&rem_of("U32", "4294967295", "16"), 15, u32);
+    assert_evals_to!(&rem_of("U16", "65535", "256"), 255, u16);
+    assert_evals_to!(&rem_of("U8", "255", "7"), 3, u8);
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
 fn gen_rem_i64() {
     assert_evals_to!(
         indoc!(
@@ -1263,7 +1597,7 @@ fn gen_rem_checked_div_by_zero_i64() {
 }
 
 #[test]
-#[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
 fn gen_is_positive_i64() {
     assert_evals_to!("Num.isPositive 0", false, bool);
     assert_evals_to!("Num.isPositive 1", true, bool);
@@ -1271,7 +1605,7 @@ fn gen_is_positive_i64() {
 }
 
 #[test]
-#[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
 fn gen_is_negative_i64() {
     assert_evals_to!("Num.isNegative 0", false, bool);
     assert_evals_to!("Num.isNegative 3", false, bool);
@@ -1279,7 +1613,7 @@ fn gen_is_negative_i64() {
 }
 
 #[test]
-#[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
 fn gen_is_positive_f64() {
     assert_evals_to!("Num.isPositive 0.0", false, bool);
     assert_evals_to!("Num.isPositive 4.7", true, bool);
@@ -1287,7 +1621,7 @@ fn gen_is_positive_f64() {
 }
 
 #[test]
-#[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
 fn gen_is_negative_f64() {
     assert_evals_to!("Num.isNegative 0.0", false, bool);
     assert_evals_to!("Num.isNegative 9.9", false, bool);
@@ -1303,11 +1637,90 @@ fn gen_is_zero_i64() {
 }
 
 #[test]
-#[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm", feature = "gen-dev"))]
 fn gen_is_zero_f64() {
     assert_evals_to!("Num.isZero 0.0", true, bool);
 }
 
+/// Applies `predicate` to `value`, of `num_type`, in a procedure of its own, so the test does not
+/// get folded
+fn sign_test_of(predicate: &str, num_type: &str, value: &str) -> String {
+    format!(
+        indoc!(
+            r#"
+            app "test" provides [main] to "./platform"
+
+            f : {1} -> Bool
+            f = \x -> {0} x
+
+            main = f ({2})
+            "#
+        ),
+        predicate, num_type, value
+    )
+}
+
+/// Checks `Num.isZero`, `Num.isPositive` and `Num.isNegative` on every case of
+/// (type, value, is zero, is positive, is negative)
+#[allow(dead_code)]
+fn assert_sign_tests(cases: &[(&str, &str, bool, bool, bool)]) {
+    for (num_type, value, zero, positive, negative) in cases.iter().copied() {
+        assert_evals_to!(&sign_test_of("Num.isZero", num_type, value), zero, bool);
+        assert_evals_to!(
+            &sign_test_of("Num.isPositive", num_type, value),
+            positive,
+            bool
+        );
+        assert_evals_to!(
+            &sign_test_of("Num.isNegative", num_type, value),
+            negative,
+            bool
+        );
+    }
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-dev"))]
+fn sign_tests_on_every_int_width() {
+    assert_sign_tests(&[
+        ("I8", "-128", false, false, true),
+        ("I8", "0", true, false, false),
+        ("I8", "127", false, true, false),
+        // the sign bit of an unsigned integer is just its highest bit
+        ("U8", "255", false, true, false),
+        ("U8", "0", true, false, false),
+        ("I16", "-1", false, false, true),
+        ("I16", "256", false, true, false),
+        ("U16", "65535", false, true, false),
+        ("I32", "-2147483648", false, false, true),
+        ("I32", "65536", false, true, false),
+        ("U32", "4294967295", false, true, false),
+        ("U32", "0", true, false, false),
+        ("I64", "-1", false, false, true),
+        ("I64", "4294967296", false, true, false),
+        ("U64", "18446744073709551615", false, true, false),
+        ("U64", "0", true, false, false),
+    ]);
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-dev"))]
+fn sign_tests_on_floats() {
+    // -0.0 is zero, and NaN is neither zero nor positive nor negative
+    assert_sign_tests(&[
+        ("F64", "0.0", true, false, false),
+        ("F64", "-0.0", true, false, false),
+        ("F64", "2.5", false, true, false),
+        ("F64", "-2.5", false, false, true),
+        ("F64", "0.0 / 0.0", false, false, false),
+        ("F32", "0.0", true, false, false),
+        ("F32", "-0.0", true, false, false),
+        ("F32", "2.5", false, true, false),
+        ("F32", "-2.5", false, false, true),
+        ("F32", "0.0 / 0.0", false, false, false),
+    ]);
+}
+
 #[test]
 #[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
 fn gen_is_odd() {
@@ -1585,6 +1998,31 @@ fn if_guard_bind_variable_true() {
     );
 }
 
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-dev", feature = "gen-wasm"))]
+fn nested_ifs() {
+    assert_evals_to!(
+        indoc!(
+            r#"
+                classify : I64 -> I64
+                classify = \n ->
+                    if n < 10 then
+                        if n < 5 then 1 else 2
+                    else if n < 100 then
+                        3
+                    else if n < 1000 then
+                        4
+                    else
+                        5
+
+                classify 3 + 10 * classify 7 + 100 * classify 50 + 1000 * classify 500 + 10000 * classify 5000
+                "#
+        ),
+        54321,
+        i64
+    );
+}
+
 #[test]
 #[cfg(any(feature = "gen-llvm", feature = "gen-dev", feature = "gen-wasm"))]
 fn tail_call_elimination() {
@@ -1810,6 +2248,25 @@ fn pow_int() {
     assert_evals_to!("Num.powInt 2 3", 8, i64);
 }
 
+#[test]
+#[cfg(feature = "gen-llvm")]
+fn pow_int_by_squaring() {
+    assert_evals_to!("Num.powInt 2 10", 1024, i64);
+    assert_evals_to!("Num.powInt 3 0", 1, i64);
+    assert_evals_to!("Num.powInt -3 3", -27, i64);
+    assert_evals_to!("Num.powInt 2 -1", 0, i64);
+    assert_evals_to!("Num.powInt 3 39", 4052555153018976267, i64);
+    assert_evals_to!("Num.powInt 2u64 63", 9223372036854775808, u64);
+    assert_evals_to!("Num.powInt 2u8 7", 128, u8);
+}
+
+#[test]
+#[cfg(feature = "gen-llvm")]
+#[should_panic(expected = r#"Roc failed with message: "integer exponentiation overflowed!"#)]
+fn pow_int_overflow() {
+    assert_evals_to!("Num.powInt 3 40", 0, i64);
+}
+
 #[test]
 #[cfg(any(feature = "gen-llvm", feature = "gen-dev", feature = "gen-wasm"))]
 fn atan() {
@@ -2760,6 +3217,75 @@ to_int_checked_tests! {
     )
 }
 
+// The dev backend checks the same bounds as LLVM, so these run on both, at and around the bounds.
+macro_rules! to_int_checked_bounds_tests {
+    ($($fn:expr, $typ:ty, ($($test_name:ident, $input:expr, $output:expr)*))*) => {$($(
+        #[test]
+        #[cfg(any(feature = "gen-llvm", feature = "gen-dev"))]
+        fn $test_name() {
+            let sentinel = 23;
+            // Some n = Ok n, None = OutOfBounds
+            let expected = match $output.into() {
+                None => sentinel,
+                Some(n) => {
+                    assert_ne!(n, sentinel);
+                    n
+                }
+            };
+            let input = format!("Result.withDefault ({} {}) {}", $fn, $input, sentinel);
+            assert_evals_to!(&input, expected, $typ)
+        }
+    )*)*}
+}
+
+to_int_checked_bounds_tests! {
+    "Num.toI8Checked", i8, (
+        to_i8_checked_bounds_max_unsigned,      "127u8",    127
+        to_i8_checked_bounds_above_max,         "128u8",    None
+        to_i8_checked_bounds_min,               "-128i64",  -128
+        to_i8_checked_bounds_below_min,         "-129i16",  None
+    )
+    "Num.toI16Checked", i16, (
+        to_i16_checked_bounds_extend_unsigned,  "200u8",    200
+        to_i16_checked_bounds_max,              "32767u64", 32767
+        to_i16_checked_bounds_above_max,        "32768u16", None
+        to_i16_checked_bounds_min,              "-32768i64", -32768
+    )
+    "Num.toI32Checked", i32, (
+        to_i32_checked_bounds_above_max,        "2147483648u32",  None
+        to_i32_checked_bounds_min,              "-2147483648i64", i32::MIN
+        to_i32_checked_bounds_below_min,        "-2147483649i64", None
+    )
+    "Num.toI64Checked", i64, (
+        to_i64_checked_bounds_extend_negative,  "-1i8",                    -1
+        to_i64_checked_bounds_max_unsigned,     "9223372036854775807u64",  i64::MAX
+        to_i64_checked_bounds_above_max,        "9223372036854775808u64",  None
+        to_i64_checked_bounds_max_u64,          "18446744073709551615u64", None
+    )
+    "Num.toU8Checked", u8, (
+        to_u8_checked_bounds_max,               "255i16",   255
+        to_u8_checked_bounds_above_max,         "256u64",   None
+        to_u8_checked_bounds_below_min,         "-1i8",     None
+    )
+    "Num.toU16Checked", u16, (
+        to_u16_checked_bounds_max,              "65535i32", 65535
+        to_u16_checked_bounds_below_min,        "-1i8",     None
+    )
+    "Num.toU32Checked", u32, (
+        to_u32_checked_bounds_max,              "4294967295i64", u32::MAX
+        to_u32_checked_bounds_above_max,        "4294967296i64", None
+        to_u32_checked_bounds_max_signed,       "2147483647i32", 2147483647
+        to_u32_checked_bounds_below_min,        "-1i32",         None
+    )
+    "Num.toU64Checked", u64, (
+        to_u64_checked_bounds_extend_unsigned,  "200u8",                    200
+        to_u64_checked_bounds_zero,             "0i64",                     0
+        to_u64_checked_bounds_max_signed,       "9223372036854775807i64",   i64::MAX as u64
+        to_u64_checked_bounds_below_min,        "-1i64",                    None
+        to_u64_checked_bounds_min_i64,          "Num.minI64",               None
+    )
+}
+
 #[test]
 #[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
 fn is_multiple_of_signed() {
@@ -3029,6 +3555,29 @@ fn num_to_str() {
     );
 }
 
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
+fn num_to_str_around_100() {
+    use roc_std::{RocList, RocStr};
+
+    assert_evals_to!(r#"Num.toStr 9"#, RocStr::from("9"), RocStr);
+    assert_evals_to!(r#"Num.toStr 10"#, RocStr::from("10"), RocStr);
+    assert_evals_to!(r#"Num.toStr 42"#, RocStr::from("42"), RocStr);
+    assert_evals_to!(r#"Num.toStr 99"#, RocStr::from("99"), RocStr);
+    assert_evals_to!(r#"Num.toStr 100"#, RocStr::from("100"), RocStr);
+    assert_evals_to!(r#"Num.toStr -5"#, RocStr::from("-5"), RocStr);
+    assert_evals_to!(
+        r#"List.map [0, 7, 58, 1000] Num.toStr"#,
+        RocList::from_slice(&[
+            RocStr::from("0"),
+            RocStr::from("7"),
+            RocStr::from("58"),
+            RocStr::from("1000"),
+        ]),
+        RocList<RocStr>
+    );
+}
+
 #[test]
 #[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
 fn num_to_str_u8() {
@@ -3093,6 +3642,135 @@ fn num_to_str_u64() {
     );
 }
 
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
+fn num_to_hex_str() {
+    use roc_std::RocStr;
+
+    assert_evals_to!(
+        r#"Num.toHexStr 0"#,
+        RocStr::from("0000000000000000"),
+        RocStr
+    );
+    assert_evals_to!(
+        r#"Num.toHexStr 0xabc"#,
+        RocStr::from("0000000000000abc"),
+        RocStr
+    );
+    assert_evals_to!(
+        r#"Num.toHexStr Num.maxU64"#,
+        RocStr::from("ffffffffffffffff"),
+        RocStr
+    );
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
+fn num_gcd() {
+    assert_evals_to!("Num.gcd 12 8", 4, u64);
+    assert_evals_to!("Num.gcd 8 12", 4, u64);
+    assert_evals_to!("Num.gcd 17 5", 1, u64);
+    assert_evals_to!("Num.gcd 0 9", 9, u64);
+    assert_evals_to!("Num.gcd 0 0", 0, u64);
+    assert_evals_to!("Num.gcd (Num.maxU64 - 1) 6", 2, u64);
+}
+
+#[test]
+#[cfg(feature = "gen-llvm")]
+fn num_lcm() {
+    assert_evals_to!("Num.lcm 12 8", 24, u64);
+    assert_evals_to!("Num.lcm 17 5", 85, u64);
+    assert_evals_to!("Num.lcm 0 9", 0, u64);
+    assert_evals_to!("Num.lcm 0 0", 0, u64);
+    assert_evals_to!("Num.lcm Num.maxU64 Num.maxU64", u64::MAX, u64);
+}
+
+#[test]
+#[cfg(feature = "gen-llvm")]
+#[should_panic(expected = r#"Roc failed with message: "integer multiplication overflowed!"#)]
+fn num_lcm_overflow() {
+    assert_evals_to!("Num.lcm Num.maxU64 2", 0, u64);
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
+fn num_add_with_overflow() {
+    assert_evals_to!("Num.addWithOverflow 2 3", (5, false), (i64, bool));
+    assert_evals_to!(
+        "Num.addWithOverflow Num.minI64 (-1)",
+        (i64::MAX, true),
+        (i64, bool)
+    );
+    assert_evals_to!(
+        "Num.addWithOverflow Num.maxI64 1",
+        (i64::MIN, true),
+        (i64, bool)
+    );
+    assert_evals_to!(
+        "Num.addWithOverflow Num.maxI64 0",
+        (i64::MAX, false),
+        (i64, bool)
+    );
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
+fn num_to_float_bits() {
+    assert_evals_to!("Num.toF64Bits 1.5", 0x3FF8_0000_0000_0000, u64);
+    assert_evals_to!("Num.toF64Bits -0.0", 0x8000_0000_0000_0000, u64);
+    assert_evals_to!("Num.toF32Bits 1.5", 0x3FC0_0000, u32);
+
+    assert_evals_to!(
+        indoc!(
+            r#"
+            infinity : F64
+            infinity = 1.0 / 0.0
+
+            Num.toF64Bits infinity
+            "#
+        ),
+        0x7FF0_0000_0000_0000,
+        u64
+    );
+
+    // the sign of the NaN that `0.0 / 0.0` gives depends on the target
+    assert_evals_to!(
+        indoc!(
+            r#"
+            nan : F64
+            nan = 0.0 / 0.0
+
+            Num.toF64Bits nan |> Num.bitwiseAnd 0x7FFF_FFFF_FFFF_FFFF
+            "#
+        ),
+        0x7FF8_0000_0000_0000,
+        u64
+    );
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
+fn num_from_float_bits() {
+    assert_evals_to!("Num.fromF64Bits 0x3FF8000000000000", 1.5, f64);
+    assert_evals_to!("Num.fromF64Bits 0x7FF0000000000000", f64::INFINITY, f64);
+    assert_evals_to!("Num.fromF64Bits 0xFFF0000000000000", f64::NEG_INFINITY, f64);
+    assert_evals_to!("Num.fromF32Bits 0x7F800000", f32::INFINITY, f32);
+
+    // a NaN is never equal to itself, so compare the bits, payload included
+    assert_evals_to!(
+        "Num.fromF64Bits 0x7FF8000000000001",
+        0x7FF8_0000_0000_0001,
+        f64,
+        f64::to_bits
+    );
+    assert_evals_to!("Num.fromF32Bits 0xFFC00001", 0xFFC0_0001, f32, f32::to_bits);
+    assert_evals_to!(
+        "Num.toF64Bits (Num.fromF64Bits 0x7FF4000000000000)",
+        0x7FF4_0000_0000_0000,
+        u64
+    );
+}
+
 #[test]
 #[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
 fn num_to_str_i8() {
@@ -3864,3 +4542,72 @@ fn condition_polymorphic_num_becomes_float() {
         f32
     );
 }
+
+/// Evaluates `src` to an `F64`, with the float operations crashing on floating-point exceptions
+#[cfg(feature = "gen-llvm")]
+fn strict_fp_evals_to(src: &str) -> f64 {
+    use crate::helpers::llvm;
+    use roc_gen_llvm::llvm::build::LlvmBackendMode;
+
+    let arena = bumpalo::Bump::new();
+    let context = inkwell::context::Context::create();
+    let config = llvm::HelperConfig {
+        mode: LlvmBackendMode::GenTest,
+        add_debug_info: false,
+        ignore_problems: false,
+        opt_level: llvm::OPT_LEVEL,
+        strict_fp_exceptions: true,
+    };
+
+    let (main_fn_name, errors, lib) = llvm::helper(&arena, config, src, &context);
+    assert!(errors.is_empty(), "Encountered errors:\n{}", errors);
+
+    match llvm::try_run_lib_function::<f64>(main_fn_name, &lib) {
+        Ok(value) => value,
+        Err((msg, _)) => panic!(r#"Roc failed with message: "{}""#, msg),
+    }
+}
+
+#[test]
+#[cfg(feature = "gen-llvm")]
+#[should_panic(expected = r#"Roc failed with message: "float division by zero!"#)]
+fn strict_fp_division_by_zero() {
+    // parsed, so LLVM can not divide by the constant while it compiles
+    strict_fp_evals_to(indoc!(
+        r#"
+        zero : F64
+        zero = Str.toF64 "0" |> Result.withDefault 1
+
+        1 / zero
+        "#
+    ));
+}
+
+#[test]
+#[cfg(feature = "gen-llvm")]
+#[should_panic(expected = r#"Roc failed with message: "float operation was invalid!"#)]
+fn strict_fp_sqrt_of_negative() {
+    strict_fp_evals_to(indoc!(
+        r#"
+        minusOne : F64
+        minusOne = Str.toF64 "-1" |> Result.withDefault 1
+
+        Num.sqrt minusOne
+        "#
+    ));
+}
+
+#[test]
+#[cfg(feature = "gen-llvm")]
+fn strict_fp_without_exceptions() {
+    let result = strict_fp_evals_to(indoc!(
+        r#"
+        four : F64
+        four = Str.toF64 "4" |> Result.withDefault 1
+
+        Num.sqrt four / 4
+        "#
+    ));
+
+    assert_eq!(result, 0.5);
+}
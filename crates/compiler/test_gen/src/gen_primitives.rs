@@ -23,6 +23,65 @@ fn basic_float() {
     assert_evals_to!("1234.0", 1234.0, f64);
 }
 
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-dev", feature = "gen-wasm"))]
+fn bool_and_or() {
+    assert_evals_to!("Bool.true && Bool.true", true, bool);
+    assert_evals_to!("Bool.true && Bool.false", false, bool);
+    assert_evals_to!("Bool.false && Bool.true", false, bool);
+    assert_evals_to!("Bool.false && Bool.false", false, bool);
+
+    assert_evals_to!("Bool.true || Bool.true", true, bool);
+    assert_evals_to!("Bool.true || Bool.false", true, bool);
+    assert_evals_to!("Bool.false || Bool.true", true, bool);
+    assert_evals_to!("Bool.false || Bool.false", false, bool);
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-dev", feature = "gen-wasm"))]
+fn bool_and_or_of_comparisons() {
+    assert_evals_to!(
+        indoc!(
+            r#"
+                x : I64
+                x = 3
+
+                inRange = x > 2 && x < 5
+                outOfRange = x < 2 || x > 5
+
+                # the results are bytes that are 0 or 1, so they compare like the literals
+                inRange == Bool.true && outOfRange == Bool.false
+            "#
+        ),
+        true,
+        bool
+    );
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-dev"))]
+fn bools_from_builtins_compare_like_literals() {
+    assert_evals_to!(
+        indoc!(
+            r#"
+                Str.startsWith "abc" "a" == Bool.true && Str.endsWith "abc" "a" == Bool.false
+            "#
+        ),
+        true,
+        bool
+    );
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-dev"))]
+fn list_of_bools_is_one_byte_per_element() {
+    assert_evals_to!(
+        "[1 == 1, Bool.false, Bool.true, 2 == 3]",
+        RocList::from_slice(&[true, false, true, false]),
+        RocList<bool>
+    );
+}
+
 #[test]
 #[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
 fn branch_first_float() {
@@ -2584,6 +2643,63 @@ fn module_thunk_is_function() {
     );
 }
 
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-dev"))]
+fn thunk_returns_constant_str() {
+    assert_evals_to!(
+        indoc!(
+            r#"
+                app "test" provides [main] to "./platform"
+
+                greeting : Str
+                greeting = "a string that is too long to be a small string"
+
+                main = greeting
+            "#
+        ),
+        RocStr::from("a string that is too long to be a small string"),
+        RocStr
+    );
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-dev"))]
+fn call_returning_unit() {
+    // the result of the call has no data, but it is still returned from `main`
+    assert_evals_to!(
+        indoc!(
+            r#"
+                app "test" provides [main] to "./platform"
+
+                ignore : I64, {} -> {}
+                ignore = \_, unit -> unit
+
+                main : {}
+                main = ignore 42 {}
+            "#
+        ),
+        (),
+        ()
+    );
+}
+
+#[test]
+#[cfg(feature = "gen-llvm")]
+fn unit_argument_transform_in_higher_order_builtin() {
+    assert_evals_to!(
+        indoc!(
+            r#"
+                app "test" provides [main] to "./platform"
+
+                main : List I64
+                main = List.map [{}, {}, {}] \{} -> 7
+            "#
+        ),
+        RocList::from_slice(&[7, 7, 7]),
+        RocList<i64>
+    );
+}
+
 #[test]
 #[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
 fn pass_through_unresolved_type_variable() {
@@ -3342,6 +3458,59 @@ fn box_and_unbox_tag_union() {
     )
 }
 
+#[test]
+#[cfg(any(feature = "gen-llvm"))]
+fn box_map_in_place() {
+    assert_evals_to!(
+        indoc!(
+            r#"
+            n = 3u64
+
+            Box.box 20u64
+                |> Box.map (\x -> x * n)
+                |> Box.unbox
+            "#
+        ),
+        60,
+        u64
+    )
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm"))]
+fn box_map_changes_size() {
+    assert_evals_to!(
+        indoc!(
+            r#"
+            Box.box 20u8
+                |> Box.map (\x -> { a: Num.toU64 x * 1000, b: x })
+                |> Box.unbox
+            "#
+        ),
+        (20000, 20),
+        (u64, u8)
+    )
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm"))]
+fn box_map_shared() {
+    assert_evals_to!(
+        indoc!(
+            r#"
+            boxed = Box.box (Str.concat "Leverage " "agile frameworks to provide a robust synopsis")
+            mapped = Box.map boxed (\s -> Str.concat s "!")
+
+            Str.concat (Box.unbox boxed) (Box.unbox mapped)
+            "#
+        ),
+        RocStr::from(
+            "Leverage agile frameworks to provide a robust synopsisLeverage agile frameworks to provide a robust synopsis!"
+        ),
+        RocStr
+    )
+}
+
 #[test]
 #[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
 fn closure_called_in_its_defining_scope() {
@@ -4276,3 +4445,68 @@ fn function_specialization_information_in_lambda_set_thunk_independent_defs() {
         u8
     );
 }
+
+#[test]
+#[cfg(feature = "gen-llvm")]
+fn host_exposed_functions_match_module() {
+    let (host_interface, external_functions) = crate::helpers::llvm::host_interface(indoc!(
+        r#"
+        app "test" provides [main] to "./platform"
+
+        main : I64 -> Str
+        main = \n -> Num.toStr (n + 1)
+        "#
+    ));
+
+    let mut registered: Vec<String> = host_interface
+        .functions
+        .iter()
+        .map(|function| function.name.clone())
+        .collect();
+
+    registered.sort();
+
+    assert!(registered.iter().any(|name| name == "$Test.main"));
+    assert_eq!(registered, external_functions);
+}
+
+/// `n` procs that each call the previous one, and add 1; `main` calls the last one with 0
+#[cfg(any(feature = "gen-llvm", feature = "gen-dev"))]
+fn chain_of_small_procs(n: usize) -> String {
+    let mut src = String::from("app \"test\" provides [main] to \"./platform\"\n\n");
+    src.push_str("f0 = \\x -> x + 1\n\n");
+
+    for i in 1..n {
+        src.push_str(&format!("f{} = \\x -> f{} (x + 1)\n\n", i, i - 1));
+    }
+
+    src.push_str(&format!("main = f{} 0\n", n - 1));
+
+    src
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-dev"))]
+fn many_small_procs() {
+    // every proc reuses the scratch buffers of the previous one in the dev backend
+    assert_evals_to!(&chain_of_small_procs(1000), 1000, i64);
+}
+
+#[test]
+#[cfg(feature = "gen-dev")]
+fn many_small_procs_grow_the_arena_linearly() {
+    use crate::helpers::dev::peak_arena_bytes;
+
+    // The arena holds the module, so ten times the procs take about ten times the memory.
+    // Scratch buffers that are allocated anew for every proc, and grow with what was built
+    // before it, would make that closer to a hundred times.
+    let hundred = peak_arena_bytes(&chain_of_small_procs(100));
+    let thousand = peak_arena_bytes(&chain_of_small_procs(1000));
+
+    assert!(
+        thousand <= 15 * hundred,
+        "100 procs took {} bytes, 1000 took {}",
+        hundred,
+        thousand
+    );
+}
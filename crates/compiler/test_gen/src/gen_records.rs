@@ -1111,3 +1111,30 @@ fn toplevel_accessor_fn_thunk() {
         u8
     )
 }
+
+#[test]
+#[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
+fn record_of_many_strings_is_shared_and_dropped() {
+    // the refcounts of the eight strings are modified together, and they must still balance
+    assert_evals_to!(
+        indoc!(
+            r#"
+            app "test" provides [main] to "./platform"
+
+            long = \s -> Str.concat s " is long enough to be allocated on the heap"
+
+            main =
+                r = { a: long "a", b: long "b", c: long "c", d: long "d", e: long "e", f: long "f", g: long "g", h: long "h" }
+                copies = [r, r, r]
+
+                when List.get copies 1 is
+                    Ok { b, h } -> Str.concat b h
+                    Err _ -> ""
+            "#
+        ),
+        RocStr::from(
+            "b is long enough to be allocated on the heaph is long enough to be allocated on the heap"
+        ),
+        RocStr
+    );
+}
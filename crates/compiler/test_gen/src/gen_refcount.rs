@@ -158,6 +158,59 @@ fn struct_dealloc() {
     );
 }
 
+#[test]
+#[cfg(any(feature = "gen-wasm"))]
+fn struct_of_many_strings_inc() {
+    // the refcounts of all eight strings are incremented for every copy of the record
+    assert_refcounts!(
+        indoc!(
+            r#"
+                long = \s -> Str.concat s " is long enough to be heap-allocated"
+                r = { a: long "a", b: long "b", c: long "c", d: long "d", e: long "e", f: long "f", g: long "g", h: long "h" }
+                [r, r, r]
+            "#
+        ),
+        RocList<[RocStr; 8]>,
+        &[
+            Live(3), // a
+            Live(3), // b
+            Live(3), // c
+            Live(3), // d
+            Live(3), // e
+            Live(3), // f
+            Live(3), // g
+            Live(3), // h
+            Live(1)  // result
+        ]
+    );
+}
+
+#[test]
+#[cfg(any(feature = "gen-wasm"))]
+fn struct_of_many_strings_dealloc() {
+    assert_refcounts!(
+        indoc!(
+            r#"
+                long = \s -> Str.concat s " is long enough to be heap-allocated"
+                r = { a: long "a", b: long "b", c: long "c", d: long "d", e: long "e", f: long "f", g: long "g", h: long "h" }
+                List.len [r, r, r]
+            "#
+        ),
+        usize,
+        &[
+            Deallocated, // a
+            Deallocated, // b
+            Deallocated, // c
+            Deallocated, // d
+            Deallocated, // e
+            Deallocated, // f
+            Deallocated, // g
+            Deallocated, // h
+            Deallocated  // list
+        ]
+    );
+}
+
 #[test]
 #[cfg(any(feature = "gen-wasm"))]
 fn union_nonrecursive_inc() {
@@ -167,6 +167,105 @@ fn intersection() {
     );
 }
 
+#[test]
+#[cfg(any(feature = "gen-llvm"))]
+fn difference_of_a_bigger_set() {
+    assert_evals_to!(
+        indoc!(
+            r#"
+            set1 : Set.Set I64
+            set1 = Set.fromList [1, 2, 3, 4, 5]
+
+            set2 : Set.Set I64
+            set2 = Set.fromList [2, 4]
+
+            Set.difference set1 set2 == Set.fromList [1, 3, 5]
+            "#
+        ),
+        true,
+        bool
+    );
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm"))]
+fn difference_of_disjoint_and_empty_sets() {
+    assert_evals_to!(
+        indoc!(
+            r#"
+            set1 : Set.Set I64
+            set1 = Set.fromList [1, 2]
+
+            set2 : Set.Set I64
+            set2 = Set.fromList [3, 4, 5]
+
+            empty : Set.Set I64
+            empty = Set.empty {}
+
+            [
+                Set.difference set1 set2 == set1,
+                Set.difference set2 set1 == set2,
+                Set.difference set1 empty == set1,
+                Set.difference empty set1 == empty,
+            ]
+            "#
+        ),
+        RocList::from_slice(&[true, true, true, true]),
+        RocList<bool>
+    );
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm"))]
+fn intersection_of_a_bigger_set() {
+    assert_evals_to!(
+        indoc!(
+            r#"
+            set1 : Set.Set I64
+            set1 = Set.fromList [1, 2, 3, 4, 5]
+
+            set2 : Set.Set I64
+            set2 = Set.fromList [2, 4, 6]
+
+            [
+                Set.intersection set1 set2 == Set.fromList [2, 4],
+                Set.intersection set2 set1 == Set.fromList [2, 4],
+            ]
+            "#
+        ),
+        RocList::from_slice(&[true, true]),
+        RocList<bool>
+    );
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm"))]
+fn intersection_of_disjoint_and_empty_sets() {
+    assert_evals_to!(
+        indoc!(
+            r#"
+            set1 : Set.Set I64
+            set1 = Set.fromList [1, 2]
+
+            set2 : Set.Set I64
+            set2 = Set.fromList [3, 4, 5]
+
+            empty : Set.Set I64
+            empty = Set.empty {}
+
+            [
+                Set.len (Set.intersection set1 set2),
+                Set.len (Set.intersection set2 set1),
+                Set.len (Set.intersection set1 empty),
+                Set.len (Set.intersection empty set1),
+            ]
+            "#
+        ),
+        RocList::from_slice(&[0, 0, 0, 0]),
+        RocList<usize>
+    );
+}
+
 #[test]
 #[cfg(any(feature = "gen-llvm"))]
 fn walk_sum() {
@@ -258,6 +258,28 @@ fn str_concat_big_to_big() {
         );
 }
 
+#[test]
+#[cfg(any(feature = "gen-llvm"))]
+fn str_concat_chain_leaves_shared_strings_alone() {
+    // the result of the first concat is unique, so the second one extends it in place,
+    // but the literal is used twice and must not be
+    assert_evals_to!(
+        indoc!(
+            r#"
+            x = "a string that is long enough to be on the heap"
+
+            first = Str.concat x ", and then some"
+            second = Str.concat first ", and then some more"
+            other = Str.concat x "."
+
+            Str.concat second other
+            "#
+        ),
+        RocStr::from("a string that is long enough to be on the heap, and then some, and then some morea string that is long enough to be on the heap."),
+        RocStr
+    );
+}
+
 #[test]
 #[cfg(any(feature = "gen-llvm", feature = "gen-dev"))]
 fn small_str_literal() {
@@ -890,6 +912,22 @@ fn str_join_comma_single() {
     assert_evals_to!(r#"Str.joinWith ["1"] ", " "#, RocStr::from("1"), RocStr);
 }
 
+#[test]
+#[cfg(any(feature = "gen-llvm"))]
+fn str_join_empty_list() {
+    assert_evals_to!(r#"Str.joinWith [] ", " "#, RocStr::from(""), RocStr);
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm"))]
+fn str_join_empty_separator() {
+    assert_evals_to!(
+        r#"Str.joinWith ["abc", "", "defghijklmnopqrstuvwxyz"] "" "#,
+        RocStr::from("abcdefghijklmnopqrstuvwxyz"),
+        RocStr
+    );
+}
+
 #[test]
 #[cfg(any(feature = "gen-llvm"))]
 fn str_to_utf8() {
@@ -1436,6 +1474,56 @@ fn str_to_u64() {
     );
 }
 
+#[test]
+#[cfg(any(feature = "gen-llvm"))]
+fn str_to_u64_hex() {
+    assert_evals_to!(
+        r#"Str.toU64Hex "ff""#,
+        RocResult::ok(0xffu64),
+        RocResult<u64, ()>
+    );
+    assert_evals_to!(
+        r#"Str.toU64Hex "AbC""#,
+        RocResult::ok(0xabcu64),
+        RocResult<u64, ()>
+    );
+    assert_evals_to!(
+        r#"Str.toU64Hex "0000000000000000""#,
+        RocResult::ok(0u64),
+        RocResult<u64, ()>
+    );
+    assert_evals_to!(
+        r#"Str.toU64Hex "FFFFFFFFFFFFFFFF""#,
+        RocResult::ok(u64::MAX),
+        RocResult<u64, ()>
+    );
+}
+
+#[test]
+#[cfg(any(feature = "gen-llvm"))]
+fn str_to_u64_hex_invalid() {
+    assert_evals_to!(
+        r#"Str.toU64Hex """#,
+        RocResult::err(()),
+        RocResult<u64, ()>
+    );
+    assert_evals_to!(
+        r#"Str.toU64Hex "12g4""#,
+        RocResult::err(()),
+        RocResult<u64, ()>
+    );
+    assert_evals_to!(
+        r#"Str.toU64Hex "0xff""#,
+        RocResult::err(()),
+        RocResult<u64, ()>
+    );
+    assert_evals_to!(
+        r#"Str.toU64Hex "10000000000000000""#,
+        RocResult::err(()),
+        RocResult<u64, ()>
+    );
+}
+
 #[test]
 #[cfg(any(feature = "gen-llvm"))]
 fn str_to_i32() {
@@ -27,19 +27,18 @@ fn promote_expr_to_module(src: &str) -> String {
     buffer
 }
 
-#[allow(dead_code)]
-pub fn helper(
+/// Loads `src` and builds it with the dev backend. Returns the name of `main`, the problems whose
+/// reports are delayed, the object, and the peak size of the arenas of the backend.
+fn build_object(
     arena: &bumpalo::Bump,
     src: &str,
-    _leak: bool,
     lazy_literals: bool,
-) -> (String, Vec<roc_problem::can::Problem>, Library) {
+    inline_small_procs: bool,
+) -> (String, Vec<roc_problem::can::Problem>, Vec<u8>, usize) {
     use std::path::PathBuf;
 
-    let dir = tempdir().unwrap();
     let filename = PathBuf::from("Test.roc");
     let src_dir = PathBuf::from("fake/test/path");
-    let app_o_file = dir.path().join("app.o");
 
     let module_src;
     let temp;
@@ -189,8 +188,32 @@ pub fn helper(
         arena,
         module_id,
         exposed_to_host: exposed_to_host.values.keys().copied().collect(),
+        export_names: Default::default(),
         lazy_literals,
         generate_allocators: true, // Needed for testing, since we don't have a platform
+        host_symbols: Default::default(),
+        alias_host_symbols: false,
+        peak_arena_bytes: Default::default(),
+        stats: Default::default(),
+        proc_sizes: Default::default(),
+        // lets the whole test suite run with each register allocation order, to compare them
+        reg_alloc_order: std::env::var("ROC_DEV_REG_ALLOC_ORDER")
+            .ok()
+            .map(|order| order.parse().unwrap()),
+        inline_small_procs,
+        // the object must not depend on this, which running the suite with more threads checks
+        codegen_threads: std::env::var("ROC_DEV_CODEGEN_THREADS")
+            .ok()
+            .map_or(1, |threads| threads.parse().unwrap()),
+        stack_canary: None,
+        patchpoints: false,
+        panic_cleanup: false,
+        runtime_context: false,
+        serialize_ticks: false,
+        zero_padding: true,
+        proc_alignment: 16,
+        statement_markers: false,
+        listings: Default::default(),
     };
 
     let target = target_lexicon::Triple::host();
@@ -200,13 +223,47 @@ pub fn helper(
         &mut layout_interner,
         &target,
         procedures,
-    );
+    )
+    .unwrap_or_else(|unsupported| panic!("{}", roc_gen_dev::describe_unsupported(&unsupported)));
 
     let module_out = module_object
         .write()
         .expect("failed to build output object");
+
+    (
+        main_fn_name,
+        delayed_errors,
+        module_out,
+        env.peak_arena_bytes.get(),
+    )
+}
+
+/// How big the arenas of the dev backend got while it built `src`, in bytes
+#[allow(dead_code)]
+pub fn peak_arena_bytes(src: &str) -> usize {
+    let arena = bumpalo::Bump::new();
+    let (_, _, _, peak_arena_bytes) = build_object(&arena, src, true, false);
+
+    peak_arena_bytes
+}
+
+#[allow(dead_code)]
+pub fn helper(
+    arena: &bumpalo::Bump,
+    src: &str,
+    _leak: bool,
+    lazy_literals: bool,
+    inline_small_procs: bool,
+) -> (String, Vec<roc_problem::can::Problem>, Library) {
+    let dir = tempdir().unwrap();
+    let app_o_file = dir.path().join("app.o");
+
+    let (main_fn_name, delayed_errors, module_out, _) =
+        build_object(arena, src, lazy_literals, inline_small_procs);
     std::fs::write(&app_o_file, module_out).expect("failed to write object to file");
 
+    let target = target_lexicon::Triple::host();
+
     let builtins_host_tempfile =
         bitcode::host_tempfile().expect("failed to write host builtins object to tempfile");
 
@@ -255,21 +312,25 @@ macro_rules! assert_evals_to {
         }
     };
     ($src:expr, $expected:expr, $ty:ty, $transform:expr, $leak:expr) => {
-        // Run both with and without lazy literal optimization.
+        // Run both with and without lazy literal optimization,
+        // and once more with inlining, which has to give the same result.
+        {
+            assert_evals_to!($src, $expected, $ty, $transform, $leak, false, false);
+        }
         {
-            assert_evals_to!($src, $expected, $ty, $transform, $leak, false);
+            assert_evals_to!($src, $expected, $ty, $transform, $leak, true, false);
         }
         {
-            assert_evals_to!($src, $expected, $ty, $transform, $leak, true);
+            assert_evals_to!($src, $expected, $ty, $transform, $leak, true, true);
         }
     };
-    ($src:expr, $expected:expr, $ty:ty, $transform:expr, $leak:expr, $lazy_literals:expr) => {
+    ($src:expr, $expected:expr, $ty:ty, $transform:expr, $leak:expr, $lazy_literals:expr, $inline_small_procs:expr) => {
         use bumpalo::Bump;
         use roc_gen_dev::run_jit_function_raw;
 
         let arena = Bump::new();
         let (main_fn_name, errors, lib) =
-            $crate::helpers::dev::helper(&arena, $src, $leak, $lazy_literals);
+            $crate::helpers::dev::helper(&arena, $src, $leak, $lazy_literals, $inline_small_procs);
 
         let transform = |success| {
             let expected = $expected;
@@ -6,6 +6,8 @@ use libloading::Library;
 use roc_build::link::llvm_module_to_dylib;
 use roc_collections::all::MutSet;
 use roc_gen_llvm::llvm::externs::add_default_roc_externs;
+use roc_gen_llvm::llvm::host_exposed::HostInterface;
+use roc_gen_llvm::llvm::memory_model::{LibcMemoryModel, RocRuntimeMemoryModel};
 use roc_gen_llvm::{llvm::build::LlvmBackendMode, run_roc::RocCallResult};
 use roc_load::{EntryPoint, ExecutionMode, LoadConfig, LoadMonomorphizedError, Threading};
 use roc_mono::ir::{CrashTag, OptLevel, SingleEntryPoint};
@@ -51,7 +53,7 @@ fn create_llvm_module<'a>(
     config: HelperConfig,
     context: &'a inkwell::context::Context,
     target: &Triple,
-) -> (&'static str, String, &'a Module<'a>) {
+) -> (&'static str, String, HostInterface, &'a Module<'a>) {
     let target_info = roc_target::TargetInfo::from(target);
 
     let filename = PathBuf::from("Test.roc");
@@ -184,7 +186,7 @@ fn create_llvm_module<'a>(
     let (module_pass, function_pass) =
         roc_gen_llvm::llvm::build::construct_optimization_passes(module, config.opt_level);
 
-    let (dibuilder, compile_unit) = roc_gen_llvm::llvm::build::Env::new_debug_info(module);
+    let (dibuilder, compile_unit) = roc_gen_llvm::llvm::build::Env::new_debug_info(module, target);
 
     // mark our zig-defined builtins as internal
     use inkwell::attributes::{Attribute, AttributeLoc};
@@ -224,8 +226,20 @@ fn create_llvm_module<'a>(
         module,
         target_info,
         mode: config.mode,
+        options: roc_gen_llvm::CodegenOptions {
+            strict_fp_exceptions: config.strict_fp_exceptions,
+            ..roc_gen_llvm::CodegenOptions::new(config.opt_level, target.clone())
+        },
+        // without a host, there is no Roc runtime to allocate with
+        memory_model: match config.mode {
+            LlvmBackendMode::GenTest => &LibcMemoryModel,
+            _ => &RocRuntimeMemoryModel,
+        },
         // important! we don't want any procedures to get the C calling convention
         exposed_to_host: MutSet::default(),
+        host_exposed: Default::default(),
+        wrappers_in_progress: Default::default(),
+        fn_origins: Default::default(),
     };
 
     // strip Zig debug stuff
@@ -257,14 +271,12 @@ fn create_llvm_module<'a>(
         LlvmBackendMode::WasmGenTest => roc_gen_llvm::llvm::build::build_wasm_test_wrapper(
             &env,
             &mut layout_interner,
-            config.opt_level,
             procedures,
             entry_point,
         ),
         LlvmBackendMode::GenTest => roc_gen_llvm::llvm::build::build_procedures_return_main(
             &env,
             &mut layout_interner,
-            config.opt_level,
             procedures,
             entry_point,
         ),
@@ -288,12 +300,13 @@ fn create_llvm_module<'a>(
 
     // Verify the module
     if let Err(errors) = env.module.verify() {
+        let report =
+            roc_gen_llvm::llvm::verify::isolate_broken_functions(&env, errors.to_string(), false);
         let path = std::env::temp_dir().join("test.ll");
         env.module.print_to_file(&path).unwrap();
         panic!(
             "Errors defining module:\n\n{}\n\nI have written the full module to `{:?}`",
-            errors.to_string(),
-            path
+            report, path
         );
     }
 
@@ -305,7 +318,10 @@ fn create_llvm_module<'a>(
     } else {
         delayed_errors.join("\n")
     };
-    (main_fn_name, delayed_errors, env.module)
+
+    let host_interface = env.host_exposed.borrow().to_interface(&layout_interner);
+
+    (main_fn_name, delayed_errors, host_interface, env.module)
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -314,6 +330,7 @@ pub struct HelperConfig {
     pub ignore_problems: bool,
     pub add_debug_info: bool,
     pub opt_level: OptLevel,
+    pub strict_fp_exceptions: bool,
 }
 
 #[allow(dead_code)]
@@ -326,7 +343,7 @@ pub fn helper<'a>(
 ) -> (&'static str, String, Library) {
     let target = target_lexicon::Triple::host();
 
-    let (main_fn_name, delayed_errors, module) =
+    let (main_fn_name, delayed_errors, _host_interface, module) =
         create_llvm_module(arena, src, config, context, &target);
 
     let res_lib = if config.add_debug_info {
@@ -341,6 +358,40 @@ pub fn helper<'a>(
     (main_fn_name, delayed_errors, lib)
 }
 
+/// The functions that gen_llvm registered as host-exposed, along with the names of the functions
+/// that are actually externally visible in the generated module.
+#[allow(dead_code)]
+pub fn host_interface(src: &str) -> (HostInterface, Vec<String>) {
+    let arena = bumpalo::Bump::new();
+    let context = inkwell::context::Context::create();
+    let target = target_lexicon::Triple::host();
+
+    let config = HelperConfig {
+        mode: LlvmBackendMode::GenTest,
+        add_debug_info: false,
+        ignore_problems: false,
+        opt_level: OPT_LEVEL,
+        strict_fp_exceptions: false,
+    };
+
+    let (_main_fn_name, _delayed_errors, host_interface, module) =
+        create_llvm_module(&arena, src, config, &context, &target);
+
+    let mut external_functions: Vec<String> = module
+        .get_functions()
+        .filter(|function| {
+            function.get_linkage() == inkwell::module::Linkage::External
+                && function.count_basic_blocks() > 0
+        })
+        .map(|function| function.get_name().to_str().unwrap().to_string())
+        .filter(|name| name.starts_with("roc__") || name.starts_with("$Test"))
+        .collect();
+
+    external_functions.sort();
+
+    (host_interface, external_functions)
+}
+
 fn annotate_with_debug_info<'ctx>(
     module: &Module<'ctx>,
     context: &'ctx inkwell::context::Context,
@@ -416,7 +467,7 @@ fn compile_to_wasm_bytes<'a>(
 ) -> Vec<u8> {
     let target = wasm32_target_tripple();
 
-    let (_main_fn_name, _delayed_errors, llvm_module) =
+    let (_main_fn_name, _delayed_errors, _host_interface, llvm_module) =
         create_llvm_module(arena, src, config, context, &target);
 
     let content_hash = crate::helpers::src_hash(src);
@@ -520,6 +571,7 @@ where
         add_debug_info: false,
         ignore_problems,
         opt_level: OPT_LEVEL,
+        strict_fp_exceptions: false,
     };
 
     let wasm_bytes = compile_to_wasm_bytes(&arena, config, src, &context);
@@ -590,6 +642,7 @@ where
         add_debug_info: false,
         ignore_problems,
         opt_level: crate::helpers::llvm::OPT_LEVEL,
+        strict_fp_exceptions: false,
     };
 
     let (main_fn_name, errors, lib) = crate::helpers::llvm::helper(&arena, config, src, &context);
@@ -126,7 +126,7 @@ void roc_dealloc(void *ptr, unsigned int alignment)
 
 extern void send_panic_msg_to_rust(void* msg, uint32_t tag_id);
 
-void roc_panic(void* msg, unsigned int tag_id)
+void roc_panic(void* msg, unsigned int tag_id, const void* source)
 {
     send_panic_msg_to_rust(msg, tag_id);
     exit(101);
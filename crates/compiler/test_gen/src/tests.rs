@@ -4,6 +4,7 @@
 // we actually want to compare against the literal float bits
 #![allow(clippy::float_cmp)]
 
+pub mod gen_abi;
 pub mod gen_abilities;
 pub mod gen_compare;
 pub mod gen_dict;
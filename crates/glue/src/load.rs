@@ -158,6 +158,7 @@ pub fn load_types(
         let target_info = TargetInfo {
             architecture: arch,
             operating_system: OperatingSystem::Unix,
+            disable_red_zone: false,
         };
 
         let types = {
@@ -5,7 +5,8 @@ use roc_build::link::llvm_module_to_dylib;
 use roc_collections::all::MutSet;
 use roc_gen_llvm::llvm::build::LlvmBackendMode;
 use roc_gen_llvm::llvm::externs::add_default_roc_externs;
-use roc_gen_llvm::{run_jit_function, run_jit_function_dynamic_type};
+use roc_gen_llvm::llvm::memory_model::RocRuntimeMemoryModel;
+use roc_gen_llvm::{run_jit_function, run_jit_function_dynamic_type, CodegenOptions};
 use roc_load::{EntryPoint, MonomorphizedModule};
 use roc_mono::ir::OptLevel;
 use roc_mono::layout::STLayoutInterner;
@@ -202,7 +203,7 @@ fn mono_module_to_dylib<'a>(
     let (module_pass, function_pass) =
         roc_gen_llvm::llvm::build::construct_optimization_passes(module, opt_level);
 
-    let (dibuilder, compile_unit) = roc_gen_llvm::llvm::build::Env::new_debug_info(module);
+    let (dibuilder, compile_unit) = roc_gen_llvm::llvm::build::Env::new_debug_info(module, &target);
 
     // Compile and add all the Procs before adding main
     let env = roc_gen_llvm::llvm::build::Env {
@@ -215,8 +216,14 @@ fn mono_module_to_dylib<'a>(
         module,
         target_info,
         mode: LlvmBackendMode::GenTest, // so roc_panic is generated
+        options: CodegenOptions::new(opt_level, target.clone()),
+        memory_model: &RocRuntimeMemoryModel,
         // important! we don't want any procedures to get the C calling convention
         exposed_to_host: MutSet::default(),
+        host_exposed: Default::default(),
+        wrappers_in_progress: Default::default(),
+        fn_origins: Default::default(),
+        sources: Default::default(),
     };
 
     // Add roc_alloc, roc_realloc, and roc_dealloc, since the repl has no
@@ -242,7 +249,6 @@ fn mono_module_to_dylib<'a>(
     let (main_fn_name, main_fn) = roc_gen_llvm::llvm::build::build_procedures_return_main(
         &env,
         &mut layout_interner,
-        opt_level,
         procedures,
         entry_point,
     );
@@ -14,9 +14,12 @@ use roc_can::expr::ExpectLookup;
 use roc_collections::{MutSet, VecMap};
 use roc_error_macros::internal_error;
 use roc_gen_llvm::{
-    llvm::{build::LlvmBackendMode, externs::add_default_roc_externs},
+    llvm::{
+        build::LlvmBackendMode, externs::add_default_roc_externs,
+        memory_model::RocRuntimeMemoryModel,
+    },
     run_roc::RocCallResult,
-    run_roc_dylib,
+    run_roc_dylib, CodegenOptions,
 };
 use roc_load::{Expectations, MonomorphizedModule};
 use roc_module::symbol::{Interns, ModuleId, Symbol};
@@ -732,7 +735,7 @@ pub fn expect_mono_module_to_dylib<'a>(
     let (module_pass, _function_pass) =
         roc_gen_llvm::llvm::build::construct_optimization_passes(module, opt_level);
 
-    let (dibuilder, compile_unit) = roc_gen_llvm::llvm::build::Env::new_debug_info(module);
+    let (dibuilder, compile_unit) = roc_gen_llvm::llvm::build::Env::new_debug_info(module, &target);
 
     // Compile and add all the Procs before adding main
     let env = roc_gen_llvm::llvm::build::Env {
@@ -745,8 +748,14 @@ pub fn expect_mono_module_to_dylib<'a>(
         module,
         target_info,
         mode,
+        options: CodegenOptions::new(opt_level, target.clone()),
+        memory_model: &RocRuntimeMemoryModel,
         // important! we don't want any procedures to get the C calling convention
         exposed_to_host: MutSet::default(),
+        host_exposed: Default::default(),
+        wrappers_in_progress: Default::default(),
+        fn_origins: Default::default(),
+        sources: Default::default(),
     };
 
     // Add roc_alloc, roc_realloc, and roc_dealloc, since the repl has no
@@ -762,7 +771,6 @@ pub fn expect_mono_module_to_dylib<'a>(
     let expect_names = roc_gen_llvm::llvm::build::build_procedures_expose_expects(
         &env,
         &mut layout_interner,
-        opt_level,
         expect_symbols.into_bump_slice(),
         procedures,
     );